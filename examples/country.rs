@@ -0,0 +1,70 @@
+//! Lists the lines and points of a country.
+//!
+//! Usage: `cargo run --example country -- <data-path> <country-code>
+//! [<languages>]`
+//!
+//! The languages are a comma-separated list of language tags in order of
+//! preference used for the names.
+
+use std::{env, process};
+use std::str::FromStr;
+use raildata::api::Dataset;
+use raildata::types::{CountryCode, LanguagePreference};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let (path, country) = match (args.next(), args.next()) {
+        (Some(path), Some(country)) => (path, country),
+        _ => {
+            eprintln!(
+                "Usage: country <data-path> <country-code> [<languages>]"
+            );
+            process::exit(1);
+        }
+    };
+    let country = match CountryCode::from_str(&country) {
+        Ok(country) => country,
+        Err(_) => {
+            eprintln!("Invalid country code '{}'.", country);
+            process::exit(1);
+        }
+    };
+    let dataset = match Dataset::open(path) {
+        Ok(dataset) => dataset,
+        Err(mut err) => {
+            err.sort();
+            eprintln!("{} errors.", err.len());
+            for item in err.iter() {
+                eprintln!("{}", item)
+            }
+            process::exit(1);
+        }
+    };
+    let preference = match args.next() {
+        Some(languages) => match LanguagePreference::from_str(&languages) {
+            Ok(preference) => preference,
+            Err(err) => {
+                eprintln!("Invalid languages: {}", err);
+                process::exit(1);
+            }
+        },
+        None => LanguagePreference::default(),
+    };
+
+    println!("Lines:");
+    for line in dataset.lines_in(country) {
+        println!(
+            "  {}  {}",
+            line.data().code().as_str(),
+            line.data().preferred_name(&preference)
+        );
+    }
+    println!("Points:");
+    for point in dataset.points_in(country) {
+        println!(
+            "  {}  {}",
+            point.key(),
+            point.data().preferred_name(&preference)
+        );
+    }
+}
@@ -0,0 +1,42 @@
+//! Feeds generated documents through the loader looking for problems.
+//!
+//! Usage: `cargo run --example fuzz_yaml -- [<iterations> [<seed>]]`
+//!
+//! Stops at the first document that makes the loader panic or fail without
+//! reporting a problem and prints that document.
+
+use std::{env, panic, process};
+use raildata::testdata::{check_yaml, YamlFuzzer};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let iterations = parse_arg(args.next(), 10_000);
+    let seed = parse_arg(args.next(), 0);
+
+    let mut fuzzer = YamlFuzzer::new(seed);
+    for iteration in 0..iterations {
+        let document = fuzzer.document();
+        if panic::catch_unwind(|| check_yaml(&document)).is_err() {
+            eprintln!(
+                "Iteration {} with seed {} failed for document:",
+                iteration, seed
+            );
+            eprintln!("{}", document);
+            process::exit(1);
+        }
+    }
+    println!("{} documents checked.", iterations);
+}
+
+fn parse_arg(arg: Option<String>, default: u64) -> u64 {
+    match arg {
+        Some(arg) => match arg.parse() {
+            Ok(value) => value,
+            Err(_) => {
+                eprintln!("Usage: fuzz_yaml [<iterations> [<seed>]]");
+                process::exit(1);
+            }
+        },
+        None => default,
+    }
+}
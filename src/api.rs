@@ -0,0 +1,354 @@
+//! A high-level interface to the data.
+//!
+//! The [`Dataset`] bundles everything needed to work with a data tree: it
+//! loads and checks the data, generates cross references and metadata, and
+//! builds the catalogue. Documents are handed out as typed documents giving
+//! access to their data, cross references, and metadata, so tools embedding
+//! the data don’t need to deal with the stores and links at all.
+//!
+//! ```no_run
+//! use std::str::FromStr;
+//! use raildata::api::Dataset;
+//! use raildata::types::{CountryCode, LanguagePreference};
+//!
+//! let Ok(dataset) = Dataset::open("data") else {
+//!     panic!("data has errors")
+//! };
+//! let preference = LanguagePreference::default();
+//! for line in dataset.lines_in(CountryCode::from_str("DE").unwrap()) {
+//!     println!(
+//!         "{}: {}", line.data().code().as_str(),
+//!         line.data().preferred_name(&preference)
+//!     );
+//! }
+//! ```
+//!
+//! The stores and the catalogue are still available via
+//! [`Dataset::store`] and [`Dataset::catalogue`] for anything not covered
+//! here.
+
+use std::path::Path;
+use std::str::FromStr;
+use crate::catalogue::{
+    Catalogue, CountrySummary, DayEvent, Operation, SearchMatch,
+};
+use crate::document::{
+    custom, entity, line, path, point, service, source, structure,
+};
+use crate::document::combined::Document;
+use crate::document::line::GaugeClass;
+use crate::document::custom::CustomRegistry;
+use crate::load::{load_tree_custom, load_tree_observed};
+use crate::load::observer::{observe, LoadObserver};
+use crate::load::report::{Report, Stage};
+use crate::query::Query;
+use crate::store::{DataStore, FullStore};
+use crate::types::{CountryCode, Date};
+
+
+//------------ Dataset -------------------------------------------------------
+
+/// A completely loaded data tree.
+pub struct Dataset {
+    store: FullStore,
+    catalogue: Catalogue,
+}
+
+impl Dataset {
+    /// Loads the data tree at `path`.
+    ///
+    /// Returns all problems found if the data can’t be loaded. Warnings
+    /// count as problems, too, except for deprecated values.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Report> {
+        Self::open_observed(path, &())
+    }
+
+    /// Loads the data tree at `path` informing `observer` of the progress.
+    pub fn open_observed(
+        path: impl AsRef<Path>, observer: &dyn LoadObserver
+    ) -> Result<Self, Report> {
+        Self::from_store_observed(
+            load_tree_observed(path.as_ref(), observer)?, observer
+        )
+    }
+
+    /// Loads the data tree at `path` accepting the given custom types.
+    pub fn open_custom(
+        path: impl AsRef<Path>, custom_types: CustomRegistry
+    ) -> Result<Self, Report> {
+        Self::from_store(load_tree_custom(path.as_ref(), custom_types, &())?)
+    }
+
+    /// Creates a dataset from an already loaded store.
+    pub fn from_store(store: DataStore) -> Result<Self, Report> {
+        Self::from_store_observed(store, &())
+    }
+
+    /// Creates a dataset from a loaded store informing `observer`.
+    pub fn from_store_observed(
+        store: DataStore, observer: &dyn LoadObserver
+    ) -> Result<Self, Report> {
+        let store = store.into_full_store_observed(observer)?;
+        let catalogue = observe(observer, Stage::Catalogue, || {
+            Catalogue::generate(&store)
+        })?;
+        Ok(Dataset { store, catalogue })
+    }
+
+    /// Returns the underlying store.
+    pub fn store(&self) -> &FullStore {
+        &self.store
+    }
+
+    /// Returns the catalogue of the data.
+    pub fn catalogue(&self) -> &Catalogue {
+        &self.catalogue
+    }
+
+    /// Returns the number of documents.
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.store.len() == 0
+    }
+
+    /// Returns the document with the given key.
+    pub fn get(&self, key: &str) -> Option<Document<'_>> {
+        self.store.get(key).map(|link| link.document(&self.store))
+    }
+
+    /// Returns an iterator over all documents ordered by key.
+    pub fn documents(&self) -> impl Iterator<Item = Document<'_>> + '_ {
+        self.store.iter_from("").map(|link| link.document(&self.store))
+    }
+}
+
+/// # Typed Access
+///
+impl Dataset {
+    pub fn line(&self, key: &str) -> Option<line::Document<'_>> {
+        self.get(key)?.try_as_line()
+    }
+
+    pub fn entity(&self, key: &str) -> Option<entity::Document<'_>> {
+        self.get(key)?.try_as_entity()
+    }
+
+    pub fn path(&self, key: &str) -> Option<path::Document<'_>> {
+        self.get(key)?.try_as_path()
+    }
+
+    pub fn point(&self, key: &str) -> Option<point::Document<'_>> {
+        self.get(key)?.try_as_point()
+    }
+
+    pub fn service(&self, key: &str) -> Option<service::Document<'_>> {
+        self.get(key)?.try_as_service()
+    }
+
+    pub fn source(&self, key: &str) -> Option<source::Document<'_>> {
+        self.get(key)?.try_as_source()
+    }
+
+    pub fn structure(&self, key: &str) -> Option<structure::Document<'_>> {
+        self.get(key)?.try_as_structure()
+    }
+
+    pub fn custom(&self, key: &str) -> Option<custom::Document<'_>> {
+        self.get(key)?.try_as_custom()
+    }
+
+    /// Returns an iterator over all lines ordered by key.
+    pub fn lines(&self) -> impl Iterator<Item = line::Document<'_>> + '_ {
+        self.documents().filter_map(Document::try_as_line)
+    }
+
+    /// Returns an iterator over all entities ordered by key.
+    pub fn entities(
+        &self
+    ) -> impl Iterator<Item = entity::Document<'_>> + '_ {
+        self.documents().filter_map(Document::try_as_entity)
+    }
+
+    /// Returns an iterator over all paths ordered by key.
+    pub fn paths(&self) -> impl Iterator<Item = path::Document<'_>> + '_ {
+        self.documents().filter_map(Document::try_as_path)
+    }
+
+    /// Returns an iterator over all points ordered by key.
+    pub fn points(&self) -> impl Iterator<Item = point::Document<'_>> + '_ {
+        self.documents().filter_map(Document::try_as_point)
+    }
+
+    /// Returns an iterator over all services ordered by key.
+    pub fn services(
+        &self
+    ) -> impl Iterator<Item = service::Document<'_>> + '_ {
+        self.documents().filter_map(Document::try_as_service)
+    }
+
+    /// Returns an iterator over all sources ordered by key.
+    pub fn sources(
+        &self
+    ) -> impl Iterator<Item = source::Document<'_>> + '_ {
+        self.documents().filter_map(Document::try_as_source)
+    }
+
+    /// Returns an iterator over all structures ordered by key.
+    pub fn structures(
+        &self
+    ) -> impl Iterator<Item = structure::Document<'_>> + '_ {
+        self.documents().filter_map(Document::try_as_structure)
+    }
+
+    /// Returns an iterator over all custom documents of the given kind.
+    pub fn custom_documents<'s>(
+        &'s self, kind: &'s str
+    ) -> impl Iterator<Item = custom::Document<'s>> + 's {
+        self.documents().filter_map(Document::try_as_custom).filter(
+            move |document| document.data().kind.as_str() == kind
+        )
+    }
+
+    /// Returns an iterator over the lines of a country.
+    pub fn lines_in(
+        &self, country: CountryCode
+    ) -> impl Iterator<Item = line::Document<'_>> + '_ {
+        self.lines().filter(move |line| {
+            line.data().country() == Some(country)
+        })
+    }
+
+    /// Returns an iterator over the points of a country.
+    ///
+    /// The country of a point is taken from its key.
+    pub fn points_in(
+        &self, country: CountryCode
+    ) -> impl Iterator<Item = point::Document<'_>> + '_ {
+        self.points().filter(move |point| {
+            point.key().country().and_then(|code| {
+                CountryCode::from_str(code).ok()
+            }) == Some(country)
+        })
+    }
+}
+
+/// # Queries
+///
+impl Dataset {
+    /// Searches for documents by name.
+    ///
+    /// See [`Catalogue::search`] for the meaning of the arguments.
+    pub fn search(
+        &self, text: &str, prefix: bool, distance: usize
+    ) -> Vec<SearchMatch<'_>> {
+        self.catalogue.search(text, prefix, distance, &self.store)
+    }
+
+    /// Returns the documents matching a query ordered by key.
+    ///
+    /// See the [`query`][crate::query] module for the syntax of queries.
+    pub fn query(&self, query: &Query) -> Vec<Document<'_>> {
+        query.run(&self.store).into_iter().map(|link| {
+            link.document(&self.store)
+        }).collect()
+    }
+
+    /// Returns the events that happened on a calendar day in any year.
+    pub fn events_on(&self, month: u8, day: u8) -> &[DayEvent] {
+        self.catalogue.events_on(month, day)
+    }
+
+    /// Returns the points with the given UIC station code.
+    pub fn points_by_uic(
+        &self, code: &str
+    ) -> impl Iterator<Item = point::Document<'_>> + '_ {
+        self.catalogue.points_by_uic(code).iter().map(|link| {
+            link.document(&self.store)
+        })
+    }
+
+    /// Returns the points that have or had the given code.
+    pub fn points_by_code(
+        &self, code_type: point::CodeType, code: &str
+    ) -> impl Iterator<Item = point::Document<'_>> + '_ {
+        self.catalogue.points_by_code(code_type, code).iter().map(|link| {
+            link.document(&self.store)
+        })
+    }
+
+    /// Returns the lines an entity may have operated or owned at `date`.
+    ///
+    /// Returns `None` if there is no entity with the given key.
+    pub fn operations_at<'a>(
+        &'a self, entity: &str, date: &'a Date
+    ) -> Option<impl Iterator<Item = &'a Operation> + 'a> {
+        let entity = self.entity(entity)?.data().link();
+        Some(self.catalogue.operations_at(entity, date))
+    }
+
+    /// Returns the lines with a concession that may expire in a range.
+    ///
+    /// Both `start` and `end` are included in the range.
+    pub fn lines_with_concession_expiring<'a>(
+        &'a self, start: &'a Date, end: &'a Date
+    ) -> impl Iterator<Item = line::Document<'a>> + 'a {
+        self.lines().filter(move |line| {
+            line.meta().concessions.iter().any(|concession| {
+                concession.may_expire_between(start, end)
+            })
+        })
+    }
+
+    /// Returns the line with the given line code of a country.
+    ///
+    /// The code is looked up in the national line numbering system of the
+    /// country, such as `de.VzG` for Germany.
+    pub fn line_by_code(
+        &self, country: CountryCode, code: &str
+    ) -> Option<line::Document<'_>> {
+        self.catalogue.line_by_national_code(country, code).map(|link| {
+            link.document(&self.store)
+        })
+    }
+
+    /// Returns the line that had a line code of a country at `date`.
+    ///
+    /// Unlike [`line_by_code`][Self::line_by_code], this also finds lines
+    /// by codes they had in the past before being renumbered.
+    pub fn line_by_code_at(
+        &self, country: CountryCode, code: &str, date: &Date
+    ) -> Option<line::Document<'_>> {
+        self.catalogue.line_by_national_code_at(
+            country, code, date
+        ).map(|link| link.document(&self.store))
+    }
+
+    /// Returns the lines with a gauge of the given class.
+    pub fn lines_by_gauge(
+        &self, class: GaugeClass
+    ) -> impl Iterator<Item = line::Document<'_>> + '_ {
+        self.catalogue.lines_by_gauge(class).iter().map(|link| {
+            link.document(&self.store)
+        })
+    }
+
+    /// Returns the documents whose key starts with `prefix`.
+    pub fn keys_with_prefix(
+        &self, prefix: &str
+    ) -> impl Iterator<Item = Document<'_>> + '_ {
+        self.catalogue.keys_with_prefix(prefix, &self.store).into_iter().map(
+            |link| link.document(&self.store)
+        )
+    }
+
+    /// Returns the summary of a country.
+    pub fn country_summary(
+        &self, country: CountryCode
+    ) -> Option<&CountrySummary> {
+        self.catalogue.country_summary(country)
+    }
+}
+
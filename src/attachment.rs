@@ -0,0 +1,250 @@
+//! Local files attached to documents.
+//!
+//! Scans, photos, maps, and similar files can be kept next to the data in
+//! the `attachments` directory of the data tree. Each document has its own
+//! sub-directory named after its key, so the files for `line.de.1` live in
+//! `attachments/line.de.1/`. All files in that directory are attached to
+//! the document.
+//!
+//! Documents can refer to individual attachments by name through their
+//! `attachments` attribute. [`Attachments::check`] makes sure that these
+//! files actually exist.
+
+use std::{fs, io};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use derive_more::Display;
+use serde_json::{json, Value as JsonValue};
+use crate::load::report::{self, Origin, Report, Reporter, Stage};
+use crate::store::DataStore;
+use crate::types::{IntoMarked, Key, Location};
+
+
+//------------ Attachments ---------------------------------------------------
+
+/// The attachments of all documents.
+#[derive(Clone, Debug, Default)]
+pub struct Attachments {
+    /// The attachments directory.
+    base: PathBuf,
+
+    /// The attachments of each document ordered by name.
+    docs: BTreeMap<Key, Vec<Attachment>>,
+}
+
+impl Attachments {
+    /// Loads the attachments of the data tree at `path`.
+    ///
+    /// If the tree doesn’t have an attachments directory, there are no
+    /// attachments.
+    pub fn load(path: &Path) -> Result<Self, io::Error> {
+        let base = path.join("attachments");
+        let mut docs = BTreeMap::new();
+        let dir = match fs::read_dir(&base) {
+            Ok(dir) => dir,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(Attachments { base, docs })
+            }
+            Err(err) => return Err(err)
+        };
+        for entry in dir {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue
+            }
+            let key = match entry.file_name().into_string() {
+                Ok(key) => key,
+                Err(_) => continue,
+            };
+            let mut files = Vec::new();
+            for file in fs::read_dir(entry.path())? {
+                let file = file?;
+                let metadata = file.metadata()?;
+                if !metadata.is_file() {
+                    continue
+                }
+                let name = match file.file_name().into_string() {
+                    Ok(name) => name,
+                    Err(_) => continue,
+                };
+                // Skip hidden files such as editor backups.
+                if name.starts_with('.') {
+                    continue
+                }
+                files.push(Attachment {
+                    path: file.path(),
+                    size: metadata.len(),
+                    media_type: media_type(&name),
+                    name,
+                });
+            }
+            files.sort_by(|left, right| left.name.cmp(&right.name));
+            if let Ok(key) = Key::from_string(key) {
+                docs.insert(key, files);
+            }
+        }
+        Ok(Attachments { base, docs })
+    }
+
+    /// Returns the attachments directory.
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.docs.is_empty()
+    }
+
+    /// Returns the attachments of the document with the given key.
+    pub fn get(&self, key: &str) -> &[Attachment] {
+        self.docs.get(key).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Returns a single attachment of a document.
+    pub fn find(&self, key: &str, name: &str) -> Option<&Attachment> {
+        self.get(key).iter().find(|item| item.name == name)
+    }
+
+    /// Returns an iterator over the documents with attachments.
+    pub fn iter(&self) -> impl Iterator<Item = (&Key, &[Attachment])> + '_ {
+        self.docs.iter().map(|(key, files)| (key, files.as_slice()))
+    }
+
+    /// Returns the metadata of a document’s attachments as JSON.
+    pub fn to_json(&self, key: &str) -> JsonValue {
+        JsonValue::Array(
+            self.get(key).iter().map(|item| item.to_json(key)).collect()
+        )
+    }
+
+    /// Checks the attachments against the documents in the store.
+    ///
+    /// Every file a document refers to in its `attachments` attribute needs
+    /// to exist, which is reported as an error otherwise. Directories for
+    /// keys that don’t exist in the store are reported as warnings.
+    pub fn check(&self, store: &DataStore) -> Report {
+        let report = Reporter::new();
+        let mut stage = report.clone().stage(Stage::Crossref);
+        for data in store.iter() {
+            let common = data.common();
+            if common.attachments.is_empty() {
+                continue
+            }
+            let mut report = stage.clone().with_path(
+                common.origin.path().clone()
+            );
+            for name in common.attachments.iter() {
+                if self.find(common.key.as_str(), name.as_str()).is_none() {
+                    report.error(
+                        MissingAttachment {
+                            key: common.key.as_value().clone(),
+                            name: name.as_value().to_string(),
+                        }.marked(name.location())
+                    )
+                }
+            }
+        }
+        for key in self.docs.keys() {
+            if store.get(key).is_none() {
+                stage.warning_at(
+                    Origin::new(
+                        report::Path::new(self.base.join(key.as_str())),
+                        Location::NONE
+                    ),
+                    UnknownAttachmentKey(key.clone())
+                )
+            }
+        }
+        drop(stage);
+        report.unwrap()
+    }
+}
+
+
+//------------ Attachment ----------------------------------------------------
+
+/// A single file attached to a document.
+#[derive(Clone, Debug)]
+pub struct Attachment {
+    /// The file name.
+    name: String,
+
+    /// The full path of the file.
+    path: PathBuf,
+
+    /// The size of the file in bytes.
+    size: u64,
+
+    /// The media type derived from the file extension.
+    media_type: &'static str,
+}
+
+impl Attachment {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the path to read the file from.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn media_type(&self) -> &'static str {
+        self.media_type
+    }
+
+    /// Returns the metadata of the attachment as JSON.
+    ///
+    /// The `href` member contains the path under which the file is served,
+    /// relative to the root of the server.
+    pub fn to_json(&self, key: &str) -> JsonValue {
+        json!({
+            "name": self.name,
+            "size": self.size,
+            "media_type": self.media_type,
+            "href": format!("/attachment/{}/{}", key, self.name),
+        })
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the media type for a file name based on its extension.
+fn media_type(name: &str) -> &'static str {
+    let ext = match name.rsplit_once('.') {
+        Some((_, ext)) => ext.to_ascii_lowercase(),
+        None => return "application/octet-stream"
+    };
+    match ext.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "tif" | "tiff" => "image/tiff",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        "djvu" => "image/vnd.djvu",
+        "txt" => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+
+//============ Errors ========================================================
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="document '{}' has no attachment '{}'", key, name)]
+pub struct MissingAttachment {
+    key: Key,
+    name: String,
+}
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="attachments for unknown document '{}'", _0)]
+pub struct UnknownAttachmentKey(Key);
+
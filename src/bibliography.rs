@@ -0,0 +1,433 @@
+//! Export of sources into bibliography formats.
+//!
+//! A [`Bibliography`] collects source documents – either all of them or
+//! those regarding a certain document – and writes them as BibTeX or
+//! CSL-JSON for use with reference managers. The subtype of a source
+//! determines the entry type. Volume, number, and date are taken from the
+//! collection if the source itself doesn’t have them.
+
+use std::fmt;
+use serde_json::{json, Map, Value as JsonValue};
+use crate::document::{entity, source};
+use crate::document::common::Progress;
+use crate::document::source::Subtype;
+use crate::store::{DataStore, DocumentLink};
+use crate::types::{Date, LanguagePreference, List, Marked, Text};
+
+
+//------------ Format --------------------------------------------------------
+
+/// The output format of a bibliography.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Format {
+    /// BibTeX using the entry types of biblatex.
+    Bibtex,
+
+    /// CSL-JSON as used by Zotero, pandoc, and citeproc.
+    CslJson,
+}
+
+impl Format {
+    pub const ALL: &'static [Format] = &[Format::Bibtex, Format::CslJson];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Format::Bibtex => "bibtex",
+            Format::CslJson => "csl-json",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|item| item.as_str() == name)
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+
+//------------ Bibliography --------------------------------------------------
+
+/// A list of sources to export.
+#[derive(Clone, Debug)]
+pub struct Bibliography<'a> {
+    /// The store the sources live in.
+    store: &'a DataStore,
+
+    /// The sources ordered by key.
+    sources: Vec<&'a source::Data>,
+
+    /// The preferred languages for names of entities.
+    preference: &'a LanguagePreference,
+}
+
+impl<'a> Bibliography<'a> {
+    /// Creates a bibliography of all sources in the store.
+    pub fn all(
+        store: &'a DataStore, preference: &'a LanguagePreference
+    ) -> Self {
+        Self::filtered(store, preference, |_| true)
+    }
+
+    /// Creates a bibliography of all sources regarding a document.
+    pub fn regarding(
+        store: &'a DataStore,
+        link: DocumentLink,
+        preference: &'a LanguagePreference,
+    ) -> Self {
+        Self::filtered(store, preference, |source| {
+            source.regards.iter().any(|item| item.into_value() == link)
+        })
+    }
+
+    fn filtered(
+        store: &'a DataStore,
+        preference: &'a LanguagePreference,
+        op: impl Fn(&source::Data) -> bool,
+    ) -> Self {
+        let mut sources = store.iter().filter_map(|data| {
+            data.try_as_source()
+        }).filter(|source| op(source)).collect::<Vec<_>>();
+        sources.sort_by(|left, right| left.key().cmp(right.key()));
+        Bibliography { store, sources, preference }
+    }
+
+    /// Drops the sources with less than the given progress.
+    pub fn with_min_progress(mut self, min: Progress) -> Self {
+        self.sources.retain(|source| {
+            source.common.progress.into_value() >= min
+        });
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Returns the bibliography in the given format.
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Bibtex => self.to_bibtex(),
+            Format::CslJson => format!("{:#}", self.to_csl_json()),
+        }
+    }
+
+    /// Returns the bibliography as BibTeX.
+    pub fn to_bibtex(&self) -> String {
+        let mut res = String::new();
+        for source in &self.sources {
+            if !res.is_empty() {
+                res.push('\n');
+            }
+            self.write_bibtex(source, &mut res);
+        }
+        res
+    }
+
+    /// Returns the bibliography as a CSL-JSON array.
+    pub fn to_csl_json(&self) -> JsonValue {
+        JsonValue::Array(
+            self.sources.iter().map(|source| {
+                self.csl_item(source)
+            }).collect()
+        )
+    }
+}
+
+impl<'a> Bibliography<'a> {
+    fn write_bibtex(&self, source: &source::Data, target: &mut String) {
+        let subtype = source.subtype.into_value();
+        let mut fields = Vec::new();
+        let mut add = |name: &'static str, value: String| {
+            fields.push((name, value))
+        };
+        if !source.author.is_empty() {
+            add("author", self.bibtex_names(&source.author));
+        }
+        if !source.editor.is_empty() {
+            add("editor", self.bibtex_names(&source.editor));
+        }
+        if let Some(title) = source.title.as_ref() {
+            add("title", escape_bibtex(title));
+        }
+        if let Some(title) = source.short_title.as_ref() {
+            add("shorttitle", escape_bibtex(title));
+        }
+        if let Some(title) = self.container_title(source) {
+            let name = match subtype {
+                Subtype::Article => "journaltitle",
+                _ => "booktitle",
+            };
+            add(name, escape_bibtex(title));
+        }
+        if let Some(edition) = source.edition.as_ref() {
+            add("edition", escape_bibtex(edition));
+        }
+        if let Some(volume) = self.inherited(source, |data| {
+            data.volume.as_ref()
+        }) {
+            add("volume", escape_bibtex(volume));
+        }
+        if let Some(number) = self.inherited(source, |data| {
+            data.number.as_ref()
+        }) {
+            add("number", escape_bibtex(number));
+        }
+        if !source.organization.is_empty() {
+            add("organization", self.bibtex_names(&source.organization));
+        }
+        if let Some(publisher) = self.inherited_list(source, |data| {
+            &data.publisher
+        }) {
+            add("publisher", self.bibtex_names(publisher));
+        }
+        if let Some(date) = self.date(source) {
+            add("date", format_iso_date(&date));
+        }
+        if let Some(pages) = source.pages.as_ref() {
+            add(
+                "pages", escape_bibtex(&pages.as_str().replace('-', "--"))
+            );
+        }
+        if let Some(isbn) = source.isbn.as_ref() {
+            add("isbn", escape_bibtex(isbn.as_str()));
+        }
+        if let Some(url) = source.url.as_ref() {
+            add("url", url.to_string());
+        }
+
+        target.push('@');
+        target.push_str(bibtex_type(subtype));
+        target.push('{');
+        target.push_str(source.key().as_str());
+        target.push_str(",\n");
+        for (name, value) in fields {
+            target.push_str("  ");
+            target.push_str(name);
+            target.push_str(" = {");
+            target.push_str(&value);
+            target.push_str("},\n");
+        }
+        target.push_str("}\n");
+    }
+
+    fn csl_item(&self, source: &source::Data) -> JsonValue {
+        let mut res = Map::new();
+        res.insert("id".into(), source.key().as_str().into());
+        res.insert(
+            "type".into(), csl_type(source.subtype.into_value()).into()
+        );
+        if !source.author.is_empty() {
+            res.insert("author".into(), self.csl_names(&source.author));
+        }
+        if !source.editor.is_empty() {
+            res.insert("editor".into(), self.csl_names(&source.editor));
+        }
+        if let Some(title) = source.title.as_ref() {
+            res.insert("title".into(), title.as_str().into());
+        }
+        if let Some(title) = source.short_title.as_ref() {
+            res.insert("title-short".into(), title.as_str().into());
+        }
+        if let Some(title) = self.container_title(source) {
+            res.insert("container-title".into(), title.into());
+        }
+        if let Some(edition) = source.edition.as_ref() {
+            res.insert("edition".into(), edition.as_str().into());
+        }
+        if let Some(volume) = self.inherited(source, |data| {
+            data.volume.as_ref()
+        }) {
+            res.insert("volume".into(), volume.into());
+        }
+        if let Some(number) = self.inherited(source, |data| {
+            data.number.as_ref()
+        }) {
+            res.insert("issue".into(), number.into());
+        }
+        if let Some(publisher) = self.inherited_list(source, |data| {
+            &data.publisher
+        }) {
+            res.insert(
+                "publisher".into(),
+                self.join_names(publisher, "; ").into()
+            );
+        }
+        if let Some(date) = self.date(source) {
+            let mut parts = vec![JsonValue::from(date.year())];
+            if let Some(month) = date.month() {
+                parts.push(month.into());
+                if let Some(day) = date.day() {
+                    parts.push(day.into());
+                }
+            }
+            res.insert("issued".into(), json!({ "date-parts": [parts] }));
+        }
+        if let Some(pages) = source.pages.as_ref() {
+            res.insert("page".into(), pages.as_str().into());
+        }
+        if let Some(isbn) = source.isbn.as_ref() {
+            res.insert("ISBN".into(), isbn.as_str().into());
+        }
+        if let Some(url) = source.url.as_ref() {
+            res.insert("URL".into(), url.to_string().into());
+        }
+        JsonValue::Object(res)
+    }
+
+    /// Returns the title of the outermost collection of a source.
+    ///
+    /// For an article in an issue of a journal, this is the journal’s
+    /// title.
+    fn container_title<'s>(
+        &'s self, source: &'s source::Data
+    ) -> Option<&'s str> {
+        let mut res = None;
+        for data in self.collections(source) {
+            if let Some(title) = data.title.as_ref() {
+                res = Some(title.as_str());
+            }
+        }
+        res
+    }
+
+    /// Returns a value from the source or the nearest collection.
+    fn inherited<'s>(
+        &'s self,
+        source: &'s source::Data,
+        op: impl Fn(&'s source::Data) -> Option<&'s Marked<Text>>,
+    ) -> Option<&'s str> {
+        op(source).or_else(|| {
+            self.collections(source).find_map(&op)
+        }).map(|value| value.as_str())
+    }
+
+    /// Returns a non-empty list from the source or the nearest collection.
+    fn inherited_list<'s>(
+        &'s self,
+        source: &'s source::Data,
+        op: impl Fn(&'s source::Data) -> &'s List<Marked<entity::Link>>,
+    ) -> Option<&'s List<Marked<entity::Link>>> {
+        std::iter::once(source).chain(
+            self.collections(source)
+        ).map(op).find(|list| !list.is_empty())
+    }
+
+    /// Returns an iterator over the collections of a source.
+    ///
+    /// The iterator starts with the immediate collection. It stops after a
+    /// number of steps to protect against circular collections.
+    fn collections<'s>(
+        &'s self, source: &'s source::Data
+    ) -> impl Iterator<Item = &'s source::Data> + 's {
+        std::iter::successors(
+            source.collection.map(|link| link.data(self.store)),
+            |data| data.collection.map(|link| link.data(self.store))
+        ).take(MAX_DEPTH)
+    }
+
+    /// Returns the first date of a source or its collection.
+    fn date(&self, source: &source::Data) -> Option<Date> {
+        source.date(self.store)?.iter().next().map(Marked::into_value)
+    }
+
+    fn bibtex_names(&self, names: &List<Marked<entity::Link>>) -> String {
+        // Entities are institutions as often as they are persons. Bracing
+        // each name keeps BibTeX from splitting it into first and last.
+        names.iter().map(|link| {
+            format!("{{{}}}", escape_bibtex(self.name(link)))
+        }).collect::<Vec<_>>().join(" and ")
+    }
+
+    fn csl_names(&self, names: &List<Marked<entity::Link>>) -> JsonValue {
+        JsonValue::Array(
+            names.iter().map(|link| {
+                json!({ "literal": self.name(link) })
+            }).collect()
+        )
+    }
+
+    fn join_names(
+        &self, names: &List<Marked<entity::Link>>, sep: &str
+    ) -> String {
+        names.iter().map(|link| {
+            self.name(link)
+        }).collect::<Vec<_>>().join(sep)
+    }
+
+    fn name(&self, link: &Marked<entity::Link>) -> &'a str {
+        link.into_value().data(self.store).preferred_name(self.preference)
+    }
+}
+
+/// The maximum depth of collections followed for a source.
+const MAX_DEPTH: usize = 8;
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the biblatex entry type for a source subtype.
+fn bibtex_type(subtype: Subtype) -> &'static str {
+    match subtype {
+        Subtype::Article => "article",
+        Subtype::Book => "book",
+        Subtype::Inarticle => "incollection",
+        Subtype::Issue => "periodical",
+        Subtype::Journal => "periodical",
+        Subtype::Map => "misc",
+        Subtype::Online => "online",
+        Subtype::Series => "mvbook",
+        Subtype::Volume => "book",
+        Subtype::Misc => "misc",
+    }
+}
+
+/// Returns the CSL item type for a source subtype.
+fn csl_type(subtype: Subtype) -> &'static str {
+    match subtype {
+        Subtype::Article => "article-journal",
+        Subtype::Book => "book",
+        Subtype::Inarticle => "chapter",
+        Subtype::Issue => "periodical",
+        Subtype::Journal => "periodical",
+        Subtype::Map => "map",
+        Subtype::Online => "webpage",
+        Subtype::Series => "book",
+        Subtype::Volume => "book",
+        Subtype::Misc => "document",
+    }
+}
+
+/// Escapes the characters with special meaning in BibTeX.
+fn escape_bibtex(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '{' | '}' | '%' | '&' | '#' | '$' | '_' => {
+                res.push('\\');
+                res.push(ch);
+            }
+            _ => res.push(ch),
+        }
+    }
+    res
+}
+
+/// Formats a date as far as it is known in ISO 8601 form.
+fn format_iso_date(date: &Date) -> String {
+    match (date.month(), date.day()) {
+        (Some(month), Some(day)) => {
+            format!("{:04}-{:02}-{:02}", date.year(), month, day)
+        }
+        (Some(month), None) => format!("{:04}-{:02}", date.year(), month),
+        _ => format!("{:04}", date.year()),
+    }
+}
+
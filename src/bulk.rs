@@ -0,0 +1,285 @@
+//! Retrieving many documents at once.
+//!
+//! Clients that show many documents at the same time, such as a map
+//! resolving the links of everything currently visible, would otherwise have
+//! to fetch each document separately. A [`DocumentSet`] collects the
+//! requested documents so they can be returned in a single response. How
+//! much of each document is included is determined by the [`Detail`] level.
+
+use std::{fmt, io};
+use serde_json::{json, Map, Value as JsonValue};
+use yaml_rust::Yaml;
+use crate::store::{DataStore, DocumentLink, FullStore, ProgressView};
+use crate::store::write::document_to_yaml;
+use crate::types::LanguagePreference;
+
+
+//------------ DocumentSet ---------------------------------------------------
+
+/// A set of documents requested by their keys.
+#[derive(Clone, Debug)]
+pub struct DocumentSet {
+    /// The documents found in the order they were requested.
+    found: Vec<DocumentLink>,
+
+    /// The requested keys that don’t exist.
+    missing: Vec<String>,
+}
+
+impl DocumentSet {
+    /// Looks up the documents for the given keys.
+    ///
+    /// Keys requested more than once are only included once. Keys of
+    /// documents outside the view are treated as missing.
+    pub fn resolve<'a, 's>(
+        store: impl Into<ProgressView<'s>>,
+        keys: impl IntoIterator<Item = &'a str>
+    ) -> Self {
+        let store = store.into();
+        let mut found = Vec::new();
+        let mut missing = Vec::new();
+        for key in keys {
+            match store.get(key) {
+                Some(link) => {
+                    if !found.contains(&link) {
+                        found.push(link)
+                    }
+                }
+                None => {
+                    if !missing.iter().any(|item| item == key) {
+                        missing.push(key.into())
+                    }
+                }
+            }
+        }
+        DocumentSet { found, missing }
+    }
+
+    /// Returns a set of all documents of the store or view.
+    pub fn all<'a>(store: impl Into<ProgressView<'a>>) -> Self {
+        DocumentSet {
+            found: store.into().links().collect(), missing: Vec::new()
+        }
+    }
+
+    pub fn found(&self) -> &[DocumentLink] {
+        &self.found
+    }
+
+    pub fn missing(&self) -> &[String] {
+        &self.missing
+    }
+
+    /// Returns the documents as JSON.
+    ///
+    /// The result is an object with the documents in the order they were
+    /// requested under `documents` and the keys that weren’t found under
+    /// `missing`. The license and attribution of the data are given under
+    /// `attribution`.
+    pub fn to_json(
+        &self,
+        detail: Detail,
+        preference: &LanguagePreference,
+        store: &FullStore,
+    ) -> JsonValue {
+        let ds: &DataStore = store.as_ref();
+        json!({
+            "detail": detail.as_str(),
+            "documents": self.found.iter().map(|link| {
+                document_to_json(*link, detail, preference, store)
+            }).collect::<Vec<_>>(),
+            "missing": self.missing,
+            "attribution": ds.attribution_to_json(self.found.iter().copied()),
+        })
+    }
+
+    /// Writes the set as JSON.
+    ///
+    /// The output is the same as that of [`to_json`][Self::to_json], but
+    /// only one document at a time is converted, so memory use doesn’t
+    /// grow with the size of the set.
+    pub fn write_json(
+        &self,
+        detail: Detail,
+        preference: &LanguagePreference,
+        store: &FullStore,
+        target: &mut (impl io::Write + ?Sized),
+    ) -> Result<(), io::Error> {
+        let ds: &DataStore = store.as_ref();
+        // The keys are in the order serde_json uses for `to_json`.
+        target.write_all(b"{\"attribution\":")?;
+        serde_json::to_writer(
+            &mut *target,
+            &ds.attribution_to_json(self.found.iter().copied())
+        )?;
+        write!(target, ",\"detail\":\"{}\",\"documents\":[", detail)?;
+        for (index, link) in self.found.iter().enumerate() {
+            if index > 0 {
+                target.write_all(b",")?;
+            }
+            serde_json::to_writer(
+                &mut *target,
+                &document_to_json(*link, detail, preference, store)
+            )?;
+        }
+        target.write_all(b"],\"missing\":")?;
+        serde_json::to_writer(&mut *target, &self.missing)?;
+        target.write_all(b"}")
+    }
+}
+
+
+//------------ Detail --------------------------------------------------------
+
+/// How much of each document to include.
+///
+/// Each level includes everything of the levels before it. List views
+/// should use [`Summary`][Self::Summary] or [`Standard`][Self::Standard]
+/// to keep responses small.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Detail {
+    /// Only the key, type, and name of the document.
+    Summary,
+
+    /// Also the progress and the current state of the document.
+    Standard,
+
+    /// Also the document’s data.
+    Data,
+
+    /// Also the cross references.
+    Full,
+}
+
+impl Detail {
+    pub const ALL: &'static [Detail] = &[
+        Detail::Summary, Detail::Standard, Detail::Data, Detail::Full,
+    ];
+
+    /// Returns the detail level with the given name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|item| item.as_str() == name)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Detail::Summary => "summary",
+            Detail::Standard => "standard",
+            Detail::Data => "data",
+            Detail::Full => "full",
+        }
+    }
+}
+
+impl fmt::Display for Detail {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns a single document as JSON.
+///
+/// Path documents can’t be expressed in YAML and thus have no data.
+fn document_to_json(
+    link: DocumentLink,
+    detail: Detail,
+    preference: &LanguagePreference,
+    store: &FullStore,
+) -> JsonValue {
+    let ds: &DataStore = store.as_ref();
+    let data = link.data(store);
+    let mut res = Map::new();
+    res.insert("key".into(), data.key().as_str().into());
+    res.insert("type".into(), data.doctype().as_str().into());
+    res.insert("name".into(), data.preferred_name(preference).into());
+    if detail >= Detail::Standard {
+        res.insert(
+            "progress".into(),
+            data.common().progress.into_value().as_str().into()
+        );
+        res.insert("current".into(), link.meta(store).to_json());
+    }
+    if detail >= Detail::Data {
+        res.insert(
+            "data".into(),
+            document_to_yaml(data, ds).map(|yaml| {
+                yaml_to_json(&yaml)
+            }).unwrap_or(JsonValue::Null)
+        );
+    }
+    if detail >= Detail::Full {
+        res.insert("xrefs".into(), link.xrefs(store).to_json(ds));
+    }
+    JsonValue::Object(res)
+}
+
+/// Converts a YAML value into JSON.
+pub(crate) fn yaml_to_json(yaml: &Yaml) -> JsonValue {
+    match *yaml {
+        Yaml::Real(ref value) => {
+            value.parse::<f64>().map(JsonValue::from).unwrap_or_else(|_| {
+                JsonValue::from(value.as_str())
+            })
+        }
+        Yaml::Integer(value) => value.into(),
+        Yaml::String(ref value) => value.as_str().into(),
+        Yaml::Boolean(value) => value.into(),
+        Yaml::Array(ref value) => value.iter().map(yaml_to_json).collect(),
+        Yaml::Hash(ref value) => {
+            JsonValue::Object(value.iter().map(|(key, value)| {
+                let key = match *key {
+                    Yaml::String(ref key) => key.clone(),
+                    Yaml::Integer(key) => key.to_string(),
+                    Yaml::Real(ref key) => key.clone(),
+                    Yaml::Boolean(key) => key.to_string(),
+                    _ => String::new(),
+                };
+                (key, yaml_to_json(value))
+            }).collect())
+        }
+        _ => JsonValue::Null,
+    }
+}
+
+
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use serde_json::Value as JsonValue;
+    use crate::testdata::Fixture;
+    use crate::types::LanguagePreference;
+    use super::{Detail, DocumentSet};
+
+    /// Checks that writing a set gives the same JSON as converting it.
+    #[test]
+    fn write_json_matches_to_json() {
+        let store = Fixture::new()
+            .yaml("key: point.de.aa\ntype: point\nevents:\n- name: Aa\n")
+            .yaml("key: point.de.bb\ntype: point\nevents:\n- name: Bb\n")
+            .yaml(
+                "key: line.de.1000\ntype: line\n\
+                 points: [point.de.aa, point.de.bb]\n"
+            )
+            .into_full_store().unwrap_or_else(|_| {
+                panic!("fixture failed to load")
+            });
+        let preference = LanguagePreference::default();
+        let set = DocumentSet::resolve(
+            &store, ["line.de.1000", "point.de.aa", "point.de.zz"]
+        );
+        for detail in [Detail::Summary, Detail::Full] {
+            let mut written = Vec::new();
+            set.write_json(detail, &preference, &store, &mut written)
+                .unwrap();
+            assert_eq!(
+                serde_json::from_slice::<JsonValue>(&written).unwrap(),
+                set.to_json(detail, &preference, &store)
+            );
+        }
+    }
+}
@@ -1,11 +1,27 @@
 
-use std::collections::HashMap;
+use std::{cmp, fmt};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::hash_map::Entry;
+use std::str::FromStr;
+use derive_more::Display;
 use radix_trie::{Trie, TrieCommon};
+use rayon::prelude::*;
+use serde_json::{json, Value as JsonValue};
 use unicode_normalization::UnicodeNormalization;
-use crate::document::{entity, line};
-use crate::load::report::{Report, Reporter, Stage};
-use crate::store::{DocumentLink, FullStore};
-use crate::types::{CountryCode, List};
+use unicode_normalization::char::is_combining_mark;
+use crate::citation::Provenance;
+use crate::document::{entity, line, point, source, Data};
+use crate::document::common::DocumentType;
+use crate::load::report::{
+    Failed, PathReporter, Report, Reporter, Stage, StageReporter,
+};
+use crate::stats::CountryStatistics;
+use crate::store::{DataStore, DocumentLink, FullStore};
+use crate::types::{
+    CountryCode, Date, EventDate, IntoMarked, Key, LanguagePreference, List,
+    Location, OperatingPeriods, Text, Transliteration,
+};
+use crate::types::date::Precision;
 
 
 //------------ CatalogueBuilder ----------------------------------------------
@@ -24,14 +40,350 @@ impl CatalogueBuilder {
         self.0.countries.insert(country, link);
     }
 
-    pub fn insert_name(&mut self, name: String, link: DocumentLink) {
-        let term = Catalogue::normalize_name(&name);
+    pub fn insert_name(&mut self, name: Text, link: DocumentLink) {
+        self.insert_dated_name(name, link, None)
+    }
+
+    /// Adds a name together with the years it was in use.
+    ///
+    /// Names written in a script with a [`Transliteration`] are also added
+    /// under their transliterated form so they can be found with Latin
+    /// queries.
+    pub fn insert_dated_name(
+        &mut self,
+        name: Text,
+        link: DocumentLink,
+        valid: Option<NameValidity>,
+    ) {
+        let term = Catalogue::normalize_name(&name, true);
+        let mut terms = vec![term];
+        for transliteration in Transliteration::ALL {
+            if let Some(latin) = transliteration.apply(&name) {
+                let latin = Catalogue::normalize_name(&latin, true);
+                if !terms.contains(&latin) {
+                    terms.push(latin)
+                }
+            }
+        }
+        for term in terms {
+            self.insert_term(term, (name.clone(), link, valid))
+        }
+    }
+
+    fn insert_term(
+        &mut self,
+        term: String,
+        entry: (Text, DocumentLink, Option<NameValidity>),
+    ) {
         if let Some(value) = self.0.names.get_mut(&term) {
-            value.push((name, link))
+            value.push(entry)
         }
         else {
-            self.0.names.insert(term, List::with_value((name, link)));
+            self.0.names.insert(term, List::with_value(entry));
+        }
+    }
+
+    /// Adds an event to the index of events by calendar day.
+    ///
+    /// Only the first date of the event is used and only if it is known
+    /// to the exact day.
+    pub fn insert_event(
+        &mut self,
+        date: &EventDate,
+        link: DocumentLink,
+        index: usize,
+        kind: EventKind,
+    ) {
+        let date = match date.iter().next() {
+            Some(date) => date.into_value(),
+            None => return
+        };
+        if date.precision() != Precision::Exact || date.doubt() {
+            return
+        }
+        let (month, day) = match (date.month(), date.day()) {
+            (Some(month), Some(day)) => (month, day),
+            _ => return
+        };
+        self.0.days.entry((month, day)).or_default().push(DayEvent {
+            year: date.year(), link, index, kind
+        })
+    }
+
+    /// Adds a code a point had at some time.
+    pub fn insert_point_code(
+        &mut self, code_type: point::CodeType, code: String, link: point::Link
+    ) {
+        self.0.point_codes.entry(code_type).or_default().entry(
+            code
+        ).or_default().push(link)
+    }
+
+    /// Adds a code a point currently has.
+    ///
+    /// A code should only belong to a single point at a time. If it has
+    /// already been added for another point, reports a warning.
+    pub fn insert_current_point_code(
+        &mut self,
+        code_type: point::CodeType,
+        code: &str,
+        location: Location,
+        link: point::Link,
+        store: &FullStore,
+        report: &mut PathReporter,
+    ) {
+        let codes = self.0.current_point_codes.entry(code_type).or_default();
+        match codes.entry(code.into()) {
+            Entry::Occupied(entry) => {
+                if *entry.get() != link {
+                    report.warning(DuplicatePointCode {
+                        code_type,
+                        code: code.into(),
+                        point: entry.get().data(store).key().clone(),
+                    }.marked(location));
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(link);
+            }
+        }
+    }
+
+    /// Adds a source with its decade of publication and subtype.
+    pub fn insert_source(
+        &mut self,
+        link: source::Link,
+        decade: Option<i16>,
+        subtype: source::Subtype,
+    ) {
+        if let Some(decade) = decade {
+            self.0.source_decades.entry(decade).or_default().push(link)
+        }
+        self.0.source_subtypes.entry(subtype).or_default().push(link)
+    }
+
+    pub fn insert_gauge_class(
+        &mut self, class: line::GaugeClass, link: line::Link
+    ) {
+        self.0.gauges.entry(class).or_default().push(link)
+    }
+
+    /// Adds a national line code a line had at some time.
+    ///
+    /// A code can only belong to a single line at a time. If its periods
+    /// overlap with those of another line with the same code, reports an
+    /// error and fails.
+    pub fn insert_code_assignment(
+        &mut self,
+        assignment: line::CodeAssignment,
+        link: line::Link,
+        store: &FullStore,
+        report: &mut PathReporter,
+    ) -> Result<(), Failed> {
+        let lines = self.0.code_history.entry(
+            assignment.code_type
+        ).or_default().entry(
+            assignment.code.as_str().into()
+        ).or_default();
+        if let Some((other, _)) = lines.iter().find(|(other, periods)| {
+            *other != link && periods.may_overlap(&assignment.periods)
+        }) {
+            report.error(OverlappingLineCode {
+                code_type: assignment.code_type,
+                code: assignment.code.as_str().into(),
+                line: other.data(store).key().clone(),
+            }.unmarked());
+            return Err(Failed)
+        }
+        lines.push((link, assignment.periods));
+        Ok(())
+    }
+
+    /// Adds a line an entity operated or owned.
+    pub fn insert_operation(
+        &mut self, entity: entity::Link, operation: Operation
+    ) {
+        self.0.operations.entry(entity).or_default().push(operation)
+    }
+
+    /// Adds a national line code of a line.
+    ///
+    /// A code can only belong to a single line. If it has already been
+    /// added for another line, reports an error and fails.
+    pub fn insert_line_code(
+        &mut self,
+        code_type: line::CodeType,
+        code: &str,
+        location: Location,
+        link: line::Link,
+        store: &FullStore,
+        report: &mut PathReporter,
+    ) -> Result<(), Failed> {
+        let codes = self.0.line_codes.entry(code_type).or_default();
+        match codes.entry(code.into()) {
+            Entry::Occupied(entry) => {
+                if *entry.get() != link {
+                    report.error(DuplicateLineCode {
+                        code_type,
+                        code: code.into(),
+                        line: entry.get().data(store).key().clone(),
+                    }.marked(location));
+                    return Err(Failed)
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(link);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds everything collected by another builder.
+    ///
+    /// The other builder must have been filled from documents that come
+    /// after those of this builder, so that the order of lists is the same
+    /// as if all documents had been added to a single builder. National
+    /// line codes already present in this builder take precedence. If the
+    /// other builder has them for different lines, reports errors for those
+    /// lines and fails.
+    pub fn merge(
+        &mut self,
+        other: CatalogueBuilder,
+        store: &FullStore,
+        report: &StageReporter,
+    ) -> Result<(), Failed> {
+        let other = other.0;
+        let mut res = Ok(());
+
+        for (term, names) in other.names.iter() {
+            if let Some(value) = self.0.names.get_mut(term) {
+                value.extend_from_slice(names.as_slice())
+            }
+            else {
+                self.0.names.insert(term.clone(), names.clone());
+            }
+        }
+        self.0.countries.extend(other.countries);
+        self.0.lines.extend_from_slice(other.lines.as_slice());
+        for (class, lines) in other.gauges {
+            self.0.gauges.entry(class).or_default().extend(lines)
+        }
+        for (day, events) in other.days {
+            self.0.days.entry(day).or_default().extend(events)
+        }
+        for (decade, sources) in other.source_decades {
+            self.0.source_decades.entry(decade).or_default().extend(sources)
+        }
+        for (subtype, sources) in other.source_subtypes {
+            self.0.source_subtypes.entry(subtype).or_default().extend(
+                sources
+            )
+        }
+        for (code_type, codes) in other.point_codes {
+            let target = self.0.point_codes.entry(code_type).or_default();
+            for (code, points) in codes {
+                target.entry(code).or_default().extend(points)
+            }
+        }
+        for (code_type, codes) in other.current_point_codes {
+            let target = self.0.current_point_codes.entry(
+                code_type
+            ).or_default();
+            for (code, link) in codes {
+                match target.entry(code) {
+                    Entry::Occupied(entry) => {
+                        if *entry.get() != link {
+                            report.clone().warning_at(
+                                link.data(store).origin().clone(),
+                                DuplicatePointCode {
+                                    code_type,
+                                    code: entry.key().clone(),
+                                    point: entry.get().data(
+                                        store
+                                    ).key().clone(),
+                                }
+                            );
+                        }
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(link);
+                    }
+                }
+            }
+        }
+        for (entity, operations) in other.operations {
+            self.0.operations.entry(entity).or_default().extend(operations)
+        }
+
+        // Lines with a duplicate current code don’t get their code history
+        // added, same as in line::Data::catalogue.
+        let mut failed = HashSet::new();
+        for (code_type, codes) in other.line_codes {
+            let target = self.0.line_codes.entry(code_type).or_default();
+            for (code, link) in codes {
+                match target.entry(code) {
+                    Entry::Occupied(entry) => {
+                        if *entry.get() != link {
+                            let line = link.data(store);
+                            let location = line.current.codes().find(
+                                |(item_type, item, _)| {
+                                    *item_type == code_type
+                                        && *item == entry.key().as_str()
+                                }
+                            ).map(|(_, _, location)| location);
+                            let existing = entry.get().data(store);
+                            report.clone().error_at(
+                                line.origin().at(
+                                    location.unwrap_or_default()
+                                ),
+                                DuplicateLineCode {
+                                    code_type,
+                                    code: entry.key().clone(),
+                                    line: existing.key().clone(),
+                                }
+                            );
+                            failed.insert(link);
+                            res = Err(Failed)
+                        }
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(link);
+                    }
+                }
+            }
+        }
+
+        for (code_type, codes) in other.code_history {
+            let target = self.0.code_history.entry(code_type).or_default();
+            for (code, lines) in codes {
+                let history = target.entry(code.clone()).or_default();
+                for (link, periods) in lines {
+                    if failed.contains(&link) {
+                        continue
+                    }
+                    if let Some((existing, _)) = history.iter().find(
+                        |(existing, existing_periods)| {
+                            *existing != link
+                                && existing_periods.may_overlap(&periods)
+                        }
+                    ) {
+                        report.clone().error_at(
+                            link.data(store).origin().clone(),
+                            OverlappingLineCode {
+                                code_type,
+                                code: code.clone(),
+                                line: existing.data(store).key().clone(),
+                            }
+                        );
+                        res = Err(Failed);
+                        continue
+                    }
+                    history.push((link, periods));
+                }
+            }
         }
+
+        res
     }
 }
 
@@ -40,60 +392,1042 @@ impl CatalogueBuilder {
 
 #[derive(Clone, Debug, Default)]
 pub struct Catalogue {
-    names: Trie<String, List<(String, DocumentLink)>>,
+    /// The documents for each normalized name.
+    ///
+    /// Each entry has the name in its original spelling and, if known, the
+    /// years the name was in use.
+    names: Trie<String, List<(Text, DocumentLink, Option<NameValidity>)>>,
+
+    /// The normalized names for approximate search.
+    search_index: SearchIndex,
     pub countries: HashMap<CountryCode, entity::Link>,
     pub lines: List<line::Link>,
+
+    /// The lines with a current gauge of each class.
+    gauges: HashMap<line::GaugeClass, Vec<line::Link>>,
+
+    /// The events on each calendar day given as month and day.
+    days: HashMap<(u8, u8), Vec<DayEvent>>,
+
+    /// The sources published in each decade.
+    ///
+    /// The key is the first year of the decade. Sources without a date
+    /// aren’t included.
+    source_decades: BTreeMap<i16, Vec<source::Link>>,
+
+    /// The sources of each subtype.
+    source_subtypes: BTreeMap<source::Subtype, Vec<source::Link>>,
+
+    /// The summary of each country.
+    summaries: BTreeMap<CountryCode, CountrySummary>,
+
+    /// The points for each point code.
+    ///
+    /// This includes the codes points had in the past, so there can be
+    /// more than one point for a code if the point was split or merged
+    /// over time.
+    point_codes: HashMap<point::CodeType, HashMap<String, Vec<point::Link>>>,
+
+    /// The point currently having each point code.
+    current_point_codes: HashMap<
+        point::CodeType, HashMap<String, point::Link>
+    >,
+
+    /// The documents in each key namespace ordered by key.
+    namespaces: BTreeMap<String, Vec<DocumentLink>>,
+
+    /// The lines each entity operated or owned.
+    operations: HashMap<entity::Link, Vec<Operation>>,
+
+    /// The line for each current national line code.
+    line_codes: HashMap<line::CodeType, HashMap<String, line::Link>>,
+
+    /// The lines and the periods they had each national line code.
+    code_history: HashMap<
+        line::CodeType, HashMap<String, Vec<(line::Link, OperatingPeriods)>>
+    >,
 }
 
 impl Catalogue {
     pub fn generate(store: &FullStore) -> Result<Self, Report> {
-        let report = Reporter::new();
-        let mut ok = true;
-        let builder = {
-            let mut stage_report = report.clone().stage(Stage::Catalogue);
-            let mut builder = CatalogueBuilder::default();
-            for link in store.links() {
-                if link.data(store).catalogue(
-                    &mut builder, store, &mut stage_report
-                ).is_err() {
-                    ok = false;
-                }
-            }
-            builder
-        };
+        let (res, report, ok) = Self::generate_all(store);
         if ok {
-            let mut builder = builder.0;
-            builder.finalize(store);
-            Ok(builder)
+            Ok(res)
         }
         else {
-            Err(report.unwrap())
+            Err(report)
         }
     }
 
+    /// Generates the catalogue despite errors.
+    ///
+    /// Documents that fail are only partially included in the catalogue.
+    /// The returned report contains all problems encountered.
+    pub fn generate_forced(store: &FullStore) -> (Self, Report) {
+        let (res, report, _) = Self::generate_all(store);
+        (res, report)
+    }
+
+    /// Generates the catalogue and returns whether all documents succeeded.
+    fn generate_all(store: &FullStore) -> (Self, Report, bool) {
+        let report = Reporter::new();
+        let (builder, ok) = {
+            let stage_report = report.clone().stage(Stage::Catalogue);
+            let links: Vec<_> = store.links().collect();
+
+            // Each worker fills its own builder from a run of consecutive
+            // documents. Rayon reduces them in order, so lists end up in
+            // the order of the links.
+            links.into_par_iter().fold(
+                || (CatalogueBuilder::default(), true),
+                |(mut builder, ok), link| {
+                    let res = link.data(store).catalogue(
+                        &mut builder, store, &stage_report
+                    );
+                    (builder, ok && res.is_ok())
+                }
+            ).reduce(
+                || (CatalogueBuilder::default(), true),
+                |(mut left, left_ok), (right, right_ok)| {
+                    let res = left.merge(right, store, &stage_report);
+                    (left, left_ok && right_ok && res.is_ok())
+                }
+            )
+        };
+        let mut builder = builder.0;
+        builder.finalize(store);
+        (builder, report.unwrap(), ok)
+    }
+
     fn finalize(&mut self, store: &FullStore) {
         self.lines.sort_by(|left, right| {
             left.data(store).code().cmp(
                 &right.data(store).code()
             )
+        });
+        for lines in self.gauges.values_mut() {
+            lines.sort_by_key(|link| link.data(store).code())
+        }
+        for operations in self.operations.values_mut() {
+            operations.sort_by(|left, right| {
+                left.line.data(store).code().cmp(
+                    right.line.data(store).code()
+                ).then_with(|| left.role.cmp(&right.role))
+            })
+        }
+        for sources in self.source_decades.values_mut() {
+            Self::sort_sources(sources, store)
+        }
+        for sources in self.source_subtypes.values_mut() {
+            Self::sort_sources(sources, store)
+        }
+        self.search_index = SearchIndex::new(
+            self.names.iter().map(|(term, _)| term)
+        );
+        self.summaries = CountrySummary::generate(store);
+        for link in store.links() {
+            self.namespaces.entry(
+                link.data(store).key().namespace().into()
+            ).or_default().push(link)
+        }
+        for events in self.days.values_mut() {
+            events.sort_by(|left, right| {
+                left.year.cmp(&right.year).then_with(|| {
+                    left.link.data(store).key().cmp(
+                        right.link.data(store).key()
+                    )
+                })
+            })
+        }
+    }
+
+    /// Returns the points with the given UIC station code.
+    pub fn points_by_uic(&self, code: &str) -> &[point::Link] {
+        self.points_by_code(point::CodeType::Uic, code)
+    }
+
+    /// Returns the points that have or had the given code.
+    pub fn points_by_code(
+        &self, code_type: point::CodeType, code: &str
+    ) -> &[point::Link] {
+        self.point_codes.get(&code_type).and_then(|codes| {
+            codes.get(code)
+        }).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Returns the point that currently has the given code.
+    pub fn point_by_current_code(
+        &self, code_type: point::CodeType, code: &str
+    ) -> Option<point::Link> {
+        self.current_point_codes.get(&code_type)?.get(code).copied()
+    }
+
+    /// Returns the lines an entity operated or owned.
+    ///
+    /// The lines are ordered by their code.
+    pub fn operations(&self, entity: entity::Link) -> &[Operation] {
+        self.operations.get(&entity).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Returns the lines an entity may have operated or owned at `date`.
+    pub fn operations_at<'a>(
+        &'a self, entity: entity::Link, date: &'a Date
+    ) -> impl Iterator<Item = &'a Operation> + 'a {
+        self.operations(entity).iter().filter(move |operation| {
+            operation.periods.may_include(date)
+        })
+    }
+
+    /// Returns the lines an entity operated or owned as a JSON array.
+    ///
+    /// If `date` is given, only the lines at that date are included.
+    pub fn operations_to_json(
+        &self, entity: entity::Link, date: Option<&Date>, store: &FullStore
+    ) -> JsonValue {
+        self.operations(entity).iter().filter(|operation| {
+            date.map(|date| operation.periods.may_include(date))
+                .unwrap_or(true)
+        }).map(|operation| operation.to_json(store)).collect()
+    }
+
+    /// Returns the line with the given national line code.
+    pub fn line_by_code(
+        &self, code_type: line::CodeType, code: &str
+    ) -> Option<line::Link> {
+        self.line_codes.get(&code_type)?.get(code).copied()
+    }
+
+    /// Returns the line with the given line code of a country.
+    ///
+    /// The code is looked up in the national line numbering system of the
+    /// country. Returns `None` if the country doesn’t have one.
+    pub fn line_by_national_code(
+        &self, country: CountryCode, code: &str
+    ) -> Option<line::Link> {
+        self.line_by_code(line::CodeType::for_country(country)?, code)
+    }
+
+    /// Returns the line that may have had a national line code at `date`.
+    ///
+    /// If the date is too imprecise to decide between several lines, the
+    /// first one is returned.
+    pub fn line_by_code_at(
+        &self, code_type: line::CodeType, code: &str, date: &Date
+    ) -> Option<line::Link> {
+        self.code_history.get(&code_type)?.get(code)?.iter().find(
+            |(_, periods)| periods.may_include(date)
+        ).map(|(link, _)| *link)
+    }
+
+    /// Returns the line with a line code of a country at `date`.
+    ///
+    /// The code is looked up in the national line numbering system of the
+    /// country. Returns `None` if the country doesn’t have one.
+    pub fn line_by_national_code_at(
+        &self, country: CountryCode, code: &str, date: &Date
+    ) -> Option<line::Link> {
+        self.line_by_code_at(
+            line::CodeType::for_country(country)?, code, date
+        )
+    }
+
+    /// Returns an iterator over all key namespaces and their documents.
+    ///
+    /// See [`Key::namespace`][crate::types::Key::namespace] for what a
+    /// namespace is. Both the namespaces and their documents are ordered
+    /// by key.
+    pub fn namespaces(
+        &self
+    ) -> impl Iterator<Item = (&str, &[DocumentLink])> + '_ {
+        self.namespaces.iter().map(|(namespace, links)| {
+            (namespace.as_str(), links.as_slice())
+        })
+    }
+
+    /// Returns the documents in the given key namespace.
+    pub fn namespace(&self, namespace: &str) -> &[DocumentLink] {
+        self.namespaces.get(namespace).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Returns the documents whose key starts with `prefix`.
+    ///
+    /// The prefix doesn’t need to be a complete namespace. The documents
+    /// are ordered by key.
+    pub fn keys_with_prefix(
+        &self, prefix: &str, store: &FullStore
+    ) -> Vec<DocumentLink> {
+        let mut res: Vec<_> = self.namespaces.iter().filter(|(name, _)| {
+            name.starts_with(prefix) || prefix.starts_with(name.as_str())
+        }).flat_map(|(_, links)| links.iter().copied()).filter(|link| {
+            link.data(store).key().starts_with(prefix)
+        }).collect();
+        res.sort_by_key(|link| link.data(store).key());
+        res
+    }
+
+    /// Returns the summary for a country.
+    pub fn country_summary(
+        &self, country: CountryCode
+    ) -> Option<&CountrySummary> {
+        self.summaries.get(&country)
+    }
+
+    /// Returns an iterator over the summaries of all countries.
+    pub fn country_summaries(
+        &self
+    ) -> impl Iterator<Item = (CountryCode, &CountrySummary)> + '_ {
+        self.summaries.iter().map(|(code, summary)| (*code, summary))
+    }
+
+    /// Returns the events that happened on a calendar day in any year.
+    ///
+    /// The events are ordered by year.
+    pub fn events_on(&self, month: u8, day: u8) -> &[DayEvent] {
+        self.days.get(&(month, day)).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Returns the events on a calendar day as a JSON array.
+    pub fn events_on_to_json(
+        &self, month: u8, day: u8,
+        preference: &LanguagePreference, store: &FullStore
+    ) -> JsonValue {
+        self.events_on(month, day).iter().map(|event| {
+            event.to_json(preference, store)
+        }).collect()
+    }
+
+    /// Returns the decades with sources and their number of sources.
+    pub fn source_decades(
+        &self
+    ) -> impl Iterator<Item = (i16, usize)> + '_ {
+        self.source_decades.iter().map(|(decade, sources)| {
+            (*decade, sources.len())
+        })
+    }
+
+    /// Returns the subtypes with sources and their number of sources.
+    pub fn source_subtypes(
+        &self
+    ) -> impl Iterator<Item = (source::Subtype, usize)> + '_ {
+        self.source_subtypes.iter().map(|(subtype, sources)| {
+            (*subtype, sources.len())
+        })
+    }
+
+    /// Returns the sources of a decade and subtype.
+    ///
+    /// The decade is given by its first year. If either is `None`, it
+    /// doesn’t limit the sources. The sources are ordered by date.
+    pub fn sources(
+        &self,
+        decade: Option<i16>,
+        subtype: Option<source::Subtype>,
+        store: &FullStore,
+    ) -> Vec<source::Link> {
+        match (decade, subtype) {
+            (Some(decade), subtype) => {
+                self.source_decades.get(&decade).map(|sources| {
+                    sources.iter().copied().filter(|link| {
+                        subtype.map(|subtype| {
+                            link.data(store).subtype.into_value() == subtype
+                        }).unwrap_or(true)
+                    }).collect()
+                }).unwrap_or_default()
+            }
+            (None, Some(subtype)) => {
+                self.source_subtypes.get(&subtype).map(|sources| {
+                    sources.clone()
+                }).unwrap_or_default()
+            }
+            (None, None) => {
+                let mut res: Vec<_> = self.source_subtypes.values().flat_map(
+                    |sources| sources.iter().copied()
+                ).collect();
+                Self::sort_sources(&mut res, store);
+                res
+            }
+        }
+    }
+
+    /// Sorts sources by date and then key.
+    ///
+    /// Sources without a date come last.
+    fn sort_sources(sources: &mut [source::Link], store: &FullStore) {
+        sources.sort_by(|left, right| {
+            let left = left.data(store);
+            let right = right.data(store);
+            match (left.date(store), right.date(store)) {
+                (Some(left), Some(right)) => left.sort_cmp(right),
+                (Some(_), None) => cmp::Ordering::Less,
+                (None, Some(_)) => cmp::Ordering::Greater,
+                (None, None) => cmp::Ordering::Equal,
+            }.then_with(|| left.key().cmp(right.key()))
+        })
+    }
+
+    /// Returns the lines with a current gauge of the given class.
+    ///
+    /// Lines with sections of different gauges appear under each of their
+    /// classes. The lines are ordered by their code.
+    pub fn lines_by_gauge(&self, class: line::GaugeClass) -> &[line::Link] {
+        self.gauges.get(&class).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Returns the lines of a country with a current gauge of the class.
+    pub fn lines_by_gauge_in<'a>(
+        &'a self,
+        class: line::GaugeClass,
+        country: CountryCode,
+        store: &'a FullStore,
+    ) -> impl Iterator<Item = line::Link> + 'a {
+        self.lines_by_gauge(class).iter().copied().filter(move |link| {
+            link.data(store).country() == Some(country)
         })
     }
 
+    /// Returns all names starting with the given prefix.
+    ///
+    /// The search ignores case, diacritics, whitespace, and punctuation.
+    /// Common abbreviations such as “St.” for “Sankt” or “Saint” are
+    /// treated as the full word. If the prefix ends in such an
+    /// abbreviation, names starting with either form are returned. The
+    /// names are returned in their original spelling.
     pub fn search_name(
         &self, prefix: &str
     ) -> impl Iterator<Item = (&str, DocumentLink)> {
-        let prefix = Self::normalize_name(prefix);
+        let partial = Self::normalize_name(prefix, false);
+        let complete = Self::normalize_name(prefix, true);
+        let complete = (complete != partial).then_some(complete);
+        self.search_term(partial).chain(
+            complete.into_iter().flat_map(|term| self.search_term(term))
+        )
+    }
+
+    fn search_term(
+        &self, prefix: String
+    ) -> impl Iterator<Item = (&str, DocumentLink)> {
         self.names.get_raw_ancestor(&prefix).iter()
             .filter(move |(key, _)| key.starts_with(&prefix))
             .flat_map(|(_, value)| value)
-            .map(|(name, link)| (name.as_str(), *link))
+            .map(|(name, link, _)| (name.as_str(), *link))
+    }
+
+    /// The largest edit distance accepted by [`search`][Self::search].
+    pub const MAX_DISTANCE: usize = 3;
+
+    /// Searches for names and returns the matches ranked by quality.
+    ///
+    /// If `prefix` is `true`, names only need to start with the search
+    /// text, which is what an autocomplete needs. With a `distance` other
+    /// than zero, names are matched if they can be reached from the search
+    /// text by at most that many inserted, deleted, or substituted
+    /// characters. Distances above [`MAX_DISTANCE`][Self::MAX_DISTANCE]
+    /// are reduced to it. The search text is normalized the same way as
+    /// in [`search_name`][Self::search_name].
+    ///
+    /// Matches are ordered by their edit distance, then full matches come
+    /// before prefix matches, then by document type via
+    /// [`type_rank`][SearchMatch::type_rank], and finally by name.
+    pub fn search(
+        &self,
+        text: &str,
+        prefix: bool,
+        distance: usize,
+        store: &FullStore,
+    ) -> Vec<SearchMatch<'_>> {
+        let distance = cmp::min(distance, Self::MAX_DISTANCE);
+        let partial = Self::normalize_name(text, !prefix);
+        let complete = Self::normalize_name(text, true);
+        let queries = if partial == complete {
+            vec![partial]
+        }
+        else {
+            vec![partial, complete]
+        };
+
+        let mut found = HashMap::new();
+        for query in queries {
+            let query: Vec<char> = query.chars().collect();
+            self.search_index.find(&query, prefix, distance, |term, item| {
+                let best = found.entry(term).or_insert(item);
+                *best = cmp::min(*best, item);
+            });
+        }
+
+        let mut res = Vec::new();
+        for (term, (found, complete)) in found {
+            let names = match self.names.get(term) {
+                Some(names) => names,
+                None => continue,
+            };
+            for (name, link, valid) in names {
+                res.push(SearchMatch {
+                    name: name.as_str(),
+                    link: *link,
+                    doctype: link.data(store).doctype(),
+                    valid: *valid,
+                    distance: found,
+                    complete,
+                });
+            }
+        }
+        res.sort_by(|left, right| left.rank().cmp(&right.rank()));
+        res
+    }
+
+    /// Normalizes a name for use as a search term.
+    ///
+    /// The name is broken into words at whitespace and punctuation. Each
+    /// word is decomposed, stripped of diacritics, and case folded. Words
+    /// from the alias table are replaced with their canonical form. The
+    /// resulting words are then concatenated.
+    ///
+    /// If `complete` is `false`, the last word is considered incomplete
+    /// and not looked up in the alias table unless it is followed by
+    /// whitespace or punctuation.
+    pub(crate) fn normalize_name(name: &str, complete: bool) -> String {
+        let mut res = String::new();
+        let mut word = String::new();
+        for ch in name.nfkd() {
+            if ch.is_alphanumeric() {
+                fold_char(ch, &mut word);
+            }
+            else if !is_combining_mark(ch) && !word.is_empty() {
+                res.push_str(resolve_alias(&word));
+                word.clear();
+            }
+        }
+        if complete {
+            res.push_str(resolve_alias(&word));
+        }
+        else {
+            res.push_str(&word);
+        }
+        res
+    }
+}
+
+
+//------------ CountrySummary ------------------------------------------------
+
+/// Aggregate information about the documents of a country.
+///
+/// Documents are attributed to the country encoded in their key.
+#[derive(Clone, Debug, Default)]
+pub struct CountrySummary {
+    /// The statistics over the country’s documents.
+    pub statistics: CountryStatistics,
+
+    /// The regions of the country ordered by key.
+    pub regions: Vec<entity::Link>,
+
+    /// The organizations that aren’t a unit of another one ordered by key.
+    pub entities: Vec<entity::Link>,
+}
+
+impl CountrySummary {
+    /// Generates the summaries of all countries.
+    ///
+    /// Because the store iterates in key order, the regions and entities
+    /// end up ordered by key.
+    fn generate(store: &FullStore) -> BTreeMap<CountryCode, Self> {
+        let data_store: &DataStore = store.as_ref();
+        let mut res = BTreeMap::<_, Self>::new();
+        for data in data_store.iter() {
+            let country = match data.key().country().and_then(|code| {
+                CountryCode::from_str(code).ok()
+            }) {
+                Some(country) => country,
+                None => continue
+            };
+            let summary = res.entry(country).or_default();
+            summary.statistics.merge(
+                &CountryStatistics::from_document(data, data_store)
+            );
+            if let Data::Entity(ref entity) = *data {
+                match entity.subtype.into_value() {
+                    entity::Subtype::Region => {
+                        summary.regions.push(entity.link())
+                    }
+                    entity::Subtype::Company
+                        if entity.superior().is_empty() =>
+                    {
+                        summary.entities.push(entity.link())
+                    }
+                    _ => { }
+                }
+            }
+        }
+        res
+    }
+
+    pub fn to_json(
+        &self, country: CountryCode, catalogue: &Catalogue,
+        preference: &LanguagePreference, store: &FullStore
+    ) -> JsonValue {
+        let entities = |links: &[entity::Link]| {
+            links.iter().map(|link| {
+                let data = link.data(store);
+                json!({
+                    "key": data.key().as_str(),
+                    "name": data.preferred_name(preference),
+                })
+            }).collect::<Vec<_>>()
+        };
+        let stats = &self.statistics;
+        json!({
+            "country": country.as_str(),
+            "entity": catalogue.countries.get(&country).map(|link| {
+                link.data(store).key().as_str()
+            }),
+            "lines": stats.lines,
+            "points": stats.points,
+            "km": {
+                "total": stats.line_km,
+                "electrified": stats.electrified_km,
+                "estimated_electrified": stats.estimated.electrified_km,
+            },
+            "regions": entities(&self.regions),
+            "entities": entities(&self.entities),
+        })
+    }
+}
+
+
+//------------ DayEvent ------------------------------------------------------
+
+/// An event found by [`Catalogue::events_on`].
+#[derive(Clone, Copy, Debug)]
+pub struct DayEvent {
+    /// The year of the event.
+    pub year: i16,
+
+    /// The document the event belongs to.
+    pub link: DocumentLink,
+
+    /// The index of the event in the document’s event list.
+    pub index: usize,
+
+    /// What kind of event this is.
+    pub kind: EventKind,
+}
+
+impl DayEvent {
+    /// Returns the sources of each record of the event.
+    pub fn provenance<'s>(
+        &self, store: &'s FullStore
+    ) -> Vec<Provenance<'s>> {
+        let res = match self.link.data(store) {
+            Data::Entity(data) => {
+                data.events.as_slice().get(self.index).map(|event| {
+                    event.records.iter().map(|record| {
+                        record.provenance()
+                    }).collect()
+                })
+            }
+            Data::Line(data) => {
+                data.events.as_slice().get(self.index).map(|event| {
+                    event.records.iter().map(|record| {
+                        record.provenance()
+                    }).collect()
+                })
+            }
+            Data::Point(data) => {
+                data.events.as_slice().get(self.index).map(|event| {
+                    event.records.iter().map(|record| {
+                        record.provenance()
+                    }).collect()
+                })
+            }
+            Data::Service(data) => {
+                data.events.as_slice().get(self.index).map(|event| {
+                    event.records.iter().map(|record| {
+                        record.provenance()
+                    }).collect()
+                })
+            }
+            Data::Structure(data) => {
+                data.events.as_slice().get(self.index).map(|event| {
+                    event.records.iter().map(|record| {
+                        record.provenance()
+                    }).collect()
+                })
+            }
+            _ => None
+        };
+        res.unwrap_or_default()
+    }
+
+    pub fn to_json(
+        &self, preference: &LanguagePreference, store: &FullStore
+    ) -> JsonValue {
+        let data = self.link.data(store);
+        json!({
+            "key": data.key().as_str(),
+            "type": data.doctype().as_str(),
+            "year": self.year,
+            "event": self.index,
+            "kind": self.kind.as_str(),
+            "provenance": self.provenance(store).iter().map(|item| {
+                item.to_json(store, preference)
+            }).collect::<Vec<_>>(),
+        })
+    }
+}
+
+
+//------------ EventKind -----------------------------------------------------
+
+/// The kind of an event for the purpose of anniversaries.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum EventKind {
+    /// Something was opened or reopened.
+    Opening,
+
+    /// Something was suspended or closed.
+    Closure,
+
+    /// Anything else.
+    Other,
+}
+
+impl EventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::Opening => "opening",
+            EventKind::Closure => "closure",
+            EventKind::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
     }
+}
+
+
+//------------ Operation -----------------------------------------------------
+
+/// A line operated or owned by an entity.
+#[derive(Clone, Debug)]
+pub struct Operation {
+    /// The line.
+    pub line: line::Link,
 
-    fn normalize_name(name: &str) -> String {
-        name.nfd()
-            .filter(|ch| ch.is_alphanumeric())
-            .flat_map(|ch| ch.to_lowercase())
-            .collect()
+    /// Whether the entity operated or owned the line.
+    pub role: OperationRole,
+
+    /// The periods during which the entity had the role.
+    pub periods: OperatingPeriods,
+}
+
+impl Operation {
+    pub fn to_json(&self, store: &FullStore) -> JsonValue {
+        let data = self.line.data(store);
+        json!({
+            "key": data.key().as_str(),
+            "code": data.code().as_str(),
+            "role": self.role.as_str(),
+            "periods": self.periods.to_json(),
+        })
     }
 }
 
+
+//------------ OperationRole -------------------------------------------------
+
+/// The role of an entity for a line.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum OperationRole {
+    /// The entity operated the line.
+    Operator,
+
+    /// The entity owned the line.
+    Owner,
+}
+
+impl OperationRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OperationRole::Operator => "operator",
+            OperationRole::Owner => "owner",
+        }
+    }
+}
+
+impl fmt::Display for OperationRole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+
+//------------ SearchMatch ---------------------------------------------------
+
+/// A name found by [`Catalogue::search`].
+#[derive(Clone, Copy, Debug)]
+pub struct SearchMatch<'a> {
+    /// The name in its original spelling.
+    pub name: &'a str,
+
+    /// The document with the name.
+    pub link: DocumentLink,
+
+    /// The type of the document.
+    pub doctype: DocumentType,
+
+    /// The years the name was in use if they are known.
+    pub valid: Option<NameValidity>,
+
+    /// The number of edits necessary to match the search text.
+    pub distance: usize,
+
+    /// Whether the whole name rather than only a prefix was matched.
+    pub complete: bool,
+}
+
+impl<'a> SearchMatch<'a> {
+    /// Returns the rank of a document type in search results.
+    ///
+    /// Points and lines are what people look for most often, so they come
+    /// first.
+    pub fn type_rank(doctype: DocumentType) -> u8 {
+        match doctype {
+            DocumentType::Point => 0,
+            DocumentType::Line => 1,
+            DocumentType::Entity => 2,
+            DocumentType::Structure => 3,
+            DocumentType::Service => 4,
+            DocumentType::Source => 5,
+            DocumentType::Path => 6,
+            DocumentType::Custom => 7,
+        }
+    }
+
+    fn rank(&self) -> (usize, bool, u8, &'a str) {
+        (
+            self.distance, !self.complete, Self::type_rank(self.doctype),
+            self.name
+        )
+    }
+
+    pub fn to_json(&self, store: &FullStore) -> JsonValue {
+        json!({
+            "key": self.link.data(store).key().as_str(),
+            "type": self.doctype.as_str(),
+            "name": self.name,
+            "valid": self.valid.and_then(|valid| valid.annotation()),
+            "distance": self.distance,
+            "complete": self.complete,
+        })
+    }
+}
+
+
+//------------ SearchIndex ---------------------------------------------------
+
+/// The normalized names in lexicographic order for approximate search.
+///
+/// Walking the sorted terms visits the same states as walking a trie of
+/// them: consecutive terms share their common prefix, so the rows of the
+/// edit distance matrix computed for that prefix are kept. Once a prefix
+/// is too far away from the query, all terms starting with it are skipped
+/// with a binary search.
+#[derive(Clone, Debug, Default)]
+struct SearchIndex {
+    terms: Vec<String>,
+}
+
+impl SearchIndex {
+    fn new<'a>(terms: impl Iterator<Item = &'a String>) -> Self {
+        let mut terms: Vec<_> = terms.cloned().collect();
+        terms.sort_unstable();
+        SearchIndex { terms }
+    }
+
+    /// Calls `op` for all terms within `max` edits of `query`.
+    ///
+    /// If `prefix` is `true`, the query only needs to match the beginning
+    /// of the term. Besides the term, `op` receives the number of edits
+    /// and whether the complete term was matched.
+    fn find<'a>(
+        &'a self,
+        query: &[char],
+        prefix: bool,
+        max: usize,
+        mut op: impl FnMut(&'a String, (usize, bool)),
+    ) {
+        // The row of the matrix after each character of the current term
+        // and the smallest distance between the query and any prefix of
+        // the term up to that character.
+        let mut rows: Vec<Vec<usize>> = vec![(0..=query.len()).collect()];
+        let mut best = vec![query.len()];
+        let mut previous = "";
+        let mut index = 0;
+        while let Some(term) = self.terms.get(index) {
+            let shared = term.chars().zip(previous.chars()).take_while(
+                |(left, right)| left == right
+            ).count();
+            rows.truncate(shared + 1);
+            best.truncate(shared + 1);
+            previous = term;
+
+            let mut dead = None;
+            for (pos, ch) in term.char_indices().skip(shared) {
+                let row = &rows[rows.len() - 1];
+                let mut next = Vec::with_capacity(row.len());
+                next.push(row[0] + 1);
+                for (j, qch) in query.iter().enumerate() {
+                    let subst = row[j] + usize::from(*qch != ch);
+                    next.push(subst.min(row[j + 1] + 1).min(next[j] + 1));
+                }
+                let reachable = next.iter().any(|&dist| dist <= max);
+                best.push(cmp::min(best[best.len() - 1], next[query.len()]));
+                rows.push(next);
+                if !reachable {
+                    dead = Some(pos + ch.len_utf8());
+                    break
+                }
+            }
+            if let Some(end) = dead {
+                let dead = &term[..end];
+                index += self.terms[index..].partition_point(|term| {
+                    term.starts_with(dead)
+                });
+                continue
+            }
+            index += 1;
+
+            let complete = rows[rows.len() - 1][query.len()];
+            let nearest = best[best.len() - 1];
+            if complete <= max && (!prefix || complete == nearest) {
+                op(term, (complete, true))
+            }
+            else if prefix && nearest <= max {
+                op(term, (nearest, false))
+            }
+        }
+    }
+}
+
+
+//------------ NameValidity --------------------------------------------------
+
+/// The years during which a name was in use.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NameValidity {
+    /// The year the name was given if it is known.
+    pub start: Option<i16>,
+
+    /// The year the name was replaced or `None` if it still is in use.
+    pub end: Option<i16>,
+}
+
+impl NameValidity {
+    pub fn new(start: Option<i16>, end: Option<i16>) -> Self {
+        NameValidity { start, end }
+    }
+
+    /// Extends the validity to also cover `other`.
+    ///
+    /// An unknown start stays unknown and a name still in use stays in use.
+    pub fn merge(&mut self, other: NameValidity) {
+        self.start = self.start.zip(other.start).map(|(left, right)| {
+            left.min(right)
+        });
+        self.end = self.end.zip(other.end).map(|(left, right)| {
+            left.max(right)
+        });
+    }
+
+    /// Returns a short description such as “valid 1905–1938.”
+    ///
+    /// Returns `None` if neither year is known.
+    pub fn annotation(self) -> Option<String> {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => {
+                Some(format!("valid {}–{}", start, end))
+            }
+            (Some(start), None) => Some(format!("valid since {}", start)),
+            (None, Some(end)) => Some(format!("valid until {}", end)),
+            (None, None) => None,
+        }
+    }
+}
+
+
+//------------ Name Aliases --------------------------------------------------
+
+/// Groups of words that are treated as equal in names.
+///
+/// The first word of each group is the canonical form. All words need to
+/// be normalized already.
+const NAME_ALIASES: &[&[&str]] = &[
+    &["sankt", "st", "skt", "saint", "sint"],
+    &["sainte", "ste"],
+    &["bahnhof", "bf", "bhf"],
+    &["hauptbahnhof", "hbf"],
+    &["station", "stn"],
+    &["junction", "jn", "jct"],
+];
+
+/// Returns the canonical form of a normalized word.
+fn resolve_alias(word: &str) -> &str {
+    NAME_ALIASES.iter().find(|group| group.contains(&word)).map(|group| {
+        group[0]
+    }).unwrap_or(word)
+}
+
+/// Appends the case folded version of a character to `target`.
+///
+/// This applies full case folding for the characters that don’t have a
+/// lower case form of their own.
+fn fold_char(ch: char, target: &mut String) {
+    match ch {
+        'ß' | 'ẞ' => target.push_str("ss"),
+        'æ' | 'Æ' => target.push_str("ae"),
+        'œ' | 'Œ' => target.push_str("oe"),
+        'ø' | 'Ø' => target.push('o'),
+        'ł' | 'Ł' => target.push('l'),
+        'đ' | 'Đ' => target.push('d'),
+        'ı' => target.push('i'),
+        _ => target.extend(ch.to_lowercase()),
+    }
+}
+
+
+//============ Errors ========================================================
+
+#[derive(Clone, Debug, Display)]
+#[display(
+    fmt="{} line code '{}' already used by line '{}'", code_type, code, line
+)]
+pub struct DuplicateLineCode {
+    code_type: line::CodeType,
+    code: String,
+    line: Key,
+}
+
+#[derive(Clone, Debug, Display)]
+#[display(
+    fmt="{} code '{}' already used by point '{}'", code_type, code, point
+)]
+pub struct DuplicatePointCode {
+    code_type: point::CodeType,
+    code: String,
+    point: Key,
+}
+
+#[derive(Clone, Debug, Display)]
+#[display(
+    fmt="{} line code '{}' overlaps with its use by line '{}'",
+    code_type, code, line
+)]
+pub struct OverlappingLineCode {
+    code_type: line::CodeType,
+    code: String,
+    line: Key,
+}
+
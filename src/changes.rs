@@ -0,0 +1,180 @@
+//! Documents changed since a point in time.
+//!
+//! Mirrors of the data can use the [`ChangeFeed`] to only fetch the
+//! documents that changed since they last synchronized. The time of the
+//! last change of a document is the time of the last commit of its file if
+//! the data was loaded with git revisions and the modification time of the
+//! file otherwise.
+//!
+//! Because changes are tracked per file, all documents in a changed file
+//! are listed. Documents that were removed can’t be listed since they
+//! aren’t part of the store anymore.
+
+use std::fmt;
+use std::collections::BTreeMap;
+use std::time::UNIX_EPOCH;
+use serde_json::{json, Value as JsonValue};
+use crate::document::common::DocumentType;
+use crate::load::report::{Origin, Path};
+use crate::store::DataStore;
+use crate::types::Key;
+
+
+//------------ ChangeFeed ----------------------------------------------------
+
+/// The documents changed after a certain time.
+#[derive(Clone, Debug)]
+pub struct ChangeFeed {
+    /// The time since which changes are included.
+    since: i64,
+
+    /// The changed documents ordered by time and key.
+    changes: Vec<Change>,
+}
+
+impl ChangeFeed {
+    /// Collects all documents changed after `since`.
+    ///
+    /// The time is given in seconds since the Unix epoch.
+    pub fn since(store: &DataStore, since: i64) -> Self {
+        let mut mtimes = BTreeMap::new();
+        let mut changes = Vec::new();
+        for data in store.iter() {
+            let origin = data.origin();
+            let (timestamp, kind) = match origin.revision() {
+                Some(revision) => {
+                    let kind = if revision.created > since {
+                        ChangeKind::Added
+                    }
+                    else {
+                        ChangeKind::Modified
+                    };
+                    (revision.timestamp, kind)
+                }
+                None => {
+                    let mtime = *mtimes.entry(origin.path().clone())
+                        .or_insert_with(|| file_mtime(origin.path()));
+                    match mtime {
+                        Some(mtime) => (mtime, ChangeKind::Modified),
+                        None => continue,
+                    }
+                }
+            };
+            if timestamp <= since {
+                continue
+            }
+            changes.push(Change {
+                key: data.key().clone(),
+                doctype: data.doctype(),
+                kind,
+                timestamp,
+                origin: origin.clone(),
+            })
+        }
+        changes.sort_by(|left, right| {
+            left.timestamp.cmp(&right.timestamp).then_with(|| {
+                left.key.cmp(&right.key)
+            })
+        });
+        ChangeFeed { since, changes }
+    }
+
+    pub fn since_time(&self) -> i64 {
+        self.since
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Change> + '_ {
+        self.changes.iter()
+    }
+
+    /// Returns the time of the most recent change.
+    ///
+    /// A mirror should use this as the `since` time for its next request.
+    pub fn latest(&self) -> Option<i64> {
+        self.changes.last().map(|change| change.timestamp)
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "since": self.since,
+            "latest": self.latest(),
+            "changes": self.changes.iter().map(|change| {
+                change.to_json()
+            }).collect::<Vec<_>>(),
+        })
+    }
+}
+
+
+//------------ Change --------------------------------------------------------
+
+/// A single changed document.
+#[derive(Clone, Debug)]
+pub struct Change {
+    pub key: Key,
+    pub doctype: DocumentType,
+    pub kind: ChangeKind,
+
+    /// The time of the change in seconds since the Unix epoch.
+    pub timestamp: i64,
+
+    pub origin: Origin,
+}
+
+impl Change {
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "key": self.key.as_str(),
+            "type": self.doctype.as_str(),
+            "kind": self.kind.as_str(),
+            "timestamp": self.timestamp,
+        })
+    }
+}
+
+
+//------------ ChangeKind ----------------------------------------------------
+
+/// How a document has changed.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ChangeKind {
+    /// The file of the document was added since.
+    Added,
+
+    /// The file of the document was modified since.
+    Modified,
+}
+
+impl ChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Modified => "modified",
+        }
+    }
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the modification time of a file in seconds since the epoch.
+fn file_mtime(path: &Path) -> Option<i64> {
+    let mtime = path.metadata().ok()?.modified().ok()?;
+    let secs = mtime.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    i64::try_from(secs).ok()
+}
+
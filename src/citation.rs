@@ -0,0 +1,478 @@
+//! Formatted citations of sources.
+//!
+//! A [`Citation`] renders a source document into a string suitable for a
+//! bibliography or a footnote. There are two [`Style`]s: a short one for
+//! references in running text and a full one for a list of sources.
+//!
+//! Sources that are part of a collection – an article in a journal issue,
+//! say – include the collection in the full citation. The date of a source
+//! is taken from its collection if the source itself doesn’t have one.
+//!
+//! The [`Provenance`] of an event record collects the sources it is based
+//! on and renders them with their citations for use in footnotes.
+
+use std::fmt;
+use serde_json::{json, Value as JsonValue};
+use crate::document::{combined, entity, source};
+use crate::document::common::{Agreement, Basis};
+use crate::store::LinkTarget;
+use crate::types::{LanguagePreference, List, Marked};
+
+
+//------------ Style ---------------------------------------------------------
+
+/// The style of a citation.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Style {
+    /// A short citation for use in running text.
+    ///
+    /// This contains the short names of the authors, the short title, the
+    /// year, and the pages.
+    Short,
+
+    /// A full citation for use in a list of sources.
+    Full,
+}
+
+impl Style {
+    pub const ALL: &'static [Style] = &[Style::Short, Style::Full];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Style::Short => "short",
+            Style::Full => "full",
+        }
+    }
+}
+
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+
+//------------ Citation ------------------------------------------------------
+
+/// The citation of a source document.
+#[derive(Clone, Copy, Debug)]
+pub struct Citation<'a, L> {
+    /// The source to cite.
+    data: &'a source::Data,
+
+    /// The library to resolve links with.
+    library: &'a L,
+
+    /// The preferred languages for names of entities.
+    preference: &'a LanguagePreference,
+}
+
+/// The maximum depth of collections included in a citation.
+///
+/// This protects against circular collections.
+const MAX_DEPTH: usize = 8;
+
+impl<'a, L: LinkTarget<combined::Data>> Citation<'a, L> {
+    pub fn new(
+        data: &'a source::Data,
+        library: &'a L,
+        preference: &'a LanguagePreference,
+    ) -> Self {
+        Citation { data, library, preference }
+    }
+
+    /// Returns the citation in the given style.
+    pub fn render(&self, style: Style) -> String {
+        match style {
+            Style::Short => self.short(),
+            Style::Full => self.full(),
+        }
+    }
+
+    /// Returns the short citation.
+    ///
+    /// This looks like ‘Author: Short title, 1999, p. 12’.
+    pub fn short(&self) -> String {
+        let mut res = String::new();
+        if let Some(names) = self.short_names(self.data, 0) {
+            res.push_str(&names);
+        }
+        if let Some(title) = self.short_title(self.data, 0) {
+            if !res.is_empty() {
+                res.push_str(": ");
+            }
+            res.push_str(title);
+        }
+        if res.is_empty() {
+            res.push_str(self.data.key().as_str());
+        }
+        if let Some(year) = self.year() {
+            res.push_str(", ");
+            res.push_str(&year);
+        }
+        if let Some(pages) = self.data.pages.as_ref() {
+            res.push_str(", ");
+            res.push_str(&format_pages(pages));
+        }
+        res
+    }
+
+    /// Returns the full citation.
+    ///
+    /// This looks like ‘Author: Title. 2nd ed. In: Editor (ed.): Collection.
+    /// Publisher, 1999. pp. 12–14.’
+    pub fn full(&self) -> String {
+        let mut parts = self.full_parts(self.data, 0);
+        let mut tail = Vec::new();
+        if let Some(year) = self.year() {
+            tail.push(year);
+        }
+        if let Some(pages) = self.data.pages.as_ref() {
+            tail.push(format_pages(pages));
+        }
+        if let Some(url) = self.data.url.as_ref() {
+            tail.push(url.as_ref().to_string());
+        }
+        if !tail.is_empty() {
+            // The year follows the publisher with a comma.
+            if !self.data.publisher.is_empty() {
+                if let Some(last) = parts.last_mut() {
+                    last.push_str(", ");
+                    last.push_str(&tail.remove(0));
+                }
+            }
+            parts.extend(tail);
+        }
+        if parts.is_empty() {
+            return self.data.key().as_str().into()
+        }
+        let mut res = String::new();
+        for part in parts {
+            if !res.is_empty() {
+                res.push(' ');
+            }
+            res.push_str(&part);
+            if !part.ends_with(['.', '?', '!']) {
+                res.push('.');
+            }
+        }
+        res
+    }
+
+    /// Returns the title used in the short citation.
+    ///
+    /// This is the short title or, if there is none, the title of the
+    /// source or its collection.
+    pub fn title(&self) -> Option<&str> {
+        self.short_title(self.data, 0)
+    }
+
+    /// Returns both citation styles as a JSON object.
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "short": self.short(),
+            "full": self.full(),
+        })
+    }
+}
+
+impl<'a, L: LinkTarget<combined::Data>> Citation<'a, L> {
+    /// Returns the year of the source or its collection.
+    fn year(&self) -> Option<String> {
+        let date = self.data.date(self.library)?.iter().next()?;
+        Some(format!("{}", date.year()))
+    }
+
+    /// Returns the parts of the full citation without year and pages.
+    fn full_parts(&self, data: &source::Data, depth: usize) -> Vec<String> {
+        let mut res = Vec::new();
+        let names = self.full_names(data);
+        match (names, data.title.as_ref()) {
+            (Some(names), Some(title)) => {
+                res.push(format!("{}: {}", names, title.as_value()))
+            }
+            (Some(names), None) => res.push(names),
+            (None, Some(title)) => res.push(title.as_value().to_string()),
+            (None, None) => { }
+        }
+        if let Some(edition) = data.edition.as_ref() {
+            res.push(format!("{} ed", edition.as_value()));
+        }
+        let mut numbering = Vec::new();
+        if let Some(volume) = data.volume.as_ref() {
+            numbering.push(format!("vol. {}", volume.as_value()));
+        }
+        if let Some(number) = data.number.as_ref() {
+            numbering.push(format!("no. {}", number.as_value()));
+        }
+        if let Some(designation) = data.designation.as_ref() {
+            numbering.push(designation.as_value().to_string());
+        }
+        let numbering = numbering.join(", ");
+        match data.collection {
+            Some(collection) if depth < MAX_DEPTH => {
+                // The numbering goes right after the collection’s title.
+                let collection = collection.data(self.library);
+                let mut inner = self.full_parts(collection, depth + 1);
+                if let Some(first) = inner.first_mut() {
+                    first.insert_str(0, "In: ");
+                    if !numbering.is_empty() {
+                        first.push_str(", ");
+                        first.push_str(&numbering);
+                    }
+                }
+                else if !numbering.is_empty() {
+                    inner.push(numbering);
+                }
+                res.extend(inner);
+            }
+            _ => {
+                if !numbering.is_empty() {
+                    res.push(numbering);
+                }
+            }
+        }
+        if !data.publisher.is_empty() {
+            res.push(self.join_names(&data.publisher, false));
+        }
+        res
+    }
+
+    /// Returns the names for the full citation.
+    ///
+    /// These are the authors or, if there are none, the editors or the
+    /// organizations.
+    fn full_names(&self, data: &source::Data) -> Option<String> {
+        if !data.author.is_empty() {
+            Some(self.join_names(&data.author, false))
+        }
+        else if !data.editor.is_empty() {
+            Some(format!(
+                "{} ({})",
+                self.join_names(&data.editor, false),
+                if data.editor.len() == 1 { "ed." } else { "eds." }
+            ))
+        }
+        else if !data.organization.is_empty() {
+            Some(self.join_names(&data.organization, false))
+        }
+        else {
+            None
+        }
+    }
+
+    /// Returns the names for the short citation.
+    ///
+    /// If there are more than two names, only the first one is given.
+    /// Sources without any names use those of their collection.
+    fn short_names(
+        &self, data: &source::Data, depth: usize
+    ) -> Option<String> {
+        let names = if !data.author.is_empty() {
+            &data.author
+        }
+        else if !data.editor.is_empty() {
+            &data.editor
+        }
+        else if !data.organization.is_empty() {
+            &data.organization
+        }
+        else {
+            let collection = data.collection?;
+            if depth >= MAX_DEPTH {
+                return None
+            }
+            return self.short_names(
+                collection.data(self.library), depth + 1
+            )
+        };
+        if names.len() > 2 {
+            Some(format!(
+                "{} et al.",
+                self.name(names.first().unwrap(), true)
+            ))
+        }
+        else {
+            Some(self.join_names(names, true))
+        }
+    }
+
+    /// Returns the title for the short citation.
+    ///
+    /// Sources without any title use the title of their collection.
+    fn short_title<'s>(
+        &'s self, data: &'s source::Data, depth: usize
+    ) -> Option<&'s str> {
+        if let Some(title) = data.short_title.as_ref() {
+            return Some(title.as_value().as_str())
+        }
+        if let Some(title) = data.title.as_ref() {
+            return Some(title.as_value().as_str())
+        }
+        let collection = data.collection?;
+        if depth >= MAX_DEPTH {
+            return None
+        }
+        self.short_title(collection.data(self.library), depth + 1)
+    }
+
+    /// Joins a list of entity names.
+    fn join_names(
+        &self, names: &List<Marked<entity::Link>>, short: bool
+    ) -> String {
+        let mut res = String::new();
+        let len = names.len();
+        for (idx, link) in names.iter().enumerate() {
+            if idx > 0 {
+                res.push_str(if idx + 1 == len { " and " } else { ", " });
+            }
+            res.push_str(self.name(link, short));
+        }
+        res
+    }
+
+    /// Returns the name of a single entity.
+    fn name(&self, link: &Marked<entity::Link>, short: bool) -> &'a str {
+        let data = link.into_value().data(self.library);
+        if short {
+            data.preferred_short_name(self.preference)
+        }
+        else {
+            data.preferred_name(self.preference)
+        }
+    }
+}
+
+
+//------------ Provenance ----------------------------------------------------
+
+/// The sources an event record is based on.
+///
+/// Records distinguish between the documents that are the primary basis
+/// of the record, the secondary sources reporting it, and the legal basis
+/// of what happened, such as an act or a contract.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Provenance<'a> {
+    /// The primary documents.
+    pub document: &'a [Marked<source::Link>],
+
+    /// The secondary sources.
+    pub source: &'a [Marked<source::Link>],
+
+    /// The legal basis.
+    pub basis: &'a [Basis],
+
+    /// An agreement the record itself is about.
+    pub agreement: Option<&'a Agreement>,
+}
+
+impl<'a> Provenance<'a> {
+    pub fn is_empty(&self) -> bool {
+        self.document.is_empty() && self.source.is_empty()
+            && self.basis.is_empty() && self.agreement.is_none()
+    }
+
+    /// Returns the provenance as a JSON object.
+    ///
+    /// Sources are resolved into their key, title, pages, and both
+    /// citation styles.
+    pub fn to_json<L: LinkTarget<combined::Data>>(
+        &self, library: &L, preference: &LanguagePreference
+    ) -> JsonValue {
+        json!({
+            "document": references_to_json(
+                self.document, library, preference
+            ),
+            "source": references_to_json(self.source, library, preference),
+            "basis": self.basis.iter().map(|basis| {
+                basis_to_json(basis, library, preference)
+            }).collect::<Vec<_>>(),
+            "agreement": self.agreement.map(|agreement| {
+                agreement_to_json(agreement, library, preference)
+            }),
+        })
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns a list of sources as a JSON array.
+fn references_to_json<L: LinkTarget<combined::Data>>(
+    links: &[Marked<source::Link>],
+    library: &L,
+    preference: &LanguagePreference,
+) -> JsonValue {
+    links.iter().map(|link| {
+        let data = link.into_value().data(library);
+        let citation = Citation::new(data, library, preference);
+        json!({
+            "key": data.key().as_str(),
+            "title": citation.title(),
+            "pages": data.pages.as_ref().map(|pages| pages.as_str()),
+            "citation": citation.to_json(),
+        })
+    }).collect()
+}
+
+/// Returns a legal basis as JSON.
+///
+/// Besides the basis’s own attributes, the object has a `type` describing
+/// what kind of basis it is. See [`Basis::basis_type`] for the values.
+fn basis_to_json<L: LinkTarget<combined::Data>>(
+    basis: &Basis,
+    library: &L,
+    preference: &LanguagePreference,
+) -> JsonValue {
+    json!({
+        "type": basis.basis_type(),
+        "date": basis.date.iter().map(|date| {
+            date.to_string()
+        }).collect::<Vec<_>>(),
+        "document": references_to_json(
+            basis.document.as_slice(), library, preference
+        ),
+        "source": references_to_json(
+            basis.source.as_slice(), library, preference
+        ),
+        "agreement": basis.agreement.as_ref().map(|agreement| {
+            agreement_to_json(agreement, library, preference)
+        }),
+        "note": basis.note.as_ref().map(|note| {
+            note.for_preference(preference)
+        }),
+    })
+}
+
+/// Returns an agreement with the names of its parties as JSON.
+fn agreement_to_json<L: LinkTarget<combined::Data>>(
+    agreement: &Agreement,
+    library: &L,
+    preference: &LanguagePreference,
+) -> JsonValue {
+    json!({
+        "type": agreement.agreement_type.as_str(),
+        "parties": agreement.parties.iter().map(|link| {
+            let data = link.into_value().data(library);
+            json!({
+                "key": data.key().as_str(),
+                "name": data.preferred_name(preference),
+            })
+        }).collect::<Vec<_>>(),
+    })
+}
+
+
+/// Formats the pages of a source.
+///
+/// A single page gets a ‘p.’ prefix, anything else ‘pp.’
+fn format_pages(pages: &source::Pages) -> String {
+    let pages = pages.as_str();
+    if pages.chars().all(|ch| ch.is_alphanumeric()) {
+        format!("p. {}", pages)
+    }
+    else {
+        format!("pp. {}", pages)
+    }
+}
+
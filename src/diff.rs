@@ -0,0 +1,506 @@
+//! Differences between two versions of the data.
+//!
+//! A [`Diff`] compares the documents of two stores, typically loaded from
+//! two checkouts of the data directory. It lists the documents that were
+//! added or removed and, for documents present in both, the attributes
+//! that changed.
+//!
+//! Documents are compared in their canonical YAML form as produced by
+//! [`store::write`][crate::store::write], so purely cosmetic edits to the
+//! files don’t show up. The elements of the `events` and `records` lists
+//! are matched by their date rather than their position so that inserting
+//! an event only shows up as a single addition.
+//!
+//! For use by programs, [`json_patch`] expresses the differences of a
+//! single document as an RFC 6902 JSON Patch instead. Since JSON Patch
+//! addresses list elements by position, lists are compared element by
+//! element there.
+
+use std::{cmp, fmt};
+use std::collections::{HashMap, HashSet};
+use serde_json::{json, Value as JsonValue};
+use yaml_rust::Yaml;
+use crate::bulk::yaml_to_json;
+use crate::document::combined::Data;
+use crate::store::DataStore;
+use crate::store::write::document_to_yaml;
+use crate::types::Key;
+
+
+//------------ Diff ----------------------------------------------------------
+
+/// The differences between two stores.
+#[derive(Clone, Debug, Default)]
+pub struct Diff {
+    /// The keys of documents only present in the new store.
+    pub added: Vec<Key>,
+
+    /// The keys of documents only present in the old store.
+    pub removed: Vec<Key>,
+
+    /// The documents present in both stores that differ.
+    pub changed: Vec<DocumentDiff>,
+}
+
+impl Diff {
+    /// Compares the documents of two stores.
+    pub fn new(old: &DataStore, new: &DataStore) -> Self {
+        let mut res = Diff::default();
+        let mut old_iter = old.iter().peekable();
+        let mut new_iter = new.iter().peekable();
+
+        // Both stores iterate in key order, so we can merge them.
+        loop {
+            match (old_iter.peek(), new_iter.peek()) {
+                (Some(left), Some(right)) => {
+                    match left.key().cmp(right.key()) {
+                        cmp::Ordering::Less => {
+                            res.removed.push(left.key().clone());
+                            old_iter.next();
+                        }
+                        cmp::Ordering::Greater => {
+                            res.added.push(right.key().clone());
+                            new_iter.next();
+                        }
+                        cmp::Ordering::Equal => {
+                            let diff = DocumentDiff::new(
+                                left, old, right, new
+                            );
+                            if !diff.is_empty() {
+                                res.changed.push(diff)
+                            }
+                            old_iter.next();
+                            new_iter.next();
+                        }
+                    }
+                }
+                (Some(left), None) => {
+                    res.removed.push(left.key().clone());
+                    old_iter.next();
+                }
+                (None, Some(right)) => {
+                    res.added.push(right.key().clone());
+                    new_iter.next();
+                }
+                (None, None) => break
+            }
+        }
+        res
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for key in &self.added {
+            writeln!(f, "+ {}", key)?;
+        }
+        for key in &self.removed {
+            writeln!(f, "- {}", key)?;
+        }
+        for doc in &self.changed {
+            write!(f, "{}", doc)?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ DocumentDiff --------------------------------------------------
+
+/// The differences of a single document.
+#[derive(Clone, Debug)]
+pub struct DocumentDiff {
+    /// The key of the document.
+    pub key: Key,
+
+    /// The changed attributes.
+    pub changes: Vec<Change>,
+}
+
+impl DocumentDiff {
+    fn new(
+        old: &Data, old_store: &DataStore,
+        new: &Data, new_store: &DataStore,
+    ) -> Self {
+        let mut changes = Vec::new();
+        compare(
+            &mut String::new(),
+            &document_yaml(old, old_store),
+            &document_yaml(new, new_store),
+            &mut changes
+        );
+        DocumentDiff { key: new.key().clone(), changes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for DocumentDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "~ {}", self.key)?;
+        for change in &self.changes {
+            writeln!(f, "    {}", change)?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ Change --------------------------------------------------------
+
+/// A change to a single attribute.
+///
+/// The attribute is given as a path of mapping keys and list elements.
+/// List elements are identified by their date if they have one, such as
+/// in `events[1880-05-01].status`, or their index otherwise.
+#[derive(Clone, Debug)]
+pub enum Change {
+    Added { attr: String, value: Yaml },
+    Removed { attr: String, value: Yaml },
+    Changed { attr: String, old: Yaml, new: Yaml },
+}
+
+impl Change {
+    pub fn attr(&self) -> &str {
+        match *self {
+            Change::Added { ref attr, .. } => attr,
+            Change::Removed { ref attr, .. } => attr,
+            Change::Changed { ref attr, .. } => attr,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Change::Added { ref attr, ref value } => {
+                write!(f, "+ {}: {}", attr, InlineYaml(value))
+            }
+            Change::Removed { ref attr, ref value } => {
+                write!(f, "- {}: {}", attr, InlineYaml(value))
+            }
+            Change::Changed { ref attr, ref old, ref new } => {
+                write!(
+                    f, "~ {}: {} -> {}",
+                    attr, InlineYaml(old), InlineYaml(new)
+                )
+            }
+        }
+    }
+}
+
+
+//------------ json_patch ----------------------------------------------------
+
+/// Returns the JSON Patch turning the old version of a document into the
+/// new one.
+///
+/// The documents are compared in the JSON form of their canonical YAML.
+/// The result is an array of operations as defined in RFC 6902. If
+/// there is no old version, the patch adds the whole document.
+pub fn json_patch(
+    old: Option<(&Data, &DataStore)>, new: &Data, new_store: &DataStore,
+) -> JsonValue {
+    let new = yaml_to_json(&document_yaml(new, new_store));
+    let mut res = Vec::new();
+    match old {
+        Some((old, old_store)) => {
+            let old = yaml_to_json(&document_yaml(old, old_store));
+            patch_value(&mut String::new(), &old, &new, &mut res);
+        }
+        None => {
+            res.push(json!({ "op": "add", "path": "", "value": new }))
+        }
+    }
+    JsonValue::Array(res)
+}
+
+/// Appends the operations turning `old` into `new` at `path` to `ops`.
+fn patch_value(
+    path: &mut String, old: &JsonValue, new: &JsonValue,
+    ops: &mut Vec<JsonValue>
+) {
+    match (old, new) {
+        (JsonValue::Object(old), JsonValue::Object(new)) => {
+            for (key, old_value) in old {
+                let len = push_pointer(path, key);
+                match new.get(key) {
+                    Some(new_value) => {
+                        patch_value(path, old_value, new_value, ops)
+                    }
+                    None => ops.push(json!({ "op": "remove", "path": path }))
+                }
+                path.truncate(len);
+            }
+            for (key, new_value) in new {
+                if !old.contains_key(key) {
+                    let len = push_pointer(path, key);
+                    ops.push(json!({
+                        "op": "add", "path": path, "value": new_value
+                    }));
+                    path.truncate(len);
+                }
+            }
+        }
+        (JsonValue::Array(old), JsonValue::Array(new)) => {
+            let pairs = old.iter().zip(new).enumerate();
+            for (idx, (old_value, new_value)) in pairs {
+                let len = push_pointer(path, &idx.to_string());
+                patch_value(path, old_value, new_value, ops);
+                path.truncate(len);
+            }
+            // Remove from the back so the indexes stay valid.
+            for idx in (new.len()..old.len()).rev() {
+                let len = push_pointer(path, &idx.to_string());
+                ops.push(json!({ "op": "remove", "path": path }));
+                path.truncate(len);
+            }
+            for new_value in new.iter().skip(old.len()) {
+                let len = push_pointer(path, "-");
+                ops.push(json!({
+                    "op": "add", "path": path, "value": new_value
+                }));
+                path.truncate(len);
+            }
+        }
+        (old, new) => {
+            if old != new {
+                ops.push(json!({
+                    "op": "replace", "path": path, "value": new
+                }))
+            }
+        }
+    }
+}
+
+/// Appends a reference token to a JSON pointer.
+///
+/// Returns the previous length of the pointer for truncating it back.
+fn push_pointer(path: &mut String, token: &str) -> usize {
+    let len = path.len();
+    path.push('/');
+    path.push_str(&token.replace('~', "~0").replace('/', "~1"));
+    len
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the YAML for a document.
+///
+/// Path documents can’t be written as YAML. For them, a summary with the
+/// name and the number of nodes is compared instead.
+fn document_yaml(data: &Data, store: &DataStore) -> Yaml {
+    if let Some(yaml) = document_to_yaml(data, store) {
+        return yaml
+    }
+    let mut res = yaml_rust::yaml::Hash::new();
+    if let Some(path) = data.try_as_path() {
+        if let Some(name) = path.name.as_ref() {
+            res.insert(
+                Yaml::String("name".into()), Yaml::String(name.clone())
+            );
+        }
+        res.insert(
+            Yaml::String("nodes".into()),
+            Yaml::Integer(path.nodes.len() as i64)
+        );
+        res.insert(
+            Yaml::String("node_names".into()),
+            Yaml::Integer(path.node_names.len() as i64)
+        );
+    }
+    Yaml::Hash(res)
+}
+
+/// Compares two YAML values and appends the differences to `changes`.
+fn compare(
+    attr: &mut String, old: &Yaml, new: &Yaml, changes: &mut Vec<Change>
+) {
+    match (old, new) {
+        (Yaml::Hash(old), Yaml::Hash(new)) => {
+            for (key, old_value) in old {
+                let len = push_attr(attr, &yaml_key(key));
+                match new.get(key) {
+                    Some(new_value) => {
+                        compare(attr, old_value, new_value, changes)
+                    }
+                    None => changes.push(Change::Removed {
+                        attr: attr.clone(), value: old_value.clone()
+                    })
+                }
+                attr.truncate(len);
+            }
+            for (key, new_value) in new {
+                if !old.contains_key(key) {
+                    let len = push_attr(attr, &yaml_key(key));
+                    changes.push(Change::Added {
+                        attr: attr.clone(), value: new_value.clone()
+                    });
+                    attr.truncate(len);
+                }
+            }
+        }
+        (Yaml::Array(old), Yaml::Array(new)) => {
+            compare_lists(attr, old, new, changes)
+        }
+        // A list with a single element is written as the plain element.
+        (Yaml::Array(old), new) => {
+            compare_lists(attr, old, std::slice::from_ref(new), changes)
+        }
+        (old, Yaml::Array(new)) => {
+            compare_lists(attr, std::slice::from_ref(old), new, changes)
+        }
+        (old, new) => {
+            if old != new {
+                changes.push(Change::Changed {
+                    attr: attr.clone(), old: old.clone(), new: new.clone()
+                })
+            }
+        }
+    }
+}
+
+/// Compares two lists.
+///
+/// If the elements are mappings with dates, they are matched by date.
+/// Otherwise, they are matched by position.
+fn compare_lists(
+    attr: &mut String, old: &[Yaml], new: &[Yaml], changes: &mut Vec<Change>
+) {
+    let old_ids = element_ids(old);
+    let new_ids = element_ids(new);
+    let new_index = new_ids.iter().enumerate().map(|(idx, id)| {
+        (id.as_str(), idx)
+    }).collect::<HashMap<_, _>>();
+    let old_index = old_ids.iter().map(|id| {
+        id.as_str()
+    }).collect::<HashSet<_>>();
+
+    for (old_value, id) in old.iter().zip(&old_ids) {
+        let len = push_element(attr, id);
+        match new_index.get(id.as_str()) {
+            Some(&idx) => compare(attr, old_value, &new[idx], changes),
+            None => changes.push(Change::Removed {
+                attr: attr.clone(), value: old_value.clone()
+            })
+        }
+        attr.truncate(len);
+    }
+    for (new_value, id) in new.iter().zip(&new_ids) {
+        if !old_index.contains(id.as_str()) {
+            let len = push_element(attr, id);
+            changes.push(Change::Added {
+                attr: attr.clone(), value: new_value.clone()
+            });
+            attr.truncate(len);
+        }
+    }
+}
+
+/// Returns identifiers for the elements of a list.
+///
+/// Elements with a date use the date, numbered if the same date appears
+/// more than once. All other elements use their index.
+fn element_ids(list: &[Yaml]) -> Vec<String> {
+    let date_key = Yaml::String("date".into());
+    let mut seen = HashMap::<String, usize>::new();
+    list.iter().enumerate().map(|(idx, item)| {
+        let date = match *item {
+            Yaml::Hash(ref hash) => hash.get(&date_key).map(|date| {
+                InlineYaml(date).to_string()
+            }),
+            _ => None
+        };
+        match date {
+            Some(date) => {
+                let count = seen.entry(date.clone()).or_default();
+                *count += 1;
+                if *count > 1 {
+                    format!("{}#{}", date, count)
+                }
+                else {
+                    date
+                }
+            }
+            None => idx.to_string()
+        }
+    }).collect()
+}
+
+/// Appends a mapping key to an attribute path.
+///
+/// Returns the previous length of the path for truncating it back.
+fn push_attr(attr: &mut String, key: &str) -> usize {
+    let len = attr.len();
+    if !attr.is_empty() {
+        attr.push('.');
+    }
+    attr.push_str(key);
+    len
+}
+
+/// Appends a list element to an attribute path.
+fn push_element(attr: &mut String, id: &str) -> usize {
+    let len = attr.len();
+    attr.push('[');
+    attr.push_str(id);
+    attr.push(']');
+    len
+}
+
+fn yaml_key(key: &Yaml) -> String {
+    match *key {
+        Yaml::String(ref key) => key.clone(),
+        ref key => InlineYaml(key).to_string()
+    }
+}
+
+
+//------------ InlineYaml ----------------------------------------------------
+
+/// Displays a YAML value on a single line in flow style.
+struct InlineYaml<'a>(&'a Yaml);
+
+impl<'a> fmt::Display for InlineYaml<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self.0 {
+            Yaml::Real(ref value) => f.write_str(value),
+            Yaml::Integer(value) => write!(f, "{}", value),
+            Yaml::String(ref value) => f.write_str(value),
+            Yaml::Boolean(value) => write!(f, "{}", value),
+            Yaml::Array(ref value) => {
+                f.write_str("[")?;
+                for (idx, item) in value.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", InlineYaml(item))?;
+                }
+                f.write_str("]")
+            }
+            Yaml::Hash(ref value) => {
+                f.write_str("{")?;
+                for (idx, (key, item)) in value.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}: {}", InlineYaml(key), InlineYaml(item))?;
+                }
+                f.write_str("}")
+            }
+            Yaml::Alias(_) | Yaml::Null | Yaml::BadValue => {
+                f.write_str("~")
+            }
+        }
+    }
+}
+
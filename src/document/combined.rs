@@ -1,5 +1,9 @@
+use std::{cmp, fmt, hash};
+use std::marker::PhantomData;
 use derive_more::From;
 use paste::paste;
+use serde_json::Value as JsonValue;
+use yaml_rust::Yaml;
 use crate::catalogue::CatalogueBuilder;
 use crate::load::report::{Failed, Origin, PathReporter, StageReporter};
 use crate::load::yaml::{FromYaml, Mapping, Value};
@@ -7,8 +11,10 @@ pub use crate::store::{
     DataStore, FullStore, LinkTarget, LinkTargetMut, DocumentLink,
     StoreLoader, XrefsBuilder,
 };
-use crate::types::{Key, Location, Marked, Set};
-use super::source;
+use crate::store::LinkMismatch;
+use crate::store::write::ToYaml;
+use crate::types::{Key, LanguagePreference, Location, Marked, Set};
+use super::{custom, source};
 use super::common::{Common, DocumentType};
 
 pub use crate::store::DocumentLink as Link;
@@ -19,6 +25,11 @@ macro_rules! document { ( $( ($vattr:ident, $vtype:ident,
     //------------ Data ------------------------------------------------------
 
     #[derive(Clone, Debug, From)]
+    #[cfg_attr(
+        feature = "serde",
+        derive(serde::Serialize, serde::Deserialize),
+        serde(tag = "type", rename_all = "lowercase")
+    )]
     pub enum Data {
         $(
             $vtype(super::$vattr::Data),
@@ -96,6 +107,24 @@ macro_rules! document { ( $( ($vattr:ident, $vtype:ident,
             }
         }
 
+        /// Creates a placeholder for a document that failed to load.
+        pub fn placeholder(
+            key: Marked<Key>,
+            doctype: DocumentType,
+            link: DocumentLink,
+            origin: Origin,
+        ) -> Self {
+            match doctype {
+                $(
+                    DocumentType::$vtype => {
+                        Data::$vtype(super::$vattr::Data::placeholder(
+                            key, link, origin
+                        ))
+                    }
+                )*
+            }
+        }
+
         pub fn default_xrefs(&self) -> Xrefs {
             match *self {
                 $(
@@ -151,6 +180,11 @@ macro_rules! document { ( $( ($vattr:ident, $vtype:ident,
     //------------ Xrefs -----------------------------------------------------
 
     #[derive(Clone, Debug, From)]
+    #[cfg_attr(
+        feature = "serde",
+        derive(serde::Serialize, serde::Deserialize),
+        serde(tag = "type", rename_all = "lowercase")
+    )]
     pub enum Xrefs {
         $(
             $vtype(super::$vattr::Xrefs),
@@ -158,6 +192,14 @@ macro_rules! document { ( $( ($vattr:ident, $vtype:ident,
     }
 
     impl Xrefs {
+        pub fn doctype(&self) -> DocumentType {
+            match *self {
+                $(
+                    Xrefs::$vtype(_) => DocumentType::$vtype,
+                )*
+            }
+        }
+
         pub fn source_regards_mut(&mut self) -> &mut Set<source::Link> {
             match *self {
                 $(
@@ -168,6 +210,16 @@ macro_rules! document { ( $( ($vattr:ident, $vtype:ident,
             }
         }
 
+        pub fn custom_regards_mut(&mut self) -> &mut Set<custom::Link> {
+            match *self {
+                $(
+                    Xrefs::$vtype(ref mut inner) => {
+                        inner.custom_regards_mut()
+                    }
+                )*
+            }
+        }
+
         pub fn finalize(&mut self, store: &DataStore) {
             match *self {
                 $(
@@ -175,12 +227,25 @@ macro_rules! document { ( $( ($vattr:ident, $vtype:ident,
                 )*
             }
         }
+
+        pub fn to_json(&self, store: &DataStore) -> JsonValue {
+            match *self {
+                $(
+                    Xrefs::$vtype(ref inner) => inner.to_json(store),
+                )*
+            }
+        }
     }
 
 
     //------------ Meta ------------------------------------------------------
 
     #[derive(Clone, Debug, From)]
+    #[cfg_attr(
+        feature = "serde",
+        derive(serde::Serialize, serde::Deserialize),
+        serde(tag = "type", rename_all = "lowercase")
+    )]
     pub enum Meta {
         $(
             $vtype(super::$vattr::Meta),
@@ -188,9 +253,33 @@ macro_rules! document { ( $( ($vattr:ident, $vtype:ident,
     }
 
     impl Meta {
+        pub fn doctype(&self) -> DocumentType {
+            match *self {
+                $(
+                    Meta::$vtype(_) => DocumentType::$vtype,
+                )*
+            }
+        }
+
+        /// Returns the document types whose metadata `doctype` needs.
+        ///
+        /// The metadata of these types is generated first and is available
+        /// via the store passed to [`generate`][Self::generate].
+        pub fn dependencies(
+            doctype: DocumentType
+        ) -> &'static [DocumentType] {
+            match doctype {
+                $(
+                    DocumentType::$vtype => {
+                        super::$vattr::Meta::DEPENDENCIES
+                    }
+                )*
+            }
+        }
+
         pub fn generate(
             data: &Data,
-            store: &crate::store::XrefsStore,
+            store: &crate::store::MetaStore,
             report: &mut crate::load::report::StageReporter,
         ) -> Result<Self, crate::load::report::Failed> {
             match *data {
@@ -206,94 +295,68 @@ macro_rules! document { ( $( ($vattr:ident, $vtype:ident,
                 )*
             }
         }
+
+        pub fn to_json(&self) -> JsonValue {
+            match *self {
+                $(
+                    Meta::$vtype(ref inner) => inner.to_json(),
+                )*
+            }
+        }
     }
 
 
     //------------ Links -----------------------------------------------------
 
     $(
-        #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-        pub struct $vlink(DocumentLink);
-
-        impl $vlink {
-            pub fn build(
-                key: Marked<Key>,
-                store: &StoreLoader,
-                report: &mut PathReporter,
-            ) -> Marked<Self> {
-                store.build_link(key, Some(DocumentType::$vtype), report)
-                    .map(Into::into)
-            }
+        pub type $vlink = TypedLink<super::$vattr::Data>;
 
-            pub fn data(
-                self, store: &impl LinkTarget<Data>
-            ) -> &super::$vattr::Data {
-                match *self.0.data(store) {
-                    Data::$vtype(ref inner) => inner,
-                    _ => panic!("link to wrong document type")
-                }
-            }
+        paste! {
+            impl DocumentKind for super::$vattr::Data {
+                const DOCTYPE: DocumentType = DocumentType::$vtype;
 
-            pub fn xrefs(
-                self, store: &impl LinkTarget<Xrefs>
-            ) -> &super::$vattr::Xrefs {
-                match *self.0.xrefs(store) {
-                    Xrefs::$vtype(ref inner) => inner,
-                    _ => panic!("link to wrong document type")
-                }
-            }
+                type Xrefs = super::$vattr::Xrefs;
+                type Meta = super::$vattr::Meta;
+                type Document<'a> = [<$vtype Document>]<'a>;
 
-            pub fn xrefs_mut(
-                self, store: &mut impl LinkTargetMut<Xrefs>
-            ) -> &mut super::$vattr::Xrefs {
-                match *self.0.xrefs_mut(store) {
-                    Xrefs::$vtype(ref mut inner) => inner,
-                    _ => panic!("link to wrong document type")
+                fn from_data(data: &Data) -> Option<&Self> {
+                    match *data {
+                        Data::$vtype(ref inner) => Some(inner),
+                        _ => None
+                    }
                 }
-            }
 
-            pub fn meta(
-                self, store: &impl LinkTarget<Meta>
-            ) -> &super::$vattr::Meta {
-                match *self.0.meta(store) {
-                    Meta::$vtype(ref inner) => inner,
-                    _ => panic!("link to wrong document type")
+                fn from_xrefs(xrefs: &Xrefs) -> Option<&Self::Xrefs> {
+                    match *xrefs {
+                        Xrefs::$vtype(ref inner) => Some(inner),
+                        _ => None
+                    }
                 }
-            }
 
-            pub fn document(
-                self, store: &FullStore,
-            ) -> super::$vattr::Document {
-                match self.0.document(store) {
-                    Document::$vtype(inner) => inner,
-                    _ => panic!("link to wrong document type")
+                fn from_xrefs_mut(
+                    xrefs: &mut Xrefs
+                ) -> Option<&mut Self::Xrefs> {
+                    match *xrefs {
+                        Xrefs::$vtype(ref mut inner) => Some(inner),
+                        _ => None
+                    }
                 }
-            }
-        }
 
-        impl From<DocumentLink> for $vlink {
-            fn from(link: DocumentLink) -> $vlink {
-                $vlink(link)
-            }
-        }
-
-        impl From<$vlink> for DocumentLink {
-            fn from(link: $vlink) -> DocumentLink {
-                link.0
-            }
-        }
+                fn from_meta(meta: &Meta) -> Option<&Self::Meta> {
+                    match *meta {
+                        Meta::$vtype(ref inner) => Some(inner),
+                        _ => None
+                    }
+                }
 
-        impl FromYaml<StoreLoader> for Marked<$vlink> {
-            fn from_yaml(
-                value: Value,
-                context: &StoreLoader,
-                report: &mut PathReporter
-            ) -> Result<Self, Failed> {
-                Ok($vlink::build(
-                    Marked::from_yaml(value, context, report)?,
-                    context,
-                    report
-                ))
+                fn from_document<'a>(
+                    document: Document<'a>
+                ) -> Option<Self::Document<'a>> {
+                    match document {
+                        Document::$vtype(inner) => Some(inner),
+                        _ => None
+                    }
+                }
             }
         }
     )*
@@ -339,6 +402,14 @@ macro_rules! document { ( $( ($vattr:ident, $vtype:ident,
                 }
             }
 
+            pub fn doctype(self) -> DocumentType {
+                match self {
+                    $(
+                        Document::$vtype(_) => DocumentType::$vtype,
+                    )*
+                }
+            }
+
             $(
                 paste! {
                     pub fn [< try_as_ $vtype:lower >](
@@ -401,7 +472,278 @@ document! (
     ( entity, Entity, EntityLink),
     ( path, Path, PathLink),
     ( point, Point, PointLink),
+    ( service, Service, ServiceLink),
     ( source, Source, SourceLink),
     ( structure, Structure, StructureLink),
+    ( custom, Custom, CustomLink),
 );
 
+
+
+//------------ DocumentKind --------------------------------------------------
+
+/// The data of a specific document type.
+///
+/// The trait connects the data type of a document type with the types of
+/// its cross references, metadata, and documents. This allows
+/// [`TypedLink`] to resolve to the right types.
+pub trait DocumentKind: Sized + 'static {
+    /// The document type.
+    const DOCTYPE: DocumentType;
+
+    /// The cross references of the document type.
+    type Xrefs;
+
+    /// The metadata of the document type.
+    type Meta;
+
+    /// The complete document of the document type.
+    type Document<'a>;
+
+    fn from_data(data: &Data) -> Option<&Self>;
+    fn from_xrefs(xrefs: &Xrefs) -> Option<&Self::Xrefs>;
+    fn from_xrefs_mut(xrefs: &mut Xrefs) -> Option<&mut Self::Xrefs>;
+    fn from_meta(meta: &Meta) -> Option<&Self::Meta>;
+    fn from_document<'a>(
+        document: Document<'a>
+    ) -> Option<Self::Document<'a>>;
+}
+
+
+//------------ TypedLink -----------------------------------------------------
+
+/// A link to a document of a specific type.
+///
+/// The type is given via the document’s data type, e.g., `TypedLink<
+/// line::Data>` is a link to a line. There are type aliases such as
+/// [`LineLink`] for all document types.
+///
+/// Typed links are built while loading. Links to documents of the wrong
+/// type are reported once when the store is created, so the methods
+/// resolving a typed link can rely on the type. Typed links created from a
+/// [`DocumentLink`] later on should be checked via
+/// [`try_from_link`][Self::try_from_link].
+pub struct TypedLink<T> {
+    link: DocumentLink,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: DocumentKind> TypedLink<T> {
+    pub fn build(
+        key: Marked<Key>,
+        store: &StoreLoader,
+        report: &mut PathReporter,
+    ) -> Marked<Self> {
+        store.build_link(key, Some(T::DOCTYPE), report).map(Into::into)
+    }
+
+    /// Converts a document link if it links to a document of type `T`.
+    pub fn try_from_link(
+        link: DocumentLink, store: &impl LinkTarget<Data>
+    ) -> Result<Self, LinkMismatch> {
+        let target = link.data(store).doctype();
+        if target == T::DOCTYPE {
+            Ok(link.into())
+        }
+        else {
+            Err(LinkMismatch::new(T::DOCTYPE, target))
+        }
+    }
+
+    pub fn data(self, store: &impl LinkTarget<Data>) -> &T {
+        let data = self.link.data(store);
+        match T::from_data(data) {
+            Some(inner) => inner,
+            None => Self::mismatch(data.doctype())
+        }
+    }
+
+    pub fn xrefs(self, store: &impl LinkTarget<Xrefs>) -> &T::Xrefs {
+        let xrefs = self.link.xrefs(store);
+        match T::from_xrefs(xrefs) {
+            Some(inner) => inner,
+            None => Self::mismatch(xrefs.doctype())
+        }
+    }
+
+    pub fn xrefs_mut(
+        self, store: &mut impl LinkTargetMut<Xrefs>
+    ) -> &mut T::Xrefs {
+        let xrefs = self.link.xrefs_mut(store);
+        let doctype = xrefs.doctype();
+        match T::from_xrefs_mut(xrefs) {
+            Some(inner) => inner,
+            None => Self::mismatch(doctype)
+        }
+    }
+
+    pub fn meta(self, store: &impl LinkTarget<Meta>) -> &T::Meta {
+        let meta = self.link.meta(store);
+        match T::from_meta(meta) {
+            Some(inner) => inner,
+            None => Self::mismatch(meta.doctype())
+        }
+    }
+
+    pub fn document(self, store: &FullStore) -> T::Document<'_> {
+        let document = self.link.document(store);
+        match T::from_document(document) {
+            Some(inner) => inner,
+            None => Self::mismatch(document.doctype())
+        }
+    }
+
+    fn mismatch(target: DocumentType) -> ! {
+        panic!("{}", LinkMismatch::new(T::DOCTYPE, target))
+    }
+}
+
+impl<T> Clone for TypedLink<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for TypedLink<T> { }
+
+impl<T> PartialEq for TypedLink<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.link == other.link
+    }
+}
+
+impl<T> Eq for TypedLink<T> { }
+
+impl<T> PartialOrd for TypedLink<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for TypedLink<T> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.link.cmp(&other.link)
+    }
+}
+
+impl<T> hash::Hash for TypedLink<T> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.link.hash(state)
+    }
+}
+
+impl<T: DocumentKind> fmt::Debug for TypedLink<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TypedLink")
+            .field("doctype", &T::DOCTYPE)
+            .field("link", &self.link)
+            .finish()
+    }
+}
+
+impl<T> From<DocumentLink> for TypedLink<T> {
+    fn from(link: DocumentLink) -> Self {
+        TypedLink { link, marker: PhantomData }
+    }
+}
+
+impl<T> From<TypedLink<T>> for DocumentLink {
+    fn from(link: TypedLink<T>) -> DocumentLink {
+        link.link
+    }
+}
+
+impl<T> ToYaml for TypedLink<T> {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        self.link.to_yaml(store)
+    }
+}
+
+impl<T: DocumentKind> FromYaml<StoreLoader> for Marked<TypedLink<T>> {
+    fn from_yaml(
+        value: Value,
+        context: &StoreLoader,
+        report: &mut PathReporter
+    ) -> Result<Self, Failed> {
+        Ok(TypedLink::build(
+            Marked::from_yaml(value, context, report)?,
+            context,
+            report
+        ))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for TypedLink<T> {
+    fn serialize<S: serde::Serializer>(
+        &self, serializer: S
+    ) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&self.link, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for TypedLink<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D
+    ) -> Result<Self, D::Error> {
+        <DocumentLink as serde::Deserialize>::deserialize(
+            deserializer
+        ).map(Into::into)
+    }
+}
+
+
+//------------ Data ----------------------------------------------------------
+
+impl Data {
+    /// Returns the current name in the most preferred language available.
+    ///
+    /// Falls back to the key if the document doesn’t have a name.
+    pub fn preferred_name(&self, preference: &LanguagePreference) -> &str {
+        match *self {
+            Data::Line(ref inner) => inner.preferred_name(preference),
+            Data::Entity(ref inner) => inner.preferred_name(preference),
+            Data::Path(ref inner) => {
+                inner.name.as_deref().unwrap_or_else(|| inner.key().as_str())
+            }
+            Data::Point(ref inner) => inner.preferred_name(preference),
+            Data::Source(ref inner) => {
+                inner.title.as_ref().map(|title| {
+                    title.as_value().as_str()
+                }).unwrap_or_else(|| inner.key().as_str())
+            }
+            Data::Service(ref inner) => inner.preferred_name(preference),
+            Data::Structure(ref inner) => inner.preferred_name(preference),
+            Data::Custom(ref inner) => inner.preferred_name(preference),
+        }
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the keys of the linked documents as a JSON array.
+pub(crate) fn keys_to_json<'a, L>(
+    links: impl IntoIterator<Item = &'a L>, store: &DataStore
+) -> JsonValue
+where L: Copy + Into<DocumentLink> + 'a {
+    links.into_iter().map(|link| {
+        JsonValue::from((*link).into().data(store).key().as_str())
+    }).collect()
+}
+
+/// Returns the keys of the linked documents as a sorted JSON array.
+///
+/// This is for sets of links whose order would otherwise depend on the
+/// order the documents were loaded in.
+pub(crate) fn sorted_keys_to_json<'a, L>(
+    links: impl IntoIterator<Item = &'a L>, store: &DataStore
+) -> JsonValue
+where L: Copy + Into<DocumentLink> + 'a {
+    let mut keys: Vec<_> = links.into_iter().map(|link| {
+        (*link).into().data(store).key().as_str()
+    }).collect();
+    keys.sort_unstable();
+    keys.into_iter().map(JsonValue::from).collect()
+}
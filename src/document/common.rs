@@ -1,11 +1,16 @@
 //! Attributes and attribute types common to all documents.
 
 use derive_more::Display;
+use serde_json::{json, Value as JsonValue};
+use yaml_rust::Yaml;
 use crate::load::report::{Failed, Origin, PathReporter};
 use crate::load::yaml::{FromYaml, Mapping, Value};
-use crate::store::StoreLoader;
+use crate::schema::Schema;
+use crate::store::{DataStore, StoreLoader};
+use crate::store::write::{MappingBuilder, ToYaml};
 use crate::types::{
-    EventDate, IntoMarked, Key, LanguageText, List, Location, Marked,
+    EventDate, IntoMarked, Key, LanguageText, List, Location, Marked, Text,
+    Url,
 };
 use super::{entity, source};
 
@@ -13,11 +18,20 @@ use super::{entity, source};
 //------------ Common --------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Common {
     //--- Attributes
     pub key: Marked<Key>,
     pub progress: Marked<Progress>,
     pub origin: Origin,
+
+    /// The names of files attached to the document.
+    ///
+    /// The files live in the document’s directory under `attachments`.
+    pub attachments: List<Marked<Text>>,
+
+    /// The license and attribution if they differ from the data tree’s.
+    pub attribution: Option<Attribution>,
 }
 
 impl Common {
@@ -30,9 +44,19 @@ impl Common {
             key,
             progress,
             origin,
+            attachments: List::default(),
+            attribution: None,
         }
     }
 
+    /// Creates the common attributes of a placeholder document.
+    ///
+    /// Placeholders stand in for documents that failed to load. They are
+    /// always stubs.
+    pub fn placeholder(key: Marked<Key>, origin: Origin) -> Self {
+        Self::new(key, Progress::Stub.marked(Location::NONE), origin)
+    }
+
     pub fn from_yaml(
         key: Marked<Key>,
         doc: &mut Mapping,
@@ -43,8 +67,25 @@ impl Common {
             key: key,
             progress: doc.take_default("progress", context, report)?,
             origin: Origin::new(report.path().clone(), doc.location()),
+            attachments: doc.take_default("attachments", context, report)?,
+            attribution: doc.take_opt("attribution", context, report)?,
         })
     }
+
+    /// Returns a mapping with the common attributes for writing.
+    pub fn to_yaml(
+        &self, doctype: DocumentType, store: &DataStore
+    ) -> MappingBuilder {
+        let mut res = MappingBuilder::new();
+        res.attr("key", &self.key, store);
+        res.attr("type", &doctype, store);
+        if *self.progress != Progress::default() {
+            res.attr("progress", &self.progress, store);
+        }
+        res.attr("attachments", &self.attachments, store);
+        res.attr("attribution", &self.attribution, store);
+        res
+    }
 }
 
 
@@ -56,8 +97,10 @@ data_enum! {
         { Entity: "entity" }
         { Path: "path" }
         { Point: "point" }
+        { Service: "service" }
         { Source: "source" }
         { Structure: "structure" }
+        { Custom: "custom" }
     }
 }
 
@@ -81,9 +124,92 @@ impl Progress {
 }
 
 
+//------------ Attribution ---------------------------------------------------
+
+/// The license of data and how to attribute it.
+///
+/// This is given for the whole data tree in its configuration file and
+/// can be overridden by documents through their `attribution` attribute.
+/// In documents, a plain string is short for an attribution with only
+/// the text.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Attribution {
+    /// The license, preferably as an SPDX identifier such as `CC-BY-4.0`.
+    pub license: Option<Text>,
+
+    /// The text to show with the data.
+    pub text: Option<Text>,
+
+    /// A URL with more information on the license.
+    pub url: Option<Url>,
+}
+
+impl Attribution {
+    pub fn is_empty(&self) -> bool {
+        self.license.is_none() && self.text.is_none() && self.url.is_none()
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "license": self.license.as_ref().map(Text::as_str),
+            "text": self.text.as_ref().map(Text::as_str),
+            "url": self.url.as_ref().map(Url::as_str),
+        })
+    }
+}
+
+impl<C> FromYaml<C> for Attribution {
+    fn from_yaml(
+        value: Value,
+        context: &C,
+        report: &mut PathReporter
+    ) -> Result<Self, Failed> {
+        let value = match value.try_into_string() {
+            Ok(text) => {
+                return Ok(Attribution {
+                    text: Some(Text::from(text.into_value())),
+                    .. Default::default()
+                })
+            }
+            Err(value) => value,
+        };
+        let mut value = value.into_mapping(report)?;
+        let license: Result<Option<Marked<Text>>, _>
+            = value.take_opt("license", context, report);
+        let text: Result<Option<Marked<Text>>, _>
+            = value.take_opt("text", context, report);
+        let url: Result<Option<Marked<Url>>, _>
+            = value.take_opt("url", context, report);
+        value.exhausted(report)?;
+        Ok(Attribution {
+            license: license?.map(Marked::into_value),
+            text: text?.map(Marked::into_value),
+            url: url?.map(Marked::into_value),
+        })
+    }
+}
+
+impl ToYaml for Attribution {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        if self.license.is_none() && self.url.is_none() {
+            if let Some(text) = self.text.as_ref() {
+                return text.to_yaml(store)
+            }
+        }
+        let mut res = MappingBuilder::new();
+        res.attr("license", &self.license, store)
+            .attr("text", &self.text, store)
+            .attr("url", &self.url, store);
+        res.into_yaml()
+    }
+}
+
+
 //------------ Alternative ---------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Alternative {
     pub date: EventDate,
     pub document: List<Marked<source::Link>>,
@@ -110,10 +236,21 @@ impl FromYaml<StoreLoader> for Alternative {
     }
 }
 
+impl ToYaml for Alternative {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = MappingBuilder::new();
+        res.attr("date", &self.date, store)
+            .attr("document", &self.document, store)
+            .attr("source", &self.source, store);
+        res.into_yaml()
+    }
+}
+
 
 //------------ Basis ---------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Basis {
     pub date: EventDate,
     pub document: List<Marked<source::Link>>,
@@ -122,6 +259,28 @@ pub struct Basis {
     pub note: Option<LanguageText>,
 }
 
+impl Basis {
+    /// Returns what kind of legal basis this is.
+    ///
+    /// This is the agreement type if there is an agreement, `"document"`
+    /// if the basis refers to primary documents, `"source"` if it only
+    /// refers to secondary sources, and `"note"` otherwise.
+    pub fn basis_type(&self) -> &'static str {
+        if let Some(agreement) = self.agreement.as_ref() {
+            agreement.agreement_type.as_str()
+        }
+        else if !self.document.is_empty() {
+            "document"
+        }
+        else if !self.source.is_empty() {
+            "source"
+        }
+        else {
+            "note"
+        }
+    }
+}
+
 impl FromYaml<StoreLoader> for Basis {
     fn from_yaml(
         value: Value,
@@ -179,10 +338,23 @@ impl FromYaml<StoreLoader> for Basis {
     }
 }
 
+impl ToYaml for Basis {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = MappingBuilder::new();
+        res.attr("date", &self.date, store)
+            .attr("document", &self.document, store)
+            .attr("source", &self.source, store)
+            .attr("agreement", &self.agreement, store)
+            .attr("note", &self.note, store);
+        res.into_yaml()
+    }
+}
+
 
 //------------ Agreement -----------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Agreement {
     pub agreement_type: AgreementType,
     pub parties: List<Marked<entity::Link>>,
@@ -206,6 +378,15 @@ impl FromYaml<StoreLoader> for Agreement {
     }
 }
 
+impl ToYaml for Agreement {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = MappingBuilder::new();
+        res.attr("type", &self.agreement_type, store)
+            .attr("parties", &self.parties, store);
+        res.into_yaml()
+    }
+}
+
 
 
 //------------ AgreementType -------------------------------------------------
@@ -221,6 +402,7 @@ data_enum! {
 //------------ Contract ------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Contract {
     pub parties: List<Marked<entity::Link>>,
     pub pos: Location,
@@ -250,6 +432,40 @@ impl FromYaml<StoreLoader> for Contract {
 }
 
 
+//------------ Schema --------------------------------------------------------
+
+pub fn schema(schema: &mut Schema) {
+    schema.object("common")
+        .mandatory("key", "key")
+        .mandatory("type", "enum(document_type)")
+        .optional("progress", "enum(progress)")
+        .optional("attachments", "list(string)")
+        .optional("attribution", "object(attribution)");
+    schema.object("attribution")
+        .optional("license", "string")
+        .optional("text", "string")
+        .optional("url", "url");
+    schema.object("basis")
+        .optional("date", "date")
+        .optional("document", "list(key(source))")
+        .optional("source", "list(key(source))")
+        .optional("agreement", "object(agreement)")
+        .optional("contract", "object(contract)")
+        .optional("treaty", "object(contract)")
+        .optional("note", "text");
+    schema.object("agreement")
+        .mandatory("type", "enum(agreement_type)")
+        .mandatory("parties", "list(key(entity))");
+    schema.object("contract")
+        .mandatory("parties", "list(key(entity))");
+
+    schema.enumeration("agreement_type", AgreementType::VARIANTS);
+    schema.enumeration("document_type", DocumentType::VARIANTS);
+    schema.enumeration("progress", Progress::VARIANTS);
+}
+
+
+
 //============ Errors ========================================================
 
 #[derive(Clone, Copy, Debug, Display)]
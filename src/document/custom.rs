@@ -0,0 +1,373 @@
+//! Document types defined outside this crate.
+//!
+//! Crates building on this one can add their own document types, such as
+//! vehicles or depots, without changing the core document types. Such
+//! documents have the type `custom` and name their actual type in the
+//! `kind` attribute:
+//!
+//! ```yaml
+//! key: vehicle.de.br01-1066
+//! type: custom
+//! kind: vehicle
+//! progress: stub
+//! class: BR 01
+//! depot: point.de.bw
+//! ```
+//!
+//! Each kind is implemented through a [`CustomType`] that parses all
+//! remaining attributes into a value implementing [`CustomData`]. The
+//! types are collected in a [`CustomRegistry`] which is handed to the
+//! [`StoreLoader`] via [`StoreLoader::with_custom_types`] or to
+//! [`load_tree_custom`][crate::load::load_tree_custom].
+//!
+//! Custom documents take part in key resolution like all other documents.
+//! Every document they link to as given by [`CustomData::links`] lists
+//! them in its `custom_regards` cross references.
+
+use std::any::Any;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+use derive_more::Display;
+use serde_json::{json, Value as JsonValue};
+use yaml_rust::Yaml;
+use crate::catalogue::CatalogueBuilder;
+use crate::load::report::{Failed, Origin, PathReporter};
+use crate::load::yaml::Mapping;
+use crate::schema::Schema;
+use crate::store::{
+    DataStore, DocumentLink, FullStore, MetaStore, StoreLoader, XrefsBuilder,
+};
+use crate::store::write::{MappingBuilder, ToYaml};
+use crate::types::{
+    IntoMarked, Key, LanguagePreference, Location, Marked, Set, Text,
+};
+use super::source;
+use super::combined::sorted_keys_to_json;
+use super::common::{Common, DocumentType, Progress};
+
+
+//------------ Link ----------------------------------------------------------
+
+pub use super::combined::CustomLink as Link;
+
+
+//------------ Document ------------------------------------------------------
+
+pub use super::combined::CustomDocument as Document;
+
+impl<'a> Document<'a> {
+}
+
+
+//------------ CustomType ----------------------------------------------------
+
+/// A document type defined outside this crate.
+pub trait CustomType: fmt::Debug + Send + Sync + 'static {
+    /// Returns the name of the type as used in the `kind` attribute.
+    fn name(&self) -> &'static str;
+
+    /// Parses the type-specific attributes of a document.
+    ///
+    /// The common attributes as well as `key`, `type`, and `kind` have
+    /// already been taken from `doc`. All other attributes need to be
+    /// taken, too, or they are reported as unexpected. Links to other
+    /// documents can be parsed as usual via `context`.
+    fn from_yaml(
+        &self,
+        doc: &mut Mapping,
+        context: &StoreLoader,
+        report: &mut PathReporter,
+    ) -> Result<Box<dyn CustomData>, Failed>;
+}
+
+
+//------------ CustomData ----------------------------------------------------
+
+/// The type-specific data of a custom document.
+pub trait CustomData: fmt::Debug + Send + Sync + 'static {
+    /// Returns the data as `Any` so it can be downcast.
+    fn as_any(&self) -> &dyn Any;
+
+    /// Returns the type-specific attributes for writing the document.
+    fn to_mapping(&self, store: &DataStore) -> MappingBuilder;
+
+    /// Returns the documents this document refers to.
+    fn links(&self) -> Vec<DocumentLink> {
+        Vec::new()
+    }
+
+    /// Returns the names the document should be found under.
+    fn names(&self) -> Vec<Text> {
+        Vec::new()
+    }
+
+    /// Returns the current name in the most preferred language available.
+    fn preferred_name(
+        &self, _preference: &LanguagePreference
+    ) -> Option<&str> {
+        None
+    }
+
+    /// Returns the type-specific data as JSON.
+    fn to_json(&self, _store: &DataStore) -> JsonValue {
+        JsonValue::Null
+    }
+}
+
+
+//------------ CustomRegistry ------------------------------------------------
+
+/// The custom document types known to a store.
+#[derive(Clone, Debug, Default)]
+pub struct CustomRegistry {
+    types: BTreeMap<&'static str, Arc<dyn CustomType>>,
+}
+
+impl CustomRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a custom type.
+    ///
+    /// A type already registered under the same name is replaced.
+    pub fn register(&mut self, custom: impl CustomType) {
+        self.types.insert(custom.name(), Arc::new(custom));
+    }
+
+    /// Returns the custom type with the given name.
+    pub fn get(&self, name: &str) -> Option<&dyn CustomType> {
+        self.types.get(name).map(|custom| custom.as_ref())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+
+    /// Returns an iterator over the names of all registered types.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.types.keys().copied()
+    }
+}
+
+
+//------------ Data ----------------------------------------------------------
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Data {
+    link: Link,
+    pub common: Common,
+
+    /// The name of the custom type.
+    pub kind: Marked<String>,
+
+    /// The type-specific data.
+    ///
+    /// This is `None` for placeholders. The data is not serialized, so it
+    /// is also missing from deserialized documents.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    inner: Option<Arc<dyn CustomData>>,
+}
+
+impl Data {
+    pub fn key(&self) -> &Key {
+        &self.common.key
+    }
+
+    pub fn progress(&self) -> Progress {
+        self.common.progress.into_value()
+    }
+
+    pub fn origin(&self) -> &Origin {
+        &self.common.origin
+    }
+
+    pub fn link(&self) -> Link {
+        self.link
+    }
+
+    /// Returns the type-specific data.
+    pub fn inner(&self) -> Option<&dyn CustomData> {
+        self.inner.as_deref()
+    }
+
+    /// Returns the type-specific data if it is of type `T`.
+    pub fn downcast_ref<T: CustomData>(&self) -> Option<&T> {
+        self.inner()?.as_any().downcast_ref()
+    }
+
+    /// Returns the current name in the most preferred language available.
+    pub fn preferred_name(&self, preference: &LanguagePreference) -> &str {
+        self.inner().and_then(|inner| {
+            inner.preferred_name(preference)
+        }).unwrap_or_else(|| self.key())
+    }
+}
+
+impl Data {
+    /// Creates a placeholder for a document that failed to load.
+    pub fn placeholder(
+        key: Marked<Key>, link: DocumentLink, origin: Origin
+    ) -> Self {
+        Data {
+            link: link.into(),
+            common: Common::placeholder(key, origin),
+            kind: String::new().marked(Location::NONE),
+            inner: None,
+        }
+    }
+
+    pub fn from_yaml(
+        key: Marked<Key>,
+        mut doc: Mapping,
+        link: DocumentLink,
+        context: &StoreLoader,
+        report: &mut PathReporter
+    ) -> Result<Self, Failed> {
+        let common = Common::from_yaml(key, &mut doc, context, report);
+        let kind: Marked<String> = doc.take("kind", context, report)?;
+        let inner = match context.custom_types().get(kind.as_str()) {
+            Some(custom) => custom.from_yaml(&mut doc, context, report),
+            None => {
+                report.error(
+                    UnknownKind(kind.as_value().clone())
+                        .marked(kind.location())
+                );
+                return Err(Failed)
+            }
+        };
+        doc.exhausted(report)?;
+        Ok(Data {
+            link: link.into(),
+            common: common?,
+            kind,
+            inner: Some(inner?.into()),
+        })
+    }
+
+    pub fn xrefs(
+        &self,
+        builder: &mut XrefsBuilder,
+        _store: &crate::store::DataStore,
+        _report: &mut PathReporter,
+    ) -> Result<(), Failed> {
+        if let Some(inner) = self.inner() {
+            for link in inner.links() {
+                link.xrefs_mut(builder).custom_regards_mut().insert(
+                    self.link
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn catalogue(
+        &self,
+        builder: &mut CatalogueBuilder,
+        _store: &FullStore,
+        _report: &mut PathReporter,
+    ) -> Result<(), Failed> {
+        let names: HashSet<_> = self.inner().map(|inner| {
+            inner.names()
+        }).unwrap_or_default().into_iter().collect();
+        for name in names {
+            builder.insert_name(name, self.link.into())
+        }
+        Ok(())
+    }
+}
+
+impl ToYaml for Data {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = self.common.to_yaml(DocumentType::Custom, store);
+        res.attr("kind", &self.kind, store);
+        if let Some(inner) = self.inner() {
+            res.extend(inner.to_mapping(store));
+        }
+        res.into_yaml()
+    }
+}
+
+
+//------------ Xrefs ---------------------------------------------------------
+
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Xrefs {
+    source_regards: Set<source::Link>,
+    custom_regards: Set<Link>,
+}
+
+impl Xrefs {
+    pub fn source_regards_mut(&mut self) -> &mut Set<source::Link> {
+        &mut self.source_regards
+    }
+
+    pub fn custom_regards_mut(&mut self) -> &mut Set<Link> {
+        &mut self.custom_regards
+    }
+
+    pub fn finalize(&mut self, _store: &DataStore) {
+    }
+
+    pub fn to_json(&self, store: &DataStore) -> JsonValue {
+        json!({
+            "source_regards": sorted_keys_to_json(
+                self.source_regards.iter(), store
+            ),
+            "custom_regards": sorted_keys_to_json(
+                self.custom_regards.iter(), store
+            ),
+        })
+    }
+}
+
+
+//------------ Meta ----------------------------------------------------------
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Meta {
+    /// The name of the custom type.
+    pub kind: String,
+}
+
+impl Meta {
+    /// Custom documents don’t have any metadata of their own.
+    pub const DEPENDENCIES: &'static [DocumentType] = &[];
+
+    pub fn generate(
+        data: &Data, _store: &MetaStore, _report: &mut PathReporter,
+    ) -> Result<Self, Failed> {
+        Ok(Meta { kind: data.kind.as_value().clone() })
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "kind": self.kind,
+        })
+    }
+}
+
+
+//------------ Schema --------------------------------------------------------
+
+/// Adds the custom document type.
+///
+/// Only the attributes common to all kinds are described since the kinds
+/// aren’t known statically.
+pub fn schema(schema: &mut Schema) {
+    schema.document("custom")
+        .include("common")
+        .mandatory("kind", "string");
+}
+
+
+//============ Errors ========================================================
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="unknown custom document kind '{}'", _0)]
+pub struct UnknownKind(String);
@@ -2,18 +2,28 @@
 use std::cmp;
 use std::collections::HashSet;
 use std::str::FromStr;
-use crate::catalogue::CatalogueBuilder;
+use serde_json::{json, Value as JsonValue};
+use yaml_rust::Yaml;
+use crate::catalogue::{CatalogueBuilder, EventKind};
+use crate::citation::Provenance;
 use crate::load::report::{Failed, Origin, PathReporter};
 use crate::load::yaml::{FromYaml, Mapping, Value};
+use crate::schema::Schema;
 use crate::store::{
-    DataStore, DocumentLink, FullStore, StoreLoader, XrefsBuilder, XrefsStore
+    DataStore, DocumentLink, FullStore, LinkTarget, MetaStore, StoreLoader,
+    XrefsBuilder
 };
+use crate::store::write::{MappingBuilder, ToYaml};
 use crate::types::{
-    CountryCode, EventDate, Key, LanguageText, LanguageCode, LocalText, List,
-    Marked, Set,
+    CountryCode, EventDate, Key, LanguageText, LanguageCode,
+    LanguagePreference, LocalText, List, Marked, OperatingPeriods, Set,
+};
+use crate::types::period::EndReason;
+use super::{combined, custom, entity, line, service, source};
+use super::combined::{keys_to_json, sorted_keys_to_json};
+use super::common::{
+    AgreementType, Basis, Common, DocumentType, Progress
 };
-use super::{entity, line, source};
-use super::common::{Basis, Common, Progress};
 
 
 //------------ Link ----------------------------------------------------------
@@ -32,6 +42,7 @@ impl<'a> Document<'a> {
 //------------ Data ----------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Data {
     link: entity::Link,
 
@@ -85,6 +96,94 @@ impl Data {
         self.local_name(lang)
     }
 
+    /// Returns the current name in the most preferred language available.
+    pub fn preferred_name(&self, preference: &LanguagePreference) -> &str {
+        self.events.iter().rev().find_map(|event| {
+            event.prop(|record| record.properties.name.as_ref())
+        }).map(|name| {
+            name.for_preference(preference)
+        }).unwrap_or_else(|| self.key())
+    }
+
+    /// Returns the current short name in the most preferred language.
+    ///
+    /// Falls back to the preferred name if there is no short name.
+    pub fn preferred_short_name(
+        &self, preference: &LanguagePreference
+    ) -> &str {
+        self.events.iter().rev().find_map(|event| {
+            event.prop(|record| record.properties.short_name.as_ref())
+        }).map(|name| {
+            name.for_preference(preference)
+        }).unwrap_or_else(|| self.preferred_name(preference))
+    }
+
+    /// Returns the organizations this organization is currently a unit of.
+    pub fn superior(&self) -> &[Marked<entity::Link>] {
+        self.events.iter().rev().find_map(|event| {
+            event.prop(|record| record.properties.superior.as_ref())
+        }).map(|list| list.as_slice()).unwrap_or_default()
+    }
+
+    /// Returns the region or country this region currently belongs to.
+    ///
+    /// This is the first current superior that is a geographical entity.
+    /// Returns `None` for entities that aren’t geographical themselves.
+    pub fn superior_region(
+        &self, store: &impl LinkTarget<combined::Data>
+    ) -> Option<entity::Link> {
+        if !self.subtype.is_geographical() {
+            return None
+        }
+        self.superior().iter().map(|link| link.into_value()).find(|link| {
+            link.data(store).subtype.is_geographical()
+        })
+    }
+
+    /// Returns the regions directly below this region.
+    pub fn subregions<'s>(
+        &self, store: &'s impl LinkTarget<combined::Xrefs>
+    ) -> &'s [entity::Link] {
+        self.link.xrefs(store).subregions.as_slice()
+    }
+
+    /// Returns all regions below this region.
+    ///
+    /// The regions are returned depth first, each followed by its own
+    /// subregions.
+    pub fn all_subregions(
+        &self, store: &impl LinkTarget<combined::Xrefs>
+    ) -> Vec<entity::Link> {
+        let mut res = Vec::new();
+        let mut seen = HashSet::new();
+        let mut stack: Vec<_> = self.subregions(store).iter().rev().collect();
+        while let Some(link) = stack.pop() {
+            // Guard against loops in broken data.
+            if *link == self.link || !seen.insert(*link) {
+                continue
+            }
+            res.push(*link);
+            stack.extend(link.xrefs(store).subregions.iter().rev());
+        }
+        res
+    }
+
+    /// Returns the regions this region belongs to, innermost first.
+    pub fn region_ancestors(
+        &self, store: &impl LinkTarget<combined::Data>
+    ) -> Vec<entity::Link> {
+        let mut res = Vec::new();
+        let mut current = self.superior_region(store);
+        while let Some(link) = current {
+            if link == self.link || res.contains(&link) {
+                break
+            }
+            res.push(link);
+            current = link.data(store).superior_region(store);
+        }
+        res
+    }
+
     pub fn historic_name(
         &self, lang: LanguageCode, date: &EventDate
     ) -> &str {
@@ -167,6 +266,29 @@ impl Data {
         }
     }
 
+    /// Returns the periods during which the entity existed.
+    ///
+    /// An entity starts existing when it is forming or opened and stops
+    /// when it is closed. The result is empty if none of the events has a
+    /// status.
+    pub fn existence_periods(&self) -> OperatingPeriods {
+        let mut res = OperatingPeriods::default();
+        for event in self.events.iter() {
+            let status = match event.prop(|record| {
+                record.properties.status.as_ref()
+            }) {
+                Some(status) => status.into_value(),
+                None => continue
+            };
+            let date = event.date.iter().next().map(Marked::into_value);
+            match status {
+                Status::Forming | Status::Open => res.open(date),
+                Status::Closed => res.close(date, EndReason::Closed),
+            }
+        }
+        res
+    }
+
     fn event_records(&self) -> impl Iterator<Item = &EventRecord> + '_ {
         self.events.iter().map(|ev| ev.records.iter()).flatten()
     }
@@ -179,6 +301,18 @@ impl Data {
 }
 
 impl Data {
+    /// Creates a placeholder for a document that failed to load.
+    pub fn placeholder(
+        key: Marked<Key>, link: DocumentLink, origin: Origin
+    ) -> Self {
+        Data {
+            link: link.into(),
+            common: Common::placeholder(key, origin),
+            subtype: Subtype::Placeholder.into(),
+            events: EventList::default(),
+        }
+    }
+
     pub fn from_yaml(
         key: Marked<Key>,
         mut doc: Mapping,
@@ -204,10 +338,14 @@ impl Data {
 
     pub fn xrefs(
         &self, 
-        _builder: &mut XrefsBuilder,
-        _store: &crate::store::DataStore,
+        builder: &mut XrefsBuilder,
+        store: &crate::store::DataStore,
         _report: &mut crate::load::report::PathReporter,
     ) -> Result<(), Failed> {
+        // entity: subregions of our superior region.
+        if let Some(superior) = self.superior_region(store) {
+            superior.xrefs_mut(builder).subregions.push(self.link);
+        }
         Ok(())
     }
 
@@ -232,7 +370,14 @@ impl Data {
             }
         }
         for name in names {
-            builder.insert_name(name.into(), self.link.into())
+            builder.insert_name(name.clone(), self.link.into())
+        }
+
+        // Insert events.
+        for (idx, event) in self.events.iter().enumerate() {
+            builder.insert_event(
+                &event.date, self.link.into(), idx, EventKind::Other
+            );
         }
 
         // Insert countries
@@ -248,16 +393,29 @@ impl Data {
     }
 }
 
+impl ToYaml for Data {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = self.common.to_yaml(DocumentType::Entity, store);
+        res.insert("subtype", self.subtype.to_yaml(store))
+            .insert("events", self.events.to_yaml(store));
+        res.into_yaml()
+    }
+}
+
 
 //------------ Xrefs ---------------------------------------------------------
 
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Xrefs {
     pub line_regions: List<(line::Link, line::Section)>,
 
     /// All the sources that refer to this entity.
     pub source_regards: Set<source::Link>,
 
+    /// All the custom documents that refer to this entity.
+    pub custom_regards: Set<custom::Link>,
+
     /// All the sources that this entity has authored.
     pub source_author: Set<source::Link>,
 
@@ -269,6 +427,15 @@ pub struct Xrefs {
 
     /// All the sources that this entity has published.
     pub source_publisher: Set<source::Link>,
+
+    /// The regions directly below this region.
+    pub subregions: List<entity::Link>,
+
+    /// The services operated by this entity.
+    pub services: Set<service::Link>,
+
+    /// The agreements on lines and points this entity is a party to.
+    pub agreements: Vec<AgreementParty>,
 }
 
 impl Xrefs {
@@ -276,9 +443,96 @@ impl Xrefs {
         &mut self.source_regards
     }
 
+    pub fn custom_regards_mut(&mut self) -> &mut Set<custom::Link> {
+        &mut self.custom_regards
+    }
+
     pub fn finalize(&mut self, store: &DataStore) {
         self.line_regions.sort_by(|left, right| {
-            left.0.data(store).code().cmp(&right.0.data(store).code())
+            line::Section::cmp_on_lines(left, right, store)
+        });
+        self.line_regions.dedup_by(|left, right| {
+            line::Section::same_on_lines(left, right)
+        });
+        self.subregions.sort_by(|left, right| {
+            left.data(store).key().cmp(right.data(store).key())
+        });
+        self.subregions.dedup();
+        self.agreements.sort_by(|left, right| {
+            left.date.sort_cmp(&right.date).then_with(|| {
+                left.document.data(store).key().cmp(
+                    right.document.data(store).key()
+                )
+            })
+        });
+        self.agreements.dedup();
+    }
+
+    pub fn to_json(&self, store: &DataStore) -> JsonValue {
+        json!({
+            "line_regions": self.line_regions.iter().map(|(line, section)| {
+                section.to_json(*line, store)
+            }).collect::<Vec<_>>(),
+            "source_regards": sorted_keys_to_json(
+                self.source_regards.iter(), store
+            ),
+            "custom_regards": sorted_keys_to_json(
+                self.custom_regards.iter(), store
+            ),
+            "source_author": sorted_keys_to_json(
+                self.source_author.iter(), store
+            ),
+            "source_editor": sorted_keys_to_json(
+                self.source_editor.iter(), store
+            ),
+            "source_organization": sorted_keys_to_json(
+                self.source_organization.iter(), store
+            ),
+            "source_publisher": sorted_keys_to_json(
+                self.source_publisher.iter(), store
+            ),
+            "subregions": keys_to_json(self.subregions.iter(), store),
+            "services": sorted_keys_to_json(self.services.iter(), store),
+            "agreements": self.agreements.iter().map(|item| {
+                item.to_json(store)
+            }).collect::<Vec<_>>(),
+        })
+    }
+}
+
+
+//------------ AgreementParty ------------------------------------------------
+
+/// An agreement on a line or point that an entity is a party to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AgreementParty {
+    /// The line or point the agreement is recorded with.
+    pub document: DocumentLink,
+
+    /// The date of the agreement.
+    pub date: EventDate,
+
+    /// The type of agreement.
+    pub agreement_type: AgreementType,
+
+    /// The roles the event of the agreement gives to the entity.
+    ///
+    /// This is empty if the event doesn’t name the entity itself.
+    pub roles: Vec<PropertyRole>,
+}
+
+impl AgreementParty {
+    pub fn to_json(&self, store: &DataStore) -> JsonValue {
+        json!({
+            "key": self.document.data(store).key().as_str(),
+            "type": self.agreement_type.as_str(),
+            "date": self.date.iter().map(|date| {
+                date.to_string()
+            }).collect::<Vec<_>>(),
+            "roles": self.roles.iter().map(|role| {
+                role.as_str()
+            }).collect::<Vec<_>>(),
         })
     }
 }
@@ -288,13 +542,17 @@ impl Xrefs {
 //------------ Meta ----------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Meta {
     pub current: Properties,
 }
 
 impl Meta {
+    /// Entity metadata only uses the entity’s own records.
+    pub const DEPENDENCIES: &'static [DocumentType] = &[];
+
     pub fn generate(
-        data: &Data, _store: &XrefsStore, _report: &mut PathReporter,
+        data: &Data, _store: &MetaStore, _report: &mut PathReporter,
     ) -> Result<Self, Failed> {
         let mut current = Properties::default();
         for record in data.event_records() {
@@ -305,6 +563,14 @@ impl Meta {
             current
         })
     }
+
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "status": self.current.status.map(|status| {
+                status.into_value().as_str()
+            }),
+        })
+    }
 }
 
 
@@ -340,6 +606,7 @@ pub type EventList = List<Event>;
 //------------ Event ---------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Event {
     pub date: EventDate,
     pub records: List<EventRecord>,
@@ -399,10 +666,27 @@ impl FromYaml<StoreLoader> for Event {
     }
 }
 
+impl ToYaml for Event {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = MappingBuilder::new();
+        res.attr("date", &self.date, store);
+        match self.records.single() {
+            Some(record) if record.date.is_none() => {
+                res.extend(record.to_mapping(store));
+            }
+            _ => {
+                res.insert("records", self.records.to_yaml(store));
+            }
+        }
+        res.into_yaml()
+    }
+}
+
 
 //------------ EventRecord ---------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EventRecord {
     pub date: Option<EventDate>,
     pub document: List<Marked<source::Link>>,
@@ -415,6 +699,18 @@ pub struct EventRecord {
     pub properties: Properties,
 }
 
+impl EventRecord {
+    /// Returns the sources the record is based on.
+    pub fn provenance(&self) -> Provenance<'_> {
+        Provenance {
+            document: self.document.as_slice(),
+            source: self.source.as_slice(),
+            basis: self.basis.as_slice(),
+            agreement: None,
+        }
+    }
+}
+
 impl EventRecord {
     fn from_mapping(
         value: &mut Mapping,
@@ -443,6 +739,20 @@ impl EventRecord {
     }
 }
 
+impl EventRecord {
+    fn to_mapping(&self, store: &DataStore) -> MappingBuilder {
+        let mut res = MappingBuilder::new();
+        res.attr("date", &self.date, store)
+            .attr("document", &self.document, store)
+            .attr("source", &self.source, store)
+            .attr("basis", &self.basis, store)
+            .attr("note", &self.note, store)
+            .attr("property", &self.property, store)
+            .extend(self.properties.to_mapping(store));
+        res
+    }
+}
+
 impl FromYaml<StoreLoader> for EventRecord {
     fn from_yaml(
         value: Value,
@@ -456,10 +766,17 @@ impl FromYaml<StoreLoader> for EventRecord {
     }
 }
 
+impl ToYaml for EventRecord {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        self.to_mapping(store).into_yaml()
+    }
+}
+
 
 //------------ Properties ----------------------------------------------------
 
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Properties {
     /// The place of domicile of an organization.
     ///
@@ -499,6 +816,18 @@ impl  Properties {
         })
     }
 
+    fn to_mapping(&self, store: &DataStore) -> MappingBuilder {
+        let mut res = MappingBuilder::new();
+        res.attr("domicile", &self.domicile, store)
+            .attr("name", &self.name, store)
+            .attr("owner", &self.owner, store)
+            .attr("short_name", &self.short_name, store)
+            .attr("status", &self.status, store)
+            .attr("successor", &self.successor, store)
+            .attr("superior", &self.superior, store);
+        res
+    }
+
     fn merge(&mut self, other: &Self) {
         if let Some(domicile) = other.domicile.as_ref() {
             self.domicile = Some(domicile.clone())
@@ -528,6 +857,7 @@ impl  Properties {
 //------------ Property ------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Property {
     pub role: Marked<PropertyRole>,
     pub region: List<Marked<entity::Link>>,
@@ -559,6 +889,18 @@ impl FromYaml<StoreLoader> for Property {
     }
 }
 
+impl ToYaml for Property {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = MappingBuilder::new();
+        res.attr("role", &self.role, store)
+            .attr("region", &self.region, store)
+            .attr("constructor", &self.constructor, store)
+            .attr("owner", &self.owner, store)
+            .attr("operator", &self.operator, store);
+        res.into_yaml()
+    }
+}
+
 
 //------------ PropertyRole --------------------------------------------------
 
@@ -581,3 +923,42 @@ data_enum! {
     }
 }
 
+
+//------------ Schema --------------------------------------------------------
+
+pub fn schema(schema: &mut Schema) {
+    schema.document("entity")
+        .include("common")
+        .mandatory("subtype", "enum(entity.subtype)")
+        .mandatory("events", "list(object(entity.event))");
+    schema.object("entity.event")
+        .optional("date", "date")
+        .optional("records", "list(object(entity.record))")
+        .include("entity.record");
+    schema.object("entity.record")
+        .optional("date", "date")
+        .optional("document", "list(key(source))")
+        .optional("source", "list(key(source))")
+        .optional("basis", "list(object(basis))")
+        .optional("note", "text")
+        .optional("property", "object(entity.property)")
+        .include("entity.properties");
+    schema.object("entity.properties")
+        .optional("domicile", "list(key(entity))")
+        .optional("name", "text")
+        .optional("owner", "list(key(entity))")
+        .optional("short_name", "text")
+        .optional("status", "enum(entity.status)")
+        .optional("successor", "key(entity)")
+        .optional("superior", "list(key(entity))");
+    schema.object("entity.property")
+        .mandatory("role", "enum(entity.property_role)")
+        .optional("region", "list(key(entity))")
+        .optional("constructor", "list(key(entity))")
+        .optional("owner", "list(key(entity))")
+        .optional("operator", "list(key(entity))");
+
+    schema.enumeration("entity.property_role", PropertyRole::VARIANTS);
+    schema.enumeration("entity.status", Status::VARIANTS);
+    schema.enumeration("entity.subtype", Subtype::VARIANTS);
+}
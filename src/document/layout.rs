@@ -0,0 +1,336 @@
+//! Track layouts of points.
+//!
+//! The layout of a station or other point describes its tracks, the
+//! platforms serving them, and the switches connecting them. It is given
+//! in the `layout` attribute of a point document as a compact text with
+//! one item per line:
+//!
+//! ```yaml
+//! layout: |
+//!   track 1
+//!   track 2
+//!   track 3 siding
+//!   platform A 1 2
+//!   switch W1 1 2
+//!   switch W2 2 3
+//! ```
+//!
+//! Each line starts with the kind of item followed by its name:
+//!
+//! * `track <name> [<kind>]` defines a track, where the kind is one of
+//!   `main`, `passing`, `siding`, or `yard` and defaults to `main`,
+//! * `platform <name> <track>...` defines a platform serving one or more
+//!   tracks, and
+//! * `switch <name> <track> <track>` defines a switch connecting two
+//!   tracks.
+//!
+//! Tracks have to be defined before they are referenced. Names have to be
+//! unique among items of the same kind. Empty lines and lines starting
+//! with `#` are ignored.
+
+use std::fmt;
+use derive_more::Display;
+use serde_json::{json, Value as JsonValue};
+use yaml_rust::Yaml;
+use crate::load::report::{Failed, PathReporter};
+use crate::load::yaml::{FromYaml, Value};
+use crate::store::DataStore;
+use crate::store::write::{ToYaml, display_to_yaml};
+use crate::types::IntoMarked;
+
+
+//------------ Layout --------------------------------------------------------
+
+/// The track layout of a point.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Layout {
+    /// The tracks in the order they were defined.
+    pub tracks: Vec<Track>,
+
+    /// The platforms.
+    pub platforms: Vec<Platform>,
+
+    /// The switches.
+    pub switches: Vec<Switch>,
+}
+
+impl Layout {
+    /// Parses a layout from its text.
+    ///
+    /// Returns all problems found together with the number of the line
+    /// they were found in, starting with 1.
+    pub fn parse(text: &str) -> Result<Self, Vec<(usize, LayoutError)>> {
+        let mut res = Layout::default();
+        let mut errors = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            if let Err(err) = res.parse_line(line) {
+                errors.push((idx + 1, err))
+            }
+        }
+        if errors.is_empty() {
+            Ok(res)
+        }
+        else {
+            Err(errors)
+        }
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), LayoutError> {
+        let mut words = line.split_whitespace();
+        let kind = words.next().unwrap_or_default();
+        let name = match words.next() {
+            Some(name) => String::from(name),
+            None => return Err(LayoutError::MissingName)
+        };
+        match kind {
+            "track" => {
+                if self.track(&name).is_some() {
+                    return Err(LayoutError::DuplicateName(name))
+                }
+                let kind = match words.next() {
+                    Some(kind) => {
+                        TrackKind::ALL.iter().copied().find(|item| {
+                            item.as_str() == kind
+                        }).ok_or_else(|| {
+                            LayoutError::InvalidTrackKind(kind.into())
+                        })?
+                    }
+                    None => TrackKind::default()
+                };
+                self.check_end(words)?;
+                self.tracks.push(Track { name, kind });
+            }
+            "platform" => {
+                if self.platforms.iter().any(|item| item.name == name) {
+                    return Err(LayoutError::DuplicateName(name))
+                }
+                let tracks = self.track_list(words)?;
+                if tracks.is_empty() {
+                    return Err(LayoutError::MissingTrack)
+                }
+                self.platforms.push(Platform { name, tracks });
+            }
+            "switch" => {
+                if self.switches.iter().any(|item| item.name == name) {
+                    return Err(LayoutError::DuplicateName(name))
+                }
+                let tracks = self.track_list(words)?;
+                let (left, right) = match tracks.as_slice() {
+                    [left, right] if left != right => (*left, *right),
+                    _ => return Err(LayoutError::InvalidSwitch)
+                };
+                self.switches.push(Switch { name, tracks: (left, right) });
+            }
+            _ => return Err(LayoutError::UnknownItem(kind.into()))
+        }
+        Ok(())
+    }
+
+    /// Resolves a list of track names into track indexes.
+    fn track_list<'s>(
+        &self, words: impl Iterator<Item = &'s str>
+    ) -> Result<Vec<usize>, LayoutError> {
+        words.map(|word| {
+            self.track(word).ok_or_else(|| {
+                LayoutError::UnknownTrack(word.into())
+            })
+        }).collect()
+    }
+
+    fn check_end<'s>(
+        &self, mut words: impl Iterator<Item = &'s str>
+    ) -> Result<(), LayoutError> {
+        match words.next() {
+            Some(word) => Err(LayoutError::TrailingText(word.into())),
+            None => Ok(())
+        }
+    }
+
+    /// Returns the index of the track with the given name.
+    pub fn track(&self, name: &str) -> Option<usize> {
+        self.tracks.iter().position(|track| track.name == name)
+    }
+
+    /// Returns the indexes of the tracks a platform serves.
+    pub fn platform_tracks(&self, name: &str) -> Option<&[usize]> {
+        self.platforms.iter().find(|platform| {
+            platform.name == name
+        }).map(|platform| platform.tracks.as_slice())
+    }
+
+    /// Returns the layout as JSON.
+    ///
+    /// Platforms and switches refer to tracks by their name.
+    pub fn to_json(&self) -> JsonValue {
+        let track_name = |idx: usize| self.tracks[idx].name.as_str();
+        json!({
+            "tracks": self.tracks.iter().map(|track| {
+                json!({
+                    "name": track.name,
+                    "kind": track.kind.as_str(),
+                })
+            }).collect::<Vec<_>>(),
+            "platforms": self.platforms.iter().map(|platform| {
+                json!({
+                    "name": platform.name,
+                    "tracks": platform.tracks.iter().map(|idx| {
+                        track_name(*idx)
+                    }).collect::<Vec<_>>(),
+                })
+            }).collect::<Vec<_>>(),
+            "switches": self.switches.iter().map(|switch| {
+                json!({
+                    "name": switch.name,
+                    "tracks": [
+                        track_name(switch.tracks.0),
+                        track_name(switch.tracks.1),
+                    ],
+                })
+            }).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl<C> FromYaml<C> for Layout {
+    fn from_yaml(
+        value: Value,
+        _: &C,
+        report: &mut PathReporter
+    ) -> Result<Self, Failed> {
+        let (text, location) = value.into_string(report)?.unwrap();
+        Layout::parse(&text).map_err(|errors| {
+            for (line, error) in errors {
+                report.error(InvalidLayout { line, error }.marked(location))
+            }
+            Failed
+        })
+    }
+}
+
+impl ToYaml for Layout {
+    fn to_yaml(&self, _store: &DataStore) -> Yaml {
+        display_to_yaml(self)
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for track in &self.tracks {
+            if track.kind == TrackKind::default() {
+                writeln!(f, "track {}", track.name)?;
+            }
+            else {
+                writeln!(f, "track {} {}", track.name, track.kind)?;
+            }
+        }
+        for platform in &self.platforms {
+            write!(f, "platform {}", platform.name)?;
+            for idx in &platform.tracks {
+                write!(f, " {}", self.tracks[*idx].name)?;
+            }
+            writeln!(f)?;
+        }
+        for switch in &self.switches {
+            writeln!(
+                f, "switch {} {} {}",
+                switch.name,
+                self.tracks[switch.tracks.0].name,
+                self.tracks[switch.tracks.1].name,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ Track ---------------------------------------------------------
+
+/// A track of a layout.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Track {
+    pub name: String,
+    pub kind: TrackKind,
+}
+
+
+//------------ TrackKind -----------------------------------------------------
+
+data_enum! {
+    pub enum TrackKind {
+        { Main: "main" }
+        { Passing: "passing" }
+        { Siding: "siding" }
+        { Yard: "yard" }
+
+        default Main
+    }
+}
+
+
+//------------ Platform ------------------------------------------------------
+
+/// A platform of a layout.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Platform {
+    pub name: String,
+
+    /// The indexes of the tracks served by the platform.
+    pub tracks: Vec<usize>,
+}
+
+
+//------------ Switch --------------------------------------------------------
+
+/// A switch connecting two tracks of a layout.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Switch {
+    pub name: String,
+
+    /// The indexes of the two tracks connected by the switch.
+    pub tracks: (usize, usize),
+}
+
+
+//============ Errors ========================================================
+
+#[derive(Clone, Debug, Display)]
+pub enum LayoutError {
+    #[display(fmt="missing name")]
+    MissingName,
+
+    #[display(fmt="unknown item '{}'", _0)]
+    UnknownItem(String),
+
+    #[display(fmt="duplicate name '{}'", _0)]
+    DuplicateName(String),
+
+    #[display(fmt="invalid track kind '{}'", _0)]
+    InvalidTrackKind(String),
+
+    #[display(fmt="unknown track '{}'", _0)]
+    UnknownTrack(String),
+
+    #[display(fmt="platform without tracks")]
+    MissingTrack,
+
+    #[display(fmt="switch needs two different tracks")]
+    InvalidSwitch,
+
+    #[display(fmt="unexpected '{}'", _0)]
+    TrailingText(String),
+}
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="layout line {}: {}", line, error)]
+pub struct InvalidLayout {
+    line: usize,
+    error: LayoutError,
+}
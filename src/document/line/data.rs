@@ -1,26 +1,42 @@
 
 use std::{cmp, fmt, ops};
+use std::hash::Hash;
 use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
+use std::sync::Arc;
 use derive_more::Display;
-use crate::catalogue::CatalogueBuilder;
+use serde_json::{json, Value as JsonValue};
+use yaml_rust::Yaml;
+use crate::catalogue::{
+    CatalogueBuilder, EventKind, Operation, OperationRole,
+};
+use crate::citation::Provenance;
 use crate::load::report::{Failed, Origin, PathReporter};
 use crate::load::yaml::{FromYaml, Mapping, Value};
-use crate::store::{FullStore, StoreLoader, XrefsBuilder};
+use crate::schema::Schema;
+use crate::store::{
+    DataStore, FullStore, LinkTarget, StoreLoader, XrefsBuilder,
+};
+use crate::store::write::{MappingBuilder, ToYaml, display_to_yaml};
 use crate::types::list;
 use crate::types::{
-    CountryCode, Date, EventDate, IntoMarked, Key, LanguageCode, LanguageText,
-    List, LocalText, Location, Marked, Set
+    CountryCode, Date, EventDate, IntoMarked, Key, LanguageCode,
+    LanguagePreference, LanguageText, List, LocalText, Location, Marked,
+    OperatingPeriods, Set, Text,
 };
-use crate::document::{entity, point};
+use crate::types::period::EndReason;
+use crate::document::{combined, entity, local, point};
 use crate::document::combined::{
     DocumentLink, LineLink, EntityLink, PathLink, PointLink,
-    SourceLink
+    SourceLink, StructureLink,
 };
 use crate::document::common::{
-    Agreement, AgreementType, Basis, Common, Contract, Progress
+    Agreement, AgreementType, Basis, Common, Contract, DocumentType, Progress
 };
+use super::electrification::ElRegistry;
+use super::meta::{ConcessionPeriod, JunctionSegment};
+use super::profile::Profile;
 
 
 //------------ Document ------------------------------------------------------
@@ -36,6 +52,11 @@ impl<'a> Document<'a> {
         })
     }
 
+    /// Returns the parts of the line between consecutive junctions.
+    pub fn junction_segments(self) -> &'a [JunctionSegment] {
+        &self.meta().junction_segments
+    }
+
     pub fn first_junction_name(
         self, store: &'a FullStore, _lang: LanguageCode,
     ) -> &'a str {
@@ -64,12 +85,69 @@ impl<'a> Document<'a> {
         }
         None
     }
+
+    /// Returns the structures along the line.
+    ///
+    /// Structures named by the line itself come with the section they
+    /// cover. Structures that only name the line themselves don’t have a
+    /// section. Each structure is only included once.
+    pub fn structures(self) -> Vec<(StructureLink, Option<Section>)> {
+        let data = self.data();
+        let mut res = Vec::<(StructureLink, Option<Section>)>::new();
+        let mut add = |structure: StructureLink, section: Option<Section>| {
+            match res.iter_mut().find(|item| item.0 == structure) {
+                Some(item) => {
+                    if let Some(section) = section {
+                        match item.1 {
+                            Some(ref mut current) => current.grow(&section),
+                            None => item.1 = Some(section),
+                        }
+                    }
+                }
+                None => res.push((structure, section))
+            }
+        };
+        for (section, structures) in data.current.structures.as_slice() {
+            for structure in structures.iter().flatten() {
+                add(structure.into_value(), Some(section.clone()))
+            }
+        }
+        for event in &data.events {
+            if let Some(structures) = event.structures() {
+                let section = event.sections.overall(data.points.len());
+                for structure in structures {
+                    add(structure.into_value(), Some(section.clone()))
+                }
+            }
+        }
+        for structure in self.xrefs().structures.iter() {
+            add(*structure, None)
+        }
+        res
+    }
+
+    /// Returns the title in the most preferred language available.
+    ///
+    /// Falls back to the first name given if none of the languages is
+    /// present.
+    pub fn preferred_title(
+        self, preference: &LanguagePreference
+    ) -> Option<&'a str> {
+        preference.iter().find_map(|lang| self.title(lang)).or_else(|| {
+            self.data().events.iter().flat_map(|event| {
+                event.records.iter()
+            }).find_map(|record| {
+                record.properties.name.as_ref().map(|name| name.first())
+            })
+        })
+    }
 }
 
 
 //------------ Data ----------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Data {
     link: LineLink,
 
@@ -123,6 +201,205 @@ impl Data {
         &self.code
     }
 
+    /// Returns the current name in the most preferred language available.
+    ///
+    /// The name given in the current values takes precedence over names
+    /// set by events. Falls back to the line code if the line doesn’t have
+    /// a name.
+    pub fn preferred_name(&self, preference: &LanguagePreference) -> &str {
+        self.current.name.iter().find_map(|(_, name)| {
+            name.as_ref()
+        }).or_else(|| {
+            self.events.iter().rev().find_map(|event| event.name())
+        }).map(|name| {
+            name.for_preference(preference)
+        }).unwrap_or_else(|| self.code.as_str())
+    }
+
+    /// Returns the classes of the line’s current gauges.
+    ///
+    /// A line without gauge information is considered standard gauge.
+    pub fn gauge_classes(&self) -> HashSet<GaugeClass> {
+        let mut res: HashSet<_> = self.current.gauge.iter().flat_map(
+            |(_, gauges)| gauges.iter().map(Gauge::class)
+        ).collect();
+        if res.is_empty() {
+            res.insert(GaugeClass::Standard);
+        }
+        res
+    }
+
+    /// Returns the length of the line’s current course in kilometres.
+    ///
+    /// Course segments that cannot be resolved are ignored, so a line
+    /// without course information has a length of zero.
+    pub fn course_length(
+        &self, store: &impl LinkTarget<combined::Data>
+    ) -> f64 {
+        self.current.course.iter().flat_map(|(_, course)| {
+            course.iter()
+        }).filter_map(|segment| segment.length(store)).sum()
+    }
+
+    /// Returns the elevation profile along the line’s current course.
+    pub fn profile(
+        &self, store: &impl LinkTarget<combined::Data>
+    ) -> Profile {
+        Profile::new(self, store)
+    }
+
+    /// Returns the periods during which some part of the line was open.
+    ///
+    /// The status of each section between two points is tracked through
+    /// the events. The line is considered in operation as long as at least
+    /// one section is open or reopened.
+    pub fn operating_periods(&self) -> OperatingPeriods {
+        let len = self.points.len();
+        let mut segments = vec![false; len.saturating_sub(1).max(1)];
+        let mut res = OperatingPeriods::default();
+        for event in self.events.iter() {
+            let status = match event.status() {
+                Some(status) => status,
+                None => continue
+            };
+            let operating = status.is_operating();
+            if len > 1 {
+                for section in event.sections.covered(len) {
+                    let end = cmp::min(section.end_idx, segments.len());
+                    for item in &mut segments[section.start_idx..end] {
+                        *item = operating
+                    }
+                }
+            }
+            else {
+                segments[0] = operating
+            }
+            let date = event.date.iter().next().map(Marked::into_value);
+            if segments.iter().any(|item| *item) {
+                res.open(date)
+            }
+            else {
+                res.close(date, status.end_reason())
+            }
+        }
+        res
+    }
+
+    /// Returns the concessions granted by the events.
+    pub fn concession_periods(&self) -> Vec<ConcessionPeriod> {
+        self.events.iter().enumerate().filter_map(|(idx, event)| {
+            let concession = event.concession()?;
+            Some(ConcessionPeriod {
+                event: idx,
+                start: event.date.iter().next(),
+                until: concession.until,
+            })
+        }).collect()
+    }
+
+    /// Returns the periods during which entities held a role on the line.
+    ///
+    /// The role is taken from the events via `role`, which should return
+    /// the operator or owner of an event. Like with operating periods,
+    /// each section between two points is tracked separately and an entity
+    /// holds the role as long as it does so on at least one section.
+    pub fn role_periods(
+        &self,
+        role: impl Fn(&Event) -> Option<&List<Marked<EntityLink>>>,
+    ) -> HashMap<EntityLink, OperatingPeriods> {
+        self.value_periods(|event| {
+            role(event).map(|entities| {
+                entities.iter().map(|link| link.into_value()).collect()
+            })
+        })
+    }
+
+    /// Returns the periods during which the line had its national codes.
+    ///
+    /// The codes are taken from the events. A code given in the current
+    /// values that never appears in an event is assumed to have been
+    /// assigned at an unknown date.
+    pub fn code_periods(&self) -> Vec<CodeAssignment> {
+        let mut res = Vec::new();
+        for code_type in CodeType::ALL.iter().copied() {
+            let mut periods = self.value_periods(|event| {
+                event.code(code_type).map(|code| {
+                    HashSet::from([code.as_value().clone()])
+                })
+            });
+            for (current_type, code, _) in self.current.codes() {
+                if current_type == code_type {
+                    periods.entry(code.into()).or_insert_with(|| {
+                        let mut res = OperatingPeriods::default();
+                        res.open(None);
+                        res
+                    });
+                }
+            }
+            res.extend(periods.into_iter().map(|(code, periods)| {
+                CodeAssignment { code_type, code, periods }
+            }));
+        }
+        res.sort_by(|left, right| {
+            left.code_type.cmp(&right.code_type).then_with(|| {
+                left.code.cmp(&right.code)
+            })
+        });
+        res
+    }
+
+    /// Returns the national codes the line may have had at `date`.
+    pub fn codes_at(&self, date: &Date) -> Vec<(CodeType, Text)> {
+        self.code_periods().into_iter().filter(|item| {
+            item.periods.may_include(date)
+        }).map(|item| (item.code_type, item.code)).collect()
+    }
+
+    /// Returns the periods during which the line had certain values.
+    ///
+    /// The values of an event are returned by `values` or `None` if the
+    /// event doesn’t change them. Each section between two points is
+    /// tracked separately and a value is present as long as it is present
+    /// on at least one section.
+    fn value_periods<T: Clone + Eq + Hash>(
+        &self,
+        values: impl Fn(&Event) -> Option<HashSet<T>>,
+    ) -> HashMap<T, OperatingPeriods> {
+        let len = self.points.len();
+        let mut segments = vec![
+            HashSet::new(); len.saturating_sub(1).max(1)
+        ];
+        let mut res = HashMap::<_, OperatingPeriods>::new();
+        for event in self.events.iter() {
+            let values = match values(event) {
+                Some(values) => values,
+                None => continue
+            };
+            if len > 1 {
+                for section in event.sections.covered(len) {
+                    let end = cmp::min(section.end_idx, segments.len());
+                    for item in &mut segments[section.start_idx..end] {
+                        item.clone_from(&values)
+                    }
+                }
+            }
+            else {
+                segments[0] = values
+            }
+            let date = event.date.iter().next().map(Marked::into_value);
+            let current: HashSet<_> = segments.iter().flatten().collect();
+            for (value, periods) in &mut res {
+                if !current.contains(value) {
+                    periods.close(date, EndReason::Closed)
+                }
+            }
+            for value in current {
+                res.entry(value.clone()).or_default().open(date)
+            }
+        }
+        res
+    }
+
     pub fn current_status_at(&self, point: PointLink) -> Option<Status> {
         self.points.index_of(point).and_then(|idx| {
             match self.current.status.at_index(idx)? {
@@ -131,9 +408,143 @@ impl Data {
             }
         })
     }
+
+    /// Returns the section of the line identified by `id`.
+    ///
+    /// Returns `None` if either point isn’t listed on the line or the end
+    /// point doesn’t come after the start point.
+    pub fn section(
+        &self, id: &SectionId, store: &impl LinkTarget<combined::Data>
+    ) -> Option<Section> {
+        let find = |key: &Key| {
+            self.points.iter().position(|link| {
+                link.data(store).key() == key
+            })
+        };
+        let start_idx = find(id.start())?;
+        let end_idx = find(id.end())?;
+        if start_idx >= end_idx {
+            return None
+        }
+        Some(Section::new(
+            (start_idx > 0).then(|| self.points[start_idx]),
+            (end_idx < self.points.len() - 1).then(|| self.points[end_idx]),
+            start_idx, end_idx
+        ))
+    }
+
+    /// Returns the events that affect some part of a section.
+    pub fn events_for_section<'s>(
+        &'s self, section: &'s Section
+    ) -> impl Iterator<Item = &'s Event> + 's {
+        let len = self.points.len();
+        self.events.iter().filter(move |event| {
+            event.sections.covered(len).iter().any(|item| {
+                item.overlaps(section)
+            })
+        })
+    }
+
+    /// Returns the records that affect some part of a section.
+    pub fn records_for_section<'s>(
+        &'s self, section: &'s Section
+    ) -> impl Iterator<Item = (SourceLink, &'s Record)> + 's {
+        let len = self.points.len();
+        self.records.documents().flat_map(|(link, records)| {
+            records.iter().map(move |record| (link, record))
+        }).filter(move |(_, record)| {
+            record.sections.covered(len).iter().any(|item| {
+                item.overlaps(section)
+            })
+        })
+    }
+
+    /// Returns the lines this line directly reuses.
+    ///
+    /// These are the lines given in the current `reused` value and in the
+    /// events. Each line is only included once.
+    pub fn reused(&self) -> Vec<Marked<LineLink>> {
+        let current = self.current.reused.iter().flat_map(|(_, list)| {
+            list.iter().flatten()
+        });
+        let events = self.events.iter().flat_map(|event| {
+            event.records.iter()
+        }).filter_map(|record| {
+            record.properties.reused.as_ref()
+        }).flatten();
+        let mut res = Vec::<Marked<LineLink>>::new();
+        for link in current.chain(events) {
+            if !res.iter().any(|item| item.as_value() == link.as_value()) {
+                res.push(*link)
+            }
+        }
+        res
+    }
+
+    /// Returns all lines this line reuses directly or indirectly.
+    ///
+    /// The lines are given in the order they are reached when following
+    /// the `reused` links, i.e., the direct predecessors come first. Each
+    /// line is only included once and the line itself is never included,
+    /// so cycles in the reuse links don’t lead to endless loops.
+    pub fn reuse_chain(
+        &self, store: &impl LinkTarget<combined::Data>
+    ) -> Vec<LineLink> {
+        self.walk_reuse(store).0
+    }
+
+    /// Follows the reuse links.
+    ///
+    /// Returns the reused lines and whether the line can be reached from
+    /// itself.
+    fn walk_reuse(
+        &self, store: &impl LinkTarget<combined::Data>
+    ) -> (Vec<LineLink>, bool) {
+        let mut res = Vec::new();
+        let mut cycle = false;
+        let mut seen = HashSet::from([self.link]);
+        let mut next = 0;
+        let mut current = self.reused();
+        loop {
+            for link in current {
+                let link = link.into_value();
+                if link == self.link {
+                    cycle = true
+                }
+                else if seen.insert(link) {
+                    res.push(link)
+                }
+            }
+            match res.get(next) {
+                Some(link) => {
+                    current = link.data(store).reused();
+                    next += 1;
+                }
+                None => break
+            }
+        }
+        (res, cycle)
+    }
 }
 
 impl Data {
+    /// Creates a placeholder for a document that failed to load.
+    pub fn placeholder(
+        key: Marked<Key>, link: DocumentLink, origin: Origin
+    ) -> Self {
+        Data {
+            link: link.into(),
+            code: LineCode::from_key(key.as_value()),
+            common: Common::placeholder(key, origin),
+            label: Set::default(),
+            note: None,
+            current: Current::default(),
+            events: EventList::default(),
+            records: RecordList::default(),
+            points: Points { points: Vec::new() },
+        }
+    }
+
     pub fn from_yaml(
         key: Marked<Key>,
         mut doc: Mapping,
@@ -169,8 +580,8 @@ impl Data {
     pub fn xrefs(
         &self, 
         builder: &mut XrefsBuilder,
-        _store: &crate::store::DataStore,
-        _report: &mut crate::load::report::PathReporter,
+        store: &crate::store::DataStore,
+        report: &mut crate::load::report::PathReporter,
     ) -> Result<(), Failed> {
         // points: line points
         for point in self.points.iter() {
@@ -204,17 +615,225 @@ impl Data {
         for (line, section) in regions {
             line.xrefs_mut(builder).line_regions.push((self.link, section));
         }
-        Ok(())
+
+        // structure: lines through or across the structure with the
+        // section they span.
+        let mut structures = HashMap::<StructureLink, Section>::new();
+        for section in &self.current.structures.sections {
+            for structure in section.1.iter().flatten() {
+                structures.entry(
+                    structure.into_value()
+                ).and_modify(|current| {
+                    current.grow(&section.0)
+                }).or_insert(section.0.clone());
+            }
+        }
+        for event in &self.events {
+            if let Some(structure_list) = event.structures() {
+                let new_section = event.sections.overall(self.points.len());
+                for structure in structure_list {
+                    structures.entry(
+                        structure.into_value()
+                    ).and_modify(|section| {
+                        section.grow(&new_section)
+                    }).or_insert(new_section.clone());
+                }
+            }
+        }
+        for (structure, section) in structures {
+            structure.xrefs_mut(builder).lines.push((self.link, section));
+        }
+
+        // entity: parties of agreements.
+        for event in &self.events {
+            for record in &event.records {
+                let date = record.date.as_ref().unwrap_or(&event.date);
+                let basis = record.basis.iter().flatten().filter_map(|basis| {
+                    let date = if basis.date.is_empty() { date }
+                               else { &basis.date };
+                    basis.agreement.as_ref().map(|agreement| {
+                        (agreement, date)
+                    })
+                });
+                let agreements = record.agreement.iter().map(|agreement| {
+                    (agreement, date)
+                }).chain(basis);
+                for (agreement, date) in agreements {
+                    for party in &agreement.parties {
+                        let party = party.into_value();
+                        let roles = record.properties.roles_of(party);
+                        party.xrefs_mut(builder).agreements.push(
+                            entity::AgreementParty {
+                                document: self.link.into(),
+                                date: date.clone(),
+                                agreement_type: agreement.agreement_type,
+                                roles,
+                            }
+                        );
+                    }
+                }
+            }
+        }
+
+        // line: the lines reused by this line and, the other way round,
+        // the lines reusing it.
+        for reused in self.reused() {
+            if reused.into_value() == self.link {
+                continue
+            }
+            reused.xrefs_mut(builder).reused_by.insert(self.link);
+            self.link.xrefs_mut(builder).reuses.insert(reused.into_value());
+        }
+        if self.walk_reuse(store).1 {
+            report.warning(
+                ReuseCycle.marked(
+                    self.reused().first().map(|link| {
+                        link.location()
+                    }).unwrap_or(Location::NONE)
+                )
+            )
+        }
+
+        // path: all course segments need to refer to existing nodes.
+        let current = self.current.course.iter().flat_map(|(_, course)| {
+            course.iter()
+        });
+        let events = self.events.iter().flat_map(|event| {
+            event.records.iter()
+        }).filter_map(|record| record.properties.course.as_ref());
+        let records = self.records.documents().flat_map(|(_, records)| {
+            records.iter()
+        }).filter_map(|record| record.properties.course.as_ref());
+        let mut res = Ok(());
+        for segment in current.chain(events.chain(records).flatten()) {
+            if segment.check_nodes(store, report).is_err() {
+                res = Err(Failed)
+            }
+        }
+        res
+    }
+
+    /// Checks the national line codes against the rules of their country.
+    pub fn check_codes(&self, report: &mut PathReporter) {
+        let current = [
+            (CodeType::AtVzg, &self.current.at_vzg),
+            (CodeType::ChBav, &self.current.ch_bav),
+            (CodeType::DeVzg, &self.current.de_vzg),
+            (CodeType::FrRfn, &self.current.fr_rfn),
+        ];
+        for (code_type, value) in current {
+            for (_, code) in value.iter() {
+                if let Some(code) = code {
+                    local::check_line_code(code_type.as_str(), code, report)
+                }
+            }
+        }
+        let events = self.events.iter().flat_map(|event| {
+            event.records.iter().map(|record| &record.properties)
+        });
+        let records = self.records.documents().flat_map(|(_, records)| {
+            records.iter().map(|record| &record.properties)
+        });
+        for properties in events.chain(records) {
+            for &code_type in CodeType::ALL {
+                if let Some(code) = properties.code(code_type) {
+                    local::check_line_code(code_type.as_str(), code, report)
+                }
+            }
+        }
+    }
+
+    /// Checks that the kilometrage of the points is monotonic.
+    ///
+    /// Points without a location on the line or with a location that isn’t
+    /// a plain number are skipped. The direction of the kilometrage is
+    /// taken from the first and last point that have one.
+    pub fn check_kilometrage(
+        &self, store: &DataStore, report: &mut PathReporter
+    ) {
+        let km = self.points.iter().filter_map(|point| {
+            let location = point.data(store).marked_line_location(
+                self.link
+            )?;
+            Some((point, location, parse_kilometrage(location)?))
+        }).collect::<Vec<_>>();
+        let direction = match (km.first(), km.last()) {
+            (Some(first), Some(last)) => last.2 - first.2,
+            _ => return
+        };
+        for pair in km.windows(2) {
+            let ((_, prev, prev_km), (point, location, km)) = (
+                &pair[0], &pair[1]
+            );
+            if (km - prev_km) * direction < 0. {
+                report.warning(
+                    KilometrageBackwards {
+                        point: point.data(store).key().clone(),
+                        km: location.as_value().to_string(),
+                        prev: prev.as_value().to_string(),
+                    }.marked(location.location())
+                )
+            }
+        }
     }
 
     pub fn catalogue(
         &self,
         builder: &mut CatalogueBuilder,
-        _store: &FullStore,
-        _report: &mut PathReporter,
+        store: &FullStore,
+        report: &mut PathReporter,
     ) -> Result<(), Failed> {
+        let mut res = Ok(());
+
         // Insert line.
         builder.catalogue_mut().lines.push(self.link);
+        for class in self.gauge_classes() {
+            builder.insert_gauge_class(class, self.link);
+        }
+
+        //--- Insert operations.
+        for (role, periods) in [
+            (OperationRole::Operator, self.role_periods(Event::operator)),
+            (OperationRole::Owner, self.role_periods(Event::owner)),
+        ] {
+            for (entity, periods) in periods {
+                builder.insert_operation(entity, Operation {
+                    line: self.link, role, periods
+                });
+            }
+        }
+
+        //--- Insert national line codes.
+        for (code_type, code, location) in self.current.codes() {
+            if builder.insert_line_code(
+                code_type, code, location, self.link, store, report
+            ).is_err() {
+                res = Err(Failed)
+            }
+        }
+        // A duplicate current code would also be reported as overlapping.
+        if res.is_ok() {
+            for assignment in self.code_periods() {
+                if builder.insert_code_assignment(
+                    assignment, self.link, store, report
+                ).is_err() {
+                    res = Err(Failed)
+                }
+            }
+        }
+
+        //--- Insert events.
+        for (idx, event) in self.events.iter().enumerate() {
+            let kind = match event.status() {
+                Some(status) if status.is_operating() => EventKind::Opening,
+                Some(
+                    Status::Suspended | Status::Closed | Status::Removed
+                    | Status::Released
+                ) => EventKind::Closure,
+                _ => EventKind::Other,
+            };
+            builder.insert_event(&event.date, self.link.into(), idx, kind);
+        }
 
         //--- Insert names.
         builder.insert_name(self.key().as_str().into(), self.link.into());
@@ -229,10 +848,25 @@ impl Data {
             }
         }
         for name in names {
-            builder.insert_name(name.into(), self.link.into());
+            builder.insert_name(name.clone(), self.link.into());
         }
 
-        Ok(())
+        res
+    }
+}
+
+impl ToYaml for Data {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = self.common.to_yaml(DocumentType::Line, store);
+        res.attr("label", &self.label, store)
+            .attr("note", &self.note, store)
+            .insert("points", self.points.to_yaml(store));
+        if let Some(current) = self.current.to_yaml(&self.points, store) {
+            res.insert("current", current);
+        }
+        res.attr("events", &self.events, store)
+            .attr("records", &self.records, store);
+        res.into_yaml()
     }
 }
 
@@ -240,6 +874,7 @@ impl Data {
 //------------ LineCode ------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LineCode {
     code: String,
     region_end: usize,
@@ -336,7 +971,7 @@ impl Ord for LineCode {
 data_enum! {
     pub enum Label {
         { Connection: "connection" }
-        { Goods: "goods" }
+        { Goods: "goods", deprecated "freight" }
         { Port: "port" }
         { DeSBahn: "de.S-Bahn" }
     }
@@ -346,11 +981,20 @@ data_enum! {
 //------------ Points --------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Points {
     points: Vec<Marked<PointLink>>,
 }
 
 impl Points {
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
     pub fn iter_documents<'s>(
         &'s self, store: &'s FullStore
     ) -> impl Iterator<Item = point::Document<'s>> + DoubleEndedIterator + 's {
@@ -432,6 +1076,12 @@ impl FromYaml<StoreLoader> for Points {
     }
 }
 
+impl ToYaml for Points {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        self.points.to_yaml(store)
+    }
+}
+
 impl ops::Deref for Points {
     type Target = [Marked<PointLink>];
 
@@ -476,6 +1126,7 @@ impl<'a> ops::Deref for PointsContext<'a> {
 //------------ Current -------------------------------------------------------
 
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Current {
     pub category: CurrentValue<Set<Category>>,
     pub course: CurrentValue<List<CourseSegment>>,
@@ -491,10 +1142,11 @@ pub struct Current {
     pub region: CurrentValue<List<Marked<EntityLink>>>,
     pub reused: CurrentValue<Option<List<Marked<LineLink>>>>,
     pub status: CurrentValue<Status>,
+    pub structures: CurrentValue<Option<List<Marked<StructureLink>>>>,
     pub tracks: CurrentValue<Marked<u8>>,
 
     pub at_vzg: CurrentValue<Option<AtVzg>>,
-    pub ch_bav: CurrentValue<Option<String>>,
+    pub ch_bav: CurrentValue<Option<ChBav>>,
     pub cz_sr72: CurrentValue<Option<String>>,
     pub de_vzg: CurrentValue<Option<DeVzg>>,
     pub fr_rfn: CurrentValue<Option<FrRfn>>,
@@ -505,6 +1157,113 @@ pub struct Current {
 }
 
 impl Current {
+    /// Converts the current values into YAML.
+    ///
+    /// Returns `None` if there are no current values at all.
+    fn to_yaml(&self, points: &Points, store: &DataStore) -> Option<Yaml> {
+        let mut res = MappingBuilder::new();
+        self.category.add_to("category", &mut res, points, store);
+        self.course.add_to("course", &mut res, points, store);
+        self.electrified.add_to("electrified", &mut res, points, store);
+        self.gauge.add_to("gauge", &mut res, points, store);
+        self.goods.add_to("goods", &mut res, points, store);
+        self.jurisdiction.add_to("jurisdiction", &mut res, points, store);
+        self.name.add_to("name", &mut res, points, store);
+        self.operator.add_to("operator", &mut res, points, store);
+        self.owner.add_to("owner", &mut res, points, store);
+        self.passenger.add_to("passenger", &mut res, points, store);
+        self.rails.add_to("rails", &mut res, points, store);
+        self.region.add_to("region", &mut res, points, store);
+        self.reused.add_to("reused", &mut res, points, store);
+        self.status.add_to("status", &mut res, points, store);
+        self.structures.add_to("structures", &mut res, points, store);
+        self.tracks.add_to("tracks", &mut res, points, store);
+
+        self.at_vzg.add_to("at.VzG", &mut res, points, store);
+        self.ch_bav.add_to("ch.BAV", &mut res, points, store);
+        self.cz_sr72.add_to("cz.SR72", &mut res, points, store);
+        self.de_vzg.add_to("de.VzG", &mut res, points, store);
+        self.fr_rfn.add_to("fr.RFN", &mut res, points, store);
+        self.pl_id12.add_to("pl.Id12", &mut res, points, store);
+
+        res.attr("source", &self.source, store)
+            .attr("note", &self.note, store);
+        if res.is_empty() {
+            None
+        }
+        else {
+            Some(res.into_yaml())
+        }
+    }
+
+    /// Returns an iterator over the current national line codes.
+    ///
+    /// Each item contains the numbering system, the code, and where the
+    /// code was given. A code appears once for each section it is given
+    /// for.
+    pub fn codes(
+        &self
+    ) -> impl Iterator<Item = (CodeType, &str, Location)> + '_ {
+        [
+            (CodeType::AtVzg, &self.at_vzg),
+            (CodeType::ChBav, &self.ch_bav),
+            (CodeType::DeVzg, &self.de_vzg),
+            (CodeType::FrRfn, &self.fr_rfn),
+        ].into_iter().flat_map(|(code_type, value)| {
+            value.iter().filter_map(move |(_, code)| {
+                code.as_ref().map(|code| {
+                    (code_type, code.as_str(), code.location())
+                })
+            })
+        }).chain([
+            (CodeType::CzSr72, &self.cz_sr72),
+            (CodeType::PlId12, &self.pl_id12),
+        ].into_iter().flat_map(|(code_type, value)| {
+            value.iter().filter_map(move |(_, code)| {
+                code.as_ref().map(|code| {
+                    (code_type, code.as_str(), Location::NONE)
+                })
+            })
+        }))
+    }
+
+    /// Returns the current values relevant for maps as JSON.
+    ///
+    /// The result is an object with the category, electrification, gauge,
+    /// number of tracks, and status. Each of them is an array with an
+    /// object for each section containing the keys of the start and end
+    /// points as `start` and `end` and the value as `value`. Gauges are
+    /// given in millimetres.
+    pub fn sections_to_json(
+        &self, line: &Data, store: &impl LinkTarget<combined::Data>
+    ) -> JsonValue {
+        json!({
+            "category": self.category.sections_to_json(
+                line, store, |value| {
+                    value.iter().map(|item| item.as_str()).collect()
+                }
+            ),
+            "electrified": self.electrified.sections_to_json(
+                line, store, |value| match value.as_ref() {
+                    Some(value) => value.iter().map(|item| {
+                        JsonValue::from(item.to_string())
+                    }).collect(),
+                    None => JsonValue::Null,
+                }
+            ),
+            "gauge": self.gauge.sections_to_json(
+                line, store, |value| {
+                    value.iter().map(|item| item.gauge()).collect()
+                }
+            ),
+            "tracks": self.tracks.sections_to_json(
+                line, store, |value| value.into_value().into()
+            ),
+            "status": self.status.sections_to_json(
+                line, store, |value| value.as_str().into()
+            ),
+        })
+    }
 }
 
 impl FromYaml<PointsContext<'_>> for Current {
@@ -529,6 +1288,7 @@ impl FromYaml<PointsContext<'_>> for Current {
         let region = value.take_default("region", context, report);
         let reused = value.take_default("reused", context, report);
         let status = value.take_default("status", context, report);
+        let structures = value.take_default("structures", context, report);
         let tracks = value.take_default("tracks", context, report);
 
         let at_vzg = value.take_default("at.VzG", context, report);
@@ -542,7 +1302,7 @@ impl FromYaml<PointsContext<'_>> for Current {
         let note = value.take_opt("note", context, report);
 
         value.exhausted(report)?;
-        
+
         Ok(Current {
             category: category?,
             course: course?,
@@ -558,6 +1318,7 @@ impl FromYaml<PointsContext<'_>> for Current {
             region: region?,
             reused: reused?,
             status: status?,
+            structures: structures?,
             tracks: tracks?,
 
             at_vzg: at_vzg?,
@@ -578,6 +1339,7 @@ impl FromYaml<PointsContext<'_>> for Current {
 //------------ CurrentValue --------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CurrentValue<T> {
     sections: List<(Section, T)>,
 }
@@ -600,6 +1362,35 @@ impl<T> CurrentValue<T> {
         self.sections.as_slice()
     }
 
+    /// Returns the sections and their values as a JSON array.
+    ///
+    /// Each element is an object with the keys of the start and end points
+    /// of the section and the value converted via `op`.
+    pub fn sections_to_json(
+        &self,
+        line: &Data,
+        store: &impl LinkTarget<combined::Data>,
+        op: impl Fn(&T) -> JsonValue,
+    ) -> JsonValue {
+        self.sections.iter().map(|(section, value)| {
+            let id = section.id(line, store);
+            json!({
+                "start": id.start().as_str(),
+                "end": id.end().as_str(),
+                "value": op(value),
+            })
+        }).collect()
+    }
+
+    /// Returns the values for the parts of a section.
+    ///
+    /// Returns each value whose section overlaps with `section`.
+    pub fn for_section<'s>(
+        &'s self, section: &'s Section
+    ) -> impl Iterator<Item = &'s (Section, T)> + 's {
+        self.sections.iter().filter(move |(item, _)| item.overlaps(section))
+    }
+
     /// Returns the value at the given point index.
     ///
     /// If the index is a at section boundary, there may actually be two
@@ -632,6 +1423,46 @@ impl<T> CurrentValue<T> {
     }
 }
 
+impl<T: ToYaml> CurrentValue<T> {
+    /// Adds the value as an attribute to a mapping.
+    ///
+    /// A value covering the whole line is written as is unless it is a
+    /// mapping itself. Otherwise, a mapping from the point ending each
+    /// section to its value is written. Nothing is added if there are no
+    /// sections.
+    fn add_to(
+        &self,
+        key: &str,
+        target: &mut MappingBuilder,
+        points: &Points,
+        store: &DataStore
+    ) {
+        if self.sections.is_empty() {
+            return
+        }
+        if let Some((section, value)) = self.sections.single() {
+            if section.start.is_none() && section.end.is_none() {
+                let value = value.to_yaml(store);
+                if !matches!(value, Yaml::Hash(_)) {
+                    target.insert(key, value);
+                    return
+                }
+            }
+        }
+        target.insert(key, Yaml::Hash(
+            self.sections.iter().map(|(section, value)| {
+                let end = match section.end {
+                    Some(end) => end.to_yaml(store),
+                    None => {
+                        points.points.last().unwrap().to_yaml(store)
+                    }
+                };
+                (end, value.to_yaml(store))
+            }).collect()
+        ));
+    }
+}
+
 impl<T> Default for CurrentValue<T> {
     fn default() -> Self {
         CurrentValue { sections: List::default() }
@@ -737,6 +1568,7 @@ impl<T> ops::Deref for CurrentValue<T> {
 //------------ EventList -----------------------------------------------------
 
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EventList {
     events: List<Event>
 }
@@ -755,6 +1587,16 @@ impl FromYaml<PointsContext<'_>> for EventList {
     }
 }
 
+impl ToYaml for EventList {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        self.events.to_yaml(store)
+    }
+
+    fn is_omitted(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
 
 impl ops::Deref for EventList {
     type Target = List<Event>;
@@ -778,6 +1620,7 @@ impl<'a> IntoIterator for &'a EventList {
 //------------ Event ---------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Event {
     pub date: EventDate,
     pub sections: SectionList,
@@ -793,10 +1636,30 @@ impl Event {
         self.prop(|prop| prop.properties.name.as_ref())
     }
 
+    pub fn code(&self, code_type: CodeType) -> Option<&Marked<Text>> {
+        self.prop(|prop| prop.properties.code(code_type))
+    }
+
+    pub fn operator(&self) -> Option<&List<Marked<EntityLink>>> {
+        self.prop(|prop| prop.properties.operator.as_ref())
+    }
+
+    pub fn owner(&self) -> Option<&List<Marked<EntityLink>>> {
+        self.prop(|prop| prop.properties.owner.as_ref())
+    }
+
     pub fn region(&self) -> Option<&List<Marked<EntityLink>>> {
         self.prop(|prop| prop.properties.region.as_ref())
     }
 
+    pub fn structures(&self) -> Option<&List<Marked<StructureLink>>> {
+        self.prop(|prop| prop.properties.structures.as_ref())
+    }
+
+    pub fn status(&self) -> Option<Status> {
+        self.prop(|prop| prop.properties.status.as_ref()).copied()
+    }
+
     pub fn concession(&self) -> Option<&Concession> {
         self.prop(|prop| prop.concession.as_ref())
     }
@@ -852,10 +1715,28 @@ impl FromYaml<PointsContext<'_>> for Event {
     }
 }
 
+impl ToYaml for Event {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = MappingBuilder::new();
+        res.attr("date", &self.date, store)
+            .extend(self.sections.to_mapping(store));
+        match self.records.single() {
+            Some(record) if record.date.is_none() => {
+                res.extend(record.to_mapping(store));
+            }
+            _ => {
+                res.insert("records", self.records.to_yaml(store));
+            }
+        }
+        res.into_yaml()
+    }
+}
+
 
 //------------ EventRecord ---------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EventRecord {
     pub date: Option<EventDate>,
     pub document: Option<List<Marked<SourceLink>>>,
@@ -869,6 +1750,21 @@ pub struct EventRecord {
     pub properties: Properties,
 }
 
+impl EventRecord {
+    /// Returns the sources the record is based on.
+    pub fn provenance(&self) -> Provenance<'_> {
+        Provenance {
+            document: self.document.as_ref().map(List::as_slice)
+                .unwrap_or_default(),
+            source: self.source.as_ref().map(List::as_slice)
+                .unwrap_or_default(),
+            basis: self.basis.as_ref().map(List::as_slice)
+                .unwrap_or_default(),
+            agreement: self.agreement.as_ref(),
+        }
+    }
+}
+
 impl EventRecord {
     fn from_mapping(
         value: &mut Mapping,
@@ -952,6 +1848,21 @@ impl EventRecord {
     }
 }
 
+impl EventRecord {
+    fn to_mapping(&self, store: &DataStore) -> MappingBuilder {
+        let mut res = MappingBuilder::new();
+        res.attr("date", &self.date, store)
+            .attr("document", &self.document, store)
+            .attr("source", &self.source, store)
+            .attr("basis", &self.basis, store)
+            .attr("note", &self.note, store)
+            .attr("concession", &self.concession, store)
+            .attr("agreement", &self.agreement, store)
+            .extend(self.properties.to_mapping(store));
+        res
+    }
+}
+
 impl FromYaml<PointsContext<'_>> for EventRecord {
     fn from_yaml(
         value: Value,
@@ -965,10 +1876,17 @@ impl FromYaml<PointsContext<'_>> for EventRecord {
     }
 }
 
+impl ToYaml for EventRecord {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        self.to_mapping(store).into_yaml()
+    }
+}
+
 
 //------------ RecordList ----------------------------------------------------
 
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RecordList {
     documents: Vec<(SourceLink, List<Record>)>,
 }
@@ -1003,10 +1921,29 @@ impl FromYaml<PointsContext<'_>> for RecordList {
     }
 }
 
+impl ToYaml for RecordList {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut documents: Vec<_> = self.documents.iter().map(|item| {
+            (item.0.data(store).key(), &item.1)
+        }).collect();
+        documents.sort_by(|left, right| left.0.cmp(right.0));
+        Yaml::Array(
+            documents.into_iter().flat_map(|(_, records)| {
+                records.iter().map(|record| record.to_yaml(store))
+            }).collect()
+        )
+    }
+
+    fn is_omitted(&self) -> bool {
+        self.documents.is_empty()
+    }
+}
+
 
 //------------ Record --------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Record {
     pub sections: SectionList,
     pub document: Marked<SourceLink>,
@@ -1042,10 +1979,22 @@ impl FromYaml<PointsContext<'_>> for Record {
     }
 }
 
+impl ToYaml for Record {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = MappingBuilder::new();
+        res.insert("document", self.document.to_yaml(store))
+            .attr("note", &self.note, store)
+            .extend(self.sections.to_mapping(store))
+            .extend(self.properties.to_mapping(store));
+        res.into_yaml()
+    }
+}
+
 
 //------------ Properties ----------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Properties {
     pub category: Option<Set<Category>>,
     pub electrified: Option<Set<Marked<Electrified>>>,
@@ -1066,8 +2015,10 @@ pub struct Properties {
 
     pub course: Option<List<CourseSegment>>,
     pub region: Option<List<Marked<EntityLink>>>,
+    pub structures: Option<List<Marked<StructureLink>>>,
 
     pub at_vzg: Option<AtVzg>,
+    pub ch_bav: Option<ChBav>,
     pub de_vzg: Option<DeVzg>,
     pub fr_rfn: Option<FrRfn>,
 }
@@ -1091,9 +2042,32 @@ impl Properties {
         || self.status.is_some()
         || self.tracks.is_some()
         || self.at_vzg.is_some()
+        || self.ch_bav.is_some()
         || self.de_vzg.is_some()
         || self.fr_rfn.is_some()
     }
+
+    /// Returns the roles these properties give to an entity.
+    pub fn roles_of(&self, entity: EntityLink) -> Vec<entity::PropertyRole> {
+        [
+            (entity::PropertyRole::Constructor, &self.constructor),
+            (entity::PropertyRole::Owner, &self.owner),
+            (entity::PropertyRole::Operator, &self.operator),
+        ].into_iter().filter(|(_, list)| {
+            list.iter().flatten().any(|item| item.into_value() == entity)
+        }).map(|(role, _)| role).collect()
+    }
+
+    /// Returns the national line code of the given system if present.
+    pub fn code(&self, code_type: CodeType) -> Option<&Marked<Text>> {
+        match code_type {
+            CodeType::AtVzg => self.at_vzg.as_ref(),
+            CodeType::ChBav => self.ch_bav.as_ref(),
+            CodeType::DeVzg => self.de_vzg.as_ref(),
+            CodeType::FrRfn => self.fr_rfn.as_ref(),
+            CodeType::CzSr72 | CodeType::PlId12 => None,
+        }
+    }
 }
 
 impl Properties {
@@ -1117,12 +2091,14 @@ impl Properties {
         let region = value.take_opt("region", context, report);
         let reused = value.take_opt("reused", context, report);
         let status = value.take_opt("status", context, report);
+        let structures = value.take_opt("structures", context, report);
         let tracks = value.take_opt("tracks", context, report);
 
         let at_vzg = value.take_opt("at.VzG", context, report);
+        let ch_bav = value.take_opt("ch.BAV", context, report);
         let de_vzg = value.take_opt("de.VzG", context, report);
         let fr_rfn = value.take_opt("fr.RFN", context, report);
-        
+
         Ok(Properties {
             category: category?,
             constructor: constructor?,
@@ -1139,19 +2115,50 @@ impl Properties {
             region: region?,
             reused: reused?,
             status: status?,
+            structures: structures?,
             tracks: tracks?,
 
             at_vzg: at_vzg?,
+            ch_bav: ch_bav?,
             de_vzg: de_vzg?,
             fr_rfn: fr_rfn?,
         })
     }
 }
 
+impl Properties {
+    fn to_mapping(&self, store: &DataStore) -> MappingBuilder {
+        let mut res = MappingBuilder::new();
+        res.attr("category", &self.category, store)
+            .attr("constructor", &self.constructor, store)
+            .attr("course", &self.course, store)
+            .attr("electrified", &self.electrified, store)
+            .attr("goods", &self.goods, store)
+            .attr("gauge", &self.gauge, store)
+            .attr("jurisdiction", &self.jurisdiction, store)
+            .attr("name", &self.name, store)
+            .attr("operator", &self.operator, store)
+            .attr("owner", &self.owner, store)
+            .attr("passenger", &self.passenger, store)
+            .attr("rails", &self.rails, store)
+            .attr("region", &self.region, store)
+            .attr("reused", &self.reused, store)
+            .attr("status", &self.status, store)
+            .attr("structures", &self.structures, store)
+            .attr("tracks", &self.tracks, store)
+            .attr("at.VzG", &self.at_vzg, store)
+            .attr("ch.BAV", &self.ch_bav, store)
+            .attr("de.VzG", &self.de_vzg, store)
+            .attr("fr.RFN", &self.fr_rfn, store);
+        res
+    }
+}
+
 
 //------------ SectionList ---------------------------------------------------
 
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SectionList {
     sections: List<Section>,
 }
@@ -1196,8 +2203,33 @@ impl SectionList {
         Ok(SectionList { sections })
     }
 
+    /// Returns a mapping with the attributes describing the sections.
+    fn to_mapping(&self, store: &DataStore) -> MappingBuilder {
+        let mut res = MappingBuilder::new();
+        if let Some(section) = self.sections.single() {
+            res.attr("start", &section.start, store)
+                .attr("end", &section.end, store);
+        }
+        else {
+            res.attr("sections", &self.sections, store);
+        }
+        res
+    }
+
+    /// Returns the sections covered on a line with `len` points.
+    ///
+    /// An empty list covers the whole line.
+    pub fn covered(&self, len: usize) -> Vec<Section> {
+        if self.sections.is_empty() {
+            vec![Section::all(len)]
+        }
+        else {
+            self.sections.iter().cloned().collect()
+        }
+    }
+
     /// Returns the maximum section covered by this event.
-    fn overall(&self, len: usize) -> Section {
+    pub fn overall(&self, len: usize) -> Section {
         if self.sections.is_empty() {
             return Section::all(len)
         }
@@ -1243,15 +2275,82 @@ impl<'a> IntoIterator for &'a SectionList {
 
 //------------ Section -------------------------------------------------------
 
+/// A section of a line.
+///
+/// The start and end are given as links to points. A missing start or end
+/// refers to the first or last point of the line, respectively. The section
+/// also keeps the indexes of the points in the line’s point list. These
+/// are only valid for the line as it was loaded, so the [`SectionId`]
+/// returned by [`Section::id`] should be used to refer to a section from
+/// elsewhere.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Section {
     pub start: Option<Marked<PointLink>>,
     pub end: Option<Marked<PointLink>>,
-    pub start_idx: usize,
-    pub end_idx: usize,
+    pub(crate) start_idx: usize,
+    pub(crate) end_idx: usize,
 }
 
 impl Section {
+    /// Compares sections on lines for ordering cross references.
+    ///
+    /// Orders by the code of the line first and then by the position of
+    /// the section on the line.
+    pub fn cmp_on_lines(
+        left: &(LineLink, Section),
+        right: &(LineLink, Section),
+        store: &DataStore,
+    ) -> Ordering {
+        left.0.data(store).code().cmp(
+            right.0.data(store).code()
+        ).then_with(|| {
+            (left.1.start_idx, left.1.end_idx).cmp(
+                &(right.1.start_idx, right.1.end_idx)
+            )
+        })
+    }
+
+    /// Returns whether two sections on lines are the same.
+    pub fn same_on_lines(
+        left: &(LineLink, Section), right: &(LineLink, Section)
+    ) -> bool {
+        left.0 == right.0
+            && left.1.start_idx == right.1.start_idx
+            && left.1.end_idx == right.1.end_idx
+    }
+
+    /// Returns the stable identifier of the section on `line`.
+    pub fn id(
+        &self, line: &Data, store: &impl LinkTarget<combined::Data>
+    ) -> SectionId {
+        SectionId::new(
+            line.points[self.start_idx].data(store).key().clone(),
+            line.points[self.end_idx].data(store).key().clone(),
+        )
+    }
+
+    /// Returns the section on `line` as JSON.
+    pub fn to_json(&self, line: LineLink, store: &DataStore) -> JsonValue {
+        let data = line.data(store);
+        json!({
+            "line": data.key().as_str(),
+            "section": self.id(data, store).to_string(),
+        })
+    }
+
+    /// Returns whether the two sections share some part of the line.
+    ///
+    /// Sections that only meet at a point don’t overlap.
+    pub fn overlaps(&self, other: &Section) -> bool {
+        self.start_idx < other.end_idx && other.start_idx < self.end_idx
+    }
+
+    /// Returns whether `other` is completely part of this section.
+    pub fn contains(&self, other: &Section) -> bool {
+        self.start_idx <= other.start_idx && other.end_idx <= self.end_idx
+    }
+
     pub fn start_point<'s>(
         &'s self, line: &'s Data, store: &'s FullStore
     ) -> point::Document<'s> {
@@ -1372,6 +2471,15 @@ impl FromYaml<PointsContext<'_>> for Section {
     }
 }
 
+impl ToYaml for Section {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = MappingBuilder::new();
+        res.attr("start", &self.start, store)
+            .attr("end", &self.end, store);
+        res.into_yaml()
+    }
+}
+
 impl PartialEq for Section {
     fn eq(&self, other: &Self) -> bool {
         self.start == other.start && self.end == other.end
@@ -1396,6 +2504,56 @@ impl Ord for Section {
 }
 
 
+//------------ SectionId -----------------------------------------------------
+
+/// A stable identifier of a section of a line.
+///
+/// The identifier consists of the keys of the points at the start and end
+/// of the section. It is written as the two keys separated by two dots,
+/// e.g., `point.de.a..point.de.b`.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SectionId {
+    start: Key,
+    end: Key,
+}
+
+impl SectionId {
+    pub fn new(start: Key, end: Key) -> Self {
+        SectionId { start, end }
+    }
+
+    pub fn start(&self) -> &Key {
+        &self.start
+    }
+
+    pub fn end(&self) -> &Key {
+        &self.end
+    }
+}
+
+impl FromStr for SectionId {
+    type Err = InvalidSectionId;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s.split_once("..").ok_or(InvalidSectionId)?;
+        if start.is_empty() || end.is_empty() {
+            return Err(InvalidSectionId)
+        }
+        Ok(SectionId::new(
+            Key::from_string(start.into()).map_err(|_| InvalidSectionId)?,
+            Key::from_string(end.into()).map_err(|_| InvalidSectionId)?,
+        ))
+    }
+}
+
+impl fmt::Display for SectionId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+
 //------------ Category ------------------------------------------------------
 
 data_enum! {
@@ -1427,6 +2585,7 @@ impl Category {
 //------------ Concession ----------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Concession {
     pub by: List<Marked<EntityLink>>,
     pub to: List<Marked<EntityLink>>,
@@ -1459,6 +2618,17 @@ impl FromYaml<StoreLoader> for Concession {
     }
 }
 
+impl ToYaml for Concession {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = MappingBuilder::new();
+        res.attr("by", &self.by, store)
+            .attr("for", &self.to, store)
+            .attr("rights", &self.rights, store)
+            .attr("until", &self.until, store);
+        res.into_yaml()
+    }
+}
+
 
 //------------ ConcessionRight -----------------------------------------------
 
@@ -1474,10 +2644,32 @@ data_enum! {
 //------------ CourseSegment -------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CourseSegment {
     pub path: Marked<PathLink>,
-    pub start: Marked<String>,
-    pub end: Marked<String>,
+    pub start: Marked<Text>,
+    pub end: Marked<Text>,
+}
+
+impl CourseSegment {
+    /// Returns the length of the segment in kilometres.
+    pub fn length(
+        &self, store: &impl LinkTarget<combined::Data>
+    ) -> Option<f64> {
+        self.path.data(store).length_between(&self.start, &self.end)
+    }
+
+    /// Checks that the path has both nodes of the segment.
+    pub fn check_nodes(
+        &self,
+        store: &impl LinkTarget<combined::Data>,
+        report: &mut PathReporter,
+    ) -> Result<(), Failed> {
+        let path = self.path.data(store);
+        let start = path.check_node(&self.start, report);
+        let end = path.check_node(&self.end, report);
+        start.and(end)
+    }
 }
 
 impl FromYaml<StoreLoader> for CourseSegment {
@@ -1510,7 +2702,7 @@ impl FromYaml<StoreLoader> for CourseSegment {
                 return Err(Failed)
             }
         };
-        let start = Marked::new(String::from(start), location);
+        let start = Marked::new(Text::from(start), location);
         let end = match value.next() {
             Some(path) => path,
             None => {
@@ -1518,7 +2710,7 @@ impl FromYaml<StoreLoader> for CourseSegment {
                 return Err(Failed)
             }
         };
-        let end = Marked::new(String::from(end), location);
+        let end = Marked::new(Text::from(end), location);
         if value.next().is_some() {
             report.error(InvalidCourseSegment.marked(location));
             return Err(Failed)
@@ -1527,6 +2719,14 @@ impl FromYaml<StoreLoader> for CourseSegment {
     }
 }
 
+impl ToYaml for CourseSegment {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        Yaml::String(format!(
+            "{} {} {}", self.path.data(store).key(), self.start, self.end
+        ))
+    }
+}
+
 impl PartialEq for CourseSegment {
     fn eq(&self, other: &Self) -> bool {
         self.path.as_value() == other.path.as_value()
@@ -1539,71 +2739,39 @@ impl PartialEq for CourseSegment {
 //------------ Electrified ---------------------------------------------------
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Electrified {
-    named: Option<&'static str>,
+    named: Option<Arc<str>>,
     generic: Option<GenericEl>,
 }
 
 impl Electrified {
-    fn from_name(s: &str) -> Option<Self> {
-        macro_rules! system {
-            (
-                $(
-                    $name:expr => $sys:ident, $volt:expr, $acdc:ident;
-                )*
-            ) => {
-                $(
-                    if s == $name {
-                        return Some(Electrified {
-                            named: Some($name),
-                            generic: Some(GenericEl::new(
-                                ElSystem::$sys, $volt, AcDc::$acdc
-                            )),
-                        })
-                    }
-                )*
-            }
+    /// Parses electrification using the given registry of named systems.
+    pub fn from_registry(
+        s: &str, registry: &ElRegistry
+    ) -> Result<Self, String> {
+        if s == "none" {
+            Ok(Self { named: None, generic: None })
+        }
+        else if let Some((name, generic)) = registry.get(s) {
+            Ok(Self {
+                named: Some(name.clone()),
+                generic: Some(generic)
+            })
+        }
+        else if let Ok(generic) = GenericEl::from_str(s) {
+            Ok(Self {
+                named: None,
+                generic: Some(generic)
+            })
+        }
+        else {
+            Err(format!("unknown electrification system '{}'", s))
         }
-
-        system!(
-            "at"      => Ole, 15000, Ac16;
-            "be"      => Ole,  3000, Dc;
-            "be.25"   => Ole, 25000, Ac50;
-            "ch"      => Ole, 15000, Ac16;
-            "ch.11k"  => Ole, 11000, Ac16;
-            "cz.3"    => Ole,  3000, Dc;
-            "cz.25"   => Ole, 25000, Ac50;
-            "de"      => Ole, 15000, Ac16;
-            "de.bln-1903"  => Rail,  550, Dc;
-            "de.bln"  => Rail,  800, Dc;
-            "de.hmb"  => Rail, 1200, Dc;
-            "de.hmb-alt" => Ole, 6300, Ac25;
-            "dk"      => Ole, 25000, Ac50;
-            "ee.ac"   => Ole, 25000, Ac50;
-            "ee.dc"   => Ole,  3000, Dc;
-            "fi"      => Ole, 25000, Ac50;
-            "gb.25"   => Ole, 25000, Ac50;
-            "gb.rail" => Rail,  750, Dc;
-            "fr.15"   => Ole,  1500, Dc;
-            "fr.25"   => Ole, 25000, Ac50;
-            "fr.lgv"  => Ole, 25000, Ac50;
-            "hu"      => Ole, 25000, Ac50;
-            "it.3"    => Ole,  3000, Dc;
-            "it.25"   => Ole, 25000, Ac50;
-            "lt"      => Ole, 25000, Ac50;
-            "lu.25"   => Ole, 25000, Ac50;
-            "lv"      => Ole,  3300, Dc;
-            "nl"      => Ole,  1500, Dc;
-            "nl.25"   => Ole, 25000, Ac50;
-            "pl"      => Ole,  3000, Dc;
-            "ru"      => Ole,  3000, Dc;
-            "si"      => Ole,  3000, Dc;
-        );
-        None
     }
 
     pub fn named(&self) -> Option<&str> {
-        self.named
+        self.named.as_deref()
     }
 
     pub fn generic(&self) -> Option<GenericEl> {
@@ -1614,28 +2782,15 @@ impl Electrified {
 impl FromStr for Electrified {
     type Err = String;
 
+    /// Parses electrification using only the built-in named systems.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s == "none" {
-            Ok(Self { named: None, generic: None })
-        }
-        else if let Some(res) = Self::from_name(s) {
-            Ok(res)
-        }
-        else if let Ok(generic) = GenericEl::from_str(s) {
-            Ok(Self {
-                named: None,
-                generic: Some(generic)
-            })
-        }
-        else {
-            Err(format!("unknown electrification system '{}'", s))
-        }
+        Self::from_registry(s, &ElRegistry::builtin())
     }
 }
 
 impl fmt::Display for Electrified {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if let Some(name) = self.named {
+        if let Some(name) = self.named.as_ref() {
             f.write_str(name)
         }
         else if let Some(generic) = self.generic {
@@ -1650,11 +2805,13 @@ impl fmt::Display for Electrified {
 impl FromYaml<StoreLoader> for Marked<Electrified> {
     fn from_yaml(
         value: Value,
-        _context: &StoreLoader,
+        context: &StoreLoader,
         report: &mut PathReporter
     ) -> Result<Self, Failed> {
         let text = value.into_string(report)?;
-        let res = text.try_map(|plain| Electrified::from_str(&plain));
+        let res = text.try_map(|plain| {
+            Electrified::from_registry(&plain, context.electrification())
+        });
         res.map_err(|err| {
             report.error(err);
             Failed
@@ -1662,10 +2819,17 @@ impl FromYaml<StoreLoader> for Marked<Electrified> {
     }
 }
 
+impl ToYaml for Electrified {
+    fn to_yaml(&self, _: &DataStore) -> Yaml {
+        display_to_yaml(self)
+    }
+}
+
 
 //------------ GenericEl -----------------------------------------------------
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GenericEl {
     pub system: ElSystem,
     pub voltage: u16,
@@ -1673,6 +2837,7 @@ pub struct GenericEl {
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ElSystem {
     Ole,
     Rail,
@@ -1680,6 +2845,7 @@ pub enum ElSystem {
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AcDc {
     Ac16,
     Ac25,
@@ -1689,7 +2855,9 @@ pub enum AcDc {
 }
 
 impl GenericEl {
-    const fn new(system: ElSystem, voltage: u16, frequency: AcDc) -> Self {
+    pub const fn new(
+        system: ElSystem, voltage: u16, frequency: AcDc
+    ) -> Self {
         Self { system, voltage, frequency }
     }
 }
@@ -1760,6 +2928,7 @@ impl fmt::Display for GenericEl {
 //------------ Gauge ---------------------------------------------------------
 
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Gauge(pub Marked<u16>);
 
 impl Gauge {
@@ -1770,11 +2939,42 @@ impl Gauge {
     pub fn location(&self) -> Location {
         self.0.location()
     }
+
+    /// Returns whether this is standard gauge of 1435 mm.
+    pub fn is_standard(&self) -> bool {
+        self.gauge() == Self::STANDARD
+    }
+
+    /// Returns whether the gauge is narrower than standard gauge.
+    pub fn is_narrow(&self) -> bool {
+        self.gauge() < Self::STANDARD
+    }
+
+    /// Returns whether the gauge is wider than standard gauge.
+    pub fn is_broad(&self) -> bool {
+        self.gauge() > Self::STANDARD
+    }
+
+    /// Returns whether the gauge is narrow, standard, or broad.
+    pub fn class(&self) -> GaugeClass {
+        match self.gauge().cmp(&Self::STANDARD) {
+            Ordering::Less => GaugeClass::Narrow,
+            Ordering::Equal => GaugeClass::Standard,
+            Ordering::Greater => GaugeClass::Broad,
+        }
+    }
+
+    /// Returns the family of common gauges this gauge belongs to.
+    pub fn family(&self) -> Option<GaugeFamily> {
+        GaugeFamily::from_gauge(self.gauge())
+    }
+
+    const STANDARD: u16 = 1435;
 }
 
 impl Default for Gauge {
     fn default() -> Gauge {
-        Gauge(Marked::from_value(1435))
+        Gauge(Marked::from_value(Self::STANDARD))
     }
 }
 
@@ -1799,6 +2999,12 @@ impl<C> FromYaml<C> for Gauge {
     }
 }
 
+impl ToYaml for Gauge {
+    fn to_yaml(&self, _: &DataStore) -> Yaml {
+        Yaml::String(format!("{}mm", self.0))
+    }
+}
+
 impl fmt::Display for Gauge {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}\u{202f}mm", self.0)
@@ -1806,6 +3012,80 @@ impl fmt::Display for Gauge {
 }
 
 
+//------------ GaugeClass ----------------------------------------------------
+
+data_enum! {
+    /// The class of a gauge relative to standard gauge.
+    pub enum GaugeClass {
+        { Narrow: "narrow" }
+        { Standard: "standard" }
+        { Broad: "broad" }
+    }
+}
+
+
+//------------ GaugeFamily ---------------------------------------------------
+
+data_enum! {
+    /// A family of commonly used gauges.
+    ///
+    /// Some families include gauges that differ by a few millimetres but
+    /// are compatible in practice.
+    pub enum GaugeFamily {
+        /// 600 mm field railway gauge.
+        { Field: "field" }
+
+        /// 750 mm and 760 mm gauge, the latter known as Bosnian gauge.
+        { Bosnian: "bosnian" }
+
+        /// 1000 mm metre gauge.
+        { Metre: "metre" }
+
+        /// 1067 mm Cape gauge.
+        { Cape: "cape" }
+
+        /// 1435 mm standard gauge.
+        { Standard: "standard" }
+
+        /// 1520 mm and 1524 mm Russian gauge.
+        { Russian: "russian" }
+
+        /// 1600 mm Irish gauge.
+        { Irish: "irish" }
+
+        /// 1668 mm Iberian gauge.
+        { Iberian: "iberian" }
+    }
+}
+
+impl GaugeFamily {
+    /// Returns the family for a gauge given in millimetres.
+    pub fn from_gauge(gauge: u16) -> Option<Self> {
+        match gauge {
+            600 => Some(GaugeFamily::Field),
+            750 | 760 => Some(GaugeFamily::Bosnian),
+            1000 => Some(GaugeFamily::Metre),
+            1067 => Some(GaugeFamily::Cape),
+            1435 => Some(GaugeFamily::Standard),
+            1520 | 1524 => Some(GaugeFamily::Russian),
+            1600 => Some(GaugeFamily::Irish),
+            1668 | 1672 | 1674 => Some(GaugeFamily::Iberian),
+            _ => None
+        }
+    }
+
+    /// Returns the class of the gauges in the family.
+    pub fn class(self) -> GaugeClass {
+        match self {
+            GaugeFamily::Standard => GaugeClass::Standard,
+            GaugeFamily::Russian | GaugeFamily::Irish
+                | GaugeFamily::Iberian => GaugeClass::Broad,
+            _ => GaugeClass::Narrow,
+        }
+    }
+}
+
+
 //------------ Goods ---------------------------------------------------------
 
 data_enum! {
@@ -1847,20 +3127,212 @@ data_enum! {
     }
 }
 
+impl Status {
+    /// Returns whether the line is in operation with this status.
+    pub fn is_operating(self) -> bool {
+        matches!(self, Status::Open | Status::Reopened)
+    }
+
+    /// Returns why operation ended when changing to this status.
+    ///
+    /// Only the statuses after which the line can never reopen without
+    /// being rebuilt count as closing it.
+    fn end_reason(self) -> EndReason {
+        match self {
+            Status::Closed | Status::Removed | Status::Released => {
+                EndReason::Closed
+            }
+            _ => EndReason::Suspended
+        }
+    }
+}
+
 
 //------------ AtVzg ---------------------------------------------------------
 
-pub type AtVzg = Marked<String>;
+pub type AtVzg = Marked<Text>;
 
 
 //------------ DeVzg ---------------------------------------------------------
 
-pub type DeVzg = Marked<String>;
+pub type DeVzg = Marked<Text>;
+
+
+//------------ ChBav ---------------------------------------------------------
+
+pub type ChBav = Marked<Text>;
 
 
 //------------ FrRfn ---------------------------------------------------------
 
-pub type FrRfn = Marked<String>;
+pub type FrRfn = Marked<Text>;
+
+
+//------------ CodeType ------------------------------------------------------
+
+data_enum! {
+    /// The national line numbering systems.
+    pub enum CodeType {
+        { AtVzg: "at.VzG" }
+        { ChBav: "ch.BAV" }
+        { CzSr72: "cz.SR72" }
+        { DeVzg: "de.VzG" }
+        { FrRfn: "fr.RFN" }
+        { PlId12: "pl.Id12" }
+    }
+}
+
+impl CodeType {
+    /// Returns the country whose lines are numbered by the system.
+    pub fn country(self) -> CountryCode {
+        match self {
+            CodeType::AtVzg => CountryCode::AT,
+            CodeType::ChBav => CountryCode::CH,
+            CodeType::CzSr72 => CountryCode::CZ,
+            CodeType::DeVzg => CountryCode::DE,
+            CodeType::FrRfn => CountryCode::FR,
+            CodeType::PlId12 => CountryCode::PL,
+        }
+    }
+
+    /// Returns the numbering system of a country if there is one.
+    pub fn for_country(country: CountryCode) -> Option<Self> {
+        Self::ALL.iter().copied().find(|item| item.country() == country)
+    }
+}
+
+
+//------------ CodeAssignment ------------------------------------------------
+
+/// A national line code a line had for certain periods.
+#[derive(Clone, Debug)]
+pub struct CodeAssignment {
+    /// The numbering system of the code.
+    pub code_type: CodeType,
+
+    /// The code.
+    pub code: Text,
+
+    /// The periods during which the line had the code.
+    pub periods: OperatingPeriods,
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Parses a kilometrage value such as `"12,3"`.
+///
+/// Returns `None` if the value isn’t a plain number, such as the `"??"`
+/// used for unknown locations.
+pub(crate) fn parse_kilometrage(value: &str) -> Option<f64> {
+    f64::from_str(&value.trim().replace(',', ".")).ok()
+}
+
+
+//------------ Schema --------------------------------------------------------
+
+pub fn schema(schema: &mut Schema) {
+    schema.document("line")
+        .include("common")
+        .optional("label", "list(enum(line.label))")
+        .optional("note", "text")
+        .mandatory("points", "list(key(point))")
+        .optional("current", "object(line.current)")
+        .optional("events", "list(object(line.event))")
+        .optional("records", "list(object(line.record))");
+    let current = schema.object("line.current");
+    current
+        .optional("category", "current(list(enum(line.category)))")
+        .optional("course", "current(list(course))")
+        .optional("electrified", "current(nullable(list(electrified)))")
+        .optional("gauge", "current(list(gauge))")
+        .optional("goods", "current(enum(line.goods))")
+        .optional("jurisdiction", "current(country)")
+        .optional("name", "current(nullable(text))")
+        .optional("operator", "current(nullable(list(key(entity))))")
+        .optional("owner", "current(nullable(list(key(entity))))")
+        .optional("passenger", "current(enum(line.passenger))")
+        .optional("rails", "current(integer)")
+        .optional("region", "current(list(key(entity)))")
+        .optional("reused", "current(nullable(list(key(line))))")
+        .optional("status", "current(enum(line.status))")
+        .optional("structures", "current(nullable(list(key(structure))))")
+        .optional("tracks", "current(integer)");
+    for item in CodeType::ALL {
+        current.optional(item.as_str(), "current(nullable(string))");
+    }
+    current
+        .optional("source", "list(key(source))")
+        .optional("note", "text");
+    schema.object("line.event")
+        .optional("date", "date")
+        .include("line.sections")
+        .optional("records", "list(object(line.event_record))")
+        .include("line.event_record");
+    schema.object("line.event_record")
+        .optional("date", "date")
+        .optional("document", "list(key(source))")
+        .optional("source", "list(key(source))")
+        .optional("basis", "list(object(basis))")
+        .optional("note", "text")
+        .optional("concession", "object(line.concession)")
+        .optional("expropriation", "object(line.concession)")
+        .optional("agreement", "object(agreement)")
+        .optional("contract", "object(contract)")
+        .optional("treaty", "object(contract)")
+        .include("line.properties");
+    schema.object("line.record")
+        .optional("date", "date")
+        .mandatory("document", "key(source)")
+        .optional("note", "text")
+        .include("line.sections")
+        .include("line.properties");
+    let properties = schema.object("line.properties");
+    properties
+        .optional("category", "list(enum(line.category))")
+        .optional("constructor", "list(key(entity))")
+        .optional("course", "list(course)")
+        .optional("electrified", "list(electrified)")
+        .optional("goods", "enum(line.goods)")
+        .optional("gauge", "list(gauge)")
+        .optional("jurisdiction", "country")
+        .optional("name", "text")
+        .optional("operator", "list(key(entity))")
+        .optional("owner", "list(key(entity))")
+        .optional("passenger", "enum(line.passenger)")
+        .optional("rails", "integer")
+        .optional("region", "list(key(entity))")
+        .optional("reused", "list(key(line))")
+        .optional("status", "enum(line.status)")
+        .optional("structures", "list(key(structure))")
+        .optional("tracks", "integer");
+    // Records and events only carry codes of the older systems.
+    for item in CodeType::ALL {
+        if !matches!(item, CodeType::CzSr72 | CodeType::PlId12) {
+            properties.optional(item.as_str(), "string");
+        }
+    }
+    schema.object("line.sections")
+        .optional("sections", "list(object(line.section))")
+        .optional("start", "key(point)")
+        .optional("end", "key(point)");
+    schema.object("line.section")
+        .optional("start", "key(point)")
+        .optional("end", "key(point)");
+    schema.object("line.concession")
+        .optional("by", "list(key(entity))")
+        .optional("for", "list(key(entity))")
+        .optional("rights", "list(enum(line.concession_right))")
+        .optional("until", "date");
+
+    schema.enumeration("line.category", Category::VARIANTS);
+    schema.enumeration("line.concession_right", ConcessionRight::VARIANTS);
+    schema.enumeration("line.goods", Goods::VARIANTS);
+    schema.enumeration("line.label", Label::VARIANTS);
+    schema.enumeration("line.passenger", Passenger::VARIANTS);
+    schema.enumeration("line.status", Status::VARIANTS);
+}
+
 
 
 //============ Errors ========================================================
@@ -1901,6 +3373,10 @@ pub struct EndNotListed;
 #[display(fmt = "start and end are equal")]
 pub struct EmptySection;
 
+#[derive(Clone, Copy, Debug, Display)]
+#[display(fmt = "invalid section identifier")]
+pub struct InvalidSectionId;
+
 #[derive(Clone, Copy, Debug, Display)]
 #[display(fmt = "end before start")]
 pub struct EndBeforeStart;
@@ -1921,4 +3397,16 @@ pub struct MultipleAgreements;
 #[display(fmt="only one of 'concession' or 'expropriation' allowed")]
 pub struct MultipleConcessions;
 
+#[derive(Clone, Debug, Display)]
+#[display(
+    fmt="kilometrage of '{}' goes backwards: {} after {}", point, km, prev
+)]
+pub struct KilometrageBackwards {
+    point: Key,
+    km: String,
+    prev: String,
+}
 
+#[derive(Clone, Copy, Debug, Display)]
+#[display(fmt="line reuses itself through its reused lines")]
+pub struct ReuseCycle;
@@ -0,0 +1,155 @@
+//! The registry of named electrification systems.
+//!
+//! Electrification of a line can be given either generically, such as
+//! `ole.15000ac16`, or by the name of a common system, such as `de`. The
+//! names are defined by an [`ElRegistry`]. It starts out with a built-in
+//! set of systems and can be extended or overridden through the file
+//! `electrification.yaml` at the root of the data tree. This file contains
+//! a single mapping from names to generic systems:
+//!
+//! ```yaml
+//! de: ole.15000ac16
+//! de.bln: rail.800dc
+//! ```
+
+use std::str::FromStr;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use derive_more::Display;
+use serde_json::{Map, Value as JsonValue};
+use crate::load::report::{Failed, PathReporter};
+use crate::load::yaml::Value;
+use crate::types::IntoMarked;
+use super::data::{AcDc, ElSystem, GenericEl};
+
+
+//------------ ElRegistry ----------------------------------------------------
+
+/// The named electrification systems.
+#[derive(Clone, Debug)]
+pub struct ElRegistry {
+    systems: BTreeMap<Arc<str>, GenericEl>,
+}
+
+impl ElRegistry {
+    /// Creates a registry with only the built-in systems.
+    pub fn builtin() -> Self {
+        ElRegistry {
+            systems: BUILTIN.iter().map(|(name, system)| {
+                (Arc::from(*name), *system)
+            }).collect()
+        }
+    }
+
+    /// Adds the systems from the YAML content of a registry file.
+    ///
+    /// Systems already present are replaced.
+    pub fn add_from_yaml(
+        &mut self, value: Value, report: &mut PathReporter
+    ) -> Result<(), Failed> {
+        let mut failed = false;
+        for (name, value) in value.into_mapping(report)?.into_iter() {
+            let value = match value.into_string(report) {
+                Ok(value) => value,
+                Err(_) => {
+                    failed = true;
+                    continue
+                }
+            };
+            match GenericEl::from_str(value.as_str()) {
+                Ok(system) => {
+                    self.systems.insert(
+                        Arc::from(name.into_value()), system
+                    );
+                }
+                Err(_) => {
+                    report.error(
+                        InvalidSystem(value.as_value().clone())
+                            .marked(value.location())
+                    );
+                    failed = true;
+                }
+            }
+        }
+        if failed {
+            Err(Failed)
+        }
+        else {
+            Ok(())
+        }
+    }
+
+    /// Returns the system with the given name.
+    pub fn get(&self, name: &str) -> Option<(&Arc<str>, GenericEl)> {
+        self.systems.get_key_value(name).map(|(name, system)| {
+            (name, *system)
+        })
+    }
+
+    /// Returns an iterator over all systems ordered by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, GenericEl)> + '_ {
+        self.systems.iter().map(|(name, system)| (name.as_ref(), *system))
+    }
+
+    /// Returns the registry as a JSON object from names to systems.
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::Object(
+            self.iter().map(|(name, system)| {
+                (String::from(name), system.to_string().into())
+            }).collect::<Map<_, _>>()
+        )
+    }
+}
+
+impl Default for ElRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+
+//------------ Built-in Systems ----------------------------------------------
+
+/// The systems known without a registry file.
+pub(super) const BUILTIN: &[(&str, GenericEl)] = &[
+    ("at", GenericEl::new(ElSystem::Ole, 15000, AcDc::Ac16)),
+    ("be", GenericEl::new(ElSystem::Ole, 3000, AcDc::Dc)),
+    ("be.25", GenericEl::new(ElSystem::Ole, 25000, AcDc::Ac50)),
+    ("ch", GenericEl::new(ElSystem::Ole, 15000, AcDc::Ac16)),
+    ("ch.11k", GenericEl::new(ElSystem::Ole, 11000, AcDc::Ac16)),
+    ("cz.3", GenericEl::new(ElSystem::Ole, 3000, AcDc::Dc)),
+    ("cz.25", GenericEl::new(ElSystem::Ole, 25000, AcDc::Ac50)),
+    ("de", GenericEl::new(ElSystem::Ole, 15000, AcDc::Ac16)),
+    ("de.bln-1903", GenericEl::new(ElSystem::Rail, 550, AcDc::Dc)),
+    ("de.bln", GenericEl::new(ElSystem::Rail, 800, AcDc::Dc)),
+    ("de.hmb", GenericEl::new(ElSystem::Rail, 1200, AcDc::Dc)),
+    ("de.hmb-alt", GenericEl::new(ElSystem::Ole, 6300, AcDc::Ac25)),
+    ("dk", GenericEl::new(ElSystem::Ole, 25000, AcDc::Ac50)),
+    ("ee.ac", GenericEl::new(ElSystem::Ole, 25000, AcDc::Ac50)),
+    ("ee.dc", GenericEl::new(ElSystem::Ole, 3000, AcDc::Dc)),
+    ("fi", GenericEl::new(ElSystem::Ole, 25000, AcDc::Ac50)),
+    ("gb.25", GenericEl::new(ElSystem::Ole, 25000, AcDc::Ac50)),
+    ("gb.rail", GenericEl::new(ElSystem::Rail, 750, AcDc::Dc)),
+    ("fr.15", GenericEl::new(ElSystem::Ole, 1500, AcDc::Dc)),
+    ("fr.25", GenericEl::new(ElSystem::Ole, 25000, AcDc::Ac50)),
+    ("fr.lgv", GenericEl::new(ElSystem::Ole, 25000, AcDc::Ac50)),
+    ("hu", GenericEl::new(ElSystem::Ole, 25000, AcDc::Ac50)),
+    ("it.3", GenericEl::new(ElSystem::Ole, 3000, AcDc::Dc)),
+    ("it.25", GenericEl::new(ElSystem::Ole, 25000, AcDc::Ac50)),
+    ("lt", GenericEl::new(ElSystem::Ole, 25000, AcDc::Ac50)),
+    ("lu.25", GenericEl::new(ElSystem::Ole, 25000, AcDc::Ac50)),
+    ("lv", GenericEl::new(ElSystem::Ole, 3300, AcDc::Dc)),
+    ("nl", GenericEl::new(ElSystem::Ole, 1500, AcDc::Dc)),
+    ("nl.25", GenericEl::new(ElSystem::Ole, 25000, AcDc::Ac50)),
+    ("pl", GenericEl::new(ElSystem::Ole, 3000, AcDc::Dc)),
+    ("ru", GenericEl::new(ElSystem::Ole, 3000, AcDc::Dc)),
+    ("si", GenericEl::new(ElSystem::Ole, 3000, AcDc::Dc)),
+];
+
+
+//============ Errors ========================================================
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="invalid electrification system '{}'", _0)]
+pub struct InvalidSystem(String);
+
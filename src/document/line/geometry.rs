@@ -0,0 +1,172 @@
+//! The assembled geometry of a line.
+
+use crate::document::path;
+use crate::document::path::Coord;
+use crate::store::MetaStore;
+use super::data::Data;
+
+
+//------------ Geometry ------------------------------------------------------
+
+/// The geometry of the current course of a line.
+///
+/// The geometry is assembled once while generating the metadata so that
+/// it can be used without resolving the paths of the course again. It
+/// contains one polyline for each course segment that could be resolved.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Geometry {
+    segments: Vec<Polyline>,
+}
+
+impl Geometry {
+    /// Assembles the geometry of a line.
+    ///
+    /// Course segments whose path doesn’t have both nodes are skipped.
+    pub fn generate(line: &Data, store: &MetaStore) -> Self {
+        Geometry {
+            segments: line.current.course.iter().flat_map(|(_, course)| {
+                course.iter()
+            }).filter_map(|segment| {
+                Polyline::generate(
+                    segment.path.data(store), segment.path.meta(store),
+                    &segment.start, &segment.end
+                )
+            }).collect()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Returns the coordinates of each course segment for a zoom level.
+    ///
+    /// The geometry is simplified the same way as the geometry of paths.
+    pub fn segments(&self, zoom: u8) -> Vec<Vec<Coord>> {
+        let level = path::Meta::level_index(zoom);
+        self.segments.iter().map(|segment| segment.coords(level)).collect()
+    }
+
+    /// Returns all coordinates at full detail.
+    pub fn coords(&self) -> impl Iterator<Item = Coord> + '_ {
+        self.segments.iter().flat_map(|segment| segment.decode())
+    }
+}
+
+
+//------------ Polyline ------------------------------------------------------
+
+/// The coordinates of a single course segment.
+///
+/// Each coordinate is stored as a byte with the simplified levels of the
+/// path it is part of as given by [`path::Meta::level_mask`] followed by
+/// the difference to the previous coordinate in units of 10⁻⁷ degrees
+/// for longitude and latitude. The differences are zigzag encoded and
+/// written as variable length integers, so the typically small steps
+/// between nodes only take a few bytes.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Polyline {
+    data: Vec<u8>,
+}
+
+impl Polyline {
+    /// The number of units per degree.
+    const SCALE: f64 = 1e7;
+
+    fn generate(
+        path: &path::Data, meta: &path::Meta, start: &str, end: &str
+    ) -> Option<Self> {
+        let start = path.get_pos(start)?;
+        let end = path.get_pos(end)?;
+        let nodes: Vec<_> = if start <= end {
+            (start..=end).collect()
+        }
+        else {
+            (end..=start).rev().collect()
+        };
+        let last = nodes.len() - 1;
+        let mut res = Polyline::default();
+        let mut prev = (0, 0);
+        for (pos, idx) in nodes.into_iter().enumerate() {
+            // The ends of the segment are always kept.
+            let mask = if pos == 0 || pos == last { u8::MAX }
+                       else { meta.level_mask(idx) };
+            let node = &path.nodes[idx];
+            let lon = (node.lon * Self::SCALE).round() as i64;
+            let lat = (node.lat * Self::SCALE).round() as i64;
+            res.data.push(mask);
+            write_varint(&mut res.data, zigzag(lon - prev.0));
+            write_varint(&mut res.data, zigzag(lat - prev.1));
+            prev = (lon, lat);
+        }
+        Some(res)
+    }
+
+    /// Returns the coordinates kept at a level or all with `None`.
+    fn coords(&self, level: Option<usize>) -> Vec<Coord> {
+        let mask = level.map(|level| 1u8 << level).unwrap_or(u8::MAX);
+        self.decode_masked().filter(|(item, _)| {
+            item & mask != 0
+        }).map(|(_, coord)| coord).collect()
+    }
+
+    /// Returns all coordinates.
+    fn decode(&self) -> impl Iterator<Item = Coord> + '_ {
+        self.decode_masked().map(|(_, coord)| coord)
+    }
+
+    /// Returns all coordinates with their level masks.
+    fn decode_masked(&self) -> impl Iterator<Item = (u8, Coord)> + '_ {
+        let mut data = self.data.as_slice();
+        let mut prev = (0, 0);
+        std::iter::from_fn(move || {
+            let (&mask, rest) = data.split_first()?;
+            data = rest;
+            let lon = prev.0 + unzigzag(read_varint(&mut data)?);
+            let lat = prev.1 + unzigzag(read_varint(&mut data)?);
+            prev = (lon, lat);
+            Some((mask, Coord {
+                lon: lon as f64 / Self::SCALE,
+                lat: lat as f64 / Self::SCALE,
+            }))
+        })
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+fn write_varint(target: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        target.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    target.push(value as u8)
+}
+
+fn read_varint(data: &mut &[u8]) -> Option<u64> {
+    let mut res = 0;
+    let mut shift = 0;
+    loop {
+        let (&byte, rest) = data.split_first()?;
+        *data = rest;
+        res |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(res)
+        }
+        shift += 7;
+        if shift >= 64 {
+            return None
+        }
+    }
+}
@@ -1,20 +1,206 @@
 
-use crate::store::XrefsStore;
+use std::cmp::Ordering;
+use serde_json::{json, Value as JsonValue};
+use crate::document::combined::PointLink;
+use crate::document::common::DocumentType;
+use crate::store::MetaStore;
 use crate::load::report::{Failed, PathReporter};
-use super::data::Data;
+use crate::types::{Date, Marked, OperatingPeriods};
+use super::data::{parse_kilometrage, Data};
+use super::geometry::Geometry;
 
 
 //------------ Meta ----------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Meta {
+    /// The periods during which the line was in operation.
+    pub operating: OperatingPeriods,
+
+    /// The concessions granted for the line in the order of the events.
+    pub concessions: Vec<ConcessionPeriod>,
+
+    /// The parts of the line between consecutive junctions.
+    pub junction_segments: Vec<JunctionSegment>,
+
+    /// The geometry of the line’s current course.
+    pub geometry: Geometry,
 }
 
 impl Meta {
+    /// Line metadata needs to know which of its points are junctions and
+    /// the simplified geometry of the paths of its course.
+    pub const DEPENDENCIES: &'static [DocumentType] = &[
+        DocumentType::Path, DocumentType::Point
+    ];
+
     pub fn generate(
-        _data: &Data, _store: &XrefsStore, _report: &mut PathReporter,
+        data: &Data, store: &MetaStore, _report: &mut PathReporter,
     ) -> Result<Self, Failed> {
-        Ok(Meta { })
+        Ok(Meta {
+            operating: data.operating_periods(),
+            concessions: data.concession_periods(),
+            junction_segments: JunctionSegment::generate(data, store),
+            geometry: Geometry::generate(data, store),
+        })
+    }
+
+    /// Returns the concessions that may have been active at `date`.
+    pub fn concessions_at<'a>(
+        &'a self, date: &'a Date
+    ) -> impl Iterator<Item = &'a ConcessionPeriod> + 'a {
+        self.concessions.iter().filter(move |item| item.may_include(date))
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "operating": self.operating.to_json(),
+            "concessions": self.concessions.iter().map(
+                ConcessionPeriod::to_json
+            ).collect::<Vec<_>>(),
+            "junction_segments": self.junction_segments.iter().map(
+                JunctionSegment::to_json
+            ).collect::<Vec<_>>(),
+        })
     }
 }
 
+
+//------------ ConcessionPeriod ----------------------------------------------
+
+/// A concession granted for a line and how long it was valid.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConcessionPeriod {
+    /// The index of the event granting the concession.
+    pub event: usize,
+
+    /// The date the concession was granted if it is known.
+    pub start: Option<Marked<Date>>,
+
+    /// The date the concession expired if it was limited.
+    pub until: Option<Marked<Date>>,
+}
+
+impl ConcessionPeriod {
+    /// Returns whether the concession may have been active at `date`.
+    ///
+    /// An unknown start doesn’t limit the concession.
+    pub fn may_include(&self, date: &Date) -> bool {
+        if let Some(start) = self.start {
+            if start.cmp_range(date) == Some(Ordering::Greater) {
+                return false
+            }
+        }
+        !self.expired_before(date)
+    }
+
+    /// Returns whether the concession definitely expired before `date`.
+    pub fn expired_before(&self, date: &Date) -> bool {
+        self.until.map(|until| {
+            until.cmp_range(date) == Some(Ordering::Less)
+        }).unwrap_or(false)
+    }
+
+    /// Returns whether the concession may expire between two dates.
+    ///
+    /// Both dates are included in the range.
+    pub fn may_expire_between(&self, start: &Date, end: &Date) -> bool {
+        match self.until {
+            Some(until) => {
+                until.cmp_range(start) != Some(Ordering::Less)
+                    && until.cmp_range(end) != Some(Ordering::Greater)
+            }
+            None => false
+        }
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "event": self.event,
+            "start": self.start.map(|date| date.to_string()),
+            "until": self.until.map(|date| date.to_string()),
+        })
+    }
+}
+
+
+
+//------------ JunctionSegment -----------------------------------------------
+
+/// A part of a line between two consecutive junctions.
+///
+/// The junctions are given by their index in the line’s points. The first
+/// and last point of the line always end a segment, even if they aren’t
+/// junctions.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JunctionSegment {
+    /// The index of the junction the segment starts at.
+    pub start: usize,
+
+    /// The index of the junction the segment ends at.
+    pub end: usize,
+
+    /// The length of the segment in kilometres.
+    ///
+    /// The length is taken from the kilometrage of the two junctions. It
+    /// is `None` if either of them doesn’t have a kilometrage.
+    pub length: Option<f64>,
+}
+
+impl JunctionSegment {
+    /// Splits a line at its junctions.
+    fn generate(data: &Data, store: &MetaStore) -> Vec<Self> {
+        let points = &data.points;
+        let last = match points.len().checked_sub(1) {
+            Some(last) if last > 0 => last,
+            _ => return Vec::new()
+        };
+        let km = |index: usize| {
+            points[index].data(store).line_location(data.link())
+                .and_then(|(km, _)| km).and_then(parse_kilometrage)
+        };
+        let mut res = Vec::new();
+        let mut start = 0;
+        for (index, point) in points.iter().enumerate().skip(1) {
+            if index == last || point.meta(store).junction {
+                res.push(JunctionSegment {
+                    start,
+                    end: index,
+                    length: km(start).zip(km(index)).map(|(start, end)| {
+                        (end - start).abs()
+                    }),
+                });
+                start = index;
+            }
+        }
+        res
+    }
+
+    /// Returns the junction the segment starts at.
+    pub fn start_point(&self, line: &Data) -> PointLink {
+        line.points[self.start].into_value()
+    }
+
+    /// Returns the junction the segment ends at.
+    pub fn end_point(&self, line: &Data) -> PointLink {
+        line.points[self.end].into_value()
+    }
+
+    /// Returns the points between the two junctions.
+    pub fn intermediate<'a>(
+        &self, line: &'a Data
+    ) -> &'a [Marked<PointLink>] {
+        &line.points[self.start + 1..self.end]
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "start": self.start,
+            "end": self.end,
+            "length": self.length,
+        })
+    }
+}
@@ -1,9 +1,15 @@
 pub use self::data::*;
+pub use self::electrification::ElRegistry;
+pub use self::geometry::Geometry;
 pub use self::xrefs::Xrefs;
-pub use self::meta::Meta;
+pub use self::meta::{ConcessionPeriod, JunctionSegment, Meta};
+pub use self::profile::{Gradient, Profile, ProfilePoint};
 pub use super::combined::LineLink as Link;
 
 pub mod data;
+pub mod electrification;
+pub mod geometry;
 pub mod meta;
+pub mod profile;
 pub mod xrefs;
 
@@ -0,0 +1,131 @@
+//! The elevation profile of a line.
+
+use serde_json::{json, Value as JsonValue};
+use crate::document::combined;
+use crate::store::LinkTarget;
+use super::data::Data;
+
+
+//------------ Profile -------------------------------------------------------
+
+/// The elevation profile along the current course of a line.
+///
+/// The profile is assembled from the course segments of the line. Only
+/// nodes of the paths with a known elevation are included, so a line
+/// whose paths don’t have elevations has an empty profile.
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    /// The points of the profile ordered by distance.
+    points: Vec<ProfilePoint>,
+}
+
+impl Profile {
+    /// Creates the profile for a line.
+    ///
+    /// Course segments that cannot be resolved are skipped without
+    /// adding to the distance.
+    pub fn new(
+        line: &Data, store: &impl LinkTarget<combined::Data>
+    ) -> Self {
+        let mut points = Vec::new();
+        let mut base = 0.;
+        for segment in line.current.course.iter().flat_map(|(_, course)| {
+            course.iter()
+        }) {
+            let path = segment.path.data(store);
+            let profile = match path.profile_between(
+                &segment.start, &segment.end
+            ) {
+                Some(profile) => profile,
+                None => continue
+            };
+            for (distance, elevation) in profile {
+                let distance = base + distance;
+                // Consecutive segments share their end and start node.
+                if points.last().map(|last: &ProfilePoint| {
+                    last.distance == distance
+                }).unwrap_or(false) {
+                    continue
+                }
+                points.push(ProfilePoint { distance, elevation });
+            }
+            base += segment.length(store).unwrap_or(0.);
+        }
+        Profile { points }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn points(&self) -> &[ProfilePoint] {
+        &self.points
+    }
+
+    /// Returns the gradients between consecutive points of the profile.
+    pub fn gradients(&self) -> impl Iterator<Item = Gradient> + '_ {
+        self.points.windows(2).filter_map(|pair| {
+            let length = pair[1].distance - pair[0].distance;
+            if length <= 0. {
+                return None
+            }
+            Some(Gradient {
+                start: pair[0].distance,
+                end: pair[1].distance,
+                // Elevation is in metres and length in kilometres, so this
+                // is per mille.
+                gradient: (pair[1].elevation - pair[0].elevation) / length,
+            })
+        })
+    }
+
+    /// Returns the profile as JSON.
+    ///
+    /// The points are given as pairs of distance in kilometres and
+    /// elevation in metres, the gradients in per mille.
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "points": self.points.iter().map(|point| {
+                json!([point.distance, point.elevation])
+            }).collect::<Vec<_>>(),
+            "gradients": self.gradients().map(|gradient| {
+                json!({
+                    "start": gradient.start,
+                    "end": gradient.end,
+                    "gradient": gradient.gradient,
+                })
+            }).collect::<Vec<_>>(),
+        })
+    }
+}
+
+
+//------------ ProfilePoint --------------------------------------------------
+
+/// A single point of an elevation profile.
+#[derive(Clone, Copy, Debug)]
+pub struct ProfilePoint {
+    /// The distance from the start of the line in kilometres.
+    pub distance: f64,
+
+    /// The elevation above sea level in metres.
+    pub elevation: f64,
+}
+
+
+//------------ Gradient ------------------------------------------------------
+
+/// The gradient between two points of an elevation profile.
+#[derive(Clone, Copy, Debug)]
+pub struct Gradient {
+    /// The distance of the start from the start of the line in kilometres.
+    pub start: f64,
+
+    /// The distance of the end from the start of the line in kilometres.
+    pub end: f64,
+
+    /// The gradient in per mille.
+    ///
+    /// The gradient is positive if the line climbs.
+    pub gradient: f64,
+}
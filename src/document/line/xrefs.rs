@@ -1,4 +1,6 @@
-use crate::document::source;
+use serde_json::{json, Value as JsonValue};
+use crate::document::{custom, line, service, source, structure};
+use crate::document::combined::sorted_keys_to_json;
 use crate::store::DataStore;
 use crate::types::Set;
 
@@ -6,8 +8,22 @@ use crate::types::Set;
 //------------ Xrefs ---------------------------------------------------------
 
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Xrefs {
     source_regards: Set<source::Link>,
+    custom_regards: Set<custom::Link>,
+
+    /// The services running along this line.
+    pub services: Set<service::Link>,
+
+    /// The structures that name this line.
+    pub structures: Set<structure::Link>,
+
+    /// The lines this line reuses.
+    pub reuses: Set<line::Link>,
+
+    /// The lines that reuse this line.
+    pub reused_by: Set<line::Link>,
 }
 
 impl Xrefs {
@@ -15,7 +31,26 @@ impl Xrefs {
         &mut self.source_regards
     }
 
+    pub fn custom_regards_mut(&mut self) -> &mut Set<custom::Link> {
+        &mut self.custom_regards
+    }
+
     pub fn finalize(&mut self, _store: &DataStore) {
     }
+
+    pub fn to_json(&self, store: &DataStore) -> JsonValue {
+        json!({
+            "source_regards": sorted_keys_to_json(
+                self.source_regards.iter(), store
+            ),
+            "custom_regards": sorted_keys_to_json(
+                self.custom_regards.iter(), store
+            ),
+            "services": sorted_keys_to_json(self.services.iter(), store),
+            "structures": sorted_keys_to_json(self.structures.iter(), store),
+            "reuses": sorted_keys_to_json(self.reuses.iter(), store),
+            "reused_by": sorted_keys_to_json(self.reused_by.iter(), store),
+        })
+    }
 }
 
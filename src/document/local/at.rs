@@ -0,0 +1,36 @@
+//! Rules for Austria.
+
+use super::{CountryRules, is_digits, is_numbered};
+
+
+//------------ At ------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct At;
+
+impl CountryRules for At {
+    fn country(&self) -> &'static str {
+        "at"
+    }
+
+    fn check_line_number(&self, number: &str) -> Result<(), &'static str> {
+        if is_numbered(number, 5) {
+            Ok(())
+        }
+        else {
+            Err("up to five digits with optional lower case suffix")
+        }
+    }
+
+    fn check_line_code(
+        &self, attr: &str, value: &str
+    ) -> Result<(), &'static str> {
+        match attr {
+            "at.VzG" if !is_digits(value, 3, 5) => {
+                Err("three to five digits")
+            }
+            _ => Ok(())
+        }
+    }
+}
+
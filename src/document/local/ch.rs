@@ -0,0 +1,43 @@
+//! Rules for Switzerland.
+
+use super::{CountryRules, is_digits, is_numbered};
+
+
+//------------ Ch ------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Ch;
+
+impl CountryRules for Ch {
+    fn country(&self) -> &'static str {
+        "ch"
+    }
+
+    fn check_line_number(&self, number: &str) -> Result<(), &'static str> {
+        if is_numbered(number, 4) {
+            Ok(())
+        }
+        else {
+            Err("up to four digits with optional lower case suffix")
+        }
+    }
+
+    fn check_line_code(
+        &self, attr: &str, value: &str
+    ) -> Result<(), &'static str> {
+        // BAV line numbers may have a sub-number after a dot.
+        let valid = match value.split_once('.') {
+            Some((main, sub)) => {
+                is_digits(main, 1, 4) && is_digits(sub, 1, 2)
+            }
+            None => is_digits(value, 1, 4)
+        };
+        match attr {
+            "ch.BAV" if !valid => {
+                Err("up to four digits with optional sub-number")
+            }
+            _ => Ok(())
+        }
+    }
+}
+
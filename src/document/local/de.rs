@@ -0,0 +1,93 @@
+//! Rules for Germany.
+
+use crate::load::report::PathReporter;
+use crate::types::{IntoMarked, Marked, Text};
+use super::{CountryRules, LongName, is_digits, is_numbered};
+
+
+//------------ De ------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct De;
+
+impl CountryRules for De {
+    fn country(&self) -> &'static str {
+        "de"
+    }
+
+    fn check_line_number(&self, number: &str) -> Result<(), &'static str> {
+        if is_numbered(number, 4) {
+            Ok(())
+        }
+        else {
+            Err("up to four digits with optional lower case suffix")
+        }
+    }
+
+    fn check_point_code(
+        &self, attr: &str, value: &str
+    ) -> Result<(), &'static str> {
+        match attr {
+            "de.DS100" if !is_ds100(value) => {
+                Err("up to five upper case letters, digits, or spaces")
+            }
+            "de.dstnr" | "de.lknr" if !is_number(value, 6) => {
+                Err("a non-zero number of up to six digits")
+            }
+            _ => Ok(())
+        }
+    }
+
+    fn check_line_code(
+        &self, attr: &str, value: &str
+    ) -> Result<(), &'static str> {
+        match attr {
+            "de.VzG" if !is_digits(value, 4, 4) => Err("four digits"),
+            _ => Ok(())
+        }
+    }
+}
+
+
+//------------ Checks --------------------------------------------------------
+
+/// Checks the length of a `de.name16` value.
+///
+/// The name is used where space is limited to 16 characters. Longer names
+/// are reported as a warning.
+pub fn check_name16(value: &Marked<Text>, report: &mut PathReporter) {
+    const MAX: usize = 16;
+
+    if value.chars().count() > MAX {
+        report.warning(
+            LongName {
+                attr: "de.name16", value: value.as_value().to_string(),
+                max: MAX
+            }.marked(value.location())
+        )
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns whether `value` is a positive number of up to `max` digits.
+///
+/// Leading zeros are allowed but the number can’t be zero.
+fn is_number(value: &str, max: usize) -> bool {
+    is_digits(value, 1, max) && value.bytes().any(|ch| ch != b'0')
+}
+
+/// Returns whether `value` is a valid DS 100 code.
+///
+/// Codes start with an upper case letter and may contain spaces for
+/// padding, so `"MH  N"` is a valid code.
+fn is_ds100(value: &str) -> bool {
+    value.len() <= 5
+        && value.starts_with(|ch: char| ch.is_ascii_uppercase())
+        && !value.ends_with(' ')
+        && value.chars().all(|ch| {
+            ch.is_ascii_uppercase() || ch.is_ascii_digit() || ch == ' '
+        })
+}
+
@@ -0,0 +1,36 @@
+//! Rules for Denmark.
+
+use super::{CountryRules, is_abbreviation, is_numbered};
+
+
+//------------ Dk ------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Dk;
+
+impl CountryRules for Dk {
+    fn country(&self) -> &'static str {
+        "dk"
+    }
+
+    fn check_line_number(&self, number: &str) -> Result<(), &'static str> {
+        if is_numbered(number, 3) {
+            Ok(())
+        }
+        else {
+            Err("up to three digits with optional lower case suffix")
+        }
+    }
+
+    fn check_point_code(
+        &self, attr: &str, value: &str
+    ) -> Result<(), &'static str> {
+        match attr {
+            "dk.ref" if !is_abbreviation(value, 1, 3) => {
+                Err("up to three letters starting with an upper case letter")
+            }
+            _ => Ok(())
+        }
+    }
+}
+
@@ -0,0 +1,42 @@
+//! Rules for France.
+
+use super::{CountryRules, is_digits};
+
+
+//------------ Fr ------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Fr;
+
+impl CountryRules for Fr {
+    fn country(&self) -> &'static str {
+        "fr"
+    }
+
+    fn check_line_number(&self, number: &str) -> Result<(), &'static str> {
+        if is_digits(number, 6, 6) {
+            Ok(())
+        }
+        else {
+            Err("six digits")
+        }
+    }
+
+    fn check_line_code(
+        &self, attr: &str, value: &str
+    ) -> Result<(), &'static str> {
+        // RFN numbers are often written with a space after the first three
+        // digits.
+        let valid = match value.split_once(' ') {
+            Some((left, right)) => {
+                is_digits(left, 3, 3) && is_digits(right, 3, 3)
+            }
+            None => is_digits(value, 6, 6)
+        };
+        match attr {
+            "fr.RFN" if !valid => Err("six digits"),
+            _ => Ok(())
+        }
+    }
+}
+
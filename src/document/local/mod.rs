@@ -0,0 +1,228 @@
+//! Country-specific rules.
+//!
+//! Keys of lines and the codes attached to lines and points follow the
+//! conventions of the country they belong to. Each country that has such
+//! conventions provides an implementation of [`CountryRules`]. The rules
+//! are looked up via the country code, i.e., the second component of a
+//! line key or the prefix of a code attribute such as `de.VzG`.
+
+use derive_more::Display;
+use crate::load::report::PathReporter;
+use crate::types::{IntoMarked, Key, Marked, Text};
+
+pub use self::at::At;
+pub use self::ch::Ch;
+pub use self::de::De;
+pub use self::dk::Dk;
+pub use self::fr::Fr;
+pub use self::nl::Nl;
+
+pub mod at;
+pub mod ch;
+pub mod de;
+pub mod dk;
+pub mod fr;
+pub mod nl;
+pub mod uic;
+
+
+//------------ CountryRules --------------------------------------------------
+
+/// The rules for keys and codes of a country.
+///
+/// Each check returns a description of the expected format if the value
+/// doesn’t follow the rules. The default implementations accept any
+/// value.
+pub trait CountryRules: Sync {
+    /// Returns the lower case country code the rules apply to.
+    fn country(&self) -> &'static str;
+
+    /// Checks the line number, i.e., the part of a line key after the
+    /// country.
+    fn check_line_number(&self, _number: &str) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    /// Checks the value of a point code attribute such as `de.DS100`.
+    fn check_point_code(
+        &self, _attr: &str, _value: &str
+    ) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    /// Checks the value of a line code attribute such as `de.VzG`.
+    fn check_line_code(
+        &self, _attr: &str, _value: &str
+    ) -> Result<(), &'static str> {
+        Ok(())
+    }
+}
+
+
+//------------ Registry ------------------------------------------------------
+
+/// The rules of all countries that have them.
+pub static RULES: &[&dyn CountryRules] = &[&At, &Ch, &De, &Dk, &Fr, &Nl];
+
+/// Returns the rules for the given lower case country code.
+pub fn rules(country: &str) -> Option<&'static dyn CountryRules> {
+    RULES.iter().copied().find(|rules| rules.country() == country)
+}
+
+/// Returns the rules for a code attribute based on its prefix.
+fn attr_rules(attr: &str) -> Option<&'static dyn CountryRules> {
+    rules(attr.split_once('.')?.0)
+}
+
+
+//------------ Checks --------------------------------------------------------
+
+/// Checks whether a key follows the namespace rules.
+///
+/// All keys consist of at least two non-empty parts separated by dots and
+/// must not contain white space. The second part normally is the country.
+/// If there are rules for that country, line keys also need to follow
+/// them for their line number.
+///
+/// Returns a description of the expected format if the key is invalid.
+pub fn is_valid_key(key: &Key) -> Result<(), &'static str> {
+    if key.split('.').count() < 2
+        || key.split('.').any(str::is_empty)
+        || key.chars().any(char::is_whitespace)
+    {
+        return Err("two or more parts separated by dots without white space")
+    }
+    match line_rules(key) {
+        Some((rules, number)) => rules.check_line_number(number),
+        None => Ok(())
+    }
+}
+
+/// Checks the value of a point code attribute.
+///
+/// Codes without a country prefix, such as the `PLC` or `uic`, are
+/// checked here directly. Invalid codes are reported as warnings.
+pub fn check_point_code(
+    attr: &'static str, value: &Marked<Text>, report: &mut PathReporter
+) {
+    let res = match attr {
+        "PLC" => check_plc(value.as_str()),
+        "uic" => uic::check_code(value.as_str()),
+        _ => match attr_rules(attr) {
+            Some(rules) => rules.check_point_code(attr, value.as_str()),
+            None => return
+        }
+    };
+    if let Err(expected) = res {
+        report.warning(InvalidCode {
+            attr, value: value.as_value().to_string(), expected
+        }.marked(value.location()))
+    }
+}
+
+/// Checks the value of a line code attribute.
+///
+/// Invalid codes are reported as warnings.
+pub fn check_line_code(
+    attr: &'static str, value: &Marked<Text>, report: &mut PathReporter
+) {
+    let rules = match attr_rules(attr) {
+        Some(rules) => rules,
+        None => return
+    };
+    if let Err(expected) = rules.check_line_code(attr, value.as_str()) {
+        report.warning(InvalidCode {
+            attr, value: value.as_value().to_string(), expected
+        }.marked(value.location()))
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the country’s rules and the line number of a line key.
+fn line_rules(key: &Key) -> Option<(&'static dyn CountryRules, &str)> {
+    if key.namespace().strip_prefix("line.")?.is_empty() {
+        return None
+    }
+    Some((rules(key.country()?)?, key.local_part()))
+}
+
+/// Checks a primary location code.
+///
+/// Primary location codes as used in the European location reference
+/// data consist of the two letter country code followed by the five digit
+/// location number. The country code has to be one of a country with
+/// location codes.
+fn check_plc(value: &str) -> Result<(), &'static str> {
+    const EXPECTED: &str = "two letter country code and five digits";
+
+    let (country, number) = match value.char_indices().nth(2) {
+        Some((idx, _)) => value.split_at(idx),
+        None => return Err(EXPECTED),
+    };
+    if !country.bytes().all(|ch| ch.is_ascii_uppercase())
+        || !is_digits(number, 5, 5)
+    {
+        return Err(EXPECTED)
+    }
+    if !PLC_COUNTRIES.contains(&country) {
+        return Err("a known country code")
+    }
+    Ok(())
+}
+
+/// The countries that assign primary location codes.
+const PLC_COUNTRIES: &[&str] = &[
+    "AT", "BA", "BE", "BG", "CH", "CZ", "DE", "DK", "EE", "ES", "FI", "FR",
+    "GB", "GR", "HR", "HU", "IE", "IT", "LT", "LU", "LV", "ME", "MK", "NL",
+    "NO", "PL", "PT", "RO", "RS", "SE", "SI", "SK", "TR",
+];
+
+/// Returns whether `s` consists of between `min` and `max` ASCII digits.
+fn is_digits(s: &str, min: usize, max: usize) -> bool {
+    (min..=max).contains(&s.len()) && s.bytes().all(|ch| ch.is_ascii_digit())
+}
+
+/// Returns whether `s` is a number with an optional lower case suffix.
+///
+/// The number itself may have at most `max` digits.
+fn is_numbered(s: &str, max: usize) -> bool {
+    let digits = s.bytes().take_while(u8::is_ascii_digit).count();
+    (1..=max).contains(&digits)
+        && s[digits..].bytes().all(|ch| ch.is_ascii_lowercase())
+}
+
+/// Returns whether `s` is an abbreviation of letters.
+///
+/// The abbreviation has between `min` and `max` letters and starts with an
+/// upper case letter.
+fn is_abbreviation(s: &str, min: usize, max: usize) -> bool {
+    let mut chars = s.chars();
+    chars.next().map(char::is_uppercase).unwrap_or(false)
+        && chars.all(char::is_alphabetic)
+        && (min..=max).contains(&s.chars().count())
+}
+
+
+//============ Errors ========================================================
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="invalid {} '{}': expected {}", attr, value, expected)]
+pub struct InvalidCode {
+    attr: &'static str,
+    value: String,
+    expected: &'static str,
+}
+
+#[derive(Clone, Debug, Display)]
+#[display(
+    fmt="{} '{}' is longer than {} characters",
+    attr, value, max
+)]
+pub struct LongName {
+    attr: &'static str,
+    value: String,
+    max: usize,
+}
+
@@ -0,0 +1,36 @@
+//! Rules for the Netherlands.
+
+use super::{CountryRules, is_abbreviation, is_numbered};
+
+
+//------------ Nl ------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Nl;
+
+impl CountryRules for Nl {
+    fn country(&self) -> &'static str {
+        "nl"
+    }
+
+    fn check_line_number(&self, number: &str) -> Result<(), &'static str> {
+        if is_numbered(number, 3) {
+            Ok(())
+        }
+        else {
+            Err("up to three digits with optional lower case suffix")
+        }
+    }
+
+    fn check_point_code(
+        &self, attr: &str, value: &str
+    ) -> Result<(), &'static str> {
+        match attr {
+            "nl.afk" if !is_abbreviation(value, 1, 5) => {
+                Err("up to five letters starting with an upper case letter")
+            }
+            _ => Ok(())
+        }
+    }
+}
+
@@ -0,0 +1,51 @@
+//! UIC station codes.
+//!
+//! UIC station codes consist of the two digit UIC country code followed by
+//! a five digit station number, e.g., `8011160` for Berlin Hbf. They are
+//! used by many timetable and open transport datasets.
+
+use super::is_digits;
+
+
+//------------ Checks --------------------------------------------------------
+
+/// Checks the format of a UIC station code.
+pub fn check_code(value: &str) -> Result<(), &'static str> {
+    if !is_digits(value, 7, 7) {
+        return Err("seven digits")
+    }
+    if country(value).is_none() {
+        return Err("a known UIC country code as the first two digits")
+    }
+    Ok(())
+}
+
+/// Returns the ISO country code for a UIC station code.
+///
+/// The country is determined from the first two digits. Returns `None`
+/// if they aren’t a known UIC country code.
+pub fn country(code: &str) -> Option<&'static str> {
+    let prefix = code.get(..2)?;
+    COUNTRIES.iter().find(|(uic, _)| *uic == prefix).map(|(_, iso)| *iso)
+}
+
+
+//------------ Country Codes -------------------------------------------------
+
+/// The UIC country codes and the ISO codes of their countries.
+const COUNTRIES: &[(&str, &str)] = &[
+    ("10", "FI"), ("20", "RU"), ("21", "BY"), ("22", "UA"), ("23", "MD"),
+    ("24", "LT"), ("25", "LV"), ("26", "EE"), ("27", "KZ"), ("28", "GE"),
+    ("29", "UZ"), ("30", "KP"), ("31", "MN"), ("32", "VN"), ("33", "CN"),
+    ("41", "AL"), ("44", "BA"), ("49", "BA"), ("50", "BA"), ("51", "PL"),
+    ("52", "BG"), ("53", "RO"), ("54", "CZ"), ("55", "HU"), ("56", "SK"),
+    ("57", "AZ"), ("58", "AM"), ("59", "KG"), ("60", "IE"), ("61", "KR"),
+    ("62", "ME"), ("65", "MK"), ("66", "TJ"), ("67", "TM"), ("68", "AF"),
+    ("70", "GB"), ("71", "ES"), ("72", "RS"), ("73", "GR"), ("74", "SE"),
+    ("75", "TR"), ("76", "NO"), ("78", "HR"), ("79", "SI"), ("80", "DE"),
+    ("81", "AT"), ("82", "LU"), ("83", "IT"), ("84", "NL"), ("85", "CH"),
+    ("86", "DK"), ("87", "FR"), ("88", "BE"), ("90", "EG"), ("91", "TN"),
+    ("92", "DZ"), ("93", "MA"), ("94", "PT"), ("95", "IL"), ("96", "IR"),
+    ("97", "SY"), ("98", "LB"), ("99", "IQ"),
+];
+
@@ -1,9 +1,13 @@
 pub use self::combined::{Data, Document, Meta, Xrefs};
 
+pub mod custom;
 pub mod entity;
+pub mod layout;
 pub mod line;
+pub mod local;
 pub mod path;
 pub mod point;
+pub mod service;
 pub mod source;
 pub mod structure;
 
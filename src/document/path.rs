@@ -1,19 +1,22 @@
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use std::f64::INFINITY;
 use std::str::FromStr;
 use derive_more::Display;
 use osmxml::elements::{MemberType, Osm, Relation};
+use serde_json::{json, Value as JsonValue};
 use crate::catalogue::CatalogueBuilder;
 use crate::load::report;
 use crate::load::report::{Failed, Origin, PathReporter};
 use crate::load::yaml::Mapping;
 use crate::store::{
-    DataStore, DocumentLink, FullStore, StoreLoader, XrefsBuilder, XrefsStore,
+    DataStore, DocumentLink, FullStore, MetaStore, StoreLoader, XrefsBuilder,
 };
-use crate::types::{IntoMarked, Location, Key, Marked, Set};
+use crate::types::{IntoMarked, Location, Key, Marked, Set, Text};
 use crate::types::key::InvalidKey;
-use super::source;
-use super::common::{Common, Progress};
+use super::{custom, source};
+use super::combined::sorted_keys_to_json;
+use super::common::{Common, DocumentType, Progress};
 
 
 //------------ Link ----------------------------------------------------------
@@ -26,12 +29,58 @@ pub use super::combined::PathLink as Link;
 pub use super::combined::PathDocument as Document;
 
 impl<'a> Document<'a> {
+    /// Returns the geometry of the path suitable for a map zoom level.
+    ///
+    /// The geometry is taken from the simplified versions prepared when
+    /// generating the meta data. For zoom levels beyond the most detailed
+    /// of them, all nodes are returned.
+    pub fn geometry(self, zoom: u8) -> Vec<Coord> {
+        let data = self.data();
+        match self.meta().level(zoom) {
+            Some(nodes) => {
+                nodes.iter().map(|&idx| data.nodes[idx].into()).collect()
+            }
+            None => data.nodes.iter().map(|&node| node.into()).collect()
+        }
+    }
+
+    /// Returns the geometry between two named nodes for a zoom level.
+    ///
+    /// The coordinates are ordered from `start` to `end`. Both nodes are
+    /// always included even if the simplified geometry doesn’t contain
+    /// them. Returns `None` if either of the nodes doesn’t exist.
+    pub fn geometry_between(
+        self, start: &str, end: &str, zoom: u8
+    ) -> Option<Vec<Coord>> {
+        let data = self.data();
+        let start = data.get_pos(start)?;
+        let end = data.get_pos(end)?;
+        let (low, high) = if start <= end { (start, end) }
+                          else { (end, start) };
+        let mut nodes = vec![low];
+        if low < high {
+            match self.meta().level(zoom) {
+                Some(level) => {
+                    nodes.extend(level.iter().copied().filter(|&idx| {
+                        idx > low && idx < high
+                    }))
+                }
+                None => nodes.extend(low + 1..high)
+            }
+            nodes.push(high);
+        }
+        if start > end {
+            nodes.reverse()
+        }
+        Some(nodes.into_iter().map(|idx| data.nodes[idx].into()).collect())
+    }
 }
 
 
 //------------ Data ----------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Data {
     pub common: Common,
 
@@ -39,8 +88,8 @@ pub struct Data {
     pub nodes: Vec<Node>,
     pub source: Vec<source::Link>,
 
-    pub node_names: HashMap<String, usize>,
-    pub node_descr: HashMap<usize, String>,
+    /// The named nodes of the path.
+    pub node_names: NodeRegistry,
 }
 
 impl Data {
@@ -56,21 +105,143 @@ impl Data {
         &self.common.origin
     }
 
-    pub fn node(&self, pos: usize) -> Option<Node> {
+    /// Returns the node at the given position.
+    pub fn node_at(&self, pos: usize) -> Option<Node> {
         self.nodes.get(pos).copied()
     }
 
+    /// Returns the coordinates of the node with the given name.
+    pub fn node(&self, name: &str) -> Option<Coord> {
+        self.get_pos(name).and_then(|pos| {
+            self.node_at(pos)
+        }).map(Into::into)
+    }
+
+    /// Returns the position of the node with the given name.
     pub fn get_pos(&self, name: &str) -> Option<usize> {
-        self.node_names.get(name).copied()
+        self.node_names.get(name)
+    }
+
+    /// Checks that a node referenced by another document exists.
+    ///
+    /// Reports an error at the location of the reference if the path has
+    /// no node of that name.
+    pub fn check_node(
+        &self, name: &Marked<Text>, report: &mut PathReporter
+    ) -> Result<(), Failed> {
+        if self.node_names.contains(name.as_str()) {
+            Ok(())
+        }
+        else {
+            report.error(UnknownNodeName {
+                path: self.key().clone(),
+                node: name.as_value().to_string(),
+            }.marked(name.location()));
+            Err(Failed)
+        }
     }
 
-    pub fn get_coord(&self, name: &str) -> Option<Coord> {
-        self.get_pos(name).and_then(|pos| self.node(pos)).map(Into::into)
+    /// Returns the indexes of the nodes kept after simplification.
+    ///
+    /// This uses the Douglas-Peucker algorithm with the given tolerance in
+    /// metres. The first and last node are always kept.
+    pub fn simplify(&self, tolerance: f64) -> Vec<usize> {
+        if self.nodes.len() < 3 {
+            return (0..self.nodes.len()).collect()
+        }
+
+        // Project the nodes onto a plane in metres. This is precise enough
+        // for the length of a path.
+        let lat0 = (
+            self.nodes.iter().map(|node| node.lat).sum::<f64>()
+            / self.nodes.len() as f64
+        ).to_radians().cos();
+        let points: Vec<_> = self.nodes.iter().map(|node| {
+            (
+                node.lon.to_radians() * lat0 * Coord::EARTH_RADIUS * 1000.,
+                node.lat.to_radians() * Coord::EARTH_RADIUS * 1000.,
+            )
+        }).collect();
+
+        let mut keep = vec![false; points.len()];
+        keep[0] = true;
+        keep[points.len() - 1] = true;
+        let mut stack = vec![(0, points.len() - 1)];
+        while let Some((first, last)) = stack.pop() {
+            let mut max = 0.;
+            let mut max_idx = first;
+            for idx in first + 1..last {
+                let dist = segment_distance(
+                    points[idx], points[first], points[last]
+                );
+                if dist > max {
+                    max = dist;
+                    max_idx = idx;
+                }
+            }
+            if max > tolerance {
+                keep[max_idx] = true;
+                stack.push((first, max_idx));
+                stack.push((max_idx, last));
+            }
+        }
+        keep.iter().enumerate().filter_map(|(idx, keep)| {
+            keep.then_some(idx)
+        }).collect()
+    }
+
+    /// Returns the elevation profile between two named nodes.
+    ///
+    /// The profile runs from `start` to `end` and contains the distance
+    /// from `start` in kilometres and the elevation in metres of each node
+    /// with a known elevation. Returns `None` if either of the names isn’t
+    /// known.
+    pub fn profile_between(
+        &self, start: &str, end: &str
+    ) -> Option<Vec<(f64, f64)>> {
+        let start = self.get_pos(start)?;
+        let end = self.get_pos(end)?;
+        let nodes: Vec<_> = if start <= end {
+            self.nodes[start..=end].to_vec()
+        }
+        else {
+            self.nodes[end..=start].iter().rev().copied().collect()
+        };
+        let mut res = Vec::new();
+        let mut distance = 0.;
+        for (idx, node) in nodes.iter().enumerate() {
+            if idx > 0 {
+                distance += Coord::from(nodes[idx - 1]).distance(
+                    (*node).into()
+                );
+            }
+            if let Some(ele) = node.ele {
+                res.push((distance, ele));
+            }
+        }
+        Some(res)
+    }
+
+    /// Returns the length in kilometres between two named nodes.
+    ///
+    /// The nodes can be given in either order. Returns `None` if either of
+    /// the names isn’t known.
+    pub fn length_between(&self, start: &str, end: &str) -> Option<f64> {
+        let start = self.get_pos(start)?;
+        let end = self.get_pos(end)?;
+        let (start, end) = if start <= end { (start, end) }
+                           else { (end, start) };
+        Some(
+            self.nodes[start..=end].windows(2).map(|pair| {
+                Coord::from(pair[0]).distance(pair[1].into())
+            }).sum()
+        )
     }
 }
 
 impl Data {
-    fn new(key: Key, path: report::Path) -> Self {
+    /// Creates a new, empty path with the given key loaded from `path`.
+    pub fn new(key: Key, path: report::Path) -> Self {
         Data {
             common: Common::new(
                 key.marked(Location::NONE),
@@ -81,7 +252,19 @@ impl Data {
             nodes: Vec::new(),
             source: Vec::new(),
             node_names: Default::default(),
-            node_descr: Default::default(),
+        }
+    }
+
+    /// Creates a placeholder for a path that failed to load.
+    pub fn placeholder(
+        key: Marked<Key>, _link: DocumentLink, origin: Origin
+    ) -> Self {
+        Data {
+            common: Common::placeholder(key, origin),
+            name: None,
+            nodes: Vec::new(),
+            source: Vec::new(),
+            node_names: Default::default(),
         }
     }
 
@@ -195,18 +378,7 @@ impl Data {
             for id in way_nodes {
                 let (node, name, descr, post_tension)
                     = Self::load_node(*id, osm, tension, report)?;
-                if let Some(name) = name {
-                    if self.node_names.insert(
-                        name.clone(), self.nodes.len()
-                    ).is_some()
-                    {
-                        report.unmarked_error(DuplicateName(name));
-                    }
-                }
-                if let Some(descr) = descr {
-                    self.node_descr.insert(self.nodes.len(), descr);
-                }
-                self.nodes.push(node);
+                self.push_node(node, name, descr, report);
                 last_tension = post_tension;
                 last_id = Some(id);
             }
@@ -247,10 +419,20 @@ impl Data {
             },
             None => (tension, false)
         };
+        let ele = match node.tags().get("ele") {
+            Some(ele) => match Self::load_f64(ele) {
+                Some(ele) => Some(ele),
+                None => {
+                    report.unmarked_warning(InvalidEle(id));
+                    None
+                }
+            },
+            None => None
+        };
         let name = node.tags().get("name").map(String::from);
         let description = node.tags().get("description").map(String::from);
         Ok((
-            Node::new(node.lon(), node.lat(), pre, post),
+            Node::new(node.lon(), node.lat(), pre, post).with_ele(ele),
             name,
             description,
             have_post
@@ -267,10 +449,44 @@ impl Data {
         context: &StoreLoader,
         report: &mut PathReporter
     ) -> Result<(), Failed> {
-        let source = match relation.tags_mut().remove("source") {
-            Some(source) => source,
-            None => return Ok(())
-        };
+        match relation.tags_mut().remove("source") {
+            Some(source) => self.add_source(&source, context, report),
+            None => Ok(())
+        }
+    }
+
+    /// Appends a node to the path.
+    ///
+    /// If the node has a name, it can be used by other documents to refer
+    /// to the node. Names must be unique within a path. If a name is used
+    /// again, an error is reported and the name keeps referring to the
+    /// first node.
+    pub fn push_node(
+        &mut self,
+        node: Node,
+        name: Option<String>,
+        descr: Option<String>,
+        report: &mut PathReporter
+    ) {
+        let pos = self.nodes.len();
+        if let Some(name) = name {
+            if let Err(first) = self.node_names.insert(name.clone(), pos) {
+                report.unmarked_error(DuplicateName { name, first, pos });
+            }
+        }
+        if let Some(descr) = descr {
+            self.node_names.set_description(pos, descr);
+        }
+        self.nodes.push(node);
+    }
+
+    /// Adds the source documents given as whitespace separated keys.
+    pub fn add_source(
+        &mut self,
+        source: &str,
+        context: &StoreLoader,
+        report: &mut PathReporter
+    ) -> Result<(), Failed> {
         for item in source.split_whitespace() {
             let key = match Key::from_str(item) {
                 Ok(key) => key,
@@ -309,8 +525,10 @@ impl Data {
 //------------ Xrefs ---------------------------------------------------------
 
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Xrefs {
     source_regards: Set<source::Link>,
+    custom_regards: Set<custom::Link>,
 }
 
 impl Xrefs {
@@ -318,21 +536,163 @@ impl Xrefs {
         &mut self.source_regards
     }
 
+    pub fn custom_regards_mut(&mut self) -> &mut Set<custom::Link> {
+        &mut self.custom_regards
+    }
+
     pub fn finalize(&mut self, _store: &DataStore) {
     }
+
+    pub fn to_json(&self, store: &DataStore) -> JsonValue {
+        json!({
+            "source_regards": sorted_keys_to_json(
+                self.source_regards.iter(), store
+            ),
+            "custom_regards": sorted_keys_to_json(
+                self.custom_regards.iter(), store
+            ),
+        })
+    }
 }
 
 
 //------------ Meta ----------------------------------------------------------
 
 #[derive(Clone, Debug)]
-pub struct Meta;
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Meta {
+    /// The simplified geometry for each of the `ZOOM_LEVELS`.
+    ///
+    /// Each level contains the indexes of the nodes that are kept.
+    levels: Vec<Vec<usize>>,
+}
 
 impl Meta {
+    /// The map zoom levels for which simplified geometry is prepared.
+    pub const ZOOM_LEVELS: &'static [u8] = &[4, 6, 8, 10, 12, 14];
+
+    /// Path metadata only uses the path’s own geometry.
+    pub const DEPENDENCIES: &'static [DocumentType] = &[];
+
     pub fn generate(
-        _data: &Data, _store: &XrefsStore, _report: &mut PathReporter,
+        data: &Data, _store: &MetaStore, _report: &mut PathReporter,
     ) -> Result<Self, Failed> {
-        Ok(Meta)
+        Ok(Meta {
+            levels: Self::ZOOM_LEVELS.iter().map(|&zoom| {
+                data.simplify(Self::tolerance(zoom))
+            }).collect()
+        })
+    }
+
+    /// Returns the simplification tolerance in metres for a zoom level.
+    ///
+    /// This is the size of a pixel of a 256 pixel map tile at the equator.
+    pub fn tolerance(zoom: u8) -> f64 {
+        156_543.03 / f64::from(1u32 << zoom.min(31))
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "zoom_levels": Self::ZOOM_LEVELS,
+        })
+    }
+
+    /// Returns the simplified levels a node is part of.
+    ///
+    /// Bit `n` of the result is set if the node is kept in the level for
+    /// the `n`th entry of `ZOOM_LEVELS`.
+    pub fn level_mask(&self, node: usize) -> u8 {
+        self.levels.iter().enumerate().fold(0, |mask, (idx, level)| {
+            if level.binary_search(&node).is_ok() {
+                mask | (1 << idx)
+            }
+            else {
+                mask
+            }
+        })
+    }
+
+    /// Returns the index into `ZOOM_LEVELS` of the level used for `zoom`.
+    ///
+    /// Returns `None` if the full geometry should be used.
+    pub fn level_index(zoom: u8) -> Option<usize> {
+        Self::ZOOM_LEVELS.iter().position(|&level| level >= zoom)
+    }
+
+    /// Returns the node indexes of the simplified geometry for `zoom`.
+    ///
+    /// Returns the least simplified level that is still detailed enough
+    /// or `None` if the full geometry should be used.
+    pub fn level(&self, zoom: u8) -> Option<&[usize]> {
+        Self::level_index(zoom).map(|idx| self.levels[idx].as_slice())
+    }
+}
+
+
+//------------ NodeRegistry --------------------------------------------------
+
+/// The names and descriptions of the nodes of a path.
+///
+/// Other documents refer to nodes of a path by their name, so names are
+/// unique within a path.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeRegistry {
+    /// The position of each named node.
+    names: HashMap<String, usize>,
+
+    /// The description of nodes by their position.
+    descr: HashMap<usize, String>,
+}
+
+impl NodeRegistry {
+    /// Returns the number of named nodes.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Returns whether there is a node with the given name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains_key(name)
+    }
+
+    /// Returns the position of the node with the given name.
+    pub fn get(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    /// Returns the description of the node at the given position.
+    pub fn description(&self, pos: usize) -> Option<&str> {
+        self.descr.get(&pos).map(String::as_str)
+    }
+
+    /// Returns an iterator over the names and positions of all nodes.
+    ///
+    /// The names are returned in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, usize)> + '_ {
+        self.names.iter().map(|(name, pos)| (name.as_str(), *pos))
+    }
+
+    /// Adds a name for the node at the given position.
+    ///
+    /// If the name is already taken, leaves the registry unchanged and
+    /// returns the position of the node that has it.
+    fn insert(&mut self, name: String, pos: usize) -> Result<(), usize> {
+        match self.names.entry(name) {
+            Entry::Occupied(entry) => Err(*entry.get()),
+            Entry::Vacant(entry) => {
+                entry.insert(pos);
+                Ok(())
+            }
+        }
+    }
+
+    fn set_description(&mut self, pos: usize, descr: String) {
+        self.descr.insert(pos, descr);
     }
 }
 
@@ -340,16 +700,26 @@ impl Meta {
 //------------ Node ----------------------------------------------------------
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Node {
     pub lon: f64,
     pub lat: f64,
     pub pre: f64,
     pub post: f64,
+
+    /// The elevation above sea level in metres if it is known.
+    pub ele: Option<f64>,
 }
 
 impl Node {
     pub fn new(lon: f64, lat: f64, pre: f64, post: f64) -> Self {
-        Node { lon, lat, pre, post }
+        Node { lon, lat, pre, post, ele: None }
+    }
+
+    /// Sets the elevation of the node.
+    pub fn with_ele(mut self, ele: Option<f64>) -> Self {
+        self.ele = ele;
+        self
     }
 }
 
@@ -357,11 +727,28 @@ impl Node {
 //------------ Coord ---------------------------------------------------------
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Coord {
     pub lon: f64,
     pub lat: f64,
 }
 
+impl Coord {
+    /// Mean earth radius in kilometres.
+    pub const EARTH_RADIUS: f64 = 6371.0;
+
+    /// Returns the great-circle distance to `other` in kilometres.
+    pub fn distance(self, other: Coord) -> f64 {
+        let lat1 = self.lat.to_radians();
+        let lat2 = other.lat.to_radians();
+        let dlat = lat2 - lat1;
+        let dlon = (other.lon - self.lon).to_radians();
+        let a = (dlat / 2.).sin().powi(2)
+            + lat1.cos() * lat2.cos() * (dlon / 2.).sin().powi(2);
+        2. * Self::EARTH_RADIUS * a.sqrt().asin()
+    }
+}
+
 impl From<Node> for Coord {
     fn from(node: Node) -> Self {
         Coord { lon: node.lon, lat: node.lat }
@@ -369,6 +756,26 @@ impl From<Node> for Coord {
 }
 
 
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the distance of `point` from the segment between `start` and `end`.
+fn segment_distance(
+    point: (f64, f64), start: (f64, f64), end: (f64, f64)
+) -> f64 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let len = dx * dx + dy * dy;
+    let t = if len == 0. {
+        0.
+    }
+    else {
+        (((point.0 - start.0) * dx + (point.1 - start.1) * dy) / len)
+            .clamp(0., 1.)
+    };
+    let (x, y) = (start.0 + t * dx, start.1 + t * dy);
+    ((point.0 - x).powi(2) + (point.1 - y).powi(2)).sqrt()
+}
+
+
 //============ Errors ========================================================
 
 #[derive(Clone, Copy, Debug, Display)]
@@ -431,7 +838,24 @@ pub struct InvalidPre(i64);
 #[display(fmt="invalid post tag in node {}", _0)]
 pub struct InvalidPost(i64);
 
+#[derive(Clone, Copy, Debug, Display)]
+#[display(fmt="invalid ele tag in node {}", _0)]
+pub struct InvalidEle(i64);
+
 #[derive(Clone, Debug, Display)]
-#[display(fmt="duplicate node name '{}'", _0)]
-pub struct DuplicateName(String);
+#[display(
+    fmt="duplicate node name '{}' at nodes {} and {}", name, first, pos
+)]
+pub struct DuplicateName {
+    name: String,
+    first: usize,
+    pos: usize,
+}
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="path '{}' has no node '{}'", path, node)]
+pub struct UnknownNodeName {
+    path: Key,
+    node: String,
+}
 
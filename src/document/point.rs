@@ -1,19 +1,27 @@
 
 use std::collections::{HashSet, HashMap};
 use derive_more::Display;
-use crate::catalogue::CatalogueBuilder;
+use serde_json::{json, Value as JsonValue};
+use yaml_rust::Yaml;
+use crate::catalogue::{CatalogueBuilder, EventKind, NameValidity};
+use crate::citation::Provenance;
 use crate::document::path::Coord;
 use crate::load::report::{Failed, Origin, PathReporter};
 use crate::load::yaml::{FromYaml, Mapping, Value};
+use crate::schema::Schema;
 use crate::store::{
-    DataStore, DocumentLink, FullStore, StoreLoader, XrefsBuilder, XrefsStore,
+    DataStore, DocumentLink, FullStore, MetaStore, StoreLoader, XrefsBuilder,
 };
+use crate::store::write::{MappingBuilder, ToYaml};
 use crate::types::{
-    CountryCode, EventDate, IntoMarked, Key, LanguageCode, LanguageText, List,
-    LocalText, Marked, Set,
+    CountryCode, EventDate, IntoMarked, Key, LanguageCode, LanguagePreference,
+    LanguageText, List, LocalText, Marked, OperatingPeriods, Set, Text,
 };
-use super::{line, path, point, source};
-use super::common::{Basis, Common, Progress};
+use crate::types::period::EndReason;
+use super::{custom, entity, line, local, path, point, service, source};
+use super::combined::{keys_to_json, sorted_keys_to_json};
+use super::common::{Basis, Common, DocumentType, Progress};
+use super::layout::Layout;
 
 
 //------------ Link ----------------------------------------------------------
@@ -26,12 +34,30 @@ pub use super::combined::PointLink as Link;
 pub use super::combined::PointDocument as Document;
 
 impl<'a> Document<'a> {
+    /// Returns the current superior points of the point.
+    pub fn superiors(
+        self, store: &'a FullStore
+    ) -> impl Iterator<Item = Document<'a>> + 'a {
+        self.data().superiors().iter().map(move |link| {
+            link.into_value().document(store)
+        })
+    }
+
+    /// Returns the points that currently have this point as a superior.
+    pub fn subordinates(
+        self, store: &'a FullStore
+    ) -> impl Iterator<Item = Document<'a>> + 'a {
+        self.xrefs().subordinates.iter().map(move |link| {
+            link.document(store)
+        })
+    }
 }
 
 
 //------------ Data ----------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Data {
     link: point::Link,
 
@@ -39,6 +65,9 @@ pub struct Data {
     pub subtype: Marked<Subtype>,
     pub junction: Option<Marked<bool>>,
 
+    /// The track layout of the point if it is known.
+    pub layout: Option<Layout>,
+
     pub events: EventList,
     pub records: RecordList,
 }
@@ -69,6 +98,16 @@ impl Data {
     pub fn events_rev(&self) -> impl Iterator<Item = &Event> + '_ {
         self.events.iter().rev()
     }
+
+    /// Returns the current superior points.
+    ///
+    /// These are the points given by the newest `superior` attribute.
+    pub fn superiors(&self) -> &[Marked<point::Link>] {
+        self.events_then_records(|properties| properties.superior.as_ref())
+            .and_then(|(value, _)| value.as_ref())
+            .map(List::as_slice)
+            .unwrap_or_default()
+    }
 }
 
 /// # Convenience Methods
@@ -114,6 +153,21 @@ impl Data {
         self.key().as_str()
     }
 
+    /// Returns the current name in the most preferred language available.
+    pub fn preferred_name(&self, preference: &LanguagePreference) -> &str {
+        self.events_then_records(|properties| {
+            if let Some(ref name) = properties.name {
+                Some(name.for_preference(preference))
+            }
+            else if let Some(ref name) = properties.designation {
+                Some(name.for_preference(preference))
+            }
+            else {
+                None
+            }
+        }).map(|res| res.0).unwrap_or_else(|| self.key().as_str())
+    }
+
     /// Returns the current location for the given line.
     ///
     /// If the point has a location on this line, returns the location as well
@@ -129,6 +183,18 @@ impl Data {
         Some((current, changed))
     }
 
+    /// Returns the current location for the given line as marked value.
+    ///
+    /// Unlike [`line_location`][Self::line_location], this keeps the
+    /// position of the value in the source for reporting.
+    pub fn marked_line_location(
+        &self, line: line::Link
+    ) -> Option<&Marked<Text>> {
+        self.events.iter().rev().find_map(|event| {
+            event.marked_line_location(line)
+        }).flatten()
+    }
+
     /// Returns an iterator over the locations for the given line.
     pub fn iter_line_locations(
         &self, line: line::Link
@@ -160,6 +226,29 @@ impl Data {
         self.status() == Status::Open
     }
 
+    /// Returns the periods of operation given by the point’s own events.
+    ///
+    /// The result is empty if none of the events has a status.
+    pub fn operating_periods(&self) -> OperatingPeriods {
+        let mut res = OperatingPeriods::default();
+        for event in self.events.iter() {
+            let status = match event.status() {
+                Some(status) => status,
+                None => continue
+            };
+            let date = event.date.iter().next().map(Marked::into_value);
+            match status {
+                Status::Open | Status::Reopened => res.open(date),
+                Status::Closed => res.close(date, EndReason::Closed),
+                Status::Planned | Status::Construction
+                    | Status::Suspended => {
+                    res.close(date, EndReason::Suspended)
+                }
+            }
+        }
+        res
+    }
+
     fn event_records_rev(&self) -> impl Iterator<Item = &EventRecord> + '_ {
         self.events_rev().map(|ev| ev.records.iter()).flatten()
     }
@@ -197,6 +286,21 @@ impl Data {
 /// # Loading
 ///
 impl Data {
+    /// Creates a placeholder for a document that failed to load.
+    pub fn placeholder(
+        key: Marked<Key>, link: DocumentLink, origin: Origin
+    ) -> Self {
+        Data {
+            link: link.into(),
+            common: Common::placeholder(key, origin),
+            subtype: Subtype::default().into(),
+            junction: None,
+            layout: None,
+            events: EventList::default(),
+            records: RecordList::default(),
+        }
+    }
+
     pub fn from_yaml(
         key: Marked<Key>,
         mut doc: Mapping,
@@ -208,6 +312,7 @@ impl Data {
         let events = doc.take_opt("events", context, report);
         let records = doc.take_opt("records", context, report);
         let junction = doc.take_opt("junction", context, report);
+        let layout = doc.take_opt("layout", context, report);
         let subtype = doc.take_default("subtype", context, report);
         doc.exhausted(report)?;
 
@@ -221,6 +326,7 @@ impl Data {
             events,
             records,
             junction: junction?,
+            layout: layout?,
             subtype: subtype?,
         })
     }
@@ -232,42 +338,303 @@ impl Data {
 
     pub fn xrefs(
         &self, 
-        _builder: &mut XrefsBuilder,
-        _store: &crate::store::DataStore,
-        _report: &mut PathReporter,
+        builder: &mut XrefsBuilder,
+        store: &crate::store::DataStore,
+        report: &mut PathReporter,
+    ) -> Result<(), Failed> {
+        // point: subordinates of our superiors.
+        for superior in self.superiors() {
+            superior.into_value().xrefs_mut(builder).subordinates.push(
+                self.link
+            );
+        }
+
+        // point: connections go both ways, so both points get them.
+        for event in self.events() {
+            for record in event.records.iter() {
+                let date = record.date.as_ref().unwrap_or(&event.date);
+                for point in record.connection.iter().flatten() {
+                    let point = point.into_value();
+                    point.xrefs_mut(builder).connections.push(
+                        (self.link, date.clone())
+                    );
+                    self.link.xrefs_mut(builder).connections.push(
+                        (point, date.clone())
+                    );
+                }
+            }
+        }
+
+        // entity: parties of agreements.
+        for event in self.events() {
+            for record in event.records.iter() {
+                let date = record.date.as_ref().unwrap_or(&event.date);
+                for basis in record.basis.iter() {
+                    let agreement = match basis.agreement.as_ref() {
+                        Some(agreement) => agreement,
+                        None => continue
+                    };
+                    let date = if basis.date.is_empty() { date }
+                               else { &basis.date };
+                    for party in &agreement.parties {
+                        party.into_value().xrefs_mut(builder).agreements.push(
+                            entity::AgreementParty {
+                                document: self.link.into(),
+                                date: date.clone(),
+                                agreement_type: agreement.agreement_type,
+                                roles: Vec::new(),
+                            }
+                        );
+                    }
+                }
+            }
+        }
+
+        // path: the site needs to refer to existing nodes.
+        let mut res = self.check_superior_cycle(store, report);
+        for event in self.events() {
+            for site in event.records.iter().filter_map(|record| {
+                record.site.as_ref()
+            }) {
+                for (path, node) in site.0.iter() {
+                    if path.data(store).check_node(node, report).is_err() {
+                        res = Err(Failed)
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    /// Checks that following the superiors never leads back to the point.
+    fn check_superior_cycle(
+        &self, store: &DataStore, report: &mut PathReporter
     ) -> Result<(), Failed> {
+        for superior in self.superiors() {
+            let mut seen = HashSet::new();
+            let mut todo = vec![superior.into_value()];
+            while let Some(link) = todo.pop() {
+                if link == self.link {
+                    report.error(
+                        SuperiorCycle(
+                            superior.data(store).key().clone()
+                        ).marked(superior.location())
+                    );
+                    return Err(Failed)
+                }
+                if seen.insert(link) {
+                    todo.extend(
+                        link.data(store).superiors().iter().map(|item| {
+                            item.into_value()
+                        })
+                    );
+                }
+            }
+        }
         Ok(())
     }
 
+    /// Checks that the lines the point gives a location for list the point.
+    pub fn check_location_lines(
+        &self, store: &DataStore, report: &mut PathReporter
+    ) {
+        for event in &self.events {
+            for record in &event.records {
+                for (line, _) in record.properties.location.0.iter() {
+                    let data = line.data(store);
+                    if data.points.index_of(self.link).is_none() {
+                        report.warning(
+                            PointNotOnLine(data.key().clone()).marked(
+                                line.location()
+                            )
+                        )
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks that connected points list this point as a connection, too.
+    pub fn check_connections(
+        &self, store: &DataStore, report: &mut PathReporter
+    ) {
+        for event in &self.events {
+            for record in &event.records {
+                for point in record.connection.iter().flatten() {
+                    let data = point.data(store);
+                    if !data.is_connected(self.link) {
+                        report.warning(
+                            AsymmetricConnection(data.key().clone()).marked(
+                                point.location()
+                            )
+                        )
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns whether any record lists `point` as a connection.
+    pub fn is_connected(&self, point: point::Link) -> bool {
+        self.events.iter().flat_map(|event| event.records.iter()).any(
+            |record| {
+                record.connection.iter().flatten().any(|item| {
+                    item.into_value() == point
+                })
+            }
+        )
+    }
+
+    /// Checks all codes against the rules of their country.
+    pub fn check_codes(&self, report: &mut PathReporter) {
+        let events = self.events.iter().flat_map(|event| {
+            event.records.iter().map(|record| &record.properties)
+        });
+        let records = self.records.iter().map(|record| &record.properties);
+        for properties in events.chain(records) {
+            for (code_type, value) in properties.codes.iter_marked() {
+                local::check_point_code(code_type.as_str(), value, report)
+            }
+        }
+    }
+
+    /// Checks that all `de.name16` values fit into 16 characters.
+    pub fn check_name16(&self, report: &mut PathReporter) {
+        self.events_then_records(|properties| {
+            if let Some(name) = properties.de_name16.as_ref() {
+                local::de::check_name16(name, report)
+            }
+            None::<()>
+        });
+    }
+
+    /// Returns all names of the point and the years they were in use.
+    ///
+    /// A name given by an event is in use from the year of the event until
+    /// the year of the next event giving a name. Names only given in
+    /// records without an event have no known validity.
+    fn name_validity(&self) -> HashMap<&Text, Option<NameValidity>> {
+        let mut res = HashMap::new();
+        let mut current: Vec<(&Text, Option<i16>)> = Vec::new();
+        for event in &self.events {
+            let names: Vec<_> = event.records.iter().filter_map(|record| {
+                record.properties.name.as_ref()
+            }).flat_map(|names| {
+                names.iter().map(|(_, name)| name.as_value())
+            }).collect();
+            if names.is_empty() {
+                continue
+            }
+            let year = event.date.iter().next().map(|date| date.year());
+            for (name, start) in current.drain(..) {
+                Self::add_validity(
+                    &mut res, name, NameValidity::new(start, year)
+                );
+            }
+            current.extend(names.into_iter().map(|name| (name, year)));
+        }
+        for (name, start) in current {
+            Self::add_validity(
+                &mut res, name, NameValidity::new(start, None)
+            );
+        }
+        for record in &self.records {
+            if let Some(names) = record.properties.name.as_ref() {
+                for (_, name) in names {
+                    res.entry(name.as_value()).or_insert(None);
+                }
+            }
+        }
+        res
+    }
+
+    fn add_validity<'a>(
+        res: &mut HashMap<&'a Text, Option<NameValidity>>,
+        name: &'a Text,
+        valid: NameValidity,
+    ) {
+        match res.get_mut(name) {
+            Some(Some(item)) => item.merge(valid),
+            _ => {
+                res.insert(name, Some(valid));
+            }
+        }
+    }
+
     pub fn catalogue(
         &self,
         builder: &mut CatalogueBuilder,
-        _store: &FullStore,
-        _report: &mut PathReporter,
+        store: &FullStore,
+        report: &mut PathReporter,
     ) -> Result<(), Failed> {
-        let mut names = HashSet::new();
+        for (name, valid) in self.name_validity() {
+            builder.insert_dated_name(name.clone(), self.link.into(), valid)
+        }
+        let mut codes = HashSet::new();
         self.events_then_records(|properties| {
-            if let Some(some) = properties.name.as_ref() {
-                for (_, name) in some {
-                    names.insert(name.as_value());
-                }
-            }
-            Some(())
+            codes.extend(properties.codes.iter().flat_map(|(kind, values)| {
+                values.map(move |value| (kind, value))
+            }));
+            None::<()>
         });
-        for name in names {
-            builder.insert_name(name.into(), self.link.into())
+        for (code_type, code) in codes {
+            builder.insert_point_code(code_type, code.into(), self.link)
+        }
+        let current = &self.link.meta(store).current.codes;
+        for (code_type, code) in current.iter_marked() {
+            builder.insert_current_point_code(
+                code_type, code.as_str(), code.location(), self.link, store,
+                report
+            )
+        }
+        for (idx, event) in self.events.iter().enumerate() {
+            let kind = match event.status() {
+                Some(Status::Open | Status::Reopened) => EventKind::Opening,
+                Some(Status::Suspended | Status::Closed) => {
+                    EventKind::Closure
+                }
+                _ => EventKind::Other,
+            };
+            builder.insert_event(&event.date, self.link.into(), idx, kind);
         }
         Ok(())
     }
 }
 
+impl ToYaml for Data {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = self.common.to_yaml(DocumentType::Point, store);
+        if *self.subtype != Subtype::default() {
+            res.attr("subtype", &self.subtype, store);
+        }
+        res.attr("junction", &self.junction, store)
+            .attr("layout", &self.layout, store)
+            .attr("events", &self.events, store)
+            .attr("records", &self.records, store);
+        res.into_yaml()
+    }
+}
+
 
 //------------ Xrefs ---------------------------------------------------------
 
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Xrefs {
     pub lines: List<line::Link>,
     pub source_regards: Set<source::Link>,
+    pub custom_regards: Set<custom::Link>,
+
+    /// The points connected to this point and the date of the connection.
+    ///
+    /// This contains connections given by either point.
+    pub connections: Vec<(point::Link, EventDate)>,
+
+    /// The points that currently have this point as a superior.
+    pub subordinates: Vec<point::Link>,
+
+    /// The services calling at this point.
+    pub services: Set<service::Link>,
 }
 
 impl Xrefs {
@@ -275,7 +642,47 @@ impl Xrefs {
         &mut self.source_regards
     }
 
-    pub fn finalize(&mut self, _store: &DataStore) {
+    pub fn custom_regards_mut(&mut self) -> &mut Set<custom::Link> {
+        &mut self.custom_regards
+    }
+
+    pub fn finalize(&mut self, store: &DataStore) {
+        self.lines.sort_by(|left, right| {
+            left.data(store).key().cmp(right.data(store).key())
+        });
+        self.lines.dedup();
+        self.connections.sort_by(|left, right| {
+            left.0.data(store).key().cmp(right.0.data(store).key()).then_with(
+                || left.1.sort_cmp(&right.1)
+            )
+        });
+        self.connections.dedup();
+        self.subordinates.sort_by(|left, right| {
+            left.data(store).key().cmp(right.data(store).key())
+        });
+        self.subordinates.dedup();
+    }
+
+    pub fn to_json(&self, store: &DataStore) -> JsonValue {
+        json!({
+            "lines": keys_to_json(self.lines.iter(), store),
+            "source_regards": sorted_keys_to_json(
+                self.source_regards.iter(), store
+            ),
+            "custom_regards": sorted_keys_to_json(
+                self.custom_regards.iter(), store
+            ),
+            "subordinates": keys_to_json(self.subordinates.iter(), store),
+            "services": sorted_keys_to_json(self.services.iter(), store),
+            "connections": self.connections.iter().map(|(point, date)| {
+                json!({
+                    "key": point.data(store).key().as_str(),
+                    "date": date.iter().map(|date| {
+                        date.to_string()
+                    }).collect::<Vec<_>>(),
+                })
+            }).collect::<Vec<_>>(),
+        })
     }
 }
 
@@ -283,15 +690,26 @@ impl Xrefs {
 //------------ Meta ----------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Meta {
     pub junction: bool,
     pub coord: Option<Coord>,
     pub current: Properties,
+
+    /// The periods during which the point was in operation.
+    ///
+    /// If the point’s events don’t have a status, these are the combined
+    /// periods of its lines.
+    pub operating: OperatingPeriods,
 }
 
 impl Meta {
+    /// Point metadata uses the cross references and the data of lines but
+    /// no metadata of other documents.
+    pub const DEPENDENCIES: &'static [DocumentType] = &[];
+
     pub fn generate(
-        data: &Data, store: &XrefsStore, _report: &mut PathReporter,
+        data: &Data, store: &MetaStore, _report: &mut PathReporter,
     ) -> Result<Self, Failed> {
         let xrefs = data.link.xrefs(store);
 
@@ -324,7 +742,7 @@ impl Meta {
             // take the first entry.
             if let Some(site) = record.site.as_ref() {
                 for item in site.0.iter() {
-                    coord = item.0.data(store).get_coord(item.1.as_value());
+                    coord = item.0.data(store).node(item.1.as_value());
                     if coord.is_some() {
                         break
                     }
@@ -334,23 +752,44 @@ impl Meta {
             current.merge(&record.properties);
         }
 
+        let mut operating = data.operating_periods();
+        if operating.is_empty() {
+            for line in xrefs.lines.iter() {
+                operating.merge(&line.data(store).operating_periods());
+            }
+        }
+
         let mut res = Self {
             junction,
             coord,
             current,
+            operating,
         };
         res.fix_current_status(data, xrefs, store);
         res.fix_current_location(xrefs, store);
         Ok(res)
     }
 
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "junction": self.junction,
+            "coord": self.coord.map(|coord| {
+                json!({ "lat": coord.lat, "lon": coord.lon })
+            }),
+            "status": self.current.status.map(|status| {
+                status.into_value().as_str()
+            }),
+            "operating": self.operating.to_json(),
+        })
+    }
+
     /// Fixes the status in the current properties.
     ///
     /// If there is no status, derives it from that of the lines the point
     /// is part of. If there is a status, checks that it doesn’t contradict
     /// the status of the lines and, if so, downgrades it accordingly.
     fn fix_current_status(
-        &mut self, data: &Data, xrefs: &Xrefs, store: &XrefsStore
+        &mut self, data: &Data, xrefs: &Xrefs, store: &MetaStore
     ) {
         if let Some(status) = xrefs.lines.iter().map(|line| {
                 line.data(store).current_status_at(data.link)
@@ -376,12 +815,12 @@ impl Meta {
     ///
     /// Then sorts the list by code.
     fn fix_current_location(
-        &mut self, xrefs: &Xrefs, store: &XrefsStore,
+        &mut self, xrefs: &Xrefs, store: &MetaStore,
     ) {
         for line in xrefs.lines.iter().copied() {
             if self.current.location.find(line).is_none() {
                 self.current.location.0.push((
-                    line.into(), Some(String::from("??").into())
+                    line.into(), Some(Text::from("??").into())
                 ))
             }
         }
@@ -420,6 +859,7 @@ pub type EventList = List<Event>;
 //------------ Event ---------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Event {
     pub date: EventDate,
     pub records: List<EventRecord>,
@@ -461,6 +901,15 @@ impl Event {
         })
     }
 
+    fn marked_line_location(
+        &self,
+        line: line::Link,
+    ) -> Option<Option<&Marked<Text>>> {
+        self.records.iter().find_map(|record| {
+            record.properties.location.find_marked(line)
+        })
+    }
+
     pub fn category(&self) -> Option<&Set<Marked<Category>>> {
         self.prop(|record| record.properties.category.as_ref())
     }
@@ -506,10 +955,27 @@ impl FromYaml<StoreLoader> for Event {
     }
 }
 
+impl ToYaml for Event {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = MappingBuilder::new();
+        res.attr("date", &self.date, store);
+        match self.records.single() {
+            Some(record) if record.date.is_none() => {
+                res.extend(record.to_mapping(store));
+            }
+            _ => {
+                res.insert("records", self.records.to_yaml(store));
+            }
+        }
+        res.into_yaml()
+    }
+}
+
 
 //------------ EventRecord ---------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EventRecord {
     pub date: Option<EventDate>,
     pub document: List<Marked<source::Link>>,
@@ -526,6 +992,18 @@ pub struct EventRecord {
     pub properties: Properties,
 }
 
+impl EventRecord {
+    /// Returns the sources the record is based on.
+    pub fn provenance(&self) -> Provenance<'_> {
+        Provenance {
+            document: self.document.as_slice(),
+            source: self.source.as_slice(),
+            basis: self.basis.as_slice(),
+            agreement: None,
+        }
+    }
+}
+
 impl EventRecord {
     fn from_mapping(
         value: &mut Mapping,
@@ -564,6 +1042,23 @@ impl EventRecord {
     }
 }
 
+impl EventRecord {
+    fn to_mapping(&self, store: &DataStore) -> MappingBuilder {
+        let mut res = MappingBuilder::new();
+        res.attr("date", &self.date, store)
+            .attr("document", &self.document, store)
+            .attr("source", &self.source, store)
+            .attr("basis", &self.basis, store)
+            .attr("note", &self.note, store)
+            .attr("split_from", &self.split_from, store)
+            .attr("merged", &self.merged, store)
+            .attr("connection", &self.connection, store)
+            .attr("site", &self.site, store)
+            .extend(self.properties.to_mapping(store));
+        res
+    }
+}
+
 impl FromYaml<StoreLoader> for EventRecord {
     fn from_yaml(
         value: Value,
@@ -577,6 +1072,12 @@ impl FromYaml<StoreLoader> for EventRecord {
     }
 }
 
+impl ToYaml for EventRecord {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        self.to_mapping(store).into_yaml()
+    }
+}
+
 
 //------------ RecordList ----------------------------------------------------
 
@@ -586,6 +1087,7 @@ pub type RecordList = List<Record>;
 //------------ Record --------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Record {
     pub document: List<Marked<source::Link>>,
     pub note: Option<LanguageText>,
@@ -617,10 +1119,21 @@ impl FromYaml<StoreLoader> for Record {
     }
 }
 
+impl ToYaml for Record {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = MappingBuilder::new();
+        res.insert("document", self.document.to_yaml(store))
+            .attr("note", &self.note, store)
+            .extend(self.properties.to_mapping(store));
+        res.into_yaml()
+    }
+}
+
 
 //------------ Properties ----------------------------------------------------
 
 #[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Properties {
     pub status: Option<Marked<Status>>,
 
@@ -764,6 +1277,30 @@ impl Properties {
     }
 }
 
+impl Properties {
+    fn to_mapping(&self, store: &DataStore) -> MappingBuilder {
+        let mut res = MappingBuilder::new();
+        res.attr("status", &self.status, store)
+            .attr("name", &self.name, store)
+            .attr("short_name", &self.short_name, store)
+            .attr("public_name", &self.public_name, store)
+            .attr("designation", &self.designation, store)
+            .attr("de.name16", &self.de_name16, store)
+            .attr("category", &self.category, store)
+            .attr("de.rang", &self.de_rang, store)
+            .attr("superior", &self.superior, store)
+            .extend(self.codes.to_mapping(store))
+            .attr("location", &self.location, store)
+            .attr("staff", &self.staff, store)
+            .attr("service", &self.service, store)
+            .attr("passenger", &self.passenger, store)
+            .attr("luggage", &self.luggage, store)
+            .attr("express", &self.express, store)
+            .attr("goods", &self.goods, store);
+        res
+    }
+}
+
 
 //------------ Category ------------------------------------------------------
 
@@ -897,7 +1434,8 @@ impl Category {
 //------------ Location ------------------------------------------------------
 
 #[derive(Clone, Debug, Default)]
-pub struct Location(List<(Marked<line::Link>, Option<Marked<String>>)>);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Location(List<(Marked<line::Link>, Option<Marked<Text>>)>);
 
 impl Location {
     pub fn is_empty(&self) -> bool {
@@ -926,6 +1464,14 @@ impl Location {
         })
     }
 
+    fn find_marked(
+        &self, line: line::Link
+    ) -> Option<Option<&Marked<Text>>> {
+        self.0.iter().find_map(|(link, value)| {
+            (link.into_value() == line).then(|| value.as_ref())
+        })
+    }
+
     fn merge(&mut self, other: &Self) {
         for item in other.0.iter() {
             let other_link = item.0;
@@ -966,7 +1512,7 @@ impl FromYaml<StoreLoader> for Location {
                 res.push((key, None))
             }
             else if let Ok(value) = value.into_string(report) {
-                res.push((key, Some(value)))
+                res.push((key, Some(value.map(Text::from))))
             }
             else {
                 err = true
@@ -981,6 +1527,18 @@ impl FromYaml<StoreLoader> for Location {
     }
 }
 
+impl ToYaml for Location {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        Yaml::Hash(self.0.iter().map(|(line, km)| {
+            (line.to_yaml(store), km.to_yaml(store))
+        }).collect())
+    }
+
+    fn is_omitted(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
 
 //------------ Service -------------------------------------------------------
 
@@ -989,7 +1547,7 @@ data_enum! {
         { Full: "full" }
         { None: "none" }
         { Passenger: "passenger" }
-        { Goods: "goods" }
+        { Goods: "goods", deprecated "freight" }
     }
 }
 
@@ -999,7 +1557,7 @@ data_enum! {
 data_enum! {
     pub enum ServiceRate {
         { None: "none" }
-        { Limited: "limited" }
+        { Limited: "limited", deprecated "restricted" }
         { Full: "full" }
     }
 }
@@ -1008,6 +1566,7 @@ data_enum! {
 //------------ ServiceSet ----------------------------------------------------
 
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ServiceSet {
     pub passenger: Option<ServiceRate>,
     pub luggage: Option<ServiceRate>,
@@ -1101,7 +1660,8 @@ data_enum! {
 //------------ Site ----------------------------------------------------------
 
 #[derive(Clone, Debug)]
-pub struct Site(pub List<(Marked<path::Link>, Marked<String>)>);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Site(pub List<(Marked<path::Link>, Marked<Text>)>);
 
 impl FromYaml<StoreLoader> for Site {
     fn from_yaml(
@@ -1121,7 +1681,7 @@ impl FromYaml<StoreLoader> for Site {
             };
             let key = path::Link::build(key, context, report);
             match value.into_string(report) {
-                Ok(value) => res.push((key, value)),
+                Ok(value) => res.push((key, value.map(Text::from))),
                 Err(_) => { err = true }
             }
         }
@@ -1134,6 +1694,14 @@ impl FromYaml<StoreLoader> for Site {
     }
 }
 
+impl ToYaml for Site {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        Yaml::Hash(self.0.iter().map(|(path, node)| {
+            (path.to_yaml(store), node.to_yaml(store))
+        }).collect())
+    }
+}
+
 
 //------------ Staff ---------------------------------------------------------
 
@@ -1196,14 +1764,15 @@ data_enum! {
 
 //------------ DeName16 ------------------------------------------------------
 
-pub type DeName16 = Marked<String>;
+pub type DeName16 = Marked<Text>;
 
 
 //------------ Codes ---------------------------------------------------------
 
 #[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Codes {
-    codes: HashMap<CodeType, List<Marked<String>>>,
+    codes: HashMap<CodeType, List<Marked<Text>>>,
 }
 
 impl Codes {
@@ -1215,6 +1784,24 @@ impl Codes {
         })
     }
 
+    /// Returns all values with their code type and location.
+    pub fn iter_marked(
+        &self
+    ) -> impl Iterator<Item = (CodeType, &Marked<Text>)> + '_ {
+        self.codes.iter().flat_map(|(key, value)| {
+            value.iter().map(move |item| (*key, item))
+        })
+    }
+
+    /// Returns the values of a certain code type.
+    pub fn get(
+        &self, code_type: CodeType
+    ) -> impl Iterator<Item = &str> + '_ {
+        self.codes.get(&code_type).into_iter().flat_map(|value| {
+            value.iter().map(|item| item.as_str())
+        })
+    }
+
     fn merge(&mut self, other: &Self) {
         self.codes.extend(other.codes.iter().map(|item| {
             (item.0.clone(), item.1.clone())
@@ -1242,12 +1829,6 @@ impl Codes {
                 }
             };
 
-            for value in &code {
-                if item.check_value(value, report).is_err() {
-                    err = true
-                }
-            }
-
             if !err {
                 res.insert(item, code);
             }
@@ -1262,6 +1843,23 @@ impl Codes {
     }
 }
 
+impl Codes {
+    fn to_mapping(&self, store: &DataStore) -> MappingBuilder {
+        let mut res = MappingBuilder::new();
+        for &item in CodeType::ALL {
+            if let Some(code) = self.codes.get(&item) {
+                if code.is_empty() {
+                    res.insert(item.as_str(), Yaml::Null);
+                }
+                else {
+                    res.insert(item.as_str(), code.to_yaml(store));
+                }
+            }
+        }
+        res
+    }
+}
+
 
 //------------ CodeType ------------------------------------------------------
 
@@ -1277,21 +1875,93 @@ data_enum! {
         { NoFs: "no.fs" }
         { NoNjk: "no.NJK" }
         { NoNsb: "no.NSB" }
+        { Uic: "uic" }
     }
 }
 
-impl CodeType {
-    fn check_value(
-        self, _value: &Marked<String>, _report: &mut PathReporter
-    ) -> Result<(), Failed> {
-        Ok(())
-    }
+//------------ Schema --------------------------------------------------------
+
+pub fn schema(schema: &mut Schema) {
+    schema.document("point")
+        .include("common")
+        .optional("subtype", "enum(point.subtype)")
+        .optional("junction", "bool")
+        .optional("layout", "string")
+        .optional("events", "list(object(point.event))")
+        .optional("records", "list(object(point.record))");
+    schema.object("point.event")
+        .optional("date", "date")
+        .optional("records", "list(object(point.event_record))")
+        .include("point.event_record");
+    schema.object("point.event_record")
+        .optional("date", "date")
+        .optional("document", "list(key(source))")
+        .optional("source", "list(key(source))")
+        .optional("basis", "list(object(basis))")
+        .optional("note", "text")
+        .optional("split_from", "key(point)")
+        .optional("merged", "key(point)")
+        .optional("connection", "list(key(point))")
+        .optional("site", "mapping(key(path), string)")
+        .include("point.properties");
+    schema.object("point.record")
+        .optional("date", "date")
+        .mandatory("document", "list(key(source))")
+        .optional("note", "text")
+        .include("point.properties");
+
+    let properties = schema.object("point.properties");
+    properties
+        .optional("status", "enum(point.status)")
+        .optional("name", "text")
+        .optional("short_name", "text")
+        .optional("public_name", "list(text)")
+        .optional("designation", "text")
+        .optional("de.name16", "string")
+        .optional("category", "list(enum(point.category))")
+        .optional("de.rang", "enum(point.de_rang)")
+        .optional("superior", "nullable(list(key(point)))")
+        .optional("master", "nullable(list(key(point)))");
+    for item in CodeType::ALL {
+        properties.optional(item.as_str(), "nullable(list(string))");
+    }
+    properties
+        .optional("location", "mapping(key(line), nullable(string))")
+        .optional("staff", "enum(point.staff)")
+        .optional("service", "enum(point.service)")
+        .optional("passenger", "enum(point.service_rate)")
+        .optional("luggage", "enum(point.service_rate)")
+        .optional("express", "enum(point.service_rate)")
+        .optional("goods", "enum(point.service_rate)");
+
+    schema.enumeration("point.category", Category::VARIANTS);
+    schema.enumeration("point.code_type", CodeType::VARIANTS);
+    schema.enumeration("point.de_rang", DeRang::VARIANTS);
+    schema.enumeration("point.service", Service::VARIANTS);
+    schema.enumeration("point.service_rate", ServiceRate::VARIANTS);
+    schema.enumeration("point.side", Side::VARIANTS);
+    schema.enumeration("point.staff", Staff::VARIANTS);
+    schema.enumeration("point.status", Status::VARIANTS);
+    schema.enumeration("point.subtype", Subtype::VARIANTS);
 }
 
 
+
 //============ Errors ========================================================
 
 #[derive(Clone, Copy, Debug, Display)]
 #[display(fmt="only one of 'superior' and 'master' allowed")]
 pub struct SuperiorAndMaster;
 
+#[derive(Clone, Debug, Display)]
+#[display(fmt="point has location on line '{}' which doesn’t list it", _0)]
+pub struct PointNotOnLine(Key);
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="superior points lead back to this point via '{}'", _0)]
+pub struct SuperiorCycle(Key);
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="connected point '{}' doesn’t list this point", _0)]
+pub struct AsymmetricConnection(Key);
+
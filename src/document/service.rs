@@ -0,0 +1,607 @@
+//! Passenger services.
+//!
+//! A service document describes a passenger service such as a named
+//! express train or a regional interval service. Like other documents, it
+//! consists of events that change its properties over time. The route of
+//! a service is a list of points and lines in the order the service
+//! travels along them:
+//!
+//! ```yaml
+//! key: service.de.rheingold
+//! type: service
+//! progress: stub
+//! events:
+//! - date: 1928-05-15
+//!   status: open
+//!   name: Rheingold
+//!   operator: [org.de.drg]
+//!   frequency: daily
+//!   route:
+//!   - point.nl.hvh
+//!   - line.de.2630
+//!   - point.ch.bs
+//! ```
+
+use std::collections::HashSet;
+use derive_more::Display;
+use serde_json::{json, Value as JsonValue};
+use yaml_rust::Yaml;
+use crate::catalogue::{CatalogueBuilder, EventKind};
+use crate::citation::Provenance;
+use crate::load::report::{Failed, Origin, PathReporter};
+use crate::load::yaml::{FromYaml, Mapping, Value};
+use crate::schema::Schema;
+use crate::store::{
+    DataStore, DocumentLink, FullStore, MetaStore, StoreLoader, XrefsBuilder,
+};
+use crate::store::write::{MappingBuilder, ToYaml};
+use crate::types::{
+    EventDate, IntoMarked, Key, LanguageCode, LanguagePreference,
+    LanguageText, List, LocalText, Marked, OperatingPeriods, Set,
+};
+use crate::types::period::EndReason;
+use super::{custom, entity, line, point, source};
+use super::combined::sorted_keys_to_json;
+use super::common::{Basis, Common, DocumentType, Progress};
+
+
+//------------ Link ----------------------------------------------------------
+
+pub use super::combined::ServiceLink as Link;
+
+
+//------------ Document ------------------------------------------------------
+
+pub use super::combined::ServiceDocument as Document;
+
+impl<'a> Document<'a> {
+}
+
+
+//------------ Data ----------------------------------------------------------
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Data {
+    link: Link,
+    pub common: Common,
+    pub events: EventList,
+}
+
+impl Data {
+    pub fn key(&self) -> &Key {
+        &self.common.key
+    }
+
+    pub fn progress(&self) -> Progress {
+        self.common.progress.into_value()
+    }
+
+    pub fn origin(&self) -> &Origin {
+        &self.common.origin
+    }
+
+    pub fn link(&self) -> Link {
+        self.link
+    }
+}
+
+impl Data {
+    /// Returns an iterator over all event records.
+    pub fn event_records(&self) -> impl Iterator<Item = &EventRecord> {
+        self.events.iter().flat_map(|event| event.records.iter())
+    }
+
+    /// Returns the current name in the given language.
+    pub fn name(&self, lang: LanguageCode) -> &str {
+        self.events.iter().rev().find_map(|event| {
+            event.name(lang)
+        }).unwrap_or_else(|| self.key())
+    }
+
+    /// Returns the current name in the most preferred language available.
+    pub fn preferred_name(&self, preference: &LanguagePreference) -> &str {
+        self.events.iter().rev().find_map(|event| {
+            preference.iter().find_map(|lang| event.name(lang))
+        }).unwrap_or_else(|| self.key())
+    }
+
+    /// Returns the periods during which the service was running.
+    ///
+    /// The result is empty if none of the events has a status.
+    pub fn operating_periods(&self) -> OperatingPeriods {
+        let mut res = OperatingPeriods::default();
+        for event in self.events.iter() {
+            let status = match event.status() {
+                Some(status) => status,
+                None => continue
+            };
+            let date = event.date.iter().next().map(Marked::into_value);
+            match status {
+                Status::Open => res.open(date),
+                Status::Closed => res.close(date, EndReason::Closed),
+                Status::Planned | Status::Suspended => {
+                    res.close(date, EndReason::Suspended)
+                }
+            }
+        }
+        res
+    }
+}
+
+impl Data {
+    /// Creates a placeholder for a document that failed to load.
+    pub fn placeholder(
+        key: Marked<Key>, link: DocumentLink, origin: Origin
+    ) -> Self {
+        Data {
+            link: link.into(),
+            common: Common::placeholder(key, origin),
+            events: EventList::default(),
+        }
+    }
+
+    pub fn from_yaml(
+        key: Marked<Key>,
+        mut doc: Mapping,
+        link: DocumentLink,
+        context: &StoreLoader,
+        report: &mut PathReporter
+    ) -> Result<Self, Failed> {
+        let common = Common::from_yaml(key, &mut doc, context, report);
+        let events = doc.take("events", context, report);
+        doc.exhausted(report)?;
+        Ok(Data {
+            link: link.into(),
+            common: common?,
+            events: events?,
+        })
+    }
+
+    pub fn xrefs(
+        &self,
+        builder: &mut XrefsBuilder,
+        store: &crate::store::DataStore,
+        report: &mut PathReporter,
+    ) -> Result<(), Failed> {
+        let mut res = Ok(());
+        for record in self.event_records() {
+            if let Some(route) = record.properties.route.as_ref() {
+                for item in route {
+                    match item.data(store).doctype() {
+                        DocumentType::Point => {
+                            point::Link::from(item.into_value())
+                                .xrefs_mut(builder)
+                                .services.insert(self.link);
+                        }
+                        DocumentType::Line => {
+                            line::Link::from(item.into_value())
+                                .xrefs_mut(builder)
+                                .services.insert(self.link);
+                        }
+                        _ => {
+                            report.error(
+                                InvalidRouteItem(
+                                    item.data(store).key().clone()
+                                ).marked(item.location())
+                            );
+                            res = Err(Failed)
+                        }
+                    }
+                }
+            }
+            if let Some(operators) = record.properties.operator.as_ref() {
+                for operator in operators {
+                    operator.xrefs_mut(builder).services.insert(self.link);
+                }
+            }
+        }
+        res
+    }
+
+    pub fn catalogue(
+        &self,
+        builder: &mut CatalogueBuilder,
+        _store: &FullStore,
+        _report: &mut PathReporter,
+    ) -> Result<(), Failed> {
+        let mut names = HashSet::new();
+        for record in self.event_records() {
+            if let Some(some) = record.properties.name.as_ref() {
+                for (_, name) in some {
+                    names.insert(name.as_value());
+                }
+            }
+        }
+        for name in names {
+            builder.insert_name(name.clone(), self.link.into())
+        }
+        for (idx, event) in self.events.iter().enumerate() {
+            let kind = match event.status() {
+                Some(Status::Open) => EventKind::Opening,
+                Some(Status::Suspended | Status::Closed) => {
+                    EventKind::Closure
+                }
+                _ => EventKind::Other,
+            };
+            builder.insert_event(&event.date, self.link.into(), idx, kind);
+        }
+        Ok(())
+    }
+}
+
+impl ToYaml for Data {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = self.common.to_yaml(DocumentType::Service, store);
+        res.insert("events", self.events.to_yaml(store));
+        res.into_yaml()
+    }
+}
+
+
+//------------ Xrefs ---------------------------------------------------------
+
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Xrefs {
+    source_regards: Set<source::Link>,
+    custom_regards: Set<custom::Link>,
+}
+
+impl Xrefs {
+    pub fn source_regards_mut(&mut self) -> &mut Set<source::Link> {
+        &mut self.source_regards
+    }
+
+    pub fn custom_regards_mut(&mut self) -> &mut Set<custom::Link> {
+        &mut self.custom_regards
+    }
+
+    pub fn finalize(&mut self, _store: &DataStore) {
+    }
+
+    pub fn to_json(&self, store: &DataStore) -> JsonValue {
+        json!({
+            "source_regards": sorted_keys_to_json(
+                self.source_regards.iter(), store
+            ),
+            "custom_regards": sorted_keys_to_json(
+                self.custom_regards.iter(), store
+            ),
+        })
+    }
+}
+
+
+//------------ Meta ----------------------------------------------------------
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Meta {
+    pub current: Properties,
+
+    /// The periods during which the service was running.
+    pub operating: OperatingPeriods,
+}
+
+impl Meta {
+    /// Service metadata only uses the service’s own records.
+    pub const DEPENDENCIES: &'static [DocumentType] = &[];
+
+    pub fn generate(
+        data: &Data, _store: &MetaStore, _report: &mut PathReporter,
+    ) -> Result<Self, Failed> {
+        let mut current = Properties::default();
+        for record in data.event_records() {
+            current.merge(&record.properties);
+        }
+        Ok(Meta {
+            current,
+            operating: data.operating_periods(),
+        })
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "status": self.current.status.map(|status| {
+                status.into_value().as_str()
+            }),
+            "frequency": self.current.frequency.map(|frequency| {
+                frequency.into_value().as_str()
+            }),
+            "operating": self.operating.to_json(),
+        })
+    }
+}
+
+
+//------------ EventList -----------------------------------------------------
+
+pub type EventList = List<Event>;
+
+
+//------------ Event ---------------------------------------------------------
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Event {
+    pub date: EventDate,
+    pub records: List<EventRecord>,
+}
+
+impl Event {
+    pub fn name(&self, lang: LanguageCode) -> Option<&str> {
+        LocalText::iter_for_language(
+            self.records.iter().filter_map(|record| {
+                record.properties.name.as_ref()
+            }),
+            lang
+        )
+    }
+
+    /// Returns the status set by the event, if any.
+    pub fn status(&self) -> Option<Status> {
+        self.records.iter().find_map(|record| {
+            record.properties.status.map(Marked::into_value)
+        })
+    }
+}
+
+impl FromYaml<StoreLoader> for Event {
+    fn from_yaml(
+        value: Value,
+        context: &StoreLoader,
+        report: &mut PathReporter
+    ) -> Result<Self, Failed> {
+        let mut value = value.into_mapping(report)?;
+
+        let date = value.take("date", context, report);
+        let records = match value.take_opt("records", context, report) {
+            Ok(Some(records)) => Ok(records),
+            Ok(None) => {
+                EventRecord::from_mapping(
+                    &mut value, context, report
+                ).map(List::with_value)
+            }
+            Err(err) => Err(err),
+        };
+
+        value.exhausted(report)?;
+
+        Ok(Event {
+            date: date?,
+            records: records?,
+        })
+    }
+}
+
+impl ToYaml for Event {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = MappingBuilder::new();
+        res.insert("date", self.date.to_yaml(store));
+        match self.records.single() {
+            Some(record) if record.date.is_none() => {
+                res.extend(record.to_mapping(store));
+            }
+            _ => {
+                res.insert("records", self.records.to_yaml(store));
+            }
+        }
+        res.into_yaml()
+    }
+}
+
+
+//------------ EventRecord ---------------------------------------------------
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EventRecord {
+    pub date: Option<EventDate>,
+    pub document: List<Marked<source::Link>>,
+    pub source: List<Marked<source::Link>>,
+    pub basis: List<Basis>,
+    pub note: Option<LanguageText>,
+
+    pub properties: Properties,
+}
+
+impl EventRecord {
+    /// Returns the sources the record is based on.
+    pub fn provenance(&self) -> Provenance<'_> {
+        Provenance {
+            document: self.document.as_slice(),
+            source: self.source.as_slice(),
+            basis: self.basis.as_slice(),
+            agreement: None,
+        }
+    }
+}
+
+impl EventRecord {
+    fn from_mapping(
+        value: &mut Mapping,
+        context: &StoreLoader,
+        report: &mut PathReporter
+    ) -> Result<Self, Failed> {
+        let date = value.take_opt("date", context, report);
+        let document = value.take_default("document", context, report);
+        let source = value.take_default("source", context, report);
+        let basis = value.take_default("basis", context, report);
+        let note = value.take_opt("note", context, report);
+        let properties = Properties::from_mapping(value, context, report);
+
+        Ok(Self {
+            date: date?,
+            document: document?,
+            source: source?,
+            basis: basis?,
+            note: note?,
+            properties: properties?,
+        })
+    }
+
+    fn to_mapping(&self, store: &DataStore) -> MappingBuilder {
+        let mut res = MappingBuilder::new();
+        res.attr("date", &self.date, store)
+            .attr("document", &self.document, store)
+            .attr("source", &self.source, store)
+            .attr("basis", &self.basis, store)
+            .attr("note", &self.note, store)
+            .extend(self.properties.to_mapping(store));
+        res
+    }
+}
+
+impl FromYaml<StoreLoader> for EventRecord {
+    fn from_yaml(
+        value: Value,
+        context: &StoreLoader,
+        report: &mut PathReporter
+    ) -> Result<Self, Failed> {
+        let mut value = value.into_mapping(report)?;
+        let res = Self::from_mapping(&mut value, context, report);
+        value.exhausted(report)?;
+        res
+    }
+}
+
+impl ToYaml for EventRecord {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        self.to_mapping(store).into_yaml()
+    }
+}
+
+
+//------------ Properties ----------------------------------------------------
+
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Properties {
+    /// How often the service runs.
+    pub frequency: Option<Marked<Frequency>>,
+
+    pub name: Option<LocalText>,
+    pub operator: Option<List<Marked<entity::Link>>>,
+
+    /// The points and lines the service runs along in travel order.
+    pub route: Option<List<Marked<DocumentLink>>>,
+
+    pub status: Option<Marked<Status>>,
+}
+
+impl Properties {
+    fn from_mapping(
+        value: &mut Mapping,
+        context: &StoreLoader,
+        report: &mut PathReporter
+    ) -> Result<Self, Failed> {
+        let frequency = value.take_opt("frequency", context, report);
+        let name = value.take_opt("name", context, report);
+        let operator = value.take_opt("operator", context, report);
+        let route = value.take_opt("route", context, report);
+        let status = value.take_opt("status", context, report);
+        Ok(Properties {
+            frequency: frequency?,
+            name: name?,
+            operator: operator?,
+            route: route?,
+            status: status?,
+        })
+    }
+
+    fn to_mapping(&self, store: &DataStore) -> MappingBuilder {
+        let mut res = MappingBuilder::new();
+        res.attr("frequency", &self.frequency, store)
+            .attr("name", &self.name, store)
+            .attr("operator", &self.operator, store)
+            .attr("route", &self.route, store)
+            .attr("status", &self.status, store);
+        res
+    }
+
+    fn merge(&mut self, other: &Self) {
+        if let Some(frequency) = other.frequency {
+            self.frequency = Some(frequency)
+        }
+        if let Some(name) = other.name.as_ref() {
+            self.name = Some(name.clone())
+        }
+        if let Some(operator) = other.operator.as_ref() {
+            self.operator = Some(operator.clone())
+        }
+        if let Some(route) = other.route.as_ref() {
+            self.route = Some(route.clone())
+        }
+        if let Some(status) = other.status {
+            self.status = Some(status)
+        }
+    }
+}
+
+
+//------------ Frequency -----------------------------------------------------
+
+data_enum! {
+    pub enum Frequency {
+        /// Less than daily, e.g., seasonal or weekend services.
+        { Occasional: "occasional" }
+
+        /// A few journeys each day without a regular pattern.
+        { Daily: "daily" }
+
+        /// A regular interval of one or two hours.
+        { Interval: "interval" }
+
+        /// More than one journey per hour.
+        { Frequent: "frequent" }
+    }
+}
+
+
+//------------ Status --------------------------------------------------------
+
+data_enum! {
+    pub enum Status {
+        { Planned: "planned" }
+        { Open: "open" }
+        { Suspended: "suspended" }
+        { Closed: "closed" }
+    }
+}
+
+
+//------------ Schema --------------------------------------------------------
+
+pub fn schema(schema: &mut Schema) {
+    schema.document("service")
+        .include("common")
+        .mandatory("events", "list(object(service.event))");
+    schema.object("service.event")
+        .mandatory("date", "date")
+        .optional("records", "list(object(service.record))")
+        .include("service.record");
+    schema.object("service.record")
+        .optional("date", "date")
+        .optional("document", "list(key(source))")
+        .optional("source", "list(key(source))")
+        .optional("basis", "list(object(basis))")
+        .optional("note", "text")
+        .include("service.properties");
+    schema.object("service.properties")
+        .optional("frequency", "enum(service.frequency)")
+        .optional("name", "text")
+        .optional("operator", "list(key(entity))")
+        .optional("route", "list(key)")
+        .optional("status", "enum(service.status)");
+
+    schema.enumeration("service.frequency", Frequency::VARIANTS);
+    schema.enumeration("service.status", Status::VARIANTS);
+}
+
+
+//============ Errors ========================================================
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="route item '{}' is neither a point nor a line", _0)]
+pub struct InvalidRouteItem(Key);
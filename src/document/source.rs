@@ -1,19 +1,26 @@
 
-use std::{fmt, ops};
+use std::{fmt, iter, ops};
+use std::collections::HashSet;
 use derive_more::Display;
+use serde_json::{json, Value as JsonValue};
+use yaml_rust::Yaml;
 use crate::catalogue::CatalogueBuilder;
+use crate::citation::Citation;
 use crate::load::report::{Failed, Origin, PathReporter};
 use crate::load::yaml::{FromYaml, Mapping, Value};
+use crate::schema::Schema;
 use crate::store::{
-    DataStore, DocumentLink, FullStore, LinkTarget, StoreLoader,
-    XrefsBuilder, XrefsStore,
+    DataStore, DocumentLink, FullStore, LinkTarget, MetaStore, StoreLoader,
+    XrefsBuilder,
 };
+use crate::store::write::ToYaml;
 use crate::types::{
-    EventDate, Key, IntoMarked, LanguageText, List, Marked,
-    Set, Url,
+    EventDate, Key, IntoMarked, LanguagePreference, LanguageText, List,
+    Marked, Set, Text, Url,
 };
-use super::{combined, entity, source};
-use super::common::{Common, Progress};
+use super::{combined, custom, entity, source};
+use super::combined::{keys_to_json, sorted_keys_to_json};
+use super::common::{Common, DocumentType, Progress};
 
 
 //------------ Link ----------------------------------------------------------
@@ -26,12 +33,59 @@ pub use super::combined::SourceLink as Link;
 pub use super::combined::SourceDocument as Document;
 
 impl<'a> Document<'a> {
+    /// Returns the collection the source is part of.
+    pub fn collection(self, store: &'a FullStore) -> Option<Document<'a>> {
+        self.data().collection.map(|link| link.into_value().document(store))
+    }
+
+    /// Returns the sources that are part of this source.
+    ///
+    /// The sources are ordered by their number and volume.
+    pub fn items(
+        self, store: &'a FullStore
+    ) -> impl Iterator<Item = Document<'a>> + 'a {
+        self.xrefs().source_collection.iter().map(move |link| {
+            link.document(store)
+        })
+    }
+
+    /// Returns the collections containing the source, innermost first.
+    pub fn ancestors(
+        self, store: &'a FullStore
+    ) -> impl Iterator<Item = Document<'a>> + 'a {
+        iter::successors(self.collection(store), move |doc| {
+            doc.collection(store)
+        })
+    }
+
+    /// Returns the outermost collection containing the source.
+    ///
+    /// If the source isn’t part of a collection, returns the source itself.
+    pub fn root(self, store: &'a FullStore) -> Document<'a> {
+        self.ancestors(store).last().unwrap_or(self)
+    }
+
+    /// Returns the source and all sources it contains as a JSON tree.
+    pub fn tree_to_json(self, store: &FullStore) -> JsonValue {
+        let data = self.data();
+        json!({
+            "key": data.key().as_str(),
+            "subtype": data.subtype.as_str(),
+            "title": data.title.as_ref().map(|title| title.as_str()),
+            "volume": data.volume.as_ref().map(|volume| volume.as_str()),
+            "number": data.number.as_ref().map(|number| number.as_str()),
+            "items": self.items(store).map(|item| {
+                item.tree_to_json(store)
+            }).collect::<Vec<_>>(),
+        })
+    }
 }
 
 
 //------------ Data ----------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Data {
     link: source::Link,
     pub common: Common,
@@ -41,24 +95,23 @@ pub struct Data {
     pub author: List<Marked<entity::Link>>,
     pub collection: Option<Marked<source::Link>>,
     pub date: EventDate,
-    pub designation: Option<Marked<String>>,
+    pub designation: Option<Marked<Text>>,
     pub digital: List<Marked<Url>>,
-    pub edition: Option<Marked<String>>,
+    pub edition: Option<Marked<Text>>,
     pub editor: List<Marked<entity::Link>>,
     pub isbn: Option<Isbn>,
-    pub number: Option<Marked<String>>,
+    pub number: Option<Marked<Text>>,
     pub organization: List<Marked<entity::Link>>,
     pub pages: Option<Pages>,
     pub publisher: List<Marked<entity::Link>>,
-    pub revision: Option<Marked<String>>,
-    pub short_title: Option<Marked<String>>,
-    pub title: Option<Marked<String>>,
+    pub revision: Option<Marked<Text>>,
+    pub short_title: Option<Marked<Text>>,
+    pub title: Option<Marked<Text>>,
     pub url: Option<Marked<Url>>,
-    pub volume: Option<Marked<String>>,
+    pub volume: Option<Marked<Text>>,
 
     // Additional attributes
     pub also: List<Marked<source::Link>>,
-    pub attribution: Option<Marked<String>>,
     pub crossref: List<Marked<source::Link>>,
     pub note: Option<LanguageText>,
     pub regards: List<Marked<DocumentLink>>,
@@ -94,9 +147,56 @@ impl Data {
             None
         }
     }
+
+    /// Returns the first year of the decade the source was published in.
+    pub fn decade(
+        &self, library: &impl LinkTarget<combined::Data>
+    ) -> Option<i16> {
+        let date = self.date(library)?.iter().next()?;
+        Some(date.year().div_euclid(10) * 10)
+    }
+
+    /// Returns the citation of the source.
+    pub fn citation<'s, L: LinkTarget<combined::Data>>(
+        &'s self, library: &'s L, preference: &'s LanguagePreference
+    ) -> Citation<'s, L> {
+        Citation::new(self, library, preference)
+    }
 }
 
 impl Data {
+    /// Creates a placeholder for a document that failed to load.
+    pub fn placeholder(
+        key: Marked<Key>, link: DocumentLink, origin: Origin
+    ) -> Self {
+        Data {
+            link: link.into(),
+            common: Common::placeholder(key, origin),
+            subtype: Subtype::default().into(),
+            author: List::default(),
+            collection: None,
+            date: EventDate::default(),
+            designation: None,
+            digital: List::default(),
+            edition: None,
+            editor: List::default(),
+            isbn: None,
+            number: None,
+            organization: List::default(),
+            pages: None,
+            publisher: List::default(),
+            revision: None,
+            short_title: None,
+            title: None,
+            url: None,
+            volume: None,
+            also: List::default(),
+            crossref: List::default(),
+            note: None,
+            regards: List::default(),
+        }
+    }
+
     pub fn from_yaml(
         key: Marked<Key>,
         mut doc: Mapping,
@@ -124,7 +224,6 @@ impl Data {
         let url = doc.take_opt("url", context, report);
         let volume = doc.take_opt("volume", context, report);
         let also = doc.take_default("also", context, report);
-        let attribution = doc.take_opt("attribution", context, report);
         let crossref = doc.take_default("crossref", context, report);
         let note = doc.take_opt("note", context, report);
         let regards = doc.take_default("regards", context, report);
@@ -151,7 +250,6 @@ impl Data {
             url: url?,
             volume: volume?,
             also: also?,
-            attribution: attribution?,
             crossref: crossref?,
             note: note?,
             regards: regards?,
@@ -163,9 +261,10 @@ impl Data {
     pub fn xrefs(
         &self, 
         builder: &mut XrefsBuilder,
-        _store: &crate::store::DataStore,
-        _report: &mut PathReporter,
+        store: &crate::store::DataStore,
+        report: &mut PathReporter,
     ) -> Result<(), Failed> {
+        self.check_collection_cycle(store, report)?;
         self.author.iter().for_each(|link| {
             link.xrefs_mut(builder).source_author.insert(self.link);
         });
@@ -193,25 +292,89 @@ impl Data {
         Ok(())
     }
 
+    /// Checks that the source isn’t part of its own collections.
+    fn check_collection_cycle(
+        &self, store: &DataStore, report: &mut PathReporter
+    ) -> Result<(), Failed> {
+        let first = match self.collection {
+            Some(collection) => collection,
+            None => return Ok(())
+        };
+        let mut seen = HashSet::new();
+        let mut current = Some(first.into_value());
+        while let Some(link) = current {
+            if link == self.link {
+                report.error(
+                    CollectionCycle(
+                        first.data(store).key().clone()
+                    ).marked(first.location())
+                );
+                return Err(Failed)
+            }
+            // A cycle further up is reported by its members.
+            if !seen.insert(link) {
+                break
+            }
+            current = link.data(store).collection.map(Marked::into_value);
+        }
+        Ok(())
+    }
+
     pub fn catalogue(
         &self,
-        _builder: &mut CatalogueBuilder,
-        _store: &FullStore,
+        builder: &mut CatalogueBuilder,
+        store: &FullStore,
         _report: &mut PathReporter,
     ) -> Result<(), Failed> {
+        builder.insert_source(
+            self.link, self.decade(store), self.subtype.into_value()
+        );
         Ok(())
     }
 }
 
+impl ToYaml for Data {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = self.common.to_yaml(DocumentType::Source, store);
+        if *self.subtype != Subtype::default() {
+            res.attr("subtype", &self.subtype, store);
+        }
+        res.attr("author", &self.author, store)
+            .attr("collection", &self.collection, store)
+            .attr("date", &self.date, store)
+            .attr("designation", &self.designation, store)
+            .attr("digital", &self.digital, store)
+            .attr("edition", &self.edition, store)
+            .attr("editor", &self.editor, store)
+            .attr("isbn", &self.isbn, store)
+            .attr("number", &self.number, store)
+            .attr("organization", &self.organization, store)
+            .attr("pages", &self.pages, store)
+            .attr("publisher", &self.publisher, store)
+            .attr("revision", &self.revision, store)
+            .attr("short_title", &self.short_title, store)
+            .attr("title", &self.title, store)
+            .attr("url", &self.url, store)
+            .attr("volume", &self.volume, store)
+            .attr("also", &self.also, store)
+            .attr("crossref", &self.crossref, store)
+            .attr("note", &self.note, store)
+            .attr("regards", &self.regards, store);
+        res.into_yaml()
+    }
+}
+
 
 //------------ Xrefs ---------------------------------------------------------
 
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Xrefs {
     source_also: Set<Link>,
     source_collection: List<Link>,
     source_crossref: Set<Link>,
     source_regards: Set<Link>,
+    custom_regards: Set<custom::Link>,
 }
 
 impl Xrefs {
@@ -219,14 +382,39 @@ impl Xrefs {
         &mut self.source_regards
     }
 
+    pub fn custom_regards_mut(&mut self) -> &mut Set<custom::Link> {
+        &mut self.custom_regards
+    }
+
     pub fn finalize(&mut self, store: &DataStore) {
         self.source_collection.sort_by(|left, right| {
             let left = left.data(store);
             let right = right.data(store);
-            (left.number.as_ref(), left.volume.as_ref()).cmp(
-                &(right.number.as_ref(), right.volume.as_ref())
+            (left.number.as_ref(), left.volume.as_ref(), left.key()).cmp(
+                &(right.number.as_ref(), right.volume.as_ref(), right.key())
             )
         });
+        self.source_collection.dedup();
+    }
+
+    pub fn to_json(&self, store: &DataStore) -> JsonValue {
+        json!({
+            "source_also": sorted_keys_to_json(
+                self.source_also.iter(), store
+            ),
+            "source_collection": keys_to_json(
+                self.source_collection.iter(), store
+            ),
+            "source_crossref": sorted_keys_to_json(
+                self.source_crossref.iter(), store
+            ),
+            "source_regards": sorted_keys_to_json(
+                self.source_regards.iter(), store
+            ),
+            "custom_regards": sorted_keys_to_json(
+                self.custom_regards.iter(), store
+            ),
+        })
     }
 }
 
@@ -234,14 +422,22 @@ impl Xrefs {
 //------------ Meta ----------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Meta;
 
 impl Meta {
+    /// Sources don’t have any metadata.
+    pub const DEPENDENCIES: &'static [DocumentType] = &[];
+
     pub fn generate(
-        _data: &Data, _store: &XrefsStore, _report: &mut PathReporter,
+        _data: &Data, _store: &MetaStore, _report: &mut PathReporter,
     ) -> Result<Self, Failed> {
         Ok(Meta)
     }
+
+    pub fn to_json(&self) -> JsonValue {
+        json!({})
+    }
 }
 
 
@@ -272,7 +468,8 @@ data_enum! {
 // XXX Temporary type. Replace with a type encoding the actual specification.
 
 #[derive(Clone, Debug)]
-pub struct Pages(Marked<String>);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pages(Marked<Text>);
 
 impl Pages {
     pub fn as_str(&self) -> &str {
@@ -288,13 +485,19 @@ impl<C> FromYaml<C> for Pages {
     ) -> Result<Self, Failed> {
         match value.try_into_integer() {
             Ok(int) => {
-                Ok(Pages(int.map(|int| format!("{}", int))))
+                Ok(Pages(int.map(|int| Text::from(int.to_string()))))
             }
             Err(value) => Marked::from_yaml(value, context, report).map(Pages)
         }
     }
 }
 
+impl ToYaml for Pages {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        self.0.to_yaml(store)
+    }
+}
+
 impl ops::Deref for Pages {
     type Target = str;
 
@@ -313,7 +516,8 @@ impl fmt::Display for Pages {
 //------------ Isbn ----------------------------------------------------------
 
 #[derive(Clone, Debug)]
-pub struct Isbn(Marked<String>);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Isbn(Marked<Text>);
 
 impl Isbn {
     pub fn as_str(&self) -> &str {
@@ -331,6 +535,12 @@ impl<C> FromYaml<C> for Isbn {
     }
 }
 
+impl ToYaml for Isbn {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        self.0.to_yaml(store)
+    }
+}
+
 impl ops::Deref for Isbn {
     type Target = str;
 
@@ -429,8 +639,45 @@ impl Data {
 }
 
 
+//------------ Schema --------------------------------------------------------
+
+pub fn schema(schema: &mut Schema) {
+    schema.document("source")
+        .include("common")
+        .optional("subtype", "enum(source.subtype)")
+        .optional("author", "list(key(entity))")
+        .optional("collection", "key(source)")
+        .optional("date", "date")
+        .optional("designation", "string")
+        .optional("digital", "list(url)")
+        .optional("edition", "string")
+        .optional("editor", "list(key(entity))")
+        .optional("isbn", "string")
+        .optional("number", "string")
+        .optional("organization", "list(key(entity))")
+        .optional("pages", "string")
+        .optional("publisher", "list(key(entity))")
+        .optional("revision", "string")
+        .optional("short_title", "string")
+        .optional("title", "string")
+        .optional("url", "url")
+        .optional("volume", "string")
+        .optional("also", "list(key(source))")
+        .optional("crossref", "list(key(source))")
+        .optional("note", "text")
+        .optional("regards", "list(key)");
+
+    schema.enumeration("source.subtype", Subtype::VARIANTS);
+}
+
+
+
 //============ Errors ========================================================
 
+#[derive(Clone, Debug, Display)]
+#[display(fmt="source is part of itself via collection '{}'", _0)]
+pub struct CollectionCycle(Key);
+
 #[derive(Clone, Debug, Display)]
 #[display(
     fmt="missing {} in {} source", missing, subtype
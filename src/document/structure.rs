@@ -1,16 +1,23 @@
 
 use std::collections::HashSet;
-use crate::catalogue::CatalogueBuilder;
+use serde_json::{json, Value as JsonValue};
+use yaml_rust::Yaml;
+use crate::catalogue::{CatalogueBuilder, EventKind};
+use crate::citation::Provenance;
 use crate::load::report::{Failed, Origin, PathReporter};
 use crate::load::yaml::{FromYaml, Mapping, Value};
+use crate::schema::Schema;
 use crate::store::{
-    DataStore, DocumentLink, FullStore, StoreLoader, XrefsBuilder, XrefsStore,
+    DataStore, DocumentLink, FullStore, MetaStore, StoreLoader, XrefsBuilder,
 };
+use crate::store::write::{MappingBuilder, ToYaml};
 use crate::types::{
-    EventDate, Key, LanguageText, List, LocalText, Marked, Set,
+    EventDate, Key, LanguageCode, LanguagePreference, LanguageText, List,
+    LocalText, Marked, Set,
 };
-use super::source;
-use super::common::{Common, Progress};
+use super::{custom, entity, line, source};
+use super::combined::sorted_keys_to_json;
+use super::common::{Basis, Common, DocumentType, Progress};
 
 
 //------------ Link ----------------------------------------------------------
@@ -29,6 +36,7 @@ impl<'a> Document<'a> {
 //------------ Data ----------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Data {
     link: Link,
     pub common: Common,
@@ -55,6 +63,39 @@ impl Data {
 }
 
 impl Data {
+    /// Returns an iterator over all event records.
+    pub fn event_records(&self) -> impl Iterator<Item = &EventRecord> {
+        self.events.iter().flat_map(|event| event.records.iter())
+    }
+
+    /// Returns the current name in the given language.
+    pub fn name(&self, lang: LanguageCode) -> &str {
+        self.events.iter().rev().find_map(|event| {
+            event.name(lang)
+        }).unwrap_or_else(|| self.key())
+    }
+
+    /// Returns the current name in the most preferred language available.
+    pub fn preferred_name(&self, preference: &LanguagePreference) -> &str {
+        self.events.iter().rev().find_map(|event| {
+            preference.iter().find_map(|lang| event.name(lang))
+        }).unwrap_or_else(|| self.key())
+    }
+}
+
+impl Data {
+    /// Creates a placeholder for a document that failed to load.
+    pub fn placeholder(
+        key: Marked<Key>, link: DocumentLink, origin: Origin
+    ) -> Self {
+        Data {
+            link: link.into(),
+            common: Common::placeholder(key, origin),
+            subtype: Subtype::Tunnel.into(),
+            events: EventList::default(),
+        }
+    }
+
     pub fn from_yaml(
         key: Marked<Key>,
         mut doc: Mapping,
@@ -76,10 +117,17 @@ impl Data {
 
     pub fn xrefs(
         &self, 
-        _builder: &mut XrefsBuilder,
+        builder: &mut XrefsBuilder,
         _store: &crate::store::DataStore,
         _report: &mut PathReporter,
     ) -> Result<(), Failed> {
+        for record in self.event_records() {
+            if let Some(lines) = record.properties.line.as_ref() {
+                for line in lines {
+                    line.xrefs_mut(builder).structures.insert(self.link);
+                }
+            }
+        }
         Ok(())
     }
 
@@ -90,26 +138,45 @@ impl Data {
         _report: &mut PathReporter,
     ) -> Result<(), Failed> {
         let mut names = HashSet::new();
-        for event in &self.events {
-            if let Some(some) = event.name.as_ref() {
+        for record in self.event_records() {
+            if let Some(some) = record.properties.name.as_ref() {
                 for (_, name) in some {
                     names.insert(name.as_value());
                 }
             }
         }
         for name in names {
-            builder.insert_name(name.into(), self.link.into())
+            builder.insert_name(name.clone(), self.link.into())
+        }
+        for (idx, event) in self.events.iter().enumerate() {
+            builder.insert_event(
+                &event.date, self.link.into(), idx, EventKind::Other
+            );
         }
         Ok(())
     }
 }
 
+impl ToYaml for Data {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = self.common.to_yaml(DocumentType::Structure, store);
+        res.insert("subtype", self.subtype.to_yaml(store))
+            .insert("events", self.events.to_yaml(store));
+        res.into_yaml()
+    }
+}
+
 
 //------------ Xrefs ---------------------------------------------------------
 
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Xrefs {
+    /// The lines naming this structure and the section they pass it.
+    pub lines: List<(line::Link, line::Section)>,
+
     source_regards: Set<source::Link>,
+    custom_regards: Set<custom::Link>,
 }
 
 impl Xrefs {
@@ -117,7 +184,31 @@ impl Xrefs {
         &mut self.source_regards
     }
 
-    pub fn finalize(&mut self, _store: &DataStore) {
+    pub fn custom_regards_mut(&mut self) -> &mut Set<custom::Link> {
+        &mut self.custom_regards
+    }
+
+    pub fn finalize(&mut self, store: &DataStore) {
+        self.lines.sort_by(|left, right| {
+            line::Section::cmp_on_lines(left, right, store)
+        });
+        self.lines.dedup_by(|left, right| {
+            line::Section::same_on_lines(left, right)
+        });
+    }
+
+    pub fn to_json(&self, store: &DataStore) -> JsonValue {
+        json!({
+            "lines": self.lines.iter().map(|(line, section)| {
+                section.to_json(*line, store)
+            }).collect::<Vec<_>>(),
+            "source_regards": sorted_keys_to_json(
+                self.source_regards.iter(), store
+            ),
+            "custom_regards": sorted_keys_to_json(
+                self.custom_regards.iter(), store
+            ),
+        })
     }
 }
 
@@ -125,13 +216,29 @@ impl Xrefs {
 //------------ Meta ----------------------------------------------------------
 
 #[derive(Clone, Debug)]
-pub struct Meta;
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Meta {
+    pub current: Properties,
+}
 
 impl Meta {
+    /// Structure metadata only uses the structure’s own records.
+    pub const DEPENDENCIES: &'static [DocumentType] = &[];
+
     pub fn generate(
-        _data: &Data, _store: &XrefsStore, _report: &mut PathReporter,
+        data: &Data, _store: &MetaStore, _report: &mut PathReporter,
     ) -> Result<Self, Failed> {
-        Ok(Meta)
+        let mut current = Properties::default();
+        for record in data.event_records() {
+            current.merge(&record.properties);
+        }
+        Ok(Meta { current })
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "length": self.current.length.map(Marked::into_value),
+        })
     }
 }
 
@@ -154,15 +261,21 @@ pub type EventList = List<Event>;
 //------------ Event ---------------------------------------------------------
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Event {
-    // Meta attributes
     pub date: EventDate,
-    pub document: List<Marked<source::Link>>,
-    pub source: List<Marked<source::Link>>,
-    pub note: Option<LanguageText>,
+    pub records: List<EventRecord>,
+}
 
-    pub length: Option<Marked<f64>>,
-    pub name: Option<LocalText>,
+impl Event {
+    pub fn name(&self, lang: LanguageCode) -> Option<&str> {
+        LocalText::iter_for_language(
+            self.records.iter().filter_map(|record| {
+                record.properties.name.as_ref()
+            }),
+            lang
+        )
+    }
 }
 
 impl FromYaml<StoreLoader> for Event {
@@ -172,21 +285,249 @@ impl FromYaml<StoreLoader> for Event {
         report: &mut PathReporter
     ) -> Result<Self, Failed> {
         let mut value = value.into_mapping(report)?;
+
         let date = value.take("date", context, report);
+        let records = match value.take_opt("records", context, report) {
+            Ok(Some(records)) => Ok(records),
+            Ok(None) => {
+                EventRecord::from_mapping(
+                    &mut value, context, report
+                ).map(List::with_value)
+            }
+            Err(err) => Err(err), 
+        };
+
+        value.exhausted(report)?;
+
+        Ok(Event {
+            date: date?,
+            records: records?,
+        })
+    }
+}
+
+impl ToYaml for Event {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        let mut res = MappingBuilder::new();
+        res.insert("date", self.date.to_yaml(store));
+        match self.records.single() {
+            Some(record) if record.date.is_none() => {
+                res.extend(record.to_mapping(store));
+            }
+            _ => {
+                res.insert("records", self.records.to_yaml(store));
+            }
+        }
+        res.into_yaml()
+    }
+}
+
+
+//------------ EventRecord ---------------------------------------------------
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EventRecord {
+    pub date: Option<EventDate>,
+    pub document: List<Marked<source::Link>>,
+    pub source: List<Marked<source::Link>>,
+    pub basis: List<Basis>,
+    pub note: Option<LanguageText>,
+
+    pub properties: Properties,
+}
+
+impl EventRecord {
+    /// Returns the sources the record is based on.
+    pub fn provenance(&self) -> Provenance<'_> {
+        Provenance {
+            document: self.document.as_slice(),
+            source: self.source.as_slice(),
+            basis: self.basis.as_slice(),
+            agreement: None,
+        }
+    }
+}
+
+impl EventRecord {
+    fn from_mapping(
+        value: &mut Mapping,
+        context: &StoreLoader,
+        report: &mut PathReporter
+    ) -> Result<Self, Failed> {
+        let date = value.take_opt("date", context, report);
         let document = value.take_default("document", context, report);
         let source = value.take_default("source", context, report);
+        let basis = value.take_default("basis", context, report);
         let note = value.take_opt("note", context, report);
-        let length = value.take_opt("length", context, report);
-        let name = value.take_opt("name", context, report);
-        value.exhausted(report)?;
-        Ok(Event {
+        let properties = Properties::from_mapping(value, context, report);
+
+        Ok(Self {
             date: date?,
             document: document?,
             source: source?,
+            basis: basis?,
             note: note?,
+            properties: properties?,
+        })
+    }
+
+    fn to_mapping(&self, store: &DataStore) -> MappingBuilder {
+        let mut res = MappingBuilder::new();
+        res.attr("date", &self.date, store)
+            .attr("document", &self.document, store)
+            .attr("source", &self.source, store)
+            .attr("basis", &self.basis, store)
+            .attr("note", &self.note, store)
+            .extend(self.properties.to_mapping(store));
+        res
+    }
+}
+
+impl FromYaml<StoreLoader> for EventRecord {
+    fn from_yaml(
+        value: Value,
+        context: &StoreLoader,
+        report: &mut PathReporter
+    ) -> Result<Self, Failed> {
+        let mut value = value.into_mapping(report)?;
+        let res = Self::from_mapping(&mut value, context, report);
+        value.exhausted(report)?;
+        res
+    }
+}
+
+impl ToYaml for EventRecord {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        self.to_mapping(store).into_yaml()
+    }
+}
+
+
+//------------ Properties ----------------------------------------------------
+
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Properties {
+    /// The length of the structure in metres.
+    pub length: Option<Marked<f64>>,
+
+    /// The lines running through or across the structure.
+    pub line: Option<List<Marked<line::Link>>>,
+
+    pub name: Option<LocalText>,
+    pub operator: Option<List<Marked<entity::Link>>>,
+    pub owner: Option<List<Marked<entity::Link>>>,
+
+    /// The number of spans of a bridge.
+    pub spans: Option<Marked<u16>>,
+
+    pub status: Option<Marked<Status>>,
+}
+
+impl Properties {
+    fn from_mapping(
+        value: &mut Mapping,
+        context: &StoreLoader,
+        report: &mut PathReporter
+    ) -> Result<Self, Failed> {
+        let length = value.take_opt("length", context, report);
+        let line = value.take_opt("line", context, report);
+        let name = value.take_opt("name", context, report);
+        let operator = value.take_opt("operator", context, report);
+        let owner = value.take_opt("owner", context, report);
+        let spans = value.take_opt("spans", context, report);
+        let status = value.take_opt("status", context, report);
+        Ok(Properties {
             length: length?,
+            line: line?,
             name: name?,
+            operator: operator?,
+            owner: owner?,
+            spans: spans?,
+            status: status?,
         })
     }
+
+    fn to_mapping(&self, store: &DataStore) -> MappingBuilder {
+        let mut res = MappingBuilder::new();
+        res.attr("length", &self.length, store)
+            .attr("line", &self.line, store)
+            .attr("name", &self.name, store)
+            .attr("operator", &self.operator, store)
+            .attr("owner", &self.owner, store)
+            .attr("spans", &self.spans, store)
+            .attr("status", &self.status, store);
+        res
+    }
+
+    fn merge(&mut self, other: &Self) {
+        if let Some(length) = other.length {
+            self.length = Some(length)
+        }
+        if let Some(line) = other.line.as_ref() {
+            self.line = Some(line.clone())
+        }
+        if let Some(name) = other.name.as_ref() {
+            self.name = Some(name.clone())
+        }
+        if let Some(operator) = other.operator.as_ref() {
+            self.operator = Some(operator.clone())
+        }
+        if let Some(owner) = other.owner.as_ref() {
+            self.owner = Some(owner.clone())
+        }
+        if let Some(spans) = other.spans {
+            self.spans = Some(spans)
+        }
+        if let Some(status) = other.status {
+            self.status = Some(status)
+        }
+    }
+}
+
+
+//------------ Status --------------------------------------------------------
+
+data_enum! {
+    pub enum Status {
+        { Planned: "planned" }
+        { Construction: "construction" }
+        { Open: "open" }
+        { Closed: "closed" }
+        { Removed: "removed" }
+    }
+}
+
+
+//------------ Schema --------------------------------------------------------
+
+pub fn schema(schema: &mut Schema) {
+    schema.document("structure")
+        .include("common")
+        .mandatory("subtype", "enum(structure.subtype)")
+        .mandatory("events", "list(object(structure.event))");
+    schema.object("structure.event")
+        .mandatory("date", "date")
+        .optional("records", "list(object(structure.record))")
+        .include("structure.record");
+    schema.object("structure.record")
+        .optional("date", "date")
+        .optional("document", "list(key(source))")
+        .optional("source", "list(key(source))")
+        .optional("basis", "list(object(basis))")
+        .optional("note", "text")
+        .include("structure.properties");
+    schema.object("structure.properties")
+        .optional("length", "number")
+        .optional("line", "list(key(line))")
+        .optional("name", "text")
+        .optional("operator", "list(key(entity))")
+        .optional("owner", "list(key(entity))")
+        .optional("spans", "integer")
+        .optional("status", "enum(structure.status)");
+
+    schema.enumeration("structure.subtype", Subtype::VARIANTS);
+    schema.enumeration("structure.status", Status::VARIANTS);
 }
 
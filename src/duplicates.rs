@@ -0,0 +1,283 @@
+//! Finding likely duplicate documents.
+//!
+//! When data is added by several people, the same thing sometimes ends up
+//! in two documents under different keys. [`Duplicates::find`] looks for
+//! such cases and returns candidate pairs for manual review:
+//!
+//! * points in the same country with the same name and at least one code
+//!   in common,
+//! * lines that currently share a national line code, and
+//! * sources with the same ISBN.
+//!
+//! Names are compared after normalization as used by the name search, so
+//! differences in case, diacritics, and punctuation don’t matter.
+//!
+//! Each candidate has a score between 0 and 1 describing how similar the
+//! two documents are beyond the property that made them candidates. For
+//! points and lines, this is the share of codes they have in common. For
+//! sources, it is based on whether title, volume, and edition agree, too.
+
+use std::fmt;
+use std::collections::{BTreeMap, HashSet};
+use serde_json::{json, Value as JsonValue};
+use crate::catalogue::Catalogue;
+use crate::document::combined::Data;
+use crate::store::{DataStore, FullStore};
+use crate::types::{Key, LanguagePreference, Marked, Text};
+
+
+//------------ Duplicates ----------------------------------------------------
+
+/// The candidate pairs of duplicate documents.
+#[derive(Clone, Debug, Default)]
+pub struct Duplicates {
+    candidates: Vec<Candidate>,
+}
+
+impl Duplicates {
+    /// Finds the candidates in a store.
+    ///
+    /// Points are compared by their name in the most preferred language.
+    /// The candidates are ordered by descending score and then by key.
+    pub fn find(store: &FullStore, preference: &LanguagePreference) -> Self {
+        let mut res = Duplicates::default();
+        res.find_points(store, preference);
+        res.find_lines(store);
+        res.find_sources(store);
+        res.candidates.sort_by(|left, right| {
+            right.score.total_cmp(&left.score).then_with(|| {
+                (&left.left, &left.right).cmp(&(&right.left, &right.right))
+            })
+        });
+        res
+    }
+
+    /// Finds points with the same name and a common code.
+    fn find_points(
+        &mut self, store: &FullStore, preference: &LanguagePreference
+    ) {
+        let mut groups = BTreeMap::<_, Vec<_>>::new();
+        for link in store.links() {
+            let point = match link.document(store).try_as_point() {
+                Some(point) => point,
+                None => continue
+            };
+            let data = point.data();
+            let codes = &point.meta().current.codes;
+            let codes: HashSet<_> = codes.iter().flat_map(|(kind, codes)| {
+                codes.map(move |code| format!("{}:{}", kind.as_str(), code))
+            }).collect();
+            if codes.is_empty() {
+                continue
+            }
+            let name = Catalogue::normalize_name(
+                data.preferred_name(preference), true
+            );
+            groups.entry(
+                (data.key().country().map(String::from), name)
+            ).or_default().push((data.key(), codes));
+        }
+        for items in groups.values() {
+            for (idx, (left, left_codes)) in items.iter().enumerate() {
+                for (right, right_codes) in &items[idx + 1..] {
+                    let common: Vec<_> = left_codes.intersection(
+                        right_codes
+                    ).collect();
+                    if common.is_empty() {
+                        continue
+                    }
+                    let union = left_codes.union(right_codes).count();
+                    let mut common: Vec<_> = common.into_iter().map(
+                        String::as_str
+                    ).collect();
+                    common.sort_unstable();
+                    self.push(
+                        Kind::Point, left, right,
+                        common.len() as f64 / union as f64,
+                        format!("same name, codes {}", common.join(", "))
+                    );
+                }
+            }
+        }
+    }
+
+    /// Finds lines with a common national line code.
+    fn find_lines(&mut self, store: &FullStore) {
+        let ds: &DataStore = store.as_ref();
+        let mut by_code = BTreeMap::<_, Vec<&Key>>::new();
+        let mut codes = BTreeMap::<&Key, HashSet<_>>::new();
+        for data in ds.iter() {
+            let line = match data.try_as_line() {
+                Some(line) => line,
+                None => continue
+            };
+            for (code_type, code, _) in line.current.codes() {
+                let code = format!("{}:{}", code_type.as_str(), code);
+                let keys = by_code.entry(code.clone()).or_default();
+                if !keys.contains(&line.key()) {
+                    keys.push(line.key());
+                }
+                codes.entry(line.key()).or_default().insert(code);
+            }
+        }
+        let mut seen = HashSet::new();
+        for (code, keys) in &by_code {
+            for (idx, left) in keys.iter().enumerate() {
+                for right in &keys[idx + 1..] {
+                    if !seen.insert((*left, *right)) {
+                        continue
+                    }
+                    let left_codes = &codes[left];
+                    let right_codes = &codes[right];
+                    let score = left_codes.intersection(
+                        right_codes
+                    ).count() as f64 / left_codes.union(
+                        right_codes
+                    ).count() as f64;
+                    self.push(
+                        Kind::Line, left, right, score,
+                        format!("same code {}", code)
+                    );
+                }
+            }
+        }
+    }
+
+    /// Finds sources with the same ISBN.
+    fn find_sources(&mut self, store: &FullStore) {
+        let ds: &DataStore = store.as_ref();
+        let mut groups = BTreeMap::<_, Vec<_>>::new();
+        for data in ds.iter() {
+            if let Data::Source(ref source) = *data {
+                if let Some(isbn) = source.isbn.as_ref() {
+                    let isbn: String = isbn.as_str().chars().filter(|ch| {
+                        ch.is_ascii_alphanumeric()
+                    }).map(|ch| ch.to_ascii_uppercase()).collect();
+                    groups.entry(isbn).or_default().push(source)
+                }
+            }
+        }
+        for (isbn, items) in &groups {
+            for (idx, left) in items.iter().enumerate() {
+                for right in &items[idx + 1..] {
+                    let matches = [
+                        opt_str(&left.title) == opt_str(&right.title),
+                        opt_str(&left.volume) == opt_str(&right.volume),
+                        opt_str(&left.edition) == opt_str(&right.edition),
+                    ].into_iter().filter(|item| *item).count();
+                    self.push(
+                        Kind::Source, left.key(), right.key(),
+                        (1 + matches) as f64 / 4.,
+                        format!("same ISBN {}", isbn)
+                    );
+                }
+            }
+        }
+    }
+
+    fn push(
+        &mut self, kind: Kind, left: &Key, right: &Key, score: f64,
+        reason: String
+    ) {
+        let (left, right) = if left <= right { (left, right) }
+                            else { (right, left) };
+        self.candidates.push(Candidate {
+            kind, left: left.clone(), right: right.clone(), score, reason
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Candidate> + '_ {
+        self.candidates.iter()
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        self.candidates.iter().map(Candidate::to_json).collect()
+    }
+}
+
+impl fmt::Display for Duplicates {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for item in &self.candidates {
+            writeln!(f, "{}", item)?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ Candidate -----------------------------------------------------
+
+/// A pair of documents that may be duplicates.
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    /// What kind of documents these are.
+    pub kind: Kind,
+
+    /// The key that sorts first.
+    pub left: Key,
+
+    /// The key that sorts last.
+    pub right: Key,
+
+    /// How similar the documents are between 0 and 1.
+    pub score: f64,
+
+    /// Why the documents are considered duplicates.
+    pub reason: String,
+}
+
+impl Candidate {
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "kind": self.kind.as_str(),
+            "keys": [self.left.as_str(), self.right.as_str()],
+            "score": self.score,
+            "reason": self.reason,
+        })
+    }
+}
+
+impl fmt::Display for Candidate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "{:.2} {} {}: {}",
+            self.score, self.left, self.right, self.reason
+        )
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+fn opt_str(value: &Option<Marked<Text>>) -> Option<&str> {
+    value.as_ref().map(|value| value.as_str())
+}
+
+
+//------------ Kind ----------------------------------------------------------
+
+/// The kind of duplicate documents.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Kind {
+    Point,
+    Line,
+    Source,
+}
+
+impl Kind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Kind::Point => "point",
+            Kind::Line => "line",
+            Kind::Source => "source",
+        }
+    }
+}
@@ -0,0 +1,1741 @@
+//! A minimal HTML browser for the data.
+//!
+//! The [`Browser`] renders simple, human-readable pages for the index of
+//! all documents, for a single document, and for search results directly
+//! from a store. With [`Browser::serve`], these pages are served over HTTP
+//! so the data can be browsed without a separate frontend.
+//!
+//! The server is deliberately simple: it only understands `GET` except
+//! for the admin endpoints and closes the connection after each response.
+//! Slow clients are cut off after a timeout and overly long request heads
+//! are rejected.
+//! It is meant for looking at the data locally, not for public deployment.
+//!
+//! A [`Server`] serves the data held by a [`State`] instead of a borrowed
+//! store. It handles connections with a pool of threads and can replace
+//! the data while running: the state keeps the current store and
+//! catalogue behind an `Arc` that is swapped atomically once new data has
+//! been loaded, so requests already being handled finish with the old
+//! data. A reload can be triggered through [`State::reload`], the admin
+//! endpoint, or by watching the data tree via [`State::watch`].
+//!
+//! The pages are available under these paths:
+//!
+//! * `/` lists the number of documents of each type,
+//! * `/type/<type>` lists all documents of a type,
+//! * `/doc/<key>` shows a document,
+//! * `/sources/<key>` shows the collections a source is part of and the
+//!   sources it contains,
+//! * `/line/by-code/<country>/<code>?date=<date>` shows the line with the
+//!   given code in the national line numbering system of the country,
+//!   either currently or at the given date,
+//! * `/point/by-code/<type>/<code>` shows the point with the given code
+//!   such as `de.DS100` or `uic` or lists all points that ever had it if
+//!   there is more than one,
+//! * `/entity/<key>/operations?date=<date>` lists the lines an entity
+//!   operated or owned, optionally only those at the given date,
+//! * `/document/<key>/profile` returns the elevation profile of a line
+//!   as JSON with pairs of distance and elevation,
+//! * `/document/<key>/diff?against=<id>` returns the changes of a
+//!   document since the snapshot with the given id as a JSON Patch if
+//!   the browser has [`Snapshots`]. As this may load the snapshot, it is
+//!   protected by the admin token described below,
+//! * `/tiles/<z>/<x>/<y>` returns the lines and points of a web-mercator
+//!   map tile as GeoJSON if the browser has a [`SpatialIndex`],
+//! * `/search?q=<text>` searches for documents by name, optionally
+//!   matching name prefixes with `prefix=1` and allowing a number of
+//!   mistyped characters with `distance=<n>`,
+//! * `/stats/geometry` returns the share of lines with course information
+//!   per country and the sections lacking it as JSON,
+//! * `/index/keys?prefix=<prefix>` lists all documents whose key starts
+//!   with the prefix or all key namespaces if there is no prefix, and
+//! * `/index/sources?decade=<year>&subtype=<subtype>` lists the sources
+//!   published in the decade starting with the year and of the subtype
+//!   ordered by date or, without either, the decades and subtypes with
+//!   their number of sources.
+//!
+//! If the browser was given a token via [`Browser::with_token`], a few
+//! admin endpoints are available in addition. Requests to them have to
+//! carry the token in an `Authorization: Bearer <token>` header or they
+//! are rejected with status 401. Without a token, they don’t exist.
+//!
+//! * `GET /admin/stats` returns the statistics of the data as JSON,
+//! * `GET /admin/snapshot?detail=<detail>` returns all documents as JSON
+//!   in the format of [`DocumentSet`] with the given level of detail,
+//!   by default all of it, and
+//! * `POST /admin/reload` reloads the data. With a [`Server`], this
+//!   happens in the background. Otherwise, it makes
+//!   [`Browser::serve_listener`] return so the caller can load the data
+//!   again and continue serving.
+//!
+//! Requests can be logged to standard error by giving a [`Logger`] to the
+//! browser or server. Each handled request is logged with its method,
+//! path, response status, duration, and client address either as a line
+//! of text or as a JSON object per line.
+//!
+//! If the data has license or attribution information, every response
+//! carries it in a `Link` header with the `license` relation and in the
+//! `X-License` and `X-Attribution` headers.
+//!
+//! This module is only available with the `html` feature.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{mpsc, Arc, Mutex, OnceLock, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::thread;
+use serde_json::{json, Value as JsonValue};
+use sha2::{Digest, Sha256};
+use url::percent_encoding::percent_decode;
+use crate::bulk::{Detail, DocumentSet};
+use crate::catalogue::Catalogue;
+use crate::diff::json_patch;
+use crate::document::common::{DocumentType, Progress};
+use crate::document::{point, source};
+use crate::load::load_tree;
+use crate::store::{DataStore, DocumentLink, FullStore, ProgressView};
+use crate::store::write::write_document;
+use crate::stats::{CourseCoverage, Statistics};
+use crate::tiles::{SpatialIndex, Tile};
+use crate::types::{CountryCode, Date, LanguagePreference, Text};
+
+
+//------------ Browser -------------------------------------------------------
+
+/// Renders the pages of the data browser.
+pub struct Browser<'a> {
+    store: &'a FullStore,
+    catalogue: &'a Catalogue,
+    preference: LanguagePreference,
+
+    /// The token needed for the admin endpoints.
+    ///
+    /// If this is `None`, the admin endpoints are disabled.
+    token: Option<String>,
+
+    /// The logger for handled requests.
+    logger: Option<Logger>,
+
+    /// The spatial index for serving map tiles.
+    index: Option<&'a SpatialIndex>,
+
+    /// The snapshots to compare documents against.
+    snapshots: Option<&'a Snapshots>,
+
+    /// The minimum progress of documents shown.
+    min_progress: Progress,
+}
+
+impl<'a> Browser<'a> {
+    /// The maximum number of search results shown.
+    const MAX_RESULTS: usize = 100;
+
+    /// The time a client has for each read of the request.
+    const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+    pub fn new(
+        store: &'a FullStore,
+        catalogue: &'a Catalogue,
+        preference: LanguagePreference,
+    ) -> Self {
+        Browser {
+            store, catalogue, preference,
+            token: None, logger: None, index: None, snapshots: None,
+            min_progress: Progress::Stub,
+        }
+    }
+
+    /// Only shows documents with at least the given progress.
+    pub fn with_min_progress(mut self, min: Progress) -> Self {
+        self.min_progress = min;
+        self
+    }
+
+    /// Compares documents against the given snapshots.
+    pub fn with_snapshots(mut self, snapshots: &'a Snapshots) -> Self {
+        self.snapshots = Some(snapshots);
+        self
+    }
+
+    /// Serves map tiles using the given spatial index.
+    pub fn with_index(mut self, index: &'a SpatialIndex) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// Enables the admin endpoints protected by the given token.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Logs all handled requests with the given logger.
+    pub fn with_logger(mut self, logger: Logger) -> Self {
+        self.logger = Some(logger);
+        self
+    }
+
+    /// Serves the pages over HTTP at `addr`.
+    ///
+    /// Returns when an error happens or a reload was requested.
+    pub fn serve(&self, addr: impl ToSocketAddrs) -> Result<(), io::Error> {
+        self.serve_listener(&TcpListener::bind(addr)?)
+    }
+
+    /// Serves the pages over HTTP using an existing listener.
+    ///
+    /// Returns when an error happens or a reload was requested via the
+    /// admin endpoint. In the latter case, the caller can load the data
+    /// again and continue with a new browser on the same listener.
+    pub fn serve_listener(
+        &self, listener: &TcpListener
+    ) -> Result<(), io::Error> {
+        for stream in listener.incoming() {
+            // A broken connection shouldn’t stop the server.
+            if let Ok(true) = self.handle(stream?) {
+                break
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles a single HTTP connection.
+    ///
+    /// Returns whether a reload was requested.
+    fn handle(&self, stream: TcpStream) -> Result<bool, io::Error> {
+        let start = Instant::now();
+        let client = stream.peer_addr().ok();
+        stream.set_read_timeout(Some(Self::READ_TIMEOUT))?;
+        let mut reader = BufReader::new(stream);
+        let head = match read_head(&mut reader) {
+            Ok(head) => Some(head),
+            Err(err) if err.kind() == io::ErrorKind::InvalidData => None,
+            Err(err) => return Err(err)
+        };
+        let request: Vec<_> = head.as_ref().map(|head| {
+            head.request.split_whitespace().collect()
+        }).unwrap_or_default();
+        let (mut page, reload) = match head {
+            Some(ref head) => match request[..] {
+                [method, target, _] => {
+                    let browser = Browser {
+                        preference: self.negotiate_language(
+                            target, head.accept_language.as_deref()
+                        ),
+                        token: self.token.clone(),
+                        .. *self
+                    };
+                    browser.respond(
+                        method, target, head.authorization.as_deref()
+                    )
+                }
+                _ => (Page::error(405, "Method Not Allowed"), false),
+            },
+            None => {
+                (Page::error(431, "Request Header Fields Too Large"), false)
+            }
+        };
+        page.add_attribution(self.store.as_ref());
+        let status = page.status;
+        page.write(reader.get_mut())?;
+        if let Some(logger) = self.logger {
+            let (method, path) = match request[..] {
+                [method, target, ..] => {
+                    (method, target.split('?').next().unwrap_or(target))
+                }
+                _ => ("-", "-"),
+            };
+            logger.log(method, path, status, start.elapsed(), client);
+        }
+        Ok(reload)
+    }
+
+    /// Returns the view of the documents shown.
+    fn view(&self) -> ProgressView<'a> {
+        self.store.with_min_progress(self.min_progress)
+    }
+
+    /// Returns the response for a request.
+    ///
+    /// The second element of the result is whether a reload was
+    /// requested.
+    fn respond(
+        &self, method: &str, target: &str, authorization: Option<&str>,
+    ) -> (Page<'a>, bool) {
+        if target.starts_with("/admin/") {
+            self.admin(method, target, authorization)
+        }
+        else if method == "GET" {
+            if is_diff(target) {
+                if let Err(page) = self.authorize(authorization) {
+                    return (page, false)
+                }
+            }
+            let mut page = self.page(target);
+            page.headers.push(("Vary", "Accept-Language".into()));
+            (page, false)
+        }
+        else {
+            (Page::error(405, "Method Not Allowed"), false)
+        }
+    }
+
+    /// Returns the language preference for a request.
+    ///
+    /// Languages given in the `lang` query parameter come first, followed
+    /// by those of the `Accept-Language` header. The languages the
+    /// browser was created with are used as the last resort. A `lang`
+    /// parameter that can’t be parsed is ignored.
+    fn negotiate_language(
+        &self, target: &str, accept_language: Option<&str>
+    ) -> LanguagePreference {
+        let query = target.split_once('?').map(|(_, query)| query);
+        let mut res = query_param(query, "lang").parse().unwrap_or_else(
+            |_| LanguagePreference::default()
+        );
+        if let Some(header) = accept_language {
+            let accepted = LanguagePreference::from_accept_language(header);
+            for lang in accepted.iter() {
+                res.push(lang)
+            }
+        }
+        for lang in self.preference.iter() {
+            res.push(lang)
+        }
+        res
+    }
+
+    /// Returns the response for an admin endpoint.
+    ///
+    /// The second element of the result is whether a reload was
+    /// requested.
+    fn admin(
+        &self, method: &str, target: &str, authorization: Option<&str>,
+    ) -> (Page<'a>, bool) {
+        if let Err(page) = self.authorize(authorization) {
+            return (page, false)
+        }
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (target, None),
+        };
+        match (method, path) {
+            ("GET", "/admin/stats") => (self.admin_stats(), false),
+            ("GET", "/admin/snapshot") => {
+                (self.admin_snapshot(&query_param(query, "detail")), false)
+            }
+            ("POST", "/admin/reload") => {
+                let mut page = Page::json(&json!({ "reload": true }));
+                page.status = 202;
+                (page, true)
+            }
+            (_, "/admin/stats" | "/admin/snapshot" | "/admin/reload") => {
+                (Page::error(405, "Method Not Allowed"), false)
+            }
+            _ => (Page::error(404, "Not Found"), false)
+        }
+    }
+
+    /// Checks that a request carries the admin token.
+    ///
+    /// Returns the error page to respond with otherwise.
+    fn authorize(&self, authorization: Option<&str>) -> Result<(), Page<'a>> {
+        let token = match self.token.as_ref() {
+            Some(token) => token,
+            None => return Err(Page::error(404, "Not Found"))
+        };
+        let provided = authorization.and_then(|value| {
+            value.strip_prefix("Bearer ")
+        }).map(str::trim);
+        if !provided.is_some_and(|provided| token_matches(provided, token)) {
+            return Err(Page::error(401, "Unauthorized"))
+        }
+        Ok(())
+    }
+
+    /// Returns the statistics of the data as JSON.
+    fn admin_stats(&self) -> Page<'a> {
+        Page::json(&Statistics::generate(self.store.as_ref()).to_json())
+    }
+
+    /// Returns all documents as JSON.
+    ///
+    /// The JSON is written while sending the page, one document at a time.
+    fn admin_snapshot(&self, detail: &str) -> Page<'a> {
+        let detail = if detail.is_empty() {
+            Detail::Full
+        }
+        else {
+            match Detail::from_name(detail) {
+                Some(detail) => detail,
+                None => return Page::error(400, "Bad Request"),
+            }
+        };
+        // The snapshot can be huge, so it is streamed.
+        let set = DocumentSet::all(self.view());
+        let preference = self.preference.clone();
+        let store = self.store;
+        Page::json_stream(move |target| {
+            set.write_json(detail, &preference, store, target)
+        })
+    }
+
+    /// Returns the page for a request target.
+    pub fn page(&self, target: &str) -> Page<'a> {
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (target, None),
+        };
+        let path = percent_decode(path.as_bytes()).decode_utf8_lossy();
+        if path == "/" {
+            return self.index()
+        }
+        if let Some(doctype) = path.strip_prefix("/type/") {
+            return self.doctype(doctype)
+        }
+        if let Some(key) = path.strip_prefix("/doc/") {
+            return self.document(key)
+        }
+        if let Some(key) = path.strip_prefix("/sources/") {
+            return self.sources(key)
+        }
+        if let Some(key) = path.strip_prefix("/entity/").and_then(|key| {
+            key.strip_suffix("/operations")
+        }) {
+            return self.operations(key, &query_param(query, "date"))
+        }
+        if let Some(key) = path.strip_prefix("/document/").and_then(|key| {
+            key.strip_suffix("/profile")
+        }) {
+            return self.profile(key)
+        }
+        if let Some(key) = path.strip_prefix("/document/").and_then(|key| {
+            key.strip_suffix("/diff")
+        }) {
+            return self.document_diff(key, &query_param(query, "against"))
+        }
+        if let Some(tile) = path.strip_prefix("/tiles/") {
+            return self.tile(tile)
+        }
+        if let Some(code) = path.strip_prefix("/line/by-code/") {
+            return match code.split_once('/') {
+                Some((country, code)) => {
+                    self.line_by_code(
+                        country, code, &query_param(query, "date")
+                    )
+                }
+                None => Page::error(404, "Not Found"),
+            }
+        }
+        if let Some(code) = path.strip_prefix("/point/by-code/") {
+            return match code.split_once('/') {
+                Some((code_type, code)) => {
+                    self.point_by_code(code_type, code)
+                }
+                None => Page::error(404, "Not Found"),
+            }
+        }
+        if path == "/search" {
+            return self.search(
+                &query_param(query, "q"),
+                &query_param(query, "prefix"),
+                &query_param(query, "distance"),
+            )
+        }
+        if path == "/stats/geometry" {
+            return self.geometry_coverage()
+        }
+        if path == "/index/keys" {
+            return self.keys(&query_param(query, "prefix"))
+        }
+        if path == "/index/sources" {
+            return self.sources_index(
+                &query_param(query, "decade"), &query_param(query, "subtype")
+            )
+        }
+        Page::error(404, "Not Found")
+    }
+
+    /// Returns the index page.
+    pub fn index(&self) -> Page<'a> {
+        let mut body = String::new();
+        body.push_str("<ul>\n");
+        for &doctype in DocumentType::ALL {
+            let count = self.view().links().filter(|link| {
+                link.data(self.store).doctype() == doctype
+            }).count();
+            let _ = writeln!(
+                body, "<li><a href=\"/type/{0}\">{0}</a>: {1}</li>",
+                doctype.as_str(), count
+            );
+        }
+        body.push_str("</ul>\n");
+        Page::new("Documents", body)
+    }
+
+    /// Returns the page listing all documents of a type.
+    pub fn doctype(&self, name: &str) -> Page<'a> {
+        let doctype = match DocumentType::ALL.iter().find(|doctype| {
+            doctype.as_str() == name
+        }) {
+            Some(doctype) => *doctype,
+            None => return Page::error(404, "Not Found"),
+        };
+        let mut links: Vec<_> = self.view().links().filter(|link| {
+            link.data(self.store).doctype() == doctype
+        }).collect();
+        links.sort_by_key(|link| link.data(self.store).key());
+        let mut body = String::new();
+        body.push_str("<ul>\n");
+        for link in links {
+            let _ = writeln!(body, "<li>{}</li>", self.link(link));
+        }
+        body.push_str("</ul>\n");
+        Page::new(name, body)
+    }
+
+    /// Returns the page for the document with the given key.
+    pub fn document(&self, key: &str) -> Page<'a> {
+        let link = match self.view().get(key) {
+            Some(link) => link,
+            None => return Page::error(404, "Not Found"),
+        };
+        let ds: &DataStore = self.store.as_ref();
+        let data = link.data(self.store);
+        let mut body = String::new();
+        let _ = writeln!(
+            body,
+            "<p>{} · {}</p>",
+            data.doctype().as_str(),
+            data.common().progress.into_value().as_str()
+        );
+
+        body.push_str("<h2>Data</h2>\n");
+        let mut yaml = String::new();
+        match write_document(&mut yaml, data, ds) {
+            Ok(()) => {
+                let _ = writeln!(body, "<pre>{}</pre>", escape(&yaml));
+            }
+            Err(_) => body.push_str("<p>Not available.</p>\n"),
+        }
+
+        body.push_str("<h2>References</h2>\n");
+        if let JsonValue::Object(xrefs) = link.xrefs(self.store).to_json(ds) {
+            body.push_str("<dl>\n");
+            for (name, items) in xrefs {
+                let items = match items {
+                    JsonValue::Array(items) if !items.is_empty() => items,
+                    _ => continue,
+                };
+                let _ = writeln!(body, "<dt>{}</dt>", escape(&name));
+                for item in items {
+                    let _ = writeln!(body, "<dd>{}</dd>", self.xref(&item));
+                }
+            }
+            body.push_str("</dl>\n");
+        }
+        Page::new(data.preferred_name(&self.preference), body)
+    }
+
+    /// Returns the page listing the lines an entity operated or owned.
+    ///
+    /// If `date` isn’t empty, only lines the entity may have operated or
+    /// owned at that date are listed.
+    pub fn operations(&self, key: &str, date: &str) -> Page<'a> {
+        let entity = match self.view().get(key).and_then(|link| {
+            link.document(self.store).try_as_entity()
+        }) {
+            Some(entity) => entity,
+            None => return Page::error(404, "Not Found"),
+        };
+        let date = if date.is_empty() {
+            None
+        }
+        else {
+            match Date::from_str(date) {
+                Ok(date) => Some(date),
+                Err(_) => return Page::error(400, "Bad Request"),
+            }
+        };
+        let mut body = String::new();
+        let _ = writeln!(
+            body, "<p>{}</p>", self.link(entity.data().link().into())
+        );
+        body.push_str("<ul>\n");
+        for operation in self.catalogue.operations(
+            entity.data().link()
+        ).iter().filter(|operation| {
+            date.as_ref().map(|date| {
+                operation.periods.may_include(date)
+            }).unwrap_or(true)
+        }) {
+            let _ = writeln!(
+                body, "<li>{} {}: {}</li>",
+                self.link(operation.line.into()), operation.role,
+                escape(&operation.periods.to_string())
+            );
+        }
+        body.push_str("</ul>\n");
+        let title = match date {
+            Some(date) => format!("Operations in {}", date),
+            None => "Operations".into(),
+        };
+        Page::new(&title, body)
+    }
+
+    /// Returns the page of the line with a national line code.
+    ///
+    /// If `date` isn’t empty, the line that had the code at that date is
+    /// shown instead of the one that has it now.
+    pub fn line_by_code(
+        &self, country: &str, code: &str, date: &str
+    ) -> Page<'a> {
+        let country = match CountryCode::from_str(country) {
+            Ok(country) => country,
+            Err(_) => return Page::error(404, "Not Found"),
+        };
+        let link = if date.is_empty() {
+            self.catalogue.line_by_national_code(country, code)
+        }
+        else {
+            match Date::from_str(date) {
+                Ok(date) => {
+                    self.catalogue.line_by_national_code_at(
+                        country, code, &date
+                    )
+                }
+                Err(_) => return Page::error(400, "Bad Request"),
+            }
+        };
+        match link {
+            Some(link) => self.document(link.data(self.store).key()),
+            None => Page::error(404, "Not Found"),
+        }
+    }
+
+    /// Returns the page for the points with a code.
+    ///
+    /// The code type is matched ignoring case. The point currently having
+    /// the code is listed first.
+    pub fn point_by_code(&self, code_type: &str, code: &str) -> Page<'a> {
+        let code_type = match point::CodeType::ALL.iter().copied().find(
+            |item| item.as_str().eq_ignore_ascii_case(code_type)
+        ) {
+            Some(code_type) => code_type,
+            None => return Page::error(404, "Not Found"),
+        };
+        let mut points = self.catalogue.points_by_code(
+            code_type, code
+        ).to_vec();
+        if let Some(current) = self.catalogue.point_by_current_code(
+            code_type, code
+        ) {
+            points.retain(|link| *link != current);
+            points.insert(0, current);
+        }
+        points.retain(|link| self.view().contains((*link).into()));
+        match points.as_slice() {
+            [] => Page::error(404, "Not Found"),
+            [link] => self.document(link.data(self.store).key()),
+            _ => {
+                let mut body = String::new();
+                body.push_str("<ul>\n");
+                for link in points {
+                    let _ = writeln!(
+                        body, "<li>{}</li>", self.link(link.into())
+                    );
+                }
+                body.push_str("</ul>\n");
+                Page::new(&format!("{} {}", code_type, code), body)
+            }
+        }
+    }
+
+    /// Returns the elevation profile of a line as JSON.
+    pub fn profile(&self, key: &str) -> Page<'a> {
+        match self.view().get(key).and_then(|link| {
+            link.document(self.store).try_as_line()
+        }) {
+            Some(line) => {
+                Page::json(&line.data().profile(self.store).to_json())
+            }
+            None => Page::error(404, "Not Found"),
+        }
+    }
+
+    /// Returns the changes of a document since a snapshot as JSON Patch.
+    ///
+    /// This doesn’t check the admin token. This happens when responding
+    /// to a request.
+    pub fn document_diff(&self, key: &str, against: &str) -> Page<'a> {
+        let snapshots = match self.snapshots {
+            Some(snapshots) => snapshots,
+            None => return Page::error(404, "Not Found"),
+        };
+        let link = match self.view().get(key) {
+            Some(link) => link,
+            None => return Page::error(404, "Not Found"),
+        };
+        let snapshot = match snapshots.get(against) {
+            Some(snapshot) => snapshot,
+            None => return Page::error(404, "Not Found"),
+        };
+        let snapshot: &DataStore = &snapshot;
+        let old = snapshot.get(key).map(|old| (old.data(snapshot), snapshot));
+        let mut page = Page::json(&json_patch(
+            old, link.data(self.store), self.store.as_ref()
+        ));
+        page.content_type = "application/json-patch+json";
+        page
+    }
+
+    /// Returns the lines and points of a map tile as GeoJSON.
+    pub fn tile(&self, tile: &str) -> Page<'a> {
+        let index = match self.index {
+            Some(index) => index,
+            None => return Page::error(404, "Not Found"),
+        };
+        match Tile::from_path(tile) {
+            Some(tile) => {
+                let mut page = Page::json(&index.tile_to_geojson(
+                    tile, &self.preference, self.view()
+                ));
+                page.content_type = "application/geo+json";
+                page
+            }
+            None => Page::error(404, "Not Found"),
+        }
+    }
+
+    /// Returns the page with the collection hierarchy of a source.
+    ///
+    /// The page shows the collections containing the source, outermost
+    /// first, and the tree of all sources contained in it.
+    pub fn sources(&self, key: &str) -> Page<'a> {
+        let source = match self.view().get(key).and_then(|link| {
+            link.document(self.store).try_as_source()
+        }) {
+            Some(source) => source,
+            None => return Page::error(404, "Not Found"),
+        };
+        let mut body = String::new();
+        let ancestors: Vec<_> = source.ancestors(self.store).collect();
+        if !ancestors.is_empty() {
+            body.push_str("<p>");
+            for (idx, item) in ancestors.iter().rev().enumerate() {
+                if idx > 0 {
+                    body.push_str(" › ");
+                }
+                body.push_str(&self.link(item.data().link().into()));
+            }
+            body.push_str("</p>\n");
+        }
+        body.push_str("<ul>\n");
+        self.source_tree(source, &mut body);
+        body.push_str("</ul>\n");
+        Page::new(
+            source.data().title.as_ref().map(|title| {
+                title.as_str()
+            }).unwrap_or_else(|| source.key().as_str()),
+            body
+        )
+    }
+
+    /// Appends the list item for a source and the sources it contains.
+    fn source_tree(&self, source: source::Document, body: &mut String) {
+        let _ = write!(
+            body, "<li>{}", self.link(source.data().link().into())
+        );
+        let mut items = source.items(self.store).peekable();
+        if items.peek().is_some() {
+            body.push_str("\n<ul>\n");
+            for item in items {
+                self.source_tree(item, body);
+            }
+            body.push_str("</ul>\n");
+        }
+        body.push_str("</li>\n");
+    }
+
+    /// Returns the search results page.
+    ///
+    /// Names only need to start with the text if `prefix` is given. The
+    /// `distance` defaults to one mistyped character.
+    pub fn search(
+        &self, text: &str, prefix: &str, distance: &str
+    ) -> Page<'a> {
+        let prefix = !prefix.is_empty();
+        let distance = if distance.is_empty() {
+            1
+        }
+        else {
+            match usize::from_str(distance) {
+                Ok(distance) => distance,
+                Err(_) => return Page::error(400, "Bad Request"),
+            }
+        };
+        let mut body = String::new();
+        let _ = write!(
+            body,
+            "<form action=\"/search\"><input name=\"q\" value=\"{}\"> \
+             <label><input type=\"checkbox\" name=\"prefix\" value=\"1\"{}> \
+             prefix</label> <select name=\"distance\">",
+            escape(text), if prefix { " checked" } else { "" }
+        );
+        for value in 0..=Catalogue::MAX_DISTANCE {
+            let _ = write!(
+                body, "<option{}>{}</option>",
+                if value == distance { " selected" } else { "" }, value
+            );
+        }
+        body.push_str("</select> <button>Search</button></form>\n");
+        if !text.is_empty() {
+            let mut found = self.catalogue.search(
+                text, prefix, distance, self.store
+            );
+            found.retain(|item| self.view().contains(item.link));
+            if found.is_empty() {
+                body.push_str("<p>Nothing found.</p>\n");
+            }
+            else {
+                body.push_str("<ul>\n");
+                for item in found.iter().take(Self::MAX_RESULTS) {
+                    let _ = writeln!(
+                        body, "<li>{} ({})</li>",
+                        self.link(item.link), escape(item.name)
+                    );
+                }
+                body.push_str("</ul>\n");
+            }
+        }
+        Page::new("Search", body)
+    }
+
+    /// Returns the course coverage of the lines as JSON.
+    pub fn geometry_coverage(&self) -> Page<'a> {
+        Page::json(&CourseCoverage::generate(self.store.as_ref()).to_json())
+    }
+
+    /// Returns the page listing the keys with the given prefix.
+    ///
+    /// If the prefix is empty, lists all key namespaces instead.
+    pub fn keys(&self, prefix: &str) -> Page<'a> {
+        let mut body = String::new();
+        body.push_str("<ul>\n");
+        if prefix.is_empty() {
+            for (namespace, links) in self.catalogue.namespaces() {
+                let _ = writeln!(
+                    body,
+                    "<li><a href=\"/index/keys?prefix={}\">{}</a>: {}</li>",
+                    url::form_urlencoded::byte_serialize(
+                        namespace.as_bytes()
+                    ).collect::<String>(),
+                    escape(namespace), links.len()
+                );
+            }
+            body.push_str("</ul>\n");
+            return Page::new("Keys", body)
+        }
+        let mut links = self.catalogue.keys_with_prefix(prefix, self.store);
+        links.retain(|link| self.view().contains(*link));
+        if links.is_empty() {
+            return Page::error(404, "Not Found")
+        }
+        for link in links {
+            let _ = writeln!(body, "<li>{}</li>", self.link(link));
+        }
+        body.push_str("</ul>\n");
+        Page::new(prefix, body)
+    }
+
+    /// Returns the page listing sources by decade and subtype.
+    pub fn sources_index(&self, decade: &str, subtype: &str) -> Page<'a> {
+        if decade.is_empty() && subtype.is_empty() {
+            let mut body = String::new();
+            body.push_str("<h2>By decade</h2>\n<ul>\n");
+            for (decade, count) in self.catalogue.source_decades() {
+                let _ = writeln!(
+                    body,
+                    "<li><a href=\"/index/sources?decade={0}\">{0}s</a>: \
+                     {1}</li>",
+                    decade, count
+                );
+            }
+            body.push_str("</ul>\n<h2>By subtype</h2>\n<ul>\n");
+            for (subtype, count) in self.catalogue.source_subtypes() {
+                let _ = writeln!(
+                    body,
+                    "<li><a href=\"/index/sources?subtype={0}\">{0}</a>: \
+                     {1}</li>",
+                    subtype, count
+                );
+            }
+            body.push_str("</ul>\n");
+            return Page::new("Sources", body)
+        }
+        let decade = if decade.is_empty() {
+            None
+        }
+        else {
+            match i16::from_str(decade) {
+                Ok(year) => Some(year.div_euclid(10) * 10),
+                Err(_) => return Page::error(400, "Bad Request"),
+            }
+        };
+        let subtype = if subtype.is_empty() {
+            None
+        }
+        else {
+            match source::Subtype::ALL.iter().copied().find(|item| {
+                item.as_str() == subtype
+            }) {
+                Some(subtype) => Some(subtype),
+                None => return Page::error(400, "Bad Request"),
+            }
+        };
+        let mut body = String::new();
+        body.push_str("<ul>\n");
+        for link in self.catalogue.sources(decade, subtype, self.store) {
+            if !self.view().contains(link.into()) {
+                continue
+            }
+            let _ = writeln!(body, "<li>{}</li>", self.link(link.into()));
+        }
+        body.push_str("</ul>\n");
+        let title = match (decade, subtype) {
+            (Some(decade), Some(subtype)) => {
+                format!("Sources of type {} from the {}s", subtype, decade)
+            }
+            (Some(decade), None) => format!("Sources from the {}s", decade),
+            (None, Some(subtype)) => format!("Sources of type {}", subtype),
+            (None, None) => "Sources".into(),
+        };
+        Page::new(&title, body)
+    }
+
+    /// Returns an HTML link to a document.
+    fn link(&self, link: DocumentLink) -> String {
+        let data = link.data(self.store);
+        format!(
+            "<a href=\"/doc/{}\">{}</a> <small>{}</small>",
+            escape(data.key().as_str()),
+            escape(data.preferred_name(&self.preference)),
+            escape(data.key().as_str())
+        )
+    }
+
+    /// Returns the HTML for an item of the JSON cross references.
+    ///
+    /// Items are either a key or an object with a line key and a section.
+    fn xref(&self, item: &JsonValue) -> String {
+        if let Some(key) = item.as_str() {
+            return self.key_link(key)
+        }
+        let line = item.get("line").and_then(JsonValue::as_str);
+        let section = item.get("section").and_then(JsonValue::as_str);
+        match (line, section) {
+            (Some(line), Some(section)) => {
+                format!("{} {}", self.key_link(line), escape(section))
+            }
+            _ => escape(&item.to_string()),
+        }
+    }
+
+    /// Returns an HTML link for a key.
+    fn key_link(&self, key: &str) -> String {
+        match self.view().get(key) {
+            Some(link) => self.link(link),
+            None => escape(key),
+        }
+    }
+}
+
+
+//------------ Server --------------------------------------------------------
+
+/// Serves the data of a [`State`] over HTTP.
+pub struct Server {
+    state: Arc<State>,
+    preference: LanguagePreference,
+    token: Option<String>,
+    logger: Option<Logger>,
+    snapshots: Option<Arc<Snapshots>>,
+
+    /// The minimum progress of documents shown.
+    min_progress: Progress,
+
+    /// The number of threads handling connections.
+    workers: usize,
+}
+
+impl Server {
+    /// The number of threads handling connections by default.
+    pub const DEFAULT_WORKERS: usize = 16;
+
+    pub fn new(state: Arc<State>, preference: LanguagePreference) -> Self {
+        Server {
+            state, preference, token: None, logger: None, snapshots: None,
+            min_progress: Progress::Stub,
+            workers: Self::DEFAULT_WORKERS,
+        }
+    }
+
+    /// Only shows documents with at least the given progress.
+    pub fn with_min_progress(mut self, min: Progress) -> Self {
+        self.min_progress = min;
+        self
+    }
+
+    /// Sets the number of threads handling connections.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Compares documents against the given snapshots.
+    pub fn with_snapshots(mut self, snapshots: Snapshots) -> Self {
+        self.snapshots = Some(Arc::new(snapshots));
+        self
+    }
+
+    /// Enables the admin endpoints protected by the given token.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Logs all handled requests with the given logger.
+    pub fn with_logger(mut self, logger: Logger) -> Self {
+        self.logger = Some(logger);
+        self
+    }
+
+    pub fn state(&self) -> &Arc<State> {
+        &self.state
+    }
+
+    /// Serves the pages over HTTP at `addr` until an error happens.
+    ///
+    /// Connections are handled by a fixed number of worker threads. If
+    /// all of them are busy, new connections wait until one is free.
+    pub fn serve(&self, addr: impl ToSocketAddrs) -> Result<(), io::Error> {
+        let listener = TcpListener::bind(addr)?;
+        let (sender, receiver) = mpsc::sync_channel::<TcpStream>(
+            self.workers
+        );
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..self.workers {
+            let receiver = receiver.clone();
+            let state = self.state.clone();
+            let preference = self.preference.clone();
+            let token = self.token.clone();
+            let logger = self.logger;
+            let snapshots = self.snapshots.clone();
+            let min_progress = self.min_progress;
+            thread::spawn(move || {
+                loop {
+                    let stream = match receiver.lock().unwrap().recv() {
+                        Ok(stream) => stream,
+                        Err(_) => break,
+                    };
+                    // The data stays alive until the request is finished
+                    // even if it is replaced in the meantime.
+                    let current = state.current();
+                    let mut browser = Browser::new(
+                        &current.store, &current.catalogue,
+                        preference.clone()
+                    ).with_index(
+                        &current.index
+                    ).with_min_progress(min_progress);
+                    browser.token = token.clone();
+                    browser.logger = logger;
+                    browser.snapshots = snapshots.as_deref();
+                    // A broken connection shouldn’t stop the server.
+                    if let Ok(true) = browser.handle(stream) {
+                        state.reload();
+                    }
+                }
+            });
+        }
+        for stream in listener.incoming() {
+            if sender.send(stream?).is_err() {
+                break
+            }
+        }
+        Ok(())
+    }
+}
+
+
+//------------ State ---------------------------------------------------------
+
+/// The data served by a [`Server`].
+pub struct State {
+    /// The currently served data.
+    current: RwLock<Arc<Loaded>>,
+
+    /// The function loading new data.
+    ///
+    /// If this is `None`, the data can only be replaced explicitly.
+    loader: Option<Box<dyn Fn() -> Option<Loaded> + Send + Sync>>,
+
+    /// Is a reload currently running?
+    reloading: AtomicBool,
+}
+
+impl State {
+    pub fn new(store: FullStore, catalogue: Catalogue) -> Self {
+        State {
+            current: RwLock::new(Arc::new(Loaded::new(store, catalogue))),
+            loader: None,
+            reloading: AtomicBool::new(false),
+        }
+    }
+
+    /// Sets the function used to load new data.
+    ///
+    /// The function should report any problems itself and return `None`
+    /// if loading failed. The current data is kept in this case.
+    pub fn with_loader(
+        mut self,
+        loader: impl Fn() -> Option<Loaded> + Send + Sync + 'static
+    ) -> Self {
+        self.loader = Some(Box::new(loader));
+        self
+    }
+
+    /// Returns the currently served data.
+    pub fn current(&self) -> Arc<Loaded> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Replaces the served data, returning the previous data.
+    pub fn replace(
+        &self, store: FullStore, catalogue: Catalogue
+    ) -> Arc<Loaded> {
+        std::mem::replace(
+            &mut *self.current.write().unwrap(),
+            Arc::new(Loaded::new(store, catalogue))
+        )
+    }
+
+    /// Loads new data in the background.
+    ///
+    /// Does nothing if there is no loader or a reload is already running.
+    pub fn reload(self: &Arc<Self>) {
+        if self.loader.is_none() {
+            return
+        }
+        if self.reloading.swap(true, Ordering::SeqCst) {
+            return
+        }
+        let state = self.clone();
+        thread::spawn(move || {
+            if let Some(loaded) = state.loader.as_ref().and_then(|loader| {
+                loader()
+            }) {
+                let old = std::mem::replace(
+                    &mut *state.current.write().unwrap(), Arc::new(loaded)
+                );
+                drop(old);
+            }
+            // Strings only used by the old data or by a failed load can go.
+            // Those still used by running requests are dropped next time.
+            Text::release_unused();
+            state.reloading.store(false, Ordering::SeqCst);
+        });
+    }
+
+    /// Reloads the data whenever a file below `path` changes.
+    ///
+    /// The tree is checked for files modified after the last check every
+    /// `interval` in a background thread.
+    pub fn watch(self: &Arc<Self>, path: PathBuf, interval: Duration) {
+        let state = self.clone();
+        thread::spawn(move || {
+            let mut last = newest_modification(&path);
+            loop {
+                thread::sleep(interval);
+                let newest = newest_modification(&path);
+                if newest > last {
+                    last = newest;
+                    state.reload();
+                }
+            }
+        });
+    }
+}
+
+
+//------------ Loaded --------------------------------------------------------
+
+/// A store and its catalogue as loaded at one time.
+pub struct Loaded {
+    pub store: FullStore,
+    pub catalogue: Catalogue,
+
+    /// The spatial index of the store for serving map tiles.
+    pub index: SpatialIndex,
+}
+
+impl Loaded {
+    /// Creates the loaded data, building the spatial index.
+    pub fn new(store: FullStore, catalogue: Catalogue) -> Self {
+        let index = SpatialIndex::new(&store);
+        Loaded { store, catalogue, index }
+    }
+}
+
+
+//------------ Snapshots -----------------------------------------------------
+
+/// Earlier versions of the data to compare documents against.
+///
+/// Each snapshot is a data tree in a directory below a base directory,
+/// such as a checkout of a release. The name of the directory is the id
+/// of the snapshot. Snapshots are loaded when first used and then kept
+/// until more than [`capacity`][Self::with_capacity] snapshots have been
+/// used, at which point the least recently used one is dropped. A
+/// snapshot that fails to load is remembered as failed in the same way.
+#[derive(Debug)]
+pub struct Snapshots {
+    /// The directory containing the snapshots.
+    base: PathBuf,
+
+    /// The maximum number of snapshots kept.
+    capacity: usize,
+
+    /// The snapshots used so far, least recently used first.
+    ///
+    /// Each snapshot is loaded through its own cell, so the lock is only
+    /// held while looking up the cell and not while loading.
+    loaded: Mutex<Vec<(String, Arc<SnapshotCell>)>>,
+}
+
+/// A snapshot that is loaded once and is `None` if that failed.
+type SnapshotCell = OnceLock<Option<Arc<DataStore>>>;
+
+impl Snapshots {
+    /// The number of snapshots kept by default.
+    pub const DEFAULT_CAPACITY: usize = 4;
+
+    pub fn new(base: impl Into<PathBuf>) -> Self {
+        Snapshots {
+            base: base.into(),
+            capacity: Self::DEFAULT_CAPACITY,
+            loaded: Default::default(),
+        }
+    }
+
+    /// Sets the maximum number of snapshots kept in memory.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Returns the snapshot with the given id.
+    ///
+    /// Returns `None` if there is no such snapshot or it fails to load.
+    pub fn get(&self, id: &str) -> Option<Arc<DataStore>> {
+        // Only allow plain directory names.
+        let plain = !id.is_empty() && !id.starts_with('.')
+            && !id.contains(['/', '\\']);
+        if !plain {
+            return None
+        }
+        let path = self.base.join(id);
+        if !path.is_dir() {
+            return None
+        }
+        let cell = self.cell(id);
+        cell.get_or_init(|| load_tree(&path).ok().map(Arc::new)).clone()
+    }
+
+    /// Returns the cell for the snapshot with the given id.
+    ///
+    /// Marks the snapshot as most recently used and drops the least
+    /// recently used ones if there are too many.
+    fn cell(&self, id: &str) -> Arc<SnapshotCell> {
+        let mut loaded = self.loaded.lock().unwrap();
+        let item = match loaded.iter().position(|item| item.0 == id) {
+            Some(index) => loaded.remove(index),
+            None => (id.into(), Default::default()),
+        };
+        let cell = item.1.clone();
+        loaded.push(item);
+        if loaded.len() > self.capacity {
+            let excess = loaded.len() - self.capacity;
+            loaded.drain(..excess);
+        }
+        cell
+    }
+}
+
+
+//------------ Logger --------------------------------------------------------
+
+/// Logs handled requests to standard error.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Logger {
+    format: LogFormat,
+    level: LogLevel,
+}
+
+impl Logger {
+    pub fn new(format: LogFormat, level: LogLevel) -> Self {
+        Logger { format, level }
+    }
+
+    /// Logs a handled request.
+    fn log(
+        &self,
+        method: &str,
+        path: &str,
+        status: u16,
+        duration: Duration,
+        client: Option<SocketAddr>,
+    ) {
+        match self.level {
+            LogLevel::Off => return,
+            LogLevel::Error if status < 400 => return,
+            _ => { }
+        }
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| {
+            time.as_secs()
+        }).unwrap_or(0);
+        let millis = duration.as_secs_f64() * 1000.;
+        match self.format {
+            LogFormat::Text => {
+                eprintln!(
+                    "{} {} {} {} {} {:.3}ms",
+                    time,
+                    client.map(|addr| addr.to_string()).unwrap_or_else(|| {
+                        String::from("-")
+                    }),
+                    method, path, status, millis
+                );
+            }
+            LogFormat::Json => {
+                eprintln!(
+                    "{}",
+                    json!({
+                        "time": time,
+                        "client": client.map(|addr| addr.to_string()),
+                        "method": method,
+                        "path": path,
+                        "status": status,
+                        "duration_ms": millis,
+                    })
+                );
+            }
+        }
+    }
+}
+
+
+//------------ LogFormat -----------------------------------------------------
+
+/// The format of logged requests.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum LogFormat {
+    /// One line of text with space separated values per request.
+    #[default]
+    Text,
+
+    /// One JSON object per line.
+    Json,
+}
+
+impl LogFormat {
+    pub const ALL: &'static [LogFormat] = &[LogFormat::Text, LogFormat::Json];
+
+    /// Returns the format with the given name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|item| item.as_str() == name)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogFormat::Text => "text",
+            LogFormat::Json => "json",
+        }
+    }
+}
+
+
+//------------ LogLevel ------------------------------------------------------
+
+/// Which requests are logged.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum LogLevel {
+    /// No requests are logged.
+    Off,
+
+    /// Only requests answered with an error status are logged.
+    Error,
+
+    /// All requests are logged.
+    #[default]
+    Info,
+}
+
+impl LogLevel {
+    pub const ALL: &'static [LogLevel] = &[
+        LogLevel::Off, LogLevel::Error, LogLevel::Info,
+    ];
+
+    /// Returns the level with the given name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|item| item.as_str() == name)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Off => "off",
+            LogLevel::Error => "error",
+            LogLevel::Info => "info",
+        }
+    }
+}
+
+
+//------------ Page ----------------------------------------------------------
+
+/// A rendered page.
+///
+/// The content of large pages is written while sending the page rather
+/// than kept in memory. Such pages borrow the data they are written from
+/// for the lifetime `'a`.
+pub struct Page<'a> {
+    /// The HTTP status code of the page.
+    pub status: u16,
+
+    /// The media type of the content.
+    pub content_type: &'static str,
+
+    /// The complete content of the page.
+    ///
+    /// This is empty if the content is streamed.
+    pub content: Vec<u8>,
+
+    /// A function writing the content while the page is sent.
+    stream: Option<Stream<'a>>,
+
+    /// Additional HTTP headers as pairs of name and value.
+    pub headers: Vec<(&'static str, String)>,
+}
+
+/// A function writing the content of a page.
+type Stream<'a> = Box<
+    dyn FnOnce(&mut dyn Write) -> Result<(), io::Error> + 'a
+>;
+
+impl<'a> Page<'a> {
+    /// Creates a page with the given title and body content.
+    ///
+    /// The title is escaped, the body is used as is.
+    fn new(title: &str, body: String) -> Self {
+        Self::with_status(200, title, body)
+    }
+
+    /// Creates an error page.
+    fn error(status: u16, reason: &str) -> Self {
+        Self::with_status(status, reason, String::new())
+    }
+
+    /// Creates a page containing JSON.
+    ///
+    /// The value is serialized straight into the content without going
+    /// through an intermediate string.
+    fn json(value: &JsonValue) -> Self {
+        let mut content = Vec::new();
+        // Writing into a vec can’t fail and neither can serializing a
+        // JSON value.
+        serde_json::to_writer(&mut content, value).unwrap();
+        Page {
+            status: 200,
+            content_type: "application/json",
+            content,
+            stream: None,
+            headers: Vec::new(),
+        }
+    }
+
+    /// Creates a page containing JSON written by `op` while sending.
+    ///
+    /// The page is sent using the chunked transfer coding, so neither
+    /// the whole JSON nor its length need to be known in advance.
+    fn json_stream(
+        op: impl FnOnce(&mut dyn Write) -> Result<(), io::Error> + 'a
+    ) -> Self {
+        Page {
+            status: 200,
+            content_type: "application/json",
+            content: Vec::new(),
+            stream: Some(Box::new(op)),
+            headers: Vec::new(),
+        }
+    }
+
+    fn with_status(status: u16, title: &str, body: String) -> Self {
+        let title = escape(title);
+        Page {
+            status,
+            content_type: "text/html; charset=utf-8",
+            content: format!(
+                "<!DOCTYPE html>\n\
+                 <html><head><meta charset=\"utf-8\">\
+                 <title>{title}</title></head>\n\
+                 <body>\n\
+                 <nav><a href=\"/\">Index</a> · \
+                 <a href=\"/search\">Search</a></nav>\n\
+                 <h1>{title}</h1>\n\
+                 {body}\
+                 </body></html>\n"
+            ).into_bytes(),
+            stream: None,
+            headers: Vec::new(),
+        }
+    }
+
+    /// Adds the headers for the license and attribution of the data.
+    fn add_attribution(&mut self, store: &DataStore) {
+        let attribution = store.attribution();
+        if let Some(url) = attribution.url.as_ref() {
+            self.headers.push(
+                ("Link", format!("<{}>; rel=\"license\"", url.as_str()))
+            );
+        }
+        if let Some(license) = attribution.license.as_ref() {
+            self.headers.push(("X-License", header_value(license.as_str())));
+        }
+        if let Some(text) = attribution.text.as_ref() {
+            self.headers.push(
+                ("X-Attribution", header_value(text.as_str()))
+            );
+        }
+    }
+
+    /// Writes the page as an HTTP response.
+    ///
+    /// The head is buffered so it is sent together with the start of the
+    /// content.
+    fn write(self, target: &mut impl Write) -> Result<(), io::Error> {
+        let mut target = BufWriter::new(target);
+        write!(
+            target,
+            "HTTP/1.1 {} {}\r\n\
+             Content-Type: {}\r\n",
+            self.status,
+            match self.status {
+                200 => "OK",
+                202 => "Accepted",
+                400 => "Bad Request",
+                401 => "Unauthorized",
+                404 => "Not Found",
+                405 => "Method Not Allowed",
+                431 => "Request Header Fields Too Large",
+                _ => "Error",
+            },
+            self.content_type,
+        )?;
+        if self.stream.is_some() {
+            write!(target, "Transfer-Encoding: chunked\r\n")?;
+        }
+        else {
+            write!(target, "Content-Length: {}\r\n", self.content.len())?;
+        }
+        for (name, value) in &self.headers {
+            write!(target, "{}: {}\r\n", name, value)?;
+        }
+        write!(target, "Connection: close\r\n\r\n")?;
+        match self.stream {
+            Some(op) => {
+                let mut chunked = BufWriter::with_capacity(
+                    Self::CHUNK_SIZE, Chunked(&mut target)
+                );
+                op(&mut chunked)?;
+                chunked.flush()?;
+                drop(chunked);
+                target.write_all(b"0\r\n\r\n")?;
+            }
+            None => target.write_all(&self.content)?,
+        }
+        target.flush()
+    }
+}
+
+impl Page<'_> {
+    /// The size of the chunks of streamed content.
+    const CHUNK_SIZE: usize = 64 * 1024;
+}
+
+impl fmt::Debug for Page<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Page")
+            .field("status", &self.status)
+            .field("content_type", &self.content_type)
+            .field("content", &self.content)
+            .field("stream", &self.stream.is_some())
+            .field("headers", &self.headers)
+            .finish()
+    }
+}
+
+
+//------------ Chunked -------------------------------------------------------
+
+/// A writer using the chunked transfer coding.
+///
+/// Each write becomes a chunk, so this should be used behind a buffer.
+/// The final empty chunk isn’t written.
+struct Chunked<W>(W);
+
+impl<W: Write> Write for Chunked<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
+        // An empty chunk would end the content.
+        if !buf.is_empty() {
+            write!(self.0, "{:x}\r\n", buf.len())?;
+            self.0.write_all(buf)?;
+            self.0.write_all(b"\r\n")?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), io::Error> {
+        self.0.flush()
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// The maximum length of a line in the head of a request.
+const MAX_HEAD_LINE: usize = 8 * 1024;
+
+/// The maximum number of header lines in a request.
+const MAX_HEADERS: usize = 100;
+
+/// The parts of the head of an HTTP request we care about.
+struct Head {
+    /// The request line.
+    request: String,
+
+    /// The value of the `Authorization` header if there is one.
+    authorization: Option<String>,
+
+    /// The value of the `Accept-Language` header if there is one.
+    accept_language: Option<String>,
+}
+
+/// Reads the head of an HTTP request.
+///
+/// If a line is longer than [`MAX_HEAD_LINE`] or there are more than
+/// [`MAX_HEADERS`] headers, returns an error of kind `InvalidData`.
+fn read_head(reader: &mut impl BufRead) -> Result<Head, io::Error> {
+    let mut head = Head {
+        request: read_head_line(reader)?,
+        authorization: None,
+        accept_language: None,
+    };
+    for _ in 0..=MAX_HEADERS {
+        let header = read_head_line(reader)?;
+        if header.trim().is_empty() {
+            return Ok(head)
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let name = name.trim();
+            if name.eq_ignore_ascii_case("authorization") {
+                head.authorization = Some(value.trim().to_string());
+            }
+            else if name.eq_ignore_ascii_case("accept-language") {
+                head.accept_language = Some(value.trim().to_string());
+            }
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "too many headers"))
+}
+
+/// Reads a single line of the head of an HTTP request.
+///
+/// Returns an empty string at the end of the stream.
+fn read_head_line(reader: &mut impl BufRead) -> Result<String, io::Error> {
+    let mut line = String::new();
+    let len = reader.take(MAX_HEAD_LINE as u64).read_line(&mut line)?;
+    if len == MAX_HEAD_LINE && !line.ends_with('\n') {
+        return Err(
+            io::Error::new(io::ErrorKind::InvalidData, "line too long")
+        )
+    }
+    Ok(line)
+}
+
+/// Returns whether a request target is for the diff of a document.
+///
+/// The path is decoded the same way as by [`Browser::page`].
+fn is_diff(target: &str) -> bool {
+    let path = target.split('?').next().unwrap_or(target);
+    let path = percent_decode(path.as_bytes()).decode_utf8_lossy();
+    path.starts_with("/document/") && path.ends_with("/diff")
+}
+
+/// Returns whether the provided token matches the expected one.
+///
+/// In order not to reveal through the response time how much of the token
+/// was right, the comparison takes the same time no matter where the two
+/// differ. Comparing the digests instead of the tokens themselves also
+/// hides their length.
+fn token_matches(provided: &str, expected: &str) -> bool {
+    let provided = Sha256::digest(provided.as_bytes());
+    let expected = Sha256::digest(expected.as_bytes());
+    provided.iter().zip(expected.iter()).fold(0, |res, (left, right)| {
+        res | (left ^ right)
+    }) == 0
+}
+
+/// Returns the newest modification time of all files below `path`.
+fn newest_modification(path: &Path) -> Option<SystemTime> {
+    ignore::Walk::new(path).filter_map(|entry| {
+        entry.ok()?.metadata().ok()?.modified().ok()
+    }).max()
+}
+
+/// Turns a text into a valid header value.
+///
+/// Line breaks and other control characters are replaced by spaces.
+fn header_value(text: &str) -> String {
+    text.chars().map(|ch| {
+        if ch.is_control() { ' ' } else { ch }
+    }).collect()
+}
+
+/// Returns the value of a query parameter or an empty string.
+fn query_param<'a>(query: Option<&'a str>, name: &str) -> Cow<'a, str> {
+    query.and_then(|query| {
+        url::form_urlencoded::parse(query.as_bytes()).find_map(
+            |(key, value)| (key == name).then_some(value)
+        )
+    }).unwrap_or_default()
+}
+
+/// Escapes text for use in HTML content and attribute values.
+fn escape(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            _ => res.push(ch),
+        }
+    }
+    res
+}
+
@@ -1,6 +1,28 @@
 #[macro_use] pub mod types;
+pub mod api;
+pub mod attachment;
+pub mod bibliography;
+pub mod bulk;
 pub mod catalogue;
+pub mod changes;
+pub mod citation;
+pub mod diff;
 pub mod document;
+pub mod duplicates;
+#[cfg(feature = "html")] pub mod html;
+#[cfg(feature = "linkcheck")] pub mod linkcheck;
 pub mod load;
+pub mod query;
+pub mod railml;
+pub mod refactor;
+pub mod release;
+pub mod repl;
+pub mod schema;
+pub mod sqlite;
+pub mod stats;
 pub mod store;
+pub mod template;
+pub mod testdata;
+pub mod tiles;
+pub mod validate;
 
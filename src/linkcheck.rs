@@ -0,0 +1,276 @@
+//! Checking external links of sources.
+//!
+//! Sources point to external resources through their `url` and `digital`
+//! attributes. The [`LinkChecker`] requests all these URLs concurrently
+//! and collects those that don’t work as [`DeadLinks`], grouped by the
+//! source document that contains them.
+//!
+//! Each distinct URL is only requested once per checker, even if it
+//! appears in several sources or if the checker is run several times.
+
+use std::{fmt, thread};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use crate::load::report::Origin;
+use crate::store::DataStore;
+use crate::types::{Key, Marked, Url};
+
+
+//------------ LinkChecker ---------------------------------------------------
+
+/// Checks the URLs of all sources.
+#[derive(Debug)]
+pub struct LinkChecker {
+    /// The maximum number of requests in flight at the same time.
+    concurrency: usize,
+
+    /// The timeout for each request.
+    timeout: Duration,
+
+    /// The results of URLs checked already.
+    cache: Mutex<HashMap<String, LinkStatus>>,
+}
+
+impl Default for LinkChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LinkChecker {
+    /// The default number of concurrent requests.
+    pub const DEFAULT_CONCURRENCY: usize = 8;
+
+    /// The default timeout for a single request.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(20);
+
+    pub fn new() -> Self {
+        LinkChecker {
+            concurrency: Self::DEFAULT_CONCURRENCY,
+            timeout: Self::DEFAULT_TIMEOUT,
+            cache: Default::default(),
+        }
+    }
+
+    /// Sets the maximum number of concurrent requests.
+    ///
+    /// A value of zero is treated as one.
+    pub fn set_concurrency(&mut self, concurrency: usize) {
+        self.concurrency = concurrency.max(1)
+    }
+
+    /// Sets the timeout for each request.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout
+    }
+
+    /// Checks all URLs of all sources in the store.
+    pub fn check(&self, store: &DataStore) -> DeadLinks {
+        let links = collect_links(store);
+        self.fetch(
+            links.iter().map(|link| link.url.as_str())
+        );
+
+        let cache = self.cache.lock().unwrap();
+        let mut res = DeadLinks::default();
+        for link in links {
+            match cache.get(link.url.as_str()) {
+                Some(LinkStatus::Ok) | None => { }
+                Some(status) => {
+                    res.sources.entry(link.key.clone()).or_insert_with(|| {
+                        (link.origin.clone(), Vec::new())
+                    }).1.push(DeadLink {
+                        url: link.url.as_value().clone(),
+                        origin: link.origin.at(link.url.location()),
+                        status: status.clone(),
+                    });
+                }
+            }
+        }
+        res
+    }
+
+    /// Requests all URLs that aren’t in the cache yet.
+    fn fetch<'a>(&self, urls: impl Iterator<Item = &'a str>) {
+        let todo = {
+            let cache = self.cache.lock().unwrap();
+            urls.filter(|url| {
+                !cache.contains_key(*url)
+            }).collect::<HashSet<_>>().into_iter().collect::<Vec<_>>()
+        };
+        if todo.is_empty() {
+            return
+        }
+
+        let agent = ureq::AgentBuilder::new()
+            .timeout(self.timeout)
+            .redirects(10)
+            .build();
+        let next = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            for _ in 0..self.concurrency.min(todo.len()) {
+                scope.spawn(|| {
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        let url = match todo.get(idx) {
+                            Some(url) => *url,
+                            None => break
+                        };
+                        let status = request(&agent, url);
+                        self.cache.lock().unwrap().insert(
+                            url.into(), status
+                        );
+                    }
+                });
+            }
+        });
+    }
+}
+
+
+//------------ LinkStatus ----------------------------------------------------
+
+/// The result of checking a single URL.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum LinkStatus {
+    /// The resource is available.
+    Ok,
+
+    /// The server answered with an error status code.
+    Status(u16),
+
+    /// The resource couldn’t be requested at all.
+    Failed(String),
+}
+
+impl fmt::Display for LinkStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LinkStatus::Ok => f.write_str("ok"),
+            LinkStatus::Status(code) => write!(f, "HTTP status {}", code),
+            LinkStatus::Failed(ref err) => f.write_str(err),
+        }
+    }
+}
+
+
+//------------ DeadLinks -----------------------------------------------------
+
+/// The dead links found by a link checker.
+#[derive(Clone, Debug, Default)]
+pub struct DeadLinks {
+    /// The dead links for each source along with the source’s origin.
+    sources: BTreeMap<Key, (Origin, Vec<DeadLink>)>,
+}
+
+impl DeadLinks {
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Returns the number of dead links.
+    pub fn len(&self) -> usize {
+        self.sources.values().map(|(_, links)| links.len()).sum()
+    }
+
+    /// Returns the number of sources with dead links.
+    pub fn source_count(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Returns an iterator over the sources and their dead links.
+    pub fn iter(
+        &self
+    ) -> impl Iterator<Item = (&Key, &Origin, &[DeadLink])> + '_ {
+        self.sources.iter().map(|(key, (origin, links))| {
+            (key, origin, links.as_slice())
+        })
+    }
+}
+
+impl fmt::Display for DeadLinks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (key, origin, links) in self.iter() {
+            writeln!(f, "{} ({}):", key, origin)?;
+            for link in links {
+                writeln!(f, "    {}: {}", link.url, link.status)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+
+//------------ DeadLink ------------------------------------------------------
+
+/// A single URL that didn’t work.
+#[derive(Clone, Debug)]
+pub struct DeadLink {
+    /// The URL.
+    pub url: Url,
+
+    /// Where the URL appears.
+    pub origin: Origin,
+
+    /// What went wrong.
+    pub status: LinkStatus,
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// A URL of a source.
+struct SourceLink<'a> {
+    key: &'a Key,
+    origin: &'a Origin,
+    url: &'a Marked<Url>,
+}
+
+/// Collects all URLs of all sources in the store.
+fn collect_links(store: &DataStore) -> Vec<SourceLink<'_>> {
+    let mut res = Vec::new();
+    for source in store.iter().filter_map(|data| data.try_as_source()) {
+        let urls = source.url.iter().chain(source.digital.iter());
+        for url in urls {
+            res.push(SourceLink {
+                key: source.key(),
+                origin: source.origin(),
+                url,
+            });
+        }
+    }
+    res
+}
+
+/// Requests a single URL.
+///
+/// Tries a HEAD request first and falls back to GET for servers that
+/// don’t support HEAD.
+fn request(agent: &ureq::Agent, url: &str) -> LinkStatus {
+    match agent.head(url).call() {
+        Ok(_) => return LinkStatus::Ok,
+        Err(ureq::Error::Status(405 | 501, _)) => { }
+        Err(err) => return error_status(err),
+    }
+    match agent.get(url).call() {
+        Ok(_) => LinkStatus::Ok,
+        Err(err) => error_status(err),
+    }
+}
+
+fn error_status(err: ureq::Error) -> LinkStatus {
+    match err {
+        ureq::Error::Status(code, _) => LinkStatus::Status(code),
+        ureq::Error::Transport(err) => {
+            // The transport error’s display includes the URL which we
+            // print already.
+            LinkStatus::Failed(match err.message() {
+                Some(msg) => format!("{}: {}", err.kind(), msg),
+                None => err.kind().to_string(),
+            })
+        }
+    }
+}
+
@@ -0,0 +1,256 @@
+//! Configuration of a data tree.
+//!
+//! A data tree can contain a file `raildata.toml` in its root directory
+//! that tunes how strictly the data is validated. For each validation
+//! rule, it determines whether problems are reported as errors, as
+//! warnings, or not at all. This can be set for all documents, for the
+//! documents of a type, and for the documents of a country:
+//!
+//! ```toml
+//! [rules]
+//! kilometrage = "error"
+//!
+//! [types.point]
+//! point-lines = "ignore"
+//!
+//! [countries.de]
+//! line-status = "error"
+//! ```
+//!
+//! The country of a document is taken from its key. The most specific
+//! setting wins, so a setting for a country overrides one for a type
+//! which in turn overrides the general one. Rules that aren’t configured
+//! at all report warnings.
+//!
+//! The file can also state the license of the data and how to attribute
+//! it. This is included in all exports of the data:
+//!
+//! ```toml
+//! [attribution]
+//! license = "CC-BY-4.0"
+//! text = "Railway History Data Project"
+//! url = "https://creativecommons.org/licenses/by/4.0/"
+//! ```
+
+use std::{fs, io};
+use std::collections::HashMap;
+use std::path::Path;
+use derive_more::Display;
+use toml::{Table, Value};
+use crate::document::combined::Data;
+use crate::document::common::{Attribution, DocumentType};
+use crate::validate;
+use super::report::Severity;
+
+
+//------------ Config --------------------------------------------------------
+
+/// The configuration of a data tree.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    /// The levels of rules for all documents.
+    rules: RuleLevels,
+
+    /// The levels of rules for documents of a type.
+    types: HashMap<DocumentType, RuleLevels>,
+
+    /// The levels of rules for documents of a country.
+    ///
+    /// The country is the lower case country part of a key.
+    countries: HashMap<String, RuleLevels>,
+
+    /// The license and attribution of the data.
+    attribution: Attribution,
+}
+
+/// The levels of rules by the name of the rule.
+type RuleLevels = HashMap<&'static str, Level>;
+
+impl Config {
+    /// The name of the configuration file in the root of a data tree.
+    pub const FILE_NAME: &'static str = "raildata.toml";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the configuration of the data tree at `base`.
+    ///
+    /// If the tree doesn’t have a configuration file, returns the default
+    /// configuration.
+    pub fn load(base: &Path) -> Result<Self, ConfigError> {
+        match fs::read_to_string(base.join(Self::FILE_NAME)) {
+            Ok(text) => Self::from_toml(&text),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                Ok(Self::default())
+            }
+            Err(err) => Err(ConfigError::Io(err.to_string())),
+        }
+    }
+
+    /// Parses the configuration from the content of a configuration file.
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        let table: Table = text.parse().map_err(|err: toml::de::Error| {
+            ConfigError::Parse(err.message().into())
+        })?;
+        let mut res = Self::default();
+        for (name, value) in &table {
+            match name.as_str() {
+                "rules" => {
+                    res.rules = rule_levels(value, name)?;
+                }
+                "types" => {
+                    for (doctype, value) in as_table(value, name)? {
+                        let path = format!("types.{}", doctype);
+                        let doctype = match DocumentType::ALL.iter().find(
+                            |item| item.as_str() == doctype
+                        ) {
+                            Some(doctype) => *doctype,
+                            None => return Err(ConfigError::UnknownType(path))
+                        };
+                        res.types.insert(doctype, rule_levels(value, &path)?);
+                    }
+                }
+                "countries" => {
+                    for (country, value) in as_table(value, name)? {
+                        let path = format!("countries.{}", country);
+                        res.countries.insert(
+                            country.to_ascii_lowercase(),
+                            rule_levels(value, &path)?
+                        );
+                    }
+                }
+                "attribution" => {
+                    res.attribution = attribution(value, name)?;
+                }
+                _ => return Err(ConfigError::UnknownSection(name.clone()))
+            }
+        }
+        Ok(res)
+    }
+
+    /// Returns the license and attribution of the data.
+    pub fn attribution(&self) -> &Attribution {
+        &self.attribution
+    }
+
+    /// Returns the level of a rule for a document.
+    pub fn level(&self, rule: &str, data: &Data) -> Level {
+        data.key().country().and_then(|country| {
+            self.countries.get(country)?.get(rule)
+        }).or_else(|| {
+            self.types.get(&data.doctype())?.get(rule)
+        }).or_else(|| {
+            self.rules.get(rule)
+        }).copied().unwrap_or_default()
+    }
+}
+
+
+//------------ Level ---------------------------------------------------------
+
+data_enum! {
+    /// How problems found by a rule are reported.
+    pub enum Level {
+        { Error: "error" }
+        { Warning: "warning" }
+        { Ignore: "ignore" }
+
+        default Warning
+    }
+}
+
+impl Level {
+    /// Returns the severity of problems or `None` if they are ignored.
+    pub fn severity(self) -> Option<Severity> {
+        match self {
+            Level::Error => Some(Severity::Error),
+            Level::Warning => Some(Severity::Warning),
+            Level::Ignore => None,
+        }
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+fn as_table<'a>(
+    value: &'a Value, path: &str
+) -> Result<&'a Table, ConfigError> {
+    value.as_table().ok_or_else(|| {
+        ConfigError::Expected { path: path.into(), expected: "a table" }
+    })
+}
+
+/// Returns the levels of the rules given in a table.
+fn rule_levels(value: &Value, path: &str) -> Result<RuleLevels, ConfigError> {
+    let mut res = RuleLevels::new();
+    for (name, value) in as_table(value, path)? {
+        let path = format!("{}.{}", path, name);
+        let rule = validate::rule(name).ok_or_else(|| {
+            ConfigError::UnknownRule(path.clone())
+        })?;
+        let level = value.as_str().and_then(|value| {
+            Level::ALL.iter().copied().find(|level| level.as_str() == value)
+        }).ok_or(ConfigError::Expected {
+            path, expected: "'error', 'warning', or 'ignore'"
+        })?;
+        res.insert(rule.name(), level);
+    }
+    Ok(res)
+}
+
+/// Returns the license and attribution given in a table.
+fn attribution(
+    value: &Value, path: &str
+) -> Result<Attribution, ConfigError> {
+    let mut res = Attribution::default();
+    for (name, value) in as_table(value, path)? {
+        let path = format!("{}.{}", path, name);
+        let value = value.as_str().ok_or_else(|| {
+            ConfigError::Expected { path: path.clone(), expected: "a string" }
+        })?;
+        match name.as_str() {
+            "license" => res.license = Some(value.into()),
+            "text" => res.text = Some(value.into()),
+            "url" => {
+                res.url = Some(value.parse().map_err(|_| {
+                    ConfigError::Expected { path, expected: "a URL" }
+                })?)
+            }
+            _ => return Err(ConfigError::UnknownKey(path))
+        }
+    }
+    Ok(res)
+}
+
+
+//============ Errors ========================================================
+
+#[derive(Clone, Debug, Display)]
+pub enum ConfigError {
+    #[display(fmt="{}", _0)]
+    Io(String),
+
+    #[display(fmt="{}", _0)]
+    Parse(String),
+
+    #[display(fmt="unknown section '{}'", _0)]
+    UnknownSection(String),
+
+    #[display(fmt="unknown document type in '{}'", _0)]
+    UnknownType(String),
+
+    #[display(fmt="unknown rule '{}'", _0)]
+    UnknownRule(String),
+
+    #[display(fmt="unknown key '{}'", _0)]
+    UnknownKey(String),
+
+    #[display(fmt="'{}' must be {}", path, expected)]
+    Expected {
+        path: String,
+        expected: &'static str,
+    },
+}
+
@@ -0,0 +1,219 @@
+//! Paths from GeoJSON files.
+//!
+//! Each feature with a `LineString` or `MultiLineString` geometry becomes
+//! a path document. The feature’s properties provide the attributes of the
+//! path: `key` is the key of the path, `name` its name, and `source` a
+//! whitespace separated list of source keys. Named nodes are given by the
+//! `nodes` property which maps node names to their index in the list of
+//! coordinates. The line strings of a multi line string are joined. A
+//! third value in a position is the elevation of the node.
+
+use std::io;
+use derive_more::Display;
+use serde_json::Value;
+use crate::document::path;
+use crate::load::report::{Failed, PathReporter};
+use crate::store::StoreLoader;
+use crate::types::Key;
+use super::{PathProvider, insert};
+
+
+//------------ GeoJson -------------------------------------------------------
+
+/// The provider for paths in GeoJSON files.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GeoJson;
+
+impl PathProvider for GeoJson {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["geojson"]
+    }
+
+    fn load(
+        &self,
+        read: &mut dyn io::Read,
+        docs: &StoreLoader,
+        report: &mut PathReporter
+    ) {
+        let value: Value = match serde_json::from_reader(read) {
+            Ok(value) => value,
+            Err(err) => {
+                report.unmarked_error(err);
+                return
+            }
+        };
+        match value.get("type").and_then(Value::as_str) {
+            Some("FeatureCollection") => {
+                let features = value.get("features").and_then(
+                    Value::as_array
+                );
+                let features = match features {
+                    Some(features) => features,
+                    None => {
+                        report.unmarked_error(InvalidGeoJson);
+                        return
+                    }
+                };
+                for (idx, feature) in features.iter().enumerate() {
+                    insert(
+                        Self::load_feature(idx, feature, docs, report),
+                        docs, report
+                    )
+                }
+            }
+            Some("Feature") => {
+                insert(
+                    Self::load_feature(0, &value, docs, report),
+                    docs, report
+                )
+            }
+            _ => report.unmarked_error(InvalidGeoJson)
+        }
+    }
+}
+
+impl GeoJson {
+    fn load_feature(
+        idx: usize,
+        feature: &Value,
+        docs: &StoreLoader,
+        report: &mut PathReporter
+    ) -> Result<path::Data, Option<Key>> {
+        let properties = feature.get("properties");
+        let property = |name: &str| {
+            properties.and_then(|properties| properties.get(name))
+        };
+        let key = match property("key").and_then(Value::as_str) {
+            Some(key) => key,
+            None => {
+                report.unmarked_error(MissingFeatureKey(idx));
+                return Err(None)
+            }
+        };
+        let key = match Key::from_string(key.into()) {
+            Ok(key) => key,
+            Err(err) => {
+                report.unmarked_error(err);
+                return Err(None)
+            }
+        };
+        let coords = match Self::load_coords(feature.get("geometry")) {
+            Some(coords) if !coords.is_empty() => coords,
+            _ => {
+                report.unmarked_error(InvalidGeometry(key.clone()));
+                return Err(Some(key))
+            }
+        };
+        let mut names = Vec::new();
+        if let Some(nodes) = property("nodes") {
+            let nodes = match nodes.as_object() {
+                Some(nodes) => nodes,
+                None => {
+                    report.unmarked_error(InvalidNodes(key.clone()));
+                    return Err(Some(key))
+                }
+            };
+            for (name, pos) in nodes {
+                match pos.as_u64() {
+                    Some(pos) if (pos as usize) < coords.len() => {
+                        names.push((pos as usize, name.clone()))
+                    }
+                    _ => {
+                        report.unmarked_error(InvalidNodes(key.clone()));
+                        return Err(Some(key))
+                    }
+                }
+            }
+        }
+        names.sort();
+
+        let mut path = path::Data::new(key.clone(), report.path());
+        path.name = property("name").and_then(Value::as_str).map(Into::into);
+        if let Some(source) = property("source").and_then(Value::as_str) {
+            if let Err(Failed) = path.add_source(source, docs, report) {
+                return Err(Some(key))
+            }
+        }
+        let mut names = names.into_iter().peekable();
+        for (pos, (lon, lat, ele)) in coords.into_iter().enumerate() {
+            let name = names.next_if(|(name_pos, _)| *name_pos == pos);
+            path.push_node(
+                path::Node::new(lon, lat, 1., 1.).with_ele(ele),
+                name.map(|(_, name)| name), None, report
+            );
+            // Multiple names for the same node are an error.
+            if let Some((_, name)) = names.next_if(|(name_pos, _)| {
+                *name_pos == pos
+            }) {
+                report.unmarked_error(
+                    DuplicateNode { key: key.clone(), name }
+                );
+                return Err(Some(key))
+            }
+        }
+        Ok(path)
+    }
+
+    /// Loads the coordinates of a line string or multi line string.
+    fn load_coords(
+        geometry: Option<&Value>
+    ) -> Option<Vec<(f64, f64, Option<f64>)>> {
+        let geometry = geometry?;
+        let coords = geometry.get("coordinates")?.as_array()?;
+        match geometry.get("type")?.as_str()? {
+            "LineString" => Self::load_line(coords),
+            "MultiLineString" => {
+                let mut res = Vec::new();
+                for line in coords {
+                    let mut line = Self::load_line(line.as_array()?)?;
+                    // Consecutive lines share their end and start point.
+                    if res.last() == line.first() {
+                        line.remove(0);
+                    }
+                    res.append(&mut line);
+                }
+                Some(res)
+            }
+            _ => None
+        }
+    }
+
+    fn load_line(
+        coords: &[Value]
+    ) -> Option<Vec<(f64, f64, Option<f64>)>> {
+        coords.iter().map(|coord| {
+            let coord = coord.as_array()?;
+            Some((
+                coord.first()?.as_f64()?, coord.get(1)?.as_f64()?,
+                coord.get(2).and_then(Value::as_f64),
+            ))
+        }).collect()
+    }
+}
+
+
+//============ Errors ========================================================
+
+#[derive(Clone, Copy, Debug, Display)]
+#[display(fmt="expected a GeoJSON feature or feature collection")]
+pub struct InvalidGeoJson;
+
+#[derive(Clone, Copy, Debug, Display)]
+#[display(fmt="feature {} is missing the 'key' property", _0)]
+pub struct MissingFeatureKey(usize);
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="path '{}' needs a non-empty line string geometry", _0)]
+pub struct InvalidGeometry(Key);
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="path '{}' has invalid 'nodes' property", _0)]
+pub struct InvalidNodes(Key);
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="path '{}': more than one name for node '{}'", key, name)]
+pub struct DuplicateNode {
+    key: Key,
+    name: String,
+}
+
@@ -0,0 +1,291 @@
+//! Paths from GPX files.
+//!
+//! Each track or route in the file becomes a path document. Since GPX has
+//! no place for our attributes, the standard elements are used instead:
+//! the `name` element of the track holds the key of the path, `desc` its
+//! name, and `src` a whitespace separated list of source keys. Named
+//! nodes are track points with a `name` element and their `desc` element
+//! becomes the node’s description. The `ele` element of a track point
+//! provides the node’s elevation.
+//!
+//! All segments of a track are joined into a single path.
+
+use std::io;
+use std::str::FromStr;
+use derive_more::Display;
+use xml::common::{Position, TextPosition};
+use xml::reader::{EventReader, XmlEvent};
+use crate::document::path;
+use crate::load::report::{Failed, PathReporter};
+use crate::store::StoreLoader;
+use crate::types::{IntoMarked, Key, Location, Marked};
+use super::{PathProvider, insert};
+
+
+//------------ Gpx -----------------------------------------------------------
+
+/// The provider for paths in GPX files.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Gpx;
+
+impl PathProvider for Gpx {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["gpx"]
+    }
+
+    fn load(
+        &self,
+        read: &mut dyn io::Read,
+        docs: &StoreLoader,
+        report: &mut PathReporter
+    ) {
+        let mut reader = EventReader::new(read);
+        let mut elements = Vec::new();
+        let mut text = String::new();
+        let mut track: Option<Track> = None;
+        let mut point: Option<Point> = None;
+        loop {
+            let event = match reader.next() {
+                Ok(event) => event,
+                Err(err) => {
+                    report.error(
+                        InvalidGpx(err.msg().into()).marked(
+                            location(err.position())
+                        )
+                    );
+                    return
+                }
+            };
+            let location = location(reader.position());
+            match event {
+                XmlEvent::StartElement { name, attributes, .. } => {
+                    text.clear();
+                    match name.local_name.as_str() {
+                        "trk" | "rte" => {
+                            track = Some(Track::new(location))
+                        }
+                        "trkpt" | "rtept" => {
+                            let coord = |name: &str| {
+                                attributes.iter().find(|attr| {
+                                    attr.name.local_name == name
+                                }).and_then(|attr| {
+                                    f64::from_str(&attr.value).ok()
+                                })
+                            };
+                            match (coord("lon"), coord("lat")) {
+                                (Some(lon), Some(lat)) => {
+                                    point = Some(Point {
+                                        lon, lat,
+                                        ele: None, name: None, descr: None
+                                    })
+                                }
+                                _ => {
+                                    report.error(
+                                        InvalidTrackPoint.marked(location)
+                                    );
+                                    if let Some(track) = track.as_mut() {
+                                        track.failed = true
+                                    }
+                                }
+                            }
+                        }
+                        _ => { }
+                    }
+                    elements.push(name.local_name);
+                }
+                XmlEvent::Characters(data) | XmlEvent::CData(data) => {
+                    text.push_str(&data)
+                }
+                XmlEvent::EndElement { .. } => {
+                    let element = elements.pop().unwrap_or_default();
+                    let parent = elements.last().map(String::as_str);
+                    let value = text.trim().to_string();
+                    text.clear();
+                    match (element.as_str(), parent) {
+                        ("name", Some("trkpt" | "rtept")) => {
+                            if let Some(point) = point.as_mut() {
+                                point.name = Some(value)
+                            }
+                        }
+                        ("desc", Some("trkpt" | "rtept")) => {
+                            if let Some(point) = point.as_mut() {
+                                point.descr = Some(value)
+                            }
+                        }
+                        ("ele", Some("trkpt" | "rtept")) => {
+                            if let Some(point) = point.as_mut() {
+                                match f64::from_str(&value) {
+                                    Ok(ele) => point.ele = Some(ele),
+                                    Err(_) => {
+                                        report.warning(
+                                            InvalidElevation.marked(location)
+                                        )
+                                    }
+                                }
+                            }
+                        }
+                        ("name", Some("trk" | "rte")) => {
+                            if let Some(track) = track.as_mut() {
+                                track.key = Some(value.marked(location))
+                            }
+                        }
+                        ("desc", Some("trk" | "rte")) => {
+                            if let Some(track) = track.as_mut() {
+                                track.name = Some(value)
+                            }
+                        }
+                        ("src", Some("trk" | "rte")) => {
+                            if let Some(track) = track.as_mut() {
+                                track.source = Some(value)
+                            }
+                        }
+                        ("trkpt" | "rtept", _) => {
+                            if let (Some(track), Some(point)) = (
+                                track.as_mut(), point.take()
+                            ) {
+                                track.points.push(point)
+                            }
+                        }
+                        ("trk" | "rte", _) => {
+                            if let Some(track) = track.take() {
+                                insert(
+                                    track.into_path(docs, report),
+                                    docs, report
+                                )
+                            }
+                        }
+                        _ => { }
+                    }
+                }
+                XmlEvent::EndDocument => break,
+                _ => { }
+            }
+        }
+    }
+}
+
+
+//------------ Track ---------------------------------------------------------
+
+/// A track or route collected from the file.
+struct Track {
+    /// The location of the start of the track.
+    location: Location,
+
+    /// The content of the track’s `name` element.
+    key: Option<Marked<String>>,
+
+    /// The content of the track’s `desc` element.
+    name: Option<String>,
+
+    /// The content of the track’s `src` element.
+    source: Option<String>,
+
+    /// The track points.
+    points: Vec<Point>,
+
+    /// Whether some part of the track was broken.
+    failed: bool,
+}
+
+impl Track {
+    fn new(location: Location) -> Self {
+        Track {
+            location,
+            key: None,
+            name: None,
+            source: None,
+            points: Vec::new(),
+            failed: false,
+        }
+    }
+
+    fn into_path(
+        self,
+        docs: &StoreLoader,
+        report: &mut PathReporter
+    ) -> Result<path::Data, Option<Key>> {
+        let key = match self.key {
+            Some(key) => key,
+            None => {
+                report.error(MissingTrackKey.marked(self.location));
+                return Err(None)
+            }
+        };
+        let (key, location) = key.unwrap();
+        let key = match Key::from_string(key) {
+            Ok(key) => key,
+            Err(err) => {
+                report.error(err.marked(location));
+                return Err(None)
+            }
+        };
+        if self.failed {
+            return Err(Some(key))
+        }
+        if self.points.is_empty() {
+            report.error(EmptyTrack.marked(self.location));
+            return Err(Some(key))
+        }
+        let mut path = path::Data::new(key.clone(), report.path());
+        path.name = self.name;
+        if let Some(source) = self.source {
+            if let Err(Failed) = path.add_source(&source, docs, report) {
+                return Err(Some(key))
+            }
+        }
+        for point in self.points {
+            path.push_node(
+                path::Node::new(
+                    point.lon, point.lat, 1., 1.
+                ).with_ele(point.ele),
+                point.name, point.descr, report
+            );
+        }
+        Ok(path)
+    }
+}
+
+
+//------------ Point ---------------------------------------------------------
+
+/// A track point collected from the file.
+struct Point {
+    lon: f64,
+    lat: f64,
+    ele: Option<f64>,
+    name: Option<String>,
+    descr: Option<String>,
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Converts an XML reader position into a location.
+fn location(pos: TextPosition) -> Location {
+    Location::new(pos.row as usize + 1, pos.column as usize)
+}
+
+
+//============ Errors ========================================================
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="invalid GPX: {}", _0)]
+pub struct InvalidGpx(String);
+
+#[derive(Clone, Copy, Debug, Display)]
+#[display(fmt="track point without valid coordinates")]
+pub struct InvalidTrackPoint;
+
+#[derive(Clone, Copy, Debug, Display)]
+#[display(fmt="invalid elevation in track point")]
+pub struct InvalidElevation;
+
+#[derive(Clone, Copy, Debug, Display)]
+#[display(fmt="track without key in its 'name' element")]
+pub struct MissingTrackKey;
+
+#[derive(Clone, Copy, Debug, Display)]
+#[display(fmt="track without track points")]
+pub struct EmptyTrack;
+
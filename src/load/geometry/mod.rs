@@ -0,0 +1,82 @@
+//! Loading path geometry.
+//!
+//! The geometry of paths is kept in files below the `paths` directory of
+//! the tree. Each file format is handled by a [`PathProvider`] that turns
+//! the content of a file into path documents. OSM XML is the primary
+//! format. GPX tracks and GeoJSON files can be used to supply surveyed
+//! geometry where there is no good OSM data.
+
+use std::io;
+use std::path::Path;
+use crate::document::path;
+use crate::document::common::DocumentType;
+use crate::store::StoreLoader;
+use crate::types::{Key, Location};
+use super::report::PathReporter;
+
+pub use self::geojson::GeoJson;
+pub use self::gpx::Gpx;
+pub use self::osm::OsmXml;
+
+pub mod geojson;
+pub mod gpx;
+pub mod osm;
+
+
+//------------ PathProvider --------------------------------------------------
+
+/// A source of path geometry.
+pub trait PathProvider: Sync {
+    /// Returns the file name extensions of the files handled.
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Loads all paths contained in a file.
+    fn load(
+        &self,
+        read: &mut dyn io::Read,
+        docs: &StoreLoader,
+        report: &mut PathReporter
+    );
+}
+
+
+//------------ Providers -----------------------------------------------------
+
+/// All available path providers.
+pub static PROVIDERS: &[&dyn PathProvider] = &[&OsmXml, &Gpx, &GeoJson];
+
+/// Returns the provider for a file based on its extension.
+pub fn provider(path: &Path) -> Option<&'static dyn PathProvider> {
+    let ext = path.extension()?.to_str()?;
+    PROVIDERS.iter().copied().find(|provider| {
+        provider.extensions().iter().any(|item| {
+            item.eq_ignore_ascii_case(ext)
+        })
+    })
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Adds the result of loading a path to the store.
+///
+/// If loading failed but the key of the path is known, a broken document
+/// is added so that references to the path don’t cause additional errors.
+fn insert(
+    path: Result<path::Data, Option<Key>>,
+    docs: &StoreLoader,
+    report: &mut PathReporter
+) {
+    match path {
+        Ok(path) => {
+            let _ = docs.insert(path.into(), report);
+        }
+        Err(Some(key)) => {
+            let _ = docs.insert_broken(
+                key, Some(DocumentType::Path), Location::NONE, report
+            );
+        }
+        Err(None) => { }
+    }
+}
+
@@ -0,0 +1,52 @@
+//! Paths from OSM XML files.
+//!
+//! Each relation of type `path` in the file becomes a path document.
+
+use std::{io, mem};
+use std::collections::HashSet;
+use osmxml::read::read_xml;
+use crate::document::path;
+use crate::load::report::PathReporter;
+use crate::store::StoreLoader;
+use crate::types::IntoMarked;
+use super::{PathProvider, insert};
+
+
+//------------ OsmXml --------------------------------------------------------
+
+/// The provider for paths in OSM XML files.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OsmXml;
+
+impl PathProvider for OsmXml {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["osm"]
+    }
+
+    fn load(
+        &self,
+        read: &mut dyn io::Read,
+        docs: &StoreLoader,
+        report: &mut PathReporter
+    ) {
+        let mut osm = match read_xml(read) {
+            Ok(osm) => osm,
+            Err(err) => {
+                report.error(err.unmarked());
+                return;
+            }
+        };
+
+        // Swap out the relations so we don’t hold a mutable reference to
+        // `osm` while draining the relations.
+        let mut relations = HashSet::new();
+        mem::swap(osm.relations_mut(), &mut relations);
+        for relation in relations.drain() {
+            insert(
+                path::Data::from_osm(relation, &osm, docs, report),
+                docs, report
+            );
+        }
+    }
+}
+
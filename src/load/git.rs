@@ -0,0 +1,83 @@
+//! Revisions of data files from git.
+//!
+//! If the data directory is part of a git repository, the last commit
+//! changing each file can be determined and attached to the origins of the
+//! documents loaded from it. This uses the `git` command and quietly does
+//! nothing if it isn’t available or the directory isn’t a repository.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use super::report::Revision;
+
+
+//------------ Revisions -----------------------------------------------------
+
+/// The last revision of each file in a directory.
+#[derive(Clone, Debug, Default)]
+pub struct Revisions {
+    files: HashMap<PathBuf, Arc<Revision>>,
+}
+
+impl Revisions {
+    /// Determines the revisions of all files under `base`.
+    ///
+    /// Returns `None` if `base` isn’t in a git repository.
+    pub fn load(base: &Path) -> Option<Self> {
+        // The log lists commits newest first, each introduced by a line
+        // with a NUL character, the hash, and the time followed by the
+        // changed files relative to `base`.
+        let output = Command::new("git")
+            .arg("-C").arg(base)
+            .args([
+                "log", "--relative", "--no-renames", "--name-only",
+                "--format=%x00%H %ct",
+            ])
+            .output().ok()?;
+        if !output.status.success() {
+            return None
+        }
+        let output = String::from_utf8(output.stdout).ok()?;
+
+        // The first commit we see for a file is its last revision, the
+        // last one we see is the one that added it.
+        let mut files = HashMap::<PathBuf, Revision>::new();
+        let mut current = None;
+        for line in output.lines() {
+            if let Some(header) = line.strip_prefix('\0') {
+                current = parse_header(header);
+            }
+            else if !line.is_empty() {
+                if let Some((ref commit, timestamp)) = current {
+                    files.entry(base.join(line)).or_insert_with(|| {
+                        Revision {
+                            commit: String::from(commit),
+                            timestamp,
+                            created: timestamp,
+                        }
+                    }).created = timestamp;
+                }
+            }
+        }
+        let files = files.into_iter().map(|(path, revision)| {
+            (path, Arc::new(revision))
+        }).collect();
+        Some(Revisions { files })
+    }
+
+    /// Returns the revision of a file.
+    pub fn get(&self, path: &Path) -> Option<Arc<Revision>> {
+        self.files.get(path).cloned()
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Parses the commit hash and time from a commit header line.
+fn parse_header(header: &str) -> Option<(String, i64)> {
+    let (commit, timestamp) = header.split_once(' ')?;
+    Some((commit.into(), timestamp.parse().ok()?))
+}
+
@@ -1,7 +1,16 @@
-pub use self::tree::load_tree;
+pub use self::observer::{LoadObserver, TerminalProgress};
+pub use self::tree::{
+    check_file, load_tree, load_tree_custom, load_tree_forced,
+    load_tree_forced_limited, load_tree_limited, load_tree_observed, Limits,
+};
 
+#[cfg(feature = "git")] pub mod git;
+pub mod config;
+pub mod geometry;
+pub mod observer;
 pub mod read;
 pub mod report;
 pub mod yaml;
 pub mod tree;
+pub mod vars;
 
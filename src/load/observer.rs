@@ -0,0 +1,140 @@
+//! Observing the progress of loading.
+//!
+//! Loading a complete data tree takes a while. A [`LoadObserver`] passed to
+//! [`load_tree_observed`][super::tree::load_tree_observed] and friends is
+//! told when each stage starts and finishes and when a file has been
+//! loaded, so that progress can be shown while waiting.
+//!
+//! The unit type is an observer that ignores everything. The
+//! [`TerminalProgress`] shows the progress on standard error.
+
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use super::report::Stage;
+
+
+//------------ LoadObserver --------------------------------------------------
+
+/// A type receiving progress information during loading.
+///
+/// Files are loaded in parallel, so the methods can be called from
+/// several threads at once. All methods do nothing by default.
+pub trait LoadObserver: Sync {
+    /// Called when a stage starts.
+    fn stage_started(&self, stage: Stage) {
+        let _ = stage;
+    }
+
+    /// Called after the file at `path` has been loaded.
+    ///
+    /// This only happens during [`Stage::Parse`].
+    fn file_loaded(&self, path: &Path) {
+        let _ = path;
+    }
+
+    /// Called when a stage has finished after taking `elapsed`.
+    ///
+    /// This is called even if the stage failed.
+    fn stage_finished(&self, stage: Stage, elapsed: Duration) {
+        let _ = (stage, elapsed);
+    }
+}
+
+impl LoadObserver for () { }
+
+
+//------------ observe -------------------------------------------------------
+
+/// Runs `op` as the stage `stage`, informing `observer`.
+pub fn observe<T>(
+    observer: &dyn LoadObserver, stage: Stage, op: impl FnOnce() -> T
+) -> T {
+    let start = Instant::now();
+    observer.stage_started(stage);
+    let res = op();
+    observer.stage_finished(stage, start.elapsed());
+    res
+}
+
+
+//------------ TerminalProgress ----------------------------------------------
+
+/// An observer showing progress on standard error.
+///
+/// The number of files loaded so far is updated in place if standard error
+/// is a terminal. The time taken is printed once each stage has finished.
+#[derive(Debug)]
+pub struct TerminalProgress {
+    /// The number of files loaded in the current stage.
+    files: AtomicUsize,
+
+    /// Whether to update the file count in place.
+    interactive: bool,
+}
+
+impl TerminalProgress {
+    /// How many files to load between updates of the file count.
+    const UPDATE_INTERVAL: usize = 100;
+
+    pub fn new() -> Self {
+        TerminalProgress {
+            files: AtomicUsize::new(0),
+            interactive: io::stderr().is_terminal(),
+        }
+    }
+
+    fn stage_name(stage: Stage) -> &'static str {
+        match stage {
+            Stage::Parse => "Loading",
+            Stage::Translate => "Resolving links",
+            Stage::Crossref => "Cross references",
+            Stage::Meta => "Metadata",
+            Stage::Catalogue => "Catalogue",
+        }
+    }
+}
+
+impl Default for TerminalProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LoadObserver for TerminalProgress {
+    fn stage_started(&self, stage: Stage) {
+        self.files.store(0, Ordering::Relaxed);
+        if self.interactive {
+            eprint!("{} ...", Self::stage_name(stage));
+            let _ = io::stderr().flush();
+        }
+    }
+
+    fn file_loaded(&self, _path: &Path) {
+        let files = self.files.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.interactive && files.is_multiple_of(Self::UPDATE_INTERVAL) {
+            eprint!("\r{}: {} files", Self::stage_name(Stage::Parse), files);
+            let _ = io::stderr().flush();
+        }
+    }
+
+    fn stage_finished(&self, stage: Stage, elapsed: Duration) {
+        if self.interactive {
+            eprint!("\r\x1b[K");
+        }
+        let files = self.files.load(Ordering::Relaxed);
+        if stage == Stage::Parse {
+            eprintln!(
+                "{}: {} files, {:.3} s",
+                Self::stage_name(stage), files, elapsed.as_secs_f32()
+            );
+        }
+        else {
+            eprintln!(
+                "{}: {:.3} s", Self::stage_name(stage), elapsed.as_secs_f32()
+            );
+        }
+    }
+}
+
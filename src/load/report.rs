@@ -1,6 +1,7 @@
 //! Reporting during loading.
 
-use std::{fmt, ops, path};
+use std::{cmp, fmt, ops, path};
+use std::any::Any;
 use std::fmt::Display;
 use std::sync::{Arc, Mutex};
 use crate::types::{IntoMarked, Location, Marked};
@@ -50,15 +51,20 @@ pub enum Stage {
 //------------ Origin --------------------------------------------------------
 
 /// The origin location of a notice.
-#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+///
+/// The origin can also carry the revision of the file it refers to. The
+/// revision is ignored when comparing origins.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Origin {
     path: Path,
     location: Location,
+    revision: Option<Arc<Revision>>,
 }
 
 impl Origin {
     pub fn new(path: Path, location: Location) -> Self {
-        Origin { path, location }
+        Origin { path, location, revision: None }
     }
 
     pub fn path(&self) -> &Path {
@@ -72,9 +78,39 @@ impl Origin {
     pub fn at(&self, location: Location) -> Origin {
         Origin {
             path: self.path.clone(),
-            location
+            location,
+            revision: self.revision.clone(),
         }
     }
+
+    /// Returns the revision of the file if known.
+    pub fn revision(&self) -> Option<&Revision> {
+        self.revision.as_deref()
+    }
+
+    pub fn set_revision(&mut self, revision: Option<Arc<Revision>>) {
+        self.revision = revision
+    }
+}
+
+impl PartialEq for Origin {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path && self.location == other.location
+    }
+}
+
+impl Eq for Origin { }
+
+impl PartialOrd for Origin {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Origin {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.path.cmp(&other.path).then(self.location.cmp(&other.location))
+    }
 }
 
 impl Display for Origin {
@@ -84,12 +120,42 @@ impl Display for Origin {
 }
 
 
+//------------ Revision ------------------------------------------------------
+
+/// The version control revision of a file.
+///
+/// This describes the last commit that changed the file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Revision {
+    /// The commit hash.
+    pub commit: String,
+
+    /// The time of the commit in seconds since the Unix epoch.
+    pub timestamp: i64,
+
+    /// The time of the commit that first added the file.
+    pub created: i64,
+}
+
+impl Revision {
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "commit": self.commit,
+            "timestamp": self.timestamp,
+            "created": self.created,
+        })
+    }
+}
+
+
 //------------ Path ----------------------------------------------------------
 
 /// A file path.
 ///
 /// This type wraps an owned path into an arc for cheaper copying.
 #[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Path(Arc<path::PathBuf>);
 
 impl Path {
@@ -147,9 +213,16 @@ impl fmt::Display for Path {
 
 //------------ Message -------------------------------------------------------
 
-pub trait Message: Display + Send + 'static { }
+pub trait Message: Display + Send + 'static {
+    /// Returns the message as `Any` so it can be downcast.
+    fn as_any(&self) -> &dyn Any;
+}
 
-impl<T: Display + Send + 'static> Message for T { }
+impl<T: Display + Send + 'static> Message for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
 
 
 //------------ Notice --------------------------------------------------------
@@ -194,6 +267,12 @@ impl Notice {
     pub fn message(&self) -> &Box<dyn Message> {
         &self.message
     }
+
+    /// Returns the message if it is of type `M`.
+    pub fn message_as<M: Message>(&self) -> Option<&M> {
+        // Deref explicitly, the box itself is a message, too.
+        (*self.message).as_any().downcast_ref()
+    }
 }
 
 impl Display for Notice {
@@ -213,14 +292,14 @@ impl Display for Notice {
 /// A report is a collection of notices.
 pub struct Report {
     notices: Vec<Notice>,
-    stage_count: [usize; 4],
+    stage_count: [usize; 5],
 }
 
 impl Report {
     pub fn new() -> Self {
         Report {
             notices: Vec::new(),
-            stage_count: [0; 4],
+            stage_count: [0; 5],
         }
     }
 
@@ -384,11 +463,25 @@ impl StageReporter {
 pub struct PathReporter {
     reporter: StageReporter,
     path: Path,
+
+    /// The severity warnings are reported with or `None` to drop them.
+    warning_severity: Option<Severity>,
 }
 
 impl PathReporter {
     pub fn new(reporter: StageReporter, path: Path) -> Self {
-        PathReporter { reporter, path }
+        PathReporter {
+            reporter, path,
+            warning_severity: Some(Severity::Warning),
+        }
+    }
+
+    /// Changes the severity of warnings reported from now on.
+    ///
+    /// With `None`, warnings are dropped. This allows tuning how strict
+    /// checks are without changing the checks themselves.
+    pub fn set_warning_severity(&mut self, severity: Option<Severity>) {
+        self.warning_severity = severity
     }
 
     pub fn path(&self) -> Path {
@@ -436,7 +529,9 @@ impl PathReporter {
     }
 
     pub fn warning<M: Message>(&mut self, message: Marked<M>) {
-        self.notice(Severity::Warning, message)
+        if let Some(severity) = self.warning_severity {
+            self.notice(severity, message)
+        }
     }
 
     pub fn unmarked_warning<M: Message>(&mut self, message: M) {
@@ -1,58 +1,279 @@
 
-use std::{io, mem};
-use std::collections::HashSet;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::Path;
 use std::sync::Arc;
+use std::thread;
 use ignore::{WalkBuilder, WalkState};
-use ignore::types::TypesBuilder;
-use osmxml::read::read_xml;
-use crate::document::path;
-use crate::document::common::DocumentType;
+use ignore::types::{Types, TypesBuilder};
+use crate::document::common::Attribution;
+use crate::document::custom::CustomRegistry;
+use crate::document::line::ElRegistry;
 use crate::store::{DataStore, StoreLoader};
-use crate::types::{IntoMarked, Location};
+use crate::types::{IntoMarked, Location, Text};
+use crate::types::enums::DeprecatedValue;
+use super::config::Config;
+use super::geometry;
+use super::observer::{observe, LoadObserver};
 use super::read::Utf8Chars;
-use super::report::{self, PathReporter, Report, Reporter, Stage};
+use super::report::{self, Report, Reporter, Stage};
+use super::vars::Vars;
 use super::yaml::Loader;
 
 
 //------------ load_tree -----------------------------------------------------
 
 pub fn load_tree(path: &Path) -> Result<DataStore, Report> {
+    load_tree_observed(path, &())
+}
+
+/// Loads the data tree at `path` informing `observer` of the progress.
+///
+/// Reading the files is reported as [`Stage::Parse`] and resolving the
+/// links between documents as [`Stage::Translate`].
+///
+/// Deprecated enum values don’t prevent the data from loading. Where they
+/// were used is available via [`DataStore::deprecated`].
+pub fn load_tree_observed(
+    path: &Path, observer: &dyn LoadObserver
+) -> Result<DataStore, Report> {
+    load_tree_custom(path, CustomRegistry::default(), observer)
+}
+
+
+//------------ load_tree_custom ----------------------------------------------
+
+/// Loads the data tree at `path` accepting the given custom document types.
+///
+/// Apart from the custom types, this is identical to
+/// [`load_tree_observed`].
+pub fn load_tree_custom(
+    path: &Path, custom_types: CustomRegistry, observer: &dyn LoadObserver
+) -> Result<DataStore, Report> {
+    load_tree_with(path, custom_types, &Limits::default(), observer)
+}
+
+
+//------------ load_tree_limited ---------------------------------------------
+
+/// Loads the data tree at `path` within the given resource limits.
+///
+/// Apart from the limits, this is identical to [`load_tree_observed`].
+pub fn load_tree_limited(
+    path: &Path, limits: &Limits, observer: &dyn LoadObserver
+) -> Result<DataStore, Report> {
+    load_tree_with(path, CustomRegistry::default(), limits, observer)
+}
+
+fn load_tree_with(
+    path: &Path,
+    custom_types: CustomRegistry,
+    limits: &Limits,
+    observer: &dyn LoadObserver,
+) -> Result<DataStore, Report> {
     let report = Reporter::new();
+    let attribution = load_attribution(path, &report);
 
     let store = {
-        let builder = Arc::new(StoreLoader::new());
-        load_facts(path, builder.clone(), report.clone());
-        load_paths(path, builder.clone(), report.clone());
-        let builder = Arc::try_unwrap(builder).unwrap();
-        builder.into_data_store(&mut report.clone().stage(Stage::Translate))
+        let builder = load_documents(
+            path, custom_types, limits, &report, observer
+        );
+        observe(observer, Stage::Translate, || {
+            builder.into_data_store(
+                &mut report.clone().stage(Stage::Translate)
+            )
+        })
     };
-    let store = match store {
+    let mut store = match store {
         Ok(store) => store,
-        Err(_) => return Err(report.unwrap())
+        Err(_) => {
+            Text::release_unused();
+            return Err(report.unwrap())
+        }
     };
-    if !report.is_empty() {
-        return Err(report.unwrap())
+
+    // Deprecated values are only warnings. Everything else is a problem.
+    let report = report.unwrap();
+    if report.iter().any(|notice| {
+        notice.message_as::<DeprecatedValue>().is_none()
+    }) {
+        drop(store);
+        Text::release_unused();
+        return Err(report)
     }
+    store.set_deprecated(&report);
+    store.set_attribution(attribution);
+    #[cfg(feature = "git")]
+    set_revisions(path, &mut store);
     Ok(store)
 }
 
 
+//------------ load_tree_forced ----------------------------------------------
+
+/// Loads the data tree at `path` despite errors.
+///
+/// Documents that can’t be loaded are replaced by placeholders as
+/// described with [`StoreLoader::into_data_store_forced`]. The returned
+/// report contains all problems found. The placeholders can be retrieved
+/// via [`DataStore::placeholders`].
+pub fn load_tree_forced(
+    path: &Path, observer: &dyn LoadObserver
+) -> (DataStore, Report) {
+    load_tree_forced_limited(path, &Limits::default(), observer)
+}
+
+/// Loads the data tree at `path` despite errors within resource limits.
+///
+/// Apart from the limits, this is identical to [`load_tree_forced`].
+pub fn load_tree_forced_limited(
+    path: &Path, limits: &Limits, observer: &dyn LoadObserver
+) -> (DataStore, Report) {
+    let report = Reporter::new();
+    let attribution = load_attribution(path, &report);
+    let builder = load_documents(
+        path, CustomRegistry::default(), limits, &report, observer
+    );
+    let mut store = observe(observer, Stage::Translate, || {
+        builder.into_data_store_forced(
+            &mut report.clone().stage(Stage::Translate)
+        )
+    });
+    let report = report.unwrap();
+    store.set_deprecated(&report);
+    store.set_attribution(attribution);
+    #[cfg(feature = "git")]
+    set_revisions(path, &mut store);
+    (store, report)
+}
+
+
+//------------ load_documents ------------------------------------------------
+
+/// Loads all documents of the data tree at `path` into a store loader.
+///
+/// The files are parsed using the number of threads determined by
+/// `limits`.
+fn load_documents(
+    path: &Path,
+    custom_types: CustomRegistry,
+    limits: &Limits,
+    report: &Reporter,
+    observer: &dyn LoadObserver,
+) -> StoreLoader {
+    observe(observer, Stage::Parse, || {
+        let threads = limits.threads(path);
+        let electrification = load_electrification(path, report);
+        let vars = Vars::load(path, report);
+        let builder = Arc::new(
+            StoreLoader::with_electrification(
+                electrification
+            ).with_custom_types(custom_types).with_vars(vars)
+        );
+        load_facts(path, threads, builder.clone(), report.clone(), observer);
+        load_paths(path, threads, builder.clone(), report.clone(), observer);
+        Arc::try_unwrap(builder).unwrap()
+    })
+}
+
+
+//------------ set_revisions -------------------------------------------------
+
+/// Attaches the git revision of their files to the documents’ origins.
+#[cfg(feature = "git")]
+fn set_revisions(base: &Path, store: &mut DataStore) {
+    let revisions = match super::git::Revisions::load(base) {
+        Some(revisions) => revisions,
+        None => return
+    };
+    for data in store.iter_mut() {
+        let origin = &mut data.common_mut().origin;
+        let revision = revisions.get(origin.path());
+        origin.set_revision(revision);
+    }
+}
+
+
+//------------ load_electrification ------------------------------------------
+
+/// Loads the registry of named electrification systems.
+///
+/// The built-in systems are extended by those from the file
+/// `electrification.yaml` in `base` if it exists. Problems with the file
+/// are added to `report` and the built-in systems are used instead.
+fn load_electrification(base: &Path, report: &Reporter) -> ElRegistry {
+    let mut registry = ElRegistry::builtin();
+    let path = base.join("electrification.yaml");
+    if !path.exists() {
+        return registry
+    }
+    let path = report::Path::new(&path);
+    match File::open(&path) {
+        Ok(file) => {
+            let file = BufReader::new(file);
+            let mut report = report.clone()
+                .stage(Stage::Translate)
+                .with_path(path);
+            let mut loaded = registry.clone();
+            let mut ok = true;
+            let res = {
+                let mut loader = Loader::new(|v| {
+                    if loaded.add_from_yaml(v, &mut report).is_err() {
+                        ok = false;
+                    }
+                });
+                loader.load(Utf8Chars::new(file))
+            };
+            if let Err(errs) = res {
+                let mut report = report.restage(Stage::Parse);
+                for err in errs {
+                    report.error(err.marked(Location::NONE));
+                }
+            }
+            else if ok {
+                registry = loaded;
+            }
+        }
+        Err(err) => {
+            report.clone().stage(Stage::Parse)
+                .with_path(path).error(err.marked(Location::NONE))
+        }
+    }
+    registry
+}
+
+
+//------------ load_attribution ----------------------------------------------
+
+/// Loads the license and attribution of the data tree.
+///
+/// They are taken from the configuration file in `base`. Problems with the
+/// file are added to `report` and an empty attribution is used instead.
+fn load_attribution(base: &Path, report: &Reporter) -> Attribution {
+    match Config::load(base) {
+        Ok(config) => config.attribution().clone(),
+        Err(err) => {
+            report.clone().stage(Stage::Parse)
+                .with_path(report::Path::new(&base.join(Config::FILE_NAME)))
+                .error(err.marked(Location::NONE));
+            Attribution::default()
+        }
+    }
+}
+
+
 //------------ load_facts ----------------------------------------------------
 
 fn load_facts(
     base: &Path,
+    threads: usize,
     docs: Arc<StoreLoader>,
-    report: Reporter
+    report: Reporter,
+    observer: &dyn LoadObserver,
 ) {
     let walk = WalkBuilder::new(base.join("facts"))
-        .types(TypesBuilder::new()
-            .add_defaults()
-            .select("yaml")
-            .build().unwrap()
-        )
+        .types(facts_types())
+        .threads(threads)
         .build_parallel();
     walk.run(|| {
         let docs = docs.clone();
@@ -65,28 +286,8 @@ fn load_facts(
                     }
                 }
                 let path = report::Path::new(path.path());
-                match File::open(&path) {
-                    Ok(file) => {
-                        let file = BufReader::new(file);
-                        let mut report = report.clone()
-                            .stage(Stage::Translate)
-                            .with_path(path);
-                        let res = {
-                            let mut loader = Loader::new(|v| {
-                                let _ = docs.from_yaml(v, &mut report);
-                            });
-                            loader.load(Utf8Chars::new(file))
-                        };
-                        if let Err(err) = res {
-                            let mut report = report.restage(Stage::Parse);
-                            report.error(err.marked(Location::NONE));
-                        }
-                    }
-                    Err(err) => {
-                        report.clone().stage(Stage::Parse)
-                            .with_path(path).error(err.marked(Location::NONE))
-                    }
-                }
+                load_file(&path, &docs, &report);
+                observer.file_loaded(path.as_ref());
             }
             WalkState::Continue
         })
@@ -94,17 +295,77 @@ fn load_facts(
 }
 
 
+/// Loads the documents in the YAML file at `path` into `docs`.
+fn load_file(path: &report::Path, docs: &StoreLoader, report: &Reporter) {
+    match File::open(path) {
+        Ok(file) => {
+            let file = BufReader::new(file);
+            let mut report = report.clone()
+                .stage(Stage::Translate)
+                .with_path(path.clone());
+            let (res, expanded) = {
+                let mut loader = Loader::new(|v| {
+                    let _ = docs.from_yaml(v, &mut report);
+                }).with_vars(docs.vars().clone());
+                let res = loader.load(Utf8Chars::new(file));
+                let expanded = loader.has_expansions().then(|| {
+                    loader.references().to_vec()
+                });
+                (res, expanded)
+            };
+            if let Some(references) = expanded {
+                docs.add_expanded(path.clone(), references);
+            }
+            if let Err(errs) = res {
+                let mut report = report.restage(Stage::Parse);
+                for err in errs {
+                    report.error(err.marked(Location::NONE));
+                }
+            }
+        }
+        Err(err) => {
+            report.clone().stage(Stage::Parse)
+                .with_path(path.clone())
+                .error(err.marked(Location::NONE))
+        }
+    }
+}
+
+
+//------------ check_file ----------------------------------------------------
+
+/// Checks a single document file against an already loaded store.
+///
+/// The file is loaded the same way as during [`load_tree`] and its
+/// documents are then checked via [`StoreLoader::check_against`]. Returns
+/// all problems found, so the file is fine if the report is empty.
+pub fn check_file(path: &Path, store: &DataStore) -> Report {
+    let report = Reporter::new();
+    let docs = StoreLoader::with_electrification(
+        store.electrification().clone()
+    ).with_custom_types(
+        store.custom_types().clone()
+    ).with_vars(store.vars().clone());
+    load_file(&report::Path::new(path), &docs, &report);
+    let _ = docs.check_against(
+        store, &mut report.clone().stage(Stage::Translate)
+    );
+    report.unwrap()
+}
+
+
 //------------ load_paths ----------------------------------------------------
 
 pub fn load_paths(
     base: &Path,
+    threads: usize,
     docs: Arc<StoreLoader>,
-    report: Reporter
+    report: Reporter,
+    observer: &dyn LoadObserver,
 ) {
-    let mut types = TypesBuilder::new();
-    types.add("osm", "*.osm").unwrap();
     let walk = WalkBuilder::new(base.join("paths"))
-                           .types(types.select("osm").build().unwrap())
+                           .types(paths_types())
+                           .threads(threads)
                            .build_parallel();
     walk.run(|| {
         let docs = docs.clone();
@@ -116,20 +377,26 @@ pub fn load_paths(
                         return WalkState::Continue
                     }
                 }
+                let provider = match geometry::provider(path.path()) {
+                    Some(provider) => provider,
+                    None => return WalkState::Continue
+                };
                 let path = report::Path::new(path.path());
                 match File::open(&path) {
                     Ok(file) => {
                         let mut file = BufReader::new(file);
                         let mut report = report.clone()
                             .stage(Stage::Translate)
-                            .with_path(path);
-                        load_osm_file(&mut file, &docs, &mut report);
+                            .with_path(path.clone());
+                        provider.load(&mut file, &docs, &mut report);
                     }
                     Err(err) => {
                         report.clone().stage(Stage::Parse)
-                            .with_path(path).error(err.marked(Location::NONE))
+                            .with_path(path.clone())
+                            .error(err.marked(Location::NONE))
                     }
                 }
+                observer.file_loaded(path.as_ref());
             }
             WalkState::Continue
         })
@@ -137,37 +404,88 @@ pub fn load_paths(
 }
 
 
-//------------ load_osm_file -------------------------------------------------
 
-fn load_osm_file<R: io::Read>(
-    read: &mut R,
-    docs: &StoreLoader,
-    report: &mut PathReporter
-) {
-    let mut osm = match read_xml(read) {
-        Ok(osm) => osm,
-        Err(err) => {
-            report.error(err.unmarked());
-            return;
+//------------ Limits --------------------------------------------------------
+
+/// Resource limits for loading a data tree.
+///
+/// The files of a data tree are parsed in parallel. Each thread keeps the
+/// file it currently parses in memory in addition to the documents
+/// already loaded, so on machines with little memory it can be necessary
+/// to parse fewer files at once.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Limits {
+    /// The number of threads used for parsing.
+    ///
+    /// If this is `None`, one thread per available CPU is used.
+    pub jobs: Option<usize>,
+
+    /// The memory in bytes available for parsing files.
+    ///
+    /// If this is `None`, the memory isn’t limited.
+    pub memory: Option<u64>,
+}
+
+impl Limits {
+    /// The factor between the size of a file and the memory for parsing it.
+    ///
+    /// This is a rough estimate covering the YAML tree built for a file
+    /// before it is converted into documents.
+    const PARSE_FACTOR: u64 = 8;
+
+    /// Returns the number of threads for parsing the data tree at `base`.
+    ///
+    /// If there is a memory limit, the number of threads is reduced so
+    /// that the largest files of the tree can be parsed at the same time
+    /// within the limit. If even a single one of them doesn’t fit, the
+    /// files are parsed one after another.
+    pub fn threads(&self, base: &Path) -> usize {
+        let jobs = self.jobs.unwrap_or_else(|| {
+            thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+        }).max(1);
+        let memory = match self.memory {
+            Some(memory) => memory,
+            None => return jobs
+        };
+        let largest = largest_file(base).saturating_mul(Self::PARSE_FACTOR);
+        if largest == 0 {
+            return jobs
         }
-    };
-    
-    // Swap out the relations so we don’t hold a mutable reference to
-    // `osm` while draining the relations.
-    let mut relations = HashSet::new();
-    mem::swap(osm.relations_mut(), &mut relations);
-    for relation in relations.drain() {
-        match path::Data::from_osm(relation, &osm, docs, report) {
-            Ok(path) => {
-                let _ = docs.insert(path.into(), report);
-            }
-            Err(Some(key)) => {
-                let _ = docs.insert_broken(
-                    key, Some(DocumentType::Path), Location::NONE, report
-                );
-            }
-            Err(None) => { }
+        let fit = usize::try_from(memory / largest).unwrap_or(usize::MAX);
+        jobs.min(fit).max(1)
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the file types of document files.
+fn facts_types() -> Types {
+    TypesBuilder::new().add_defaults().select("yaml").build().unwrap()
+}
+
+/// Returns the file types of path files.
+fn paths_types() -> Types {
+    let mut types = TypesBuilder::new();
+    for provider in geometry::PROVIDERS {
+        for ext in provider.extensions() {
+            types.add("paths", &format!("*.{}", ext)).unwrap();
         }
     }
+    types.select("paths").build().unwrap()
 }
 
+/// Returns the size in bytes of the largest file to be loaded from `base`.
+fn largest_file(base: &Path) -> u64 {
+    let facts = WalkBuilder::new(base.join("facts")).types(
+        facts_types()
+    ).build();
+    let paths = WalkBuilder::new(base.join("paths")).types(
+        paths_types()
+    ).build();
+    facts.chain(paths).filter_map(|entry| {
+        entry.ok()?.metadata().ok()
+    }).filter(|metadata| metadata.is_file()).map(|metadata| {
+        metadata.len()
+    }).max().unwrap_or(0)
+}
@@ -0,0 +1,202 @@
+//! Variables shared by the documents of a data tree.
+//!
+//! A data tree can contain a file `vars.yaml` in its root directory with a
+//! mapping from variable names to arbitrary values. This is useful for
+//! values repeated throughout the data, such as frequently cited sources.
+//! Documents refer to a variable through the `!var` tag:
+//!
+//! ```yaml
+//! # vars.yaml
+//! kursbuch-1914:
+//!   - source.de.kb1914
+//!
+//! # In a document
+//! source: !var kursbuch-1914
+//! ```
+//!
+//! While loading a document, each reference is replaced by a copy of the
+//! value. The copy is marked with the location of the reference, so
+//! problems with the value are reported where it is used.
+//!
+//! Variables can refer to other variables as long as the references don’t
+//! form a cycle. Within the variables file, references can’t be used as
+//! the value of a merge key.
+//!
+//! Copies of variables count towards the same limit as copies for YAML
+//! aliases, both for each document and for the variables file itself.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+use crate::types::{IntoMarked, Location};
+use super::read::Utf8Chars;
+use super::report::{self, PathReporter, Reporter, Stage};
+use super::yaml::{Loader, Value, ValueError, MAX_ALIAS_VALUES};
+
+
+//------------ Vars ----------------------------------------------------------
+
+/// The variables of a data tree.
+///
+/// Cloning the variables is cheap as they are shared.
+#[derive(Clone, Debug, Default)]
+pub struct Vars {
+    values: Arc<HashMap<String, Value>>,
+
+    /// The path of the variables file if there is one.
+    path: Option<report::Path>,
+}
+
+impl Vars {
+    /// The name of the variables file in the root of a data tree.
+    pub const FILE_NAME: &'static str = "vars.yaml";
+
+    /// The suffix of the tag referring to a variable.
+    pub const TAG: &'static str = "var";
+
+    /// Loads the variables of the data tree at `base`.
+    ///
+    /// If the tree doesn’t have a variables file, there are no variables.
+    /// Problems with the file are added to `report`.
+    pub fn load(base: &Path, report: &Reporter) -> Self {
+        let path = base.join(Self::FILE_NAME);
+        if !path.exists() {
+            return Self::default()
+        }
+        let path = report::Path::new(&path);
+        let file = match File::open(&path) {
+            Ok(file) => BufReader::new(file),
+            Err(err) => {
+                report.clone().stage(Stage::Parse)
+                    .with_path(path).error(err.marked(Location::NONE));
+                return Self::default()
+            }
+        };
+        let mut report = report.clone()
+            .stage(Stage::Translate)
+            .with_path(path.clone());
+        let mut docs = Vec::new();
+        let res = {
+            let mut loader = Loader::new(|doc| docs.push(doc));
+            loader.load(Utf8Chars::new(file))
+        };
+        if let Err(errs) = res {
+            let mut report = report.restage(Stage::Parse);
+            for err in errs {
+                report.error(err.marked(Location::NONE));
+            }
+            return Self::default()
+        }
+        let mut res = Self::from_docs(docs, &mut report);
+        res.path = Some(path);
+        res
+    }
+
+    /// Creates the variables from the documents of a variables file.
+    fn from_docs(docs: Vec<Value>, report: &mut PathReporter) -> Self {
+        let mut raw = HashMap::new();
+        for doc in docs {
+            let mut mapping = match doc.into_mapping(report) {
+                Ok(mapping) => mapping,
+                Err(_) => continue
+            };
+            let _ = mapping.check(report);
+            for (name, value) in mapping.into_iter() {
+                raw.insert(name.into_value(), value);
+            }
+        }
+        let mut values = HashMap::new();
+        let names: Vec<_> = raw.keys().cloned().collect();
+        let mut copied = 0;
+        for name in names {
+            resolve(
+                &name, &mut raw, &mut values, &mut Vec::new(), &mut copied,
+                report
+            );
+        }
+        Vars { values: Arc::new(values), path: None }
+    }
+
+    /// Returns the value of the variable with the given name.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.values.get(name)
+    }
+
+    /// Returns the path of the variables file.
+    pub fn path(&self) -> Option<&report::Path> {
+        self.path.as_ref()
+    }
+
+    /// Returns the locations of all strings equal to `text`.
+    ///
+    /// The locations are within the variables file. As variables can
+    /// contain copies of other variables, they may contain duplicates.
+    pub fn find(&self, text: &str) -> Vec<Location> {
+        let mut res = Vec::new();
+        for value in self.values.values() {
+            value.find_strings(text, &mut res)
+        }
+        res
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Resolves the references in the variable `name`.
+///
+/// The variable is taken from `raw` and, once all variables it refers to
+/// have been resolved, added to `values`. The names of the variables
+/// currently being resolved are kept in `stack` to detect cycles. The
+/// number of values copied for references so far is kept in `copied`.
+fn resolve(
+    name: &str,
+    raw: &mut HashMap<String, Value>,
+    values: &mut HashMap<String, Value>,
+    stack: &mut Vec<String>,
+    copied: &mut usize,
+    report: &mut PathReporter,
+) {
+    let mut value = match raw.remove(name) {
+        Some(value) => value,
+        None => return
+    };
+    stack.push(name.into());
+    value.replace_variables(&mut |target: &str, location: Location| {
+        if stack.iter().any(|item| item == target) {
+            let err = ValueError::VariableCycle(target.into());
+            report.error(err.clone().marked(location));
+            return Some(Value::Error(err.marked(location)))
+        }
+        resolve(target, raw, values, stack, copied, report);
+        let res = match values.get(target) {
+            Some(res) => res,
+            None => {
+                report.error(
+                    ValueError::UnknownVariable(target.into())
+                        .marked(location)
+                );
+                return None
+            }
+        };
+        *copied += res.size();
+        if *copied > MAX_ALIAS_VALUES {
+            let err = ValueError::AliasLimit;
+            report.error(err.clone().marked(location));
+            return Some(Value::Error(err.marked(location)))
+        }
+        Some(res.clone())
+    });
+    stack.pop();
+    values.insert(name.into(), value);
+}
@@ -1,11 +1,13 @@
 
-use std::{f64, fmt, ops};
+use std::{f64, fmt, iter, mem, ops};
+use std::collections::HashMap;
 use std::str::FromStr;
 use derive_more::Display;
 use yaml_rust::scanner::{Marker, ScanError, TokenType, TScalarStyle};
 use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
 use crate::types::{IntoMarked, Location, Marked};
 use super::report::{Failed, Message, PathReporter, ResultExt};
+use super::vars::Vars;
 
 
 //------------ Constructor ---------------------------------------------------
@@ -29,73 +31,206 @@ impl<F: FnMut(Value)> Constructor for F {
 
 //------------ Loader --------------------------------------------------------
 
+/// The maximum number of values copied for aliases and variables.
+///
+/// This applies to each document and to the variables file as a whole.
+pub const MAX_ALIAS_VALUES: usize = 100_000;
+
+/// Loads the documents of a YAML stream into values.
+///
+/// Anchors and aliases are supported by copying the anchored value for
+/// each alias. The copies keep the locations of the anchored value.
+///
+/// Scalars tagged with `!var` are replaced with a copy of the variable
+/// of that name as described in [`vars`][super::vars].
+///
+/// To guard against documents expanding into huge values through nested
+/// aliases or variables, the number of values copied for a document is
+/// limited to [`MAX_ALIAS_VALUES`].
 pub struct Loader<C: Constructor> {
     constructor: C,
-    nodes: Vec<Value>,
+
+    /// The unfinished collections with their anchor IDs.
+    nodes: Vec<(Value, usize)>,
     keys: Vec<Option<Value>>,
+
+    /// The anchored values of the current document and their sizes.
+    anchors: HashMap<usize, (Value, usize)>,
+
+    /// The number of values copied for aliases and variables in the
+    /// current document.
+    copied: usize,
+
+    /// The variables available to the documents.
+    vars: Vars,
+
+    /// Whether the stream used aliases or merge keys.
+    expanded: bool,
+
+    /// The locations of the variable references in the stream.
+    references: Vec<Location>,
 }
 
 impl<C: Constructor> Loader<C> {
     pub fn new(constructor: C) -> Self {
-        Loader { constructor, nodes: Vec::new(), keys: Vec::new() }
+        Loader {
+            constructor,
+            nodes: Vec::new(),
+            keys: Vec::new(),
+            anchors: HashMap::new(),
+            copied: 0,
+            vars: Vars::default(),
+            expanded: false,
+            references: Vec::new(),
+        }
     }
 
-    pub fn load<I>(&mut self, source: I) -> Result<(), ScanError>
+    /// Sets the variables available to the documents.
+    pub fn with_vars(mut self, vars: Vars) -> Self {
+        self.vars = vars;
+        self
+    }
+
+    /// Returns whether the stream used aliases, merge keys, or variables.
+    ///
+    /// The values of such streams don’t reflect the stream’s text, so it
+    /// can’t be recreated from them.
+    pub fn has_expansions(&self) -> bool {
+        self.expanded || !self.references.is_empty()
+    }
+
+    /// Returns the locations of the variable references in the stream.
+    pub fn references(&self) -> &[Location] {
+        &self.references
+    }
+
+    /// Loads all documents of a YAML stream.
+    ///
+    /// If a document fails to scan, it is dropped and loading continues
+    /// with the next document that starts after the error, so that an
+    /// error only affects the document it occurs in. Returns all scan
+    /// errors encountered.
+    pub fn load<I>(&mut self, source: I) -> Result<(), Vec<ScanError>>
                 where I: IntoIterator<Item=char> {
-        let mut parser = Parser::new(source.into_iter());
-        parser.load(self, true)?;
-        Ok(())
+        let source: String = source.into_iter().collect();
+        self.load_from_str(&source)
+    }
+
+    pub fn load_from_str(
+        &mut self, source: &str
+    ) -> Result<(), Vec<ScanError>> {
+        let mut errors = Vec::new();
+        let (mut line, mut start) = (0, 0);
+        loop {
+            // Prefix the remaining stream with empty lines so the
+            // positions of resumed documents stay correct.
+            let chars = iter::repeat('\n').take(line).chain(
+                source[start..].chars()
+            );
+            let res = Parser::new(chars).load(self, true);
+            self.nodes.clear();
+            self.keys.clear();
+            self.anchors.clear();
+            let err = match res {
+                Ok(()) => break,
+                Err(err) => err,
+            };
+            // Marker lines start at 1, so this is the line after the error.
+            let next = next_document(source, err.marker().line());
+            errors.push(err);
+            match next {
+                Some(next) => (line, start) = next,
+                None => break
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(errors)
+        }
     }
+}
 
-    pub fn load_from_str(&mut self, source: &str) -> Result<(), ScanError> {
-        self.load(source.chars())
+/// Returns the first document start at or after the given line.
+///
+/// Returns the zero-based line number and the byte position of the start
+/// of that line.
+fn next_document(source: &str, from: usize) -> Option<(usize, usize)> {
+    let mut start = 0;
+    for (line, text) in source.split_inclusive('\n').enumerate() {
+        if line >= from {
+            if let Some(rest) = text.strip_prefix("---") {
+                if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+                    return Some((line, start))
+                }
+            }
+        }
+        start += text.len();
     }
+    None
 }
 
 impl<C: Constructor> MarkedEventReceiver for Loader<C> {
     fn on_event(&mut self, ev: Event, mark: Marker) {
         match ev {
             Event::DocumentStart => {
-                assert!(self.nodes.is_empty())
+                assert!(self.nodes.is_empty());
+                self.anchors.clear();
+                self.copied = 0;
             }
             Event::DocumentEnd => {
-                if let Some(node) = self.nodes.pop() {
+                if let Some((node, _)) = self.nodes.pop() {
                     self.constructor.construct(node)
                 }
             }
-            Event::SequenceStart(_) => {
-                self.nodes.push(Value::sequence(mark))
+            Event::SequenceStart(anchor) => {
+                self.nodes.push((Value::sequence(mark), anchor))
             }
             Event::SequenceEnd => {
-                let node = self.nodes.pop().unwrap();
-                self.push_value(node);
+                let (node, anchor) = self.nodes.pop().unwrap();
+                self.push_value(node, anchor);
             }
-            Event::MappingStart(_) => {
-                self.nodes.push(Value::mapping(mark));
+            Event::MappingStart(anchor) => {
+                self.nodes.push((Value::mapping(mark), anchor));
                 self.keys.push(None);
             }
             Event::MappingEnd => {
                 self.keys.pop().unwrap();
-                let node = self.nodes.pop().unwrap();
-                self.push_value(node);
-            }
-            Event::Scalar(value, style, _, tag) => {
-                let plain = style == TScalarStyle::Plain;
-                self.push_value(
-                    Value::scalar(
-                        value, plain,
-                        tag.and_then(|ttype| {
-                            if let TokenType::Tag(x, y) = ttype {
-                                Some((x, y))
-                            }
-                            else {
-                                None
-                            }
-                        }),
-                        mark))
+                let (mut node, anchor) = self.nodes.pop().unwrap();
+                if let Value::Mapping(ref mut mapping) = node {
+                    mapping.finish()
+                }
+                self.push_value(node, anchor);
+            }
+            Event::Scalar(value, style, anchor, tag) => {
+                let value = match tag {
+                    Some(TokenType::Tag(ref handle, ref suffix))
+                        if handle == "!" && suffix == Vars::TAG
+                    => {
+                        self.variable(value, mark)
+                    }
+                    tag => {
+                        let plain = style == TScalarStyle::Plain;
+                        Value::scalar(
+                            value, plain,
+                            tag.and_then(|ttype| {
+                                if let TokenType::Tag(x, y) = ttype {
+                                    Some((x, y))
+                                }
+                                else {
+                                    None
+                                }
+                            }),
+                            mark
+                        )
+                    }
+                };
+                self.push_value(value, anchor)
             }
-            Event::Alias(_) => {
-                self.push_value(Value::alias(mark))
+            Event::Alias(anchor) => {
+                let value = self.alias(anchor, mark);
+                self.push_value(value, 0)
             }
             _ => { }
         }
@@ -103,12 +238,65 @@ impl<C: Constructor> MarkedEventReceiver for Loader<C> {
 }
 
 impl<C: Constructor> Loader<C> {
-    fn push_value(&mut self, value: Value) {
+    /// Returns a copy of the value anchored under the given ID.
+    fn alias(&mut self, anchor: usize, mark: Marker) -> Value {
+        self.expanded = true;
+        let (value, size) = match self.anchors.get(&anchor) {
+            Some(item) => item,
+            // The parser rejects unknown anchors, so the anchor is still
+            // open and the alias is part of the value it refers to.
+            None => {
+                return Value::Error(
+                    ValueError::RecursiveAlias.marked(mark.into())
+                )
+            }
+        };
+        self.copied += size;
+        if self.copied > MAX_ALIAS_VALUES {
+            return Value::Error(ValueError::AliasLimit.marked(mark.into()))
+        }
+        value.clone()
+    }
+
+    /// Returns a copy of the variable with the given name.
+    ///
+    /// The copy is moved to the location of the reference. Its size
+    /// counts towards the same limit as the copies for aliases.
+    fn variable(&mut self, name: String, mark: Marker) -> Value {
+        let location = mark.into();
+        self.references.push(location);
+        match self.vars.get(&name) {
+            Some(value) => {
+                self.copied += value.size();
+                if self.copied > MAX_ALIAS_VALUES {
+                    return Value::Error(
+                        ValueError::AliasLimit.marked(location)
+                    )
+                }
+                let mut value = value.clone();
+                value.relocate(location);
+                value
+            }
+            None => {
+                Value::Error(
+                    ValueError::UnknownVariable(name).marked(location)
+                )
+            }
+        }
+    }
+
+    /// Adds a finished value to the collection it is part of.
+    ///
+    /// If `anchor` isn’t zero, the value is remembered for aliases.
+    fn push_value(&mut self, value: Value, anchor: usize) {
+        if anchor != 0 {
+            self.anchors.insert(anchor, (value.clone(), value.size()));
+        }
         if self.nodes.is_empty() {
-            self.nodes.push(value)
+            self.nodes.push((value, 0))
         }
         else {
-            match *self.nodes.last_mut().unwrap() {
+            match self.nodes.last_mut().unwrap().0 {
                 Value::Sequence(ref mut sequence) => {
                     sequence.push(value)
                 }
@@ -117,6 +305,9 @@ impl<C: Constructor> Loader<C> {
                         mapping.insert(key, value)
                     }
                     else {
+                        if value.is_merge_key() {
+                            self.expanded = true
+                        }
                         *self.keys.last_mut().unwrap() = Some(value)
                     }
                 }
@@ -158,8 +349,121 @@ impl Value {
         }
     }
 
-    fn alias(mark: Marker) -> Self {
-        Value::Error(Marked::new(ValueError::Alias, mark.into()))
+    /// Moves the value and all values it contains to a new location.
+    fn relocate(&mut self, location: Location) {
+        match *self {
+            Value::Sequence(ref mut inner) => {
+                inner.location = location;
+                for item in &mut inner.items {
+                    item.relocate(location)
+                }
+            }
+            Value::Mapping(ref mut inner) => {
+                inner.location = location;
+                for (key, value) in &mut inner.items {
+                    key.set_location(location);
+                    if let Some(value) = value {
+                        value.relocate(location)
+                    }
+                }
+                for err in &mut inner.errors {
+                    err.set_location(location)
+                }
+            }
+            Value::Scalar(ref mut inner) => inner.set_location(location),
+            Value::Error(ref mut inner) => inner.set_location(location),
+        }
+    }
+
+    /// Replaces the references to unknown variables within the value.
+    ///
+    /// Calls `op` with the name and location of each reference. If it
+    /// returns a value, the reference is replaced with it.
+    pub(super) fn replace_variables(
+        &mut self, op: &mut impl FnMut(&str, Location) -> Option<Value>
+    ) {
+        let replacement = match *self {
+            Value::Sequence(ref mut inner) => {
+                for item in &mut inner.items {
+                    item.replace_variables(op)
+                }
+                None
+            }
+            Value::Mapping(ref mut inner) => {
+                for value in inner.items.iter_mut().filter_map(|item| {
+                    item.1.as_mut()
+                }) {
+                    value.replace_variables(op)
+                }
+                None
+            }
+            Value::Scalar(_) => None,
+            Value::Error(ref inner) => match *inner.as_value() {
+                ValueError::UnknownVariable(ref name) => {
+                    op(name, inner.location())
+                }
+                _ => None
+            }
+        };
+        if let Some(value) = replacement {
+            *self = value
+        }
+    }
+
+    /// Returns whether the value is the merge key of a mapping.
+    fn is_merge_key(&self) -> bool {
+        match *self {
+            Value::Scalar(Scalar::String(ref key)) => {
+                key.as_value() == Mapping::MERGE_KEY
+            }
+            _ => false
+        }
+    }
+
+    /// Adds the locations of all strings equal to `text` to `res`.
+    ///
+    /// This includes the keys of mappings.
+    pub(super) fn find_strings(
+        &self, text: &str, res: &mut Vec<Location>
+    ) {
+        match *self {
+            Value::Sequence(ref inner) => {
+                for item in &inner.items {
+                    item.find_strings(text, res)
+                }
+            }
+            Value::Mapping(ref inner) => {
+                for (key, value) in &inner.items {
+                    if key.as_value() == text {
+                        res.push(key.location())
+                    }
+                    if let Some(value) = value {
+                        value.find_strings(text, res)
+                    }
+                }
+            }
+            Value::Scalar(Scalar::String(ref inner)) => {
+                if inner.as_value() == text {
+                    res.push(inner.location())
+                }
+            }
+            _ => { }
+        }
+    }
+
+    /// Returns the number of values this value consists of.
+    pub(super) fn size(&self) -> usize {
+        1 + match *self {
+            Value::Sequence(ref inner) => {
+                inner.items.iter().map(Value::size).sum()
+            }
+            Value::Mapping(ref inner) => {
+                inner.items.iter().filter_map(|(_, value)| {
+                    value.as_ref()
+                }).map(Value::size).sum()
+            }
+            _ => 0
+        }
     }
 
     pub fn location(&self) -> Location {
@@ -346,14 +650,30 @@ pub struct Mapping {
     items: Vec<(Marked<String>, Option<Value>)>,
     errors: Vec<Marked<ValueError>>,
     location: Location,
+
+    /// The items of mappings given via merge keys.
+    ///
+    /// These are added to `items` once the mapping is complete.
+    merged: Vec<(Marked<String>, Value)>,
 }
 
 impl Mapping {
+    /// The key for merging other mappings into a mapping.
+    ///
+    /// The value of the key must be a mapping or a sequence of mappings.
+    /// Their items are added to the mapping unless it contains the same
+    /// key explicitly. If several mappings contain a key, the one given
+    /// first wins. The merged items keep their original locations.
+    ///
+    /// Unlike in YAML 1.1, the key is recognized even if it is quoted.
+    const MERGE_KEY: &'static str = "<<";
+
     fn new(location: Location) -> Self {
         Mapping {
             items: Vec::new(),
             errors: Vec::new(),
-            location
+            location,
+            merged: Vec::new(),
         }
     }
 
@@ -367,6 +687,10 @@ impl Mapping {
                 return
             }
         };
+        if key.as_value() == Self::MERGE_KEY {
+            self.merge(value);
+            return
+        }
         if self.items.iter().find(|item| item.0 == key).is_some() {
             self.errors.push(
                 ValueError::DuplicateMappingKey.marked(
@@ -377,6 +701,58 @@ impl Mapping {
         }
         self.items.push((key, Some(value)));
     }
+
+    /// Remembers the items of the value of a merge key.
+    fn merge(&mut self, value: Value) {
+        match value {
+            Value::Mapping(mapping) => self.merge_mapping(mapping),
+            Value::Sequence(sequence) => {
+                for item in sequence.items {
+                    match item {
+                        Value::Mapping(mapping) => {
+                            self.merge_mapping(mapping)
+                        }
+                        Value::Error(err) => self.errors.push(err),
+                        item => {
+                            self.errors.push(
+                                ValueError::InvalidMerge.marked(
+                                    item.location()
+                                )
+                            )
+                        }
+                    }
+                }
+            }
+            Value::Error(err) => self.errors.push(err),
+            value => {
+                self.errors.push(
+                    ValueError::InvalidMerge.marked(value.location())
+                )
+            }
+        }
+    }
+
+    fn merge_mapping(&mut self, mapping: Mapping) {
+        self.errors.extend(mapping.errors);
+        for (key, value) in mapping.items {
+            let value = match value {
+                Some(value) => value,
+                None => continue
+            };
+            if !self.merged.iter().any(|item| item.0 == key) {
+                self.merged.push((key, value))
+            }
+        }
+    }
+
+    /// Adds the merged items whose keys weren’t given explicitly.
+    fn finish(&mut self) {
+        for (key, value) in mem::take(&mut self.merged) {
+            if !self.items.iter().any(|item| item.0 == key) {
+                self.items.push((key, Some(value)))
+            }
+        }
+    }
 }
 
 impl Mapping {
@@ -454,6 +830,8 @@ impl Mapping {
     }
 
     fn remove(&mut self, key: &str) -> Option<Value> {
+        #[cfg(test)]
+        record_key(key);
         if let Some(item) = self.items.iter_mut().find(|item|
             item.0.as_value() == key
         ) {
@@ -472,6 +850,37 @@ impl Mapping {
 }
 
 
+#[cfg(test)]
+thread_local! {
+    /// The keys asked for from mappings while recording on this thread.
+    static RECORDED_KEYS: std::cell::RefCell<
+        Option<std::collections::BTreeSet<String>>
+    > = const { std::cell::RefCell::new(None) };
+}
+
+#[cfg(test)]
+fn record_key(key: &str) {
+    RECORDED_KEYS.with(|keys| {
+        if let Some(keys) = keys.borrow_mut().as_mut() {
+            keys.insert(key.into());
+        }
+    })
+}
+
+/// Runs `op` and returns the keys it asked for from any mapping.
+///
+/// Only mappings processed on the current thread are considered.
+#[cfg(test)]
+pub(crate) fn record_keys<R>(
+    op: impl FnOnce() -> R
+) -> (R, std::collections::BTreeSet<String>) {
+    RECORDED_KEYS.with(|keys| *keys.borrow_mut() = Some(Default::default()));
+    let res = op();
+    let keys = RECORDED_KEYS.with(|keys| keys.borrow_mut().take());
+    (res, keys.unwrap_or_default())
+}
+
+
 //------------ Sequence ------------------------------------------------------
 
 #[derive(Clone, Debug, Default)]
@@ -629,6 +1038,16 @@ impl Scalar {
         }
     }
 
+    fn set_location(&mut self, location: Location) {
+        match *self {
+            Scalar::String(ref mut inner) => inner.set_location(location),
+            Scalar::Null(ref mut inner) => inner.set_location(location),
+            Scalar::Boolean(ref mut inner) => inner.set_location(location),
+            Scalar::Integer(ref mut inner) => inner.set_location(location),
+            Scalar::Float(ref mut inner) => inner.set_location(location),
+        }
+    }
+
     fn into_error<M: Message>(
         self,
         message: M
@@ -749,6 +1168,33 @@ impl<C> FromYaml<C> for u8 {
     }
 }
 
+impl<C> FromYaml<C> for Marked<u16> {
+    fn from_yaml(
+        value: Value,
+        _: &C,
+        report: &mut PathReporter
+    ) -> Result<Self, Failed> {
+        value.into_integer(report)?.try_map(|int| {
+            if int < 0 || int > ::std::u16::MAX as i64 {
+                Err(RangeError::new(0, ::std::u16::MAX as i64, int))
+            }
+            else {
+                Ok(int as u16)
+            }
+         }).or_error(report)
+    }
+}
+
+impl<C> FromYaml<C> for u16 {
+    fn from_yaml(
+        value: Value,
+        context: &C,
+        report: &mut PathReporter
+    ) -> Result<Self, Failed> {
+        Marked::from_yaml(value, context, report).map(Marked::into_value)
+    }
+}
+
 impl<C> FromYaml<C> for Marked<f64> {
     fn from_yaml(
         value: Value,
@@ -829,8 +1275,20 @@ pub enum ValueError {
     #[display(fmt="invalid float")]
     InvalidFloat,
 
-    #[display(fmt="aliases are not allowed")]
-    Alias,
+    #[display(fmt="alias refers to a value containing it")]
+    RecursiveAlias,
+
+    #[display(fmt="aliases or variables expand to too many values")]
+    AliasLimit,
+
+    #[display(fmt="merge key requires a mapping or a sequence of mappings")]
+    InvalidMerge,
+
+    #[display(fmt="unknown variable '{}'", _0)]
+    UnknownVariable(String),
+
+    #[display(fmt="variable '{}' refers to itself", _0)]
+    VariableCycle(String),
 
     #[display(fmt="unknown tag !{}{}", _0, _1)]
     UnknownTag(String, String),
@@ -1,12 +1,41 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, IsTerminal};
 use std::process;
-use std::path::PathBuf;
+use std::str::FromStr;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use serde_json::Value as JsonValue;
+use raildata::attachment::Attachments;
+use raildata::bibliography::{Bibliography, Format};
+use raildata::bulk::{Detail, DocumentSet};
 use raildata::catalogue::Catalogue;
-use raildata::document::Data;
-use raildata::load::load_tree;
-use raildata::load::report::Stage;
-use raildata::store::DataStore;
+use raildata::changes::ChangeFeed;
+use raildata::diff::Diff;
+#[cfg(feature = "linkcheck")]
+use raildata::linkcheck::LinkChecker;
+use raildata::document::common::{DocumentType, Progress};
+use raildata::duplicates::Duplicates;
+use raildata::load::{
+    load_tree, load_tree_forced_limited, load_tree_limited, Limits,
+    LoadObserver, TerminalProgress
+};
+use raildata::load::config::Config;
+use raildata::load::observer::observe;
+use raildata::load::report::{Report, Severity, Stage};
+use raildata::query::Query;
+use raildata::railml::Railml;
+use raildata::refactor::Rename;
+use raildata::release::Release;
+use raildata::repl::Session;
+use raildata::schema::Schema;
+use raildata::sqlite::SqlDump;
+use raildata::stats::{CourseCoverage, Statistics};
+use raildata::store::{DataStore, FullStore};
+use raildata::store::write::format_files;
+use raildata::template::Template;
+use raildata::types::{CountryCode, Key, LanguagePreference, Text};
+use raildata::validate::Validator;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -22,43 +51,879 @@ struct Args {
     /// Verbose output.
     #[arg(long, short)]
     verbose: bool,
+
+    /// Don’t show the progress of loading on standard error.
+    #[arg(long)]
+    no_progress: bool,
+
+    /// Continue despite errors, skipping broken documents.
+    #[arg(long)]
+    force: bool,
+
+    /// Print statistics as JSON.
+    #[arg(long)]
+    json: bool,
+
+    /// Only include documents with at least this progress in the output.
+    #[arg(long, value_name = "PROGRESS", value_parser = parse_progress)]
+    min_progress: Option<Progress>,
+
+    /// Only include complete documents in the output.
+    #[arg(long, conflicts_with = "min_progress")]
+    released_only: bool,
+
+    /// Check that the URLs of all sources can be retrieved.
+    #[cfg(feature = "linkcheck")]
+    #[arg(long)]
+    check_urls: bool,
+
+    /// Disable a validation rule.
+    #[arg(long, value_name = "RULE")]
+    disable_rule: Vec<String>,
+
+    /// The number of threads to use instead of one per CPU.
+    #[arg(long, short, value_name = "N", value_parser = parse_jobs)]
+    jobs: Option<usize>,
+
+    /// Limit the memory for parsing files to this many megabytes.
+    ///
+    /// Fewer files are parsed in parallel if necessary.
+    #[arg(long, value_name = "MB")]
+    memory_limit: Option<u64>,
+
+    /// The preferred languages for names, separated by commas.
+    ///
+    /// The server prefers the languages requested by the client and only
+    /// falls back to these.
+    #[arg(long, value_name = "LANGS", value_parser = parse_languages)]
+    lang: Option<LanguagePreference>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print a description of the document schema as JSON.
+    Schema,
+
+    /// Print a skeleton for a new document.
+    ///
+    /// The document type is derived from the key unless given
+    /// explicitly. The skeleton is checked before it is printed.
+    New {
+        /// The key of the new document.
+        key: String,
+
+        /// The document type.
+        #[arg(long = "type", value_parser = parse_doctype)]
+        doctype: Option<DocumentType>,
+
+        /// The subtype of the document.
+        #[arg(long)]
+        subtype: Option<String>,
+
+        /// The name of the document or the title of a source.
+        #[arg(long)]
+        name: Option<String>,
+
+        /// A category of a line or point.
+        #[arg(long)]
+        category: Vec<String>,
+
+        /// The key of a point of a line.
+        #[arg(long = "point", value_name = "KEY")]
+        points: Vec<String>,
+    },
+
+    /// Rewrite all document files in canonical form.
+    ///
+    /// Files using aliases, merge keys, or variables are left unchanged.
+    Fmt {
+        /// Only report which files would change.
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// Change the key of a document and update all references to it.
+    ///
+    /// If a document with the new key exists already, all references are
+    /// redirected to it and the old document needs to be merged manually.
+    Rename {
+        /// The current key of the document.
+        old: String,
+
+        /// The new key of the document.
+        new: String,
+
+        /// Only print which files would change.
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Show the changes relative to an older version of the data.
+    Diff {
+        /// Path to the data directory of the older version.
+        old: PathBuf,
+    },
+
+    /// Print the documents changed after a time as JSON.
+    Changes {
+        /// The time in seconds since the Unix epoch.
+        #[arg(long, default_value_t = 0)]
+        since: i64,
+    },
+
+    /// List the files that still use deprecated spellings of values.
+    Deprecated,
+
+    /// Export sources as a bibliography.
+    Bib {
+        /// The output format, either 'bibtex' or 'csl-json'.
+        #[arg(
+            long, default_value = "bibtex", value_parser = parse_format
+        )]
+        format: Format,
+
+        /// Only export sources regarding the document with this key.
+        #[arg(long, value_name = "KEY")]
+        regarding: Option<String>,
+    },
+
+    /// Print the events on a calendar day in any year as JSON.
+    On {
+        /// The month from 1 to 12.
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=12))]
+        month: u8,
+
+        /// The day of the month from 1 to 31.
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=31))]
+        day: u8,
+    },
+
+    /// Print a summary of a country as JSON.
+    Country {
+        /// The two letter country code.
+        #[arg(value_parser = parse_country)]
+        code: CountryCode,
+    },
+
+    /// Print the hierarchy of collections of a source as JSON.
+    Sources {
+        /// The key of any source in the hierarchy.
+        key: String,
+    },
+
+    /// Print several documents as JSON.
+    Get {
+        /// The keys of the documents.
+        #[arg(required = true)]
+        keys: Vec<String>,
+
+        /// How much of each document to print: 'summary', 'standard',
+        /// 'data', or 'full'.
+        #[arg(
+            long, default_value = "summary", value_parser = parse_detail
+        )]
+        detail: Detail,
+    },
+
+    /// Search documents by name and print the matches as JSON.
+    Search {
+        /// The name or, with --prefix, its beginning.
+        text: String,
+
+        /// Also match names that only start with the text.
+        #[arg(long)]
+        prefix: bool,
+
+        /// The number of mistyped characters to allow, at most 3.
+        #[arg(long, default_value_t = 1, value_parser = parse_distance)]
+        distance: usize,
+    },
+
+    /// Print the keys of the documents matching a query.
+    ///
+    /// A query consists of terms such as 'type:line country:de
+    /// status:open electrified:15kv' all of which must match.
+    Query {
+        /// The query.
+        #[arg(value_parser = parse_query)]
+        query: Query,
+
+        /// Print the documents as JSON with this detail instead: 'summary',
+        /// 'standard', 'data', or 'full'.
+        #[arg(long, value_parser = parse_detail)]
+        detail: Option<Detail>,
+    },
+
+    /// List pairs of documents that are likely duplicates.
+    Duplicates {
+        /// Print the candidates as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List the lines and sections that lack course information.
+    Coverage {
+        /// Print the coverage as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Export lines and points as railML.
+    Railml {
+        /// Only export the documents of this country.
+        #[arg(long, value_parser = parse_country)]
+        country: Option<CountryCode>,
+    },
+
+    /// Export the data as an SQL script creating an SQLite database.
+    Sqlite,
+
+    /// Bundle all exports of a data release.
+    ///
+    /// The data is checked as usual first. The exports and a manifest
+    /// are written into a new directory named after the version.
+    Release {
+        /// The version of the release.
+        version: String,
+
+        /// The directory to create the release directory in.
+        #[arg(long, default_value = "releases")]
+        output: PathBuf,
+    },
+
+    /// Serve the data as HTML pages for browsing.
+    #[cfg(feature = "html")]
+    Serve(ServeArgs),
+
+    /// Keep the data loaded and check files and look up documents
+    /// interactively.
+    Repl,
+}
+
+/// The arguments of the `serve` command.
+#[cfg(feature = "html")]
+#[derive(clap::Args, Debug)]
+struct ServeArgs {
+    /// The address to listen on.
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: String,
+
+    /// Enable the admin endpoints protected by the token in this file.
+    ///
+    /// Instead of a file, the token can be given in the RAILDATA_TOKEN
+    /// environment variable. Without either, the admin endpoints are
+    /// disabled.
+    #[arg(long, value_name = "FILE")]
+    token_file: Option<PathBuf>,
+
+    /// Reload the data when files change, checking every SECONDS.
+    #[arg(long, value_name = "SECONDS")]
+    watch: Option<u64>,
+
+    /// A directory with data trees to compare documents against.
+    ///
+    /// Comparing requires the admin token.
+    #[arg(long, value_name = "DIR")]
+    snapshots: Option<PathBuf>,
+
+    /// The number of threads handling requests.
+    #[arg(
+        long, value_name = "N",
+        default_value_t = raildata::html::Server::DEFAULT_WORKERS,
+        value_parser = parse_jobs
+    )]
+    workers: usize,
+
+    /// The format of the request log: text or json.
+    #[arg(long, default_value = "text", value_parser = parse_log_format)]
+    log_format: raildata::html::LogFormat,
+
+    /// Which requests to log: off, error, or info.
+    #[arg(long, default_value = "info", value_parser = parse_log_level)]
+    log_level: raildata::html::LogLevel,
+}
+
+#[cfg(feature = "html")]
+impl ServeArgs {
+    /// The environment variable containing the admin token.
+    const TOKEN_VAR: &'static str = "RAILDATA_TOKEN";
+
+    /// Returns the token for the admin endpoints if there is one.
+    ///
+    /// A token file takes precedence over the environment variable.
+    /// White space around the token is ignored.
+    fn token(&self) -> Result<Option<String>, String> {
+        let token = match self.token_file.as_ref() {
+            Some(path) => std::fs::read_to_string(path).map_err(|err| {
+                format!("cannot read {}: {}", path.display(), err)
+            })?,
+            None => match std::env::var(Self::TOKEN_VAR) {
+                Ok(token) => token,
+                Err(_) => return Ok(None)
+            }
+        };
+        let token = token.trim();
+        if token.is_empty() {
+            return Err("the admin token is empty".into())
+        }
+        Ok(Some(token.into()))
+    }
+}
+
+impl Args {
+    /// Returns the minimum progress of documents to include in the output.
+    fn min_progress(&self) -> Progress {
+        if self.released_only {
+            Progress::Complete
+        }
+        else {
+            self.min_progress.unwrap_or(Progress::Stub)
+        }
+    }
+
+    /// Returns the preferred languages for names.
+    fn preference(&self) -> LanguagePreference {
+        self.lang.clone().unwrap_or_default()
+    }
+
+    /// Returns the resource limits for loading the data.
+    fn limits(&self) -> Limits {
+        Limits {
+            jobs: self.jobs,
+            memory: self.memory_limit.map(|mb| mb.saturating_mul(1 << 20)),
+        }
+    }
+
+    /// Returns the options for loading the data.
+    fn load_options(&self) -> LoadOptions {
+        LoadOptions {
+            path: self.path.clone(),
+            force: self.force,
+            limits: self.limits(),
+            disable_rule: self.disable_rule.clone(),
+            progress: !self.no_progress,
+        }
+    }
+}
+
+/// The options for loading the data.
+///
+/// These are used for the initial load as well as when the data is loaded
+/// again by the `serve` command.
+#[derive(Clone, Debug)]
+struct LoadOptions {
+    path: PathBuf,
+    force: bool,
+    limits: Limits,
+    disable_rule: Vec<String>,
+    progress: bool,
+}
+
+impl LoadOptions {
+    /// Loads the data store.
+    ///
+    /// With `force`, problems are printed and broken documents skipped.
+    fn data_store(
+        &self, observer: &dyn LoadObserver
+    ) -> Result<DataStore, Report> {
+        if self.force {
+            let (store, report) = load_tree_forced_limited(
+                &self.path, &self.limits, observer
+            );
+            print_forced(report);
+            Ok(store)
+        }
+        else {
+            load_tree_limited(&self.path, &self.limits, observer)
+        }
+    }
+
+    /// Returns the validator configured for the data.
+    fn validator(&self) -> Result<Validator, String> {
+        let config = Config::load(&self.path).map_err(|err| {
+            format!("{}: {}", Config::FILE_NAME, err)
+        })?;
+        let mut validator = Validator::with_config(config);
+        for rule in &self.disable_rule {
+            validator.disable(rule).map_err(|err| err.to_string())?;
+        }
+        Ok(validator)
+    }
+
+    /// Converts the data store into a full store.
+    fn full_store(
+        &self, store: DataStore, observer: &dyn LoadObserver
+    ) -> Result<FullStore, Report> {
+        if self.force {
+            let (store, report) = store.into_full_store_forced(observer);
+            print_forced(report);
+            Ok(store)
+        }
+        else {
+            store.into_full_store_observed(observer)
+        }
+    }
+
+    /// Generates the catalogue of the full store.
+    fn catalogue(
+        &self, store: &FullStore, observer: &dyn LoadObserver
+    ) -> Result<Catalogue, Report> {
+        observe(observer, Stage::Catalogue, || {
+            if self.force {
+                let (catalogue, report) = Catalogue::generate_forced(store);
+                print_forced(report);
+                Ok(catalogue)
+            }
+            else {
+                Catalogue::generate(store)
+            }
+        })
+    }
+}
+
+fn parse_progress(s: &str) -> Result<Progress, String> {
+    Progress::ALL.iter().copied().find(|item| {
+        item.as_str() == s
+    }).ok_or_else(|| {
+        format!("expected one of {}", Progress::VARIANTS.join(", "))
+    })
+}
+
+fn parse_languages(s: &str) -> Result<LanguagePreference, String> {
+    LanguagePreference::from_str(s).map_err(|err| err.to_string())
+}
+
+fn parse_jobs(s: &str) -> Result<usize, String> {
+    match usize::from_str(s) {
+        Ok(0) => Err("expected at least one thread".into()),
+        Ok(jobs) => Ok(jobs),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn parse_distance(s: &str) -> Result<usize, String> {
+    match usize::from_str(s) {
+        Ok(distance) if distance > Catalogue::MAX_DISTANCE => {
+            Err(format!("expected at most {}", Catalogue::MAX_DISTANCE))
+        }
+        Ok(distance) => Ok(distance),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn parse_doctype(s: &str) -> Result<DocumentType, String> {
+    DocumentType::ALL.iter().copied().find(|item| {
+        item.as_str() == s
+    }).ok_or_else(|| {
+        format!("expected one of {}", DocumentType::VARIANTS.join(", "))
+    })
+}
+
+fn parse_country(s: &str) -> Result<CountryCode, String> {
+    CountryCode::from_str(s).map_err(|_| {
+        String::from("expected a two letter country code")
+    })
+}
+
+#[cfg(feature = "html")]
+fn parse_log_format(s: &str) -> Result<raildata::html::LogFormat, String> {
+    use raildata::html::LogFormat;
+
+    LogFormat::from_name(s).ok_or_else(|| {
+        format!(
+            "expected one of {}",
+            LogFormat::ALL.iter().map(|item| {
+                item.as_str()
+            }).collect::<Vec<_>>().join(", ")
+        )
+    })
+}
+
+#[cfg(feature = "html")]
+fn parse_log_level(s: &str) -> Result<raildata::html::LogLevel, String> {
+    use raildata::html::LogLevel;
+
+    LogLevel::from_name(s).ok_or_else(|| {
+        format!(
+            "expected one of {}",
+            LogLevel::ALL.iter().map(|item| {
+                item.as_str()
+            }).collect::<Vec<_>>().join(", ")
+        )
+    })
+}
+
+fn parse_detail(s: &str) -> Result<Detail, String> {
+    Detail::from_name(s).ok_or_else(|| {
+        format!(
+            "expected one of {}",
+            Detail::ALL.iter().map(|item| {
+                item.as_str()
+            }).collect::<Vec<_>>().join(", ")
+        )
+    })
+}
+
+fn parse_query(s: &str) -> Result<Query, String> {
+    Query::from_str(s).map_err(|err| err.to_string())
+}
+
+fn parse_format(s: &str) -> Result<Format, String> {
+    Format::from_name(s).ok_or_else(|| {
+        format!(
+            "expected one of {}",
+            Format::ALL.iter().map(|item| {
+                item.as_str()
+            }).collect::<Vec<_>>().join(", ")
+        )
+    })
+}
+
+fn print_stats(store: &DataStore, min: Progress, json: bool) {
+    let stats = Statistics::generate_with_progress(store, min);
+    if json {
+        println!("{}", stats.to_json());
+    }
+    else {
+        print!("{}", stats);
+    }
+}
+
+/// Prints the problems found when continuing despite errors.
+///
+/// These go to standard error so they don’t end up in exported data.
+fn print_forced(mut report: Report) {
+    if report.is_empty() {
+        return
+    }
+    report.sort();
+    eprintln!("{} problems.", report.len());
+    for item in report.iter() {
+        eprintln!("{}", item)
+    }
+}
+
+/// Prints the documents replaced by placeholders.
+fn print_skipped(store: &DataStore) {
+    if store.placeholders().is_empty() {
+        return
+    }
+    eprintln!("Skipped {} documents.", store.placeholders().len());
+    for link in store.placeholders() {
+        let data = link.data(store);
+        eprintln!("{}: {}", data.origin(), data.key());
+    }
 }
 
-fn print_stats(store: &DataStore) {
-    let mut lines = 0;
-    let mut entities = 0;
-    let mut paths = 0;
-    let mut points = 0;
-    let mut sources = 0;
-    let mut structures = 0;
+fn print_deprecated_files(store: &DataStore) {
+    let mut files = BTreeMap::<_, (usize, BTreeSet<_>)>::new();
+    for (origin, value) in store.deprecated() {
+        let file = files.entry(origin.path().to_string()).or_default();
+        file.0 += 1;
+        file.1.insert(value.found());
+    }
+    println!("{} files use deprecated values.", files.len());
+    for (path, (count, found)) in files {
+        println!(
+            "{}: {} ({})",
+            path, count, found.into_iter().collect::<Vec<_>>().join(", ")
+        );
+    }
+}
 
-    for key in store.links() {
-        match *key.data(store) {
-            Data::Line(_) => lines += 1,
-            Data::Entity (_) => entities += 1,
-            Data::Path(_) => paths += 1,
-            Data::Point(_) => points += 1,
-            Data::Source(_) => sources += 1,
-            Data::Structure(_) => structures += 1,
+fn new_document(
+    key: &str,
+    doctype: Option<DocumentType>,
+    subtype: Option<&str>,
+    name: Option<&str>,
+    category: &[String],
+    points: &[String],
+) {
+    let key = Key::from_str(key).unwrap();
+    let template = match doctype {
+        Some(doctype) => Template::with_doctype(key, doctype),
+        None => Template::new(key),
+    };
+    let mut template = match template {
+        Ok(template) => template,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
         }
+    };
+    if let Some(subtype) = subtype {
+        template.set_subtype(subtype);
+    }
+    if let Some(name) = name {
+        template.set_name(name);
+    }
+    for item in category {
+        template.add_category(item.as_str());
     }
+    for item in points {
+        template.add_point(item.as_str());
+    }
+    match template.render() {
+        Ok(yaml) => print!("{}", yaml),
+        Err(mut err) => {
+            err.sort();
+            println!("{} errors.", err.len());
+            for item in err.iter() {
+                println!("{}", item)
+            }
+            process::exit(1);
+        }
+    }
+}
+
+fn format(store: &DataStore, check: bool) {
+    let formatted = match format_files(store, check) {
+        Ok(formatted) => formatted,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
+    for path in &formatted.skipped {
+        println!(
+            "{}: not formatted, uses aliases, merges, or variables", path
+        );
+    }
+    for path in &formatted.commented {
+        println!("{}: not formatted, contains comments", path);
+    }
+    let changed = formatted.changed;
+    for path in &changed {
+        println!("{}", path);
+    }
+    if check {
+        println!("{} files need formatting.", changed.len());
+        if !changed.is_empty() {
+            process::exit(1);
+        }
+    }
+    else {
+        println!("{} files changed.", changed.len());
+    }
+}
+
+fn rename(store: &DataStore, old: &str, new: &str, dry_run: bool) {
+    let (old_key, new_key) = match (Key::from_str(old), Key::from_str(new)) {
+        (Ok(old), Ok(new)) => (old, new),
+        _ => {
+            println!("invalid key");
+            process::exit(1);
+        }
+    };
+    let rename = match Rename::new(store, old_key, new_key) {
+        Ok(rename) => rename,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
+    for path in rename.files() {
+        println!("{}", path);
+    }
+    if !dry_run {
+        if let Err(err) = rename.apply() {
+            println!("{}", err);
+            process::exit(1);
+        }
+    }
+    if let Some(origin) = rename.merged() {
+        println!(
+            "Document '{}' at {} needs to be merged into '{}'.",
+            old, origin, new
+        );
+    }
+}
+
+fn diff(store: &DataStore, old: &Path) {
+    let old = match load_tree(old) {
+        Ok(old) => old,
+        Err(mut err) => {
+            err.sort();
+            println!("Cannot load {}: {} errors.", old.display(), err.len());
+            for item in err.iter() {
+                println!("{}", item)
+            }
+            process::exit(1);
+        }
+    };
+    let diff = Diff::new(&old, store);
+    print!("{}", diff);
     println!(
-        "{} documents:",
-        lines + entities + paths + points + sources + structures
+        "{} added, {} removed, {} changed.",
+        diff.added.len(), diff.removed.len(), diff.changed.len()
     );
-    println!("   {} lines", lines);
-    println!("   {} entities", entities);
-    println!("   {} paths", paths);
-    println!("   {} points", points);
-    println!("   {} sources", sources);
-    println!("   {} structures", structures);
+}
+
+fn bibliography(
+    store: &DataStore, format: Format, regarding: Option<&str>,
+    min: Progress, preference: &LanguagePreference,
+) {
+    let bib = match regarding {
+        Some(key) => {
+            let link = match store.get(key) {
+                Some(link) => link,
+                None => {
+                    println!("no document '{}'", key);
+                    process::exit(1);
+                }
+            };
+            Bibliography::regarding(store, link, preference)
+        }
+        None => Bibliography::all(store, preference)
+    }.with_min_progress(min);
+    print!("{}", bib.render(format));
+    if format == Format::CslJson {
+        println!();
+    }
+}
+
+/// Serves the data until an error happens.
+///
+/// The data is loaded again in the background whenever a reload is
+/// requested through the admin endpoint or, if watching is enabled, a
+/// file changes. If that fails, the errors are printed and the old data
+/// continues to be served.
+#[cfg(feature = "html")]
+fn serve(
+    options: LoadOptions,
+    store: FullStore,
+    catalogue: Catalogue,
+    min_progress: Progress,
+    preference: LanguagePreference,
+    args: &ServeArgs,
+) {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use raildata::html::{Logger, Server, Snapshots, State};
+
+    let path = options.path.clone();
+    let state = Arc::new(State::new(store, catalogue).with_loader(move || {
+        println!("Reloading data.");
+        let res = reload(&options);
+        if res.is_some() {
+            println!("Reloaded data.");
+        }
+        res
+    }));
+    if let Some(secs) = args.watch {
+        state.watch(path, Duration::from_secs(secs));
+    }
+    let mut server = Server::new(state, preference).with_logger(
+        Logger::new(args.log_format, args.log_level)
+    ).with_workers(args.workers).with_min_progress(min_progress);
+    match args.token() {
+        Ok(Some(token)) => server = server.with_token(token),
+        Ok(None) => { }
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    }
+    if let Some(snapshots) = args.snapshots.as_ref() {
+        server = server.with_snapshots(Snapshots::new(snapshots));
+    }
+    println!("Listening on http://{}/", args.listen);
+    if let Err(err) = server.serve(args.listen.as_str()) {
+        println!("{}", err);
+        process::exit(1);
+    }
+}
+
+/// Loads the data again for serving.
+///
+/// The data is loaded and validated the same way as initially. Returns
+/// `None` after printing the problems if that fails.
+#[cfg(feature = "html")]
+fn reload(options: &LoadOptions) -> Option<raildata::html::Loaded> {
+    let progress = TerminalProgress::new();
+    let observer: &dyn LoadObserver = if options.progress {
+        &progress
+    }
+    else {
+        &()
+    };
+    let res = options.data_store(observer).map_err(Some).and_then(|store| {
+        let validator = options.validator().map_err(|err| {
+            println!("{}", err);
+            None
+        })?;
+        let warnings = validator.run(&store);
+        if warnings.iter().any(|item| item.severity() != Severity::Warning) {
+            return Err(Some(warnings))
+        }
+        options.full_store(store, observer).map_err(Some)
+    }).and_then(|store| {
+        let catalogue = options.catalogue(&store, observer).map_err(Some)?;
+        Ok(raildata::html::Loaded::new(store, catalogue))
+    });
+    match res {
+        Ok(res) => Some(res),
+        Err(err) => {
+            if let Some(mut err) = err {
+                err.sort();
+                println!("{} errors. Keeping the old data.", err.len());
+                for item in err.iter() {
+                    println!("{}", item)
+                }
+            }
+            else {
+                println!("Keeping the old data.");
+            }
+            None
+        }
+    }
 }
 
 fn main() {
     let args = Args::parse();
 
+    if let Some(Command::Schema) = args.command {
+        println!("{:#}", Schema::new().to_json());
+        return
+    }
+    if let Some(Command::New {
+        ref key, doctype, ref subtype, ref name, ref category, ref points
+    }) = args.command {
+        new_document(
+            key, doctype, subtype.as_deref(), name.as_deref(), category,
+            points
+        );
+        return
+    }
+
+    if let Some(jobs) = args.jobs {
+        // Generating metadata and the catalogue use the global pool.
+        let _ = rayon::ThreadPoolBuilder::new().num_threads(
+            jobs
+        ).build_global();
+    }
+
+    let progress = TerminalProgress::new();
+    let observer: &dyn LoadObserver = if args.no_progress {
+        &()
+    }
+    else {
+        &progress
+    };
+
+    if args.force && matches!(
+        args.command, Some(Command::Fmt { .. } | Command::Rename { .. })
+    ) {
+        println!("cannot rewrite files with --force");
+        process::exit(1);
+    }
+
+    let options = args.load_options();
     let time = Instant::now();
-    let store = match load_tree(&args.path) {
+    let store = match options.data_store(observer) {
         Ok(store) => store,
         Err(mut err) => {
             err.sort();
@@ -85,10 +950,105 @@ fn main() {
             "Parsing: {:.3} s",
             Instant::now().duration_since(time).as_secs_f32()
         );
+        println!("Strings: {}", Text::pool_stats());
+    }
+    match args.command {
+        Some(Command::Fmt { check }) => {
+            format(&store, check);
+            return
+        }
+        Some(Command::Rename { ref old, ref new, dry_run }) => {
+            rename(&store, old, new, dry_run);
+            return
+        }
+        Some(Command::Diff { ref old }) => {
+            diff(&store, old);
+            return
+        }
+        Some(Command::Changes { since }) => {
+            println!("{:#}", ChangeFeed::since(&store, since).to_json());
+            return
+        }
+        Some(Command::Deprecated) => {
+            print_deprecated_files(&store);
+            return
+        }
+        Some(Command::Bib { format, ref regarding }) => {
+            bibliography(
+                &store, format, regarding.as_deref(), args.min_progress(),
+                &args.preference()
+            );
+            return
+        }
+        _ => { }
+    }
+    let validator = match options.validator() {
+        Ok(validator) => validator,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    };
+    let mut warnings = validator.run(&store);
+    if !warnings.is_empty() {
+        warnings.sort();
+        let errors = warnings.iter().filter(|item| {
+            item.severity() != Severity::Warning
+        }).count();
+        if errors > 0 {
+            println!(
+                "{} errors and {} warnings.",
+                errors, warnings.len() - errors
+            );
+        }
+        else {
+            println!("{} warnings.", warnings.len());
+        }
+        for item in warnings.iter() {
+            println!("{}", item)
+        }
+        if errors > 0 {
+            process::exit(1);
+        }
+    }
+    if !store.deprecated().is_empty() {
+        println!("{} deprecated values.", store.deprecated().len());
+        for (origin, value) in store.deprecated() {
+            println!("{}: {}", origin, value)
+        }
+    }
+    let attachments = match Attachments::load(&args.path) {
+        Ok(attachments) => attachments,
+        Err(err) => {
+            println!("cannot read attachments: {}", err);
+            process::exit(1);
+        }
+    };
+    let mut problems = attachments.check(&store);
+    if !problems.is_empty() {
+        problems.sort();
+        println!("{} attachment problems.", problems.len());
+        for item in problems.iter() {
+            println!("{}", item)
+        }
+        if problems.iter().any(|item| item.severity() != Severity::Warning) {
+            process::exit(1);
+        }
+    }
+    #[cfg(feature = "linkcheck")]
+    if args.check_urls {
+        let dead = LinkChecker::new().check(&store);
+        if !dead.is_empty() {
+            println!(
+                "{} dead links in {} sources.",
+                dead.len(), dead.source_count()
+            );
+            print!("{}", dead);
+        }
     }
     if args.quick {
-        if args.verbose {
-            print_stats(&store);
+        if args.verbose || args.json {
+            print_stats(&store, args.min_progress(), args.json);
         }
         else {
             println!("Ok.");
@@ -96,7 +1056,7 @@ fn main() {
         process::exit(1);
     }
 
-    let store = match store.into_full_store() {
+    let store = match options.full_store(store, observer) {
         Ok(store) => store,
         Err(mut err) => {
             err.sort();
@@ -108,8 +1068,7 @@ fn main() {
         }
     };
 
-    #[allow(unused_variables)]
-    let catalogue = match Catalogue::generate(&store) {
+    let catalogue = match options.catalogue(&store, observer) {
         Ok(catalogue) => catalogue,
         Err(mut err) => {
             err.sort();
@@ -120,11 +1079,186 @@ fn main() {
             process::exit(1);
         }
     };
+    if args.force {
+        print_skipped(store.as_ref());
+    }
+    let view = store.with_min_progress(args.min_progress());
+
+    match args.command {
+        Some(Command::On { month, day }) => {
+            println!(
+                "{:#}", catalogue.events_on_to_json(
+                    month, day, &args.preference(), &store
+                )
+            );
+            return
+        }
+        Some(Command::Country { code }) => {
+            match catalogue.country_summary(code) {
+                Some(summary) => {
+                    println!(
+                        "{:#}",
+                        summary.to_json(
+                            code, &catalogue, &args.preference(), &store
+                        )
+                    );
+                }
+                None => {
+                    println!("no documents for country '{}'", code);
+                    process::exit(1);
+                }
+            }
+            return
+        }
+        Some(Command::Sources { ref key }) => {
+            let source = match store.get(key.as_str()) {
+                Some(link) => link.document(&store).try_as_source(),
+                None => None
+            };
+            match source {
+                Some(source) => {
+                    println!("{:#}", source.root(&store).tree_to_json(&store))
+                }
+                None => {
+                    println!("no source '{}'", key);
+                    process::exit(1);
+                }
+            }
+            return
+        }
+        Some(Command::Get { ref keys, detail }) => {
+            let documents = DocumentSet::resolve(
+                view, keys.iter().map(String::as_str)
+            );
+            println!(
+                "{:#}",
+                documents.to_json(detail, &args.preference(), &store)
+            );
+            return
+        }
+        Some(Command::Search { ref text, prefix, distance }) => {
+            let found = catalogue.search(text, prefix, distance, &store);
+            println!(
+                "{:#}",
+                JsonValue::from(found.iter().map(|item| {
+                    item.to_json(&store)
+                }).collect::<Vec<_>>())
+            );
+            return
+        }
+        Some(Command::Query { ref query, detail }) => {
+            let found = query.run(view);
+            match detail {
+                Some(detail) => {
+                    let documents = DocumentSet::resolve(
+                        view,
+                        found.iter().map(|link| {
+                            link.data(&store).key().as_str()
+                        })
+                    );
+                    println!(
+                        "{:#}",
+                        documents.to_json(
+                            detail, &args.preference(), &store
+                        )
+                    );
+                }
+                None => {
+                    for link in found {
+                        println!("{}", link.data(&store).key())
+                    }
+                }
+            }
+            return
+        }
+        Some(Command::Duplicates { json }) => {
+            let duplicates = Duplicates::find(&store, &args.preference());
+            if json {
+                println!("{:#}", duplicates.to_json());
+            }
+            else {
+                print!("{}", duplicates);
+                println!("{} candidates.", duplicates.len());
+            }
+            return
+        }
+        Some(Command::Coverage { json }) => {
+            let coverage = CourseCoverage::generate(store.as_ref());
+            if json {
+                println!("{:#}", coverage.to_json());
+            }
+            else {
+                print!("{}", coverage);
+            }
+            return
+        }
+        Some(Command::Railml { country }) => {
+            let preference = args.preference();
+            let export = match country {
+                Some(country) => {
+                    Railml::country(view, country, &preference)
+                }
+                None => Railml::all(view, &preference),
+            };
+            if export.is_empty() {
+                println!("no lines or points to export");
+                process::exit(1);
+            }
+            print!("{}", export.render());
+            return
+        }
+        Some(Command::Sqlite) => {
+            let preference = args.preference();
+            print!("{}", SqlDump::new(view, &preference).render());
+            return
+        }
+        Some(Command::Release { ref version, ref output }) => {
+            let release = Release::new(
+                view, &catalogue, version.as_str(), args.preference()
+            );
+            match release.write(output) {
+                Ok(dir) => println!("Release written to {}.", dir.display()),
+                Err(err) => {
+                    println!("cannot write release: {}", err);
+                    process::exit(1);
+                }
+            }
+            return
+        }
+        #[cfg(feature = "html")]
+        Some(Command::Serve(ref serve_args)) => {
+            serve(
+                options, store, catalogue, args.min_progress(),
+                args.preference(), serve_args
+            );
+            return
+        }
+        Some(Command::Repl) => {
+            let session = Session::new(
+                &store, &catalogue, args.preference()
+            );
+            let stdin = io::stdin();
+            let prompt = stdin.is_terminal();
+            if let Err(err) = session.run(
+                stdin.lock(), &mut io::stdout(), prompt
+            ) {
+                println!("{}", err);
+                process::exit(1);
+            }
+            return
+        }
+        _ => { }
+    }
+
+    if args.json {
+        print_stats(store.as_ref(), args.min_progress(), true);
+        return
+    }
 
     println!("Ok.");
     if args.verbose {
         let time = Instant::now().duration_since(time);
         println!("Total: {:.3} s.", time.as_secs_f32());
-        print_stats(store.as_ref());
+        print_stats(store.as_ref(), args.min_progress(), false);
     }
 }
@@ -0,0 +1,316 @@
+//! Filtering documents with simple queries.
+//!
+//! A [`Query`] selects the documents of a store that match all of its
+//! terms. Terms are separated by white space and consist of a field and a
+//! value separated by a colon, for instance:
+//!
+//! ```text
+//! type:line country:de status:open electrified:15kv
+//! ```
+//!
+//! The following fields are available:
+//!
+//! * `type:<type>` matches documents of the given type,
+//! * `country:<code>` matches documents whose key has the country code,
+//! * `key:<prefix>` matches documents whose key starts with the prefix,
+//! * `progress:<progress>` matches documents with the given progress,
+//! * `status:<status>` matches lines with a section currently having the
+//!   status and points currently having it, and
+//! * `electrified:<system>` matches lines with a section currently
+//!   electrified with the system. The system is either a named or generic
+//!   system, a voltage such as `15kv` or `750v`, or `none`.
+//!
+//! Fields other than `type`, `country`, `key`, and `progress` only match
+//! document types that have them.
+
+use std::fmt;
+use std::str::FromStr;
+use derive_more::Display;
+use crate::document::combined::Data;
+use crate::document::common::{DocumentType, Progress};
+use crate::document::line;
+use crate::store::{DocumentLink, ProgressView};
+use crate::types::CountryCode;
+
+
+//------------ Query ---------------------------------------------------------
+
+/// A parsed query.
+#[derive(Clone, Debug, Default)]
+pub struct Query {
+    /// The filters all of which a document has to match.
+    filters: Vec<Filter>,
+}
+
+impl Query {
+    /// Returns whether the query has no terms and matches everything.
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Returns whether a document matches the query.
+    pub fn matches(&self, data: &Data) -> bool {
+        self.filters.iter().all(|filter| filter.matches(data))
+    }
+
+    /// Returns the documents of the store matching the query.
+    ///
+    /// The documents are ordered by key.
+    pub fn run<'a>(
+        &self, store: impl Into<ProgressView<'a>>
+    ) -> Vec<DocumentLink> {
+        let view = store.into();
+        let store = view.store();
+        let mut res: Vec<_> = view.links().filter(|link| {
+            self.matches(link.data(store))
+        }).collect();
+        res.sort_by_key(|link| link.data(store).key());
+        res
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split_whitespace().map(Filter::from_str).collect::<Result<_, _>>()
+            .map(|filters| Query { filters })
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, filter) in self.filters.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(" ")?;
+            }
+            filter.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ Filter --------------------------------------------------------
+
+/// A single term of a query.
+#[derive(Clone, Debug)]
+enum Filter {
+    Type(DocumentType),
+    Country(CountryCode),
+    Key(String),
+    Progress(Progress),
+    Status(line::Status),
+    Electrified(ElFilter),
+}
+
+impl Filter {
+    fn matches(&self, data: &Data) -> bool {
+        match *self {
+            Filter::Type(doctype) => data.doctype() == doctype,
+            Filter::Country(country) => {
+                data.key().country().and_then(|code| {
+                    CountryCode::from_str(code).ok()
+                }) == Some(country)
+            }
+            Filter::Key(ref prefix) => data.key().starts_with(prefix.as_str()),
+            Filter::Progress(progress) => {
+                data.common().progress.into_value() == progress
+            }
+            Filter::Status(status) => {
+                if let Some(line) = data.try_as_line() {
+                    line.current.status.iter().any(|(_, item)| {
+                        *item == status
+                    })
+                }
+                else if let Some(point) = data.try_as_point() {
+                    point.status().as_str() == status.as_str()
+                }
+                else {
+                    false
+                }
+            }
+            Filter::Electrified(ref filter) => {
+                data.try_as_line().map(|line| {
+                    line.current.electrified.iter().any(|(_, item)| {
+                        filter.matches(item.as_ref())
+                    })
+                }).unwrap_or(false)
+            }
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (field, value) = match s.split_once(':') {
+            Some((field, value)) if !value.is_empty() => (field, value),
+            _ => return Err(QueryError::InvalidTerm(s.into()))
+        };
+        let invalid = || QueryError::InvalidValue {
+            field: field.into(), value: value.into()
+        };
+        match field {
+            "type" => {
+                DocumentType::ALL.iter().copied().find(|item| {
+                    item.as_str() == value
+                }).map(Filter::Type).ok_or_else(invalid)
+            }
+            "country" => {
+                CountryCode::from_str(value).map(Filter::Country).map_err(
+                    |_| invalid()
+                )
+            }
+            "key" => Ok(Filter::Key(value.into())),
+            "progress" => {
+                Progress::ALL.iter().copied().find(|item| {
+                    item.as_str() == value
+                }).map(Filter::Progress).ok_or_else(invalid)
+            }
+            "status" => {
+                line::Status::ALL.iter().copied().find(|item| {
+                    item.as_str() == value
+                }).map(Filter::Status).ok_or_else(invalid)
+            }
+            "electrified" => {
+                ElFilter::from_str(value).map(Filter::Electrified).map_err(
+                    |_| invalid()
+                )
+            }
+            _ => Err(QueryError::UnknownField(field.into()))
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Filter::Type(doctype) => write!(f, "type:{}", doctype.as_str()),
+            Filter::Country(country) => {
+                write!(f, "country:{}", country.as_str().to_lowercase())
+            }
+            Filter::Key(ref prefix) => write!(f, "key:{}", prefix),
+            Filter::Progress(progress) => {
+                write!(f, "progress:{}", progress.as_str())
+            }
+            Filter::Status(status) => write!(f, "status:{}", status.as_str()),
+            Filter::Electrified(ref filter) => {
+                write!(f, "electrified:{}", filter)
+            }
+        }
+    }
+}
+
+
+//------------ ElFilter ------------------------------------------------------
+
+/// The value of an `electrified` term.
+#[derive(Clone, Debug)]
+enum ElFilter {
+    /// Matches lines that aren’t electrified.
+    None,
+
+    /// Matches systems with the given voltage in volts.
+    Voltage(u16),
+
+    /// Matches a named or generic system by its name.
+    System(String),
+}
+
+impl ElFilter {
+    /// Returns whether the electrification of a section matches.
+    ///
+    /// The electrification is `None` if it isn’t known.
+    fn matches(
+        &self, electrified: Option<&crate::types::Set<
+            crate::types::Marked<line::Electrified>
+        >>
+    ) -> bool {
+        let electrified = match electrified {
+            Some(electrified) => electrified,
+            None => return false
+        };
+        match *self {
+            ElFilter::None => {
+                electrified.iter().all(|item| {
+                    item.named().is_none() && item.generic().is_none()
+                })
+            }
+            ElFilter::Voltage(voltage) => {
+                electrified.iter().any(|item| {
+                    item.generic().map(|generic| generic.voltage)
+                        == Some(voltage)
+                })
+            }
+            ElFilter::System(ref system) => {
+                electrified.iter().any(|item| {
+                    item.to_string() == *system
+                        || item.generic().map(|generic| {
+                            generic.to_string() == *system
+                        }).unwrap_or(false)
+                })
+            }
+        }
+    }
+}
+
+impl FromStr for ElFilter {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "none" {
+            return Ok(ElFilter::None)
+        }
+        let lower = s.to_ascii_lowercase();
+        if let Some(kv) = lower.strip_suffix("kv") {
+            let kv = u16::from_str(kv).map_err(|_| "invalid voltage")?;
+            return kv.checked_mul(1000).map(ElFilter::Voltage).ok_or(
+                "invalid voltage"
+            )
+        }
+        if let Some(v) = lower.strip_suffix('v') {
+            if let Ok(v) = u16::from_str(v) {
+                return Ok(ElFilter::Voltage(v))
+            }
+        }
+        Ok(ElFilter::System(s.into()))
+    }
+}
+
+impl fmt::Display for ElFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ElFilter::None => f.write_str("none"),
+            ElFilter::Voltage(voltage) => {
+                if voltage % 1000 == 0 {
+                    write!(f, "{}kv", voltage / 1000)
+                }
+                else {
+                    write!(f, "{}v", voltage)
+                }
+            }
+            ElFilter::System(ref system) => f.write_str(system),
+        }
+    }
+}
+
+
+//============ Errors ========================================================
+
+#[derive(Clone, Debug, Display)]
+pub enum QueryError {
+    #[display(fmt="invalid term '{}', expected 'field:value'", _0)]
+    InvalidTerm(String),
+
+    #[display(fmt="unknown field '{}'", _0)]
+    UnknownField(String),
+
+    #[display(fmt="invalid value '{}' for field '{}'", value, field)]
+    InvalidValue {
+        field: String,
+        value: String,
+    },
+}
+
@@ -0,0 +1,400 @@
+//! Export of lines and points as railML.
+//!
+//! A [`Railml`] export writes a pragmatic subset of the railML 2.4
+//! infrastructure schema for use with simulation and planning tools. Each
+//! line becomes a track and each point an operation control point. The
+//! points of a line are attached to its track as cross sections and the
+//! current gauge and electrification become gauge and electrification
+//! changes.
+//!
+//! Positions along a track are derived from the kilometrage of the points
+//! on the line. Points with an unknown kilometrage are left out of the
+//! track, as are changes starting at them. Lines with several tracks are
+//! still exported as a single track.
+//!
+//! The license and attribution of the data as well as those of exported
+//! documents that have their own are included as Dublin Core rights in
+//! the metadata.
+
+use std::fmt::Write;
+use std::str::FromStr;
+use crate::document::{line, point};
+use crate::document::common::Attribution;
+use crate::document::line::{AcDc, ElSystem, GenericEl};
+use crate::store::{DataStore, FullStore, ProgressView};
+use crate::types::{CountryCode, Key, LanguagePreference};
+
+
+//------------ Railml --------------------------------------------------------
+
+/// The lines and points to export.
+#[derive(Clone, Debug)]
+pub struct Railml<'a> {
+    /// The store the documents live in.
+    store: &'a FullStore,
+
+    /// The lines ordered by key.
+    lines: Vec<&'a line::Data>,
+
+    /// The points ordered by key.
+    points: Vec<&'a point::Data>,
+
+    /// The preferred languages for names.
+    preference: &'a LanguagePreference,
+}
+
+impl<'a> Railml<'a> {
+    /// The railML version written.
+    pub const VERSION: &'static str = "2.4";
+
+    /// Creates an export of all lines and points in the store.
+    pub fn all(
+        store: impl Into<ProgressView<'a>>,
+        preference: &'a LanguagePreference,
+    ) -> Self {
+        Self::filtered(store.into(), preference, |_| true)
+    }
+
+    /// Creates an export of the lines and points of a country.
+    ///
+    /// The country is determined from the document keys.
+    pub fn country(
+        store: impl Into<ProgressView<'a>>,
+        country: CountryCode,
+        preference: &'a LanguagePreference,
+    ) -> Self {
+        Self::filtered(store.into(), preference, |key| {
+            key.country().and_then(|code| {
+                CountryCode::from_str(code).ok()
+            }) == Some(country)
+        })
+    }
+
+    fn filtered(
+        view: ProgressView<'a>,
+        preference: &'a LanguagePreference,
+        op: impl Fn(&Key) -> bool,
+    ) -> Self {
+        let store = view.store();
+        let mut lines = Vec::new();
+        let mut points = Vec::new();
+        for link in view.links() {
+            let data = link.data(store);
+            if !op(data.key()) {
+                continue
+            }
+            if let Some(line) = data.try_as_line() {
+                lines.push(line)
+            }
+            else if let Some(point) = data.try_as_point() {
+                points.push(point)
+            }
+        }
+        lines.sort_by(|left, right| left.key().cmp(right.key()));
+        points.sort_by(|left, right| left.key().cmp(right.key()));
+        Railml { store, lines, points, preference }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty() && self.points.is_empty()
+    }
+
+    /// Returns the export as a railML document.
+    pub fn render(&self) -> String {
+        let mut res = String::new();
+        res.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            res,
+            "<railml xmlns=\"https://www.railml.org/schemas/2018\" \
+             xmlns:dc=\"http://purl.org/dc/elements/1.1/\" \
+             version=\"{}\">",
+            Self::VERSION
+        );
+        self.write_metadata(&mut res);
+        res.push_str("  <infrastructure id=\"inf\">\n");
+        res.push_str("    <tracks>\n");
+        for line in &self.lines {
+            self.write_track(line, &mut res);
+        }
+        res.push_str("    </tracks>\n");
+        res.push_str("    <operationControlPoints>\n");
+        for point in &self.points {
+            self.write_ocp(point, &mut res);
+        }
+        res.push_str("    </operationControlPoints>\n");
+        res.push_str("  </infrastructure>\n");
+        res.push_str("</railml>\n");
+        res
+    }
+
+    fn write_metadata(&self, target: &mut String) {
+        let ds: &DataStore = self.store.as_ref();
+        let documents = self.lines.iter().map(|line| {
+            (line.key(), line.common.attribution.as_ref())
+        }).chain(self.points.iter().map(|point| {
+            (point.key(), point.common.attribution.as_ref())
+        })).filter_map(|(key, attribution)| Some((key, attribution?)));
+        let mut rights = Vec::new();
+        if !ds.attribution().is_empty() {
+            rights.push(rights_text(ds.attribution()));
+        }
+        for (key, attribution) in documents {
+            rights.push(format!("{}: {}", key, rights_text(attribution)));
+        }
+        if rights.is_empty() {
+            return
+        }
+        target.push_str("  <metadata>\n");
+        for item in rights {
+            let _ = writeln!(
+                target, "    <dc:rights>{}</dc:rights>", escape(&item)
+            );
+        }
+        target.push_str("  </metadata>\n");
+    }
+
+    fn write_track(&self, line: &line::Data, target: &mut String) {
+        let id = xml_id(line.key());
+        let positions = Positions::new(line, self.store);
+        let _ = writeln!(
+            target,
+            "      <track id=\"{}\" name=\"{}\" code=\"{}\" \
+             type=\"mainTrack\">",
+            id,
+            escape(line.preferred_name(self.preference)),
+            escape(line.code().as_str()),
+        );
+        target.push_str("        <trackTopology>\n");
+        let (begin, end) = positions.bounds();
+        for (kind, pos) in [("trackBegin", begin), ("trackEnd", end)] {
+            let _ = write!(
+                target,
+                "          <{kind} id=\"{id}_{kind}\" pos=\"{}\"",
+                pos.map(|pos| pos.0).unwrap_or(0.)
+            );
+            if let Some((_, abs)) = pos {
+                let _ = write!(target, " absPos=\"{}\"", abs);
+            }
+            let _ = writeln!(
+                target,
+                ">\n            <openEnd id=\"{id}_{kind}_end\"/>\n          \
+                 </{kind}>"
+            );
+        }
+        let sections = line.points.iter().enumerate().filter_map(
+            |(idx, point)| positions.get(idx).map(|pos| (idx, point, pos))
+        ).collect::<Vec<_>>();
+        if !sections.is_empty() {
+            target.push_str("          <crossSections>\n");
+            for (idx, point, (pos, abs)) in sections {
+                let _ = writeln!(
+                    target,
+                    "            <crossSection id=\"{id}_cs{idx}\" \
+                     pos=\"{pos}\" absPos=\"{abs}\" ocpRef=\"{}\"/>",
+                    xml_id(point.data(self.store).key()),
+                );
+            }
+            target.push_str("          </crossSections>\n");
+        }
+        target.push_str("        </trackTopology>\n");
+
+        let mut elements = String::new();
+        let gauges = line.current.gauge.iter().filter_map(
+            |(section, gauges)| {
+                positions.get(section.start_idx).map(|pos| (pos, gauges))
+            }
+        ).collect::<Vec<_>>();
+        if !gauges.is_empty() {
+            elements.push_str("          <gaugeChanges>\n");
+            for (idx, ((pos, abs), gauges)) in gauges.iter().enumerate() {
+                for gauge in gauges.iter() {
+                    let _ = writeln!(
+                        elements,
+                        "            <gaugeChange id=\"{id}_gc{idx}_{}\" \
+                         pos=\"{pos}\" absPos=\"{abs}\" value=\"{}\"/>",
+                        gauge.gauge(), gauge.gauge()
+                    );
+                }
+            }
+            elements.push_str("          </gaugeChanges>\n");
+        }
+        let electrified = line.current.electrified.iter().filter_map(
+            |(section, systems)| {
+                positions.get(section.start_idx).map(|pos| (pos, systems))
+            }
+        ).collect::<Vec<_>>();
+        if !electrified.is_empty() {
+            elements.push_str("          <electrificationChanges>\n");
+            for (idx, ((pos, abs), systems)) in electrified.iter().enumerate()
+            {
+                let _ = write!(
+                    elements,
+                    "            <electrificationChange \
+                     id=\"{id}_ec{idx}\" pos=\"{pos}\" absPos=\"{abs}\""
+                );
+                let system = systems.as_ref().and_then(|systems| {
+                    systems.iter().find_map(|system| system.generic())
+                });
+                match (systems, system) {
+                    (None, _) => elements.push_str(" type=\"none\""),
+                    (Some(_), Some(system)) => {
+                        write_el(system, &mut elements)
+                    }
+                    (Some(_), None) => { }
+                }
+                elements.push_str("/>\n");
+            }
+            elements.push_str("          </electrificationChanges>\n");
+        }
+        if !elements.is_empty() {
+            target.push_str("        <trackElements>\n");
+            target.push_str(&elements);
+            target.push_str("        </trackElements>\n");
+        }
+        target.push_str("      </track>\n");
+    }
+
+    fn write_ocp(&self, point: &point::Data, target: &mut String) {
+        let meta = point.link().meta(self.store);
+        let _ = writeln!(
+            target,
+            "      <ocp id=\"{}\" name=\"{}\">",
+            xml_id(point.key()),
+            escape(point.preferred_name(self.preference)),
+        );
+        let mut codes = meta.current.codes.iter().flat_map(|(code, values)| {
+            values.map(move |value| (code, value))
+        }).collect::<Vec<_>>();
+        codes.sort();
+        for (code, value) in codes {
+            let _ = writeln!(
+                target,
+                "        <designator register=\"{}\" entry=\"{}\"/>",
+                escape(code.as_str()), escape(value)
+            );
+        }
+        if let Some(coord) = meta.coord {
+            let _ = writeln!(
+                target,
+                "        <geoCoord coord=\"{} {}\" epsgCode=\"4326\"/>",
+                coord.lat, coord.lon
+            );
+        }
+        target.push_str("      </ocp>\n");
+    }
+}
+
+
+//------------ Positions -----------------------------------------------------
+
+/// The positions of the points of a line along its track.
+struct Positions {
+    points: Vec<Option<Position>>,
+}
+
+/// The distance from the start of the track and the kilometrage in metres.
+type Position = (f64, f64);
+
+impl Positions {
+    fn new(line: &line::Data, store: &FullStore) -> Self {
+        let abs = line.points.iter().map(|point| {
+            point.data(store).line_location(line.link()).and_then(
+                |(location, _)| location
+            ).and_then(line::parse_kilometrage).map(|km| {
+                (km * 1000.).round()
+            })
+        }).collect::<Vec<_>>();
+        let start = abs.iter().flatten().next().copied();
+        Positions {
+            points: abs.into_iter().map(|abs| {
+                let abs = abs?;
+                Some(((abs - start?).abs(), abs))
+            }).collect()
+        }
+    }
+
+    fn get(&self, idx: usize) -> Option<Position> {
+        self.points.get(idx).copied().flatten()
+    }
+
+    /// Returns the positions of the start and end of the track.
+    ///
+    /// These are the known positions closest to and farthest from the
+    /// start.
+    fn bounds(&self) -> (Option<Position>, Option<Position>) {
+        let known = || self.points.iter().flatten().copied();
+        (
+            known().min_by(|left, right| left.0.total_cmp(&right.0)),
+            known().max_by(|left, right| left.0.total_cmp(&right.0)),
+        )
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Writes the attributes of an electrification system.
+fn write_el(system: GenericEl, target: &mut String) {
+    let kind = match system.system {
+        ElSystem::Ole => "overhead",
+        ElSystem::Rail => "3rdRail",
+        ElSystem::Rail4 => "4thRail",
+    };
+    let frequency = match system.frequency {
+        AcDc::Ac16 => "16.7",
+        AcDc::Ac25 => "25",
+        AcDc::Ac50 | AcDc::Tc50 => "50",
+        AcDc::Dc => "0",
+    };
+    let _ = write!(
+        target,
+        " type=\"{}\" voltage=\"{}\" frequency=\"{}\"",
+        kind, system.voltage, frequency
+    );
+}
+
+/// Returns the content of a Dublin Core rights element.
+fn rights_text(attribution: &Attribution) -> String {
+    let mut res = Vec::new();
+    if let Some(license) = attribution.license.as_ref() {
+        res.push(license.as_str())
+    }
+    if let Some(text) = attribution.text.as_ref() {
+        res.push(text.as_str())
+    }
+    if let Some(url) = attribution.url.as_ref() {
+        res.push(url.as_str())
+    }
+    res.join(", ")
+}
+
+/// Converts a key into a valid XML identifier.
+///
+/// Keys mostly consist of characters allowed in identifiers already. All
+/// others are replaced by an underscore.
+fn xml_id(key: &Key) -> String {
+    key.as_str().chars().map(|ch| {
+        if ch.is_alphanumeric() || matches!(ch, '.' | '-' | '_') {
+            ch
+        }
+        else {
+            '_'
+        }
+    }).collect()
+}
+
+/// Escapes text for use in an XML attribute value.
+fn escape(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            _ => res.push(ch),
+        }
+    }
+    res
+}
+
@@ -0,0 +1,239 @@
+//! Renaming and merging documents.
+//!
+//! A [`Rename`] changes the key of a document and updates all references
+//! to it. The places to change are taken from the origins of the links
+//! recorded by the store, and the YAML files are edited in place so that
+//! their formatting and comments are preserved. Links given through a
+//! variable are changed in the variables file rather than where the
+//! variable is used.
+//!
+//! If the new key already belongs to a document of the same type, the two
+//! documents are merged: all references are redirected to the existing
+//! document while the old document itself is left in place so that its
+//! content can be merged by hand.
+
+use std::{fs, io};
+use std::collections::BTreeMap;
+use derive_more::Display;
+use crate::document::common::DocumentType;
+use crate::load::report::{Origin, Path};
+use crate::store::DataStore;
+use crate::types::{Key, Location};
+
+
+//------------ Rename --------------------------------------------------------
+
+/// A prepared rename of a document key.
+#[derive(Clone, Debug)]
+pub struct Rename {
+    /// The current key of the document.
+    old: Key,
+
+    /// The key the document will have afterwards.
+    new: Key,
+
+    /// The locations of the old key, grouped by file.
+    edits: BTreeMap<Path, Vec<Location>>,
+
+    /// The origin of the old document if it is merged into another one.
+    merged: Option<Origin>,
+}
+
+impl Rename {
+    /// Prepares renaming the document `old` to `new`.
+    pub fn new(
+        store: &DataStore, old: Key, new: Key
+    ) -> Result<Self, RefactorError> {
+        let link = match store.get(&old) {
+            Some(link) => link,
+            None => return Err(RefactorError::Missing(old))
+        };
+        let data = link.data(store);
+        if data.doctype() == DocumentType::Path {
+            return Err(RefactorError::Unsupported(data.doctype()))
+        }
+        let merge = match store.get(&new) {
+            Some(target) if target == link => {
+                return Err(RefactorError::Unchanged(old))
+            }
+            Some(target) => {
+                let doctype = target.data(store).doctype();
+                if doctype != data.doctype() {
+                    return Err(RefactorError::TypeMismatch {
+                        key: new, expected: data.doctype(), found: doctype
+                    })
+                }
+                true
+            }
+            None => false
+        };
+
+        let mut edits = BTreeMap::<_, Vec<_>>::new();
+        let merged = if merge {
+            Some(data.origin().clone())
+        }
+        else {
+            edits.entry(data.origin().path().clone()).or_default().push(
+                data.common().key.location()
+            );
+            None
+        };
+        for origin in store.referrers(link) {
+            // Links in variables are edited in the variables file below.
+            if store.is_reference(origin) {
+                continue
+            }
+            edits.entry(origin.path().clone()).or_default().push(
+                origin.location()
+            );
+        }
+        if let Some(path) = store.vars().path() {
+            let locations = store.vars().find(old.as_str());
+            if !locations.is_empty() {
+                edits.entry(path.clone()).or_default().extend(locations);
+            }
+        }
+        for locations in edits.values_mut() {
+            locations.sort();
+            locations.dedup();
+        }
+        Ok(Rename { old, new, edits, merged })
+    }
+
+    pub fn old(&self) -> &Key {
+        &self.old
+    }
+
+    pub fn new_key(&self) -> &Key {
+        &self.new
+    }
+
+    /// Returns an iterator over the files that will be changed.
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.edits.keys()
+    }
+
+    /// Returns the origin of the old document if it is merged.
+    ///
+    /// The document itself is not changed by a merge and needs to be
+    /// removed manually.
+    pub fn merged(&self) -> Option<&Origin> {
+        self.merged.as_ref()
+    }
+
+    /// Applies the rename to the files.
+    ///
+    /// All edits are prepared before the first file is written, so if a
+    /// reference can’t be found, no file is changed at all.
+    pub fn apply(&self) -> Result<(), RefactorError> {
+        let mut files = Vec::new();
+        for (path, locations) in &self.edits {
+            let content = fs::read_to_string(path).map_err(|err| {
+                RefactorError::Io(path.clone(), err)
+            })?;
+            let content = self.edit(&content, locations).map_err(|loc| {
+                RefactorError::NotFound(Origin::new(path.clone(), loc))
+            })?;
+            files.push((path, content));
+        }
+        for (path, content) in files {
+            fs::write(path, content).map_err(|err| {
+                RefactorError::Io(path.clone(), err)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the old key at the given locations.
+    ///
+    /// The locations must be sorted. Returns the first location that
+    /// doesn’t contain the old key as an error.
+    fn edit(
+        &self, content: &str, locations: &[Location]
+    ) -> Result<String, Location> {
+        let mut res = String::with_capacity(content.len());
+        let mut locations = locations.iter().peekable();
+        for (idx, line) in content.split_inclusive('\n').enumerate() {
+            let mut col = 0;
+            let mut rest = line;
+            while let Some(&&loc) = locations.peek() {
+                if loc.line().map(usize::from) != Some(idx + 1) {
+                    break
+                }
+                locations.next();
+
+                // Columns count characters, not bytes.
+                let start = loc.col().map(usize::from).ok_or(loc)?;
+                let (offset, _) = rest.char_indices().nth(
+                    start.checked_sub(col).ok_or(loc)?
+                ).ok_or(loc)?;
+                let (head, tail) = rest.split_at(offset);
+                let quote = match tail.chars().next() {
+                    Some(ch @ ('"' | '\'')) => ch.len_utf8(),
+                    _ => 0
+                };
+                let tail = &tail[quote..];
+                if !tail.starts_with(self.old.as_str())
+                    || !is_key_end(&tail[self.old.len()..])
+                {
+                    return Err(loc)
+                }
+                res.push_str(head);
+                res.push_str(&rest[offset..offset + quote]);
+                res.push_str(self.new.as_str());
+                rest = &tail[self.old.len()..];
+                col = start + quote + self.old.chars().count();
+            }
+            res.push_str(rest);
+        }
+        match locations.next() {
+            Some(loc) => Err(*loc),
+            None => Ok(res)
+        }
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns whether `s` starts with something that can follow a key.
+fn is_key_end(s: &str) -> bool {
+    match s.chars().next() {
+        Some(ch) => {
+            ch.is_whitespace()
+            || matches!(ch, ':' | ',' | ']' | '}' | '"' | '\'' | '#')
+        }
+        None => true
+    }
+}
+
+
+//============ Errors ========================================================
+
+#[derive(Debug, Display)]
+pub enum RefactorError {
+    #[display(fmt="no document with key '{}'", _0)]
+    Missing(Key),
+
+    #[display(fmt="document '{}' already has this key", _0)]
+    Unchanged(Key),
+
+    #[display(
+        fmt="'{}' is a {} document, expected {}", key, found, expected
+    )]
+    TypeMismatch {
+        key: Key,
+        expected: DocumentType,
+        found: DocumentType,
+    },
+
+    #[display(fmt="{} documents can’t be renamed", _0)]
+    Unsupported(DocumentType),
+
+    #[display(fmt="{}: cannot find reference to update", _0)]
+    NotFound(Origin),
+
+    #[display(fmt="{}: {}", _0, _1)]
+    Io(Path, io::Error),
+}
+
@@ -0,0 +1,210 @@
+//! Bundling a data release.
+//!
+//! A [`Release`] writes all artifacts of a data release into a directory
+//! named after the release version:
+//!
+//! * `snapshot.json` contains all documents with all details in the
+//!   format of [`DocumentSet`],
+//! * `catalogue.json` contains the country summaries and the keys of the
+//!   documents in each key namespace,
+//! * `map.geojson` contains the lines and points as GeoJSON features,
+//! * `documents.csv` lists the key, type, name, and progress of all
+//!   documents,
+//! * `stats.json` contains the statistics of the data, and
+//! * `manifest.json` describes the release with its version, date, the
+//!   number of documents of each type, and the size and SHA-256 hash of
+//!   each of the other files.
+//!
+//! A release can be limited to documents with a minimum progress by
+//! creating it from a [`ProgressView`]. The country summaries are taken
+//! from the catalogue as is and still cover all documents.
+//!
+//! The release doesn’t check the data itself. It should only be created
+//! from a store that loaded and validated without errors.
+
+use std::{fs, io};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use serde_json::{json, Map, Value as JsonValue};
+use sha2::{Digest, Sha256};
+use crate::bulk::{Detail, DocumentSet};
+use crate::catalogue::Catalogue;
+use crate::document::common::DocumentType;
+use crate::stats::Statistics;
+use crate::store::{FullStore, ProgressView};
+use crate::tiles::features_to_geojson;
+use crate::types::{Date, LanguagePreference};
+
+
+//------------ Release -------------------------------------------------------
+
+/// The artifacts of a data release.
+pub struct Release<'a> {
+    store: &'a FullStore,
+
+    /// The documents to include.
+    view: ProgressView<'a>,
+    catalogue: &'a Catalogue,
+
+    /// The version of the release.
+    version: String,
+
+    /// The preferred languages for names.
+    preference: LanguagePreference,
+}
+
+impl<'a> Release<'a> {
+    pub fn new(
+        store: impl Into<ProgressView<'a>>,
+        catalogue: &'a Catalogue,
+        version: impl Into<String>,
+        preference: LanguagePreference,
+    ) -> Self {
+        let view = store.into();
+        Release {
+            store: view.store(), view, catalogue,
+            version: version.into(), preference
+        }
+    }
+
+    /// Writes the release into a new directory below `base`.
+    ///
+    /// The directory is named after the version and must not exist yet.
+    /// Returns the path of the directory.
+    pub fn write(&self, base: &Path) -> Result<PathBuf, io::Error> {
+        if self.version.is_empty()
+            || self.version.starts_with('.')
+            || self.version.contains(['/', '\\'])
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid release version '{}'", self.version)
+            ))
+        }
+        let dir = base.join(&self.version);
+        fs::create_dir_all(base)?;
+        fs::create_dir(&dir)?;
+
+        let mut files = Vec::new();
+        let mut write = |name: &str, content: String| {
+            let content = content.into_bytes();
+            fs::write(dir.join(name), &content)?;
+            files.push(json!({
+                "name": name,
+                "size": content.len(),
+                "sha256": hex(&Sha256::digest(&content)),
+            }));
+            Ok::<_, io::Error>(())
+        };
+        write("snapshot.json", format!("{:#}\n", self.snapshot()))?;
+        write("catalogue.json", format!("{:#}\n", self.catalogue_json()))?;
+        write("map.geojson", format!("{}\n", self.geojson()))?;
+        write("documents.csv", self.csv())?;
+        write("stats.json", format!("{:#}\n", self.stats()))?;
+
+        let manifest = json!({
+            "version": self.version,
+            "date": Date::today().to_string(),
+            "documents": self.counts(),
+            "files": files,
+        });
+        fs::write(dir.join("manifest.json"), format!("{:#}\n", manifest))?;
+        Ok(dir)
+    }
+
+    /// Returns all documents as JSON.
+    fn snapshot(&self) -> JsonValue {
+        DocumentSet::all(self.view).to_json(
+            Detail::Full, &self.preference, self.store
+        )
+    }
+
+    /// Returns the country summaries and namespaces as JSON.
+    fn catalogue_json(&self) -> JsonValue {
+        json!({
+            "countries": self.catalogue.country_summaries().map(
+                |(country, summary)| {
+                    summary.to_json(
+                        country, self.catalogue, &self.preference, self.store
+                    )
+                }
+            ).collect::<Vec<_>>(),
+            "namespaces": self.catalogue.namespaces().map(
+                |(namespace, links)| {
+                    (
+                        namespace.to_string(),
+                        links.iter().filter(|link| {
+                            self.view.contains(**link)
+                        }).map(|link| {
+                            link.data(self.store).key().as_str()
+                        }).collect::<JsonValue>()
+                    )
+                }
+            ).collect::<Map<_, _>>(),
+        })
+    }
+
+    /// Returns the lines and points as GeoJSON.
+    fn geojson(&self) -> JsonValue {
+        features_to_geojson(
+            self.view.links(), u8::MAX, &self.preference, self.store
+        )
+    }
+
+    /// Returns the list of documents as CSV.
+    fn csv(&self) -> String {
+        let mut res = String::from("key,type,name,progress\r\n");
+        for link in self.view.links() {
+            let data = link.data(self.store);
+            let _ = write!(
+                res, "{},{},{},{}\r\n",
+                csv_field(data.key().as_str()),
+                data.doctype().as_str(),
+                csv_field(data.preferred_name(&self.preference)),
+                data.common().progress.into_value().as_str(),
+            );
+        }
+        res
+    }
+
+    /// Returns the statistics as JSON.
+    fn stats(&self) -> JsonValue {
+        Statistics::generate_with_progress(
+            self.store.as_ref(), self.view.min_progress()
+        ).to_json()
+    }
+
+    /// Returns the number of documents of each type.
+    fn counts(&self) -> JsonValue {
+        DocumentType::ALL.iter().map(|&doctype| {
+            (
+                doctype.as_str().to_string(),
+                self.view.links().filter(|link| {
+                    link.data(self.store).doctype() == doctype
+                }).count().into()
+            )
+        }).collect::<Map<_, _>>().into()
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Quotes a CSV field if necessary.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    }
+    else {
+        value.into()
+    }
+}
+
+/// Returns bytes in lower case hex digits.
+fn hex(data: &[u8]) -> String {
+    let mut res = String::with_capacity(data.len() * 2);
+    for ch in data {
+        let _ = write!(res, "{:02x}", ch);
+    }
+    res
+}
@@ -0,0 +1,188 @@
+//! An interactive session on loaded data.
+//!
+//! Loading a large data tree takes a while. A [`Session`] keeps the loaded
+//! data in memory and reads commands from a prompt, so that files can be
+//! checked and documents looked up repeatedly without loading everything
+//! again. The following commands are available:
+//!
+//! * `check <file>...` loads the files and checks them against the data,
+//! * `get <key>...` prints a summary of the documents with the keys,
+//! * `keys <prefix>` lists all documents whose key starts with the prefix,
+//! * `search <text>` searches for documents by name,
+//! * `help` lists the commands, and
+//! * `quit` ends the session.
+//!
+//! Checking a file only finds problems within the file and with its links.
+//! The validation rules and cross references need all the data, so they
+//! are only checked when the complete tree is loaded.
+
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use crate::bulk::{Detail, DocumentSet};
+use crate::catalogue::Catalogue;
+use crate::load::check_file;
+use crate::store::FullStore;
+use crate::types::LanguagePreference;
+
+
+//------------ Session -------------------------------------------------------
+
+/// An interactive session on a loaded data tree.
+pub struct Session<'a> {
+    store: &'a FullStore,
+    catalogue: &'a Catalogue,
+    preference: LanguagePreference,
+}
+
+impl<'a> Session<'a> {
+    /// The maximum number of search results to show.
+    const MAX_RESULTS: usize = 20;
+
+    const HELP: &'static str = "\
+check <file>...  check files against the loaded data
+get <key>...     show a summary of documents
+keys <prefix>    list the documents whose key starts with prefix
+search <text>    search for documents by name
+help             show this list
+quit             end the session
+";
+
+    pub fn new(
+        store: &'a FullStore,
+        catalogue: &'a Catalogue,
+        preference: LanguagePreference,
+    ) -> Self {
+        Session { store, catalogue, preference }
+    }
+
+    /// Runs the session, reading commands from `input`.
+    ///
+    /// If `prompt` is `true`, a prompt is written to `output` before each
+    /// command. Returns when `input` ends or the session is quit.
+    pub fn run(
+        &self,
+        input: impl BufRead,
+        output: &mut impl Write,
+        prompt: bool,
+    ) -> Result<(), io::Error> {
+        let mut lines = input.lines();
+        loop {
+            if prompt {
+                write!(output, "> ")?;
+                output.flush()?;
+            }
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => break
+            };
+            if !self.execute(&line, output)? {
+                break
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes a single command line.
+    ///
+    /// Returns whether the session should continue.
+    pub fn execute(
+        &self, line: &str, output: &mut impl Write
+    ) -> Result<bool, io::Error> {
+        let line = line.trim();
+        let (command, args) = match line.split_once(char::is_whitespace) {
+            Some((command, args)) => (command, args.trim()),
+            None => (line, "")
+        };
+        match command {
+            "" => { }
+            "check" => self.check(args, output)?,
+            "get" => self.get(args, output)?,
+            "keys" => self.keys(args, output)?,
+            "search" => self.search(args, output)?,
+            "help" => write!(output, "{}", Self::HELP)?,
+            "quit" | "exit" => return Ok(false),
+            _ => {
+                writeln!(
+                    output, "unknown command '{}', try 'help'", command
+                )?;
+            }
+        }
+        Ok(true)
+    }
+
+    fn check(
+        &self, args: &str, output: &mut impl Write
+    ) -> Result<(), io::Error> {
+        if args.is_empty() {
+            return writeln!(output, "usage: check <file>...")
+        }
+        for path in args.split_whitespace() {
+            let mut report = check_file(Path::new(path), self.store.as_ref());
+            if report.is_empty() {
+                writeln!(output, "{}: Ok.", path)?;
+                continue
+            }
+            report.sort();
+            writeln!(output, "{}: {} errors.", path, report.len())?;
+            for item in report.iter() {
+                writeln!(output, "{}", item)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn get(
+        &self, args: &str, output: &mut impl Write
+    ) -> Result<(), io::Error> {
+        if args.is_empty() {
+            return writeln!(output, "usage: get <key>...")
+        }
+        let documents = DocumentSet::resolve(
+            self.store, args.split_whitespace()
+        );
+        writeln!(
+            output, "{:#}",
+            documents.to_json(Detail::Summary, &self.preference, self.store)
+        )
+    }
+
+    fn keys(
+        &self, prefix: &str, output: &mut impl Write
+    ) -> Result<(), io::Error> {
+        let links = self.catalogue.keys_with_prefix(prefix, self.store);
+        for link in &links {
+            writeln!(output, "{}", link.data(self.store).key())?;
+        }
+        writeln!(output, "{} documents.", links.len())
+    }
+
+    fn search(
+        &self, text: &str, output: &mut impl Write
+    ) -> Result<(), io::Error> {
+        if text.is_empty() {
+            return writeln!(output, "usage: search <text>")
+        }
+        let found = self.catalogue.search(text, true, 1, self.store);
+        if found.is_empty() {
+            return writeln!(output, "Nothing found.")
+        }
+        for item in found.iter().take(Self::MAX_RESULTS) {
+            write!(
+                output, "{} ({}): {}",
+                item.link.data(self.store).key(), item.doctype.as_str(),
+                item.name
+            )?;
+            match item.valid.and_then(|valid| valid.annotation()) {
+                Some(valid) => writeln!(output, ", {}", valid)?,
+                None => writeln!(output)?,
+            }
+        }
+        if found.len() > Self::MAX_RESULTS {
+            writeln!(
+                output, "... and {} more.", found.len() - Self::MAX_RESULTS
+            )?;
+        }
+        Ok(())
+    }
+}
+
@@ -0,0 +1,465 @@
+//! A machine-readable description of the document schema.
+//!
+//! The schema lists the attributes accepted by each document type and by
+//! the objects nested within them as well as the variants of all enums.
+//! Each document module contributes its part through a `schema` function
+//! that lives next to its YAML parsing code.
+//!
+//! Attribute values are described by a short type expression:
+//!
+//! * `string`, `integer`, `number`, `bool`, `url`, `country`: plain scalars,
+//! * `date`: an event date, i.e., a date or a list of dates,
+//! * `text`: a localized text, either a string or a mapping from language
+//!   codes to strings,
+//! * `key` or `key(type)`: the key of a document, optionally of the given
+//!   document type,
+//! * `enum(name)`: a variant of the enum with the given name,
+//! * `object(name)`: a mapping described by the object with the given name,
+//! * `list(x)`: either a single `x` or a sequence of them,
+//! * `mapping(k, v)`: a mapping with keys `k` and values `v`,
+//! * `current(x)`: either an `x` for the whole line or a mapping from the
+//!   point keys ending each section to an `x`,
+//! * `nullable(x)`: either an `x` or null,
+//! * `course`: a path key followed by the names of the start and end nodes,
+//! * `electrified`: the name of an electrification system from the
+//!   [registry][crate::document::line::electrification], a generic system
+//!   such as `ole.15000ac16`, or `none`,
+//! * `gauge`: a track gauge in millimetres, e.g., `1435mm`.
+//!
+//! Path documents are read from OSM files and are therefore not described.
+//!
+//! Objects can include the attributes of other objects. This is used for
+//! attributes common to all documents and for records that can appear
+//! directly within an event.
+
+use std::collections::BTreeMap;
+use serde_json::{json, Map, Value as JsonValue};
+use crate::document::{
+    common, custom, entity, line, point, service, source, structure,
+};
+
+
+//------------ Schema --------------------------------------------------------
+
+/// The description of the complete document schema.
+#[derive(Clone, Debug, Default)]
+pub struct Schema {
+    /// The objects by name.
+    objects: BTreeMap<&'static str, ObjectSchema>,
+
+    /// The variants of all enums by the name of the enum.
+    enums: BTreeMap<&'static str, &'static [&'static str]>,
+}
+
+impl Schema {
+    /// Creates the schema for all document types.
+    pub fn new() -> Self {
+        let mut res = Self::default();
+        common::schema(&mut res);
+        entity::schema(&mut res);
+        line::schema(&mut res);
+        point::schema(&mut res);
+        service::schema(&mut res);
+        source::schema(&mut res);
+        structure::schema(&mut res);
+        custom::schema(&mut res);
+        res
+    }
+
+    /// Adds a new document type and returns its description.
+    pub fn document(&mut self, name: &'static str) -> &mut ObjectSchema {
+        self.add_object(name, true)
+    }
+
+    /// Adds a new object and returns its description.
+    pub fn object(&mut self, name: &'static str) -> &mut ObjectSchema {
+        self.add_object(name, false)
+    }
+
+    fn add_object(
+        &mut self, name: &'static str, document: bool
+    ) -> &mut ObjectSchema {
+        let res = self.objects.entry(name).or_default();
+        res.document = document;
+        res
+    }
+
+    /// Adds an enum with the given variants.
+    pub fn enumeration(
+        &mut self, name: &'static str, variants: &'static [&'static str]
+    ) {
+        self.enums.insert(name, variants);
+    }
+
+    /// Returns the description of the object with the given name.
+    pub fn get_object(&self, name: &str) -> Option<&ObjectSchema> {
+        self.objects.get(name)
+    }
+
+    /// Returns the variants of the enum with the given name.
+    pub fn get_enum(&self, name: &str) -> Option<&'static [&'static str]> {
+        self.enums.get(name).copied()
+    }
+
+    /// Returns an iterator over the document types.
+    pub fn documents(
+        &self
+    ) -> impl Iterator<Item = (&'static str, &ObjectSchema)> + '_ {
+        self.objects().filter(|(_, object)| object.document)
+    }
+
+    /// Returns an iterator over all objects including document types.
+    pub fn objects(
+        &self
+    ) -> impl Iterator<Item = (&'static str, &ObjectSchema)> + '_ {
+        self.objects.iter().map(|(name, object)| (*name, object))
+    }
+
+    /// Returns an iterator over all enums.
+    pub fn enums(
+        &self
+    ) -> impl Iterator<Item = (&'static str, &'static [&'static str])> + '_ {
+        self.enums.iter().map(|(name, variants)| (*name, *variants))
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let mut documents = Map::new();
+        let mut objects = Map::new();
+        for (name, object) in self.objects() {
+            if object.document {
+                documents.insert(name.into(), object.to_json());
+            }
+            else {
+                objects.insert(name.into(), object.to_json());
+            }
+        }
+        let enums: Map<_, _> = self.enums().map(|(name, variants)| {
+            (name.into(), JsonValue::from(variants))
+        }).collect();
+        json!({
+            "documents": documents,
+            "objects": objects,
+            "enums": enums,
+        })
+    }
+}
+
+
+//------------ ObjectSchema --------------------------------------------------
+
+/// The description of a document type or a nested object.
+#[derive(Clone, Debug, Default)]
+pub struct ObjectSchema {
+    /// Is this a document type?
+    document: bool,
+
+    /// The names of objects whose attributes are included.
+    include: Vec<&'static str>,
+
+    /// The attributes in the order they were added.
+    attributes: Vec<Attribute>,
+}
+
+impl ObjectSchema {
+    /// Adds a mandatory attribute.
+    pub fn mandatory(
+        &mut self, name: &'static str, value: &'static str
+    ) -> &mut Self {
+        self.attributes.push(Attribute { name, value, mandatory: true });
+        self
+    }
+
+    /// Adds an optional attribute.
+    pub fn optional(
+        &mut self, name: &'static str, value: &'static str
+    ) -> &mut Self {
+        self.attributes.push(Attribute { name, value, mandatory: false });
+        self
+    }
+
+    /// Includes all attributes of another object.
+    pub fn include(&mut self, object: &'static str) -> &mut Self {
+        self.include.push(object);
+        self
+    }
+
+    pub fn is_document(&self) -> bool {
+        self.document
+    }
+
+    pub fn includes(&self) -> &[&'static str] {
+        &self.include
+    }
+
+    pub fn attributes(&self) -> &[Attribute] {
+        &self.attributes
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let attributes: Map<_, _> = self.attributes.iter().map(|attr| {
+            (
+                attr.name.into(),
+                json!({
+                    "type": attr.value,
+                    "mandatory": attr.mandatory,
+                })
+            )
+        }).collect();
+        json!({
+            "include": self.include,
+            "attributes": attributes,
+        })
+    }
+}
+
+
+//------------ Attribute -----------------------------------------------------
+
+/// The description of a single attribute.
+#[derive(Clone, Copy, Debug)]
+pub struct Attribute {
+    /// The name of the attribute.
+    pub name: &'static str,
+
+    /// The type expression for the value of the attribute.
+    pub value: &'static str,
+
+    /// Does the attribute have to be present?
+    pub mandatory: bool,
+}
+
+
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+    use serde_json::{Map, Value as JsonValue};
+    use crate::load::yaml::{
+        record_keys, MissingKey, TypeMismatch, UnexpectedKey
+    };
+    use crate::testdata::Fixture;
+    use super::{ObjectSchema, Schema};
+
+    /// The depth of nested objects after which only mandatory attributes
+    /// are included.
+    const MAX_DEPTH: usize = 4;
+
+    /// Splits a type expression into its kind and argument.
+    fn split_expr(expr: &str) -> (&str, &str) {
+        match expr.split_once('(') {
+            Some((kind, arg)) => {
+                (kind, arg.strip_suffix(')').unwrap_or(arg))
+            }
+            None => (expr, "")
+        }
+    }
+
+    /// Returns a well-formed value for a type expression.
+    ///
+    /// If `nested` is true, events use a list of records rather than
+    /// having the attributes of a record directly.
+    fn sample(
+        schema: &Schema, expr: &str, depth: usize, nested: bool
+    ) -> JsonValue {
+        let (kind, arg) = split_expr(expr);
+        match kind {
+            "list" => {
+                JsonValue::Array(vec![sample(schema, arg, depth, nested)])
+            }
+            "nullable" | "current" => sample(schema, arg, depth, nested),
+            "object" => object(schema, arg, depth + 1, nested),
+            "mapping" => {
+                let (key, value) = split_pair(arg);
+                let key = match sample(schema, key, depth, nested) {
+                    JsonValue::String(key) => key,
+                    key => key.to_string(),
+                };
+                let mut res = Map::new();
+                res.insert(key, sample(schema, value, depth, nested));
+                JsonValue::Object(res)
+            }
+            "enum" => {
+                let variants = schema.get_enum(arg).unwrap_or_else(|| {
+                    panic!("unknown enum {}", arg)
+                });
+                variants[0].into()
+            }
+            "key" => {
+                let doctype = if arg.is_empty() { "entity" } else { arg };
+                format!("{}.de.sample", doctype).into()
+            }
+            "date" => 1900.into(),
+            "integer" => 1.into(),
+            "number" => 1.5.into(),
+            "bool" => true.into(),
+            "url" => "https://example.com/".into(),
+            "country" => "de".into(),
+            "course" => "path.de.sample a b".into(),
+            "electrified" => "none".into(),
+            "gauge" => "1435mm".into(),
+            _ => "sample".into(),
+        }
+    }
+
+    /// Returns a mapping with the attributes of an object.
+    fn object(
+        schema: &Schema, name: &str, depth: usize, nested: bool
+    ) -> JsonValue {
+        let mut res = Map::new();
+        add_attributes(schema, name, depth, nested, &mut res);
+        JsonValue::Object(res)
+    }
+
+    /// Adds the attributes of an object and those it includes.
+    ///
+    /// An event has either a list of records or the attributes of a
+    /// single record, so only one of the two is added.
+    fn add_attributes(
+        schema: &Schema, name: &str, depth: usize, nested: bool,
+        target: &mut Map<String, JsonValue>,
+    ) {
+        let object = get_object(schema, name);
+        let record = included_record(object);
+        for include in object.includes() {
+            if nested && record == Some(*include) {
+                continue
+            }
+            add_attributes(schema, include, depth, nested, target);
+        }
+        for attr in object.attributes() {
+            if !nested && record.is_some() && attr.name == "records" {
+                continue
+            }
+            if attr.mandatory || depth <= MAX_DEPTH {
+                target.insert(
+                    attr.name.into(),
+                    sample(schema, attr.value, depth, nested)
+                );
+            }
+        }
+    }
+
+    /// Returns the record object both included and listed in `records`.
+    fn included_record(object: &ObjectSchema) -> Option<&'static str> {
+        let records = object.attributes().iter().find(|attr| {
+            attr.name == "records"
+        })?;
+        let (_, item) = split_expr(records.value);
+        let (_, record) = split_expr(item);
+        object.includes().iter().copied().find(|name| *name == record)
+    }
+
+    /// Adds the names of all attributes reachable from an object.
+    fn add_names(
+        schema: &Schema, name: &'static str,
+        visited: &mut BTreeSet<&'static str>, target: &mut BTreeSet<String>
+    ) {
+        if !visited.insert(name) {
+            return
+        }
+        let object = get_object(schema, name);
+        for include in object.includes() {
+            add_names(schema, include, visited, target);
+        }
+        for attr in object.attributes() {
+            target.insert(attr.name.into());
+            add_expr_names(schema, attr.value, visited, target);
+        }
+    }
+
+    /// Adds the names of attributes of the objects in a type expression.
+    fn add_expr_names(
+        schema: &Schema, expr: &'static str,
+        visited: &mut BTreeSet<&'static str>, target: &mut BTreeSet<String>
+    ) {
+        let (kind, arg) = split_expr(expr);
+        match kind {
+            "list" | "nullable" | "current" => {
+                add_expr_names(schema, arg, visited, target)
+            }
+            "mapping" => {
+                let (key, value) = split_pair(arg);
+                add_expr_names(schema, key, visited, target);
+                add_expr_names(schema, value, visited, target);
+            }
+            "object" => add_names(schema, arg, visited, target),
+            _ => { }
+        }
+    }
+
+    fn get_object<'a>(schema: &'a Schema, name: &str) -> &'a ObjectSchema {
+        schema.get_object(name).unwrap_or_else(|| {
+            panic!("unknown object {}", name)
+        })
+    }
+
+    /// Splits the arguments of a type expression with two arguments.
+    fn split_pair(arg: &str) -> (&str, &str) {
+        let mut depth = 0usize;
+        for (pos, ch) in arg.char_indices() {
+            match ch {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                ',' if depth == 0 => {
+                    return (arg[..pos].trim(), arg[pos + 1..].trim())
+                }
+                _ => { }
+            }
+        }
+        panic!("expected two arguments in {}", arg)
+    }
+
+    /// Loads a document with all attributes of each document type.
+    ///
+    /// The values follow the schema but don’t necessarily make sense, so
+    /// loading can fail. However, the parser must neither reject an
+    /// attribute nor expect one the schema doesn’t have, and each value
+    /// must be of the kind it expects. Every key the parser asks for must
+    /// be described by the schema, too.
+    #[test]
+    fn documents_match_parser() {
+        let schema = Schema::new();
+        for (name, _) in schema.documents() {
+            // Custom types are only known from the data tree.
+            if name == "custom" {
+                continue
+            }
+            let mut names = BTreeSet::new();
+            add_names(&schema, name, &mut BTreeSet::new(), &mut names);
+            for nested in [false, true] {
+                let mut doc = match object(&schema, name, 0, nested) {
+                    JsonValue::Object(doc) => doc,
+                    _ => unreachable!()
+                };
+                doc.insert(
+                    "key".into(), format!("{}.de.sample", name).into()
+                );
+                doc.insert("type".into(), name.into());
+                let yaml = JsonValue::Object(doc).to_string();
+                let (res, keys) = record_keys(|| {
+                    Fixture::new().yaml(&yaml).into_data_store()
+                });
+                for key in &keys {
+                    assert!(
+                        names.contains(key),
+                        "{} document: parser takes {} missing from schema",
+                        name, key
+                    );
+                }
+                let report = match res {
+                    Ok(_) => continue,
+                    Err(report) => report,
+                };
+                for notice in report.iter() {
+                    let drift =
+                        notice.message_as::<UnexpectedKey>().is_some()
+                        || notice.message_as::<MissingKey>().is_some()
+                        || notice.message_as::<TypeMismatch>().is_some();
+                    assert!(!drift, "{} document: {}\n{}", name, notice, yaml);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,345 @@
+//! Export of the store as an SQLite database.
+//!
+//! An [`SqlDump`] renders the whole store as an SQL script that creates
+//! and fills a relational version of the data. Feeding it to the `sqlite3`
+//! command creates a database ready for analysis:
+//!
+//! ```text
+//! raildata data sqlite | sqlite3 raildata.db
+//! ```
+//!
+//! The database has these tables:
+//!
+//! * `documents` with the key, type, preferred name, progress, and file
+//!   of each document,
+//! * `events` with the date and status of each event of a document,
+//! * `sections` with the sections of a line each line event applies to,
+//! * `line_points` with the points of each line and their kilometrage,
+//! * `codes` with the current codes of each point,
+//! * `xrefs` with the cross references between documents, and
+//! * `attribution` with the license and attribution of the data, where
+//!   the row without a document applies to all documents without a row
+//!   of their own.
+//!
+//! Links between documents are stored as the key of the linked document
+//! and declared as foreign keys into `documents`. When exporting only
+//! documents of a certain progress, rows linking to documents left out
+//! are dropped so the foreign keys stay intact.
+
+use std::fmt::Write;
+use serde_json::Value as JsonValue;
+use crate::document::combined::Data;
+use crate::document::common::Attribution;
+use crate::store::{DataStore, DocumentLink, FullStore, ProgressView};
+use crate::types::{EventDate, Key, LanguagePreference};
+
+
+//------------ SqlDump -------------------------------------------------------
+
+/// A store to be exported as SQL.
+#[derive(Clone, Debug)]
+pub struct SqlDump<'a> {
+    /// The store to export.
+    store: &'a FullStore,
+
+    /// The documents to include.
+    view: ProgressView<'a>,
+
+    /// The documents ordered by key.
+    links: Vec<DocumentLink>,
+
+    /// The preferred languages for names.
+    preference: &'a LanguagePreference,
+}
+
+impl<'a> SqlDump<'a> {
+    pub fn new(
+        store: impl Into<ProgressView<'a>>,
+        preference: &'a LanguagePreference,
+    ) -> Self {
+        let view = store.into();
+        let store = view.store();
+        let mut links = view.links().collect::<Vec<_>>();
+        links.sort_by(|left, right| {
+            left.data(store).key().cmp(right.data(store).key())
+        });
+        SqlDump { store, view, links, preference }
+    }
+
+    /// Returns the SQL script creating the database.
+    ///
+    /// All statements are wrapped in a single transaction. Because all
+    /// documents are inserted first, the script also works with foreign
+    /// key checks enabled.
+    pub fn render(&self) -> String {
+        let mut res = String::new();
+        res.push_str("BEGIN TRANSACTION;\n");
+        res.push_str(SCHEMA);
+        let ds: &DataStore = self.store.as_ref();
+        write_attribution(None, ds.attribution(), &mut res);
+        for link in &self.links {
+            self.write_document(*link, &mut res);
+        }
+        for link in &self.links {
+            self.write_events(*link, &mut res);
+            self.write_line_points(*link, &mut res);
+            self.write_codes(*link, &mut res);
+            self.write_xrefs(*link, &mut res);
+            self.write_attribution(*link, &mut res);
+        }
+        res.push_str("COMMIT;\n");
+        res
+    }
+
+    fn write_attribution(&self, link: DocumentLink, target: &mut String) {
+        let data = link.data(self.store);
+        if let Some(attribution) = data.common().attribution.as_ref() {
+            write_attribution(Some(data.key()), attribution, target)
+        }
+    }
+
+    fn write_document(&self, link: DocumentLink, target: &mut String) {
+        let data = link.data(self.store);
+        let _ = writeln!(
+            target,
+            "INSERT INTO documents VALUES ({}, {}, {}, {}, {});",
+            quote(data.key().as_str()),
+            quote(data.doctype().as_str()),
+            quote(data.preferred_name(self.preference)),
+            quote(data.common().progress.into_value().as_str()),
+            quote(&data.origin().path().to_string_lossy()),
+        );
+    }
+
+    fn write_events(&self, link: DocumentLink, target: &mut String) {
+        let data = link.data(self.store);
+        let key = quote(data.key().as_str());
+        let events: Vec<(&EventDate, Option<&str>)> = match *data {
+            Data::Line(ref line) => {
+                line.events.iter().map(|event| {
+                    (&event.date, event.status().map(|s| s.as_str()))
+                }).collect()
+            }
+            Data::Entity(ref entity) => {
+                entity.events.iter().map(|event| {
+                    let status = event.records.iter().find_map(|record| {
+                        record.properties.status.map(|status| {
+                            status.into_value().as_str()
+                        })
+                    });
+                    (&event.date, status)
+                }).collect()
+            }
+            Data::Point(ref point) => {
+                point.events.iter().map(|event| {
+                    (&event.date, event.status().map(|s| s.as_str()))
+                }).collect()
+            }
+            Data::Service(ref service) => {
+                service.events.iter().map(|event| {
+                    (&event.date, event.status().map(|s| s.as_str()))
+                }).collect()
+            }
+            Data::Structure(ref structure) => {
+                structure.events.iter().map(|event| {
+                    (&event.date, None)
+                }).collect()
+            }
+            Data::Path(_) | Data::Source(_) | Data::Custom(_) => return,
+        };
+        for (idx, (date, status)) in events.into_iter().enumerate() {
+            let _ = writeln!(
+                target,
+                "INSERT INTO events VALUES ({}, {}, {}, {});",
+                key, idx,
+                opt_quote(date.iter().next().map(|date| date.to_string())),
+                opt_quote(status),
+            );
+        }
+        if let Data::Line(ref line) = *data {
+            let ds: &DataStore = self.store.as_ref();
+            for (idx, event) in line.events.iter().enumerate() {
+                for section in event.sections.covered(line.points.len()) {
+                    let id = section.id(line, ds);
+                    if self.view.get(id.start()).is_none()
+                        || self.view.get(id.end()).is_none()
+                    {
+                        continue
+                    }
+                    let _ = writeln!(
+                        target,
+                        "INSERT INTO sections VALUES ({}, {}, {}, {});",
+                        key, idx,
+                        quote(id.start().as_str()), quote(id.end().as_str()),
+                    );
+                }
+            }
+        }
+    }
+
+    fn write_line_points(&self, link: DocumentLink, target: &mut String) {
+        let line = match link.data(self.store).try_as_line() {
+            Some(line) => line,
+            None => return,
+        };
+        let key = quote(line.key().as_str());
+        for (idx, point) in line.points.iter().enumerate() {
+            if !self.view.contains(point.into_value().into()) {
+                continue
+            }
+            let point = point.data(self.store);
+            let km = point.line_location(line.link()).and_then(
+                |(location, _)| location
+            );
+            let _ = writeln!(
+                target,
+                "INSERT INTO line_points VALUES ({}, {}, {}, {});",
+                key, idx, quote(point.key().as_str()), opt_quote(km),
+            );
+        }
+    }
+
+    fn write_codes(&self, link: DocumentLink, target: &mut String) {
+        let point = match link.data(self.store).try_as_point() {
+            Some(point) => point,
+            None => return,
+        };
+        let key = quote(point.key().as_str());
+        let meta = point.link().meta(self.store);
+        let mut codes = meta.current.codes.iter().flat_map(|(code, values)| {
+            values.map(move |value| (code, value))
+        }).collect::<Vec<_>>();
+        codes.sort();
+        for (code, value) in codes {
+            let _ = writeln!(
+                target,
+                "INSERT INTO codes VALUES ({}, {}, {});",
+                key, quote(code.as_str()), quote(value),
+            );
+        }
+    }
+
+    fn write_xrefs(&self, link: DocumentLink, target: &mut String) {
+        let key = quote(link.data(self.store).key().as_str());
+        let xrefs = link.xrefs(self.store).to_json(self.store.as_ref());
+        let xrefs = match xrefs {
+            JsonValue::Object(xrefs) => xrefs,
+            _ => return,
+        };
+        for (kind, items) in xrefs {
+            let items = match items {
+                JsonValue::Array(items) => items,
+                _ => continue,
+            };
+            for item in items {
+                let (target_key, section) = match item {
+                    JsonValue::String(target_key) => (target_key, None),
+                    JsonValue::Object(mut item) => {
+                        match (item.remove("line"), item.remove("section")) {
+                            (
+                                Some(JsonValue::String(line)),
+                                Some(JsonValue::String(section))
+                            ) => (line, Some(section)),
+                            _ => continue,
+                        }
+                    }
+                    _ => continue,
+                };
+                if self.view.get(target_key.as_str()).is_none() {
+                    continue
+                }
+                let _ = writeln!(
+                    target,
+                    "INSERT INTO xrefs VALUES ({}, {}, {}, {});",
+                    key, quote(&kind), quote(&target_key),
+                    opt_quote(section),
+                );
+            }
+        }
+    }
+}
+
+
+//------------ Schema --------------------------------------------------------
+
+/// The statements creating the tables.
+const SCHEMA: &str = r#"CREATE TABLE documents (
+    key TEXT PRIMARY KEY,
+    type TEXT NOT NULL,
+    name TEXT NOT NULL,
+    progress TEXT NOT NULL,
+    file TEXT
+);
+CREATE TABLE events (
+    document TEXT NOT NULL REFERENCES documents(key),
+    idx INTEGER NOT NULL,
+    date TEXT,
+    status TEXT,
+    PRIMARY KEY (document, idx)
+);
+CREATE TABLE sections (
+    document TEXT NOT NULL,
+    event INTEGER NOT NULL,
+    start_point TEXT NOT NULL REFERENCES documents(key),
+    end_point TEXT NOT NULL REFERENCES documents(key),
+    FOREIGN KEY (document, event) REFERENCES events(document, idx)
+);
+CREATE TABLE line_points (
+    line TEXT NOT NULL REFERENCES documents(key),
+    idx INTEGER NOT NULL,
+    point TEXT NOT NULL REFERENCES documents(key),
+    km TEXT,
+    PRIMARY KEY (line, idx)
+);
+CREATE TABLE codes (
+    document TEXT NOT NULL REFERENCES documents(key),
+    type TEXT NOT NULL,
+    value TEXT NOT NULL
+);
+CREATE TABLE xrefs (
+    document TEXT NOT NULL REFERENCES documents(key),
+    kind TEXT NOT NULL,
+    target TEXT NOT NULL REFERENCES documents(key),
+    section TEXT
+);
+CREATE TABLE attribution (
+    document TEXT UNIQUE REFERENCES documents(key),
+    license TEXT,
+    text TEXT,
+    url TEXT
+);
+"#;
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Writes the attribution of a document or, without a key, of all data.
+fn write_attribution(
+    key: Option<&Key>, attribution: &Attribution, target: &mut String
+) {
+    if attribution.is_empty() {
+        return
+    }
+    let _ = writeln!(
+        target,
+        "INSERT INTO attribution VALUES ({}, {}, {}, {});",
+        opt_quote(key.map(Key::as_str)),
+        opt_quote(attribution.license.as_ref()),
+        opt_quote(attribution.text.as_ref()),
+        opt_quote(attribution.url.as_ref()),
+    );
+}
+
+/// Returns a string as an SQL literal.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Returns an optional string as an SQL literal or `NULL`.
+fn opt_quote(value: Option<impl AsRef<str>>) -> String {
+    match value {
+        Some(value) => quote(value.as_ref()),
+        None => String::from("NULL"),
+    }
+}
+
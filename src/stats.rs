@@ -0,0 +1,788 @@
+//! Statistics about the data set.
+//!
+//! The statistics are generated from a [`DataStore`] and are broken down by
+//! the country encoded in the document keys. They can be printed as text
+//! via the `Display` implementation or converted into JSON.
+//!
+//! [`CourseCoverage`] separately reports how much of the lines has course
+//! information and which sections still lack it.
+
+use std::fmt;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use serde_json::{json, Map, Value as JsonValue};
+use crate::document::{line, source, Data};
+use crate::document::common::Progress;
+use crate::store::DataStore;
+use crate::types::{CountryCode, EventDate, Key};
+
+
+//------------ Statistics ----------------------------------------------------
+
+/// Statistics for the complete data set.
+#[derive(Clone, Debug, Default)]
+pub struct Statistics {
+    /// The statistics over all documents.
+    total: CountryStatistics,
+
+    /// The statistics for each country.
+    ///
+    /// Documents that can’t be attributed to a country are collected under
+    /// `None`.
+    countries: BTreeMap<Option<CountryCode>, CountryStatistics>,
+}
+
+impl Statistics {
+    pub fn generate(store: &DataStore) -> Self {
+        Self::generate_with_progress(store, Progress::Stub)
+    }
+
+    /// Generates statistics only for documents with a minimum progress.
+    pub fn generate_with_progress(store: &DataStore, min: Progress) -> Self {
+        let mut res = Self::default();
+        for data in store.iter() {
+            if data.common().progress.into_value() < min {
+                continue
+            }
+            let item = CountryStatistics::from_document(data, store);
+            res.total.merge(&item);
+            res.countries.entry(
+                Self::country(data.key())
+            ).or_default().merge(&item);
+        }
+        res
+    }
+
+    fn country(key: &Key) -> Option<CountryCode> {
+        key.country().and_then(|code| CountryCode::from_str(code).ok())
+    }
+
+    pub fn total(&self) -> &CountryStatistics {
+        &self.total
+    }
+
+    pub fn country_statistics(
+        &self, country: Option<CountryCode>
+    ) -> Option<&CountryStatistics> {
+        self.countries.get(&country)
+    }
+
+    pub fn countries(
+        &self
+    ) -> impl Iterator<Item = (Option<CountryCode>, &CountryStatistics)> {
+        self.countries.iter().map(|(key, value)| (*key, value))
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let mut countries = Map::new();
+        for (country, stats) in self.countries() {
+            countries.insert(
+                match country {
+                    Some(country) => country.as_str().into(),
+                    None => "other".into(),
+                },
+                stats.to_json()
+            );
+        }
+        json!({
+            "total": self.total.to_json(),
+            "countries": countries,
+        })
+    }
+}
+
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total = &self.total;
+        writeln!(f, "{} documents:", total.documents())?;
+        writeln!(f, "   {} lines", total.lines)?;
+        writeln!(f, "   {} entities", total.entities)?;
+        writeln!(f, "   {} paths", total.paths)?;
+        writeln!(f, "   {} points", total.points)?;
+        writeln!(f, "   {} services", total.services)?;
+        writeln!(f, "   {} sources", total.sources)?;
+        writeln!(f, "   {} structures", total.structures)?;
+        writeln!(f, "   {} custom documents", total.custom)?;
+        writeln!(f, "Lines:")?;
+        total.fmt_lines(f)?;
+
+        writeln!(f, "By country:")?;
+        for (country, stats) in self.countries() {
+            match country {
+                Some(country) => write!(f, "   {}: ", country)?,
+                None => write!(f, "   other: ")?,
+            }
+            writeln!(f,
+                "{} documents, {} lines, {} points, {:.1} km",
+                stats.documents(), stats.lines, stats.points, stats.line_km
+            )?;
+        }
+
+        writeln!(f, "Events per decade:")?;
+        for (decade, count) in &total.decades {
+            writeln!(f, "   {}s: {}", decade, count)?;
+        }
+
+        writeln!(f, "Sources per decade:")?;
+        for (decade, count) in &total.source_decades {
+            writeln!(f, "   {}s: {}", decade, count)?;
+        }
+        writeln!(f, "Sources per subtype:")?;
+        for (subtype, count) in &total.source_subtypes {
+            writeln!(f, "   {}: {}", subtype, count)?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ CountryStatistics ---------------------------------------------
+
+/// Statistics for a subset of documents.
+#[derive(Clone, Debug, Default)]
+pub struct CountryStatistics {
+    pub lines: usize,
+    pub entities: usize,
+    pub paths: usize,
+    pub points: usize,
+    pub services: usize,
+    pub sources: usize,
+    pub structures: usize,
+    pub custom: usize,
+
+    /// The number of lines with at least one currently open section.
+    pub open_lines: usize,
+
+    /// The number of lines without open but with closed sections.
+    pub closed_lines: usize,
+
+    /// The length of all lines with course information in kilometres.
+    pub line_km: f64,
+
+    /// The length of the currently electrified sections in kilometres.
+    pub electrified_km: f64,
+
+    /// The length of the currently open sections in kilometres.
+    pub open_km: f64,
+
+    /// The length of the currently closed sections in kilometres.
+    pub closed_km: f64,
+
+    /// The part of the section lengths that is only estimated.
+    ///
+    /// These are sections where neither the kilometrage of both end
+    /// points nor the course of exactly the section is known. Their
+    /// length is estimated from the number of points they span.
+    pub estimated: EstimatedKm,
+
+    /// The number of events per decade.
+    ///
+    /// The key is the first year of the decade.
+    pub decades: BTreeMap<i16, usize>,
+
+    /// The number of sources per decade of publication.
+    ///
+    /// The key is the first year of the decade.
+    pub source_decades: BTreeMap<i16, usize>,
+
+    /// The number of sources of each subtype.
+    pub source_subtypes: BTreeMap<source::Subtype, usize>,
+}
+
+impl CountryStatistics {
+    pub(crate) fn from_document(data: &Data, store: &DataStore) -> Self {
+        let mut res = Self::default();
+        match *data {
+            Data::Line(ref line) => {
+                res.lines = 1;
+                res.add_line(line, store);
+                res.add_events(line.events.iter().map(|ev| &ev.date));
+            }
+            Data::Entity(ref entity) => {
+                res.entities = 1;
+                res.add_events(entity.events.iter().map(|ev| &ev.date));
+            }
+            Data::Path(_) => {
+                res.paths = 1;
+            }
+            Data::Point(ref point) => {
+                res.points = 1;
+                res.add_events(point.events.iter().map(|ev| &ev.date));
+            }
+            Data::Service(ref service) => {
+                res.services = 1;
+                res.add_events(service.events.iter().map(|ev| &ev.date));
+            }
+            Data::Source(ref source) => {
+                res.sources = 1;
+                if let Some(decade) = source.decade(store) {
+                    res.source_decades.insert(decade, 1);
+                }
+                res.source_subtypes.insert(source.subtype.into_value(), 1);
+            }
+            Data::Structure(ref structure) => {
+                res.structures = 1;
+                res.add_events(structure.events.iter().map(|ev| &ev.date));
+            }
+            Data::Custom(_) => {
+                res.custom = 1;
+            }
+        }
+        res
+    }
+
+    fn add_line(&mut self, line: &line::Data, store: &DataStore) {
+        let lengths = SectionLengths::new(line, store);
+
+        self.line_km = lengths.length;
+        for (section, value) in line.current.electrified.as_slice() {
+            let electrified = value.as_ref().map(|value| {
+                value.iter().any(|item| item.generic().is_some())
+            }).unwrap_or(false);
+            if electrified {
+                let (km, estimated) = lengths.get(section);
+                self.electrified_km += km;
+                if estimated {
+                    self.estimated.electrified_km += km;
+                }
+            }
+        }
+
+        let mut open = false;
+        let mut closed = false;
+        for (section, status) in line.current.status.as_slice() {
+            match *status {
+                line::Status::Open | line::Status::Reopened => {
+                    let (km, estimated) = lengths.get(section);
+                    self.open_km += km;
+                    if estimated {
+                        self.estimated.open_km += km;
+                    }
+                    open = true;
+                }
+                line::Status::Suspended | line::Status::Closed
+                | line::Status::Removed | line::Status::Released => {
+                    let (km, estimated) = lengths.get(section);
+                    self.closed_km += km;
+                    if estimated {
+                        self.estimated.closed_km += km;
+                    }
+                    closed = true;
+                }
+                _ => { }
+            }
+        }
+        if open {
+            self.open_lines = 1;
+        }
+        else if closed {
+            self.closed_lines = 1;
+        }
+    }
+
+    fn add_events<'a>(&mut self, dates: impl Iterator<Item = &'a EventDate>) {
+        for date in dates {
+            if let Some(date) = date.iter().next() {
+                *self.decades.entry(
+                    date.year().div_euclid(10) * 10
+                ).or_default() += 1;
+            }
+        }
+    }
+
+    pub(crate) fn merge(&mut self, other: &Self) {
+        self.lines += other.lines;
+        self.entities += other.entities;
+        self.paths += other.paths;
+        self.points += other.points;
+        self.services += other.services;
+        self.sources += other.sources;
+        self.structures += other.structures;
+        self.custom += other.custom;
+        self.open_lines += other.open_lines;
+        self.closed_lines += other.closed_lines;
+        self.line_km += other.line_km;
+        self.electrified_km += other.electrified_km;
+        self.open_km += other.open_km;
+        self.closed_km += other.closed_km;
+        self.estimated.electrified_km += other.estimated.electrified_km;
+        self.estimated.open_km += other.estimated.open_km;
+        self.estimated.closed_km += other.estimated.closed_km;
+        for (decade, count) in &other.decades {
+            *self.decades.entry(*decade).or_default() += count;
+        }
+        for (decade, count) in &other.source_decades {
+            *self.source_decades.entry(*decade).or_default() += count;
+        }
+        for (subtype, count) in &other.source_subtypes {
+            *self.source_subtypes.entry(*subtype).or_default() += count;
+        }
+    }
+
+    /// Returns the total number of documents.
+    pub fn documents(&self) -> usize {
+        self.lines + self.entities + self.paths + self.points + self.services
+        + self.sources + self.structures + self.custom
+    }
+
+    /// Returns the share of electrified line kilometres.
+    ///
+    /// Returns `None` if there are no line kilometres at all.
+    pub fn electrified_share(&self) -> Option<f64> {
+        self.km_share(self.electrified_km)
+    }
+
+    /// Returns the share of open line kilometres.
+    pub fn open_share(&self) -> Option<f64> {
+        self.km_share(self.open_km)
+    }
+
+    fn km_share(&self, km: f64) -> Option<f64> {
+        if self.line_km > 0. {
+            Some(km / self.line_km)
+        }
+        else {
+            None
+        }
+    }
+
+    fn fmt_lines(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f,
+            "   {} open, {} closed", self.open_lines, self.closed_lines
+        )?;
+        write!(f, "   {:.1} km", self.line_km)?;
+        if let Some(share) = self.electrified_share() {
+            write!(f, ", {:.1} % electrified", share * 100.)?;
+        }
+        if let Some(share) = self.open_share() {
+            write!(f, ", {:.1} % open", share * 100.)?;
+        }
+        writeln!(f)?;
+        if !self.estimated.is_empty() {
+            writeln!(f,
+                "   estimated: {:.1} km electrified, {:.1} km open, \
+                 {:.1} km closed",
+                self.estimated.electrified_km, self.estimated.open_km,
+                self.estimated.closed_km,
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let decades: Map<_, _> = self.decades.iter().map(|(decade, count)| {
+            (decade.to_string(), JsonValue::from(*count))
+        }).collect();
+        let source_decades: Map<_, _> = self.source_decades.iter().map(
+            |(decade, count)| (decade.to_string(), JsonValue::from(*count))
+        ).collect();
+        let source_subtypes: Map<_, _> = self.source_subtypes.iter().map(
+            |(subtype, count)| {
+                (subtype.as_str().to_string(), JsonValue::from(*count))
+            }
+        ).collect();
+        json!({
+            "documents": self.documents(),
+            "lines": self.lines,
+            "entities": self.entities,
+            "paths": self.paths,
+            "points": self.points,
+            "services": self.services,
+            "sources": self.sources,
+            "structures": self.structures,
+            "custom": self.custom,
+            "open_lines": self.open_lines,
+            "closed_lines": self.closed_lines,
+            "km": {
+                "total": self.line_km,
+                "electrified": self.electrified_km,
+                "open": self.open_km,
+                "closed": self.closed_km,
+                "estimated": {
+                    "electrified": self.estimated.electrified_km,
+                    "open": self.estimated.open_km,
+                    "closed": self.estimated.closed_km,
+                },
+            },
+            "electrified_share": self.electrified_share(),
+            "open_share": self.open_share(),
+            "events_per_decade": decades,
+            "sources_per_decade": source_decades,
+            "sources_per_subtype": source_subtypes,
+        })
+    }
+}
+
+
+//------------ EstimatedKm ---------------------------------------------------
+
+/// The estimated part of the section lengths in kilometres.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EstimatedKm {
+    /// The estimated length of electrified sections.
+    pub electrified_km: f64,
+
+    /// The estimated length of open sections.
+    pub open_km: f64,
+
+    /// The estimated length of closed sections.
+    pub closed_km: f64,
+}
+
+impl EstimatedKm {
+    /// Returns whether no length at all was estimated.
+    pub fn is_empty(&self) -> bool {
+        self.electrified_km == 0. && self.open_km == 0.
+        && self.closed_km == 0.
+    }
+}
+
+
+//------------ SectionLengths ------------------------------------------------
+
+/// Determines the length of the sections of a line.
+///
+/// The length of a section is the difference in kilometrage between its
+/// end points if both have a numeric location on the line. Otherwise, it
+/// is the length of the course given for parts of the line within the
+/// section if these cover the whole section. If neither is available, the
+/// length is estimated from the share of the section in the points of the
+/// line.
+struct SectionLengths<'a> {
+    line: &'a line::Data,
+    store: &'a DataStore,
+
+    /// The length of the line’s current course in kilometres.
+    length: f64,
+
+    /// The kilometrage of each point of the line if it has one.
+    km: Vec<Option<f64>>,
+}
+
+impl<'a> SectionLengths<'a> {
+    fn new(line: &'a line::Data, store: &'a DataStore) -> Self {
+        SectionLengths {
+            line, store,
+            length: line.course_length(store),
+            km: line.points.iter().map(|point| {
+                point.data(store).line_location(
+                    line.link()
+                )?.0.and_then(line::parse_kilometrage)
+            }).collect(),
+        }
+    }
+
+    /// Returns the length of a section and whether it is estimated.
+    ///
+    /// The length never exceeds the length of the line’s course, so the
+    /// shares derived from it stay meaningful.
+    fn get(&self, section: &line::Section) -> (f64, bool) {
+        if let Some(km) = self.from_kilometrage(section).or_else(|| {
+            self.from_course(section)
+        }) {
+            return (km.min(self.length), false)
+        }
+        let edges = self.line.points.len().saturating_sub(1).max(1) as f64;
+        let share = (section.end_idx - section.start_idx) as f64 / edges;
+        (self.length * share, true)
+    }
+
+    fn from_kilometrage(&self, section: &line::Section) -> Option<f64> {
+        let start = (*self.km.get(section.start_idx)?)?;
+        let end = (*self.km.get(section.end_idx)?)?;
+        Some((end - start).abs())
+    }
+
+    fn from_course(&self, section: &line::Section) -> Option<f64> {
+        let mut covered = 0;
+        let mut res = 0.;
+        for (part, course) in self.line.current.course.as_slice() {
+            if !section.contains(part) {
+                continue
+            }
+            covered += part.end_idx - part.start_idx;
+            for segment in course.iter() {
+                res += segment.length(self.store)?;
+            }
+        }
+        (covered == section.end_idx - section.start_idx).then_some(res)
+    }
+}
+
+
+//------------ CourseCoverage ------------------------------------------------
+
+/// How much of the lines has course information.
+///
+/// Coverage is measured in the edges between consecutive points of a
+/// line: a section counts as covered if its current course lists at least
+/// one segment. Lines with less than two points are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct CourseCoverage {
+    /// The coverage over all lines.
+    total: Coverage,
+
+    /// The coverage for each country.
+    countries: BTreeMap<Option<CountryCode>, Coverage>,
+
+    /// The lines that aren’t fully covered ordered by key.
+    lines: Vec<LineCoverage>,
+}
+
+impl CourseCoverage {
+    pub fn generate(store: &DataStore) -> Self {
+        let mut res = Self::default();
+        for data in store.iter() {
+            let line = match data.try_as_line() {
+                Some(line) => line,
+                None => continue
+            };
+            let item = match LineCoverage::new(line, store) {
+                Some(item) => item,
+                None => continue
+            };
+            let coverage = Coverage::from_line(&item);
+            res.total.merge(&coverage);
+            res.countries.entry(
+                Statistics::country(line.key())
+            ).or_default().merge(&coverage);
+            if !item.gaps.is_empty() {
+                res.lines.push(item)
+            }
+        }
+        res.lines.sort_by(|left, right| left.key.cmp(&right.key));
+        res
+    }
+
+    pub fn total(&self) -> &Coverage {
+        &self.total
+    }
+
+    pub fn countries(
+        &self
+    ) -> impl Iterator<Item = (Option<CountryCode>, &Coverage)> {
+        self.countries.iter().map(|(key, value)| (*key, value))
+    }
+
+    /// Returns the lines that lack course for at least one section.
+    pub fn lines(&self) -> &[LineCoverage] {
+        &self.lines
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let mut countries = Map::new();
+        for (country, coverage) in self.countries() {
+            countries.insert(
+                match country {
+                    Some(country) => country.as_str().into(),
+                    None => "other".into(),
+                },
+                coverage.to_json()
+            );
+        }
+        json!({
+            "total": self.total.to_json(),
+            "countries": countries,
+            "lines": self.lines.iter().map(
+                LineCoverage::to_json
+            ).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl fmt::Display for CourseCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Total: ")?;
+        self.total.fmt_summary(f)?;
+        writeln!(f, "By country:")?;
+        for (country, coverage) in self.countries() {
+            match country {
+                Some(country) => write!(f, "   {}: ", country)?,
+                None => write!(f, "   other: ")?,
+            }
+            coverage.fmt_summary(f)?;
+        }
+        writeln!(f, "Missing course:")?;
+        for line in &self.lines {
+            writeln!(f, "   {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ Coverage ------------------------------------------------------
+
+/// The course coverage of a set of lines.
+#[derive(Clone, Debug, Default)]
+pub struct Coverage {
+    /// The number of lines.
+    pub lines: usize,
+
+    /// The number of lines with course for all sections.
+    pub complete: usize,
+
+    /// The number of lines with course for some sections.
+    pub partial: usize,
+
+    /// The number of lines without any course.
+    pub missing: usize,
+
+    /// The number of edges between points of all lines.
+    pub edges: usize,
+
+    /// The number of edges with course.
+    pub covered: usize,
+}
+
+impl Coverage {
+    fn from_line(line: &LineCoverage) -> Self {
+        let mut res = Coverage {
+            lines: 1,
+            edges: line.edges,
+            covered: line.covered,
+            .. Default::default()
+        };
+        if line.covered == line.edges {
+            res.complete = 1
+        }
+        else if line.covered == 0 {
+            res.missing = 1
+        }
+        else {
+            res.partial = 1
+        }
+        res
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.lines += other.lines;
+        self.complete += other.complete;
+        self.partial += other.partial;
+        self.missing += other.missing;
+        self.edges += other.edges;
+        self.covered += other.covered;
+    }
+
+    /// Returns the share of edges with course.
+    ///
+    /// Returns `None` if there are no edges at all.
+    pub fn share(&self) -> Option<f64> {
+        if self.edges > 0 {
+            Some(self.covered as f64 / self.edges as f64)
+        }
+        else {
+            None
+        }
+    }
+
+    fn fmt_summary(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+            "{} lines, {} complete, {} partial, {} missing",
+            self.lines, self.complete, self.partial, self.missing
+        )?;
+        if let Some(share) = self.share() {
+            write!(f, ", {:.1} % covered", share * 100.)?;
+        }
+        writeln!(f)
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "lines": self.lines,
+            "complete": self.complete,
+            "partial": self.partial,
+            "missing": self.missing,
+            "edges": self.edges,
+            "covered": self.covered,
+            "share": self.share(),
+        })
+    }
+}
+
+
+//------------ LineCoverage --------------------------------------------------
+
+/// The course coverage of a single line.
+#[derive(Clone, Debug)]
+pub struct LineCoverage {
+    /// The key of the line.
+    pub key: Key,
+
+    /// The number of edges between the points of the line.
+    pub edges: usize,
+
+    /// The number of edges with course.
+    pub covered: usize,
+
+    /// The sections without course.
+    pub gaps: Vec<line::SectionId>,
+}
+
+impl LineCoverage {
+    /// Determines the coverage of a line.
+    ///
+    /// Returns `None` if the line has less than two points.
+    fn new(line: &line::Data, store: &DataStore) -> Option<Self> {
+        if line.points.len() < 2 {
+            return None
+        }
+        let edges = line.points.len() - 1;
+        let mut covered = vec![false; edges];
+        for (section, course) in line.current.course.as_slice() {
+            if !course.is_empty() {
+                covered[section.start_idx..section.end_idx].fill(true)
+            }
+        }
+        let point = |idx: usize| line.points[idx].data(store).key().clone();
+        let mut gaps = Vec::new();
+        let mut start = None;
+        for (idx, &item) in covered.iter().enumerate() {
+            match (item, start) {
+                (false, None) => start = Some(idx),
+                (true, Some(gap)) => {
+                    gaps.push(line::SectionId::new(point(gap), point(idx)));
+                    start = None;
+                }
+                _ => { }
+            }
+        }
+        if let Some(gap) = start {
+            gaps.push(line::SectionId::new(point(gap), point(edges)));
+        }
+        Some(LineCoverage {
+            key: line.key().clone(),
+            edges,
+            covered: covered.into_iter().filter(|item| *item).count(),
+            gaps,
+        })
+    }
+
+    /// Returns the share of edges with course.
+    pub fn share(&self) -> f64 {
+        self.covered as f64 / self.edges as f64
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "key": self.key.as_str(),
+            "share": self.share(),
+            "gaps": self.gaps.iter().map(|gap| {
+                json!({
+                    "start": gap.start().as_str(),
+                    "end": gap.end().as_str(),
+                })
+            }).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl fmt::Display for LineCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({:.1} %):", self.key, self.share() * 100.)?;
+        for gap in &self.gaps {
+            write!(f, " {}", gap)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+//! Contiguous storage for the data of a finished store.
+//!
+//! While loading, the store collects its per-document information in
+//! whatever form is most convenient for inserting. Once loading has
+//! finished, this information doesn’t change anymore. The types in this
+//! module keep it in a small number of contiguous buffers instead of one
+//! allocation per document, which reduces the number of allocations and
+//! keeps scans over all documents within consecutive memory.
+
+use std::{borrow, ops};
+use crate::types::Key;
+use super::DocumentLink;
+
+
+//------------ Slices --------------------------------------------------------
+
+/// A sequence of lists kept in a single buffer.
+///
+/// The items of all lists are stored back to back. A second buffer keeps
+/// the index of the end of each list.
+#[derive(Clone, Debug)]
+pub struct Slices<T> {
+    items: Vec<T>,
+    ends: Vec<usize>,
+}
+
+impl<T> Slices<T> {
+    /// Returns the list with the given index.
+    pub fn get(&self, index: usize) -> Option<&[T]> {
+        let end = *self.ends.get(index)?;
+        let start = match index {
+            0 => 0,
+            _ => self.ends[index - 1],
+        };
+        Some(&self.items[start..end])
+    }
+}
+
+impl<T> Default for Slices<T> {
+    fn default() -> Self {
+        Slices { items: Vec::new(), ends: Vec::new() }
+    }
+}
+
+impl<T> ops::Index<usize> for Slices<T> {
+    type Output = [T];
+
+    fn index(&self, index: usize) -> &[T] {
+        match self.get(index) {
+            Some(res) => res,
+            None => panic!("list index {} out of range", index),
+        }
+    }
+}
+
+impl<T, I: IntoIterator<Item = T>> FromIterator<I> for Slices<T> {
+    fn from_iter<It: IntoIterator<Item = I>>(iter: It) -> Self {
+        let mut res = Slices::default();
+        for list in iter {
+            res.items.extend(list);
+            res.ends.push(res.items.len());
+        }
+        res.items.shrink_to_fit();
+        res.ends.shrink_to_fit();
+        res
+    }
+}
+
+
+//------------ KeyIndex ------------------------------------------------------
+
+/// The links of all documents ordered by their key.
+///
+/// The keys are kept in a single sorted buffer. Lookups use a binary
+/// search, iteration simply walks the buffer.
+#[derive(Clone, Debug, Default)]
+pub struct KeyIndex {
+    entries: Vec<(Key, DocumentLink)>,
+}
+
+impl KeyIndex {
+    /// Returns the link of the document with the given key.
+    pub fn get<Q>(&self, key: &Q) -> Option<DocumentLink>
+    where Key: borrow::Borrow<Q>, Q: Ord + ?Sized {
+        self.entries.binary_search_by(|(item, _)| {
+            borrow::Borrow::<Q>::borrow(item).cmp(key)
+        }).ok().map(|index| self.entries[index].1)
+    }
+
+    /// Returns the links of all documents in key order.
+    pub fn links(&self) -> impl Iterator<Item = DocumentLink> + '_ {
+        self.entries.iter().map(|(_, link)| *link)
+    }
+
+    /// Returns the links of all documents starting at the given key.
+    pub fn links_from<Q>(
+        &self, start: &Q
+    ) -> impl Iterator<Item = DocumentLink> + '_
+    where Key: borrow::Borrow<Q>, Q: Ord + ?Sized {
+        let start = self.entries.partition_point(|(item, _)| {
+            borrow::Borrow::<Q>::borrow(item) < start
+        });
+        self.entries[start..].iter().map(|(_, link)| *link)
+    }
+}
+
+impl FromIterator<(Key, DocumentLink)> for KeyIndex {
+    fn from_iter<I: IntoIterator<Item = (Key, DocumentLink)>>(
+        iter: I
+    ) -> Self {
+        let mut entries: Vec<_> = iter.into_iter().collect();
+        entries.sort_unstable_by(|left, right| left.0.cmp(&right.0));
+        entries.dedup_by(|left, right| left.0 == right.0);
+        entries.shrink_to_fit();
+        KeyIndex { entries }
+    }
+}
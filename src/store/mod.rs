@@ -0,0 +1,1308 @@
+use std::{borrow, mem};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic;
+use std::sync::atomic::AtomicBool;
+use derive_more::Display;
+use rayon::prelude::*;
+use serde_json::Value as JsonValue;
+use crate::document::combined::{Data, Document, Meta, Xrefs};
+use crate::document::common::{Attribution, DocumentType, Progress};
+use crate::document::custom::CustomRegistry;
+use crate::document::line::ElRegistry;
+use crate::load::observer::{observe, LoadObserver};
+use crate::load::vars::Vars;
+use crate::load::report::{
+    Failed, Origin, Path, PathReporter, Report, Reporter, Stage,
+    StageReporter
+};
+use crate::load::yaml::{FromYaml, Value};
+use crate::types::{IntoMarked, Key, Location, Marked};
+use crate::types::enums::DeprecatedValue;
+use crate::validate::Validator;
+use self::arena::{KeyIndex, Slices};
+
+mod arena;
+pub mod write;
+
+
+//------------ StoreLoader ---------------------------------------------------
+
+/// The store during loading.
+#[derive(Debug)]
+pub struct StoreLoader {
+    data: Mutex<Vec<Option<Data>>>,
+    keys: Mutex<HashMap<Key, DocumentInfo>>,
+    failed: AtomicBool,
+
+    /// The named electrification systems.
+    electrification: ElRegistry,
+
+    /// The custom document types.
+    custom_types: CustomRegistry,
+
+    /// The variables of the data tree.
+    vars: Vars,
+
+    /// The files using expansions and their variable references.
+    expanded: Mutex<BTreeMap<Path, Vec<Location>>>,
+}
+
+
+#[derive(Debug)]
+struct DocumentInfo {
+    /// A link to this document.
+    link: DocumentLink,
+
+    /// The document type, if already known.
+    doctype: Option<DocumentType>,
+
+    /// The document origin, if already known.
+    ///
+    /// This also serves as an indication whether we have an actual document
+    /// for this key already.
+    origin: Option<Origin>,
+
+    /// A list of who linked to this document.
+    ///
+    /// The entries are the origin and optionally if they requested a certain
+    /// type.
+    linked_from: Vec<(Option<DocumentType>, Origin)>,
+
+    /// Is this a broken document?
+    broken: bool,
+}
+
+impl StoreLoader {
+    pub fn new() -> Self {
+        Self::with_electrification(ElRegistry::builtin())
+    }
+
+    /// Creates a loader using the given electrification systems.
+    pub fn with_electrification(electrification: ElRegistry) -> Self {
+        StoreLoader {
+            data: Mutex::new(Vec::new()),
+            keys: Mutex::new(HashMap::new()),
+            failed: AtomicBool::new(false),
+            electrification,
+            custom_types: CustomRegistry::default(),
+            vars: Vars::default(),
+            expanded: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Sets the custom document types to accept.
+    pub fn with_custom_types(mut self, custom_types: CustomRegistry) -> Self {
+        self.custom_types = custom_types;
+        self
+    }
+
+    /// Sets the variables available to documents.
+    pub fn with_vars(mut self, vars: Vars) -> Self {
+        self.vars = vars;
+        self
+    }
+
+    /// Returns the named electrification systems.
+    pub fn electrification(&self) -> &ElRegistry {
+        &self.electrification
+    }
+
+    /// Returns the custom document types.
+    pub fn custom_types(&self) -> &CustomRegistry {
+        &self.custom_types
+    }
+
+    /// Returns the variables available to documents.
+    pub fn vars(&self) -> &Vars {
+        &self.vars
+    }
+
+    /// Records that a file used aliases, merge keys, or variables.
+    ///
+    /// The documents of such a file don’t reflect its text exactly.
+    /// `references` are the locations of the variable references in the
+    /// file.
+    pub fn add_expanded(&self, path: Path, mut references: Vec<Location>) {
+        references.sort();
+        self.expanded.lock().unwrap().insert(path, references);
+    }
+
+    pub fn from_yaml(
+        &self,
+        value: Value,
+        report: &mut PathReporter
+    ) -> Result<(), Failed> {
+        let location = value.location();
+        let mut doc = value.into_mapping(report)?;
+        let key: Marked<Key> = doc.take("key", self, report)?;
+        let link = self.get_link(key.as_value());
+        let doctype = match doc.take("type", self, report) {
+            Ok(doctype) => doctype,
+            Err(_) => {
+                let _ = self.update_broken(
+                    key.as_value(), None, doc.location(), report
+                );
+                return Ok(())
+            }
+        };
+        match Data::from_yaml(
+            key.clone(), doctype, doc, link, self, report
+        ) {
+            Ok(doc) => self.update(link, doc, report),
+            Err(_) => {
+                self.update_broken(&key, Some(doctype), location, report)
+            }
+        }
+    }
+
+    pub fn insert(
+        &self,
+        data: Data,
+        report: &mut PathReporter
+    ) -> Result<(), Failed> {
+        let link = self.get_link(data.key());
+        self.update(link, data, report)
+    }
+
+    pub fn insert_broken(
+        &self,
+        key: Key,
+        doctype: Option<DocumentType>,
+        location: Location,
+        report: &mut PathReporter
+    ) -> Result<(), Failed> {
+        let _ = self.get_link(&key);
+        self.update_broken(&key, doctype, location, report)
+    }
+
+    fn get_link(
+        &self,
+        key: &Key,
+    ) -> DocumentLink {
+        let mut keys = self.keys.lock().unwrap();
+
+        if let Some(info) = keys.get_mut(key) {
+            return info.link
+        }
+
+        let link = self.push_none();
+        keys.insert(
+            key.clone(),
+            DocumentInfo {
+                link,
+                doctype: None,
+                origin: None,
+                linked_from: Vec::new(),
+                broken: false,
+            }
+        );
+        link
+    }
+
+    fn push_none(&self) -> DocumentLink {
+        let mut data = self.data.lock().unwrap();
+        let index = data.len();
+        data.push(None);
+        DocumentLink::from_index(index)
+    }
+
+    fn update(
+        &self, link: DocumentLink, document: Data, report: &mut PathReporter
+    ) -> Result<(), Failed> {
+        let mut keys = self.keys.lock().unwrap();
+
+        let info = keys.get_mut(document.key()).unwrap();
+
+        if info.origin.is_some() {
+            report.error(
+                DuplicateDocument(
+                    info.origin.clone().unwrap()
+                ).marked(document.origin().location())
+            );
+            self.failed.store(true, atomic::Ordering::Relaxed);
+            return Err(Failed);
+        }
+
+        info.doctype = Some(document.doctype());
+        info.origin = Some(document.origin().clone());
+        info.broken = false;
+
+        let old = mem::replace(
+            &mut self.data.lock().unwrap()[link.index],
+            Some(document)
+        );
+        assert!(old.is_none());
+        Ok(())
+    }
+
+    fn update_broken(
+        &self,
+        key: &Key,
+        doctype: Option<DocumentType>,
+        location: Location,
+        report: &mut PathReporter
+    ) -> Result<(), Failed> {
+        let mut keys = self.keys.lock().unwrap();
+
+        let info = keys.get_mut(key).unwrap();
+
+        if info.origin.is_some() {
+            report.error(
+                DuplicateDocument(
+                    info.origin.clone().unwrap()
+                ).marked(location)
+            );
+            self.failed.store(true, atomic::Ordering::Relaxed);
+            return Err(Failed);
+        }
+            
+        info.doctype = doctype;
+        info.origin = Some(report.origin(location));
+        info.broken = true;
+        Ok(())
+    }
+
+    pub fn build_link(
+        &self,
+        key: Marked<Key>,
+        doctype: Option<DocumentType>,
+        report: &mut PathReporter
+    ) -> Marked<DocumentLink> {
+        let location = key.location();
+        let mut keys = self.keys.lock().unwrap();
+
+        if let Some(info) = keys.get_mut(key.as_ref()) {
+            // We don’t check link types here just yet. That happens once
+            // when converting to a LibraryMut for all links.
+            info.linked_from.push((doctype, report.origin(key.location())));
+            return info.link.marked(location)
+        }
+
+        let link = self.push_none();
+        keys.insert(
+            key.into_value(),
+            DocumentInfo {
+                link,
+                doctype: None,
+                origin: None,
+                linked_from: vec![(doctype, report.origin(location))],
+                broken: false
+            }
+        );
+        link.marked(location)
+    }
+
+    pub fn into_data_store(
+        self, report: &mut StageReporter
+    ) -> Result<DataStore, Failed> {
+        self.into_store(false, report)
+    }
+
+    /// Converts the loader into a data store despite errors.
+    ///
+    /// All problems are reported as usual. However, documents that failed
+    /// to load or are missing are replaced by placeholders, as are
+    /// documents that link to documents of the wrong type. If the type of
+    /// a broken document is unknown, it is taken from the links to it or
+    /// the placeholder becomes an entity.
+    pub fn into_data_store_forced(
+        self, report: &mut StageReporter
+    ) -> DataStore {
+        // into_store never fails when forced.
+        self.into_store(true, report).unwrap()
+    }
+
+    fn into_store(
+        self, force: bool, report: &mut StageReporter
+    ) -> Result<DataStore, Failed> {
+        let mut data = self.data.into_inner().unwrap();
+        let docinfo = self.keys.into_inner().unwrap();
+
+        let mut failed = self.failed.load(atomic::Ordering::Relaxed);
+        let mut keys = Vec::new();
+        let mut referrers = vec![Vec::new(); data.len()];
+        let mut placeholders = Vec::new();
+        let mut mismatched = Vec::new();
+        for (key, info) in docinfo {
+            // If the document is broken, there was an error before and we
+            // don’t need to worry about it. But, we said failed just so we
+            // stop.
+            if info.broken {
+                failed = true;
+            }
+
+            // If origin is None, we have a missing document. All links are
+            // errors.
+            if info.origin.is_none() {
+                for &(_, ref origin) in &info.linked_from {
+                    report.error_at(
+                        origin.clone(), MissingDocument(key.clone())
+                    );
+                }
+                failed = true;
+            }
+
+            // All links that have a differing doctype are bad.
+            if let Some(target) = info.doctype {
+                for (expected, origin) in &info.linked_from {
+                    if let Some(expected) = *expected {
+                        if expected != target {
+                            report.error_at(
+                                origin.clone(),
+                                LinkMismatch { expected, target }
+                            );
+                            mismatched.push(origin.clone());
+                            failed = true;
+                        }
+                    }
+                }
+            }
+
+            if force && data[info.link.index].is_none() {
+                let doctype = info.doctype.or_else(|| {
+                    info.linked_from.iter().find_map(|item| item.0)
+                }).unwrap_or(DocumentType::Entity);
+                let origin = match info.origin {
+                    Some(ref origin) => origin.clone(),
+                    None => info.linked_from[0].1.at(Location::NONE),
+                };
+
+                // Links expecting a different type weren’t checked above.
+                if info.doctype.is_none() {
+                    for (expected, origin) in &info.linked_from {
+                        if expected.is_some_and(|item| item != doctype) {
+                            mismatched.push(origin.clone())
+                        }
+                    }
+                }
+
+                data[info.link.index] = Some(Data::placeholder(
+                    key.clone().into(), doctype, info.link, origin
+                ));
+                placeholders.push(info.link);
+            }
+
+            if !failed || force {
+                keys.push((key, info.link));
+                referrers[info.link.index] = info.linked_from.into_iter().map(
+                    |(_, origin)| origin
+                ).collect();
+            }
+        }
+        if failed && !force {
+            return Err(Failed)
+        }
+
+        placeholders.sort();
+        let mut res = DataStore::new(
+            data.into_iter().map(Option::unwrap).collect(),
+            keys.into_iter().collect(),
+            referrers.into_iter().collect(),
+            self.electrification,
+            self.custom_types,
+            self.vars,
+        );
+        res.placeholders = placeholders;
+        res.expanded = self.expanded.into_inner().unwrap();
+
+        // Documents with mismatched links are replaced, too.
+        for origin in mismatched {
+            if let Some(link) = res.find_origin(&origin) {
+                res.make_placeholder(link)
+            }
+        }
+        Ok(res)
+    }
+
+    /// Checks the loaded documents against an existing store.
+    ///
+    /// This allows checking a few files without loading all the data
+    /// again. Links are resolved to documents loaded here first and to
+    /// documents of `store` otherwise. A loaded document replaces the
+    /// document with the same key in `store` if both come from the same
+    /// file. Otherwise it is a duplicate.
+    pub fn check_against(
+        self, store: &DataStore, report: &mut StageReporter
+    ) -> Result<(), Failed> {
+        let docinfo = self.keys.into_inner().unwrap();
+        let mut failed = self.failed.load(atomic::Ordering::Relaxed);
+        for (key, info) in docinfo {
+            if info.broken {
+                failed = true;
+            }
+
+            let existing = store.get(&key).map(|link| link.data(store));
+            if let (Some(origin), Some(existing)) = (&info.origin, existing) {
+                if !same_file(origin.path(), existing.origin().path()) {
+                    report.error_at(
+                        origin.clone(),
+                        DuplicateDocument(existing.origin().clone())
+                    );
+                    failed = true;
+                }
+            }
+            else if info.origin.is_none() && existing.is_none() {
+                for (_, origin) in &info.linked_from {
+                    report.error_at(
+                        origin.clone(), MissingDocument(key.clone())
+                    );
+                }
+                failed = true;
+            }
+
+            let target = info.doctype.or_else(|| {
+                existing.map(|data| data.doctype())
+            });
+            if let Some(target) = target {
+                for (expected, origin) in &info.linked_from {
+                    if let Some(expected) = *expected {
+                        if expected != target {
+                            report.error_at(
+                                origin.clone(),
+                                LinkMismatch { expected, target }
+                            );
+                            failed = true;
+                        }
+                    }
+                }
+            }
+        }
+        if failed {
+            Err(Failed)
+        }
+        else {
+            Ok(())
+        }
+    }
+}
+
+/// Returns whether two paths refer to the same file.
+fn same_file(left: &Path, right: &Path) -> bool {
+    match (left.canonicalize(), right.canonicalize()) {
+        (Ok(left), Ok(right)) => left == right,
+        _ => left == right
+    }
+}
+
+
+//------------ DataStore -----------------------------------------------------
+
+/// A store holding the data portion of documents only.
+#[derive(Debug)]
+pub struct DataStore {
+    data: Vec<Data>,
+
+    /// The links of all documents ordered by key.
+    keys: KeyIndex,
+
+    /// The origins of all links to each document.
+    referrers: Slices<Origin>,
+
+    /// The named electrification systems used by the data.
+    electrification: ElRegistry,
+
+    /// The custom document types used by the data.
+    custom_types: CustomRegistry,
+
+    /// The variables of the data tree.
+    vars: Vars,
+
+    /// The files using expansions and their variable references.
+    expanded: BTreeMap<Path, Vec<Location>>,
+
+    /// The sorted links of documents replaced by placeholders.
+    placeholders: Vec<DocumentLink>,
+
+    /// The places where deprecated enum values were used.
+    deprecated: Vec<(Origin, DeprecatedValue)>,
+
+    /// The license and attribution of the data tree.
+    attribution: Attribution,
+}
+
+impl DataStore {
+    fn new(
+        data: Vec<Data>,
+        keys: KeyIndex,
+        referrers: Slices<Origin>,
+        electrification: ElRegistry,
+        custom_types: CustomRegistry,
+        vars: Vars,
+    ) -> Self {
+        DataStore {
+            data, keys, referrers, electrification, custom_types, vars,
+            expanded: BTreeMap::new(),
+            placeholders: Vec::new(),
+            deprecated: Vec::new(),
+            attribution: Attribution::default(),
+        }
+    }
+
+    pub fn into_xref_store(
+        self, report: StageReporter
+    ) -> Result<XrefsStore, Failed> {
+        XrefsStore::generate(self, report, false)
+    }
+
+    pub fn into_full_store(self) -> Result<FullStore, Report> {
+        self.into_full_store_observed(&())
+    }
+
+    /// Converts the store into a full store informing `observer`.
+    pub fn into_full_store_observed(
+        self, observer: &dyn LoadObserver
+    ) -> Result<FullStore, Report> {
+        let report = Reporter::new();
+
+        // Generate the cross references.
+        let store = match observe(observer, Stage::Crossref, || {
+            self.into_xref_store(report.clone().stage(Stage::Crossref))
+        }) {
+            Ok(store) => store,
+            Err(_) => return Err(report.unwrap())
+        };
+
+        // Generate meta data.
+        match observe(observer, Stage::Meta, || {
+            store.into_full_store(report.clone().stage(Stage::Meta))
+        }) {
+            Ok(store) => Ok(store),
+            Err(_) => Err(report.unwrap())
+        }
+    }
+
+    /// Converts the store into a full store despite errors.
+    ///
+    /// Documents for which cross references or metadata can’t be
+    /// generated are replaced by placeholders. The returned report
+    /// contains all problems encountered on the way.
+    pub fn into_full_store_forced(
+        self, observer: &dyn LoadObserver
+    ) -> (FullStore, Report) {
+        let report = Reporter::new();
+        let store = observe(observer, Stage::Crossref, || {
+            XrefsStore::generate(
+                self, report.clone().stage(Stage::Crossref), true
+            )
+        }).unwrap();
+        let store = observe(observer, Stage::Meta, || {
+            FullStore::generate(
+                store, report.clone().stage(Stage::Meta), true
+            )
+        }).unwrap();
+        (store, report.unwrap())
+    }
+
+    /// Checks the data for likely mistakes.
+    ///
+    /// Runs all rules of [`validate`][crate::validate]. Unlike the errors
+    /// found while loading, the problems found here don’t make the data
+    /// unusable, so the report only contains warnings.
+    pub fn check(&self) -> Report {
+        Validator::new().run(self)
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<DocumentLink>
+    where Key: borrow::Borrow<Q>, Q: Ord + ?Sized {
+        self.keys.get(key)
+    }
+
+    pub fn links(&self) -> impl Iterator<Item = DocumentLink> + '_ {
+        self.keys.links()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&'_ Data> + '_ {
+        self.keys.links().map(move |link| self.resolve(link))
+    }
+
+    /// Returns an iterator over mutable references to all documents.
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item=&'_ mut Data> {
+        self.data.iter_mut()
+    }
+
+    pub fn iter_from<T>(
+        &self,
+        start: &T
+    ) -> impl Iterator<Item=&'_ Data> + '_
+    where T: Ord + ?Sized, Key: borrow::Borrow<T> {
+        self.keys.links_from(start).map(move |link| self.resolve(link))
+    }
+
+    /// Returns the origins of all links to the given document.
+    pub fn referrers(&self, link: DocumentLink) -> &[Origin] {
+        &self.referrers[link.index]
+    }
+
+    /// Returns the named electrification systems used by the data.
+    pub fn electrification(&self) -> &ElRegistry {
+        &self.electrification
+    }
+
+    /// Returns the custom document types used by the data.
+    pub fn custom_types(&self) -> &CustomRegistry {
+        &self.custom_types
+    }
+
+    /// Returns the variables of the data tree.
+    pub fn vars(&self) -> &Vars {
+        &self.vars
+    }
+
+    /// Returns whether a file used aliases, merge keys, or variables.
+    ///
+    /// The documents of such a file contain values that were copied from
+    /// elsewhere, so the file can’t be recreated from them.
+    pub fn is_expanded(&self, path: &Path) -> bool {
+        self.expanded.contains_key(path)
+    }
+
+    /// Returns whether the origin is a reference to a variable.
+    ///
+    /// All values of a variable are located at the reference, so links
+    /// contained in the variable have this origin.
+    pub fn is_reference(&self, origin: &Origin) -> bool {
+        self.expanded.get(origin.path()).map(|references| {
+            references.binary_search(&origin.location()).is_ok()
+        }).unwrap_or(false)
+    }
+
+    /// Returns whether the linked document is a placeholder.
+    ///
+    /// Placeholders only appear in stores loaded despite errors. They
+    /// stand in for documents that failed to load.
+    pub fn is_placeholder(&self, link: DocumentLink) -> bool {
+        self.placeholders.binary_search(&link).is_ok()
+    }
+
+    /// Returns the links of all placeholder documents.
+    pub fn placeholders(&self) -> &[DocumentLink] {
+        &self.placeholders
+    }
+
+    /// Returns where deprecated enum values were used while loading.
+    ///
+    /// The items are sorted by origin.
+    pub fn deprecated(&self) -> &[(Origin, DeprecatedValue)] {
+        &self.deprecated
+    }
+
+    /// Returns the license and attribution of the data tree.
+    pub fn attribution(&self) -> &Attribution {
+        &self.attribution
+    }
+
+    pub fn set_attribution(&mut self, attribution: Attribution) {
+        self.attribution = attribution
+    }
+
+    /// Returns the attribution of the given documents as JSON.
+    ///
+    /// The result contains the attribution of the data tree and, under
+    /// `documents`, that of all documents that have their own.
+    pub fn attribution_to_json(
+        &self, links: impl IntoIterator<Item = DocumentLink>
+    ) -> JsonValue {
+        let mut res = self.attribution.to_json();
+        res["documents"] = links.into_iter().filter_map(|link| {
+            let common = link.data(self).common();
+            let mut res = common.attribution.as_ref()?.to_json();
+            res["key"] = common.key.as_str().into();
+            Some(res)
+        }).collect::<Vec<_>>().into();
+        res
+    }
+
+    /// Collects the deprecated enum values reported while loading.
+    pub(crate) fn set_deprecated(&mut self, report: &Report) {
+        self.deprecated = report.iter().filter_map(|notice| {
+            Some((
+                notice.origin()?.clone(),
+                notice.message_as::<DeprecatedValue>()?.clone()
+            ))
+        }).collect();
+        self.deprecated.sort_by(|left, right| left.0.cmp(&right.0));
+    }
+
+    /// Replaces the linked document with a placeholder.
+    fn make_placeholder(&mut self, link: DocumentLink) {
+        let data = &self.data[link.index];
+        self.data[link.index] = Data::placeholder(
+            data.common().key.clone(), data.doctype(), link,
+            data.origin().clone()
+        );
+        if let Err(index) = self.placeholders.binary_search(&link) {
+            self.placeholders.insert(index, link)
+        }
+    }
+
+    /// Returns the document defined at `origin`.
+    ///
+    /// This is the document in the same file that starts last before
+    /// `origin`.
+    fn find_origin(&self, origin: &Origin) -> Option<DocumentLink> {
+        self.data.iter().enumerate().filter(|(_, data)| {
+            data.origin().path() == origin.path()
+                && data.location() <= origin.location()
+        }).max_by_key(|(_, data)| data.location()).map(|(index, _)| {
+            DocumentLink::from_index(index)
+        })
+    }
+}
+
+impl LinkTarget<Data> for DataStore {
+    fn resolve(&self, link: DocumentLink) -> &Data {
+        &self.data[link.index]
+    }
+}
+
+
+//------------ XrefsBuilder --------------------------------------------------
+
+#[derive(Debug)]
+pub struct XrefsBuilder {
+    xrefs: Vec<Xrefs>,
+}
+
+impl XrefsBuilder {
+    fn new(store: &DataStore) -> Self {
+        XrefsBuilder {
+            xrefs: store.data.iter().map(Data::default_xrefs).collect()
+        }
+    }
+
+    fn finalize(&mut self, store: &DataStore) {
+        self.xrefs.iter_mut().for_each(|item| item.finalize(&store));
+    }
+
+}
+
+impl LinkTargetMut<Xrefs> for XrefsBuilder {
+    fn resolve_mut(&mut self, link: DocumentLink) -> &mut Xrefs {
+        &mut self.xrefs[link.index]
+    }
+}
+
+
+//------------ XrefsStore -----------------------------------------------------
+
+/// A store holding the data and cross references of documents.
+#[derive(Debug)]
+pub struct XrefsStore {
+    data: DataStore,
+    xrefs: Vec<Xrefs>,
+}
+
+impl XrefsStore {
+    /// Generates the cross references for `data`.
+    ///
+    /// If `force` is true, documents that fail are replaced with
+    /// placeholders and generation starts over.
+    fn generate(
+        mut data: DataStore, mut report: StageReporter, force: bool
+    ) -> Result<Self, Failed> {
+        let mut xrefs = XrefsBuilder::new(&data);
+        let mut failed = Vec::new();
+        for (index, item) in data.data.iter().enumerate() {
+            if item.xrefs(&mut xrefs, &data, &mut report).is_err() {
+                failed.push(DocumentLink::from_index(index));
+            }
+        }
+        if !failed.is_empty() {
+            if !force {
+                return Err(Failed)
+            }
+            for link in failed {
+                data.make_placeholder(link)
+            }
+
+            // The problems have been reported already.
+            let mut report = Reporter::new().stage(Stage::Crossref);
+            xrefs = XrefsBuilder::new(&data);
+            for item in &data.data {
+                let _ = item.xrefs(&mut xrefs, &data, &mut report);
+            }
+        }
+        xrefs.finalize(&data);
+        Ok(XrefsStore {
+            data,
+            xrefs: xrefs.xrefs,
+        })
+    }
+
+    pub fn into_full_store(
+        self, report: StageReporter
+    ) -> Result<FullStore, Failed> {
+        FullStore::generate(self, report, false)
+    }
+
+    pub fn links(&self) -> impl Iterator<Item = DocumentLink> + '_ {
+        self.data.links()
+    }
+}
+
+impl LinkTarget<Data> for XrefsStore {
+    fn resolve(&self, link: DocumentLink) -> &Data {
+        self.data.resolve(link)
+    }
+}
+
+impl LinkTarget<Xrefs> for XrefsStore {
+    fn resolve(&self, link: DocumentLink) -> &Xrefs {
+        &self.xrefs[link.index]
+    }
+}
+
+impl AsRef<DataStore> for XrefsStore {
+    fn as_ref(&self) -> &DataStore {
+        &self.data
+    }
+}
+
+
+//------------ MetaStore -----------------------------------------------------
+
+/// The store while the metadata is generated.
+///
+/// Besides the data and cross references, this gives access to the
+/// metadata of the document types the current type depends on.
+pub struct MetaStore<'a> {
+    xrefs: &'a XrefsStore,
+    meta: &'a [Option<Meta>],
+}
+
+impl LinkTarget<Data> for MetaStore<'_> {
+    fn resolve(&self, link: DocumentLink) -> &Data {
+        self.xrefs.resolve(link)
+    }
+}
+
+impl LinkTarget<Xrefs> for MetaStore<'_> {
+    fn resolve(&self, link: DocumentLink) -> &Xrefs {
+        self.xrefs.resolve(link)
+    }
+}
+
+impl LinkTarget<Meta> for MetaStore<'_> {
+    /// Returns the metadata of a document.
+    ///
+    /// # Panics
+    ///
+    /// The method panics if the metadata of the document hasn’t been
+    /// generated yet because its type isn’t declared as a dependency.
+    fn resolve(&self, link: DocumentLink) -> &Meta {
+        match self.meta[link.index].as_ref() {
+            Some(meta) => meta,
+            None => {
+                panic!(
+                    "metadata of a {} document used before generated",
+                    LinkTarget::<Data>::resolve(
+                        self.xrefs, link
+                    ).doctype().as_str()
+                )
+            }
+        }
+    }
+}
+
+/// Returns the document types grouped by the phase of metadata generation.
+///
+/// The types of each phase only depend on types of earlier phases.
+///
+/// # Panics
+///
+/// The function panics if the dependencies are circular.
+fn meta_phases() -> Vec<Vec<DocumentType>> {
+    let mut done: Vec<DocumentType> = Vec::new();
+    let mut res = Vec::new();
+    while done.len() < DocumentType::ALL.len() {
+        let phase: Vec<_> = DocumentType::ALL.iter().copied().filter(|ty| {
+            !done.contains(ty) && Meta::dependencies(*ty).iter().all(|dep| {
+                done.contains(dep)
+            })
+        }).collect();
+        if phase.is_empty() {
+            panic!("circular metadata dependencies")
+        }
+        done.extend_from_slice(&phase);
+        res.push(phase);
+    }
+    res
+}
+
+
+//------------ FullStore -----------------------------------------------------
+
+/// The store with both the data and the meta data.
+#[derive(Debug)]
+pub struct FullStore {
+    xrefs: XrefsStore,
+    meta: Vec<Meta>,
+}
+
+impl FullStore {
+    /// Generates the metadata for `store`.
+    ///
+    /// The metadata is generated in phases so that the metadata of the
+    /// document types declared via [`Meta::dependencies`] is complete
+    /// before it is needed. Within a phase, documents are processed in
+    /// parallel.
+    ///
+    /// If `force` is true, documents that fail are replaced with
+    /// placeholders. Otherwise generation stops after the first phase
+    /// with failed documents.
+    fn generate(
+        mut store: XrefsStore, report: StageReporter, force: bool
+    ) -> Result<Self, Failed> {
+        let mut meta: Vec<Option<Meta>> = Vec::new();
+        meta.resize_with(store.data.len(), Default::default);
+        for phase in meta_phases() {
+            let generated: Vec<_> = {
+                let view = MetaStore { xrefs: &store, meta: &meta };
+                store.data.data.par_iter().enumerate().filter(|(_, data)| {
+                    phase.contains(&data.doctype())
+                }).map(|(index, data)| {
+                    (index, Meta::generate(data, &view, &mut report.clone()))
+                }).collect()
+            };
+            let mut failed = Vec::new();
+            for (index, res) in generated {
+                match res {
+                    Ok(res) => meta[index] = Some(res),
+                    Err(_) => failed.push(DocumentLink::from_index(index)),
+                }
+            }
+            if failed.is_empty() {
+                continue
+            }
+            if !force {
+                return Err(Failed)
+            }
+            for link in failed.iter().copied() {
+                store.data.make_placeholder(link);
+            }
+            let generated: Vec<_> = {
+                let view = MetaStore { xrefs: &store, meta: &meta };
+                failed.into_iter().map(|link| {
+                    (link, Meta::generate(
+                        link.data(&store), &view, &mut report.clone()
+                    ).ok())
+                }).collect()
+            };
+            for (link, res) in generated {
+                meta[link.index] = res
+            }
+        }
+        Ok(FullStore {
+            xrefs: store,
+            // Metadata for placeholders can always be generated.
+            meta: meta.into_iter().map(Option::unwrap).collect(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.xrefs.data.len()
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<DocumentLink>
+    where Key: borrow::Borrow<Q>, Q: Ord + ?Sized {
+        self.xrefs.data.keys.get(key)
+    }
+
+    pub fn links(&self) -> impl Iterator<Item = DocumentLink> + '_ {
+        self.xrefs.links()
+    }
+
+    pub fn iter_from<T>(
+        &self,
+        start: &T
+    ) -> impl Iterator<Item = DocumentLink> + '_
+    where T: Ord + ?Sized, Key: borrow::Borrow<T> {
+        self.xrefs.data.keys.links_from(start)
+    }
+
+    /// Returns a view of the documents with at least the given progress.
+    pub fn with_min_progress(&self, min: Progress) -> ProgressView<'_> {
+        ProgressView { store: self, min }
+    }
+
+    /// Returns a view of the documents that are ready for release.
+    ///
+    /// These are all documents with a progress of ‘complete.’
+    pub fn released_only(&self) -> ProgressView<'_> {
+        self.with_min_progress(Progress::Complete)
+    }
+}
+
+impl LinkTarget<Data> for FullStore {
+    fn resolve(&self, link: DocumentLink) -> &Data {
+        self.xrefs.resolve(link)
+    }
+}
+
+impl LinkTarget<Xrefs> for FullStore {
+    fn resolve(&self, link: DocumentLink) -> &Xrefs {
+        self.xrefs.resolve(link)
+    }
+}
+
+impl LinkTarget<Meta> for FullStore {
+    fn resolve(&self, link: DocumentLink) -> &Meta {
+        &self.meta[link.index]
+    }
+}
+
+impl AsRef<DataStore> for FullStore {
+    fn as_ref(&self) -> &DataStore {
+        self.xrefs.as_ref()
+    }
+}
+
+impl AsRef<XrefsStore> for FullStore {
+    fn as_ref(&self) -> &XrefsStore {
+        &self.xrefs
+    }
+}
+
+
+//------------ ProgressView --------------------------------------------------
+
+/// A view of a full store limited by the progress of documents.
+///
+/// The view only hides documents when looking them up or iterating over
+/// them. Links from visible documents to hidden ones can still be followed
+/// via the underlying store, so users should check them with
+/// [`contains`][Self::contains] where that matters.
+#[derive(Clone, Copy, Debug)]
+pub struct ProgressView<'a> {
+    store: &'a FullStore,
+    min: Progress,
+}
+
+impl<'a> ProgressView<'a> {
+    /// Returns the underlying store.
+    pub fn store(self) -> &'a FullStore {
+        self.store
+    }
+
+    /// Returns the minimum progress of documents in the view.
+    pub fn min_progress(self) -> Progress {
+        self.min
+    }
+
+    /// Returns whether the linked document is part of the view.
+    pub fn contains(self, link: DocumentLink) -> bool {
+        link.data(self.store).common().progress.into_value() >= self.min
+    }
+
+    /// Returns the number of documents in the view.
+    pub fn len(self) -> usize {
+        self.links().count()
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.links().next().is_none()
+    }
+
+    pub fn get<Q>(self, key: &Q) -> Option<DocumentLink>
+    where Key: borrow::Borrow<Q>, Q: Ord + ?Sized {
+        self.store.get(key).filter(|link| self.contains(*link))
+    }
+
+    pub fn links(self) -> impl Iterator<Item = DocumentLink> + 'a {
+        self.store.links().filter(move |link| self.contains(*link))
+    }
+
+    pub fn iter_from<T>(
+        self,
+        start: &T
+    ) -> impl Iterator<Item = DocumentLink> + 'a
+    where T: Ord + ?Sized, Key: borrow::Borrow<T> {
+        self.store.iter_from(start).filter(move |link| self.contains(*link))
+    }
+}
+
+impl<'a> From<&'a FullStore> for ProgressView<'a> {
+    /// Returns a view of all documents of the store.
+    fn from(store: &'a FullStore) -> Self {
+        store.with_min_progress(Progress::Stub)
+    }
+}
+
+
+//------------ DocumentLink --------------------------------------------------
+
+/// A link to another document.
+///
+/// Links remain stable between all stores derived from the same
+/// [`StoreLoader`] instance.
+///
+/// With serde, a link is serialized as the index of the linked document
+/// and thus is only meaningful together with its store.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct DocumentLink {
+    index: usize,
+}
+
+impl DocumentLink {
+    fn from_index(index: usize) -> Self {
+        DocumentLink { index }
+    }
+
+    pub fn document(self, store: &FullStore) -> Document {
+        Document::new(self.data(store), self.xrefs(store), self.meta(store))
+    }
+
+    pub fn data(self, store: &impl LinkTarget<Data>) -> &Data {
+        store.resolve(self)
+    }
+
+    pub fn xrefs(self, store: &impl LinkTarget<Xrefs>) -> &Xrefs {
+        store.resolve(self)
+    }
+
+    pub fn xrefs_mut(
+        self, store: &mut impl LinkTargetMut<Xrefs>
+    ) -> &mut Xrefs {
+        store.resolve_mut(self)
+    }
+
+    pub fn meta(self, store: &impl LinkTarget<Meta>) -> &Meta {
+        store.resolve(self)
+    }
+}
+
+impl FromYaml<StoreLoader> for Marked<DocumentLink> {
+    fn from_yaml(
+        value: Value,
+        context: &StoreLoader,
+        report: &mut PathReporter
+    ) -> Result<Self, Failed> {
+        Ok(context.build_link(
+            Marked::from_yaml(value, context, report)?, None, report
+        ))
+    }
+}
+
+
+//------------ LinkTarget ----------------------------------------------------
+
+pub trait LinkTarget<T> {
+    fn resolve(&self, link: DocumentLink) -> &T;
+}
+
+impl<T, U: LinkTarget<T>> LinkTarget<T> for Arc<U> {
+    fn resolve(&self, link: DocumentLink) -> &T {
+        self.as_ref().resolve(link)
+    }
+}
+
+pub trait LinkTargetMut<T> {
+    fn resolve_mut(&mut self, link: DocumentLink) -> &mut T;
+}
+
+
+
+//============ Errors ========================================================
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="document already exists, first defined at {}", _0)]
+pub struct DuplicateDocument(Origin);
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="link to '{}', expected '{}'", target, expected)]
+pub struct LinkMismatch {
+    expected: DocumentType,
+    target: DocumentType
+}
+
+impl LinkMismatch {
+    pub fn new(expected: DocumentType, target: DocumentType) -> Self {
+        LinkMismatch { expected, target }
+    }
+
+    pub fn expected(&self) -> DocumentType {
+        self.expected
+    }
+
+    pub fn target(&self) -> DocumentType {
+        self.target
+    }
+}
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="link to missing document '{}'", _0)]
+pub struct MissingDocument(Key);
+
+
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use serde_json::Value as JsonValue;
+    use crate::testdata::Fixture;
+    use super::FullStore;
+
+    /// Documents with cross references, in no particular order.
+    const DOCUMENTS: &[&str] = &[
+        "key: source.de.kursbuch\ntype: source\ntitle: Kursbuch 1914\n\
+         regards: [line.de.1001, point.de.bm, line.de.1000]\n",
+        "key: source.de.kb-11\ntype: source\ntitle: Strecke 11\n\
+         collection: source.de.kursbuch\n\
+         regards: [point.de.dw, point.de.aa]\n",
+        "key: source.de.kb-2\ntype: source\ntitle: Strecke 2\n\
+         collection: source.de.kursbuch\n\
+         regards: [point.de.bm]\n",
+        "key: point.de.aa\ntype: point\nevents:\n- name: Altstadt\n",
+        "key: point.de.bm\ntype: point\nevents:\n- name: Bergmünde\n",
+        "key: point.de.cd\ntype: point\nevents:\n- name: Chausseedorf\n",
+        "key: point.de.dw\ntype: point\nevents:\n- name: Dornwalde\n",
+        "key: line.de.1001\ntype: line\n\
+         points: [point.de.bm, point.de.cd, point.de.dw]\n",
+        "key: line.de.1000\ntype: line\n\
+         points: [point.de.aa, point.de.bm]\n",
+        "key: line.de.1002\ntype: line\n\
+         points: [point.de.dw, point.de.bm, point.de.aa]\n",
+    ];
+
+    /// Loads the documents in the given order.
+    fn load<'a>(
+        documents: impl Iterator<Item = &'a &'a str>
+    ) -> FullStore {
+        documents.fold(Fixture::new(), |fixture, document| {
+            fixture.yaml(document)
+        }).into_full_store().unwrap_or_else(|report| {
+            let problems: Vec<_> = report.iter().map(|item| {
+                item.to_string()
+            }).collect();
+            panic!("fixture failed to load: {:?}", problems)
+        })
+    }
+
+    /// Returns the cross references of all documents in key order.
+    fn snapshot(store: &FullStore) -> Vec<(String, JsonValue)> {
+        store.links().map(|link| {
+            (
+                link.data(store).key().to_string(),
+                link.xrefs(store).to_json(store.as_ref())
+            )
+        }).collect()
+    }
+
+    #[test]
+    fn xrefs_independent_of_order() {
+        let forward = load(DOCUMENTS.iter());
+        let backward = load(DOCUMENTS.iter().rev());
+        assert_eq!(snapshot(&forward), snapshot(&backward));
+    }
+}
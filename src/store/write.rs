@@ -0,0 +1,375 @@
+//! Writing documents as YAML.
+//!
+//! This module turns the data of a document back into YAML so that tools
+//! can modify documents programmatically and write them back. The output
+//! is canonical: attributes appear in a fixed order, attributes with empty
+//! or default values are left out, lists with a single element are written
+//! as a plain value, and the elements of sets are sorted.
+//!
+//! Links to other documents are written as the key of the linked document
+//! which is why writing needs access to a [`DataStore`]. Path documents are
+//! kept in OSM files and can’t be written as YAML.
+//!
+//! With [`format_files`], all document files of a store can be rewritten
+//! in canonical form.
+
+use std::{fmt, fs, io};
+use std::collections::BTreeMap;
+use derive_more::Display;
+use yaml_rust::{EmitError, Yaml, YamlEmitter};
+use yaml_rust::yaml::Hash;
+use crate::document::combined::Data;
+use crate::document::common::DocumentType;
+use crate::load::report::Path;
+use super::{DataStore, DocumentLink};
+
+
+//------------ write_document ------------------------------------------------
+
+/// Writes a document as a YAML document to `target`.
+///
+/// The output starts with a document start marker and ends in a line
+/// feed, so several documents can be written into the same file.
+pub fn write_document(
+    target: &mut impl fmt::Write,
+    data: &Data,
+    store: &DataStore
+) -> Result<(), WriteError> {
+    let yaml = document_to_yaml(data, store).ok_or(
+        WriteError::Unsupported(data.doctype())
+    )?;
+    YamlEmitter::new(target).dump(&yaml)?;
+    target.write_char('\n').map_err(EmitError::from)?;
+    Ok(())
+}
+
+/// Converts a document into a YAML value.
+///
+/// Returns `None` if the document can’t be expressed in YAML.
+pub fn document_to_yaml(data: &Data, store: &DataStore) -> Option<Yaml> {
+    match *data {
+        Data::Line(ref inner) => Some(inner.to_yaml(store)),
+        Data::Entity(ref inner) => Some(inner.to_yaml(store)),
+        Data::Path(_) => None,
+        Data::Point(ref inner) => Some(inner.to_yaml(store)),
+        Data::Service(ref inner) => Some(inner.to_yaml(store)),
+        Data::Source(ref inner) => Some(inner.to_yaml(store)),
+        Data::Structure(ref inner) => Some(inner.to_yaml(store)),
+        Data::Custom(ref inner) => Some(inner.to_yaml(store)),
+    }
+}
+
+
+//------------ format_files --------------------------------------------------
+
+/// Rewrites all document files of a store in canonical form.
+///
+/// The documents are grouped by the file they were loaded from and written
+/// back in their original order. Files whose content doesn’t change are
+/// left untouched. If `check` is `true`, no files are written at all.
+///
+/// Files that use aliases, merge keys, or variables are skipped since
+/// their documents contain copies of the referenced values which would
+/// be written out in full. Because the files are generated from the loaded
+/// data, files containing comments are skipped, too, as the comments
+/// would be lost.
+pub fn format_files(
+    store: &DataStore, check: bool
+) -> Result<Formatted, FormatError> {
+    let mut files = BTreeMap::<_, Vec<_>>::new();
+    for data in store.iter() {
+        if data.doctype() == DocumentType::Path {
+            continue
+        }
+        files.entry(data.origin().path().clone()).or_default().push(data);
+    }
+
+    let mut res = Formatted::default();
+    for (path, mut docs) in files {
+        if store.is_expanded(&path) {
+            res.skipped.push(path);
+            continue
+        }
+        let old = fs::read_to_string(&path).map_err(|err| {
+            FormatError::Io(path.clone(), err)
+        })?;
+        if has_comments(&old) {
+            res.commented.push(path);
+            continue
+        }
+        docs.sort_by_key(|data| data.location());
+        let mut content = String::new();
+        for data in docs {
+            write_document(&mut content, data, store).map_err(|err| {
+                FormatError::Write(path.clone(), err)
+            })?;
+        }
+        if old == content {
+            continue
+        }
+        if !check {
+            fs::write(&path, content).map_err(|err| {
+                FormatError::Io(path.clone(), err)
+            })?;
+        }
+        res.changed.push(path);
+    }
+    Ok(res)
+}
+
+
+//------------ Formatted -----------------------------------------------------
+
+/// The outcome of [`format_files`].
+#[derive(Debug, Default)]
+pub struct Formatted {
+    /// The files that have changed or, when checking, would change.
+    pub changed: Vec<Path>,
+
+    /// The files that weren’t formatted because they use expansions.
+    pub skipped: Vec<Path>,
+
+    /// The files that weren’t formatted because they contain comments.
+    pub commented: Vec<Path>,
+}
+
+
+//------------ ToYaml --------------------------------------------------------
+
+/// A type that can be converted into a YAML value.
+pub trait ToYaml {
+    fn to_yaml(&self, store: &DataStore) -> Yaml;
+
+    /// Returns whether the value is left out when used as an attribute.
+    fn is_omitted(&self) -> bool {
+        false
+    }
+}
+
+impl<T: ToYaml> ToYaml for Option<T> {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        match *self {
+            Some(ref value) => value.to_yaml(store),
+            None => Yaml::Null,
+        }
+    }
+
+    fn is_omitted(&self) -> bool {
+        self.is_none()
+    }
+}
+
+impl<T: ToYaml> ToYaml for Vec<T> {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        Yaml::Array(self.iter().map(|item| item.to_yaml(store)).collect())
+    }
+
+    fn is_omitted(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+impl<T: ToYaml + ?Sized> ToYaml for &T {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        (*self).to_yaml(store)
+    }
+
+    fn is_omitted(&self) -> bool {
+        (*self).is_omitted()
+    }
+}
+
+impl ToYaml for bool {
+    fn to_yaml(&self, _: &DataStore) -> Yaml {
+        Yaml::Boolean(*self)
+    }
+}
+
+impl ToYaml for str {
+    fn to_yaml(&self, _: &DataStore) -> Yaml {
+        Yaml::String(self.into())
+    }
+}
+
+impl ToYaml for String {
+    fn to_yaml(&self, _: &DataStore) -> Yaml {
+        Yaml::String(self.clone())
+    }
+}
+
+impl ToYaml for u8 {
+    fn to_yaml(&self, _: &DataStore) -> Yaml {
+        Yaml::Integer((*self).into())
+    }
+}
+
+impl ToYaml for u16 {
+    fn to_yaml(&self, _: &DataStore) -> Yaml {
+        Yaml::Integer((*self).into())
+    }
+}
+
+impl ToYaml for f64 {
+    fn to_yaml(&self, _: &DataStore) -> Yaml {
+        // The Debug impl always includes a fraction, so the value is read
+        // back as a float.
+        Yaml::Real(format!("{:?}", self))
+    }
+}
+
+impl ToYaml for DocumentLink {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        Yaml::String(self.data(store).key().as_str().into())
+    }
+}
+
+
+//------------ MappingBuilder ------------------------------------------------
+
+/// A YAML mapping under construction.
+///
+/// Attributes are kept in the order they are added.
+#[derive(Clone, Debug, Default)]
+pub struct MappingBuilder {
+    items: Hash,
+}
+
+impl MappingBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an attribute unless its value is to be omitted.
+    pub fn attr(
+        &mut self, key: &str, value: &impl ToYaml, store: &DataStore
+    ) -> &mut Self {
+        if !value.is_omitted() {
+            self.insert(key, value.to_yaml(store));
+        }
+        self
+    }
+
+    /// Adds an attribute with the given YAML value.
+    pub fn insert(&mut self, key: &str, value: Yaml) -> &mut Self {
+        self.items.insert(Yaml::String(key.into()), value);
+        self
+    }
+
+    /// Adds all attributes of another mapping.
+    pub fn extend(&mut self, other: MappingBuilder) -> &mut Self {
+        self.items.extend(other.items);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn into_yaml(self) -> Yaml {
+        Yaml::Hash(self.items)
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Converts a sequence of values into YAML.
+///
+/// A single value is written as a plain value, everything else as a
+/// sequence.
+pub fn list_to_yaml<'a, T: ToYaml + 'a>(
+    iter: impl Iterator<Item = &'a T>,
+    store: &DataStore
+) -> Yaml {
+    let mut res: Vec<_> = iter.map(|item| item.to_yaml(store)).collect();
+    if res.len() == 1 {
+        res.pop().unwrap()
+    }
+    else {
+        Yaml::Array(res)
+    }
+}
+
+/// Converts an unordered collection of values into YAML.
+///
+/// The values are sorted to make the output stable.
+pub fn set_to_yaml<'a, T: ToYaml + 'a>(
+    iter: impl Iterator<Item = &'a T>,
+    store: &DataStore
+) -> Yaml {
+    let mut res: Vec<_> = iter.map(|item| item.to_yaml(store)).collect();
+    if res.len() == 1 {
+        res.pop().unwrap()
+    }
+    else {
+        res.sort();
+        Yaml::Array(res)
+    }
+}
+
+/// Converts a value into a YAML string via its `Display` impl.
+pub fn display_to_yaml(value: &impl fmt::Display) -> Yaml {
+    Yaml::String(value.to_string())
+}
+
+/// Returns whether YAML source contains comments.
+///
+/// A comment starts with a `#` at the beginning of a line or after white
+/// space outside of a quoted string. Quoted strings spanning lines and
+/// block scalars aren’t recognized, so this may find comments that
+/// aren’t. This only means that a file is left alone needlessly.
+fn has_comments(content: &str) -> bool {
+    content.lines().any(|line| {
+        let mut quote = None;
+        let mut prev = ' ';
+        for ch in line.chars() {
+            match quote {
+                Some(end) => {
+                    if ch == end {
+                        quote = None
+                    }
+                }
+                None => match ch {
+                    '#' if prev.is_whitespace() => return true,
+                    '\'' | '"'
+                        if prev.is_whitespace()
+                            || matches!(prev, '[' | '{' | ',') =>
+                    {
+                        quote = Some(ch)
+                    }
+                    _ => { }
+                }
+            }
+            prev = ch;
+        }
+        false
+    })
+}
+
+
+//============ Errors ========================================================
+
+#[derive(Debug, Display)]
+pub enum WriteError {
+    #[display(fmt="{} documents can’t be written as YAML", _0)]
+    Unsupported(DocumentType),
+
+    #[display(fmt="{}", _0)]
+    Emit(EmitError),
+}
+
+impl From<EmitError> for WriteError {
+    fn from(err: EmitError) -> Self {
+        WriteError::Emit(err)
+    }
+}
+
+#[derive(Debug, Display)]
+pub enum FormatError {
+    #[display(fmt="{}: {}", _0, _1)]
+    Io(Path, io::Error),
+
+    #[display(fmt="{}: {}", _0, _1)]
+    Write(Path, WriteError),
+}
+
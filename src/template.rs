@@ -0,0 +1,239 @@
+//! Skeletons for new documents.
+//!
+//! A [`Template`] creates the YAML for a new document of some type. The
+//! skeleton contains all mandatory attributes of the type plus whatever
+//! was given explicitly, such as a name or a category. Before it is handed
+//! out, the skeleton is loaded the same way as the document files are, so
+//! it is guaranteed to load unless it is missing information only the
+//! author can provide.
+//!
+//! Links to other documents can’t be checked without the data, so they
+//! are only checked when the new document is loaded with the rest of the
+//! data.
+
+use derive_more::Display;
+use yaml_rust::{Yaml, YamlEmitter};
+use crate::document::common::{DocumentType, Progress};
+use crate::document::local;
+use crate::load::report::{self, Report, Reporter, Stage};
+use crate::load::yaml::Loader;
+use crate::store::StoreLoader;
+use crate::store::write::MappingBuilder;
+use crate::types::{IntoMarked, Key, Location};
+
+
+//------------ Template ------------------------------------------------------
+
+/// The skeleton of a new document.
+#[derive(Clone, Debug)]
+pub struct Template {
+    /// The key of the new document.
+    key: Key,
+
+    /// The type of the new document.
+    doctype: DocumentType,
+
+    /// The subtype of the document if it has one.
+    subtype: Option<String>,
+
+    /// The name of the document.
+    ///
+    /// This becomes the title for sources.
+    name: Option<String>,
+
+    /// The categories of a line or point.
+    category: Vec<String>,
+
+    /// The keys of the points of a line.
+    points: Vec<String>,
+}
+
+impl Template {
+    /// Creates a template for a document with the given key.
+    ///
+    /// The type of the document is derived from the first part of the
+    /// key. For entities, this also determines the subtype.
+    pub fn new(key: Key) -> Result<Self, TemplateError> {
+        let (doctype, subtype) = match key.split('.').next() {
+            Some("line") => (DocumentType::Line, None),
+            Some("point") => (DocumentType::Point, None),
+            Some("service") => (DocumentType::Service, None),
+            Some("source") => (DocumentType::Source, None),
+            Some("structure") => (DocumentType::Structure, None),
+            Some("path") => (DocumentType::Path, None),
+            Some("org") => (DocumentType::Entity, Some("company")),
+            Some("country") => (DocumentType::Entity, Some("country")),
+            Some("region") => (DocumentType::Entity, Some("region")),
+            Some("person") => (DocumentType::Entity, Some("person")),
+            Some("place") => (DocumentType::Entity, Some("place")),
+            _ => return Err(TemplateError::UnknownType(key))
+        };
+        let mut res = Self::with_doctype(key, doctype)?;
+        res.subtype = subtype.map(Into::into);
+        Ok(res)
+    }
+
+    /// Creates a template for a document with the given key and type.
+    pub fn with_doctype(
+        key: Key, doctype: DocumentType
+    ) -> Result<Self, TemplateError> {
+        if doctype == DocumentType::Path {
+            return Err(TemplateError::Path)
+        }
+        if doctype == DocumentType::Custom {
+            return Err(TemplateError::Custom)
+        }
+        if let Err(expected) = local::is_valid_key(&key) {
+            return Err(TemplateError::InvalidKey { key, expected })
+        }
+        Ok(Template {
+            key, doctype,
+            subtype: None,
+            name: None,
+            category: Vec::new(),
+            points: Vec::new(),
+        })
+    }
+
+    /// Returns the type of the new document.
+    pub fn doctype(&self) -> DocumentType {
+        self.doctype
+    }
+
+    pub fn set_subtype(&mut self, subtype: impl Into<String>) {
+        self.subtype = Some(subtype.into())
+    }
+
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.name = Some(name.into())
+    }
+
+    pub fn add_category(&mut self, category: impl Into<String>) {
+        self.category.push(category.into())
+    }
+
+    /// Adds a point to a line.
+    pub fn add_point(&mut self, point: impl Into<String>) {
+        self.points.push(point.into())
+    }
+
+    /// Returns the skeleton as a YAML value.
+    pub fn to_yaml(&self) -> Yaml {
+        let mut res = MappingBuilder::new();
+        res.insert("key", string(self.key.as_str()));
+        res.insert("type", string(self.doctype.as_str()));
+        res.insert("progress", string(Progress::Stub.as_str()));
+        if let Some(subtype) = self.subtype.as_ref() {
+            res.insert("subtype", string(subtype));
+        }
+        match self.doctype {
+            DocumentType::Line => {
+                res.insert("points", list(&self.points));
+                if self.name.is_some() || !self.category.is_empty() {
+                    res.insert("events", self.event());
+                }
+            }
+            DocumentType::Source => {
+                if let Some(name) = self.name.as_ref() {
+                    res.insert("title", string(name));
+                }
+            }
+            _ => {
+                res.insert("events", self.event());
+            }
+        }
+        res.into_yaml()
+    }
+
+    /// Returns the list with the single event of the skeleton.
+    fn event(&self) -> Yaml {
+        let mut res = MappingBuilder::new();
+        res.insert("date", Yaml::Null);
+        if let Some(name) = self.name.as_ref() {
+            res.insert("name", string(name));
+        }
+        if !self.category.is_empty() {
+            res.insert("category", list(&self.category));
+        }
+        Yaml::Array(vec![res.into_yaml()])
+    }
+
+    /// Returns the skeleton as a YAML document after checking it.
+    ///
+    /// The document starts with a document start marker and ends in a
+    /// line feed so it can be appended to an existing file. If the
+    /// skeleton doesn’t load, returns the problems.
+    pub fn render(&self) -> Result<String, Report> {
+        let mut res = String::new();
+        // Emitting into a string can only fail for invalid YAML values
+        // which we don’t create.
+        YamlEmitter::new(&mut res).dump(&self.to_yaml()).unwrap();
+        res.push('\n');
+        check(&res)?;
+        Ok(res)
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Loads a document the same way as document files are loaded.
+fn check(yaml: &str) -> Result<(), Report> {
+    let report = Reporter::new();
+    let store = StoreLoader::new();
+    {
+        let mut report = report.clone().stage(Stage::Translate).with_path(
+            report::Path::new("new document")
+        );
+        let res = Loader::new(|value| {
+            let _ = store.from_yaml(value, &mut report);
+        }).load(yaml.chars());
+        if let Err(errs) = res {
+            let mut report = report.restage(Stage::Parse);
+            for err in errs {
+                report.error(err.marked(Location::NONE));
+            }
+        }
+    }
+    let report = report.unwrap();
+    if report.is_empty() {
+        Ok(())
+    }
+    else {
+        Err(report)
+    }
+}
+
+fn string(value: &str) -> Yaml {
+    Yaml::String(value.into())
+}
+
+/// Returns a list of strings, using a plain string for a single item.
+fn list(values: &[String]) -> Yaml {
+    match values {
+        [value] => string(value),
+        _ => Yaml::Array(values.iter().map(|value| string(value)).collect()),
+    }
+}
+
+
+//============ Errors ========================================================
+
+#[derive(Clone, Debug, Display)]
+pub enum TemplateError {
+    #[display(fmt="cannot determine the document type of '{}'", _0)]
+    UnknownType(Key),
+
+    #[display(fmt="path documents are created from OSM files")]
+    Path,
+
+    #[display(fmt="custom documents have no template")]
+    Custom,
+
+    #[display(fmt="invalid key '{}', expected {}", key, expected)]
+    InvalidKey {
+        key: Key,
+        expected: &'static str,
+    }
+}
+
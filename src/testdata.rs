@@ -0,0 +1,405 @@
+//! Miniature data sets for tests.
+//!
+//! The [`Fixture`] type collects documents in their YAML form and loads
+//! them the same way the documents of a data tree are loaded. This allows
+//! tests to work with a handful of lines, points, and sources rather than
+//! depending on a checkout of the complete data.
+//!
+//! [`Fixture::sample`] provides a small but complete data set that can be
+//! extended with further documents as needed.
+//!
+//! In addition, [`YamlFuzzer`] generates arbitrary documents and
+//! [`check_yaml`] loads them, checking that the loader neither panics nor
+//! fails silently.
+
+use serde_json::{Map, Value as JsonValue};
+use crate::load::report::{self, Failed, Report, Reporter, Stage};
+use crate::load::yaml::Loader;
+use crate::schema::{Attribute, Schema};
+use crate::store::{DataStore, FullStore, StoreLoader};
+use crate::types::{IntoMarked, Location};
+
+
+//------------ Fixture -------------------------------------------------------
+
+/// A collection of documents to be loaded into a store.
+#[derive(Clone, Debug, Default)]
+pub struct Fixture {
+    /// The YAML text of the documents collected so far.
+    yaml: String,
+}
+
+impl Fixture {
+    /// The path used for the origin of all documents.
+    pub const PATH: &'static str = "testdata.yaml";
+
+    /// Creates a new, empty fixture.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a fixture with a small sample data set.
+    ///
+    /// The data set consists of two German lines that share the junction
+    /// `point.de.bm`, four points, and a source for the lines.
+    pub fn sample() -> Self {
+        Self::new()
+            .source("source.de.kursbuch", "Kursbuch 1914")
+            .point("point.de.aa", "Altstadt")
+            .point("point.de.bm", "Bergmünde")
+            .point("point.de.cd", "Chausseedorf")
+            .point("point.de.dw", "Dornwalde")
+            .line("line.de.1000", &["point.de.aa", "point.de.bm"])
+            .line(
+                "line.de.1001",
+                &["point.de.bm", "point.de.cd", "point.de.dw"]
+            )
+    }
+
+    /// Adds a source document with the given title.
+    pub fn source(self, key: &str, title: &str) -> Self {
+        self.yaml(&format!(
+            "key: {}\ntype: source\ntitle: {}\n",
+            quote(key), quote(title)
+        ))
+    }
+
+    /// Adds a point document with a single event giving its name.
+    pub fn point(self, key: &str, name: &str) -> Self {
+        self.yaml(&format!(
+            "key: {}\ntype: point\nevents:\n- name: {}\n",
+            quote(key), quote(name)
+        ))
+    }
+
+    /// Adds a line document running through the given points.
+    pub fn line(self, key: &str, points: &[&str]) -> Self {
+        let mut yaml = format!("key: {}\ntype: line\npoints:\n", quote(key));
+        for point in points {
+            yaml.push_str("- ");
+            yaml.push_str(&quote(point));
+            yaml.push('\n');
+        }
+        self.yaml(&yaml)
+    }
+
+    /// Adds a document given as YAML text.
+    ///
+    /// The text must contain exactly one document without the leading
+    /// `---` separator.
+    pub fn yaml(mut self, document: &str) -> Self {
+        self.yaml.push_str("---\n");
+        self.yaml.push_str(document);
+        if !document.ends_with('\n') {
+            self.yaml.push('\n')
+        }
+        self
+    }
+
+    /// Returns the YAML text of all documents.
+    pub fn as_yaml(&self) -> &str {
+        &self.yaml
+    }
+
+    /// Loads the documents into a data store.
+    ///
+    /// Returns all problems found if any of the documents fail to load.
+    pub fn into_data_store(self) -> Result<DataStore, Report> {
+        let (store, report) = load_str(&self.yaml);
+        match store {
+            Ok(store) if report.is_empty() => Ok(store),
+            _ => Err(report)
+        }
+    }
+
+    /// Loads the documents into a full store.
+    pub fn into_full_store(self) -> Result<FullStore, Report> {
+        self.into_data_store()?.into_full_store()
+    }
+}
+
+
+//------------ YamlFuzzer ----------------------------------------------------
+
+/// A generator for arbitrary, mostly malformed documents.
+///
+/// The documents are built following the [`Schema`] so they use the
+/// attributes the loader expects, but the values are frequently of the
+/// wrong kind. Each document is returned as YAML text and can be fed to
+/// [`check_yaml`].
+///
+/// The generator is deterministic: the same seed always produces the same
+/// sequence of documents.
+#[derive(Clone, Debug)]
+pub struct YamlFuzzer {
+    /// The schema of all documents.
+    schema: Schema,
+
+    /// The state of the random number generator.
+    state: u64,
+}
+
+impl YamlFuzzer {
+    /// The maximum nesting depth of generated values.
+    const MAX_DEPTH: usize = 4;
+
+    /// Creates a new generator from the given seed.
+    pub fn new(seed: u64) -> Self {
+        YamlFuzzer {
+            schema: Schema::new(),
+            // The state of a xorshift generator must never be zero.
+            state: seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1,
+        }
+    }
+
+    /// Generates the YAML text of a single document.
+    pub fn document(&mut self) -> String {
+        let documents: Vec<_> = self.schema.documents().map(|(name, _)| {
+            name
+        }).collect();
+        let doctype = self.choose(&documents);
+        let mut res = match self.object(doctype, 0) {
+            JsonValue::Object(map) => map,
+            _ => unreachable!()
+        };
+        // Mostly use a proper key and type so the document reaches the
+        // parser for its type.
+        if !self.chance(8) {
+            res.insert("key".into(), self.key(doctype).into());
+            res.insert("type".into(), doctype.into());
+        }
+        JsonValue::Object(res).to_string()
+    }
+
+    /// Generates a mapping for the object with the given name.
+    fn object(&mut self, name: &str, depth: usize) -> JsonValue {
+        let mut attributes = Vec::new();
+        self.attributes(name, &mut attributes);
+        let mut res = Map::new();
+        for attr in attributes {
+            if attr.mandatory || self.chance(3) {
+                let value = self.typed_value(attr.value, depth + 1);
+                res.insert(attr.name.into(), value);
+            }
+        }
+        if self.chance(10) {
+            res.insert("unknown".into(), self.value(depth + 1));
+        }
+        JsonValue::Object(res)
+    }
+
+    /// Collects the attributes of an object including those it includes.
+    fn attributes(&self, name: &str, target: &mut Vec<Attribute>) {
+        if let Some(object) = self.schema.get_object(name) {
+            for include in object.includes() {
+                self.attributes(include, target);
+            }
+            target.extend_from_slice(object.attributes());
+        }
+    }
+
+    /// Generates a value mostly following the given type expression.
+    fn typed_value(&mut self, expr: &str, depth: usize) -> JsonValue {
+        if depth > Self::MAX_DEPTH || self.chance(5) {
+            return self.value(depth)
+        }
+        let (kind, arg) = match expr.split_once('(') {
+            Some((kind, arg)) => {
+                (kind, arg.strip_suffix(')').unwrap_or(arg))
+            }
+            None => (expr, "")
+        };
+        match kind {
+            "list" => {
+                if self.chance(2) {
+                    self.typed_value(arg, depth + 1)
+                }
+                else {
+                    let len = self.below(4);
+                    JsonValue::Array(
+                        (0..len).map(|_| {
+                            self.typed_value(arg, depth + 1)
+                        }).collect()
+                    )
+                }
+            }
+            "nullable" => {
+                if self.chance(3) {
+                    JsonValue::Null
+                }
+                else {
+                    self.typed_value(arg, depth + 1)
+                }
+            }
+            "object" => self.object(arg, depth),
+            "enum" => {
+                match self.schema.get_enum(arg) {
+                    Some(variants) if !variants.is_empty() => {
+                        self.choose(variants).into()
+                    }
+                    _ => self.value(depth)
+                }
+            }
+            "key" => self.key(arg).into(),
+            "date" => self.choose(DATES).into(),
+            "integer" => (self.next() as i64 >> self.below(64)).into(),
+            "bool" => self.chance(2).into(),
+            _ => self.scalar(),
+        }
+    }
+
+    /// Generates an arbitrary value.
+    fn value(&mut self, depth: usize) -> JsonValue {
+        if depth > Self::MAX_DEPTH {
+            return self.scalar()
+        }
+        match self.below(6) {
+            0 => {
+                let len = self.below(4);
+                JsonValue::Array(
+                    (0..len).map(|_| self.value(depth + 1)).collect()
+                )
+            }
+            1 => {
+                let len = self.below(4);
+                JsonValue::Object(
+                    (0..len).map(|_| {
+                        (self.choose(SCALARS).into(), self.value(depth + 1))
+                    }).collect()
+                )
+            }
+            _ => self.scalar()
+        }
+    }
+
+    /// Generates an arbitrary scalar value.
+    fn scalar(&mut self) -> JsonValue {
+        match self.below(5) {
+            0 => JsonValue::Null,
+            1 => self.chance(2).into(),
+            2 => (self.next() as i64).into(),
+            3 => (self.next() as f64 / u32::MAX as f64).into(),
+            _ => self.choose(SCALARS).into(),
+        }
+    }
+
+    /// Generates a key for a document of the given type.
+    fn key(&mut self, doctype: &str) -> String {
+        let doctype = if doctype.is_empty() || self.chance(10) {
+            self.choose(&["entity", "line", "path", "point", "source"])
+        }
+        else {
+            doctype
+        };
+        format!(
+            "{}.{}.{}",
+            doctype, self.choose(COUNTRIES), self.choose(LOCAL_PARTS)
+        )
+    }
+
+    /// Returns a random element of a non-empty slice.
+    fn choose<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())]
+    }
+
+    /// Returns `true` with a chance of one in `n`.
+    fn chance(&mut self, n: usize) -> bool {
+        self.below(n) == 0
+    }
+
+    /// Returns a random number less than `n`.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// Advances the xorshift64* generator.
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+/// Date values, both valid and invalid.
+const DATES: &[&str] = &[
+    "1900", "1900-05", "1900-05-17", "1900-13", "1900-02-30", "?1900",
+    "1900?", "c1900", "", "0", "-1", "99999-01-01", "1900-05-17-01",
+];
+
+/// Scalar values that tend to trip up parsers.
+const SCALARS: &[&str] = &[
+    "", " ", "none", "null", "~", "true", "1435mm", "0mm", "-1", "1e400",
+    "NaN", "point.de.", "line.de.1000", "de", "ZZ", "name", "de: x",
+    "ole.15000ac16", "ole.", "€", "\u{0}", "a\nb", "k", ".", "..",
+];
+
+/// Country codes for keys including some that don’t exist.
+const COUNTRIES: &[&str] = &["de", "dk", "ch", "at", "nl", "fr", "zz", ""];
+
+/// Local parts of keys.
+const LOCAL_PARTS: &[&str] = &[
+    "1000", "1000a", "12345", "x", "", "bm", "1.2", "ABC",
+];
+
+
+//------------ check_yaml ----------------------------------------------------
+
+/// Loads arbitrary YAML text and checks the loader’s invariants.
+///
+/// Loading may fail, but it has to explain why: if the text can’t be
+/// turned into a data store, the report must contain at least one
+/// notice.
+///
+/// # Panics
+///
+/// The function panics if an invariant is violated. Any panic inside the
+/// loader itself is passed through, too.
+pub fn check_yaml(text: &str) {
+    let (store, report) = load_str(text);
+    if store.is_err() && report.is_empty() {
+        panic!("loading failed without a report for:\n{}", text)
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Loads the documents in `text` into a data store.
+///
+/// Returns the store if it could be created and the report with all
+/// problems found on the way.
+fn load_str(text: &str) -> (Result<DataStore, Failed>, Report) {
+    let report = Reporter::new();
+    let docs = StoreLoader::new();
+    {
+        let path = report::Path::new(Fixture::PATH);
+        let mut report = report.clone()
+            .stage(Stage::Translate)
+            .with_path(path);
+        let res = {
+            let mut loader = Loader::new(|v| {
+                let _ = docs.from_yaml(v, &mut report);
+            });
+            loader.load_from_str(text)
+        };
+        if let Err(errs) = res {
+            let mut report = report.restage(Stage::Parse);
+            for err in errs {
+                report.error(err.marked(Location::NONE));
+            }
+        }
+    }
+    let store = docs.into_data_store(
+        &mut report.clone().stage(Stage::Translate)
+    );
+    (store, report.unwrap())
+}
+
+
+/// Returns `value` as a double quoted YAML scalar.
+///
+/// JSON strings happen to be valid double quoted YAML scalars.
+fn quote(value: &str) -> String {
+    serde_json::Value::from(value).to_string()
+}
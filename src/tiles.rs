@@ -0,0 +1,289 @@
+//! Lines and points by map tile.
+//!
+//! Slippy maps request their data in web-mercator tiles addressed by a
+//! zoom level and the column and row of the tile at that zoom. A
+//! [`SpatialIndex`] keeps the bounding boxes of all lines and points of a
+//! store so the documents intersecting a [`Tile`] can be found quickly and
+//! returned as a GeoJSON feature collection.
+//!
+//! The geometry of lines is taken from the geometry assembled for each
+//! line when generating metadata, simplified for the tile’s zoom level.
+//! Features are not clipped to the tile, so lines crossing tile
+//! boundaries appear in full in every tile they touch.
+
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::PI;
+use serde_json::{json, Value as JsonValue};
+use crate::document::path::Coord;
+use crate::store::{DocumentLink, FullStore, ProgressView};
+use crate::types::LanguagePreference;
+
+
+//------------ Tile ----------------------------------------------------------
+
+/// A web-mercator map tile.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Tile {
+    /// The zoom level.
+    pub z: u8,
+
+    /// The column of the tile counted from the west.
+    pub x: u32,
+
+    /// The row of the tile counted from the north.
+    pub y: u32,
+}
+
+impl Tile {
+    /// The highest zoom level supported.
+    pub const MAX_ZOOM: u8 = 24;
+
+    /// Creates a tile if it exists at the zoom level.
+    pub fn new(z: u8, x: u32, y: u32) -> Option<Self> {
+        if z > Self::MAX_ZOOM {
+            return None
+        }
+        let size = 1u32 << z;
+        if x >= size || y >= size {
+            return None
+        }
+        Some(Tile { z, x, y })
+    }
+
+    /// Parses a tile from a path of the form `<z>/<x>/<y>`.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let mut parts = path.split('/');
+        let z = parts.next()?.parse().ok()?;
+        let x = parts.next()?.parse().ok()?;
+        let y = parts.next()?.parse().ok()?;
+        if parts.next().is_some() {
+            return None
+        }
+        Self::new(z, x, y)
+    }
+
+    /// Returns the area covered by the tile.
+    pub fn bounds(self) -> Bounds {
+        let size = f64::from(1u32 << self.z);
+        let lon = |x: f64| x / size * 360. - 180.;
+        let lat = |y: f64| {
+            (PI * (1. - 2. * y / size)).sinh().atan().to_degrees()
+        };
+        Bounds {
+            west: lon(f64::from(self.x)),
+            south: lat(f64::from(self.y + 1)),
+            east: lon(f64::from(self.x + 1)),
+            north: lat(f64::from(self.y)),
+        }
+    }
+}
+
+
+//------------ Bounds --------------------------------------------------------
+
+/// A rectangular area in longitude and latitude.
+#[derive(Clone, Copy, Debug)]
+pub struct Bounds {
+    pub west: f64,
+    pub south: f64,
+    pub east: f64,
+    pub north: f64,
+}
+
+impl Bounds {
+    /// Returns the bounds of a sequence of coordinates.
+    ///
+    /// Returns `None` if there are no coordinates.
+    pub fn from_coords(
+        coords: impl IntoIterator<Item = Coord>
+    ) -> Option<Self> {
+        let mut coords = coords.into_iter();
+        let first = coords.next()?;
+        let mut res = Bounds {
+            west: first.lon, south: first.lat,
+            east: first.lon, north: first.lat,
+        };
+        for coord in coords {
+            res.west = res.west.min(coord.lon);
+            res.south = res.south.min(coord.lat);
+            res.east = res.east.max(coord.lon);
+            res.north = res.north.max(coord.lat);
+        }
+        Some(res)
+    }
+
+    /// Returns whether the two areas overlap.
+    pub fn intersects(&self, other: &Bounds) -> bool {
+        self.west <= other.east && other.west <= self.east
+            && self.south <= other.north && other.south <= self.north
+    }
+
+    /// Returns the grid cells covered by the area.
+    fn cells(&self) -> impl Iterator<Item = (i32, i32)> {
+        let west = self.west.floor() as i32;
+        let east = self.east.floor() as i32;
+        let south = self.south.floor() as i32;
+        let north = self.north.floor() as i32;
+        (west..=east).flat_map(move |lon| {
+            (south..=north).map(move |lat| (lon, lat))
+        })
+    }
+
+    /// Returns the number of grid cells covered by the area.
+    fn cell_count(&self) -> usize {
+        let width = self.east.floor() - self.west.floor() + 1.;
+        let height = self.north.floor() - self.south.floor() + 1.;
+        (width * height) as usize
+    }
+}
+
+
+//------------ SpatialIndex --------------------------------------------------
+
+/// An index of lines and points by their location.
+///
+/// The bounding box of each document is placed in a grid of cells one
+/// degree in size.
+#[derive(Clone, Debug, Default)]
+pub struct SpatialIndex {
+    /// The documents and their bounding boxes.
+    entries: Vec<(DocumentLink, Bounds)>,
+
+    /// The indexes into `entries` for each grid cell.
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialIndex {
+    /// Creates the index for all lines and points of a store.
+    ///
+    /// Points without coordinates and lines without a resolvable course
+    /// are left out.
+    pub fn new(store: &FullStore) -> Self {
+        let mut res = SpatialIndex::default();
+        for link in store.links() {
+            let document = link.document(store);
+            let bounds = if let Some(line) = document.try_as_line() {
+                Bounds::from_coords(line.meta().geometry.coords())
+            }
+            else if let Some(point) = document.try_as_point() {
+                point.meta().coord.and_then(|coord| {
+                    Bounds::from_coords(Some(coord))
+                })
+            }
+            else {
+                None
+            };
+            if let Some(bounds) = bounds {
+                let idx = res.entries.len();
+                res.entries.push((link, bounds));
+                for cell in bounds.cells() {
+                    res.cells.entry(cell).or_default().push(idx)
+                }
+            }
+        }
+        res
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the documents whose bounding box intersects an area.
+    pub fn query(&self, bounds: &Bounds) -> Vec<DocumentLink> {
+        let matches = |idx: &usize| {
+            self.entries[*idx].1.intersects(bounds)
+        };
+        // Large areas are quicker to check entry by entry.
+        let mut found: Vec<usize> = if bounds.cell_count() > self.len() {
+            (0..self.len()).filter(matches).collect()
+        }
+        else {
+            let mut seen = HashSet::new();
+            bounds.cells().filter_map(|cell| self.cells.get(&cell)).flat_map(
+                |cell| cell.iter().copied()
+            ).filter(|idx| seen.insert(*idx)).filter(matches).collect()
+        };
+        found.sort_unstable();
+        found.into_iter().map(|idx| self.entries[idx].0).collect()
+    }
+
+    /// Returns the lines and points of a tile as a GeoJSON feature
+    /// collection.
+    ///
+    /// See [`features_to_geojson`] for the content of the features.
+    pub fn tile_to_geojson<'a>(
+        &self,
+        tile: Tile,
+        preference: &LanguagePreference,
+        store: impl Into<ProgressView<'a>>,
+    ) -> JsonValue {
+        let view = store.into();
+        features_to_geojson(
+            self.query(&tile.bounds()).into_iter().filter(|link| {
+                view.contains(*link)
+            }),
+            tile.z, preference, view.store()
+        )
+    }
+}
+
+
+//------------ features_to_geojson -------------------------------------------
+
+/// Returns lines and points as a GeoJSON feature collection.
+///
+/// The geometry of lines is simplified for the given zoom level. Each
+/// feature has the key, type, and name of its document as properties.
+/// Documents that aren’t lines or points or have no location are skipped.
+pub fn features_to_geojson(
+    links: impl IntoIterator<Item = DocumentLink>,
+    zoom: u8,
+    preference: &LanguagePreference,
+    store: &FullStore,
+) -> JsonValue {
+    let features = links.into_iter().filter_map(|link| {
+        let document = link.document(store);
+        let geometry = if let Some(line) = document.try_as_line() {
+            let segments = line.meta().geometry.segments(zoom);
+            if segments.is_empty() {
+                return None
+            }
+            json!({
+                "type": "MultiLineString",
+                "coordinates": segments.into_iter().map(|segment| {
+                    segment.into_iter().map(|coord| {
+                        [coord.lon, coord.lat]
+                    }).collect::<Vec<_>>()
+                }).collect::<Vec<_>>(),
+            })
+        }
+        else if let Some(point) = document.try_as_point() {
+            let coord = point.meta().coord?;
+            json!({
+                "type": "Point",
+                "coordinates": [coord.lon, coord.lat],
+            })
+        }
+        else {
+            return None
+        };
+        let data = link.data(store);
+        Some(json!({
+            "type": "Feature",
+            "geometry": geometry,
+            "properties": {
+                "key": data.key().as_str(),
+                "type": data.doctype().as_str(),
+                "name": data.preferred_name(preference),
+            },
+        }))
+    }).collect::<Vec<_>>();
+    json!({
+        "type": "FeatureCollection",
+        "features": features,
+    })
+}
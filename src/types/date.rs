@@ -1,9 +1,13 @@
 //! The date type.
 
-use std::{cmp, fmt, str};
+use std::{cmp, fmt, ops, str};
 use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
 use crate::load::yaml::{FromYaml, Value};
+use yaml_rust::Yaml;
 use crate::load::report::{Failed, PathReporter};
+use crate::store::DataStore;
+use crate::store::write::{ToYaml, list_to_yaml};
 use super::list::List;
 use super::marked::Marked;
 
@@ -143,7 +147,164 @@ impl Date {
     }
 
     pub fn is_leap(&self) -> bool {
-        (self.year % 4 == 0 && self.year % 100 != 0) || self.year % 400 == 0
+        is_leap_year(self.year)
+    }
+}
+
+/// # Date Arithmetic
+///
+/// Because dates can be imprecise, each date describes a range of days
+/// it may refer to. A date that lacks the month or day covers the whole
+/// year or month. A ‘circa’ date is widened by one unit of its finest
+/// component on either side, i.e., a year, a month, or a day. ‘Before’ and
+/// ‘after’ dates are open-ended in one direction. Doubt doesn’t change
+/// the range.
+impl Date {
+    /// Creates an exact date for the given day.
+    pub fn from_ymd(year: i16, month: u8, day: u8) -> Self {
+        Date {
+            year, month: Some(month), day: Some(day),
+            precision: Precision::Exact,
+            doubt: false
+        }
+    }
+
+    /// Returns the current day in UTC.
+    pub fn today() -> Self {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| {
+            time.as_secs()
+        }).unwrap_or(0);
+        Self::from_day_number((secs / 86_400) as i64)
+    }
+
+    /// Returns the earliest day the date may refer to.
+    ///
+    /// Returns `None` if the date is open-ended towards the past.
+    pub fn min_date(&self) -> Option<Date> {
+        self.day_range().0.map(Self::from_day_number)
+    }
+
+    /// Returns the latest day the date may refer to.
+    ///
+    /// Returns `None` if the date is open-ended towards the future.
+    pub fn max_date(&self) -> Option<Date> {
+        self.day_range().1.map(Self::from_day_number)
+    }
+
+    /// Returns whether the two dates may refer to the same day.
+    pub fn overlaps(&self, other: &Date) -> bool {
+        let (left_min, left_max) = self.day_range();
+        let (right_min, right_max) = other.day_range();
+        !is_before(left_max, right_min) && !is_before(right_max, left_min)
+    }
+
+    /// Returns whether all days `other` may refer to are covered by `self`.
+    pub fn contains(&self, other: &Date) -> bool {
+        let (left_min, left_max) = self.day_range();
+        let (right_min, right_max) = other.day_range();
+        let min_ok = match (left_min, right_min) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(left), Some(right)) => left <= right,
+        };
+        let max_ok = match (left_max, right_max) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(left), Some(right)) => left >= right,
+        };
+        min_ok && max_ok
+    }
+
+    /// Compares the ranges of two dates.
+    ///
+    /// Returns `Less` or `Greater` if `self` is definitely before or after
+    /// `other`, and `Equal` if both refer to the same single day. If the
+    /// ranges overlap otherwise, the order can’t be decided and `None` is
+    /// returned.
+    pub fn cmp_range(&self, other: &Date) -> Option<cmp::Ordering> {
+        let (left_min, left_max) = self.day_range();
+        let (right_min, right_max) = other.day_range();
+        if is_before(left_max, right_min) {
+            Some(cmp::Ordering::Less)
+        }
+        else if is_before(right_max, left_min) {
+            Some(cmp::Ordering::Greater)
+        }
+        else if left_min.is_some() && left_min == left_max
+            && left_min == right_min && right_min == right_max
+        {
+            Some(cmp::Ordering::Equal)
+        }
+        else {
+            None
+        }
+    }
+
+    /// Returns the range of the number of days from `self` to `other`.
+    ///
+    /// The number is negative if `other` is before `self`. Returns `None`
+    /// if either date is open-ended so that there is no bound.
+    pub fn days_until(
+        &self, other: &Date
+    ) -> Option<ops::RangeInclusive<i64>> {
+        let (left_min, left_max) = self.day_range();
+        let (right_min, right_max) = other.day_range();
+        Some((right_min? - left_max?)..=(right_max? - left_min?))
+    }
+
+    /// Returns the first and last day number the date may refer to.
+    fn day_range(&self) -> (Option<i64>, Option<i64>) {
+        let (first, last) = match (self.month, self.day) {
+            (Some(month), Some(day)) => {
+                let day = days_from_civil(self.year, month, day);
+                if self.precision == Precision::Circa {
+                    (day - 1, day + 1)
+                }
+                else {
+                    (day, day)
+                }
+            }
+            (Some(month), None) => {
+                let (first, last) = if self.precision == Precision::Circa {
+                    (
+                        add_months(self.year, month, -1),
+                        add_months(self.year, month, 1)
+                    )
+                }
+                else {
+                    ((self.year, month), (self.year, month))
+                };
+                (
+                    days_from_civil(first.0, first.1, 1),
+                    days_from_civil(
+                        last.0, last.1, days_in_month(last.0, last.1)
+                    )
+                )
+            }
+            _ => {
+                let (first, last) = if self.precision == Precision::Circa {
+                    (self.year - 1, self.year + 1)
+                }
+                else {
+                    (self.year, self.year)
+                };
+                (
+                    days_from_civil(first, 1, 1),
+                    days_from_civil(last, 12, 31)
+                )
+            }
+        };
+        match self.precision {
+            Precision::Exact | Precision::Circa => (Some(first), Some(last)),
+            Precision::Before => (None, Some(first - 1)),
+            Precision::After => (Some(last + 1), None),
+        }
+    }
+
+    /// Creates an exact date from a day number.
+    fn from_day_number(days: i64) -> Self {
+        let (year, month, day) = civil_from_days(days);
+        Self::from_ymd(year, month, day)
     }
 }
 
@@ -178,6 +339,19 @@ impl<C> FromYaml<C> for Marked<Date> {
     }
 }
 
+impl ToYaml for Date {
+    fn to_yaml(&self, _: &DataStore) -> Yaml {
+        if self.month.is_none() && self.precision == Precision::Exact
+            && !self.doubt
+        {
+            Yaml::Integer(self.year.into())
+        }
+        else {
+            Yaml::String(self.to_string())
+        }
+    }
+}
+
 
 impl Ord for Date {
     /// Returns the ordering between `self` and `other`.
@@ -310,10 +484,102 @@ impl str::FromStr for Date {
     }
 }
 
+impl fmt::Display for Date {
+    /// Formats the date in the format accepted by `from_str`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.precision {
+            Precision::Exact => { }
+            Precision::Circa => f.write_str("c")?,
+            Precision::Before => f.write_str("b")?,
+            Precision::After => f.write_str("a")?,
+        }
+        write!(f, "{:04}", self.year)?;
+        if let Some(month) = self.month {
+            write!(f, "-{:02}", month)?;
+            if let Some(day) = self.day {
+                write!(f, "-{:02}", day)?;
+            }
+        }
+        if self.doubt {
+            f.write_str("?")?;
+        }
+        Ok(())
+    }
+}
+
+serde_via_str!(Date);
+
+
+//------------ Helper Functions ----------------------------------------------
+
+fn is_leap_year(year: i16) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31
+    }
+}
+
+/// Adds a number of months to the given year and month.
+fn add_months(year: i16, month: u8, months: i16) -> (i16, u8) {
+    let months = year as i32 * 12 + (month as i32 - 1) + months as i32;
+    (months.div_euclid(12) as i16, (months.rem_euclid(12) + 1) as u8)
+}
+
+/// Returns the number of days since 1970-01-01 of the given date.
+///
+/// This is the algorithm from Howard Hinnant’s ‘chrono-Compatible Low-Level
+/// Date Algorithms’ for the proleptic Gregorian calendar.
+fn days_from_civil(year: i16, month: u8, day: u8) -> i64 {
+    let year = year as i64 - if month <= 2 { 1 } else { 0 };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let month = month as i64;
+    let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5
+        + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Returns year, month, and day for a number of days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i16, u8, u8) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year as i16, month, day)
+}
+
+/// Returns whether the day `left` is definitely before `right`.
+///
+/// Either side being `None` means that the range is open in that
+/// direction.
+fn is_before(left: Option<i64>, right: Option<i64>) -> bool {
+    match (left, right) {
+        (Some(left), Some(right)) => left < right,
+        _ => false
+    }
+}
+
 
 //------------ EventDate -----------------------------------------------------
 
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct EventDate(List<Marked<Date>>);
 
 impl EventDate {
@@ -367,6 +633,16 @@ impl<C> FromYaml<C> for EventDate {
     }
 }
 
+impl ToYaml for EventDate {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        list_to_yaml(self.0.iter(), store)
+    }
+
+    fn is_omitted(&self) -> bool {
+        self.is_empty()
+    }
+}
+
 impl PartialEq for EventDate {
     fn eq(&self, other: &Self) -> bool {
         self.0.as_slice() == other.0.as_slice()
@@ -6,7 +6,7 @@ macro_rules! data_enum {
         pub enum $name:ident {
             $(
                 $( #[$variant_attr:meta] )*
-                {$variant:ident: $yaml:expr}
+                {$variant:ident: $yaml:expr $(, deprecated $alias:expr)* }
             )*
 
             default $default:ident
@@ -17,7 +17,7 @@ macro_rules! data_enum {
             pub enum $name {
                 $(
                     $(#[$variant_attr])*
-                    { $variant: $yaml }
+                    { $variant: $yaml $(, deprecated $alias)* }
                 )*
             }
         }
@@ -34,7 +34,7 @@ macro_rules! data_enum {
         pub enum $name:ident {
             $(
                 $( #[$variant_attr:meta] )*
-                {$variant:ident: $yaml:expr}
+                {$variant:ident: $yaml:expr $(, deprecated $alias:expr)* }
             )*
         }
     ) => {
@@ -49,6 +49,15 @@ macro_rules! data_enum {
                 $( $name::$variant ),*
             ];
 
+            pub const VARIANTS: &'static [&'static str] = &[
+                $( $yaml ),*
+            ];
+
+            /// The deprecated spellings and the variants they stand for.
+            pub const DEPRECATED: &'static [(&'static str, $name)] = &[
+                $( $( ($alias, $name::$variant), )* )*
+            ];
+
             pub fn as_str(self) -> &'static str {
                 match self {
                     $(
@@ -77,17 +86,41 @@ macro_rules! data_enum {
                 _: &C,
                 report: &mut $crate::load::report::PathReporter
             ) -> Result<Self, $crate::load::report::Failed> {
-                let text = value.into_string(report)?;
-                let res = text.try_map(|plain| match plain.as_ref() {
+                use $crate::types::Marked;
+                use $crate::types::enums::EnumError;
+
+                let (plain, location) = value.into_string(report)?.unwrap();
+                let res = match plain.as_ref() {
                     $(
-                        $yaml => Ok($name::$variant),
+                        $yaml => $name::$variant,
+                        $(
+                            $alias => {
+                                use $crate::types::enums::DeprecatedValue;
+
+                                report.warning(Marked::new(
+                                    DeprecatedValue::new(plain, $yaml),
+                                    location
+                                ));
+                                $name::$variant
+                            }
+                        )*
                     )*
-                    _ => Err($crate::types::enums::EnumError::new(plain))
-                });
-                res.map_err(|err| {
-                    report.error(err);
-                    $crate::load::report::Failed
-                })
+                    _ => {
+                        report.error(Marked::new(
+                            EnumError::new(plain), location
+                        ));
+                        return Err($crate::load::report::Failed)
+                    }
+                };
+                Ok(Marked::new(res, location))
+            }
+        }
+
+        impl $crate::store::write::ToYaml for $name {
+            fn to_yaml(
+                &self, _: &$crate::store::DataStore
+            ) -> ::yaml_rust::Yaml {
+                ::yaml_rust::Yaml::String(self.as_str().into())
             }
         }
 
@@ -102,6 +135,34 @@ macro_rules! data_enum {
             }
         }
 
+        #[cfg(feature = "serde")]
+        impl ::serde::Serialize for $name {
+            fn serialize<S: ::serde::Serializer>(
+                &self, serializer: S
+            ) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> ::serde::Deserialize<'de> for $name {
+            fn deserialize<D: ::serde::Deserializer<'de>>(
+                deserializer: D
+            ) -> Result<Self, D::Error> {
+                let s = <String as ::serde::Deserialize>::deserialize(
+                    deserializer
+                )?;
+                match s.as_str() {
+                    $(
+                        $yaml $( | $alias )* => Ok($name::$variant),
+                    )*
+                    _ => Err(::serde::de::Error::unknown_variant(
+                        &s, Self::VARIANTS
+                    ))
+                }
+            }
+        }
+
         /* XXX Remove,
         #[cfg(feature = "http")]
         impl crate::http::json::StateBuildJson for $name {
@@ -130,3 +191,32 @@ impl EnumError {
     }
 }
 
+
+//------------ DeprecatedValue -----------------------------------------------
+
+/// A deprecated spelling of an enum value was used.
+///
+/// The value is still accepted and mapped to its canonical spelling.
+#[derive(Clone, Debug, Display)]
+#[display(fmt="deprecated value '{}', use '{}' instead", found, canonical)]
+pub struct DeprecatedValue {
+    found: String,
+    canonical: &'static str,
+}
+
+impl DeprecatedValue {
+    pub fn new(found: String, canonical: &'static str) -> Self {
+        DeprecatedValue { found, canonical }
+    }
+
+    /// Returns the deprecated spelling that was used.
+    pub fn found(&self) -> &str {
+        &self.found
+    }
+
+    /// Returns the canonical spelling of the value.
+    pub fn canonical(&self) -> &'static str {
+        self.canonical
+    }
+}
+
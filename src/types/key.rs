@@ -2,18 +2,26 @@
 use std::{borrow, fmt, ops, str};
 use derive_more::Display;
 use crate::load::report::{Failed, PathReporter};
+use yaml_rust::Yaml;
 use crate::load::yaml::{FromYaml, Value};
+use crate::store::DataStore;
+use crate::store::write::ToYaml;
 use super::marked::Marked;
+use super::text::Text;
 
 
 //------------ Key -----------------------------------------------------------
 
+/// The key of a document.
+///
+/// The key is kept as a shared [`Text`], so all copies of a key use the
+/// same memory.
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub struct Key(String);
+pub struct Key(Text);
 
 impl Key {
     pub fn from_string(s: String) -> Result<Self, InvalidKey> {
-        Ok(Key(s))
+        Ok(Key(s.into()))
     }
 
     pub fn as_str(&self) -> &str {
@@ -21,18 +29,44 @@ impl Key {
     }
 
     pub fn to_string(&self) -> String {
-        self.0.clone()
+        self.0.as_str().into()
     }
 
     pub fn country(&self) -> Option<&str> {
         self.0.split('.').nth(1)
     }
+
+    /// Returns the namespace of the key.
+    ///
+    /// The namespace is the key up to and including the second dot, e.g.,
+    /// `line.de.` for `line.de.4010`. Keys with only one dot are in the
+    /// namespace given by their first part, e.g., `country.` for
+    /// `country.de`. Keys without a dot have an empty namespace.
+    pub fn namespace(&self) -> &str {
+        &self.0[..self.namespace_len()]
+    }
+
+    /// Returns the part of the key after the namespace.
+    ///
+    /// This is `4010` for `line.de.4010`.
+    pub fn local_part(&self) -> &str {
+        &self.0[self.namespace_len()..]
+    }
+
+    fn namespace_len(&self) -> usize {
+        let mut dots = self.0.match_indices('.').map(|(index, _)| index + 1);
+        match (dots.next(), dots.next()) {
+            (_, Some(second)) => second,
+            (Some(first), None) => first,
+            (None, None) => 0,
+        }
+    }
 }
 
 impl Marked<Key> {
     pub fn from_string(s: Marked<String>, _report: &mut PathReporter)
                        -> Result<Self, Failed> {
-        Ok(s.map(Key))
+        Ok(s.map(|s| Key(s.into())))
     }
 }
 
@@ -76,7 +110,13 @@ impl<C> FromYaml<C> for Marked<Key> {
         _: &C,
         report: &mut PathReporter
     ) -> Result<Self, Failed> {
-        Ok(value.into_string(report)?.map(Key))
+        Ok(value.into_string(report)?.map(|s| Key(s.into())))
+    }
+}
+
+impl ToYaml for Key {
+    fn to_yaml(&self, _: &DataStore) -> Yaml {
+        Yaml::String(self.0.as_str().into())
     }
 }
 
@@ -90,6 +130,11 @@ impl fmt::Display for Key {
 }
 
 
+//--- Serialize and Deserialize
+
+serde_via_str!(Key);
+
+
 //------------ InvalidKey ----------------------------------------------------
 
 #[derive(Clone, Copy, Debug, Display)]
@@ -1,8 +1,11 @@
 //! A list with an optimization for holding a single item.
 
 use std::{cmp, fmt, mem, ops, slice};
+use yaml_rust::Yaml;
 use crate::load::report::{Failed, PathReporter};
 use crate::load::yaml::{FromYaml, Value};
+use crate::store::DataStore;
+use crate::store::write::{ToYaml, list_to_yaml};
 use super::marked::IntoMarked;
 
 
@@ -98,6 +101,20 @@ impl<T> List<T> {
         }
     }
 
+    /// Removes consecutive items that are considered the same by `op`.
+    pub fn dedup_by<F>(&mut self, op: F)
+    where F: FnMut(&mut T, &mut T) -> bool {
+        if let Inner::Many(ref mut inner) = self.inner {
+            inner.dedup_by(op)
+        }
+    }
+
+    /// Removes consecutive equal items.
+    pub fn dedup(&mut self)
+    where T: PartialEq {
+        self.dedup_by(|left, right| left == right)
+    }
+
     pub fn iter(&self) -> Iter<T> {
         Iter::new(self)
     }
@@ -235,6 +252,34 @@ impl<C, T: FromYaml<C>> FromYaml<C> for List<T> {
     }
 }
 
+impl<T: ToYaml> ToYaml for List<T> {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        list_to_yaml(self.iter(), store)
+    }
+
+    fn is_omitted(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for List<T> {
+    fn serialize<S: serde::Serializer>(
+        &self, serializer: S
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.as_slice())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for List<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D
+    ) -> Result<Self, D::Error> {
+        Vec::deserialize(deserializer).map(List::from)
+    }
+}
+
 impl<'a, T> IntoIterator for &'a List<T> {
     type Item = &'a T;
     type IntoIter = Iter<'a, T>;
@@ -3,9 +3,13 @@
 use std::{fmt, ops, str};
 use std::str::FromStr;
 use derive_more::Display;
+use yaml_rust::Yaml;
 use crate::load::yaml::{FromYaml, Value};
 use crate::load::report::{Failed, Message, PathReporter};
+use crate::store::DataStore;
+use crate::store::write::ToYaml;
 use super::marked::Marked;
+use super::text::Text;
 
 
 //------------ CountryCode ---------------------------------------------------
@@ -17,6 +21,7 @@ impl CountryCode {
     pub const AT: Self = CountryCode(*b"AT");
     pub const BE: Self = CountryCode(*b"BE");
     pub const CH: Self = CountryCode(*b"CH");
+    pub const CZ: Self = CountryCode(*b"CZ");
     pub const DD: Self = CountryCode(*b"DD");
     pub const DE: Self = CountryCode(*b"DE");
     pub const DK: Self = CountryCode(*b"DK");
@@ -58,6 +63,12 @@ impl<C> FromYaml<C> for Marked<CountryCode> {
     }
 }
 
+impl ToYaml for CountryCode {
+    fn to_yaml(&self, _: &DataStore) -> Yaml {
+        Yaml::String(self.as_str().to_ascii_lowercase())
+    }
+}
+
 impl FromStr for CountryCode {
     type Err = CountryCodeError;
 
@@ -84,6 +95,8 @@ impl fmt::Debug for CountryCode {
     }
 }
 
+serde_via_str!(CountryCode);
+
 
 //------------ LanguageCode --------------------------------------------------
 
@@ -94,6 +107,7 @@ impl LanguageCode {
     pub const CES: Self = LanguageCode(*b"CES");
     pub const DAN: Self = LanguageCode(*b"DAN");
     pub const DEU: Self = LanguageCode(*b"DEU");
+    pub const ELL: Self = LanguageCode(*b"ELL");
     pub const ENG: Self = LanguageCode(*b"ENG");
     pub const FRA: Self = LanguageCode(*b"FRA");
     pub const LAV: Self = LanguageCode(*b"LAV");
@@ -103,13 +117,65 @@ impl LanguageCode {
     pub const POL: Self = LanguageCode(*b"POL");
     pub const RUS: Self = LanguageCode(*b"RUS");
     pub const SWE: Self = LanguageCode(*b"SWE");
+    pub const UKR: Self = LanguageCode(*b"UKR");
 }
 
 impl LanguageCode {
     pub fn as_str(&self) -> &str {
         unsafe { str::from_utf8_unchecked(&self.0) }
     }
-}
+
+    /// Returns the language code for a language tag.
+    ///
+    /// Only the primary subtag is considered. It can be either a three
+    /// letter ISO 639-2 code or one of the two letter ISO 639-1 codes of
+    /// the languages used in the data.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let primary = tag.split(['-', '_']).next().unwrap_or("");
+        if primary.len() == 2 {
+            let primary = primary.to_ascii_lowercase();
+            ISO_639_1.iter().find(|item| item.0 == primary).map(|item| {
+                item.1
+            })
+        }
+        else {
+            Self::from_str(primary).ok()
+        }
+    }
+
+    /// Returns the table for transliterating the language into Latin.
+    pub fn transliteration(self) -> Option<&'static Transliteration> {
+        Transliteration::ALL.iter().find(|item| item.language == self)
+    }
+}
+
+/// The mapping of two letter to three letter language codes.
+const ISO_639_1: &[(&str, LanguageCode)] = &[
+    ("cs", LanguageCode::CES),
+    ("da", LanguageCode::DAN),
+    ("de", LanguageCode::DEU),
+    ("el", LanguageCode::ELL),
+    ("en", LanguageCode::ENG),
+    ("es", LanguageCode(*b"SPA")),
+    ("et", LanguageCode(*b"EST")),
+    ("fi", LanguageCode(*b"FIN")),
+    ("fr", LanguageCode::FRA),
+    ("hu", LanguageCode(*b"HUN")),
+    ("it", LanguageCode(*b"ITA")),
+    ("lt", LanguageCode(*b"LIT")),
+    ("lv", LanguageCode::LAV),
+    ("nb", LanguageCode::NOB),
+    ("nl", LanguageCode::NLD),
+    ("nn", LanguageCode::NNO),
+    ("no", LanguageCode::NOB),
+    ("pl", LanguageCode::POL),
+    ("pt", LanguageCode(*b"POR")),
+    ("ru", LanguageCode::RUS),
+    ("sk", LanguageCode(*b"SLK")),
+    ("sl", LanguageCode(*b"SLV")),
+    ("sv", LanguageCode::SWE),
+    ("uk", LanguageCode::UKR),
+];
 
 impl ops::Deref for LanguageCode {
     type Target = str;
@@ -137,6 +203,12 @@ impl<C> FromYaml<C> for Marked<LanguageCode> {
     }
 }
 
+impl ToYaml for LanguageCode {
+    fn to_yaml(&self, _: &DataStore) -> Yaml {
+        Yaml::String(self.as_str().to_ascii_lowercase())
+    }
+}
+
 impl FromStr for LanguageCode {
     type Err = LanguageCodeError;
 
@@ -164,6 +236,8 @@ impl fmt::Debug for LanguageCode {
     }
 }
 
+serde_via_str!(LanguageCode);
+
 
 //------------ LocalCode -----------------------------------------------------
 
@@ -226,6 +300,12 @@ impl<C> FromYaml<C> for Marked<LocalCode> {
     }
 }
 
+impl ToYaml for LocalCode {
+    fn to_yaml(&self, _: &DataStore) -> Yaml {
+        Yaml::String(self.as_str().to_ascii_lowercase())
+    }
+}
+
 impl FromStr for LocalCode {
     type Err = LocalCodeError;
 
@@ -252,21 +332,195 @@ impl fmt::Display for LocalCode {
     }
 }
 
+serde_via_str!(LocalCode);
+
+
+//------------ Transliteration -----------------------------------------------
+
+/// A table for transliterating a language written in a non-Latin script.
+///
+/// The table maps lower case characters to their Latin spelling. The
+/// result is only meant for finding names with Latin queries, so it is all
+/// lower case and uses plain ASCII letters.
+#[derive(Clone, Copy, Debug)]
+pub struct Transliteration {
+    /// The language the table is for.
+    pub language: LanguageCode,
+
+    /// The Latin spelling of each character of the script.
+    pub table: &'static [(char, &'static str)],
+}
+
+impl Transliteration {
+    /// The tables for all languages that need transliteration.
+    pub const ALL: &'static [Transliteration] = &[
+        Transliteration { language: LanguageCode::ELL, table: ELL_TABLE },
+        Transliteration { language: LanguageCode::RUS, table: RUS_TABLE },
+        Transliteration { language: LanguageCode::UKR, table: UKR_TABLE },
+    ];
+
+    /// Transliterates `text`.
+    ///
+    /// Characters not in the table are kept. Returns `None` if the text
+    /// doesn’t contain any characters from the table.
+    pub fn apply(&self, text: &str) -> Option<String> {
+        let mut res = String::with_capacity(text.len());
+        let mut changed = false;
+        for ch in text.chars() {
+            let lower = ch.to_lowercase().next().unwrap_or(ch);
+            match self.table.iter().find(|item| item.0 == lower) {
+                Some((_, latin)) => {
+                    res.push_str(latin);
+                    changed = true;
+                }
+                None => res.push(ch)
+            }
+        }
+        changed.then_some(res)
+    }
+}
+
+const ELL_TABLE: &[(char, &str)] = &[
+    ('α', "a"), ('ά', "a"), ('β', "v"), ('γ', "g"), ('δ', "d"),
+    ('ε', "e"), ('έ', "e"), ('ζ', "z"), ('η', "i"), ('ή', "i"),
+    ('θ', "th"), ('ι', "i"), ('ί', "i"), ('ϊ', "i"), ('ΐ', "i"),
+    ('κ', "k"), ('λ', "l"), ('μ', "m"), ('ν', "n"), ('ξ', "x"),
+    ('ο', "o"), ('ό', "o"), ('π', "p"), ('ρ', "r"), ('σ', "s"),
+    ('ς', "s"), ('τ', "t"), ('υ', "y"), ('ύ', "y"), ('ϋ', "y"),
+    ('ΰ', "y"), ('φ', "f"), ('χ', "ch"), ('ψ', "ps"), ('ω', "o"),
+    ('ώ', "o"),
+];
+
+const RUS_TABLE: &[(char, &str)] = &[
+    ('а', "a"), ('б', "b"), ('в', "v"), ('г', "g"), ('д', "d"),
+    ('е', "e"), ('ё', "e"), ('ж', "zh"), ('з', "z"), ('и', "i"),
+    ('й', "y"), ('к', "k"), ('л', "l"), ('м', "m"), ('н', "n"),
+    ('о', "o"), ('п', "p"), ('р', "r"), ('с', "s"), ('т', "t"),
+    ('у', "u"), ('ф', "f"), ('х', "kh"), ('ц', "ts"), ('ч', "ch"),
+    ('ш', "sh"), ('щ', "shch"), ('ъ', ""), ('ы', "y"), ('ь', ""),
+    ('э', "e"), ('ю', "yu"), ('я', "ya"),
+];
+
+const UKR_TABLE: &[(char, &str)] = &[
+    ('а', "a"), ('б', "b"), ('в', "v"), ('г', "h"), ('ґ', "g"),
+    ('д', "d"), ('е', "e"), ('є', "ye"), ('ж', "zh"), ('з', "z"),
+    ('и', "y"), ('і', "i"), ('ї', "yi"), ('й', "y"), ('к', "k"),
+    ('л', "l"), ('м', "m"), ('н', "n"), ('о', "o"), ('п', "p"),
+    ('р', "r"), ('с', "s"), ('т', "t"), ('у', "u"), ('ф', "f"),
+    ('х', "kh"), ('ц', "ts"), ('ч', "ch"), ('ш', "sh"), ('щ', "shch"),
+    ('ь', ""), ('ю', "yu"), ('я', "ya"), ('\'', ""), ('’', ""),
+];
+
+
+//------------ LanguagePreference --------------------------------------------
+
+/// An ordered list of preferred languages.
+///
+/// This is used to pick the best text out of localized texts. Languages
+/// earlier in the list are preferred.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LanguagePreference(Vec<LanguageCode>);
+
+impl LanguagePreference {
+    pub fn new(languages: Vec<LanguageCode>) -> Self {
+        let mut res = LanguagePreference(Vec::with_capacity(languages.len()));
+        for lang in languages {
+            res.push(lang)
+        }
+        res
+    }
+
+    /// Creates the preference from the value of an Accept-Language header.
+    ///
+    /// Only the primary language subtag of each language range is used.
+    /// Ranges that aren’t understood, the wildcard, and ranges with a
+    /// quality of zero are ignored.
+    pub fn from_accept_language(header: &str) -> Self {
+        let mut ranges = Vec::new();
+        for item in header.split(',') {
+            let mut parts = item.split(';');
+            let tag = parts.next().unwrap_or("").trim();
+            let quality = parts.find_map(|param| {
+                param.trim().strip_prefix("q=").map(|q| {
+                    q.trim().parse::<f32>().unwrap_or(0.)
+                })
+            }).unwrap_or(1.);
+            if quality <= 0. {
+                continue
+            }
+            if let Some(lang) = LanguageCode::from_tag(tag) {
+                ranges.push((lang, quality))
+            }
+        }
+        // The sort is stable, so equal qualities keep the header order.
+        ranges.sort_by(|left, right| right.1.total_cmp(&left.1));
+        Self::new(ranges.into_iter().map(|(lang, _)| lang).collect())
+    }
+
+    /// Adds a language at the end unless it is already present.
+    pub fn push(&mut self, lang: LanguageCode) {
+        if !self.0.contains(&lang) {
+            self.0.push(lang)
+        }
+    }
+
+    /// Returns the preference with `lang` as the last resort.
+    pub fn with_fallback(mut self, lang: LanguageCode) -> Self {
+        self.push(lang);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = LanguageCode> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+impl FromStr for LanguagePreference {
+    type Err = LanguageCodeError;
+
+    /// Parses a comma-separated list of language tags.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',').map(|tag| {
+            LanguageCode::from_tag(tag.trim()).ok_or_else(|| {
+                LanguageCodeError(tag.into())
+            })
+        }).collect::<Result<_, _>>().map(Self::new)
+    }
+}
+
 
 //------------ CodedText and friends -----------------------------------------
 
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct CodedText<C: Ord>(CTInner<C>);
 
+/// The content of a coded text.
+///
+/// With serde, plain text is a string and a map is a sequence of pairs of
+/// code and text, keeping the order of the map.
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(untagged)
+)]
 enum CTInner<C: Ord> {
-    Plain(Marked<String>),
-    Map(Vec<(Marked<C>, Marked<String>)>),
+    Plain(Marked<Text>),
+    Map(Vec<(Marked<C>, Marked<Text>)>),
 }
 
 impl<C: Ord> CodedText<C> {
     pub fn new_plain(content: String) -> Self {
-        Self(CTInner::Plain(content.into()))
+        Self(CTInner::Plain(Text::from(content).into()))
     }
 
     pub fn first(&self) -> &str {
@@ -355,6 +609,20 @@ impl<C: Ord + From<LanguageCode>> CodedText<C> {
         }
     }
 
+    /// Returns the text for the most preferred language available.
+    ///
+    /// Falls back to the first text if none of the languages is present.
+    pub fn for_preference(&self, preference: &LanguagePreference) -> &str {
+        match self.0 {
+            CTInner::Plain(ref inner) => inner.as_str(),
+            CTInner::Map(_) => {
+                preference.iter().find_map(|lang| {
+                    self.for_language(lang)
+                }).unwrap_or_else(|| self.first())
+            }
+        }
+    }
+
     pub fn iter_for_language<'a>(
         iter: impl Iterator<Item = &'a Self> + 'a,
         language: LanguageCode
@@ -398,7 +666,9 @@ where <C as FromStr>::Err: Message {
                 for (key, value) in value.into_iter() {
                     let key = key.try_map(|s| C::from_str(&s))
                                  .map_err(|err| { report.error(err); Failed });
-                    let value = value.into_string(report);
+                    let value = value.into_string(report).map(|value| {
+                        value.map(Text::from)
+                    });
                     match (key, value, failed) {
                         (Ok(key), Ok(value), false) => {
                             res.push((key, value));
@@ -415,15 +685,28 @@ where <C as FromStr>::Err: Message {
             }
             Err(value) => {
                 value.into_string(report).map(|res| {
-                    CodedText(CTInner::Plain(res))
+                    CodedText(CTInner::Plain(res.map(Text::from)))
                 })
             }
         }
     }
 }
 
+impl<C: Ord + ToYaml> ToYaml for CodedText<C> {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        match self.0 {
+            CTInner::Plain(ref inner) => inner.to_yaml(store),
+            CTInner::Map(ref inner) => {
+                Yaml::Hash(inner.iter().map(|(code, text)| {
+                    (code.to_yaml(store), text.to_yaml(store))
+                }).collect())
+            }
+        }
+    }
+}
+
 impl<'a, C: Ord> IntoIterator for &'a CodedText<C> {
-    type Item = (Option<&'a Marked<C>>, &'a Marked<String>);
+    type Item = (Option<&'a Marked<C>>, &'a Marked<Text>);
     type IntoIter = CodedTextIter<'a, C>;
 
     fn into_iter(self) -> Self::IntoIter {
@@ -443,7 +726,7 @@ pub struct CodedTextIter<'a, C: Ord> {
 }
 
 impl<'a, C: Ord> Iterator for CodedTextIter<'a, C> {
-    type Item = (Option<&'a Marked<C>>, &'a Marked<String>);
+    type Item = (Option<&'a Marked<C>>, &'a Marked<Text>);
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.text.0 {
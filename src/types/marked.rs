@@ -2,15 +2,27 @@
 
 use std::{borrow, cmp, fmt, hash, ops};
 use std::cmp::min;
+use yaml_rust::Yaml;
 use yaml_rust::scanner::Marker;
+use crate::store::DataStore;
+use crate::store::write::ToYaml;
 
 
 //------------ Marked --------------------------------------------------------
 
 /// A value that optionally is marked by its source location.
+///
+/// With serde, only the value is serialized. Deserialized values have no
+/// location.
 #[derive(Clone, Copy, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct Marked<T> {
     value: T,
+    #[cfg_attr(feature = "serde", serde(skip))]
     location: Location,
 }
 
@@ -27,6 +39,10 @@ impl<T> Marked<T> {
         self.location
     }
 
+    pub fn set_location(&mut self, location: Location) {
+        self.location = location
+    }
+
     pub fn as_value(&self) -> &T {
         &self.value
     }
@@ -122,6 +138,16 @@ impl<T> AsMut<T> for Marked<T> {
     }
 }
 
+impl<T: ToYaml> ToYaml for Marked<T> {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        self.value.to_yaml(store)
+    }
+
+    fn is_omitted(&self) -> bool {
+        self.value.is_omitted()
+    }
+}
+
 
 //--- PartialEq and Eq
 //
@@ -200,6 +226,7 @@ impl<T> IntoMarked for T { }
 //------------ Location ------------------------------------------------------
 
 #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Location(u32);
 
 impl Location {
@@ -1,18 +1,24 @@
 pub use self::date::{Date, EventDate};
 pub use self::key::Key;
 pub use self::list::List;
-pub use self::local::{CountryCode, LanguageCode, LocalCode, LocalText,
-                      LanguageText};
+pub use self::local::{CountryCode, LanguageCode, LanguagePreference,
+                      LocalCode, LocalText, LanguageText, Transliteration};
 pub use self::marked::{IntoMarked, Location, Marked};
+pub use self::period::OperatingPeriods;
 pub use self::set::Set;
+pub use self::text::Text;
 pub use self::url::Url;
 
+#[macro_use] mod serial;
+
 pub mod date;
 #[macro_use] pub mod enums;
 pub mod key;
 pub mod list;
 pub mod local;
 pub mod marked;
+pub mod period;
 pub mod set;
+pub mod text;
 pub mod url;
 
@@ -0,0 +1,264 @@
+//! Periods of operation.
+
+use std::fmt;
+use std::cmp::Ordering;
+use serde_json::{json, Value as JsonValue};
+use super::date::Date;
+
+
+//------------ OperatingPeriods ----------------------------------------------
+
+/// The periods during which something was in operation.
+///
+/// The periods are derived from status changes in events. They are built
+/// by feeding the changes in chronological order via [`open`][Self::open]
+/// and [`close`][Self::close].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OperatingPeriods {
+    periods: Vec<Period>,
+}
+
+impl OperatingPeriods {
+    /// Starts a period at `date` unless one is already running.
+    ///
+    /// A period started on the same date the previous one ended continues
+    /// the previous period instead.
+    pub fn open(&mut self, date: Option<Date>) {
+        if let Some(last) = self.periods.last_mut() {
+            match last.end {
+                None => return,
+                Some(end) => {
+                    if end.date.is_some() && end.date == date {
+                        last.end = None;
+                        return
+                    }
+                }
+            }
+        }
+        self.periods.push(Period { start: date, end: None })
+    }
+
+    /// Ends the running period at `date`, if there is one.
+    pub fn close(&mut self, date: Option<Date>, reason: EndReason) {
+        if let Some(last) = self.periods.last_mut() {
+            if last.end.is_none() {
+                last.end = Some(PeriodEnd { date, reason })
+            }
+        }
+    }
+
+    /// Adds all periods of `other`, merging overlapping periods.
+    ///
+    /// Periods with an unknown start are considered to start before all
+    /// others.
+    pub fn merge(&mut self, other: &OperatingPeriods) {
+        if other.periods.is_empty() {
+            return
+        }
+        let mut all = std::mem::take(&mut self.periods);
+        all.extend(other.periods.iter().cloned());
+        all.sort_by_key(|period| period.start);
+        for period in all {
+            match self.periods.last_mut() {
+                Some(last) if last.overlaps_start(&period) => {
+                    if last.ends_before(&period) {
+                        last.end = period.end
+                    }
+                }
+                _ => self.periods.push(period)
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.periods.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Period> + '_ {
+        self.periods.iter()
+    }
+
+    /// Returns whether any of the periods may include `date`.
+    pub fn may_include(&self, date: &Date) -> bool {
+        self.periods.iter().any(|period| period.may_include(date))
+    }
+
+    /// Returns whether any period may overlap with any period of `other`.
+    pub fn may_overlap(&self, other: &OperatingPeriods) -> bool {
+        self.periods.iter().any(|left| {
+            other.periods.iter().any(|right| left.may_overlap(right))
+        })
+    }
+
+    /// Returns whether the last period is still running.
+    pub fn is_open(&self) -> bool {
+        self.periods.last().map(|last| last.end.is_none()).unwrap_or(false)
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        self.periods.iter().map(Period::to_json).collect()
+    }
+}
+
+impl fmt::Display for OperatingPeriods {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, period) in self.periods.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(", ")?;
+            }
+            period.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ Period --------------------------------------------------------
+
+/// A single period of operation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Period {
+    /// The date operation started if it is known.
+    pub start: Option<Date>,
+
+    /// How the period ended if it has.
+    pub end: Option<PeriodEnd>,
+}
+
+impl Period {
+    /// Returns whether `other` starts before or when this period ends.
+    fn overlaps_start(&self, other: &Period) -> bool {
+        match self.end {
+            None => true,
+            Some(end) => match (end.date, other.start) {
+                (Some(end), Some(start)) => start <= end,
+                _ => true,
+            }
+        }
+    }
+
+    /// Returns whether this period ends before `other`.
+    fn ends_before(&self, other: &Period) -> bool {
+        match (self.end, other.end) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(left), Some(right)) => left.date < right.date
+        }
+    }
+
+    /// Returns whether the two periods may overlap.
+    ///
+    /// Periods where one ends on the day the other starts don’t overlap.
+    pub fn may_overlap(&self, other: &Period) -> bool {
+        !self.ends_by(other.start) && !other.ends_by(self.start)
+    }
+
+    /// Returns whether the period definitely ends before or on `date`.
+    fn ends_by(&self, date: Option<Date>) -> bool {
+        match (self.end.and_then(|end| end.date), date) {
+            (Some(end), Some(date)) => {
+                end == date || matches!(
+                    end.cmp_range(&date),
+                    Some(Ordering::Less | Ordering::Equal)
+                )
+            }
+            _ => false
+        }
+    }
+
+    /// Returns whether the period may include `date`.
+    ///
+    /// Unknown start and end dates don’t limit the period. A date that
+    /// can’t be ordered definitely, such as a year for a period starting
+    /// in the middle of it, is included.
+    pub fn may_include(&self, date: &Date) -> bool {
+        if let Some(start) = self.start {
+            if start.cmp_range(date) == Some(Ordering::Greater) {
+                return false
+            }
+        }
+        if let Some(end) = self.end.and_then(|end| end.date) {
+            if end.cmp_range(date) == Some(Ordering::Less) {
+                return false
+            }
+        }
+        true
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "start": self.start.map(|date| date.to_string()),
+            "end": self.end.and_then(|end| {
+                end.date.map(|date| date.to_string())
+            }),
+            "end_reason": self.end.map(|end| end.reason.as_str()),
+        })
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.start {
+            Some(date) => write!(f, "{}", date)?,
+            None => f.write_str("?")?,
+        }
+        f.write_str("\u{2013}")?;
+        if let Some(end) = self.end {
+            match end.date {
+                Some(date) => write!(f, "{}", date)?,
+                None => f.write_str("?")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+
+//------------ PeriodEnd -----------------------------------------------------
+
+/// The end of a period of operation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PeriodEnd {
+    /// The date operation ended if it is known.
+    pub date: Option<Date>,
+
+    /// Why operation ended.
+    pub reason: EndReason,
+}
+
+
+//------------ EndReason -----------------------------------------------------
+
+/// Why a period of operation ended.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum EndReason {
+    /// Operation was suspended and may have resumed later.
+    Suspended,
+
+    /// Operation ended for good, at least at the time.
+    Closed,
+}
+
+impl EndReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EndReason::Suspended => "suspended",
+            EndReason::Closed => "closed",
+        }
+    }
+}
+
+impl fmt::Display for EndReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
@@ -0,0 +1,34 @@
+//! Support for serde.
+//!
+//! Serde support is only available with the `serde` feature. Types that are
+//! strings in the YAML data are serialized as strings, too. Source
+//! locations are not serialized at all.
+
+/// Implements `Serialize` and `Deserialize` via `Display` and `FromStr`.
+macro_rules! serde_via_str {
+    ( $name:ty ) => {
+        #[cfg(feature = "serde")]
+        impl ::serde::Serialize for $name {
+            fn serialize<S: ::serde::Serializer>(
+                &self, serializer: S
+            ) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> ::serde::Deserialize<'de> for $name {
+            fn deserialize<D: ::serde::Deserializer<'de>>(
+                deserializer: D
+            ) -> Result<Self, D::Error> {
+                let s = <String as ::serde::Deserialize>::deserialize(
+                    deserializer
+                )?;
+                ::std::str::FromStr::from_str(&s).map_err(
+                    <D::Error as ::serde::de::Error>::custom
+                )
+            }
+        }
+    }
+}
+
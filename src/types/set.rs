@@ -4,8 +4,11 @@ use std::mem;
 use std::hash::Hash;
 use std::collections::hash_set;
 use std::collections::HashSet;
+use yaml_rust::Yaml;
 use crate::load::yaml::{FromYaml, Value};
 use crate::load::report::{Failed, PathReporter};
+use crate::store::DataStore;
+use crate::store::write::{ToYaml, set_to_yaml};
 use super::marked::Location;
 
 
@@ -54,13 +57,15 @@ impl<T: Hash + Eq> Set<T> {
             true
         }
         else {
-            let mut set = HashSet::new();
-            set.insert(match mem::replace(&mut self.inner, Inner::Empty) {
+            let first = match mem::replace(&mut self.inner, Inner::Empty) {
                 Inner::One(first) => first,
                 _ => unreachable!()
-            });
+            };
+            let mut set = HashSet::new();
+            set.insert(first);
+            let res = set.insert(value);
             self.inner = Inner::Many(set);
-            true
+            res
         }
     }
 
@@ -150,6 +155,40 @@ impl<C, T: FromYaml<C> + Hash + Eq> FromYaml<C> for Set<T> {
     }
 }
 
+impl<T: ToYaml + Hash + Eq> ToYaml for Set<T> {
+    fn to_yaml(&self, store: &DataStore) -> Yaml {
+        set_to_yaml(self.iter(), store)
+    }
+
+    fn is_omitted(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize + Hash + Eq> serde::Serialize for Set<T> {
+    fn serialize<S: serde::Serializer>(
+        &self, serializer: S
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for Set<T>
+where T: serde::Deserialize<'de> + Hash + Eq {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D
+    ) -> Result<Self, D::Error> {
+        let mut set = HashSet::deserialize(deserializer)?;
+        Ok(match set.len() {
+            0 => Set::new(),
+            1 => Set::one(set.drain().next().unwrap(), Location::default()),
+            _ => Set::many(set, Location::default()),
+        })
+    }
+}
+
 impl<T: Hash + Eq> PartialEq for Set<T> {
     fn eq(&self, other: &Self) -> bool {
         use self::Inner::*;
@@ -0,0 +1,342 @@
+//! Shared strings.
+//!
+//! Names, codes, and keys repeat a lot throughout the data. Instead of
+//! keeping a separate copy for each occurrence, a [`Text`] refers to a
+//! single shared copy. All texts are created through a process-wide pool
+//! that hands out the same copy for equal strings.
+
+use std::{borrow, fmt, ops, str};
+use std::collections::HashSet;
+use std::collections::hash_map::RandomState;
+use std::convert::Infallible;
+use std::hash::BuildHasher;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, TryLockError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use yaml_rust::Yaml;
+use crate::load::report::{Failed, PathReporter};
+use crate::load::yaml::{FromYaml, Value};
+use crate::store::DataStore;
+use crate::store::write::ToYaml;
+use super::marked::Marked;
+
+
+//------------ Text ----------------------------------------------------------
+
+/// An immutable, interned string.
+///
+/// Cloning a text is cheap as it only increases a reference count.
+#[derive(Clone, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Text(Arc<str>);
+
+impl Text {
+    /// Returns the text for `s`, sharing it with equal texts.
+    pub fn new(s: &str) -> Self {
+        Text(pool().intern(s))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Drops all strings from the pool that aren’t used anymore.
+    ///
+    /// This should be called after a larger amount of data has been
+    /// dropped, such as after a failed load.
+    pub fn release_unused() {
+        pool().release_unused()
+    }
+
+    /// Returns statistics about the strings interned so far.
+    pub fn pool_stats() -> PoolStats {
+        pool().stats()
+    }
+}
+
+
+//--- From and FromStr
+
+impl From<&str> for Text {
+    fn from(s: &str) -> Self {
+        Text::new(s)
+    }
+}
+
+impl From<String> for Text {
+    fn from(s: String) -> Self {
+        Text::new(&s)
+    }
+}
+
+impl str::FromStr for Text {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Text::new(s))
+    }
+}
+
+
+//--- Deref, AsRef, and Borrow
+
+impl ops::Deref for Text {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Text {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl borrow::Borrow<str> for Text {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl borrow::Borrow<str> for Marked<Text> {
+    fn borrow(&self) -> &str {
+        self.as_value().as_str()
+    }
+}
+
+
+//--- PartialEq
+
+impl PartialEq<str> for Text {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<'a> PartialEq<&'a str> for Text {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+
+//--- FromYaml and ToYaml
+
+impl<C> FromYaml<C> for Marked<Text> {
+    fn from_yaml(
+        value: Value,
+        _: &C,
+        report: &mut PathReporter
+    ) -> Result<Self, Failed> {
+        Ok(value.into_string(report)?.map(Text::from))
+    }
+}
+
+impl ToYaml for Text {
+    fn to_yaml(&self, _: &DataStore) -> Yaml {
+        Yaml::String(self.as_str().into())
+    }
+}
+
+
+//--- Display and Debug
+
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Debug for Text {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+
+//--- Serialize and Deserialize
+
+serde_via_str!(Text);
+
+
+//------------ Pool ----------------------------------------------------------
+
+/// The pool of all interned strings.
+///
+/// The documents of a data tree are loaded in parallel and all of them
+/// intern their strings. In order for the threads not to queue up behind
+/// a single lock, the strings are spread over a number of shards by their
+/// hash value, each with its own lock.
+#[derive(Debug)]
+struct Pool {
+    /// The shards holding the interned strings.
+    shards: Box<[Mutex<HashSet<Arc<str>>>]>,
+
+    /// The hasher used to pick the shard for a string.
+    hasher: RandomState,
+
+    /// The number of strings requested.
+    requested: AtomicUsize,
+
+    /// The combined length of all strings requested.
+    requested_bytes: AtomicUsize,
+
+    /// The number of requests that had to wait for their shard.
+    contended: AtomicUsize,
+}
+
+impl Pool {
+    /// The number of shards.
+    const SHARDS: usize = 64;
+
+    fn new(shards: usize) -> Self {
+        Pool {
+            shards: (0..shards.max(1)).map(|_| Default::default()).collect(),
+            hasher: RandomState::new(),
+            requested: AtomicUsize::new(0),
+            requested_bytes: AtomicUsize::new(0),
+            contended: AtomicUsize::new(0),
+        }
+    }
+
+    fn intern(&self, s: &str) -> Arc<str> {
+        self.requested.fetch_add(1, Ordering::Relaxed);
+        self.requested_bytes.fetch_add(s.len(), Ordering::Relaxed);
+        let mut strings = self.lock(self.shard(s));
+        if let Some(res) = strings.get(s) {
+            return res.clone()
+        }
+        let res = Arc::<str>::from(s);
+        strings.insert(res.clone());
+        res
+    }
+
+    fn release_unused(&self) {
+        for shard in self.shards.iter() {
+            shard.lock().unwrap().retain(|item| Arc::strong_count(item) > 1)
+        }
+    }
+
+    fn stats(&self) -> PoolStats {
+        let mut res = PoolStats {
+            requested: self.requested.load(Ordering::Relaxed),
+            requested_bytes: self.requested_bytes.load(Ordering::Relaxed),
+            contended: self.contended.load(Ordering::Relaxed),
+            shards: self.shards.len(),
+            .. Default::default()
+        };
+        for shard in self.shards.iter() {
+            let strings = shard.lock().unwrap();
+            res.unique += strings.len();
+            res.unique_bytes += strings.iter().map(|item| {
+                item.len()
+            }).sum::<usize>();
+        }
+        res
+    }
+
+    /// Returns the shard for `s`.
+    fn shard(&self, s: &str) -> &Mutex<HashSet<Arc<str>>> {
+        let hash = self.hasher.hash_one(s) as usize;
+        &self.shards[hash % self.shards.len()]
+    }
+
+    /// Locks `shard`, counting the request if it has to wait.
+    fn lock<'a>(
+        &self, shard: &'a Mutex<HashSet<Arc<str>>>
+    ) -> MutexGuard<'a, HashSet<Arc<str>>> {
+        match shard.try_lock() {
+            Ok(res) => res,
+            Err(TryLockError::WouldBlock) => {
+                self.contended.fetch_add(1, Ordering::Relaxed);
+                shard.lock().unwrap()
+            }
+            Err(TryLockError::Poisoned(err)) => panic!("{}", err),
+        }
+    }
+}
+
+/// Returns the process-wide pool.
+fn pool() -> &'static Pool {
+    static POOL: OnceLock<Pool> = OnceLock::new();
+    POOL.get_or_init(|| Pool::new(Pool::SHARDS))
+}
+
+
+//------------ PoolStats -----------------------------------------------------
+
+/// Statistics about interned strings.
+///
+/// The requested numbers are what would have been stored without
+/// interning, the unique numbers what is actually stored.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PoolStats {
+    /// The number of strings requested so far.
+    pub requested: usize,
+
+    /// The combined length of all strings requested so far.
+    pub requested_bytes: usize,
+
+    /// The number of strings currently in the pool.
+    pub unique: usize,
+
+    /// The combined length of all strings currently in the pool.
+    pub unique_bytes: usize,
+
+    /// The number of requests that had to wait for a lock.
+    pub contended: usize,
+
+    /// The number of separately locked shards of the pool.
+    pub shards: usize,
+}
+
+impl fmt::Display for PoolStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} strings with {} bytes stored as {} strings with {} bytes, \
+             {} requests waited for one of {} locks",
+            self.requested, self.requested_bytes,
+            self.unique, self.unique_bytes,
+            self.contended, self.shards,
+        )
+    }
+}
+
+
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+    use super::*;
+
+    /// Interns the same strings from a number of threads.
+    fn intern_parallel(pool: &Pool) -> PoolStats {
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for i in 0..20_000 {
+                        let s = format!("name.{}", i % 5_000);
+                        let left = pool.intern(&s);
+                        let right = pool.intern(&s);
+                        assert!(Arc::ptr_eq(&left, &right));
+                    }
+                });
+            }
+        });
+        pool.stats()
+    }
+
+    /// Checks that the sharded pool stores the same as a single lock.
+    #[test]
+    fn sharded_pool() {
+        let single = intern_parallel(&Pool::new(1));
+        let sharded = intern_parallel(&Pool::new(Pool::SHARDS));
+        assert_eq!(single.unique, 5_000);
+        assert_eq!(sharded.unique, 5_000);
+        assert_eq!(single.requested, sharded.requested);
+        assert_eq!(single.unique_bytes, sharded.unique_bytes);
+    }
+}
@@ -1,7 +1,11 @@
 use std::fmt;
+use std::str::FromStr;
 use derive_more::Display;
+use yaml_rust::Yaml;
 use crate::load::yaml::{FromYaml, Value};
 use crate::load::report::{Failed, PathReporter};
+use crate::store::DataStore;
+use crate::store::write::ToYaml;
 use super::{IntoMarked, Marked};
 
 
@@ -39,12 +43,28 @@ impl<C> FromYaml<C> for Marked<Url> {
     }
 }
 
+impl ToYaml for Url {
+    fn to_yaml(&self, _: &DataStore) -> Yaml {
+        Yaml::String(self.as_str().into())
+    }
+}
+
+impl FromStr for Url {
+    type Err = url::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        url::Url::parse(s).map(Url)
+    }
+}
+
 impl fmt::Display for Url {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.write_str(self.0.as_str())
     }
 }
 
+serde_via_str!(Url);
+
 
 //------------ UrlError ------------------------------------------------------
 
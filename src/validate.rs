@@ -0,0 +1,796 @@
+//! Consistency checks of the data.
+//!
+//! Validation runs on a loaded [`DataStore`] and looks for data that is
+//! likely wrong but doesn’t prevent the data from being used. It is made
+//! up of individual [`Rule`]s, each of which can be switched off through
+//! the [`Validator`]. Problems are reported as warnings unless the
+//! [`Config`] of the data tree says otherwise.
+
+use std::collections::HashSet;
+use std::cmp::Ordering;
+use derive_more::Display;
+use crate::document::{line, point};
+use crate::document::combined::Data;
+use crate::document::line::ConcessionRight;
+use crate::document::local;
+use crate::load::config::{Config, Level};
+use crate::load::report::{PathReporter, Report, Reporter, Stage};
+use crate::store::DataStore;
+use crate::types::{Date, EventDate, IntoMarked, Key, Location, Marked};
+
+
+//------------ Rule ----------------------------------------------------------
+
+/// A single validation rule.
+pub trait Rule: Sync {
+    /// Returns the name used to refer to the rule.
+    fn name(&self) -> &'static str;
+
+    /// Returns a short description of what the rule checks.
+    fn description(&self) -> &'static str;
+
+    /// Checks a single document.
+    fn check(
+        &self, data: &Data, store: &DataStore, report: &mut PathReporter
+    );
+}
+
+
+//------------ Registry ------------------------------------------------------
+
+/// All available rules.
+pub static RULES: &[&dyn Rule] = &[
+    &Kilometrage,
+    &PointLines,
+    &PointConnections,
+    &ConcessionDate,
+    &ConcessionExpiry,
+    &LineStatus,
+    &PointReopened,
+    &LineRegion,
+    &LineEntityExists,
+    &BasisDate,
+    &PointName16,
+    &KeyNamespace,
+    &LineCode,
+    &PointCode,
+];
+
+/// Returns the rule with the given name.
+pub fn rule(name: &str) -> Option<&'static dyn Rule> {
+    RULES.iter().copied().find(|rule| rule.name() == name)
+}
+
+
+//------------ Validator -----------------------------------------------------
+
+/// Runs a selection of rules over the data.
+///
+/// A new validator has all rules enabled and reports all problems as
+/// warnings. A [`Config`] can change the level of rules per document.
+#[derive(Clone, Debug, Default)]
+pub struct Validator {
+    disabled: HashSet<&'static str>,
+    config: Config,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a validator using the given configuration.
+    pub fn with_config(config: Config) -> Self {
+        Validator { disabled: HashSet::new(), config }
+    }
+
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config
+    }
+
+    /// Disables the rule with the given name.
+    pub fn disable(&mut self, name: &str) -> Result<(), UnknownRule> {
+        let rule = rule(name).ok_or_else(|| UnknownRule(name.into()))?;
+        self.disabled.insert(rule.name());
+        Ok(())
+    }
+
+    /// Enables the rule with the given name.
+    pub fn enable(&mut self, name: &str) -> Result<(), UnknownRule> {
+        let rule = rule(name).ok_or_else(|| UnknownRule(name.into()))?;
+        self.disabled.remove(rule.name());
+        Ok(())
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        !self.disabled.contains(name)
+    }
+
+    /// Returns an iterator over the enabled rules.
+    pub fn rules(&self) -> impl Iterator<Item = &'static dyn Rule> + '_ {
+        RULES.iter().copied().filter(|rule| self.is_enabled(rule.name()))
+    }
+
+    /// Runs all enabled rules over all documents of the store.
+    pub fn run(&self, store: &DataStore) -> Report {
+        let report = Reporter::new();
+        let stage = report.clone().stage(Stage::Crossref);
+        for data in store.iter() {
+            let mut report = stage.clone().with_path(
+                data.origin().path().clone()
+            );
+            for rule in self.rules() {
+                let level = self.config.level(rule.name(), data);
+                if level == Level::Ignore {
+                    continue
+                }
+                report.set_warning_severity(level.severity());
+                rule.check(data, store, &mut report)
+            }
+        }
+        drop(stage);
+        report.unwrap()
+    }
+}
+
+
+//------------ Kilometrage ---------------------------------------------------
+
+/// The kilometrage of the points of a line must be monotonic.
+pub struct Kilometrage;
+
+impl Rule for Kilometrage {
+    fn name(&self) -> &'static str {
+        "kilometrage"
+    }
+
+    fn description(&self) -> &'static str {
+        "the kilometrage of a line’s points runs in one direction"
+    }
+
+    fn check(
+        &self, data: &Data, store: &DataStore, report: &mut PathReporter
+    ) {
+        if let Some(line) = data.try_as_line() {
+            line.check_kilometrage(store, report)
+        }
+    }
+}
+
+
+//------------ PointLines ----------------------------------------------------
+
+/// Points only give locations for lines that list them.
+pub struct PointLines;
+
+impl Rule for PointLines {
+    fn name(&self) -> &'static str {
+        "point-lines"
+    }
+
+    fn description(&self) -> &'static str {
+        "points only have locations on lines that list them"
+    }
+
+    fn check(
+        &self, data: &Data, store: &DataStore, report: &mut PathReporter
+    ) {
+        if let Some(point) = data.try_as_point() {
+            point.check_location_lines(store, report)
+        }
+    }
+}
+
+
+//------------ PointConnections ----------------------------------------------
+
+/// Connected points list each other.
+pub struct PointConnections;
+
+impl Rule for PointConnections {
+    fn name(&self) -> &'static str {
+        "point-connections"
+    }
+
+    fn description(&self) -> &'static str {
+        "connected points list each other as connections"
+    }
+
+    fn check(
+        &self, data: &Data, store: &DataStore, report: &mut PathReporter
+    ) {
+        if let Some(point) = data.try_as_point() {
+            point.check_connections(store, report)
+        }
+    }
+}
+
+
+//------------ ConcessionDate ------------------------------------------------
+
+/// Construction and operation can’t predate the construction concession.
+pub struct ConcessionDate;
+
+impl Rule for ConcessionDate {
+    fn name(&self) -> &'static str {
+        "concession-date"
+    }
+
+    fn description(&self) -> &'static str {
+        "a line isn’t built or operated before its construction concession"
+    }
+
+    fn check(
+        &self, data: &Data, _store: &DataStore, report: &mut PathReporter
+    ) {
+        let line = match data.try_as_line() {
+            Some(line) => line,
+            None => return
+        };
+        let len = line.points.len();
+
+        // The first construction concession for each event section.
+        let concessions = line.events.iter().filter_map(|event| {
+            let concession = event.concession()?;
+            if !concession.rights.iter().any(|right| {
+                right.into_value() == ConcessionRight::Construction
+            }) {
+                return None
+            }
+            Some((first_date(&event.date)?, event.sections.overall(len)))
+        }).collect::<Vec<_>>();
+        if concessions.is_empty() {
+            return
+        }
+
+        for event in line.events.iter() {
+            let status = match event.status() {
+                Some(status) => status,
+                None => continue
+            };
+            if matches!(status, line::Status::None | line::Status::Planned) {
+                continue
+            }
+            let date = match first_date(&event.date) {
+                Some(date) => date,
+                None => continue
+            };
+            let section = event.sections.overall(len);
+            let concession = concessions.iter().find(|(_, concession)| {
+                concession.start_idx <= section.end_idx
+                    && section.start_idx <= concession.end_idx
+            });
+            if let Some((concession, _)) = concession {
+                if date.cmp_range(concession) == Some(Ordering::Less) {
+                    report.warning(
+                        BeforeConcession {
+                            status, concession: concession.into_value()
+                        }.marked(date.location())
+                    )
+                }
+            }
+        }
+    }
+}
+
+
+//------------ ConcessionExpiry ----------------------------------------------
+
+/// A line doesn’t continue after its concession expired unless renewed.
+///
+/// A concession is considered renewed if a later event grants another
+/// concession. Events that suspend or close the line are expected after
+/// a concession expired and are ignored.
+pub struct ConcessionExpiry;
+
+impl Rule for ConcessionExpiry {
+    fn name(&self) -> &'static str {
+        "concession-expiry"
+    }
+
+    fn description(&self) -> &'static str {
+        "a line has no events after its concession expired unless renewed"
+    }
+
+    fn check(
+        &self, data: &Data, _store: &DataStore, report: &mut PathReporter
+    ) {
+        use crate::document::line::Status as S;
+
+        let line = match data.try_as_line() {
+            Some(line) => line,
+            None => return
+        };
+        let concessions = line.concession_periods();
+        for concession in &concessions {
+            let until = match concession.until {
+                Some(until) => until,
+                None => continue
+            };
+            let renewed = concessions.iter().any(|other| {
+                match (other.start, concession.start) {
+                    (Some(other), Some(start)) => {
+                        other.cmp_range(&start) == Some(Ordering::Greater)
+                    }
+                    _ => false
+                }
+            });
+            if renewed {
+                continue
+            }
+            let later = line.events.iter().filter(|event| {
+                !matches!(
+                    event.status(),
+                    Some(S::Suspended | S::Closed | S::Removed | S::Released)
+                )
+            }).filter_map(|event| first_date(&event.date)).find(|date| {
+                concession.expired_before(date)
+            });
+            if let Some(date) = later {
+                report.warning(
+                    AfterConcessionExpiry {
+                        until: until.into_value()
+                    }.marked(date.location())
+                )
+            }
+        }
+    }
+}
+
+
+//------------ LineStatus ----------------------------------------------------
+
+/// The status of a line section must progress in a possible order.
+///
+/// A closed or removed section can only become open again via the
+/// ‘reopened’ status, and a section can only be reopened if it was closed
+/// or suspended before.
+pub struct LineStatus;
+
+impl Rule for LineStatus {
+    fn name(&self) -> &'static str {
+        "line-status"
+    }
+
+    fn description(&self) -> &'static str {
+        "the status of a line progresses in a possible order"
+    }
+
+    fn check(
+        &self, data: &Data, _store: &DataStore, report: &mut PathReporter
+    ) {
+        use crate::document::line::Status as S;
+
+        let line = match data.try_as_line() {
+            Some(line) => line,
+            None => return
+        };
+        let len = line.points.len();
+
+        // The last status and whether the section was ever closed for
+        // each stretch between two points.
+        let mut current = vec![(None, false); len.saturating_sub(1)];
+        for event in line.events.iter() {
+            let status = match event.status() {
+                Some(status) => status,
+                None => continue
+            };
+            let mut problem = None;
+            for section in event.sections.covered(len) {
+                let end = section.end_idx.min(current.len());
+                for item in &mut current[section.start_idx..end] {
+                    let (last, closed) = *item;
+                    match (last, status) {
+                        (
+                            Some(
+                                last @ (S::Closed | S::Removed | S::Released)
+                            ),
+                            S::Open
+                        ) => {
+                            problem = Some((last, status))
+                        }
+                        (Some(last), S::Reopened) if !closed => {
+                            problem = Some((last, status))
+                        }
+                        _ => { }
+                    }
+                    *item = (
+                        Some(status),
+                        closed || matches!(
+                            status,
+                            S::Suspended | S::Closed | S::Removed
+                            | S::Released
+                        )
+                    );
+                }
+            }
+            if let Some((from, to)) = problem {
+                report.warning(
+                    StatusProgression { from, to }.marked(
+                        event_location(&event.date)
+                    )
+                )
+            }
+        }
+    }
+}
+
+
+//------------ PointReopened -------------------------------------------------
+
+/// A point can only be reopened if it was closed or suspended before.
+pub struct PointReopened;
+
+impl Rule for PointReopened {
+    fn name(&self) -> &'static str {
+        "point-reopened"
+    }
+
+    fn description(&self) -> &'static str {
+        "points are only reopened after being closed"
+    }
+
+    fn check(
+        &self, data: &Data, _store: &DataStore, report: &mut PathReporter
+    ) {
+        let point = match data.try_as_point() {
+            Some(point) => point,
+            None => return
+        };
+        let mut closed = false;
+        for event in point.events() {
+            match event.status() {
+                Some(point::Status::Reopened) if !closed => {
+                    report.warning(
+                        ReopenedNeverClosed.marked(
+                            event_location(&event.date)
+                        )
+                    )
+                }
+                Some(point::Status::Closed | point::Status::Suspended) => {
+                    closed = true
+                }
+                _ => { }
+            }
+        }
+    }
+}
+
+
+//------------ PointName16 ---------------------------------------------------
+
+/// The `de.name16` of a point must have at most 16 characters.
+pub struct PointName16;
+
+impl Rule for PointName16 {
+    fn name(&self) -> &'static str {
+        "point-name16"
+    }
+
+    fn description(&self) -> &'static str {
+        "the de.name16 of a point has at most 16 characters"
+    }
+
+    fn check(
+        &self, data: &Data, _store: &DataStore, report: &mut PathReporter
+    ) {
+        if let Some(point) = data.try_as_point() {
+            point.check_name16(report)
+        }
+    }
+}
+
+
+//------------ LineRegion ----------------------------------------------------
+
+/// The regions of a line must be geographical entities.
+pub struct LineRegion;
+
+impl Rule for LineRegion {
+    fn name(&self) -> &'static str {
+        "line-region"
+    }
+
+    fn description(&self) -> &'static str {
+        "the regions of a line are countries, regions, or places"
+    }
+
+    fn check(
+        &self, data: &Data, store: &DataStore, report: &mut PathReporter
+    ) {
+        let line = match data.try_as_line() {
+            Some(line) => line,
+            None => return
+        };
+        let current = line.current.region.iter().flat_map(|(_, list)| {
+            list.iter()
+        });
+        let events = line.events.iter().filter_map(|event| {
+            event.region()
+        }).flat_map(|list| list.iter());
+        for region in current.chain(events) {
+            let entity = region.data(store);
+            if !entity.subtype.is_geographical() {
+                report.warning(
+                    NonGeographicalRegion(entity.key().clone()).marked(
+                        region.location()
+                    )
+                )
+            }
+        }
+    }
+}
+
+
+//------------ LineEntityExists ----------------------------------------------
+
+/// The operators and owners of a line must exist at the time of an event.
+///
+/// Entities without any status events are assumed to always exist.
+pub struct LineEntityExists;
+
+impl Rule for LineEntityExists {
+    fn name(&self) -> &'static str {
+        "line-entity-exists"
+    }
+
+    fn description(&self) -> &'static str {
+        "operators and owners of a line exist at the time of an event"
+    }
+
+    fn check(
+        &self, data: &Data, store: &DataStore, report: &mut PathReporter
+    ) {
+        let line = match data.try_as_line() {
+            Some(line) => line,
+            None => return
+        };
+        for event in line.events.iter() {
+            let date = match first_date(&event.date) {
+                Some(date) => date.into_value(),
+                None => continue
+            };
+            let entities = event.records.iter().flat_map(|record| {
+                let operator = record.properties.operator.iter().flat_map(
+                    |list| list.iter().map(|link| ("operator", link))
+                );
+                let owner = record.properties.owner.iter().flat_map(
+                    |list| list.iter().map(|link| ("owner", link))
+                );
+                operator.chain(owner)
+            });
+            for (role, link) in entities {
+                let entity = link.data(store);
+                let periods = entity.existence_periods();
+                if !periods.is_empty() && !periods.may_include(&date) {
+                    report.warning(
+                        EntityNotExisting {
+                            role, entity: entity.key().clone(), date
+                        }.marked(link.location())
+                    )
+                }
+            }
+        }
+    }
+}
+
+
+//------------ BasisDate -----------------------------------------------------
+
+/// The legal basis of an event can’t date after the event.
+///
+/// The date of a record is used if it has one, otherwise the date of its
+/// event. Dates are only compared if they can be ordered definitely.
+pub struct BasisDate;
+
+impl Rule for BasisDate {
+    fn name(&self) -> &'static str {
+        "basis-date"
+    }
+
+    fn description(&self) -> &'static str {
+        "the legal basis of an event doesn’t date after the event"
+    }
+
+    fn check(
+        &self, data: &Data, _store: &DataStore, report: &mut PathReporter
+    ) {
+        macro_rules! records {
+            ( $events:expr ) => {
+                $events.iter().flat_map(|event| {
+                    event.records.iter().map(move |record| {
+                        (
+                            record.date.as_ref().unwrap_or(&event.date),
+                            record.provenance()
+                        )
+                    })
+                }).collect()
+            }
+        }
+
+        let records: Vec<_> = match *data {
+            Data::Entity(ref inner) => records!(inner.events),
+            Data::Line(ref inner) => records!(inner.events),
+            Data::Point(ref inner) => records!(inner.events),
+            Data::Service(ref inner) => records!(inner.events),
+            Data::Structure(ref inner) => records!(inner.events),
+            _ => return
+        };
+        for (date, provenance) in records {
+            let date = match first_date(date) {
+                Some(date) => date.into_value(),
+                None => continue
+            };
+            for basis in provenance.basis {
+                let basis_date = match first_date(&basis.date) {
+                    Some(basis_date) => basis_date,
+                    None => continue
+                };
+                if basis_date.cmp_range(&date) == Some(Ordering::Greater) {
+                    report.warning(
+                        BasisAfterEvent {
+                            basis: basis_date.into_value(), event: date
+                        }.marked(basis_date.location())
+                    )
+                }
+            }
+        }
+    }
+}
+
+
+//------------ KeyNamespace --------------------------------------------------
+
+/// Keys must follow the namespace rules of their country.
+///
+/// See [`local::is_valid_key`] for the rules.
+pub struct KeyNamespace;
+
+impl Rule for KeyNamespace {
+    fn name(&self) -> &'static str {
+        "key-namespace"
+    }
+
+    fn description(&self) -> &'static str {
+        "keys follow the namespace rules of their country"
+    }
+
+    fn check(
+        &self, data: &Data, _store: &DataStore, report: &mut PathReporter
+    ) {
+        let key = &data.common().key;
+        if let Err(expected) = local::is_valid_key(key) {
+            report.warning(
+                InvalidKeyFormat {
+                    key: key.as_value().clone(), expected
+                }.marked(key.location())
+            )
+        }
+    }
+}
+
+
+//------------ LineCode ------------------------------------------------------
+
+/// National line codes must follow the rules of their country.
+///
+/// See [`local::check_line_code`] for the rules.
+pub struct LineCode;
+
+impl Rule for LineCode {
+    fn name(&self) -> &'static str {
+        "line-code"
+    }
+
+    fn description(&self) -> &'static str {
+        "national line codes follow the rules of their country"
+    }
+
+    fn check(
+        &self, data: &Data, _store: &DataStore, report: &mut PathReporter
+    ) {
+        if let Some(line) = data.try_as_line() {
+            line.check_codes(report)
+        }
+    }
+}
+
+
+//------------ PointCode -----------------------------------------------------
+
+/// Point codes must follow the rules of their country.
+///
+/// See [`local::check_point_code`] for the rules.
+pub struct PointCode;
+
+impl Rule for PointCode {
+    fn name(&self) -> &'static str {
+        "point-code"
+    }
+
+    fn description(&self) -> &'static str {
+        "point codes follow the rules of their country or system"
+    }
+
+    fn check(
+        &self, data: &Data, _store: &DataStore, report: &mut PathReporter
+    ) {
+        if let Some(point) = data.try_as_point() {
+            point.check_codes(report)
+        }
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the first date of an event date.
+fn first_date(date: &EventDate) -> Option<Marked<Date>> {
+    date.iter().next()
+}
+
+/// Returns the location of an event date for reporting.
+fn event_location(date: &EventDate) -> Location {
+    first_date(date).map(|date| date.location()).unwrap_or(Location::NONE)
+}
+
+
+//============ Errors ========================================================
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="unknown validation rule '{}'", _0)]
+pub struct UnknownRule(String);
+
+#[derive(Clone, Copy, Debug, Display)]
+#[display(
+    fmt="status '{}' before construction concession of {}",
+    status, concession
+)]
+pub struct BeforeConcession {
+    status: line::Status,
+    concession: Date,
+}
+
+#[derive(Clone, Copy, Debug, Display)]
+#[display(fmt="event after concession expired in {} without renewal", until)]
+pub struct AfterConcessionExpiry {
+    until: Date,
+}
+
+#[derive(Clone, Copy, Debug, Display)]
+#[display(fmt="impossible status change from '{}' to '{}'", from, to)]
+pub struct StatusProgression {
+    from: line::Status,
+    to: line::Status,
+}
+
+#[derive(Clone, Copy, Debug, Display)]
+#[display(fmt="point reopened without being closed before")]
+pub struct ReopenedNeverClosed;
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="region '{}' is not a geographical entity", _0)]
+pub struct NonGeographicalRegion(Key);
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="{} '{}' doesn’t exist in {}", role, entity, date)]
+pub struct EntityNotExisting {
+    role: &'static str,
+    entity: Key,
+    date: Date,
+}
+
+#[derive(Clone, Copy, Debug, Display)]
+#[display(fmt="legal basis of {} dates after the event of {}", basis, event)]
+pub struct BasisAfterEvent {
+    basis: Date,
+    event: Date,
+}
+
+#[derive(Clone, Debug, Display)]
+#[display(fmt="invalid key '{}', expected {}", key, expected)]
+pub struct InvalidKeyFormat {
+    key: Key,
+    expected: &'static str,
+}
+